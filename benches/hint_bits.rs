@@ -0,0 +1,109 @@
+//! Scans the same rows over and over. The first scan has to look up every row's transactions and
+//! leaves hint bits behind, so the scans after it don't need the transaction manager.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use feophantlib::{
+    constants::{BuiltinSqlTypes, DeserializeTypes, Nullable},
+    engine::{
+        io::{IOManager, RowManager, VisibleRowManager},
+        objects::{Attribute, SqlTuple, Table},
+        transactions::{TransactionId, TransactionManager},
+    },
+};
+use futures::stream::StreamExt;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+const ROWS: usize = 1000;
+
+struct Setup {
+    vm: VisibleRowManager,
+    tm: TransactionManager,
+    table: Arc<Table>,
+    reader: TransactionId,
+}
+
+async fn setup() -> Setup {
+    let table = Arc::new(Table::new(
+        "bench_table".to_string(),
+        vec![Attribute::new(
+            uuid::Uuid::new_v4(),
+            "header".to_string(),
+            DeserializeTypes::Text,
+            Nullable::NotNull,
+        )],
+    ));
+    let mut tm = TransactionManager::new();
+    let rm = RowManager::new(IOManager::new());
+    let vm = VisibleRowManager::new(rm.clone(), tm.clone());
+
+    let tran_id = tm.start_trans().await.unwrap();
+    let mut pointers = vec![];
+    for i in 0..ROWS {
+        let row = Arc::new(SqlTuple(vec![Some(BuiltinSqlTypes::Text(format!(
+            "row {}",
+            i
+        )))]));
+        pointers.push(
+            rm.clone()
+                .insert_row(tran_id, table.clone(), row)
+                .await
+                .unwrap(),
+        );
+    }
+    tm.commit_trans(tran_id).await.unwrap();
+
+    //Deleted rows need their max looked up as well
+    let deleter = tm.start_trans().await.unwrap();
+    for p in pointers.iter().step_by(4) {
+        rm.clone()
+            .delete_row(deleter, table.clone(), *p)
+            .await
+            .unwrap();
+    }
+    tm.commit_trans(deleter).await.unwrap();
+
+    let reader = tm.start_trans().await.unwrap();
+    Setup {
+        vm,
+        tm,
+        table,
+        reader,
+    }
+}
+
+async fn scan(s: &Setup) -> usize {
+    s.vm.clone()
+        .get_stream(s.reader, s.table.clone())
+        .map(Result::unwrap)
+        .count()
+        .await
+}
+
+fn hint_bits_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let s = rt.block_on(setup());
+    for pass in 1..=3 {
+        let before = s.tm.lookups();
+        let found = rt.block_on(scan(&s));
+        println!(
+            "Scan {} found {} rows with {} transaction lookups",
+            pass,
+            found,
+            s.tm.lookups() - before
+        );
+    }
+
+    c.bench_function("scan without hint bits", |b| {
+        b.iter_batched(
+            || rt.block_on(setup()),
+            |s| rt.block_on(scan(&s)),
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("scan with hint bits", |b| b.iter(|| rt.block_on(scan(&s))));
+}
+
+criterion_group!(benches, hint_bits_benchmark);
+criterion_main!(benches);
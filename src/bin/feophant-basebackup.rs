@@ -0,0 +1,68 @@
+#![forbid(unsafe_code)]
+
+use feophantlib::replication::{format_lsn, BaseBackupReceiver};
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "Usage: feophant-basebackup [-h <host>] [-p <port>] -D <dir>
+  Copies a running server's cluster, the copy redoes its log the first time it is started.
+  -h <host>  The server's host, 127.0.0.1 if not given
+  -p <port>  The server's port, 50000 if not given
+  -D <dir>   Where to write the copy, it must be missing or empty";
+
+struct Args {
+    address: String,
+    target: PathBuf,
+}
+
+fn parse_args(args: &[String]) -> Option<Args> {
+    let mut host = "127.0.0.1".to_string();
+    let mut port: u16 = 50000;
+    let mut target = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-h" => host = args.next()?.to_string(),
+            "-p" => port = args.next()?.parse().ok()?,
+            "-D" => target = Some(PathBuf::from(args.next()?)),
+            _ => return None,
+        }
+    }
+
+    Some(Args {
+        address: format!("{}:{}", host, port),
+        target: target?,
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Some(a) => a,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match BaseBackupReceiver::new(args.address.clone())
+        .run(&args.target)
+        .await
+    {
+        Ok(backup) => println!(
+            "Copied {} files of {} to {}, its log runs from {} to {}",
+            backup.files,
+            args.address,
+            args.target.display(),
+            format_lsn(backup.start_lsn),
+            format_lsn(backup.stop_lsn)
+        ),
+        Err(e) => {
+            eprintln!("Unable to back up {}: {}", args.address, e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+#![forbid(unsafe_code)]
+
+use feophantlib::dump::{Client, Dumper, Restorer};
+use std::env;
+use std::process;
+use tokio::fs::File;
+use tokio::io::BufReader;
+
+const USAGE: &str = "Usage: feophant-dump [-h <host>] [-p <port>] [-f <file>]
+       feophant-dump [-h <host>] [-p <port>] --restore <file>
+  Writes the database as a script of CREATE and COPY statements from one snapshot.
+  -h <host>         The server's host, 127.0.0.1 if not given
+  -p <port>         The server's port, 50000 if not given
+  -f <file>         Where to write the script, standard out if not given
+  --restore <file>  Load a script into the server instead, it should be empty";
+
+struct Args {
+    address: String,
+    file: Option<String>,
+    restore: Option<String>,
+}
+
+fn parse_args(args: &[String]) -> Option<Args> {
+    let mut host = "127.0.0.1".to_string();
+    let mut port: u16 = 50000;
+    let mut file = None;
+    let mut restore = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-h" => host = args.next()?.to_string(),
+            "-p" => port = args.next()?.parse().ok()?,
+            "-f" => file = Some(args.next()?.to_string()),
+            "--restore" => restore = Some(args.next()?.to_string()),
+            _ => return None,
+        }
+    }
+    if file.is_some() && restore.is_some() {
+        return None;
+    }
+
+    Some(Args {
+        address: format!("{}:{}", host, port),
+        file,
+        restore,
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Some(a) => a,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let client = match Client::connect(&args.address).await {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Unable to connect to {}: {}", args.address, e);
+            process::exit(1);
+        }
+    };
+
+    let result = match (args.file, args.restore) {
+        (_, Some(script)) => match File::open(&script).await {
+            Ok(f) => Restorer::new(client)
+                .restore(BufReader::new(f))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(format!("Unable to read {}: {}", script, e)),
+        },
+        (Some(file), None) => match File::create(&file).await {
+            Ok(mut f) => Dumper::new(client)
+                .dump(&mut f)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(format!("Unable to create {}: {}", file, e)),
+        },
+        (None, None) => Dumper::new(client)
+            .dump(&mut tokio::io::stdout())
+            .await
+            .map_err(|e| e.to_string()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -1,3 +1,19 @@
+mod copy_binary;
+pub use copy_binary::CopyBinary;
+pub use copy_binary::CopyBinaryError;
+
+mod copy_csv;
+pub use copy_csv::CopyCsv;
+pub use copy_csv::CopyCsvError;
+
+mod copy_format;
+pub use copy_format::CopyFormat;
+pub use copy_format::CopyFormatError;
+
+mod copy_text;
+pub use copy_text::CopyText;
+pub use copy_text::CopyTextError;
+
 mod network_frame;
 pub use network_frame::NetworkFrame;
 pub use network_frame::NetworkFrameError;
@@ -0,0 +1,147 @@
+//! The binary format of COPY, a fixed header then each row as a count of its fields and each
+//! field's length and bytes, finished by a count of -1.
+//! Format here: https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.4
+//!
+//! Only the framing is done here, the values are in each type's binary send format, see
+//! PgBinary.
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
+use std::num::TryFromIntError;
+use thiserror::Error;
+
+const SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+
+//Bit 16 of the flags says each row starts with an oid, which nothing here has
+const HAS_OIDS: u32 = 1 << 16;
+
+pub struct CopyBinary {}
+
+impl CopyBinary {
+    /// The signature with no flags or header extension
+    pub fn header() -> Bytes {
+        let mut buffer = BytesMut::with_capacity(SIGNATURE.len() + 8);
+        buffer.put(SIGNATURE);
+        buffer.put_u32(0);
+        buffer.put_u32(0);
+        buffer.freeze()
+    }
+
+    pub fn trailer() -> Bytes {
+        Bytes::from_static(&[0xff, 0xff])
+    }
+
+    pub fn format_row(values: &[Option<Bytes>]) -> Result<Bytes, CopyBinaryError> {
+        let mut buffer = BytesMut::new();
+        buffer.put_i16(i16::try_from(values.len())?);
+        for value in values {
+            match value {
+                Some(v) => {
+                    buffer.put_i32(i32::try_from(v.len())?);
+                    buffer.put_slice(v);
+                }
+                None => buffer.put_i32(-1),
+            }
+        }
+        Ok(buffer.freeze())
+    }
+
+    /// Every row up to the trailer, a missing trailer is allowed if the data ends between rows
+    pub fn parse_rows(mut data: Bytes) -> Result<Vec<Vec<Option<Bytes>>>, CopyBinaryError> {
+        if data.remaining() < SIGNATURE.len() + 8 || &data[..SIGNATURE.len()] != SIGNATURE {
+            return Err(CopyBinaryError::BadSignature());
+        }
+        data.advance(SIGNATURE.len());
+        if data.get_u32() & HAS_OIDS != 0 {
+            return Err(CopyBinaryError::HasOids());
+        }
+        let extension = data.get_u32() as usize;
+        if data.remaining() < extension {
+            return Err(CopyBinaryError::Truncated(0));
+        }
+        data.advance(extension);
+
+        let mut rows = vec![];
+        while data.has_remaining() {
+            let row_num = rows.len() + 1;
+            if data.remaining() < 2 {
+                return Err(CopyBinaryError::Truncated(row_num));
+            }
+            let count = data.get_i16();
+            if count == -1 {
+                break;
+            }
+            let count = usize::try_from(count).map_err(|_| CopyBinaryError::Truncated(row_num))?;
+
+            let mut row = Vec::with_capacity(count);
+            for _ in 0..count {
+                if data.remaining() < 4 {
+                    return Err(CopyBinaryError::Truncated(row_num));
+                }
+                let len = data.get_i32();
+                if len < 0 {
+                    row.push(None);
+                    continue;
+                }
+                let len = len as usize;
+                if data.remaining() < len {
+                    return Err(CopyBinaryError::Truncated(row_num));
+                }
+                row.push(Some(data.split_to(len)));
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CopyBinaryError {
+    #[error("COPY file signature not recognized")]
+    BadSignature(),
+    #[error("COPY data with oids is not supported")]
+    HasOids(),
+    #[error("unexpected end of the COPY data in row {0}")]
+    Truncated(usize),
+    #[error(transparent)]
+    TooLarge(#[from] TryFromIntError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_binary_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let rows = vec![
+            vec![Some(Bytes::from_static(b"\0\0\0\x01")), None],
+            vec![Some(Bytes::new()), Some(Bytes::from_static(b"abc"))],
+        ];
+        let mut data = BytesMut::new();
+        data.put(CopyBinary::header());
+        for row in rows.iter() {
+            data.put(CopyBinary::format_row(row)?);
+        }
+        data.put(CopyBinary::trailer());
+        assert_eq!(&data[..11], b"PGCOPY\n\xff\r\n\0");
+        assert_eq!(CopyBinary::parse_rows(data.freeze())?, rows);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_binary_errors() {
+        assert!(matches!(
+            CopyBinary::parse_rows(Bytes::from_static(b"PGCOPY\n")),
+            Err(CopyBinaryError::BadSignature())
+        ));
+
+        let mut data = BytesMut::new();
+        data.put(CopyBinary::header());
+        data.put_i16(1);
+        data.put_i32(10);
+        data.put(&b"short"[..]);
+        assert!(matches!(
+            CopyBinary::parse_rows(data.freeze()),
+            Err(CopyBinaryError::Truncated(1))
+        ));
+    }
+}
@@ -0,0 +1,212 @@
+//! The CSV format of COPY, like the text format a line per row but values are quoted instead
+//! of escaped so they can hold delimiters and newlines.
+//! Format here: https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.3
+//!
+//! An unquoted value that is exactly the null string, empty by default, is null. Quoting it
+//! makes it text again, which is how an empty string is told apart from null. Inside quotes a
+//! quote or the escape character is written after the escape, which is the quote by default so
+//! a quote is doubled.
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopyCsv {
+    pub delimiter: char,
+    pub null: String,
+    pub quote: char,
+    pub escape: char,
+    ///The first line is the column names, skipped when loading
+    pub header: bool,
+}
+
+impl Default for CopyCsv {
+    fn default() -> Self {
+        CopyCsv {
+            delimiter: ',',
+            null: "".to_string(),
+            quote: '"',
+            escape: '"',
+            header: false,
+        }
+    }
+}
+
+impl CopyCsv {
+    /// The header line, if there is one
+    pub fn format_header(&self, columns: &[String]) -> Option<String> {
+        if !self.header {
+            return None;
+        }
+        let names: Vec<Option<String>> = columns.iter().cloned().map(Some).collect();
+        Some(self.format_row(&names))
+    }
+
+    /// The row as a line, ending in a newline
+    pub fn format_row(&self, values: &[Option<String>]) -> String {
+        let mut line = String::new();
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                line.push(self.delimiter);
+            }
+            match value {
+                Some(v) if self.needs_quotes(v) => {
+                    line.push(self.quote);
+                    for c in v.chars() {
+                        if c == self.quote || c == self.escape {
+                            line.push(self.escape);
+                        }
+                        line.push(c);
+                    }
+                    line.push(self.quote);
+                }
+                Some(v) => line.push_str(v),
+                None => line.push_str(&self.null),
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Every row in the data after the header, the last line doesn't need a newline. A line
+    /// of \. outside of quotes ends the data early.
+    pub fn parse_rows(&self, data: &str) -> Result<Vec<Vec<Option<String>>>, CopyCsvError> {
+        let mut rows = vec![];
+        let mut row = vec![];
+        let mut value = String::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+        let mut line = 1;
+        let mut line_start = true;
+
+        let mut chars = data.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if line_start && !in_quotes {
+                let rest = &data[i..];
+                if rest == "\\." || rest.starts_with("\\.\n") || rest.starts_with("\\.\r\n") {
+                    break;
+                }
+                line_start = false;
+            }
+
+            if in_quotes {
+                let next = chars.peek().map(|(_, n)| *n);
+                if c == self.escape && (next == Some(self.quote) || next == Some(self.escape)) {
+                    value.push(next.unwrap_or(c));
+                    chars.next();
+                } else if c == self.quote {
+                    in_quotes = false;
+                } else {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    value.push(c);
+                }
+                continue;
+            }
+
+            if c == self.quote {
+                in_quotes = true;
+                quoted = true;
+            } else if c == self.delimiter {
+                row.push(self.finish_value(&mut value, &mut quoted));
+            } else if c == '\n' || (c == '\r' && chars.peek().map(|(_, n)| *n) == Some('\n')) {
+                if c == '\r' {
+                    chars.next();
+                }
+                row.push(self.finish_value(&mut value, &mut quoted));
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+                line_start = true;
+            } else {
+                value.push(c);
+            }
+        }
+
+        if in_quotes {
+            return Err(CopyCsvError::UnterminatedQuote(line));
+        }
+        if !line_start {
+            row.push(self.finish_value(&mut value, &mut quoted));
+            rows.push(row);
+        }
+
+        if self.header && !rows.is_empty() {
+            rows.remove(0);
+        }
+        Ok(rows)
+    }
+
+    fn finish_value(&self, value: &mut String, quoted: &mut bool) -> Option<String> {
+        let is_null = !*quoted && *value == self.null;
+        *quoted = false;
+        let value = std::mem::take(value);
+        match is_null {
+            true => None,
+            false => Some(value),
+        }
+    }
+
+    //Anything that would read back as something else, including a line that ends the data
+    fn needs_quotes(&self, value: &str) -> bool {
+        value == self.null
+            || value == "\\."
+            || value.chars().any(|c| {
+                c == self.delimiter || c == self.quote || c == self.escape || c == '\n' || c == '\r'
+            })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CopyCsvError {
+    #[error("unterminated CSV quoted field on line {0} of the COPY data")]
+    UnterminatedQuote(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_csv_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let csv = CopyCsv::default();
+        let row = vec![
+            Some("plain".to_string()),
+            None,
+            Some("".to_string()),
+            Some("a, \"quoted\"\nline".to_string()),
+        ];
+        let line = csv.format_row(&row);
+        assert_eq!(line, "plain,,\"\",\"a, \"\"quoted\"\"\nline\"\n");
+        assert_eq!(csv.parse_rows(&line)?, vec![row]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_csv_options() -> Result<(), Box<dyn std::error::Error>> {
+        let csv = CopyCsv {
+            delimiter: ';',
+            null: "NULL".to_string(),
+            quote: '\'',
+            escape: '\\',
+            header: true,
+        };
+        assert_eq!(
+            csv.format_header(&["a".to_string(), "b".to_string()]),
+            Some("a;b\n".to_string())
+        );
+        let row = vec![Some("NULL".to_string()), None, Some("it's\\".to_string())];
+        let line = csv.format_row(&row);
+        assert_eq!(line, "'NULL';NULL;'it\\'s\\\\'\n");
+
+        let data = format!("a;b;c\r\n{}\\.\nignored", line);
+        assert_eq!(csv.parse_rows(&data)?, vec![row]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_csv_errors() {
+        assert!(matches!(
+            CopyCsv::default().parse_rows("a,b\n\"c,d\n"),
+            Err(CopyCsvError::UnterminatedQuote(3))
+        ));
+    }
+}
@@ -0,0 +1,301 @@
+//! Which of the COPY formats a statement asked for, built from its options the same way
+//! postgres checks them. See: https://www.postgresql.org/docs/current/sql-copy.html
+//!
+//! Values are passed in and out as bytes, the text form of each value for text and CSV and
+//! its binary send format for binary.
+use super::{CopyBinary, CopyBinaryError, CopyCsv, CopyCsvError, CopyText, CopyTextError};
+use crate::constants::PgErrorCodes;
+use bytes::Bytes;
+use std::str::Utf8Error;
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CopyFormat {
+    Text(CopyText),
+    Csv(CopyCsv),
+    Binary,
+}
+
+impl CopyFormat {
+    /// Options are the lowercase name and its value if it had one
+    pub fn from_options(
+        options: &[(String, Option<String>)],
+    ) -> Result<CopyFormat, CopyFormatError> {
+        for (i, (name, _)) in options.iter().enumerate() {
+            if options[..i].iter().any(|(n, _)| n == name) {
+                return Err(CopyFormatError::DuplicateOption(name.clone()));
+            }
+        }
+        let option = |name: &str| {
+            options
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+        };
+        let value = |name: &str| -> Result<Option<String>, CopyFormatError> {
+            match option(name) {
+                Some(Some(v)) => Ok(Some(v)),
+                Some(None) => Err(CopyFormatError::MissingValue(name.to_string())),
+                None => Ok(None),
+            }
+        };
+        let single_char = |name: &str| -> Result<Option<char>, CopyFormatError> {
+            match value(name)? {
+                Some(v) => {
+                    let mut chars = v.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c.is_ascii() => Ok(Some(c)),
+                        _ => Err(CopyFormatError::NotSingleCharacter(name.to_string())),
+                    }
+                }
+                None => Ok(None),
+            }
+        };
+
+        if let Some((name, _)) = options.iter().find(|(n, _)| {
+            !matches!(
+                n.as_str(),
+                "format" | "delimiter" | "null" | "header" | "quote" | "escape"
+            )
+        }) {
+            return Err(CopyFormatError::UnknownOption(name.clone()));
+        }
+
+        let format = value("format")?.unwrap_or_else(|| "text".to_string());
+        let delimiter = single_char("delimiter")?;
+        let null = value("null")?;
+        let header = match option("header") {
+            Some(v) => Some(CopyFormat::parse_bool(v)?),
+            None => None,
+        };
+        let quote = single_char("quote")?;
+        let escape = single_char("escape")?;
+
+        let csv_only = [
+            ("header", header.is_some()),
+            ("quote", quote.is_some()),
+            ("escape", escape.is_some()),
+        ];
+        let copy_format = match format.to_lowercase().as_str() {
+            "text" => {
+                if let Some((name, _)) = csv_only.iter().find(|(_, set)| *set) {
+                    return Err(CopyFormatError::CsvOnly(name.to_string()));
+                }
+                let defaults = CopyText::default();
+                CopyFormat::Text(CopyText {
+                    delimiter: delimiter.unwrap_or(defaults.delimiter),
+                    null: null.unwrap_or(defaults.null),
+                })
+            }
+            "csv" => {
+                let defaults = CopyCsv::default();
+                let quote = quote.unwrap_or(defaults.quote);
+                CopyFormat::Csv(CopyCsv {
+                    delimiter: delimiter.unwrap_or(defaults.delimiter),
+                    null: null.unwrap_or(defaults.null),
+                    quote,
+                    escape: escape.unwrap_or(quote),
+                    header: header.unwrap_or(false),
+                })
+            }
+            "binary" => {
+                if delimiter.is_some() || null.is_some() || csv_only.iter().any(|(_, set)| *set) {
+                    return Err(CopyFormatError::BinaryOptions());
+                }
+                CopyFormat::Binary
+            }
+            f => return Err(CopyFormatError::UnknownFormat(f.to_string())),
+        };
+
+        let (delimiter, null) = match &copy_format {
+            CopyFormat::Text(t) => (t.delimiter, &t.null),
+            CopyFormat::Csv(c) => (c.delimiter, &c.null),
+            CopyFormat::Binary => return Ok(copy_format),
+        };
+        if matches!(delimiter, '\n' | '\r' | '\\') || null.contains(['\n', '\r']) {
+            return Err(CopyFormatError::LineBreakOrBackslash());
+        }
+        if null.contains(delimiter) {
+            return Err(CopyFormatError::DelimiterInNull());
+        }
+        if let CopyFormat::Csv(c) = &copy_format {
+            if c.quote == c.delimiter {
+                return Err(CopyFormatError::QuoteIsDelimiter());
+            }
+        }
+        Ok(copy_format)
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, CopyFormat::Binary)
+    }
+
+    /// What comes before the rows when sending them, if anything
+    pub fn header(&self, columns: &[String]) -> Option<Bytes> {
+        match self {
+            CopyFormat::Text(_) => None,
+            CopyFormat::Csv(c) => c.format_header(columns).map(Bytes::from),
+            CopyFormat::Binary => Some(CopyBinary::header()),
+        }
+    }
+
+    /// What comes after the rows when sending them, if anything
+    pub fn trailer(&self) -> Option<Bytes> {
+        match self {
+            CopyFormat::Binary => Some(CopyBinary::trailer()),
+            _ => None,
+        }
+    }
+
+    pub fn format_row(&self, values: &[Option<Bytes>]) -> Result<Bytes, CopyFormatError> {
+        let as_text = || {
+            values
+                .iter()
+                .map(|v| match v {
+                    Some(b) => Ok(Some(std::str::from_utf8(b)?.to_string())),
+                    None => Ok(None),
+                })
+                .collect::<Result<Vec<_>, Utf8Error>>()
+        };
+        match self {
+            CopyFormat::Text(t) => Ok(Bytes::from(t.format_row(&as_text()?))),
+            CopyFormat::Csv(c) => Ok(Bytes::from(c.format_row(&as_text()?))),
+            CopyFormat::Binary => Ok(CopyBinary::format_row(values)?),
+        }
+    }
+
+    /// Every row sent for a COPY FROM, text and CSV must be utf8
+    pub fn parse_rows(&self, data: Bytes) -> Result<Vec<Vec<Option<Bytes>>>, CopyFormatError> {
+        let rows = match self {
+            CopyFormat::Text(t) => t.parse_rows(std::str::from_utf8(&data)?)?,
+            CopyFormat::Csv(c) => c.parse_rows(std::str::from_utf8(&data)?)?,
+            CopyFormat::Binary => return Ok(CopyBinary::parse_rows(data)?),
+        };
+        Ok(rows
+            .into_iter()
+            .map(|r| r.into_iter().map(|v| v.map(Bytes::from)).collect())
+            .collect())
+    }
+
+    //A boolean option can be given without a value to mean true
+    fn parse_bool(value: Option<String>) -> Result<bool, CopyFormatError> {
+        match value.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("true" | "on" | "1") => Ok(true),
+            Some("false" | "off" | "0") => Ok(false),
+            Some(v) => Err(CopyFormatError::NotBoolean(v.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CopyFormatError {
+    #[error("cannot specify DELIMITER, NULL, HEADER, QUOTE or ESCAPE in BINARY mode")]
+    BinaryOptions(),
+    #[error(transparent)]
+    CopyBinaryError(#[from] CopyBinaryError),
+    #[error(transparent)]
+    CopyCsvError(#[from] CopyCsvError),
+    #[error(transparent)]
+    CopyTextError(#[from] CopyTextError),
+    #[error("COPY {0} available only in CSV mode")]
+    CsvOnly(String),
+    #[error("COPY delimiter must not appear in the NULL specification")]
+    DelimiterInNull(),
+    #[error("conflicting or redundant options, {0} was given more than once")]
+    DuplicateOption(String),
+    #[error("COPY delimiter and null string cannot be newline, carriage return or backslash")]
+    LineBreakOrBackslash(),
+    #[error("COPY option {0} requires a value")]
+    MissingValue(String),
+    #[error("{0} requires a Boolean value")]
+    NotBoolean(String),
+    #[error("COPY {0} must be a single one-byte character")]
+    NotSingleCharacter(String),
+    #[error(transparent)]
+    NotUtf8(#[from] Utf8Error),
+    #[error("COPY delimiter and quote must be different")]
+    QuoteIsDelimiter(),
+    #[error("COPY format \"{0}\" not recognized")]
+    UnknownFormat(String),
+    #[error("option \"{0}\" not recognized")]
+    UnknownOption(String),
+}
+
+impl CopyFormatError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            CopyFormatError::CopyBinaryError(_)
+            | CopyFormatError::CopyCsvError(_)
+            | CopyFormatError::CopyTextError(_)
+            | CopyFormatError::NotUtf8(_) => PgErrorCodes::BadCopyFileFormat,
+            CopyFormatError::BinaryOptions()
+            | CopyFormatError::CsvOnly(_)
+            | CopyFormatError::LineBreakOrBackslash() => PgErrorCodes::FeatureNotSupported,
+            CopyFormatError::DuplicateOption(_)
+            | CopyFormatError::MissingValue(_)
+            | CopyFormatError::UnknownOption(_) => PgErrorCodes::SyntaxError,
+            _ => PgErrorCodes::InvalidParameterValue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        list.iter()
+            .map(|(n, v)| (n.to_string(), v.map(|v| v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_copy_format_options() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            CopyFormat::from_options(&[])?,
+            CopyFormat::Text(CopyText::default())
+        );
+        assert_eq!(
+            CopyFormat::from_options(&options(&[
+                ("format", Some("csv")),
+                ("header", None),
+                ("delimiter", Some(";")),
+                ("quote", Some("'")),
+                ("null", Some("NULL"))
+            ]))?,
+            CopyFormat::Csv(CopyCsv {
+                delimiter: ';',
+                null: "NULL".to_string(),
+                quote: '\'',
+                escape: '\'',
+                header: true,
+            })
+        );
+        assert_eq!(
+            CopyFormat::from_options(&options(&[("format", Some("BINARY"))]))?,
+            CopyFormat::Binary
+        );
+
+        for (bad, code) in [
+            (vec![("format", Some("xml"))], "22023"),
+            (vec![("header", None)], "0A000"),
+            (
+                vec![("format", Some("binary")), ("null", Some(""))],
+                "0A000",
+            ),
+            (vec![("delimiter", Some("ab"))], "22023"),
+            (vec![("delimiter", Some("\\"))], "0A000"),
+            (
+                vec![("delimiter", Some(",")), ("null", Some("a,b"))],
+                "22023",
+            ),
+            (vec![("null", Some("a")), ("null", Some("b"))], "42601"),
+            (vec![("oids", None)], "42601"),
+            (vec![("format", Some("csv")), ("quote", Some(","))], "22023"),
+        ] {
+            let err = CopyFormat::from_options(&options(&bad)).unwrap_err();
+            assert_eq!(err.pg_error_code().value(), code, "{:?}", bad);
+        }
+        Ok(())
+    }
+}
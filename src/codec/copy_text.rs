@@ -0,0 +1,211 @@
+//! The text format of COPY, a line per row with its columns separated by a delimiter, a tab
+//! unless another is asked for.
+//! Format here: https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.2
+//!
+//! Backslash escapes keep delimiters, newlines and backslashes in a value from ending its column
+//! or row. A column that is exactly the null string, \N by default, is null. It is compared
+//! before escapes are decoded so \\N is the text \N. A line of \. ends the data early.
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopyText {
+    pub delimiter: char,
+    pub null: String,
+}
+
+impl Default for CopyText {
+    fn default() -> Self {
+        CopyText {
+            delimiter: '\t',
+            null: "\\N".to_string(),
+        }
+    }
+}
+
+impl CopyText {
+    /// The row as a line, ending in a newline
+    pub fn format_row(&self, values: &[Option<String>]) -> String {
+        let mut line = String::new();
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                line.push(self.delimiter);
+            }
+            match value {
+                Some(v) => {
+                    for c in v.chars() {
+                        match c {
+                            '\\' => line.push_str("\\\\"),
+                            '\u{8}' => line.push_str("\\b"),
+                            '\u{c}' => line.push_str("\\f"),
+                            '\n' => line.push_str("\\n"),
+                            '\r' => line.push_str("\\r"),
+                            '\t' => line.push_str("\\t"),
+                            '\u{b}' => line.push_str("\\v"),
+                            c if c == self.delimiter => {
+                                line.push('\\');
+                                line.push(c);
+                            }
+                            c => line.push(c),
+                        }
+                    }
+                }
+                None => line.push_str(&self.null),
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Every row in the data, the last line doesn't need a newline
+    pub fn parse_rows(&self, data: &str) -> Result<Vec<Vec<Option<String>>>, CopyTextError> {
+        let mut rows = vec![];
+        for (i, line) in data.split_terminator('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line == "\\." {
+                break;
+            }
+            let row = self
+                .split_columns(line)
+                .into_iter()
+                .map(|c| match c == self.null {
+                    true => Some(None),
+                    false => CopyText::parse_value(c).map(Some),
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(CopyTextError::InvalidEscape(i + 1))?;
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    //An escaped delimiter stays in its column
+    fn split_columns<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        let mut columns = vec![];
+        let mut start = 0;
+        let mut chars = line.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == self.delimiter {
+                columns.push(&line[start..i]);
+                start = i + c.len_utf8();
+            }
+        }
+        columns.push(&line[start..]);
+        columns
+    }
+
+    //None if the escapes don't make utf8
+    fn parse_value(column: &str) -> Option<String> {
+        let mut value = vec![];
+        let mut chars = column.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buffer = [0; 4];
+                value.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some(e) => e,
+                None => {
+                    value.push(b'\\');
+                    break;
+                }
+            };
+            match escaped {
+                'b' => value.push(0x08),
+                'f' => value.push(0x0c),
+                'n' => value.push(b'\n'),
+                'r' => value.push(b'\r'),
+                't' => value.push(b'\t'),
+                'v' => value.push(0x0b),
+                //Up to three octal digits or two hex digits give a byte
+                '0'..='7' => {
+                    let mut byte = escaped.to_digit(8)?;
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|d| d.to_digit(8)) {
+                            Some(d) => {
+                                byte = byte * 8 + d;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    value.push(byte as u8);
+                }
+                'x' if chars.peek().is_some_and(|d| d.is_ascii_hexdigit()) => {
+                    let mut byte = 0;
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|d| d.to_digit(16)) {
+                            Some(d) => {
+                                byte = byte * 16 + d;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    value.push(byte as u8);
+                }
+                c => {
+                    let mut buffer = [0; 4];
+                    value.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+            }
+        }
+        String::from_utf8(value).ok()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CopyTextError {
+    #[error("invalid escape on line {0} of the COPY data")]
+    InvalidEscape(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_text_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let row = vec![
+            Some("tab\there".to_string()),
+            None,
+            Some("line\nbreak \\N".to_string()),
+            Some("".to_string()),
+        ];
+        let text = CopyText::default();
+        let line = text.format_row(&row);
+        assert_eq!(line, "tab\\there\t\\N\tline\\nbreak \\\\N\t\n");
+        assert_eq!(text.parse_rows(&line)?, vec![row]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_text_escapes() -> Result<(), Box<dyn std::error::Error>> {
+        let text = CopyText::default();
+        let rows = text.parse_rows("\\101\\x42\\q\tb\r\n\\.\nignored")?;
+        assert_eq!(
+            rows,
+            vec![vec![Some("ABq".to_string()), Some("b".to_string())]]
+        );
+        assert!(matches!(
+            text.parse_rows("ok\n\\377"),
+            Err(CopyTextError::InvalidEscape(2))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_text_options() -> Result<(), Box<dyn std::error::Error>> {
+        let text = CopyText {
+            delimiter: '|',
+            null: "".to_string(),
+        };
+        let row = vec![Some("a|b".to_string()), None, Some("\\N".to_string())];
+        let line = text.format_row(&row);
+        assert_eq!(line, "a\\|b||\\\\N\n");
+        assert_eq!(text.parse_rows(&line)?, vec![row]);
+        Ok(())
+    }
+}
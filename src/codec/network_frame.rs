@@ -4,8 +4,8 @@ use std::convert::TryFrom;
 use std::num::TryFromIntError;
 use thiserror::Error;
 
-use crate::constants::{PgErrorCodes, PgErrorLevels};
-use crate::engine::objects::SqlTuple;
+use crate::constants::{DeserializeTypes, PgErrorCodes, PgErrorLevels};
+use crate::engine::objects::{SessionSettings, SqlTuple};
 
 #[derive(Clone, Debug)]
 pub struct NetworkFrame {
@@ -16,8 +16,8 @@ pub struct NetworkFrame {
 impl NetworkFrame {
     pub fn new(message_type: u8, payload: Bytes) -> NetworkFrame {
         NetworkFrame {
-            message_type: message_type,
-            payload: payload,
+            message_type,
+            payload,
         }
     }
     pub fn authentication_ok() -> NetworkFrame {
@@ -33,7 +33,51 @@ impl NetworkFrame {
         NetworkFrame::new(b'C', buffer.freeze())
     }
 
-    pub fn data_rows(rows: Vec<SqlTuple>) -> Result<Vec<NetworkFrame>, NetworkFrameError> {
+    /// Starts a replication stream, binary copy with no columns like postgres
+    pub fn copy_both_response() -> NetworkFrame {
+        NetworkFrame::new(b'W', Bytes::from_static(b"\0\0\0"))
+    }
+
+    /// Asks the client for the rows of a COPY FROM STDIN, binary or text which covers CSV
+    pub fn copy_in_response(
+        binary: bool,
+        columns: usize,
+    ) -> Result<NetworkFrame, NetworkFrameError> {
+        Ok(NetworkFrame::new(
+            b'G',
+            NetworkFrame::copy_formats(binary, columns)?,
+        ))
+    }
+
+    /// Starts sending the rows of a COPY TO STDOUT, each follows in its own copy data
+    pub fn copy_out_response(
+        binary: bool,
+        columns: usize,
+    ) -> Result<NetworkFrame, NetworkFrameError> {
+        Ok(NetworkFrame::new(
+            b'H',
+            NetworkFrame::copy_formats(binary, columns)?,
+        ))
+    }
+
+    //The overall format and then every column's, they are all the same
+    fn copy_formats(binary: bool, columns: usize) -> Result<Bytes, NetworkFrameError> {
+        let format = u16::from(binary);
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(u8::from(binary));
+        buffer.put_u16(u16::try_from(columns)?);
+        for _ in 0..columns {
+            buffer.put_u16(format);
+        }
+        Ok(buffer.freeze())
+    }
+
+    /// Values are written the way the session's settings ask for, such as timestamps with
+    /// time zone in the session's zone
+    pub fn data_rows(
+        rows: Vec<SqlTuple>,
+        settings: &SessionSettings,
+    ) -> Result<Vec<NetworkFrame>, NetworkFrameError> {
         let mut frames = vec![];
 
         for row in rows {
@@ -45,7 +89,7 @@ impl NetworkFrame {
             for field in row.0.into_iter() {
                 match field {
                     Some(f) => {
-                        let f_str = format!("{}", f);
+                        let f_str = f.to_string_with(&settings.time_zone, settings.bytea_output);
                         let f_bytes = f_str.as_bytes();
                         let f_len = i32::try_from(f_bytes.len())?;
                         buffer.put_i32(f_len);
@@ -63,28 +107,53 @@ impl NetworkFrame {
         Ok(frames)
     }
 
+    pub fn empty_query_response() -> NetworkFrame {
+        NetworkFrame::new(b'I', Bytes::new())
+    }
+
+    /// Tells the client the current value of a setting it needs to know about
+    pub fn parameter_status(name: &str, value: &str) -> NetworkFrame {
+        let mut buffer = BytesMut::new();
+
+        buffer.put(name.as_bytes());
+        buffer.put_u8(b'\0');
+        buffer.put(value.as_bytes());
+        buffer.put_u8(b'\0');
+
+        NetworkFrame::new(b'S', buffer.freeze())
+    }
+
     //Note this claims that the server is ALWAYS ready, even if its not
     pub fn ready_for_query() -> NetworkFrame {
-        NetworkFrame::new(b'Z', Bytes::from_static(b"I"))
+        NetworkFrame::ready_for_query_in(b'I')
+    }
+
+    /// The status is I outside a transaction block, T inside one and E in one that has failed
+    pub fn ready_for_query_in(transaction_status: u8) -> NetworkFrame {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(transaction_status);
+        NetworkFrame::new(b'Z', buffer.freeze())
     }
 
-    pub fn row_description(column_names: Vec<String>) -> Result<NetworkFrame, NetworkFrameError> {
+    pub fn row_description(
+        columns: Vec<(String, DeserializeTypes)>,
+    ) -> Result<NetworkFrame, NetworkFrameError> {
         let mut buffer = BytesMut::new();
 
-        let field_count = u16::try_from(column_names.len())?;
+        let field_count = u16::try_from(columns.len())?;
         buffer.put_u16(field_count);
 
-        for column in column_names {
-            buffer.put(column.as_bytes());
+        for (name, sql_type) in columns {
+            buffer.put(name.as_bytes());
             buffer.put_u8(b'\0');
 
-            //The next following fields are going to be dummied out unless testing shows I need them.
+            //The table fields are going to be dummied out unless testing shows I need them.
             //https://www.postgresql.org/docs/current/protocol-message-formats.html
             buffer.put_u32(0); //Table OID
             buffer.put_u16(0); //Table Column
-            buffer.put_u32(0); //Type OID
-            buffer.put_i16(0); //Type length
-            buffer.put_i32(0); //Type specifier
+            buffer.put_u32(sql_type.oid()); //Type OID
+            buffer.put_i16(sql_type.type_length()); //Type length
+            buffer.put_i32(sql_type.type_modifier()); //Type modifier
             buffer.put_i16(0); //Format code, we're doing text for everything
         }
 
@@ -136,6 +205,36 @@ mod tests {
             PgErrorCodes::SystemError,
             "test".to_string(),
         );
-        assert!(true);
+    }
+
+    #[test]
+    fn test_row_description_types() -> Result<(), Box<dyn std::error::Error>> {
+        let frame =
+            NetworkFrame::row_description(vec![("a".to_string(), DeserializeTypes::BigInt)])?;
+
+        let mut expected = BytesMut::new();
+        expected.put_u16(1);
+        expected.put(&b"a\0"[..]);
+        expected.put_u32(0);
+        expected.put_u16(0);
+        expected.put_u32(20);
+        expected.put_i16(8);
+        expected.put_i32(-1);
+        expected.put_i16(0);
+
+        assert_eq!(frame.payload, expected.freeze());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_responses() -> Result<(), Box<dyn std::error::Error>> {
+        let frame = NetworkFrame::copy_in_response(false, 2)?;
+        assert_eq!(frame.message_type, b'G');
+        assert_eq!(&frame.payload[..], b"\0\0\x02\0\0\0\0");
+
+        let frame = NetworkFrame::copy_out_response(true, 1)?;
+        assert_eq!(frame.message_type, b'H');
+        assert_eq!(&frame.payload[..], b"\x01\0\x01\0\x01");
+        Ok(())
     }
 }
@@ -26,16 +26,11 @@ impl Decoder for PgCodec {
         let message_type = u8::from_be(message_bytes[0]);
 
         //If the message_type is 0, then it doesn't have a type and should just be seen as the length
-        let prefix_len;
-        if message_type == 0 {
-            prefix_len = 4;
-        } else {
-            prefix_len = 5;
-        }
+        let prefix_len = if message_type == 0 { 4 } else { 5 };
         let mut length_bytes = [0u8; 4];
         length_bytes.copy_from_slice(&src[(prefix_len - 4)..prefix_len]);
 
-        let length = u32::from_be_bytes(length_bytes) as u32;
+        let length = u32::from_be_bytes(length_bytes);
         if length < 4 {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -142,6 +137,6 @@ mod tests {
         let msg = codec.decode(&mut buf).unwrap().unwrap();
 
         assert_eq!(msg.message_type, 0);
-        assert_eq!(ssl_and_gssapi_parser::is_ssl_request(&msg.payload), true);
+        assert!(ssl_and_gssapi_parser::is_ssl_request(&msg.payload));
     }
 }
@@ -1,16 +1,52 @@
+mod array;
+pub use array::ArrayDimension;
+pub use array::SqlArray;
+
 mod builtin_sql_types;
 pub use builtin_sql_types::BuiltinSqlTypes;
 pub use builtin_sql_types::DeserializeTypes;
+pub use builtin_sql_types::NumericTypmod;
 pub use builtin_sql_types::SqlTypeError;
 
+mod bytea;
+pub use bytea::Bytea;
+pub use bytea::ByteaOutput;
+pub use bytea::ByteaOutputError;
+
+mod date_time;
+pub use date_time::DateTime;
+
+mod interval;
+pub use interval::Interval;
+
+mod json;
+pub use json::JsonValue;
+
 mod nullable;
 pub use nullable::Nullable;
 
+mod pg_binary;
+pub use pg_binary::PgBinary;
+pub use pg_binary::PgBinaryError;
+
 mod pg_error_codes;
 pub use pg_error_codes::PgErrorCodes;
 
 mod pg_error_levels;
 pub use pg_error_levels::PgErrorLevels;
 
+mod system_views;
+pub use system_views::SystemViews;
+
 mod table_definitions;
 pub use table_definitions::TableDefinitions;
+
+mod time_zone;
+pub use time_zone::TimeZone;
+pub use time_zone::TimeZoneError;
+
+mod user_types;
+pub use user_types::CompositeField;
+pub use user_types::CompositeType;
+pub use user_types::EnumType;
+pub use user_types::FIRST_USER_OID;
@@ -0,0 +1,787 @@
+//! Values of the array types.
+//!
+//! Like postgres an array has up to six dimensions, each with its own length and lower bound,
+//! and the elements are kept in row major order. An empty array has no dimensions at all. The
+//! dimensions are not part of the type, an integer[] column holds arrays of any shape.
+//!
+//! On disk an array is:
+//! * The dimension count as a byte
+//! * The length as a little endian u32 and lower bound as a little endian i32 of each dimension
+//! * A byte flagging if a null bitmap follows
+//! * The null bitmap, one bit per element with the high bit first and set meaning null
+//! * Each non null element serialized as its own type
+use super::{BuiltinSqlTypes, DeserializeTypes, SqlTypeError, TimeZone};
+use bytes::{Buf, BufMut, BytesMut};
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
+
+///Postgres' limit on the number of dimensions
+pub const ARRAY_MAX_DIMENSIONS: usize = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SqlArray {
+    pub dimensions: Vec<ArrayDimension>,
+    pub elements: Vec<Option<BuiltinSqlTypes>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArrayDimension {
+    pub length: usize,
+    pub lower_bound: i32,
+}
+
+impl ArrayDimension {
+    pub fn upper_bound(&self) -> i64 {
+        i64::from(self.lower_bound) + self.length as i64 - 1
+    }
+}
+
+impl SqlArray {
+    /// A one dimensional array starting at 1, what ARRAY[...] makes
+    pub fn new(elements: Vec<Option<BuiltinSqlTypes>>) -> SqlArray {
+        let dimensions = if elements.is_empty() {
+            vec![]
+        } else {
+            vec![ArrayDimension {
+                length: elements.len(),
+                lower_bound: 1,
+            }]
+        };
+        SqlArray {
+            dimensions,
+            elements,
+        }
+    }
+
+    /// Stacks arrays of the same shape into one with an extra outer dimension, this is how
+    /// ARRAY[ARRAY[1,2],ARRAY[3,4]] becomes a two dimensional array
+    pub fn stack(arrays: Vec<SqlArray>) -> Result<SqlArray, SqlTypeError> {
+        let inner = match arrays.first() {
+            Some(a) => a.dimensions.clone(),
+            None => return Ok(SqlArray::new(vec![])),
+        };
+        if arrays.iter().any(|a| a.dimensions != inner) {
+            return Err(SqlTypeError::ArrayDimensionMismatch());
+        }
+        if inner.is_empty() {
+            return Ok(SqlArray::new(vec![]));
+        }
+        if inner.len() + 1 > ARRAY_MAX_DIMENSIONS {
+            return Err(SqlTypeError::ArrayTooManyDimensions(inner.len() + 1));
+        }
+
+        let mut dimensions = vec![ArrayDimension {
+            length: arrays.len(),
+            lower_bound: 1,
+        }];
+        dimensions.extend(inner);
+        Ok(SqlArray {
+            dimensions,
+            elements: arrays.into_iter().flat_map(|a| a.elements).collect(),
+        })
+    }
+
+    /// Parses the text form such as {1,2}, {{a,b},{"c d",NULL}} or [0:1]={1,2}
+    pub fn parse(
+        input: &str,
+        element_type: &DeserializeTypes,
+        zone: &TimeZone,
+    ) -> Result<SqlArray, SqlTypeError> {
+        let malformed = || SqlTypeError::MalformedArray(input.to_string());
+        let mut parser = ArrayParser {
+            input: input.chars().collect(),
+            position: 0,
+        };
+
+        let bounds = parser.parse_bounds().ok_or_else(malformed)?;
+        let root = parser.parse_list().ok_or_else(malformed)?;
+        parser.skip_whitespace();
+        if parser.position != parser.input.len() {
+            return Err(malformed());
+        }
+
+        let mut lengths = vec![];
+        let mut leaf_depth = None;
+        let mut items = vec![];
+        if !ArrayParser::flatten(root, 0, &mut lengths, &mut leaf_depth, &mut items) {
+            return Err(malformed());
+        }
+        if items.is_empty() {
+            //Postgres ignores any bounds given for an empty array
+            return Ok(SqlArray::new(vec![]));
+        }
+        if lengths.len() > ARRAY_MAX_DIMENSIONS {
+            return Err(SqlTypeError::ArrayTooManyDimensions(lengths.len()));
+        }
+
+        let dimensions = match bounds {
+            Some(bounds) => {
+                if bounds.len() != lengths.len() {
+                    return Err(malformed());
+                }
+                let mut dimensions = vec![];
+                for ((lower, upper), length) in bounds.into_iter().zip(lengths) {
+                    if upper - i64::from(lower) + 1 != length as i64 {
+                        return Err(malformed());
+                    }
+                    dimensions.push(ArrayDimension {
+                        length,
+                        lower_bound: lower,
+                    });
+                }
+                dimensions
+            }
+            None => lengths
+                .into_iter()
+                .map(|length| ArrayDimension {
+                    length,
+                    lower_bound: 1,
+                })
+                .collect(),
+        };
+
+        let mut elements = Vec::with_capacity(items.len());
+        for item in items {
+            elements.push(match item {
+                Some(text) => Some(BuiltinSqlTypes::parse_in_zone(
+                    element_type.clone(),
+                    text,
+                    zone,
+                )?),
+                None => None,
+            });
+        }
+        Ok(SqlArray {
+            dimensions,
+            elements,
+        })
+    }
+
+    /// The text form with each element written by the given function, bounds are only shown
+    /// when one of them isn't 1
+    pub fn format(&self, element: &dyn Fn(&BuiltinSqlTypes) -> String) -> String {
+        let mut output = String::new();
+        if self.dimensions.iter().any(|d| d.lower_bound != 1) {
+            for d in self.dimensions.iter() {
+                output.push_str(&format!("[{}:{}]", d.lower_bound, d.upper_bound()));
+            }
+            output.push('=');
+        }
+        if self.dimensions.is_empty() {
+            output.push_str("{}");
+            return output;
+        }
+
+        let mut elements = self.elements.iter();
+        self.format_dimension(0, &mut elements, element, &mut output);
+        output
+    }
+
+    fn format_dimension<'a>(
+        &self,
+        depth: usize,
+        elements: &mut impl Iterator<Item = &'a Option<BuiltinSqlTypes>>,
+        element: &dyn Fn(&BuiltinSqlTypes) -> String,
+        output: &mut String,
+    ) {
+        output.push('{');
+        for i in 0..self.dimensions[depth].length {
+            if i > 0 {
+                output.push(',');
+            }
+            if depth + 1 < self.dimensions.len() {
+                self.format_dimension(depth + 1, elements, element, output);
+            } else {
+                match elements.next() {
+                    Some(Some(e)) => output.push_str(&SqlArray::quote(&element(e))),
+                    _ => output.push_str("NULL"),
+                }
+            }
+        }
+        output.push('}');
+    }
+
+    //Elements are quoted if they would otherwise read back differently
+    fn quote(text: &str) -> String {
+        let needs_quotes = text.is_empty()
+            || text.eq_ignore_ascii_case("null")
+            || text
+                .chars()
+                .any(|c| matches!(c, '{' | '}' | ',' | '"' | '\\') || ArrayParser::is_space(c));
+        if !needs_quotes {
+            return text.to_string();
+        }
+
+        let mut quoted = String::with_capacity(text.len() + 2);
+        quoted.push('"');
+        for c in text.chars() {
+            if c == '"' || c == '\\' {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    pub fn serialize(&self, buff: &mut BytesMut) {
+        buff.put_u8(self.dimensions.len() as u8);
+        for d in self.dimensions.iter() {
+            buff.put_u32_le(d.length as u32);
+            buff.put_i32_le(d.lower_bound);
+        }
+
+        let has_nulls = self.elements.iter().any(Option::is_none);
+        buff.put_u8(u8::from(has_nulls));
+        if has_nulls {
+            for chunk in self.elements.chunks(8) {
+                let mut bits = 0;
+                for (i, e) in chunk.iter().enumerate() {
+                    if e.is_none() {
+                        bits |= 0x80 >> i;
+                    }
+                }
+                buff.put_u8(bits);
+            }
+        }
+
+        for e in self.elements.iter().flatten() {
+            buff.extend_from_slice(&e.serialize());
+        }
+    }
+
+    pub fn deserialize(
+        element_type: &DeserializeTypes,
+        buffer: &mut impl Buf,
+    ) -> Result<SqlArray, SqlTypeError> {
+        if !buffer.has_remaining() {
+            return Err(SqlTypeError::BufferTooShort());
+        }
+        let dimension_count = usize::from(buffer.get_u8());
+        if dimension_count > ARRAY_MAX_DIMENSIONS {
+            return Err(SqlTypeError::ArrayTooManyDimensions(dimension_count));
+        }
+        if buffer.remaining() < dimension_count * 2 * mem::size_of::<u32>() + 1 {
+            return Err(SqlTypeError::BufferTooShort());
+        }
+
+        let mut dimensions = Vec::with_capacity(dimension_count);
+        let mut count: usize = if dimension_count == 0 { 0 } else { 1 };
+        for _ in 0..dimension_count {
+            let length =
+                usize::try_from(buffer.get_u32_le()).map_err(|_| SqlTypeError::BufferTooShort())?;
+            let lower_bound = buffer.get_i32_le();
+            count = count
+                .checked_mul(length)
+                .ok_or(SqlTypeError::BufferTooShort())?;
+            dimensions.push(ArrayDimension {
+                length,
+                lower_bound,
+            });
+        }
+
+        let nulls = if buffer.get_u8() != 0 {
+            let bitmap_length = count.div_ceil(8);
+            if buffer.remaining() < bitmap_length {
+                return Err(SqlTypeError::BufferTooShort());
+            }
+            let bitmap = buffer.copy_to_bytes(bitmap_length);
+            (0..count)
+                .map(|i| bitmap[i / 8] & (0x80 >> (i % 8)) != 0)
+                .collect()
+        } else {
+            vec![false; count]
+        };
+
+        //Every element takes at least a byte so a bad count can't make a huge allocation
+        if nulls.iter().filter(|n| !**n).count() > buffer.remaining() {
+            return Err(SqlTypeError::BufferTooShort());
+        }
+        let mut elements = Vec::with_capacity(count);
+        for is_null in nulls {
+            if is_null {
+                elements.push(None);
+            } else {
+                elements.push(Some(BuiltinSqlTypes::deserialize(
+                    element_type.clone(),
+                    &mut *buffer,
+                )?));
+            }
+        }
+
+        Ok(SqlArray {
+            dimensions,
+            elements,
+        })
+    }
+
+    /// The element at the subscripts, None if there isn't one or it is null. Like postgres
+    /// the number of subscripts has to match the dimensions.
+    pub fn get(&self, subscripts: &[i64]) -> Option<&BuiltinSqlTypes> {
+        if subscripts.len() != self.dimensions.len() || self.dimensions.is_empty() {
+            return None;
+        }
+        let mut offset = 0;
+        for (s, d) in subscripts.iter().zip(self.dimensions.iter()) {
+            if *s < i64::from(d.lower_bound) || *s > d.upper_bound() {
+                return None;
+            }
+            offset = offset * d.length + (s - i64::from(d.lower_bound)) as usize;
+        }
+        self.elements[offset].as_ref()
+    }
+
+    /// The part of the array between the bounds of each dimension, missing bounds mean the
+    /// rest of that dimension. The slice is cut down to what exists and starts at 1.
+    pub fn slice(&self, bounds: &[(Option<i64>, Option<i64>)]) -> SqlArray {
+        if bounds.len() > self.dimensions.len() {
+            return SqlArray::new(vec![]);
+        }
+
+        let mut ranges = vec![];
+        for (i, d) in self.dimensions.iter().enumerate() {
+            let (lower, upper) = bounds.get(i).copied().unwrap_or((None, None));
+            let lower =
+                lower.unwrap_or(i64::MIN).max(i64::from(d.lower_bound)) - i64::from(d.lower_bound);
+            let upper = upper.unwrap_or(i64::MAX).min(d.upper_bound()) - i64::from(d.lower_bound);
+            if lower > upper {
+                return SqlArray::new(vec![]);
+            }
+            ranges.push((lower as usize, upper as usize));
+        }
+
+        let mut elements = vec![];
+        self.collect_slice(0, 0, &ranges, &mut elements);
+        SqlArray {
+            dimensions: ranges
+                .iter()
+                .map(|(lower, upper)| ArrayDimension {
+                    length: upper - lower + 1,
+                    lower_bound: 1,
+                })
+                .collect(),
+            elements,
+        }
+    }
+
+    fn collect_slice(
+        &self,
+        depth: usize,
+        offset: usize,
+        ranges: &[(usize, usize)],
+        output: &mut Vec<Option<BuiltinSqlTypes>>,
+    ) {
+        let (lower, upper) = ranges[depth];
+        for i in lower..=upper {
+            let position = offset * self.dimensions[depth].length + i;
+            if depth + 1 < ranges.len() {
+                self.collect_slice(depth + 1, position, ranges, output);
+            } else {
+                output.push(self.elements[position].clone());
+            }
+        }
+    }
+}
+
+impl fmt::Display for SqlArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(&|e| e.to_string()))
+    }
+}
+
+//The text form is nested lists of items, the shape is checked once it is all read
+enum ArrayNode {
+    List(Vec<ArrayNode>),
+    ///None is an unquoted NULL
+    Item(Option<String>),
+}
+
+struct ArrayParser {
+    input: Vec<char>,
+    position: usize,
+}
+
+impl ArrayParser {
+    //Same as postgres' array_isspace
+    fn is_space(c: char) -> bool {
+        matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c')
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(ArrayParser::is_space).unwrap_or(false) {
+            self.position += 1;
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<i64> {
+        self.skip_whitespace();
+        let start = self.position;
+        if matches!(self.peek(), Some('+') | Some('-')) {
+            self.position += 1;
+        }
+        while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.position += 1;
+        }
+        let number: String = self.input[start..self.position].iter().collect();
+        self.skip_whitespace();
+        number.parse().ok()
+    }
+
+    //The optional [lower:upper] decoration of each dimension followed by =, a lone number is
+    //the upper bound
+    fn parse_bounds(&mut self) -> Option<Option<Vec<(i32, i64)>>> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') {
+            return Some(None);
+        }
+
+        let mut bounds = vec![];
+        while self.peek() == Some('[') {
+            self.position += 1;
+            let first = self.parse_number()?;
+            let (lower, upper) = if self.peek() == Some(':') {
+                self.position += 1;
+                (first, self.parse_number()?)
+            } else {
+                (1, first)
+            };
+            if self.next()? != ']' || upper < lower - 1 {
+                return None;
+            }
+            bounds.push((i32::try_from(lower).ok()?, upper));
+            self.skip_whitespace();
+        }
+        if self.next()? != '=' {
+            return None;
+        }
+        self.skip_whitespace();
+        Some(Some(bounds))
+    }
+
+    fn parse_list(&mut self) -> Option<ArrayNode> {
+        self.skip_whitespace();
+        if self.next()? != '{' {
+            return None;
+        }
+        self.skip_whitespace();
+
+        let mut children = vec![];
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Some(ArrayNode::List(children));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == '{' {
+                children.push(self.parse_list()?);
+            } else {
+                children.push(self.parse_item()?);
+            }
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                '}' => return Some(ArrayNode::List(children)),
+                _ => return None,
+            }
+        }
+    }
+
+    fn parse_item(&mut self) -> Option<ArrayNode> {
+        if self.peek()? == '"' {
+            self.position += 1;
+            let mut text = String::new();
+            loop {
+                match self.next()? {
+                    '"' => return Some(ArrayNode::Item(Some(text))),
+                    '\\' => text.push(self.next()?),
+                    c => text.push(c),
+                }
+            }
+        }
+
+        //Unquoted items lose surrounding whitespace unless it was escaped
+        let mut text = String::new();
+        let mut kept = 0;
+        let mut escaped = false;
+        loop {
+            match self.peek()? {
+                ',' | '}' => break,
+                '{' | '"' => return None,
+                '\\' => {
+                    self.position += 1;
+                    text.push(self.next()?);
+                    kept = text.len();
+                    escaped = true;
+                }
+                c => {
+                    self.position += 1;
+                    text.push(c);
+                    if !ArrayParser::is_space(c) {
+                        kept = text.len();
+                    }
+                }
+            }
+        }
+        text.truncate(kept);
+
+        if text.is_empty() {
+            None
+        } else if !escaped && text.eq_ignore_ascii_case("null") {
+            Some(ArrayNode::Item(None))
+        } else {
+            Some(ArrayNode::Item(Some(text)))
+        }
+    }
+
+    //Every list at a depth must be the same length and every item must be at the same depth
+    fn flatten(
+        node: ArrayNode,
+        depth: usize,
+        lengths: &mut Vec<usize>,
+        leaf_depth: &mut Option<usize>,
+        items: &mut Vec<Option<String>>,
+    ) -> bool {
+        match node {
+            ArrayNode::List(children) => {
+                if leaf_depth.map(|l| depth >= l).unwrap_or(false) {
+                    return false;
+                }
+                if depth == lengths.len() {
+                    lengths.push(children.len());
+                } else if lengths[depth] != children.len() {
+                    return false;
+                }
+                children
+                    .into_iter()
+                    .all(|c| ArrayParser::flatten(c, depth + 1, lengths, leaf_depth, items))
+            }
+            ArrayNode::Item(item) => {
+                match leaf_depth {
+                    Some(l) if *l != depth => return false,
+                    Some(_) => {}
+                    None if depth != lengths.len() => return false,
+                    None => *leaf_depth = Some(depth),
+                }
+                items.push(item);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, element_type: DeserializeTypes) -> Result<SqlArray, SqlTypeError> {
+        SqlArray::parse(input, &element_type, &TimeZone::default())
+    }
+
+    fn integers(values: &[Option<i32>]) -> Vec<Option<BuiltinSqlTypes>> {
+        values
+            .iter()
+            .map(|v| v.map(BuiltinSqlTypes::Integer))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), Box<dyn std::error::Error>> {
+        let array = parse(" { 1 ,NULL, 3 } ", DeserializeTypes::Integer)?;
+        assert_eq!(array, SqlArray::new(integers(&[Some(1), None, Some(3)])));
+
+        let array = parse("{{1,2},{3,4},{5,6}}", DeserializeTypes::Integer)?;
+        assert_eq!(
+            array.dimensions,
+            vec![
+                ArrayDimension {
+                    length: 3,
+                    lower_bound: 1
+                },
+                ArrayDimension {
+                    length: 2,
+                    lower_bound: 1
+                }
+            ]
+        );
+        assert_eq!(array.to_string(), "{{1,2},{3,4},{5,6}}");
+
+        let array = parse(
+            r#"{"a b", "", "NULL", \"q\", x\ , "back\\slash",  spaced  out }"#,
+            DeserializeTypes::Text,
+        )?;
+        assert_eq!(
+            array
+                .elements
+                .iter()
+                .map(|e| e.as_ref().unwrap().to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "a b",
+                "",
+                "NULL",
+                "\"q\"",
+                "x ",
+                "back\\slash",
+                "spaced  out"
+            ]
+        );
+        assert_eq!(
+            array.to_string(),
+            r#"{"a b","","NULL","\"q\"","x ","back\\slash","spaced  out"}"#
+        );
+
+        let array = parse("[0:1][-1:-1]={{a},{b}}", DeserializeTypes::Text)?;
+        assert_eq!(array.dimensions[0].lower_bound, 0);
+        assert_eq!(array.to_string(), "[0:1][-1:-1]={{a},{b}}");
+
+        assert_eq!(parse("{}", DeserializeTypes::Integer)?.to_string(), "{}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "",
+            "1,2",
+            "{1,2",
+            "{1,,2}",
+            "{{1,2},{3}}",
+            "{{1,2},3}",
+            "{1,{2}}",
+            "{a\"b\"}",
+            "{1} x",
+            "[1:3]={1,2}",
+            "[1:2]{1,2}",
+            "{{{{{{{1}}}}}}}",
+        ] {
+            assert!(parse(bad, DeserializeTypes::Integer).is_err(), "{}", bad);
+        }
+        assert!(matches!(
+            parse("{1,x}", DeserializeTypes::Integer),
+            Err(SqlTypeError::InvalidInput(_, _))
+        ));
+        assert!(matches!(
+            parse("{{{{{{{1}}}}}}}", DeserializeTypes::Integer),
+            Err(SqlTypeError::ArrayTooManyDimensions(7))
+        ));
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let array = parse("[0:1][1:2]={{1,NULL},{NULL,4}}", DeserializeTypes::Integer)?;
+        let mut buffer = BytesMut::new();
+        array.serialize(&mut buffer);
+        assert_eq!(
+            buffer.to_vec(),
+            vec![
+                2,
+                2,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                2,
+                0,
+                0,
+                0,
+                1,
+                0,
+                0,
+                0,
+                1,
+                0b0110_0000,
+                1,
+                0,
+                0,
+                0,
+                4,
+                0,
+                0,
+                0
+            ]
+        );
+        let parsed = SqlArray::deserialize(&DeserializeTypes::Integer, &mut buffer.freeze())?;
+        assert_eq!(array, parsed);
+
+        let empty = SqlArray::new(vec![]);
+        let mut buffer = BytesMut::new();
+        empty.serialize(&mut buffer);
+        assert_eq!(buffer.to_vec(), vec![0, 0]);
+        assert_eq!(
+            SqlArray::deserialize(&DeserializeTypes::Integer, &mut buffer.freeze())?,
+            empty
+        );
+
+        let mut truncated = BytesMut::new();
+        truncated.put_u8(1);
+        truncated.put_u32_le(u32::MAX);
+        truncated.put_i32_le(1);
+        truncated.put_u8(0);
+        assert!(
+            SqlArray::deserialize(&DeserializeTypes::Integer, &mut truncated.freeze()).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscripts() -> Result<(), Box<dyn std::error::Error>> {
+        let array = parse("{{1,2,3},{4,5,6}}", DeserializeTypes::Integer)?;
+        assert_eq!(array.get(&[2, 1]), Some(&BuiltinSqlTypes::Integer(4)));
+        assert_eq!(array.get(&[3, 1]), None);
+        assert_eq!(array.get(&[1]), None);
+
+        assert_eq!(array.slice(&[(Some(2), None)]).to_string(), "{{4,5,6}}");
+        assert_eq!(
+            array.slice(&[(None, None), (Some(2), Some(9))]).to_string(),
+            "{{2,3},{5,6}}"
+        );
+        assert_eq!(array.slice(&[(Some(3), Some(4))]).to_string(), "{}");
+        assert_eq!(
+            array
+                .slice(&[(None, None), (None, None), (None, None)])
+                .to_string(),
+            "{}"
+        );
+
+        let shifted = parse("[-1:1]={a,b,c}", DeserializeTypes::Text)?;
+        assert_eq!(
+            shifted.get(&[-1]),
+            Some(&BuiltinSqlTypes::Text("a".to_string()))
+        );
+        assert_eq!(shifted.slice(&[(Some(0), None)]).to_string(), "{b,c}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack() -> Result<(), Box<dyn std::error::Error>> {
+        let rows = vec![
+            SqlArray::new(integers(&[Some(1), Some(2)])),
+            SqlArray::new(integers(&[Some(3), None])),
+        ];
+        assert_eq!(SqlArray::stack(rows)?.to_string(), "{{1,2},{3,NULL}}");
+
+        let mismatched = vec![
+            SqlArray::new(integers(&[Some(1), Some(2)])),
+            SqlArray::new(integers(&[Some(3)])),
+        ];
+        assert!(matches!(
+            SqlArray::stack(mismatched),
+            Err(SqlTypeError::ArrayDimensionMismatch())
+        ));
+        Ok(())
+    }
+}
@@ -1,52 +1,157 @@
+use super::{
+    Bytea, ByteaOutput, CompositeType, DateTime, EnumType, Interval, JsonValue, PgErrorCodes,
+    SqlArray, TimeZone,
+};
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
 use std::fmt;
 use std::mem;
-use std::num::ParseIntError;
-use std::str::{FromStr, ParseBoolError};
+use std::num::IntErrorKind;
+use std::str::FromStr;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum BuiltinSqlTypes {
     Bool(bool),
-    Integer(u32),
+    SmallInt(i16),
+    Integer(i32),
+    BigInt(i64),
+    Real(f32),
+    DoublePrecision(f64),
+    Numeric(BigDecimal),
     Text(String),
     Uuid(uuid::Uuid),
+    ///Days since 2000-01-01
+    Date(i32),
+    ///Microseconds since midnight
+    Time(i64),
+    ///Microseconds since 2000-01-01 00:00:00
+    Timestamp(i64),
+    ///Microseconds since 2000-01-01 00:00:00 UTC
+    TimestampTz(i64),
+    Interval(Interval),
+    Bytea(Vec<u8>),
+    ///The text as given, it has been checked to be valid json
+    Json(String),
+    Jsonb(JsonValue),
+    Array(SqlArray),
+    ///The label's position in its type, which is how it sorts, and the label itself
+    Enum(u32, String),
+    Composite(Vec<Option<BuiltinSqlTypes>>),
 }
 
 //This is effectively a selector for BuiltinSqlTypes since I can't figure out a better method :(
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum DeserializeTypes {
     Bool,
+    SmallInt,
     Integer,
+    BigInt,
+    Real,
+    DoublePrecision,
+    Numeric(Option<NumericTypmod>),
     Text,
     Uuid,
+    Date,
+    Time,
+    Timestamp,
+    TimestampTz,
+    Interval,
+    Bytea,
+    ///Values are stored as text, the modifier is the maximum length in characters
+    VarChar(Option<u32>),
+    ///Values are stored as text padded with spaces to the length in the modifier
+    Char(Option<u32>),
+    Json,
+    Jsonb,
+    ///The element type, it is never an array itself since the dimensions aren't part of the type
+    Array(Box<DeserializeTypes>),
+    Enum(Arc<EnumType>),
+    Composite(Arc<CompositeType>),
 }
 
+/// The precision and scale of a numeric(p,s) column
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NumericTypmod {
+    pub precision: u32,
+    pub scale: u32,
+}
+
+//Postgres caps the declared precision of a numeric column at this
+const NUMERIC_MAX_PRECISION: u32 = 1000;
+
+//The longest length a varchar or char column can be declared with
+const STRING_MAX_LENGTH: u32 = 10 * 1024 * 1024;
+
 impl BuiltinSqlTypes {
-    pub const VALUES: [DeserializeTypes; 3] = [
+    pub const VALUES: [DeserializeTypes; 19] = [
+        DeserializeTypes::Bool,
+        DeserializeTypes::SmallInt,
         DeserializeTypes::Integer,
+        DeserializeTypes::BigInt,
+        DeserializeTypes::Real,
+        DeserializeTypes::DoublePrecision,
+        DeserializeTypes::Numeric(None),
         DeserializeTypes::Text,
         DeserializeTypes::Uuid,
+        DeserializeTypes::Date,
+        DeserializeTypes::Time,
+        DeserializeTypes::Timestamp,
+        DeserializeTypes::TimestampTz,
+        DeserializeTypes::Interval,
+        DeserializeTypes::Bytea,
+        DeserializeTypes::VarChar(None),
+        DeserializeTypes::Char(None),
+        DeserializeTypes::Json,
+        DeserializeTypes::Jsonb,
     ];
 
     //Used to map if we have the types linked up right
-    pub fn type_matches(&self, right: DeserializeTypes) -> bool {
+    pub fn type_matches(&self, right: &DeserializeTypes) -> bool {
         match *self {
-            BuiltinSqlTypes::Bool(_) => match right {
-                DeserializeTypes::Bool => return true,
-                _ => return false,
-            },
-            BuiltinSqlTypes::Integer(_) => match right {
-                DeserializeTypes::Integer => return true,
-                _ => return false,
+            BuiltinSqlTypes::Bool(_) => matches!(right, DeserializeTypes::Bool),
+            BuiltinSqlTypes::SmallInt(_) => matches!(right, DeserializeTypes::SmallInt),
+            BuiltinSqlTypes::Integer(_) => matches!(right, DeserializeTypes::Integer),
+            BuiltinSqlTypes::BigInt(_) => matches!(right, DeserializeTypes::BigInt),
+            BuiltinSqlTypes::Real(_) => matches!(right, DeserializeTypes::Real),
+            BuiltinSqlTypes::DoublePrecision(_) => {
+                matches!(right, DeserializeTypes::DoublePrecision)
+            }
+            BuiltinSqlTypes::Numeric(_) => matches!(right, DeserializeTypes::Numeric(_)),
+            BuiltinSqlTypes::Uuid(_) => matches!(right, DeserializeTypes::Uuid),
+            BuiltinSqlTypes::Text(_) => right.is_string(),
+            BuiltinSqlTypes::Date(_) => matches!(right, DeserializeTypes::Date),
+            BuiltinSqlTypes::Time(_) => matches!(right, DeserializeTypes::Time),
+            BuiltinSqlTypes::Timestamp(_) => matches!(right, DeserializeTypes::Timestamp),
+            BuiltinSqlTypes::TimestampTz(_) => matches!(right, DeserializeTypes::TimestampTz),
+            BuiltinSqlTypes::Interval(_) => matches!(right, DeserializeTypes::Interval),
+            BuiltinSqlTypes::Bytea(_) => matches!(right, DeserializeTypes::Bytea),
+            BuiltinSqlTypes::Json(_) => matches!(right, DeserializeTypes::Json),
+            BuiltinSqlTypes::Jsonb(_) => matches!(right, DeserializeTypes::Jsonb),
+            BuiltinSqlTypes::Array(ref a) => match right {
+                DeserializeTypes::Array(element_type) => a
+                    .elements
+                    .iter()
+                    .flatten()
+                    .all(|e| e.type_matches(element_type)),
+                _ => false,
             },
-            BuiltinSqlTypes::Uuid(_) => match right {
-                DeserializeTypes::Uuid => return true,
-                _ => return false,
+            BuiltinSqlTypes::Enum(position, ref label) => match right {
+                DeserializeTypes::Enum(t) => t.labels.get(position as usize) == Some(label),
+                _ => false,
             },
-            BuiltinSqlTypes::Text(_) => match right {
-                DeserializeTypes::Text => return true,
-                _ => return false,
+            BuiltinSqlTypes::Composite(ref values) => match right {
+                DeserializeTypes::Composite(t) => {
+                    values.len() == t.fields.len()
+                        && values
+                            .iter()
+                            .zip(t.fields.iter())
+                            .all(|(v, f)| v.as_ref().is_none_or(|v| v.type_matches(&f.sql_type)))
+                }
+                _ => false,
             },
         }
     }
@@ -62,9 +167,40 @@ impl BuiltinSqlTypes {
                 }
                 buff.freeze()
             }
+            BuiltinSqlTypes::SmallInt(ref value) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<i16>());
+                buff.put_i16_le(*value);
+                buff.freeze()
+            }
             BuiltinSqlTypes::Integer(ref value) => {
-                let mut buff = BytesMut::with_capacity(mem::size_of::<u32>());
-                buff.put_u32_le(*value);
+                let mut buff = BytesMut::with_capacity(mem::size_of::<i32>());
+                buff.put_i32_le(*value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::BigInt(ref value) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<i64>());
+                buff.put_i64_le(*value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Real(ref value) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<f32>());
+                buff.put_f32_le(*value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::DoublePrecision(ref value) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<f64>());
+                buff.put_f64_le(*value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Numeric(ref value) => {
+                //Stored as the scale followed by the length prefixed two's complement digits
+                let (digits, scale) = value.as_bigint_and_exponent();
+                let digit_bytes = digits.to_signed_bytes_le();
+
+                let mut buff = BytesMut::with_capacity(mem::size_of::<i64>() + digit_bytes.len());
+                buff.put_i64_le(scale);
+                BuiltinSqlTypes::serialize_length(&mut buff, digit_bytes.len());
+                buff.extend_from_slice(&digit_bytes);
                 buff.freeze()
             }
             BuiltinSqlTypes::Uuid(ref value) => {
@@ -72,25 +208,73 @@ impl BuiltinSqlTypes {
                 buff.put_u128_le(value.as_u128());
                 buff.freeze()
             }
-            BuiltinSqlTypes::Text(ref value) => {
-                let mut length = value.len();
-
-                let mut buff = BytesMut::with_capacity((length + 6) / 7);
-
-                while length > 0 {
-                    let last_length = length as u8;
-                    let mut digit: u8 = last_length & 0x7f;
-                    length >>= 7;
-                    if length > 0 {
-                        digit |= 0x80;
-                    }
-                    buff.put_u8(digit);
-                }
-
+            BuiltinSqlTypes::Text(ref value) | BuiltinSqlTypes::Json(ref value) => {
+                let mut buff = BytesMut::with_capacity(value.len().div_ceil(7) + value.len());
+                BuiltinSqlTypes::serialize_length(&mut buff, value.len());
                 buff.extend_from_slice(value.as_bytes());
 
                 buff.freeze()
             }
+            BuiltinSqlTypes::Date(ref value) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<i32>());
+                buff.put_i32_le(*value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Time(ref value)
+            | BuiltinSqlTypes::Timestamp(ref value)
+            | BuiltinSqlTypes::TimestampTz(ref value) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<i64>());
+                buff.put_i64_le(*value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Interval(ref value) => {
+                //Same field order as postgres, the time part then days then months
+                let mut buff = BytesMut::with_capacity(16);
+                buff.put_i64_le(value.microseconds);
+                buff.put_i32_le(value.days);
+                buff.put_i32_le(value.months);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Bytea(ref value) => {
+                let mut buff = BytesMut::with_capacity(value.len().div_ceil(7) + value.len());
+                BuiltinSqlTypes::serialize_length(&mut buff, value.len());
+                buff.extend_from_slice(value);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Jsonb(ref value) => {
+                //Length prefixed so a row can be read without decoding the tree
+                let mut tree = BytesMut::new();
+                value.serialize(&mut tree);
+
+                let mut buff = BytesMut::with_capacity(tree.len().div_ceil(7) + tree.len());
+                BuiltinSqlTypes::serialize_length(&mut buff, tree.len());
+                buff.extend_from_slice(&tree);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Array(ref value) => {
+                //Length prefixed for the same reason as jsonb
+                let mut body = BytesMut::new();
+                value.serialize(&mut body);
+
+                let mut buff = BytesMut::with_capacity(body.len().div_ceil(7) + body.len());
+                BuiltinSqlTypes::serialize_length(&mut buff, body.len());
+                buff.extend_from_slice(&body);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Enum(position, _) => {
+                let mut buff = BytesMut::with_capacity(mem::size_of::<u32>());
+                buff.put_u32_le(position);
+                buff.freeze()
+            }
+            BuiltinSqlTypes::Composite(ref values) => {
+                let mut body = BytesMut::new();
+                CompositeType::serialize(values, &mut body);
+
+                let mut buff = BytesMut::with_capacity(body.len().div_ceil(7) + body.len());
+                BuiltinSqlTypes::serialize_length(&mut buff, body.len());
+                buff.extend_from_slice(&body);
+                buff.freeze()
+            }
         }
     }
 
@@ -111,15 +295,51 @@ impl BuiltinSqlTypes {
 
                 Ok(value)
             }
+            DeserializeTypes::SmallInt => {
+                if buffer.remaining() < mem::size_of::<i16>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                Ok(BuiltinSqlTypes::SmallInt(buffer.get_i16_le()))
+            }
             DeserializeTypes::Integer => {
-                if buffer.remaining() < mem::size_of::<u32>() {
+                if buffer.remaining() < mem::size_of::<i32>() {
                     return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
                 }
-                let dest = buffer.get_u32_le();
+                let dest = buffer.get_i32_le();
                 let value = BuiltinSqlTypes::Integer(dest);
 
                 Ok(value)
             }
+            DeserializeTypes::BigInt => {
+                if buffer.remaining() < mem::size_of::<i64>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                Ok(BuiltinSqlTypes::BigInt(buffer.get_i64_le()))
+            }
+            DeserializeTypes::Real => {
+                if buffer.remaining() < mem::size_of::<f32>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                Ok(BuiltinSqlTypes::Real(buffer.get_f32_le()))
+            }
+            DeserializeTypes::DoublePrecision => {
+                if buffer.remaining() < mem::size_of::<f64>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                Ok(BuiltinSqlTypes::DoublePrecision(buffer.get_f64_le()))
+            }
+            DeserializeTypes::Numeric(_) => {
+                if buffer.remaining() < mem::size_of::<i64>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                let scale = buffer.get_i64_le();
+
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
+                let digit_bytes = buffer.copy_to_bytes(length);
+                let digits = BigInt::from_signed_bytes_le(&digit_bytes);
+
+                Ok(BuiltinSqlTypes::Numeric(BigDecimal::new(digits, scale)))
+            }
             DeserializeTypes::Uuid => {
                 if buffer.remaining() < mem::size_of::<u128>() {
                     return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
@@ -129,52 +349,666 @@ impl BuiltinSqlTypes {
 
                 Ok(value)
             }
-            DeserializeTypes::Text => {
-                if !buffer.has_remaining() {
-                    return Err(SqlTypeError::EmptyBuffer());
-                }
-
-                let mut length: usize = 0;
-                let mut high_bit = 1;
-                let mut loop_count = 0;
-                while high_bit == 1 {
-                    if !buffer.has_remaining() {
-                        return Err(SqlTypeError::BufferTooShort());
-                    }
+            DeserializeTypes::Text | DeserializeTypes::VarChar(_) | DeserializeTypes::Char(_) => {
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
 
-                    let b = buffer.get_u8();
-                    high_bit = b >> 7;
+                let value_buff = buffer.copy_to_bytes(length);
+                let value_str = String::from_utf8(value_buff.to_vec())?;
 
-                    let mut low_bits: usize = (b & 0x7f).into();
-                    low_bits <<= 7 * loop_count;
-                    loop_count += 1;
+                let value = BuiltinSqlTypes::Text(value_str);
 
-                    length += low_bits;
+                Ok(value)
+            }
+            DeserializeTypes::Date => {
+                if buffer.remaining() < mem::size_of::<i32>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
                 }
-
-                if length > buffer.remaining() {
+                Ok(BuiltinSqlTypes::Date(buffer.get_i32_le()))
+            }
+            DeserializeTypes::Time
+            | DeserializeTypes::Timestamp
+            | DeserializeTypes::TimestampTz => {
+                if buffer.remaining() < mem::size_of::<i64>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                let value = buffer.get_i64_le();
+                Ok(match target_type {
+                    DeserializeTypes::Time => BuiltinSqlTypes::Time(value),
+                    DeserializeTypes::Timestamp => BuiltinSqlTypes::Timestamp(value),
+                    _ => BuiltinSqlTypes::TimestampTz(value),
+                })
+            }
+            DeserializeTypes::Interval => {
+                if buffer.remaining() < 16 {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                let microseconds = buffer.get_i64_le();
+                let days = buffer.get_i32_le();
+                let months = buffer.get_i32_le();
+                Ok(BuiltinSqlTypes::Interval(Interval::new(
+                    months,
+                    days,
+                    microseconds,
+                )))
+            }
+            DeserializeTypes::Bytea => {
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
+                Ok(BuiltinSqlTypes::Bytea(
+                    buffer.copy_to_bytes(length).to_vec(),
+                ))
+            }
+            DeserializeTypes::Json => {
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
+                let value_buff = buffer.copy_to_bytes(length);
+                Ok(BuiltinSqlTypes::Json(String::from_utf8(
+                    value_buff.to_vec(),
+                )?))
+            }
+            DeserializeTypes::Jsonb => {
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
+                let mut tree = buffer.copy_to_bytes(length);
+                let value = JsonValue::deserialize(&mut tree)?;
+                if tree.has_remaining() {
+                    return Err(SqlTypeError::InvalidStringLength(
+                        length,
+                        length - tree.remaining(),
+                    ));
+                }
+                Ok(BuiltinSqlTypes::Jsonb(value))
+            }
+            DeserializeTypes::Array(element_type) => {
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
+                let mut body = buffer.copy_to_bytes(length);
+                let value = SqlArray::deserialize(&element_type, &mut body)?;
+                if body.has_remaining() {
+                    return Err(SqlTypeError::InvalidStringLength(
+                        length,
+                        length - body.remaining(),
+                    ));
+                }
+                Ok(BuiltinSqlTypes::Array(value))
+            }
+            DeserializeTypes::Enum(t) => {
+                if buffer.remaining() < mem::size_of::<u32>() {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                t.label(buffer.get_u32_le())
+            }
+            DeserializeTypes::Composite(t) => {
+                let length = BuiltinSqlTypes::deserialize_length(&mut buffer)?;
+                let mut body = buffer.copy_to_bytes(length);
+                let values = t.deserialize(&mut body)?;
+                if body.has_remaining() {
                     return Err(SqlTypeError::InvalidStringLength(
                         length,
-                        buffer.remaining(),
+                        length - body.remaining(),
                     ));
                 }
+                Ok(BuiltinSqlTypes::Composite(values))
+            }
+        }
+    }
 
-                let value_buff = buffer.copy_to_bytes(length);
-                let value_str = String::from_utf8(value_buff.to_vec())?;
+    //Variable length types are prefixed by their length, 7 bits at a time with the high bit
+    //flagging that more length follows. A zero length still takes a byte.
+    fn serialize_length(buff: &mut BytesMut, mut length: usize) {
+        loop {
+            let last_length = length as u8;
+            let mut digit: u8 = last_length & 0x7f;
+            length >>= 7;
+            if length > 0 {
+                digit |= 0x80;
+            }
+            buff.put_u8(digit);
+            if length == 0 {
+                break;
+            }
+        }
+    }
 
-                let value = BuiltinSqlTypes::Text(value_str);
+    fn deserialize_length(buffer: &mut impl Buf) -> Result<usize, SqlTypeError> {
+        if !buffer.has_remaining() {
+            return Err(SqlTypeError::EmptyBuffer());
+        }
 
-                Ok(value)
+        let mut length: usize = 0;
+        let mut high_bit = 1;
+        let mut loop_count = 0;
+        while high_bit == 1 {
+            if !buffer.has_remaining() {
+                return Err(SqlTypeError::BufferTooShort());
             }
+
+            let b = buffer.get_u8();
+            high_bit = b >> 7;
+
+            let mut low_bits: usize = (b & 0x7f).into();
+            low_bits <<= 7 * loop_count;
+            loop_count += 1;
+
+            length += low_bits;
         }
+
+        if length > buffer.remaining() {
+            return Err(SqlTypeError::InvalidStringLength(
+                length,
+                buffer.remaining(),
+            ));
+        }
+
+        Ok(length)
     }
 
+    /// Parses the text form of a value, a timestamp with time zone without an explicit zone is
+    /// read as UTC
     pub fn parse(target_type: DeserializeTypes, buffer: String) -> Result<Self, SqlTypeError> {
+        BuiltinSqlTypes::parse_in_zone(target_type, buffer, &TimeZone::default())
+    }
+
+    /// Parses the text form of a value, reading a timestamp with time zone in the given zone
+    /// unless it has one of its own
+    pub fn parse_in_zone(
+        target_type: DeserializeTypes,
+        buffer: String,
+        zone: &TimeZone,
+    ) -> Result<Self, SqlTypeError> {
         match target_type {
-            DeserializeTypes::Bool => Ok(BuiltinSqlTypes::Bool(buffer.parse::<bool>()?)),
-            DeserializeTypes::Integer => Ok(BuiltinSqlTypes::Integer(buffer.parse::<u32>()?)),
+            DeserializeTypes::Bool => {
+                Ok(BuiltinSqlTypes::Bool(BuiltinSqlTypes::parse_bool(&buffer)?))
+            }
+            DeserializeTypes::SmallInt => Ok(BuiltinSqlTypes::SmallInt(
+                BuiltinSqlTypes::parse_integer(target_type, &buffer)?,
+            )),
+            DeserializeTypes::Integer => Ok(BuiltinSqlTypes::Integer(
+                BuiltinSqlTypes::parse_integer(target_type, &buffer)?,
+            )),
+            DeserializeTypes::BigInt => Ok(BuiltinSqlTypes::BigInt(
+                BuiltinSqlTypes::parse_integer(target_type, &buffer)?,
+            )),
+            DeserializeTypes::Real => Ok(BuiltinSqlTypes::Real(BuiltinSqlTypes::parse_float(
+                target_type,
+                &buffer,
+            )?)),
+            DeserializeTypes::DoublePrecision => Ok(BuiltinSqlTypes::DoublePrecision(
+                BuiltinSqlTypes::parse_float(target_type, &buffer)?,
+            )),
+            DeserializeTypes::Numeric(typmod) => {
+                let mut value = BigDecimal::from_str(buffer.trim())
+                    .map_err(|_| SqlTypeError::InvalidInput(target_type, buffer.clone()))?;
+                if value.fractional_digit_count() < 0 {
+                    value = value.with_scale(0);
+                }
+                Ok(BuiltinSqlTypes::Numeric(
+                    BuiltinSqlTypes::apply_numeric_typmod(value, typmod)?,
+                ))
+            }
             DeserializeTypes::Uuid => Ok(BuiltinSqlTypes::Uuid(uuid::Uuid::parse_str(&buffer)?)),
             DeserializeTypes::Text => Ok(BuiltinSqlTypes::Text(buffer)),
+            DeserializeTypes::Date => Ok(BuiltinSqlTypes::Date(DateTime::parse_date(&buffer)?)),
+            DeserializeTypes::Time => Ok(BuiltinSqlTypes::Time(DateTime::parse_time(&buffer)?)),
+            DeserializeTypes::Timestamp => Ok(BuiltinSqlTypes::Timestamp(
+                DateTime::parse_timestamp(&buffer, None)?,
+            )),
+            DeserializeTypes::TimestampTz => Ok(BuiltinSqlTypes::TimestampTz(
+                DateTime::parse_timestamp(&buffer, Some(zone))?,
+            )),
+            DeserializeTypes::Interval => {
+                Ok(BuiltinSqlTypes::Interval(Interval::from_str(&buffer)?))
+            }
+            DeserializeTypes::Bytea => Ok(BuiltinSqlTypes::Bytea(Bytea::parse(&buffer)?)),
+            DeserializeTypes::VarChar(_) | DeserializeTypes::Char(_) => Ok(BuiltinSqlTypes::Text(
+                BuiltinSqlTypes::apply_length_typmod(buffer, target_type, false)?,
+            )),
+            DeserializeTypes::Json => {
+                JsonValue::parse(&buffer, target_type)?;
+                Ok(BuiltinSqlTypes::Json(buffer))
+            }
+            DeserializeTypes::Jsonb => Ok(BuiltinSqlTypes::Jsonb(JsonValue::parse_jsonb(&buffer)?)),
+            DeserializeTypes::Array(element_type) => Ok(BuiltinSqlTypes::Array(SqlArray::parse(
+                &buffer,
+                &element_type,
+                zone,
+            )?)),
+            DeserializeTypes::Enum(t) => t.parse(&buffer),
+            DeserializeTypes::Composite(t) => {
+                Ok(BuiltinSqlTypes::Composite(t.parse(&buffer, zone)?))
+            }
+        }
+    }
+
+    /// The text form of the value, a timestamp with time zone is shown in the given zone and
+    /// bytea in the given format
+    pub fn to_string_with(&self, zone: &TimeZone, bytea_output: ByteaOutput) -> String {
+        match self {
+            BuiltinSqlTypes::TimestampTz(value) => DateTime::format_timestamp(*value, Some(zone)),
+            BuiltinSqlTypes::Bytea(value) => Bytea::format(value, bytea_output),
+            BuiltinSqlTypes::Array(value) => {
+                value.format(&|e| e.to_string_with(zone, bytea_output))
+            }
+            BuiltinSqlTypes::Composite(values) => {
+                CompositeType::format(values, &|v| v.to_string_with(zone, bytea_output))
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// Fits a string to a varchar(n) or char(n), char values are padded with spaces. Like
+    /// postgres an explicit cast cuts the value to length, otherwise only trailing spaces may
+    /// be cut and anything else is an error.
+    pub fn apply_length_typmod(
+        value: String,
+        target_type: DeserializeTypes,
+        explicit: bool,
+    ) -> Result<String, SqlTypeError> {
+        let (length, pad) = match target_type {
+            DeserializeTypes::VarChar(Some(l)) => (l as usize, false),
+            DeserializeTypes::Char(Some(l)) => (l as usize, true),
+            _ => return Ok(value),
+        };
+
+        let mut result = match value.char_indices().nth(length) {
+            Some((cut, _)) => {
+                if !explicit && !value[cut..].chars().all(|c| c == ' ') {
+                    return Err(SqlTypeError::ValueTooLong(target_type));
+                }
+                value[..cut].to_string()
+            }
+            None => value,
+        };
+        if pad {
+            let missing = length - result.chars().count();
+            result.extend(std::iter::repeat_n(' ', missing));
+        }
+        Ok(result)
+    }
+
+    //Postgres accepts any unique prefix of true/false/yes/no plus on/off/1/0
+    fn parse_bool(buffer: &str) -> Result<bool, SqlTypeError> {
+        let value = buffer.trim().to_lowercase();
+        let is_prefix =
+            |full: &str, min_len: usize| value.len() >= min_len && full.starts_with(value.as_str());
+
+        if is_prefix("true", 1) || is_prefix("yes", 1) || value == "on" || value == "1" {
+            Ok(true)
+        } else if is_prefix("false", 1) || is_prefix("no", 1) || is_prefix("off", 2) || value == "0"
+        {
+            Ok(false)
+        } else {
+            Err(SqlTypeError::InvalidInput(
+                DeserializeTypes::Bool,
+                buffer.to_string(),
+            ))
+        }
+    }
+
+    fn parse_integer<T: FromStr<Err = std::num::ParseIntError>>(
+        target_type: DeserializeTypes,
+        buffer: &str,
+    ) -> Result<T, SqlTypeError> {
+        buffer.trim().parse::<T>().map_err(|e| match e.kind() {
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                SqlTypeError::OutOfRange(buffer.to_string(), target_type)
+            }
+            _ => SqlTypeError::InvalidInput(target_type, buffer.to_string()),
+        })
+    }
+
+    fn parse_float<T: FromStr + Into<f64> + Copy>(
+        target_type: DeserializeTypes,
+        buffer: &str,
+    ) -> Result<T, SqlTypeError> {
+        let trimmed = buffer.trim();
+        let value = trimmed
+            .parse::<T>()
+            .map_err(|_| SqlTypeError::InvalidInput(target_type.clone(), buffer.to_string()))?;
+
+        //Rust quietly saturates to infinity or zero, postgres refuses
+        let as_f64: f64 = value.into();
+        let mantissa = trimmed.split(['e', 'E']).next().unwrap_or("");
+        if (as_f64.is_infinite() && mantissa.chars().any(|c| c.is_ascii_digit()))
+            || (as_f64 == 0.0 && mantissa.chars().any(|c| ('1'..='9').contains(&c)))
+        {
+            return Err(SqlTypeError::OutOfRange(buffer.to_string(), target_type));
+        }
+
+        Ok(value)
+    }
+
+    /// Rounds a numeric to the scale of the column and checks that it still fits the precision
+    pub fn apply_numeric_typmod(
+        value: BigDecimal,
+        typmod: Option<NumericTypmod>,
+    ) -> Result<BigDecimal, SqlTypeError> {
+        let typmod = match typmod {
+            Some(t) => t,
+            None => return Ok(value),
+        };
+
+        let rounded = value.with_scale_round(typmod.scale.into(), RoundingMode::HalfUp);
+        if !rounded.is_zero() {
+            let integer_digits =
+                i64::try_from(rounded.digits()).unwrap_or(i64::MAX) - i64::from(typmod.scale);
+            if integer_digits > i64::from(typmod.precision - typmod.scale) {
+                return Err(SqlTypeError::NumericFieldOverflow(
+                    typmod.precision,
+                    typmod.scale,
+                ));
+            }
+        }
+
+        Ok(rounded)
+    }
+
+    //The shortest digits that read back as the same value, with postgres' spellings of the rest
+    fn format_float(
+        f: &mut fmt::Formatter<'_>,
+        value: f64,
+        shortest: &dyn fmt::Display,
+    ) -> fmt::Result {
+        if value.is_nan() {
+            write!(f, "NaN")
+        } else if value == f64::INFINITY {
+            write!(f, "Infinity")
+        } else if value == f64::NEG_INFINITY {
+            write!(f, "-Infinity")
+        } else {
+            write!(f, "{}", shortest)
+        }
+    }
+}
+
+impl DeserializeTypes {
+    /// The Postgres OID for the type, clients use this to decode column values
+    pub fn oid(&self) -> u32 {
+        match self {
+            DeserializeTypes::Bool => 16,
+            DeserializeTypes::SmallInt => 21,
+            DeserializeTypes::Integer => 23,
+            DeserializeTypes::BigInt => 20,
+            DeserializeTypes::Real => 700,
+            DeserializeTypes::DoublePrecision => 701,
+            DeserializeTypes::Numeric(_) => 1700,
+            DeserializeTypes::Text => 25,
+            DeserializeTypes::Uuid => 2950,
+            DeserializeTypes::Date => 1082,
+            DeserializeTypes::Time => 1083,
+            DeserializeTypes::Timestamp => 1114,
+            DeserializeTypes::TimestampTz => 1184,
+            DeserializeTypes::Interval => 1186,
+            DeserializeTypes::Bytea => 17,
+            DeserializeTypes::VarChar(_) => 1043,
+            DeserializeTypes::Char(_) => 1042,
+            DeserializeTypes::Json => 114,
+            DeserializeTypes::Jsonb => 3802,
+            DeserializeTypes::Array(element_type) => element_type.array_oid(),
+            DeserializeTypes::Enum(t) => t.oid,
+            DeserializeTypes::Composite(t) => t.oid,
+        }
+    }
+
+    /// The name pg_type stores for the type, display uses the SQL spelling instead
+    pub fn typname(&self) -> String {
+        match self {
+            DeserializeTypes::Bool => "bool".to_string(),
+            DeserializeTypes::SmallInt => "int2".to_string(),
+            DeserializeTypes::Integer => "int4".to_string(),
+            DeserializeTypes::BigInt => "int8".to_string(),
+            DeserializeTypes::Real => "float4".to_string(),
+            DeserializeTypes::DoublePrecision => "float8".to_string(),
+            DeserializeTypes::Numeric(_) => "numeric".to_string(),
+            DeserializeTypes::Text => "text".to_string(),
+            DeserializeTypes::Uuid => "uuid".to_string(),
+            DeserializeTypes::Date => "date".to_string(),
+            DeserializeTypes::Time => "time".to_string(),
+            DeserializeTypes::Timestamp => "timestamp".to_string(),
+            DeserializeTypes::TimestampTz => "timestamptz".to_string(),
+            DeserializeTypes::Interval => "interval".to_string(),
+            DeserializeTypes::Bytea => "bytea".to_string(),
+            DeserializeTypes::VarChar(_) => "varchar".to_string(),
+            DeserializeTypes::Char(_) => "bpchar".to_string(),
+            DeserializeTypes::Json => "json".to_string(),
+            DeserializeTypes::Jsonb => "jsonb".to_string(),
+            DeserializeTypes::Array(element_type) => format!("_{}", element_type.typname()),
+            DeserializeTypes::Enum(t) => t.name.clone(),
+            DeserializeTypes::Composite(t) => t.name.clone(),
+        }
+    }
+
+    //Every builtin type has its array type, varchar(n)[] is the same type as varchar[]
+    fn array_oid(&self) -> u32 {
+        match self {
+            DeserializeTypes::Bool => 1000,
+            DeserializeTypes::SmallInt => 1005,
+            DeserializeTypes::Integer => 1007,
+            DeserializeTypes::BigInt => 1016,
+            DeserializeTypes::Real => 1021,
+            DeserializeTypes::DoublePrecision => 1022,
+            DeserializeTypes::Numeric(_) => 1231,
+            DeserializeTypes::Text => 1009,
+            DeserializeTypes::Uuid => 2951,
+            DeserializeTypes::Date => 1182,
+            DeserializeTypes::Time => 1183,
+            DeserializeTypes::Timestamp => 1115,
+            DeserializeTypes::TimestampTz => 1185,
+            DeserializeTypes::Interval => 1187,
+            DeserializeTypes::Bytea => 1001,
+            DeserializeTypes::VarChar(_) => 1015,
+            DeserializeTypes::Char(_) => 1014,
+            DeserializeTypes::Json => 199,
+            DeserializeTypes::Jsonb => 3807,
+            DeserializeTypes::Array(element_type) => element_type.array_oid(),
+            DeserializeTypes::Enum(t) => t.array_oid,
+            DeserializeTypes::Composite(t) => t.array_oid,
+        }
+    }
+
+    /// The builtin type or builtin array type with the OID, without any modifier
+    pub fn from_oid(oid: u32) -> Option<DeserializeTypes> {
+        BuiltinSqlTypes::VALUES.iter().find_map(|t| {
+            if t.oid() == oid {
+                Some(t.clone())
+            } else if t.array_oid() == oid {
+                Some(DeserializeTypes::Array(Box::new(t.clone())))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Looks up a type name in the builtin types and then the given user types, either can
+    /// be made an array with a suffix
+    pub fn resolve(
+        s: &str,
+        user_types: &[DeserializeTypes],
+    ) -> Result<DeserializeTypes, SqlTypeError> {
+        match DeserializeTypes::from_str(s) {
+            Err(SqlTypeError::InvalidType(_)) => {}
+            result => return result,
+        }
+
+        let lower = s.trim().to_lowercase();
+        if let Some(element_name) = DeserializeTypes::strip_array_suffix(s, &lower)? {
+            return Ok(DeserializeTypes::Array(Box::new(
+                DeserializeTypes::resolve(element_name, user_types)?,
+            )));
+        }
+        user_types
+            .iter()
+            .find(|t| t.to_string() == lower)
+            .cloned()
+            .ok_or_else(|| SqlTypeError::InvalidType(s.to_string()))
+    }
+
+    /// Size of the type in bytes, -1 means variable length
+    pub fn type_length(&self) -> i16 {
+        match self {
+            DeserializeTypes::Bool => 1,
+            DeserializeTypes::SmallInt => 2,
+            DeserializeTypes::Integer => 4,
+            DeserializeTypes::BigInt => 8,
+            DeserializeTypes::Real => 4,
+            DeserializeTypes::DoublePrecision => 8,
+            DeserializeTypes::Numeric(_) => -1,
+            DeserializeTypes::Text => -1,
+            DeserializeTypes::Uuid => 16,
+            DeserializeTypes::Date => 4,
+            DeserializeTypes::Time => 8,
+            DeserializeTypes::Timestamp => 8,
+            DeserializeTypes::TimestampTz => 8,
+            DeserializeTypes::Interval => 16,
+            DeserializeTypes::Bytea => -1,
+            DeserializeTypes::VarChar(_) => -1,
+            DeserializeTypes::Char(_) => -1,
+            DeserializeTypes::Json => -1,
+            DeserializeTypes::Jsonb => -1,
+            DeserializeTypes::Array(_) => -1,
+            DeserializeTypes::Enum(_) => 4,
+            DeserializeTypes::Composite(_) => -1,
+        }
+    }
+
+    /// The type modifier as Postgres encodes it on the wire, -1 means none
+    pub fn type_modifier(&self) -> i32 {
+        match self {
+            DeserializeTypes::Numeric(Some(t)) => {
+                i32::try_from((t.precision << 16) | t.scale).unwrap_or(-1) + 4
+            }
+            DeserializeTypes::VarChar(Some(l)) | DeserializeTypes::Char(Some(l)) => {
+                i32::try_from(*l).unwrap_or(-1) + 4
+            }
+            //Like postgres an array carries the modifier of its elements
+            DeserializeTypes::Array(element_type) => element_type.type_modifier(),
+            _ => -1,
+        }
+    }
+
+    /// Reverses type_modifier, used to rebuild a column's type from the catalog
+    pub fn with_typmod(&self, typmod: i32) -> Result<DeserializeTypes, SqlTypeError> {
+        let invalid = || SqlTypeError::InvalidTypeModifier(format!("{}({})", self, typmod));
+        if typmod < 0 {
+            return Ok(self.without_typmod());
+        }
+        let value = u32::try_from(typmod - 4).map_err(|_| invalid())?;
+        match self {
+            DeserializeTypes::Numeric(_) => Ok(DeserializeTypes::Numeric(Some(NumericTypmod {
+                precision: value >> 16,
+                scale: value & 0xffff,
+            }))),
+            DeserializeTypes::VarChar(_) => Ok(DeserializeTypes::VarChar(Some(value))),
+            DeserializeTypes::Char(_) => Ok(DeserializeTypes::Char(Some(value))),
+            DeserializeTypes::Array(element_type) => Ok(DeserializeTypes::Array(Box::new(
+                element_type.with_typmod(typmod)?,
+            ))),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The same type without any modifier, numeric(5,2) becomes numeric
+    pub fn without_typmod(&self) -> DeserializeTypes {
+        match self {
+            DeserializeTypes::Numeric(_) => DeserializeTypes::Numeric(None),
+            DeserializeTypes::VarChar(_) => DeserializeTypes::VarChar(None),
+            DeserializeTypes::Char(_) => DeserializeTypes::Char(None),
+            DeserializeTypes::Array(element_type) => {
+                DeserializeTypes::Array(Box::new(element_type.without_typmod()))
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// The element type of an array, any other type is its own element
+    pub fn element_type(&self) -> &DeserializeTypes {
+        match self {
+            DeserializeTypes::Array(element_type) => element_type,
+            _ => self,
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, DeserializeTypes::Array(_))
+    }
+
+    /// Text, varchar and char all hold their values as text
+    pub fn is_string(&self) -> bool {
+        matches!(
+            self,
+            DeserializeTypes::Text | DeserializeTypes::VarChar(_) | DeserializeTypes::Char(_)
+        )
+    }
+
+    pub fn is_json(&self) -> bool {
+        matches!(self, DeserializeTypes::Json | DeserializeTypes::Jsonb)
+    }
+
+    pub fn is_date_time(&self) -> bool {
+        matches!(
+            self,
+            DeserializeTypes::Date | DeserializeTypes::Timestamp | DeserializeTypes::TimestampTz
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            DeserializeTypes::SmallInt
+                | DeserializeTypes::Integer
+                | DeserializeTypes::BigInt
+                | DeserializeTypes::Real
+                | DeserializeTypes::DoublePrecision
+                | DeserializeTypes::Numeric(_)
+        )
+    }
+
+    fn parse_numeric_typmod(
+        s: &str,
+        modifiers: &[u32],
+    ) -> Result<Option<NumericTypmod>, SqlTypeError> {
+        let (precision, scale) = match *modifiers {
+            [] => return Ok(None),
+            [p] => (p, 0),
+            [p, s] => (p, s),
+            _ => return Err(SqlTypeError::InvalidTypeModifier(s.to_string())),
+        };
+        if !(1..=NUMERIC_MAX_PRECISION).contains(&precision) || scale > precision {
+            return Err(SqlTypeError::InvalidTypeModifier(s.to_string()));
+        }
+        Ok(Some(NumericTypmod { precision, scale }))
+    }
+
+    //Any [] or [n] suffixes or the standard's ARRAY keyword make an array type, like postgres
+    //the sizes and number of dimensions are not kept
+    fn strip_array_suffix<'a>(s: &str, name: &'a str) -> Result<Option<&'a str>, SqlTypeError> {
+        let mut element_name = name;
+        let mut found = false;
+        while let Some(rest) = element_name.strip_suffix(']') {
+            let open = rest
+                .rfind('[')
+                .ok_or_else(|| SqlTypeError::InvalidType(s.to_string()))?;
+            if !rest[open + 1..].trim().chars().all(|c| c.is_ascii_digit()) {
+                return Err(SqlTypeError::InvalidType(s.to_string()));
+            }
+            element_name = rest[..open].trim_end();
+            found = true;
+        }
+        if let Some(rest) = element_name.strip_suffix("array") {
+            if rest.ends_with(char::is_whitespace) {
+                element_name = rest.trim_end();
+                found = true;
+            }
+        }
+
+        if found {
+            Ok(Some(element_name))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn parse_length_typmod(s: &str, modifiers: &[u32]) -> Result<Option<u32>, SqlTypeError> {
+        match *modifiers {
+            [] => Ok(None),
+            [l] if (1..=STRING_MAX_LENGTH).contains(&l) => Ok(Some(l)),
+            _ => Err(SqlTypeError::InvalidTypeModifier(s.to_string())),
         }
     }
 }
@@ -182,12 +1016,76 @@ impl BuiltinSqlTypes {
 impl FromStr for DeserializeTypes {
     type Err = SqlTypeError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "integer" => Ok(DeserializeTypes::Integer),
-            "text" => Ok(DeserializeTypes::Text),
-            "uuid" => Ok(DeserializeTypes::Uuid),
-            _ => Err(SqlTypeError::InvalidType(s.to_string())),
+        let lower = s.trim().to_lowercase();
+
+        if let Some(element_name) = DeserializeTypes::strip_array_suffix(s, &lower)? {
+            return Ok(DeserializeTypes::Array(Box::new(
+                DeserializeTypes::from_str(element_name)?,
+            )));
+        }
+
+        //Split off any type modifiers such as numeric(10,2)
+        let (name, modifiers) = match lower.split_once('(') {
+            Some((name, rest)) => {
+                let rest = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| SqlTypeError::InvalidType(s.to_string()))?;
+                let modifiers = rest
+                    .split(',')
+                    .map(|m| m.trim().parse::<u32>())
+                    .collect::<Result<Vec<u32>, _>>()
+                    .map_err(|_| SqlTypeError::InvalidTypeModifier(s.to_string()))?;
+                (name.trim(), modifiers)
+            }
+            None => (lower.as_str(), vec![]),
+        };
+        let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+        let sql_type = match name.as_str() {
+            "bool" | "boolean" => DeserializeTypes::Bool,
+            "smallint" | "int2" => DeserializeTypes::SmallInt,
+            "integer" | "int" | "int4" => DeserializeTypes::Integer,
+            "bigint" | "int8" => DeserializeTypes::BigInt,
+            "real" | "float4" => DeserializeTypes::Real,
+            "double precision" | "float8" | "float" => DeserializeTypes::DoublePrecision,
+            "numeric" | "decimal" => {
+                return Ok(DeserializeTypes::Numeric(
+                    DeserializeTypes::parse_numeric_typmod(s, &modifiers)?,
+                ))
+            }
+            "text" => DeserializeTypes::Text,
+            "uuid" => DeserializeTypes::Uuid,
+            "date" => DeserializeTypes::Date,
+            "time" | "time without time zone" => DeserializeTypes::Time,
+            "timestamp" | "timestamp without time zone" => DeserializeTypes::Timestamp,
+            "timestamptz" | "timestamp with time zone" => DeserializeTypes::TimestampTz,
+            "interval" => DeserializeTypes::Interval,
+            "bytea" => DeserializeTypes::Bytea,
+            "json" => DeserializeTypes::Json,
+            "jsonb" => DeserializeTypes::Jsonb,
+            "varchar" | "character varying" | "char varying" => {
+                return Ok(DeserializeTypes::VarChar(
+                    DeserializeTypes::parse_length_typmod(s, &modifiers)?,
+                ))
+            }
+            //A plain char is char(1), only bpchar has no length
+            "char" | "character" => {
+                return Ok(DeserializeTypes::Char(Some(
+                    DeserializeTypes::parse_length_typmod(s, &modifiers)?.unwrap_or(1),
+                )))
+            }
+            "bpchar" => {
+                return Ok(DeserializeTypes::Char(
+                    DeserializeTypes::parse_length_typmod(s, &modifiers)?,
+                ))
+            }
+            _ => return Err(SqlTypeError::InvalidType(s.to_string())),
+        };
+
+        if !modifiers.is_empty() {
+            return Err(SqlTypeError::InvalidTypeModifier(s.to_string()));
         }
+        Ok(sql_type)
     }
 }
 
@@ -195,35 +1093,155 @@ impl fmt::Display for BuiltinSqlTypes {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             BuiltinSqlTypes::Bool(ref value) => {
+                if *value {
+                    write!(f, "t")
+                } else {
+                    write!(f, "f")
+                }
+            }
+            BuiltinSqlTypes::SmallInt(ref value) => {
                 write!(f, "{}", value)
             }
             BuiltinSqlTypes::Integer(ref value) => {
                 write!(f, "{}", value)
             }
+            BuiltinSqlTypes::BigInt(ref value) => {
+                write!(f, "{}", value)
+            }
+            BuiltinSqlTypes::Real(ref value) => {
+                BuiltinSqlTypes::format_float(f, f64::from(*value), value)
+            }
+            BuiltinSqlTypes::DoublePrecision(ref value) => {
+                BuiltinSqlTypes::format_float(f, *value, value)
+            }
+            BuiltinSqlTypes::Numeric(ref value) => {
+                write!(f, "{}", value.to_plain_string())
+            }
             BuiltinSqlTypes::Uuid(ref value) => {
                 write!(f, "{}", value)
             }
             BuiltinSqlTypes::Text(ref value) => {
                 write!(f, "{}", value)
             }
+            BuiltinSqlTypes::Date(ref value) => {
+                write!(f, "{}", DateTime::format_date(*value))
+            }
+            BuiltinSqlTypes::Time(ref value) => {
+                write!(f, "{}", DateTime::format_time(*value))
+            }
+            BuiltinSqlTypes::Timestamp(ref value) => {
+                write!(f, "{}", DateTime::format_timestamp(*value, None))
+            }
+            BuiltinSqlTypes::TimestampTz(ref value) => {
+                write!(
+                    f,
+                    "{}",
+                    DateTime::format_timestamp(*value, Some(&TimeZone::default()))
+                )
+            }
+            BuiltinSqlTypes::Interval(ref value) => {
+                write!(f, "{}", value)
+            }
+            BuiltinSqlTypes::Bytea(ref value) => {
+                write!(f, "{}", Bytea::format(value, ByteaOutput::Hex))
+            }
+            BuiltinSqlTypes::Json(ref value) => {
+                write!(f, "{}", value)
+            }
+            BuiltinSqlTypes::Jsonb(ref value) => {
+                write!(f, "{}", value)
+            }
+            BuiltinSqlTypes::Array(ref value) => {
+                write!(f, "{}", value)
+            }
+            BuiltinSqlTypes::Enum(_, ref label) => {
+                write!(f, "{}", label)
+            }
+            BuiltinSqlTypes::Composite(ref values) => {
+                write!(f, "{}", CompositeType::format(values, &|v| v.to_string()))
+            }
         }
     }
 }
 
+//Uses the Postgres names so the output can be fed back into from_str
 impl fmt::Display for DeserializeTypes {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             DeserializeTypes::Bool => {
-                write!(f, "Bool")
+                write!(f, "boolean")
+            }
+            DeserializeTypes::SmallInt => {
+                write!(f, "smallint")
             }
             DeserializeTypes::Integer => {
-                write!(f, "Integer")
+                write!(f, "integer")
+            }
+            DeserializeTypes::BigInt => {
+                write!(f, "bigint")
+            }
+            DeserializeTypes::Real => {
+                write!(f, "real")
+            }
+            DeserializeTypes::DoublePrecision => {
+                write!(f, "double precision")
+            }
+            DeserializeTypes::Numeric(None) => {
+                write!(f, "numeric")
+            }
+            DeserializeTypes::Numeric(Some(t)) => {
+                write!(f, "numeric({},{})", t.precision, t.scale)
             }
             DeserializeTypes::Uuid => {
-                write!(f, "Uuid")
+                write!(f, "uuid")
             }
             DeserializeTypes::Text => {
-                write!(f, "Text")
+                write!(f, "text")
+            }
+            DeserializeTypes::Date => {
+                write!(f, "date")
+            }
+            DeserializeTypes::Time => {
+                write!(f, "time without time zone")
+            }
+            DeserializeTypes::Timestamp => {
+                write!(f, "timestamp without time zone")
+            }
+            DeserializeTypes::TimestampTz => {
+                write!(f, "timestamp with time zone")
+            }
+            DeserializeTypes::Interval => {
+                write!(f, "interval")
+            }
+            DeserializeTypes::Bytea => {
+                write!(f, "bytea")
+            }
+            DeserializeTypes::VarChar(None) => {
+                write!(f, "character varying")
+            }
+            DeserializeTypes::VarChar(Some(l)) => {
+                write!(f, "character varying({})", l)
+            }
+            DeserializeTypes::Char(None) => {
+                write!(f, "bpchar")
+            }
+            DeserializeTypes::Char(Some(l)) => {
+                write!(f, "character({})", l)
+            }
+            DeserializeTypes::Json => {
+                write!(f, "json")
+            }
+            DeserializeTypes::Jsonb => {
+                write!(f, "jsonb")
+            }
+            DeserializeTypes::Array(element_type) => {
+                write!(f, "{}[]", element_type)
+            }
+            DeserializeTypes::Enum(t) => {
+                write!(f, "{}", t.name)
+            }
+            DeserializeTypes::Composite(t) => {
+                write!(f, "{}", t.name)
             }
         }
     }
@@ -241,14 +1259,53 @@ pub enum SqlTypeError {
     InvalidStringLength(usize, usize),
     #[error(transparent)]
     InvalidUtf8(#[from] std::string::FromUtf8Error),
-    #[error(transparent)]
-    InvalidBool(#[from] ParseBoolError),
-    #[error(transparent)]
-    InvalidInt(#[from] ParseIntError),
+    #[error("invalid input syntax for type {0}: \"{1}\"")]
+    InvalidInput(DeserializeTypes, String),
+    #[error("value \"{0}\" is out of range for type {1}")]
+    OutOfRange(String, DeserializeTypes),
+    #[error("numeric field overflow, a field with precision {0}, scale {1} must round to an absolute value less than 10^{}", .0 - .1)]
+    NumericFieldOverflow(u32, u32),
     #[error(transparent)]
     InvalidUuid(#[from] uuid::Error),
     #[error("Invalid type {0}")]
     InvalidType(String),
+    #[error("Invalid type modifier {0}")]
+    InvalidTypeModifier(String),
+    #[error("value too long for type {0}")]
+    ValueTooLong(DeserializeTypes),
+    #[error("malformed array literal: \"{0}\"")]
+    MalformedArray(String),
+    #[error("Unknown jsonb tag {0}")]
+    InvalidJsonbTag(u8),
+    #[error("multidimensional arrays must have array expressions with matching dimensions")]
+    ArrayDimensionMismatch(),
+    #[error("number of array dimensions ({0}) exceeds the maximum allowed (6)")]
+    ArrayTooManyDimensions(usize),
+    #[error("invalid input value for enum {0}: \"{1}\"")]
+    InvalidEnumInput(String, String),
+    #[error("enum {0} has no label at position {1}")]
+    InvalidEnumPosition(String, u32),
+    #[error("malformed record literal: \"{0}\"")]
+    MalformedRecord(String),
+}
+
+impl SqlTypeError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            SqlTypeError::InvalidInput(_, _)
+            | SqlTypeError::InvalidUuid(_)
+            | SqlTypeError::MalformedArray(_)
+            | SqlTypeError::InvalidEnumInput(_, _)
+            | SqlTypeError::MalformedRecord(_) => PgErrorCodes::InvalidTextRepresentation,
+            SqlTypeError::OutOfRange(_, _) | SqlTypeError::NumericFieldOverflow(_, _) => {
+                PgErrorCodes::NumericValueOutOfRange
+            }
+            SqlTypeError::ValueTooLong(_) => PgErrorCodes::StringDataRightTruncation,
+            SqlTypeError::ArrayDimensionMismatch() => PgErrorCodes::ArraySubscriptError,
+            SqlTypeError::ArrayTooManyDimensions(_) => PgErrorCodes::ProgramLimitExceeded,
+            _ => PgErrorCodes::SystemError,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -275,6 +1332,13 @@ mod tests {
         assert_eq!(output, test);
     }
 
+    #[test]
+    fn test_empty_roundtrip() {
+        let output = roundtrip("".to_string());
+
+        assert_eq!(output, "");
+    }
+
     #[test]
     fn test_long_roundtrip() {
         let test = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Donec vel porta enim. Sed interdum egestas velit et porttitor. Vestibulum sollicitudin mi enim, in fringilla lectus tincidunt quis. Morbi eget.";
@@ -283,29 +1347,379 @@ mod tests {
         assert_eq!(output, test);
     }
 
+    #[test]
+    fn test_numeric_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        for test in ["0", "-1.50", "123456789012345678901234567890.0001", "1e-20"] {
+            let value = BuiltinSqlTypes::Numeric(BigDecimal::from_str(test)?);
+            let parsed =
+                BuiltinSqlTypes::deserialize(DeserializeTypes::Numeric(None), value.serialize())?;
+            assert_eq!(value, parsed);
+            assert_eq!(value.to_string(), parsed.to_string());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_width_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let values = vec![
+            BuiltinSqlTypes::SmallInt(i16::MIN),
+            BuiltinSqlTypes::Integer(-1),
+            BuiltinSqlTypes::BigInt(i64::MAX),
+            BuiltinSqlTypes::Real(-1.5),
+            BuiltinSqlTypes::DoublePrecision(std::f64::consts::PI),
+        ];
+        for (value, sql_type) in values.into_iter().zip(BuiltinSqlTypes::VALUES[1..].iter()) {
+            let parsed = BuiltinSqlTypes::deserialize(sql_type.clone(), value.serialize())?;
+            assert_eq!(value, parsed);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_date_time_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let values = vec![
+            BuiltinSqlTypes::Date(-1),
+            BuiltinSqlTypes::Time(86_399_999_999),
+            BuiltinSqlTypes::Timestamp(i64::MIN),
+            BuiltinSqlTypes::TimestampTz(1_000_000),
+            BuiltinSqlTypes::Interval(Interval::new(-14, 3, 4_000_000)),
+        ];
+        for (value, sql_type) in values.into_iter().zip(BuiltinSqlTypes::VALUES[9..].iter()) {
+            let serialized = value.serialize();
+            assert_eq!(serialized.len(), sql_type.type_length() as usize);
+            assert_eq!(
+                BuiltinSqlTypes::deserialize(sql_type.clone(), serialized)?,
+                value
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_date_time() -> Result<(), Box<dyn std::error::Error>> {
+        for (sql_type, input) in [
+            (DeserializeTypes::Date, "2021-03-04"),
+            (DeserializeTypes::Time, "13:14:15.5"),
+            (DeserializeTypes::Timestamp, "2021-03-04 13:14:15"),
+            (DeserializeTypes::TimestampTz, "2021-03-04 13:14:15+00"),
+            (DeserializeTypes::Interval, "1 year 2 mons 3 days 04:05:06"),
+        ] {
+            assert_eq!(
+                BuiltinSqlTypes::parse(sql_type, input.to_string())?.to_string(),
+                input
+            );
+        }
+
+        let berlin = TimeZone::from_str("Europe/Berlin")?;
+        let value = BuiltinSqlTypes::parse_in_zone(
+            DeserializeTypes::TimestampTz,
+            "2021-03-04 13:14:15".to_string(),
+            &berlin,
+        )?;
+        assert_eq!(value.to_string(), "2021-03-04 12:14:15+00");
+        assert_eq!(
+            value.to_string_with(&berlin, ByteaOutput::Hex),
+            "2021-03-04 13:14:15+01"
+        );
+        assert!(matches!(
+            BuiltinSqlTypes::parse(DeserializeTypes::Date, "2021-13-01".to_string()),
+            Err(SqlTypeError::InvalidInput(DeserializeTypes::Date, _))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytea_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let value = BuiltinSqlTypes::parse(DeserializeTypes::Bytea, "\\x00ff".to_string())?;
+        assert_eq!(value, BuiltinSqlTypes::Bytea(vec![0x00, 0xff]));
+        assert_eq!(
+            BuiltinSqlTypes::deserialize(DeserializeTypes::Bytea, value.serialize())?,
+            value
+        );
+        assert_eq!(value.to_string(), "\\x00ff");
+        assert_eq!(
+            value.to_string_with(&TimeZone::default(), ByteaOutput::Escape),
+            "\\000\\377"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let text = r#"{"b": 1,  "a": [true, null]}"#;
+        let json = BuiltinSqlTypes::parse(DeserializeTypes::Json, text.to_string())?;
+        assert_eq!(json.to_string(), text);
+        assert_eq!(
+            BuiltinSqlTypes::deserialize(DeserializeTypes::Json, json.serialize())?,
+            json
+        );
+
+        let jsonb = BuiltinSqlTypes::parse(DeserializeTypes::Jsonb, text.to_string())?;
+        assert_eq!(jsonb.to_string(), r#"{"a": [true, null], "b": 1}"#);
+        assert_eq!(
+            BuiltinSqlTypes::deserialize(DeserializeTypes::Jsonb, jsonb.serialize())?,
+            jsonb
+        );
+
+        let err = BuiltinSqlTypes::parse(DeserializeTypes::Jsonb, "{".to_string()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid input syntax for type jsonb: \"{\""
+        );
+        assert_eq!(
+            err.pg_error_code().value(),
+            PgErrorCodes::InvalidTextRepresentation.value()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_typmods() -> Result<(), Box<dyn std::error::Error>> {
+        let varchar = DeserializeTypes::from_str("varchar(3)")?;
+        let char = DeserializeTypes::from_str("character(3)")?;
+        let parse =
+            |t: &DeserializeTypes, s: &str| BuiltinSqlTypes::parse(t.clone(), s.to_string());
+
+        assert_eq!(
+            parse(&varchar, "ab")?,
+            BuiltinSqlTypes::Text("ab".to_string())
+        );
+        assert_eq!(
+            parse(&char, "ab")?,
+            BuiltinSqlTypes::Text("ab ".to_string())
+        );
+        //Trailing spaces are quietly dropped, anything else is too long
+        assert_eq!(
+            parse(&varchar, "abc   ")?,
+            BuiltinSqlTypes::Text("abc".to_string())
+        );
+        assert!(matches!(
+            parse(&varchar, "abcd"),
+            Err(SqlTypeError::ValueTooLong(_))
+        ));
+        assert!(matches!(
+            parse(&char, "äöüß"),
+            Err(SqlTypeError::ValueTooLong(_))
+        ));
+        assert_eq!(
+            parse(&char, "äöü")?,
+            BuiltinSqlTypes::Text("äöü".to_string())
+        );
+        assert_eq!(
+            BuiltinSqlTypes::apply_length_typmod("abcd".to_string(), varchar.clone(), true)?,
+            "abc"
+        );
+        assert_eq!(
+            BuiltinSqlTypes::deserialize(
+                char.clone(),
+                BuiltinSqlTypes::Text("ab ".to_string()).serialize()
+            )?,
+            BuiltinSqlTypes::Text("ab ".to_string())
+        );
+
+        assert_eq!(
+            DeserializeTypes::from_str("char")?,
+            DeserializeTypes::Char(Some(1))
+        );
+        assert_eq!(
+            DeserializeTypes::from_str("character varying")?,
+            DeserializeTypes::VarChar(None)
+        );
+        assert!(DeserializeTypes::from_str("varchar(0)").is_err());
+        assert!(DeserializeTypes::from_str("varchar(1,2)").is_err());
+        assert_eq!(varchar.type_modifier(), 7);
+        for t in [varchar, char, DeserializeTypes::from_str("numeric(5,2)")?] {
+            assert_eq!(t.without_typmod().with_typmod(t.type_modifier())?, t);
+            assert_eq!(DeserializeTypes::from_str(&t.to_string())?, t);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_integers() {
+        assert_eq!(
+            BuiltinSqlTypes::parse(DeserializeTypes::SmallInt, " -32768 ".to_string()).unwrap(),
+            BuiltinSqlTypes::SmallInt(-32768)
+        );
+        assert!(matches!(
+            BuiltinSqlTypes::parse(DeserializeTypes::SmallInt, "32768".to_string()),
+            Err(SqlTypeError::OutOfRange(_, DeserializeTypes::SmallInt))
+        ));
+        assert_eq!(
+            BuiltinSqlTypes::parse(DeserializeTypes::Integer, "-1".to_string()).unwrap(),
+            BuiltinSqlTypes::Integer(-1)
+        );
+        assert!(matches!(
+            BuiltinSqlTypes::parse(DeserializeTypes::Integer, "2147483648".to_string()),
+            Err(SqlTypeError::OutOfRange(_, DeserializeTypes::Integer))
+        ));
+        assert!(matches!(
+            BuiltinSqlTypes::parse(DeserializeTypes::BigInt, "12a".to_string()),
+            Err(SqlTypeError::InvalidInput(DeserializeTypes::BigInt, _))
+        ));
+    }
+
+    #[test]
+    fn test_parse_floats() {
+        assert_eq!(
+            BuiltinSqlTypes::parse(DeserializeTypes::Real, "1.5".to_string()).unwrap(),
+            BuiltinSqlTypes::Real(1.5)
+        );
+        assert_eq!(
+            BuiltinSqlTypes::parse(DeserializeTypes::DoublePrecision, "-Infinity".to_string())
+                .unwrap(),
+            BuiltinSqlTypes::DoublePrecision(f64::NEG_INFINITY)
+        );
+        assert!(matches!(
+            BuiltinSqlTypes::parse(DeserializeTypes::Real, "1e39".to_string()),
+            Err(SqlTypeError::OutOfRange(_, DeserializeTypes::Real))
+        ));
+        assert!(matches!(
+            BuiltinSqlTypes::parse(DeserializeTypes::DoublePrecision, "1e-400".to_string()),
+            Err(SqlTypeError::OutOfRange(
+                _,
+                DeserializeTypes::DoublePrecision
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_parse_numeric() -> Result<(), Box<dyn std::error::Error>> {
+        let typmod = DeserializeTypes::from_str("numeric(5,2)")?;
+        assert_eq!(
+            BuiltinSqlTypes::parse(typmod.clone(), "123.455".to_string())?.to_string(),
+            "123.46"
+        );
+        assert_eq!(
+            BuiltinSqlTypes::parse(typmod.clone(), "-1".to_string())?.to_string(),
+            "-1.00"
+        );
+        assert!(matches!(
+            BuiltinSqlTypes::parse(typmod, "999.999".to_string()),
+            Err(SqlTypeError::NumericFieldOverflow(5, 2))
+        ));
+        assert_eq!(
+            BuiltinSqlTypes::parse(DeserializeTypes::Numeric(None), "1e3".to_string())?.to_string(),
+            "1000"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_bool() -> Result<(), Box<dyn std::error::Error>> {
+        for t in ["t", "TRUE", "yes", "on", "1", " y "] {
+            assert_eq!(
+                BuiltinSqlTypes::parse(DeserializeTypes::Bool, t.to_string())?,
+                BuiltinSqlTypes::Bool(true)
+            );
+        }
+        for f in ["f", "false", "no", "off", "0"] {
+            assert_eq!(
+                BuiltinSqlTypes::parse(DeserializeTypes::Bool, f.to_string())?,
+                BuiltinSqlTypes::Bool(false)
+            );
+        }
+        assert!(BuiltinSqlTypes::parse(DeserializeTypes::Bool, "o".to_string()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_output() {
+        assert_eq!(BuiltinSqlTypes::Real(1000000.0).to_string(), "1000000");
+        assert_eq!(BuiltinSqlTypes::Real(123456.0).to_string(), "123456");
+        assert_eq!(BuiltinSqlTypes::Real(0.1).to_string(), "0.1");
+        assert_eq!(BuiltinSqlTypes::Real(1.0 / 3.0).to_string(), "0.33333334");
+        assert_eq!(
+            BuiltinSqlTypes::DoublePrecision(1234567.0).to_string(),
+            "1234567"
+        );
+        assert_eq!(
+            BuiltinSqlTypes::DoublePrecision(0.1 + 0.2).to_string(),
+            "0.30000000000000004"
+        );
+        assert_eq!(
+            BuiltinSqlTypes::DoublePrecision(0.00001234).to_string(),
+            "0.00001234"
+        );
+        assert_eq!(
+            BuiltinSqlTypes::DoublePrecision(f64::NAN).to_string(),
+            "NaN"
+        );
+        assert_eq!(BuiltinSqlTypes::Real(f32::INFINITY).to_string(), "Infinity");
+        assert_eq!(
+            BuiltinSqlTypes::DoublePrecision(f64::NEG_INFINITY).to_string(),
+            "-Infinity"
+        );
+
+        //Every value reads back as itself
+        for v in [1e15, 1.0 / 3.0, 123.456e-20, f64::MAX, f64::MIN_POSITIVE] {
+            let output = BuiltinSqlTypes::DoublePrecision(v).to_string();
+            assert_eq!(output.parse::<f64>(), Ok(v));
+        }
+    }
+
+    #[test]
+    fn test_type_names() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(DeserializeTypes::from_str("bool")?, DeserializeTypes::Bool);
+        assert_eq!(
+            DeserializeTypes::from_str("int8")?,
+            DeserializeTypes::BigInt
+        );
+        assert_eq!(
+            DeserializeTypes::from_str("Double  Precision")?,
+            DeserializeTypes::DoublePrecision
+        );
+        assert_eq!(
+            DeserializeTypes::from_str("decimal(10)")?,
+            DeserializeTypes::Numeric(Some(NumericTypmod {
+                precision: 10,
+                scale: 0
+            }))
+        );
+        assert!(DeserializeTypes::from_str("numeric(2,3)").is_err());
+        assert!(DeserializeTypes::from_str("integer(3)").is_err());
+        assert_eq!(
+            DeserializeTypes::from_str("timestamptz")?,
+            DeserializeTypes::TimestampTz
+        );
+
+        for t in BuiltinSqlTypes::VALUES.iter() {
+            assert_eq!(DeserializeTypes::from_str(&t.to_string())?, *t);
+        }
+        Ok(())
+    }
+
     #[test]
     //Used to map if we have the types linked up right
     pub fn test_type_matches() {
-        assert!(BuiltinSqlTypes::Bool(true).type_matches(DeserializeTypes::Bool));
-        assert!(!BuiltinSqlTypes::Bool(true).type_matches(DeserializeTypes::Integer));
-        assert!(!BuiltinSqlTypes::Bool(true).type_matches(DeserializeTypes::Uuid));
-        assert!(!BuiltinSqlTypes::Bool(true).type_matches(DeserializeTypes::Text));
+        assert!(BuiltinSqlTypes::Bool(true).type_matches(&DeserializeTypes::Bool));
+        assert!(!BuiltinSqlTypes::Bool(true).type_matches(&DeserializeTypes::Integer));
+        assert!(!BuiltinSqlTypes::Bool(true).type_matches(&DeserializeTypes::Uuid));
+        assert!(!BuiltinSqlTypes::Bool(true).type_matches(&DeserializeTypes::Text));
+
+        assert!(!BuiltinSqlTypes::Integer(0).type_matches(&DeserializeTypes::Bool));
+        assert!(BuiltinSqlTypes::Integer(0).type_matches(&DeserializeTypes::Integer));
+        assert!(!BuiltinSqlTypes::Integer(0).type_matches(&DeserializeTypes::BigInt));
+        assert!(!BuiltinSqlTypes::Integer(0).type_matches(&DeserializeTypes::Uuid));
+        assert!(!BuiltinSqlTypes::Integer(0).type_matches(&DeserializeTypes::Text));
 
-        assert!(!BuiltinSqlTypes::Integer(0).type_matches(DeserializeTypes::Bool));
-        assert!(BuiltinSqlTypes::Integer(0).type_matches(DeserializeTypes::Integer));
-        assert!(!BuiltinSqlTypes::Integer(0).type_matches(DeserializeTypes::Uuid));
-        assert!(!BuiltinSqlTypes::Integer(0).type_matches(DeserializeTypes::Text));
+        assert!(BuiltinSqlTypes::Numeric(BigDecimal::from(1)).type_matches(
+            &DeserializeTypes::Numeric(Some(NumericTypmod {
+                precision: 1,
+                scale: 0
+            }))
+        ));
 
-        assert!(!BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(DeserializeTypes::Bool));
+        assert!(!BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(&DeserializeTypes::Bool));
         assert!(
-            !BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(DeserializeTypes::Integer)
+            !BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(&DeserializeTypes::Integer)
         );
-        assert!(BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(DeserializeTypes::Uuid));
-        assert!(!BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(DeserializeTypes::Text));
+        assert!(BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(&DeserializeTypes::Uuid));
+        assert!(!BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()).type_matches(&DeserializeTypes::Text));
 
-        assert!(!BuiltinSqlTypes::Text("foo".to_string()).type_matches(DeserializeTypes::Bool));
-        assert!(!BuiltinSqlTypes::Text("foo".to_string()).type_matches(DeserializeTypes::Integer));
-        assert!(!BuiltinSqlTypes::Text("foo".to_string()).type_matches(DeserializeTypes::Uuid));
-        assert!(BuiltinSqlTypes::Text("foo".to_string()).type_matches(DeserializeTypes::Text));
+        assert!(!BuiltinSqlTypes::Text("foo".to_string()).type_matches(&DeserializeTypes::Bool));
+        assert!(!BuiltinSqlTypes::Text("foo".to_string()).type_matches(&DeserializeTypes::Integer));
+        assert!(!BuiltinSqlTypes::Text("foo".to_string()).type_matches(&DeserializeTypes::Uuid));
+        assert!(BuiltinSqlTypes::Text("foo".to_string()).type_matches(&DeserializeTypes::Text));
     }
 }
@@ -0,0 +1,162 @@
+//! Text input and output for bytea values.
+//!
+//! Input accepts both of postgres' formats, hex such as \x0a0b and the older escape format
+//! where backslashes introduce octal byte values. Output follows the bytea_output setting.
+use super::{DeserializeTypes, SqlTypeError};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub struct Bytea {}
+
+impl Bytea {
+    pub fn parse(s: &str) -> Result<Vec<u8>, SqlTypeError> {
+        match s.strip_prefix("\\x").or_else(|| s.strip_prefix("\\X")) {
+            Some(hex) => Bytea::parse_hex(s, hex),
+            None => Bytea::parse_escape(s),
+        }
+    }
+
+    //Whitespace is allowed between pairs of digits but not inside one
+    fn parse_hex(s: &str, hex: &str) -> Result<Vec<u8>, SqlTypeError> {
+        let invalid = || SqlTypeError::InvalidInput(DeserializeTypes::Bytea, s.to_string());
+        let mut result = Vec::with_capacity(hex.len() / 2);
+        let mut chars = hex.chars();
+        while let Some(high) = chars.next() {
+            if high.is_ascii_whitespace() {
+                continue;
+            }
+            let low = chars.next().ok_or_else(invalid)?;
+            let (high, low) = high
+                .to_digit(16)
+                .zip(low.to_digit(16))
+                .ok_or_else(invalid)?;
+            result.push((high * 16 + low) as u8);
+        }
+        Ok(result)
+    }
+
+    fn parse_escape(s: &str) -> Result<Vec<u8>, SqlTypeError> {
+        let invalid = || SqlTypeError::InvalidInput(DeserializeTypes::Bytea, s.to_string());
+        let bytes = s.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != b'\\' {
+                result.push(bytes[i]);
+                i += 1;
+            } else if bytes.get(i + 1) == Some(&b'\\') {
+                result.push(b'\\');
+                i += 2;
+            } else {
+                //Three octal digits, the first no more than 3 so it fits in a byte
+                let octal = bytes.get(i + 1..i + 4).ok_or_else(invalid)?;
+                if !(b'0'..=b'3').contains(&octal[0])
+                    || !octal[1..].iter().all(|b| (b'0'..=b'7').contains(b))
+                {
+                    return Err(invalid());
+                }
+                result.push(octal.iter().fold(0, |acc, b| acc * 8 + (b - b'0')));
+                i += 4;
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn format(bytes: &[u8], output: ByteaOutput) -> String {
+        match output {
+            ByteaOutput::Hex => {
+                let mut result = String::with_capacity(2 + bytes.len() * 2);
+                result.push_str("\\x");
+                for b in bytes {
+                    result.push_str(&format!("{:02x}", b));
+                }
+                result
+            }
+            ByteaOutput::Escape => {
+                let mut result = String::with_capacity(bytes.len());
+                for b in bytes {
+                    match b {
+                        b'\\' => result.push_str("\\\\"),
+                        0x20..=0x7e => result.push(char::from(*b)),
+                        _ => result.push_str(&format!("\\{:03o}", b)),
+                    }
+                }
+                result
+            }
+        }
+    }
+}
+
+/// How bytea values are written out, the bytea_output setting
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ByteaOutput {
+    #[default]
+    Hex,
+    Escape,
+}
+
+impl FromStr for ByteaOutput {
+    type Err = ByteaOutputError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hex" => Ok(ByteaOutput::Hex),
+            "escape" => Ok(ByteaOutput::Escape),
+            _ => Err(ByteaOutputError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ByteaOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteaOutput::Hex => write!(f, "hex"),
+            ByteaOutput::Escape => write!(f, "escape"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ByteaOutputError {
+    #[error("unknown bytea output format \"{0}\"")]
+    UnknownFormat(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(Bytea::parse("\\x00ff10")?, vec![0x00, 0xff, 0x10]);
+        assert_eq!(
+            Bytea::parse("\\xDE AD be ef")?,
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert_eq!(Bytea::parse("\\x")?, Vec::<u8>::new());
+        assert_eq!(
+            Bytea::parse("ab\\\\c\\001\\377")?,
+            b"ab\\c\x01\xff".to_vec()
+        );
+        assert!(Bytea::parse("\\x0").is_err());
+        assert!(Bytea::parse("\\x0 0").is_err());
+        assert!(Bytea::parse("\\xzz").is_err());
+        assert!(Bytea::parse("\\9").is_err());
+        assert!(Bytea::parse("\\400").is_err());
+        assert!(Bytea::parse("abc\\").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format() {
+        let bytes = b"a\\b\x00\xff".to_vec();
+        assert_eq!(Bytea::format(&bytes, ByteaOutput::Hex), "\\x615c6200ff");
+        assert_eq!(
+            Bytea::format(&bytes, ByteaOutput::Escape),
+            "a\\\\b\\000\\377"
+        );
+        for output in [ByteaOutput::Hex, ByteaOutput::Escape] {
+            assert_eq!(Bytea::parse(&Bytea::format(&bytes, output)).unwrap(), bytes);
+        }
+    }
+}
@@ -0,0 +1,589 @@
+//! Conversions for the date and time types. Like postgres they are stored as offsets from
+//! 2000-01-01, dates as days and the rest as microseconds, with the min and max values
+//! reserved for -infinity and infinity.
+//!
+//! Text follows postgres' ISO date style: "2021-03-04", "13:14:15.5", "2021-03-04 13:14:15"
+//! and "2021-03-04 13:14:15+05:30" for values with a time zone.
+use super::builtin_sql_types::{DeserializeTypes, SqlTypeError};
+use super::interval::{parse_seconds, Interval, USECS_PER_DAY, USECS_PER_HOUR, USECS_PER_SEC};
+use super::time_zone::TimeZone;
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+pub const DATE_NOBEGIN: i32 = i32::MIN;
+pub const DATE_NOEND: i32 = i32::MAX;
+pub const TIMESTAMP_NOBEGIN: i64 = i64::MIN;
+pub const TIMESTAMP_NOEND: i64 = i64::MAX;
+
+//Microseconds between the unix and postgres epochs
+const UNIX_EPOCH_OFFSET: i64 = 946_684_800 * USECS_PER_SEC;
+
+pub struct DateTime {}
+
+impl DateTime {
+    fn epoch() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2000, 1, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .unwrap_or(NaiveDateTime::MIN)
+    }
+
+    pub fn date_to_naive(date: i32) -> Option<NaiveDate> {
+        DateTime::epoch()
+            .date()
+            .checked_add_signed(Duration::try_days(i64::from(date))?)
+    }
+
+    pub fn naive_to_date(date: NaiveDate) -> Option<i32> {
+        i32::try_from(
+            date.signed_duration_since(DateTime::epoch().date())
+                .num_days(),
+        )
+        .ok()
+    }
+
+    pub fn timestamp_to_naive(timestamp: i64) -> Option<NaiveDateTime> {
+        DateTime::epoch().checked_add_signed(Duration::microseconds(timestamp))
+    }
+
+    pub fn naive_to_timestamp(timestamp: NaiveDateTime) -> Option<i64> {
+        timestamp
+            .signed_duration_since(DateTime::epoch())
+            .num_microseconds()
+    }
+
+    /// A timestamp with time zone for a moment on the system clock
+    pub fn from_system_time(time: SystemTime) -> i64 {
+        let unix = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(d) => i64::try_from(d.as_micros()).unwrap_or(TIMESTAMP_NOEND),
+            Err(e) => -i64::try_from(e.duration().as_micros()).unwrap_or(TIMESTAMP_NOEND),
+        };
+        unix - UNIX_EPOCH_OFFSET
+    }
+
+    pub fn is_infinite(timestamp: i64) -> bool {
+        timestamp == TIMESTAMP_NOBEGIN || timestamp == TIMESTAMP_NOEND
+    }
+
+    pub fn is_infinite_date(date: i32) -> bool {
+        date == DATE_NOBEGIN || date == DATE_NOEND
+    }
+
+    pub fn parse_date(s: &str) -> Result<i32, SqlTypeError> {
+        let sql_type = DeserializeTypes::Date;
+        match s.trim().to_lowercase().as_str() {
+            "infinity" | "+infinity" => return Ok(DATE_NOEND),
+            "-infinity" => return Ok(DATE_NOBEGIN),
+            "epoch" => return Ok(-10957),
+            _ => {}
+        }
+
+        //A timestamp is fine too, the time is dropped
+        let parts = DateTime::split_date_time(s, sql_type.clone())?;
+        let date = DateTime::parse_ymd(parts.date, parts.bc)
+            .ok_or_else(|| SqlTypeError::InvalidInput(sql_type.clone(), s.to_string()))?;
+        DateTime::naive_to_date(date)
+            .ok_or_else(|| SqlTypeError::OutOfRange(s.to_string(), sql_type.clone()))
+    }
+
+    pub fn parse_time(s: &str) -> Result<i64, SqlTypeError> {
+        let sql_type = DeserializeTypes::Time;
+        let invalid = || SqlTypeError::InvalidInput(sql_type.clone(), s.to_string());
+
+        //Time only inputs have no date, so find the clock wherever it is
+        let lower = s.trim().to_lowercase();
+        let mut clock = None;
+        let mut meridiem = None;
+        for token in lower.split_whitespace() {
+            if token == "am" || token == "pm" {
+                meridiem = Some(token);
+            } else if token.contains(':') {
+                let token = token.rsplit('t').next().unwrap_or(token);
+                clock = Some(DateTime::strip_zone(token).0);
+            }
+        }
+        let clock = clock.ok_or_else(invalid)?;
+        DateTime::parse_clock(clock, meridiem).ok_or_else(invalid)
+    }
+
+    /// Parses a timestamp, if a zone is given the result is a timestamp with time zone in UTC.
+    /// An explicit offset or zone in the text wins over the session zone, a timestamp without
+    /// time zone ignores it like postgres does.
+    pub fn parse_timestamp(s: &str, zone: Option<&TimeZone>) -> Result<i64, SqlTypeError> {
+        let sql_type = match zone {
+            Some(_) => DeserializeTypes::TimestampTz,
+            None => DeserializeTypes::Timestamp,
+        };
+        let invalid = || SqlTypeError::InvalidInput(sql_type.clone(), s.to_string());
+
+        match s.trim().to_lowercase().as_str() {
+            "infinity" | "+infinity" => return Ok(TIMESTAMP_NOEND),
+            "-infinity" => return Ok(TIMESTAMP_NOBEGIN),
+            "epoch" => return Ok(-UNIX_EPOCH_OFFSET),
+            _ => {}
+        }
+
+        let parts = DateTime::split_date_time(s, sql_type.clone())?;
+        let date = DateTime::parse_ymd(parts.date, parts.bc).ok_or_else(invalid)?;
+        let clock = match parts.clock {
+            Some(c) => DateTime::parse_clock(c, parts.meridiem).ok_or_else(invalid)?,
+            None => 0,
+        };
+
+        let out_of_range = || SqlTypeError::OutOfRange(s.to_string(), sql_type.clone());
+        let local = date
+            .and_hms_opt(0, 0, 0)
+            .and_then(DateTime::naive_to_timestamp)
+            .and_then(|t| t.checked_add(clock))
+            .ok_or_else(out_of_range)?;
+        let local_naive = DateTime::timestamp_to_naive(local).ok_or_else(out_of_range)?;
+
+        let session = match zone {
+            Some(z) => z,
+            None => return Ok(local),
+        };
+        let offset = match parts.zone {
+            Some(z) => match TimeZone::from_str(z) {
+                Ok(tz) => tz.local_offset(local_naive),
+                Err(_) => return Err(invalid()),
+            },
+            None => session.local_offset(local_naive),
+        };
+        local
+            .checked_sub(i64::from(offset) * USECS_PER_SEC)
+            .ok_or_else(out_of_range)
+    }
+
+    //Breaks "2021-03-04 12:00:00+05 BC" and friends into their pieces
+    fn split_date_time(
+        s: &str,
+        sql_type: DeserializeTypes,
+    ) -> Result<DateTimeParts<'_>, SqlTypeError> {
+        let invalid = || SqlTypeError::InvalidInput(sql_type.clone(), s.to_string());
+
+        let mut parts = DateTimeParts {
+            date: "",
+            clock: None,
+            zone: None,
+            meridiem: None,
+            bc: false,
+        };
+        let mut tokens = s.split_whitespace();
+
+        //ISO 8601 puts a T between the date and time
+        let first = tokens.next().ok_or_else(invalid)?;
+        match first.find(['T', 't']) {
+            Some(i) => {
+                parts.date = &first[..i];
+                let (clock, zone) = DateTime::strip_zone(&first[i + 1..]);
+                parts.clock = Some(clock);
+                parts.zone = zone;
+            }
+            None => parts.date = first,
+        }
+
+        for token in tokens {
+            if token.eq_ignore_ascii_case("bc") {
+                parts.bc = true;
+            } else if token.eq_ignore_ascii_case("ad") {
+                //The default era, nothing to do
+                continue;
+            } else if token.eq_ignore_ascii_case("am") || token.eq_ignore_ascii_case("pm") {
+                parts.meridiem = Some(if token.eq_ignore_ascii_case("am") {
+                    "am"
+                } else {
+                    "pm"
+                });
+            } else if parts.clock.is_none() && token.contains(':') {
+                let (clock, zone) = DateTime::strip_zone(token);
+                parts.clock = Some(clock);
+                parts.zone = zone;
+            } else if parts.zone.is_none() {
+                parts.zone = Some(token);
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(parts)
+    }
+
+    //Splits an attached zone off a clock such as 12:00:00+05:30 or 12:00Z
+    fn strip_zone(token: &str) -> (&str, Option<&str>) {
+        match token.find(['+', '-', 'z', 'Z']) {
+            Some(i) if i > 0 => (&token[..i], Some(&token[i..])),
+            _ => (token, None),
+        }
+    }
+
+    fn parse_ymd(date: &str, bc: bool) -> Option<NaiveDate> {
+        let mut fields = date.split('-');
+        let year: i32 = fields.next()?.parse().ok()?;
+        let month: u32 = fields.next()?.parse().ok()?;
+        let day: u32 = fields.next()?.parse().ok()?;
+        if fields.next().is_some() || year < 1 {
+            return None;
+        }
+
+        //There is no year zero, 1 BC comes right before 1 AD
+        let year = if bc { 1 - year } else { year };
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    //Parses hh:mm[:ss[.ffffff]] into microseconds after midnight, 24:00:00 is allowed
+    fn parse_clock(clock: &str, meridiem: Option<&str>) -> Option<i64> {
+        let fields: Vec<&str> = clock.split(':').collect();
+        if fields.len() < 2 || fields.len() > 3 {
+            return None;
+        }
+        let mut hours: i64 = fields[0].parse().ok()?;
+        let minutes: i64 = fields[1].parse().ok()?;
+        let seconds = match fields.get(2) {
+            Some(s) => parse_seconds(s)?,
+            None => 0,
+        };
+
+        match meridiem {
+            Some(_) if !(1..=12).contains(&hours) => return None,
+            Some("am") if hours == 12 => hours = 0,
+            Some("pm") if hours != 12 => hours += 12,
+            _ => {}
+        }
+
+        //Seconds may hit 60 for a leap second, postgres rolls it into the next minute
+        let micros = hours * USECS_PER_HOUR + minutes * 60 * USECS_PER_SEC + seconds;
+        if !(0..=24).contains(&hours)
+            || !(0..60).contains(&minutes)
+            || seconds > 60 * USECS_PER_SEC
+            || micros > USECS_PER_DAY
+        {
+            return None;
+        }
+        Some(micros)
+    }
+
+    pub fn format_date(date: i32) -> String {
+        match date {
+            DATE_NOBEGIN => "-infinity".to_string(),
+            DATE_NOEND => "infinity".to_string(),
+            _ => match DateTime::date_to_naive(date) {
+                Some(d) => {
+                    let (ymd, bc) = DateTime::format_ymd(d);
+                    format!("{}{}", ymd, bc)
+                }
+                None => "invalid".to_string(),
+            },
+        }
+    }
+
+    pub fn format_time(time: i64) -> String {
+        let seconds = time / USECS_PER_SEC;
+        let mut output = format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        );
+        let fraction = time % USECS_PER_SEC;
+        if fraction != 0 {
+            output.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+        }
+        output
+    }
+
+    /// Formats a timestamp, given a zone the value is treated as UTC and shown in that zone
+    /// followed by the offset
+    pub fn format_timestamp(timestamp: i64, zone: Option<&TimeZone>) -> String {
+        match timestamp {
+            TIMESTAMP_NOBEGIN => return "-infinity".to_string(),
+            TIMESTAMP_NOEND => return "infinity".to_string(),
+            _ => {}
+        }
+
+        let (local, offset) = match zone {
+            Some(z) => {
+                let offset = DateTime::timestamp_to_naive(timestamp)
+                    .map(|n| z.utc_offset(n))
+                    .unwrap_or(0);
+                (
+                    timestamp + i64::from(offset) * USECS_PER_SEC,
+                    TimeZone::format_offset(offset),
+                )
+            }
+            None => (timestamp, String::new()),
+        };
+
+        match DateTime::timestamp_to_naive(local) {
+            Some(n) => {
+                let (ymd, bc) = DateTime::format_ymd(n.date());
+                format!(
+                    "{} {}{}{}",
+                    ymd,
+                    DateTime::format_time(local.rem_euclid(USECS_PER_DAY)),
+                    offset,
+                    bc
+                )
+            }
+            None => "invalid".to_string(),
+        }
+    }
+
+    fn format_ymd(date: NaiveDate) -> (String, &'static str) {
+        let (year, bc) = if date.year() <= 0 {
+            (1 - date.year(), " BC")
+        } else {
+            (date.year(), "")
+        };
+        (
+            format!("{:04}-{:02}-{:02}", year, date.month(), date.day()),
+            bc,
+        )
+    }
+
+    pub fn date_to_timestamp(date: i32) -> Option<i64> {
+        match date {
+            DATE_NOBEGIN => Some(TIMESTAMP_NOBEGIN),
+            DATE_NOEND => Some(TIMESTAMP_NOEND),
+            _ => i64::from(date).checked_mul(USECS_PER_DAY),
+        }
+    }
+
+    pub fn timestamp_to_date(timestamp: i64) -> Option<i32> {
+        match timestamp {
+            TIMESTAMP_NOBEGIN => Some(DATE_NOBEGIN),
+            TIMESTAMP_NOEND => Some(DATE_NOEND),
+            _ => i32::try_from(timestamp.div_euclid(USECS_PER_DAY)).ok(),
+        }
+    }
+
+    /// Moves a UTC timestamp to the wall clock time in the zone
+    pub fn utc_to_local(timestamp: i64, zone: &TimeZone) -> Option<i64> {
+        if DateTime::is_infinite(timestamp) {
+            return Some(timestamp);
+        }
+        let offset = zone.utc_offset(DateTime::timestamp_to_naive(timestamp)?);
+        timestamp.checked_add(i64::from(offset) * USECS_PER_SEC)
+    }
+
+    /// Moves a wall clock time in the zone to UTC
+    pub fn local_to_utc(timestamp: i64, zone: &TimeZone) -> Option<i64> {
+        if DateTime::is_infinite(timestamp) {
+            return Some(timestamp);
+        }
+        let offset = zone.local_offset(DateTime::timestamp_to_naive(timestamp)?);
+        timestamp.checked_sub(i64::from(offset) * USECS_PER_SEC)
+    }
+
+    /// Adds an interval to a timestamp, months keep the day of the month when it exists
+    /// otherwise the end of the month is used
+    pub fn add_interval(timestamp: i64, interval: &Interval) -> Option<i64> {
+        if DateTime::is_infinite(timestamp) {
+            return Some(timestamp);
+        }
+        let with_days = DateTime::add_months_and_days(timestamp, interval)?;
+        let result = with_days.checked_add(interval.microseconds)?;
+        DateTime::check_timestamp(result)
+    }
+
+    /// Adds an interval to a timestamp with time zone, months and days are added to the wall
+    /// clock time so one day later is the same time of day even across daylight savings
+    pub fn add_interval_in_zone(
+        timestamp: i64,
+        interval: &Interval,
+        zone: &TimeZone,
+    ) -> Option<i64> {
+        if DateTime::is_infinite(timestamp) {
+            return Some(timestamp);
+        }
+        let mut result = timestamp;
+        if interval.months != 0 || interval.days != 0 {
+            let local = DateTime::utc_to_local(timestamp, zone)?;
+            result = DateTime::local_to_utc(DateTime::add_months_and_days(local, interval)?, zone)?;
+        }
+        DateTime::check_timestamp(result.checked_add(interval.microseconds)?)
+    }
+
+    fn add_months_and_days(timestamp: i64, interval: &Interval) -> Option<i64> {
+        let mut naive = DateTime::timestamp_to_naive(timestamp)?;
+        if interval.months > 0 {
+            naive = naive.checked_add_months(Months::new(interval.months.unsigned_abs()))?;
+        } else if interval.months < 0 {
+            naive = naive.checked_sub_months(Months::new(interval.months.unsigned_abs()))?;
+        }
+        naive = naive.checked_add_signed(Duration::try_days(i64::from(interval.days))?)?;
+        DateTime::naive_to_timestamp(naive)
+    }
+
+    //Finite timestamps must stay inside the range that can be displayed
+    fn check_timestamp(timestamp: i64) -> Option<i64> {
+        if DateTime::is_infinite(timestamp) {
+            return None;
+        }
+        DateTime::timestamp_to_naive(timestamp).map(|_| timestamp)
+    }
+
+    /// The difference between two timestamps as days and time, infinite ones have no difference
+    pub fn subtract_timestamps(left: i64, right: i64) -> Option<Interval> {
+        if DateTime::is_infinite(left) || DateTime::is_infinite(right) {
+            return None;
+        }
+        Interval::from_microseconds(left.checked_sub(right)?)
+    }
+
+    pub fn timestamp_to_time(timestamp: i64) -> Option<i64> {
+        if DateTime::is_infinite(timestamp) {
+            return None;
+        }
+        Some(timestamp.rem_euclid(USECS_PER_DAY))
+    }
+
+    /// Only the time part of the interval matters, the result wraps around midnight
+    pub fn add_interval_to_time(time: i64, interval: &Interval) -> i64 {
+        (time + interval.microseconds % USECS_PER_DAY).rem_euclid(USECS_PER_DAY)
+    }
+
+    pub fn check_date(date: i32) -> Option<i32> {
+        if date == DATE_NOBEGIN || date == DATE_NOEND {
+            return None;
+        }
+        DateTime::date_to_naive(date).map(|_| date)
+    }
+}
+
+struct DateTimeParts<'a> {
+    date: &'a str,
+    clock: Option<&'a str>,
+    zone: Option<&'a str>,
+    meridiem: Option<&'static str>,
+    bc: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Tz;
+
+    fn timestamp(s: &str) -> i64 {
+        DateTime::parse_timestamp(s, None).unwrap()
+    }
+
+    #[test]
+    fn test_dates() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(DateTime::parse_date("2000-01-01")?, 0);
+        assert_eq!(DateTime::parse_date("1999-12-31")?, -1);
+        assert_eq!(DateTime::parse_date("2000-03-01 12:00")?, 60);
+        assert_eq!(
+            DateTime::parse_date("epoch")?,
+            DateTime::parse_date("1970-01-01")?
+        );
+        assert_eq!(DateTime::parse_date("-infinity")?, DATE_NOBEGIN);
+        assert!(DateTime::parse_date("2021-02-29").is_err());
+        assert!(DateTime::parse_date("0000-01-01").is_err());
+
+        for d in ["2021-03-04", "0044-03-15 BC", "infinity", "12345-01-01"] {
+            assert_eq!(DateTime::format_date(DateTime::parse_date(d)?), d);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_times() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(DateTime::parse_time("01:02")?, 3_720_000_000);
+        assert_eq!(DateTime::parse_time("24:00:00")?, USECS_PER_DAY);
+        assert_eq!(DateTime::parse_time("12:00 am")?, 0);
+        assert_eq!(
+            DateTime::parse_time("1:30 pm")?,
+            13 * USECS_PER_HOUR + 1_800_000_000
+        );
+        assert_eq!(
+            DateTime::parse_time("2021-01-01 00:00:01+05")?,
+            USECS_PER_SEC
+        );
+        assert_eq!(DateTime::parse_time("00:00:00.1234567")?, 123_457);
+        assert!(DateTime::parse_time("24:00:01").is_err());
+        assert!(DateTime::parse_time("12:60").is_err());
+        assert!(DateTime::parse_time("noon").is_err());
+
+        for t in ["00:00:00", "13:14:15.5", "23:59:59.999999"] {
+            assert_eq!(DateTime::format_time(DateTime::parse_time(t)?), t);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(timestamp("2000-01-01 00:00:00"), 0);
+        assert_eq!(timestamp("2000-01-01T00:00:01.5"), 1_500_000);
+        assert_eq!(timestamp("2000-01-02"), USECS_PER_DAY);
+        //A zone on a timestamp without time zone is ignored
+        assert_eq!(timestamp("2000-01-01 00:00:00+05"), 0);
+        assert_eq!(timestamp("epoch"), -UNIX_EPOCH_OFFSET);
+        assert!(DateTime::parse_timestamp("2000-01-01 25:00", None).is_err());
+        assert!(DateTime::parse_timestamp("2000-01-01 00:00 extra stuff", None).is_err());
+
+        for t in [
+            "2021-03-04 13:14:15",
+            "2021-03-04 13:14:15.000001",
+            "0001-01-01 00:00:00 BC",
+            "-infinity",
+        ] {
+            assert_eq!(DateTime::format_timestamp(timestamp(t), None), t);
+        }
+    }
+
+    #[test]
+    fn test_timestamps_with_zones() -> Result<(), Box<dyn std::error::Error>> {
+        let utc = TimeZone::default();
+        let ny = TimeZone::Named(Tz::America__New_York);
+        let parse = |s: &str, z: &TimeZone| DateTime::parse_timestamp(s, Some(z));
+
+        assert_eq!(parse("2000-01-01 00:00:00", &utc)?, 0);
+        assert_eq!(parse("2000-01-01 00:00:00+05:30", &utc)?, -19_800_000_000);
+        assert_eq!(parse("2000-01-01T05:00:00Z", &ny)?, 5 * USECS_PER_HOUR);
+        assert_eq!(parse("2000-01-01 00:00:00", &ny)?, 5 * USECS_PER_HOUR);
+        assert_eq!(
+            parse("2000-07-01 00:00:00 America/New_York", &utc)?,
+            parse("2000-07-01 04:00:00", &utc)?
+        );
+        assert!(parse("2000-01-01 00:00:00 Nowhere/Special", &utc).is_err());
+
+        let value = parse("2021-07-04 12:00:00", &ny)?;
+        assert_eq!(
+            DateTime::format_timestamp(value, Some(&ny)),
+            "2021-07-04 12:00:00-04"
+        );
+        assert_eq!(
+            DateTime::format_timestamp(value, Some(&utc)),
+            "2021-07-04 16:00:00+00"
+        );
+        assert_eq!(
+            DateTime::format_timestamp(value, Some(&TimeZone::Fixed(19800))),
+            "2021-07-04 21:30:00+05:30"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interval_math() -> Result<(), Box<dyn std::error::Error>> {
+        let month = Interval::new(1, 0, 0);
+        assert_eq!(
+            DateTime::add_interval(timestamp("2021-01-31 10:00"), &month),
+            Some(timestamp("2021-02-28 10:00"))
+        );
+        assert_eq!(
+            DateTime::add_interval(timestamp("2021-03-31"), &Interval::new(-1, 0, 0)),
+            Some(timestamp("2021-02-28"))
+        );
+        assert_eq!(
+            DateTime::add_interval(TIMESTAMP_NOEND, &month),
+            Some(TIMESTAMP_NOEND)
+        );
+
+        //A day across the spring change is 23 hours long in New York
+        let ny = TimeZone::Named(Tz::America__New_York);
+        let before = DateTime::parse_timestamp("2021-03-13 12:00", Some(&ny))?;
+        let after = DateTime::add_interval_in_zone(before, &Interval::new(0, 1, 0), &ny);
+        assert_eq!(after, Some(before + 23 * USECS_PER_HOUR));
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+//! A span of time, kept the same way postgres does as separate months, days and microseconds
+//! since the length of a month or a day depends on when it is applied.
+//!
+//! Text input and output follows postgres' default "postgres" interval style, for example
+//! "1 year 2 mons 3 days 04:05:06.5".
+use super::builtin_sql_types::{DeserializeTypes, SqlTypeError};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+pub const USECS_PER_SEC: i64 = 1_000_000;
+pub const USECS_PER_HOUR: i64 = 3600 * USECS_PER_SEC;
+pub const USECS_PER_DAY: i64 = 24 * USECS_PER_HOUR;
+//Postgres treats a month as 30 days whenever it has to compare or split one
+const DAYS_PER_MONTH: i64 = 30;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub microseconds: i64,
+}
+
+impl Interval {
+    pub fn new(months: i32, days: i32, microseconds: i64) -> Interval {
+        Interval {
+            months,
+            days,
+            microseconds,
+        }
+    }
+
+    /// Splits microseconds into whole days and the remainder, the way a timestamp difference is
+    /// reported
+    pub fn from_microseconds(microseconds: i64) -> Option<Interval> {
+        Some(Interval::new(
+            0,
+            i32::try_from(microseconds / USECS_PER_DAY).ok()?,
+            microseconds % USECS_PER_DAY,
+        ))
+    }
+
+    /// A single number used to order intervals, months count as 30 days and days as 24 hours
+    pub fn cmp_value(&self) -> i128 {
+        (i128::from(self.months) * i128::from(DAYS_PER_MONTH) + i128::from(self.days))
+            * i128::from(USECS_PER_DAY)
+            + i128::from(self.microseconds)
+    }
+
+    pub fn checked_add(&self, other: &Interval) -> Option<Interval> {
+        Some(Interval::new(
+            self.months.checked_add(other.months)?,
+            self.days.checked_add(other.days)?,
+            self.microseconds.checked_add(other.microseconds)?,
+        ))
+    }
+
+    pub fn checked_sub(&self, other: &Interval) -> Option<Interval> {
+        self.checked_add(&other.checked_neg()?)
+    }
+
+    pub fn checked_neg(&self) -> Option<Interval> {
+        Some(Interval::new(
+            self.months.checked_neg()?,
+            self.days.checked_neg()?,
+            self.microseconds.checked_neg()?,
+        ))
+    }
+
+    /// Scales each field, fractional months spill into days and fractional days into time
+    pub fn checked_mul(&self, factor: f64) -> Option<Interval> {
+        Interval::from_parts(
+            f64::from(self.months) * factor,
+            f64::from(self.days) * factor,
+            self.microseconds as f64 * factor,
+        )
+    }
+
+    pub fn checked_div(&self, divisor: f64) -> Option<Interval> {
+        Interval::from_parts(
+            f64::from(self.months) / divisor,
+            f64::from(self.days) / divisor,
+            self.microseconds as f64 / divisor,
+        )
+    }
+
+    fn from_parts(months: f64, days: f64, microseconds: f64) -> Option<Interval> {
+        let whole_months = months.trunc();
+        let days = days + (months - whole_months) * DAYS_PER_MONTH as f64;
+        let whole_days = days.trunc();
+        let microseconds =
+            (microseconds + (days - whole_days) * USECS_PER_DAY as f64).round_ties_even();
+
+        if !microseconds.is_finite()
+            || microseconds < i64::MIN as f64
+            || microseconds >= i64::MAX as f64
+        {
+            return None;
+        }
+        Some(Interval::new(
+            Interval::f64_to_i32(whole_months)?,
+            Interval::f64_to_i32(whole_days)?,
+            microseconds as i64,
+        ))
+    }
+
+    fn f64_to_i32(value: f64) -> Option<i32> {
+        if value.is_finite() && value >= f64::from(i32::MIN) && value <= f64::from(i32::MAX) {
+            Some(value as i32)
+        } else {
+            None
+        }
+    }
+
+    fn invalid(s: &str) -> SqlTypeError {
+        SqlTypeError::InvalidInput(DeserializeTypes::Interval, s.to_string())
+    }
+
+    //Returns the unit as months, days or microseconds per one of it
+    fn parse_unit(unit: &str) -> Option<IntervalUnit> {
+        let unit = match unit {
+            "microsecond" | "microseconds" | "usec" | "usecs" | "us" => {
+                IntervalUnit::Microseconds(1)
+            }
+            "millisecond" | "milliseconds" | "msec" | "msecs" | "ms" => {
+                IntervalUnit::Microseconds(1000)
+            }
+            "second" | "seconds" | "sec" | "secs" | "s" => {
+                IntervalUnit::Microseconds(USECS_PER_SEC)
+            }
+            "minute" | "minutes" | "min" | "mins" | "m" => {
+                IntervalUnit::Microseconds(60 * USECS_PER_SEC)
+            }
+            "hour" | "hours" | "hr" | "hrs" | "h" => IntervalUnit::Microseconds(USECS_PER_HOUR),
+            "day" | "days" | "d" => IntervalUnit::Days(1),
+            "week" | "weeks" | "w" => IntervalUnit::Days(7),
+            "month" | "months" | "mon" | "mons" => IntervalUnit::Months(1),
+            "year" | "years" | "yr" | "yrs" | "y" => IntervalUnit::Months(12),
+            "decade" | "decades" => IntervalUnit::Months(120),
+            "century" | "centuries" => IntervalUnit::Months(1200),
+            "millennium" | "millennia" | "millenniums" => IntervalUnit::Months(12000),
+            _ => return None,
+        };
+        Some(unit)
+    }
+
+    //Parses [-]hh:mm[:ss[.ffffff]] into microseconds
+    fn parse_clock(clock: &str) -> Option<i64> {
+        let (sign, clock) = match clock.strip_prefix('-') {
+            Some(c) => (-1, c),
+            None => (1, clock.strip_prefix('+').unwrap_or(clock)),
+        };
+        let parts: Vec<&str> = clock.split(':').collect();
+        if parts.len() < 2 || parts.len() > 3 {
+            return None;
+        }
+
+        let hours: i64 = parts[0].parse().ok()?;
+        let minutes: i64 = parts[1].parse().ok()?;
+        let seconds = match parts.get(2) {
+            Some(s) => parse_seconds(s)?,
+            None => 0,
+        };
+        if minutes >= 60 || seconds >= 60 * USECS_PER_SEC {
+            return None;
+        }
+
+        hours
+            .checked_mul(USECS_PER_HOUR)?
+            .checked_add(minutes * 60 * USECS_PER_SEC + seconds)?
+            .checked_mul(sign)
+    }
+}
+
+enum IntervalUnit {
+    Months(i64),
+    Days(i64),
+    Microseconds(i64),
+}
+
+/// Parses seconds with an optional fraction into microseconds, rounding any digits past the
+/// sixth
+pub(super) fn parse_seconds(s: &str) -> Option<i64> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut micros: i64 = 0;
+    for (i, b) in fraction.bytes().take(7).enumerate() {
+        let digit = i64::from(b - b'0');
+        if i < 6 {
+            micros = micros * 10 + digit;
+        } else if digit >= 5 {
+            micros += 1;
+        }
+    }
+    for _ in fraction.len()..6 {
+        micros *= 10;
+    }
+
+    whole
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(USECS_PER_SEC)?
+        .checked_add(micros)
+}
+
+impl FromStr for Interval {
+    type Err = SqlTypeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let mut tokens: Vec<&str> = lower.split_whitespace().collect();
+        if tokens.first() == Some(&"@") {
+            tokens.remove(0);
+        }
+        let ago = tokens.last() == Some(&"ago");
+        if ago {
+            tokens.pop();
+        }
+        if tokens.is_empty() {
+            return Err(Interval::invalid(s));
+        }
+
+        let mut months: f64 = 0.0;
+        let mut days: f64 = 0.0;
+        let mut microseconds: i64 = 0;
+
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = tokens[i];
+            i += 1;
+
+            if token.contains(':') {
+                let clock = Interval::parse_clock(token).ok_or_else(|| Interval::invalid(s))?;
+                microseconds = microseconds
+                    .checked_add(clock)
+                    .ok_or_else(|| Interval::invalid(s))?;
+                continue;
+            }
+
+            //The unit may be glued to the number, as in 10min
+            let split = token
+                .find(|c: char| c.is_ascii_alphabetic())
+                .unwrap_or(token.len());
+            let (number, mut unit) = token.split_at(split);
+            if unit.is_empty() {
+                if let Some(next) = tokens.get(i) {
+                    if Interval::parse_unit(next).is_some() {
+                        unit = next;
+                        i += 1;
+                    }
+                }
+            }
+
+            let value: f64 = number.parse().map_err(|_| Interval::invalid(s))?;
+            if !value.is_finite() {
+                return Err(Interval::invalid(s));
+            }
+
+            match Interval::parse_unit(unit) {
+                Some(IntervalUnit::Months(m)) => months += value * m as f64,
+                Some(IntervalUnit::Days(d)) => days += value * d as f64,
+                Some(IntervalUnit::Microseconds(u)) => {
+                    //Keep whole numbers exact, only the fraction goes through floating point
+                    let whole = value.trunc();
+                    let part = (whole as i64)
+                        .checked_mul(u)
+                        .and_then(|w| w.checked_add(((value - whole) * u as f64).round() as i64))
+                        .ok_or_else(|| Interval::invalid(s))?;
+                    microseconds = microseconds
+                        .checked_add(part)
+                        .ok_or_else(|| Interval::invalid(s))?;
+                }
+                None if unit.is_empty() && i == tokens.len() => {
+                    //A bare number on the end is seconds
+                    microseconds = microseconds
+                        .checked_add((value * USECS_PER_SEC as f64).round() as i64)
+                        .ok_or_else(|| Interval::invalid(s))?;
+                }
+                None => return Err(Interval::invalid(s)),
+            }
+        }
+
+        let whole_months = months.trunc();
+        days += (months - whole_months) * DAYS_PER_MONTH as f64;
+        let whole_days = days.trunc();
+        let day_micros = ((days - whole_days) * USECS_PER_DAY as f64).round() as i64;
+
+        let out_of_range = || SqlTypeError::OutOfRange(s.to_string(), DeserializeTypes::Interval);
+        let interval = Interval::new(
+            Interval::f64_to_i32(whole_months).ok_or_else(out_of_range)?,
+            Interval::f64_to_i32(whole_days).ok_or_else(out_of_range)?,
+            microseconds
+                .checked_add(day_micros)
+                .ok_or_else(out_of_range)?,
+        );
+
+        if ago {
+            interval.checked_neg().ok_or_else(out_of_range)
+        } else {
+            Ok(interval)
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        //Once a field is negative the following positive ones get an explicit plus
+        let mut is_before = false;
+
+        for (value, unit) in [
+            (i64::from(self.months / 12), "year"),
+            (i64::from(self.months % 12), "mon"),
+            (i64::from(self.days), "day"),
+        ] {
+            if value != 0 {
+                let sign = if is_before && value > 0 { "+" } else { "" };
+                let plural = if value != 1 { "s" } else { "" };
+                parts.push(format!("{}{} {}{}", sign, value, unit, plural));
+                is_before = value < 0;
+            }
+        }
+
+        if self.microseconds != 0 || parts.is_empty() {
+            let sign = if self.microseconds < 0 {
+                "-"
+            } else if is_before {
+                "+"
+            } else {
+                ""
+            };
+            let abs = self.microseconds.unsigned_abs();
+            let usecs_per_sec = USECS_PER_SEC.unsigned_abs();
+            let seconds = abs / usecs_per_sec;
+            let mut clock = format!(
+                "{}{:02}:{:02}:{:02}",
+                sign,
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            );
+            let fraction = abs % usecs_per_sec;
+            if fraction != 0 {
+                clock.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+            }
+            parts.push(clock);
+        }
+
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Interval {
+        Interval::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("1 day"), Interval::new(0, 1, 0));
+        assert_eq!(
+            parse("1 year 2 mons 3 days 04:05:06.789"),
+            Interval::new(14, 3, 14_706_789_000)
+        );
+        assert_eq!(
+            parse("2 hours 30 minutes"),
+            Interval::new(0, 0, 9000 * USECS_PER_SEC)
+        );
+        assert_eq!(parse("10min"), Interval::new(0, 0, 600 * USECS_PER_SEC));
+        assert_eq!(parse("1.5 days"), Interval::new(0, 1, 12 * USECS_PER_HOUR));
+        assert_eq!(parse("1.5 months"), Interval::new(1, 15, 0));
+        assert_eq!(parse("@ 1 week ago"), Interval::new(0, -7, 0));
+        assert_eq!(parse("-01:00"), Interval::new(0, 0, -USECS_PER_HOUR));
+        assert_eq!(parse("90"), Interval::new(0, 0, 90 * USECS_PER_SEC));
+        assert!(Interval::from_str("1 fortnight").is_err());
+        assert!(Interval::from_str("").is_err());
+        assert!(Interval::from_str("1 day 00:61").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        for (input, expected) in [
+            ("0", "00:00:00"),
+            ("1 day", "1 day"),
+            (
+                "1 year 2 mons 3 days 04:05:06.789",
+                "1 year 2 mons 3 days 04:05:06.789",
+            ),
+            ("-1 mon 2 days", "-1 mons +2 days"),
+            ("1 day -01:00", "1 day -01:00:00"),
+            ("-1 days 01:00", "-1 days +01:00:00"),
+            ("36 hours", "36:00:00"),
+            ("0.5 seconds", "00:00:00.5"),
+        ] {
+            assert_eq!(parse(input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(
+            parse("1 day").checked_mul(1.5),
+            Some(Interval::new(0, 1, 12 * USECS_PER_HOUR))
+        );
+        assert_eq!(
+            parse("1 mon").checked_div(2.0),
+            Some(Interval::new(0, 15, 0))
+        );
+        assert_eq!(
+            parse("1 day").checked_sub(&parse("1 hour")),
+            Some(Interval::new(0, 1, -USECS_PER_HOUR))
+        );
+        assert_eq!(parse("1 mon").cmp_value(), parse("30 days").cmp_value());
+        assert!(parse("1 day").cmp_value() > parse("23 hours").cmp_value());
+    }
+}
@@ -0,0 +1,724 @@
+//! Values of the json and jsonb types.
+//!
+//! A json value is kept as the text it was given, a jsonb value is parsed once into a JsonValue
+//! and follows postgres' rules: the last of any duplicate keys wins and object keys are kept
+//! sorted shortest first then bytewise. That order is what lets two equal jsonb values compare
+//! equal no matter how they were written.
+//!
+//! On disk jsonb is a tree of tagged values so reading it back never has to parse text:
+//! * Null, false and true are a lone tag byte
+//! * A number is its tag, the scale and the length prefixed two's complement digits
+//! * A string is its tag and the length prefixed utf8 bytes
+//! * An array is its tag, the element count and the elements
+//! * An object is its tag, the pair count and then each length prefixed key and its value
+//!
+//! Lengths and counts are little endian u32s.
+use super::{DeserializeTypes, SqlTypeError};
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use bytes::{Buf, BufMut, BytesMut};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+//Deeper documents are refused instead of risking the stack
+const MAX_DEPTH: usize = 1000;
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_ARRAY: u8 = 5;
+const TAG_OBJECT: u8 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(BigDecimal),
+    String(String),
+    Array(Vec<JsonValue>),
+    ///Pairs in the order they were written, jsonb values have them sorted and unique
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Parses json text exactly as written, duplicate keys and all. Errors name the target
+    /// type since json and jsonb share the parser.
+    pub fn parse(s: &str, target: DeserializeTypes) -> Result<JsonValue, SqlTypeError> {
+        let mut parser = JsonParser {
+            input: s.as_bytes(),
+            position: 0,
+        };
+        let value = parser.parse_document();
+        match value {
+            Some(v) => Ok(v),
+            None => Err(SqlTypeError::InvalidInput(target, s.to_string())),
+        }
+    }
+
+    /// Parses json text into a jsonb value
+    pub fn parse_jsonb(s: &str) -> Result<JsonValue, SqlTypeError> {
+        Ok(JsonValue::parse(s, DeserializeTypes::Jsonb)?.into_jsonb())
+    }
+
+    /// Applies the jsonb rules for objects all the way down
+    pub fn into_jsonb(self) -> JsonValue {
+        match self {
+            JsonValue::Array(a) => {
+                JsonValue::Array(a.into_iter().map(JsonValue::into_jsonb).collect())
+            }
+            JsonValue::Object(o) => {
+                let mut pairs: Vec<(String, JsonValue)> = vec![];
+                for (k, v) in o {
+                    let v = v.into_jsonb();
+                    match pairs.iter_mut().find(|(existing, _)| *existing == k) {
+                        Some(pair) => pair.1 = v,
+                        None => pairs.push((k, v)),
+                    }
+                }
+                pairs.sort_by(|(l, _), (r, _)| JsonValue::key_cmp(l, r));
+                JsonValue::Object(pairs)
+            }
+            _ => self,
+        }
+    }
+
+    fn key_cmp(left: &str, right: &str) -> Ordering {
+        left.len()
+            .cmp(&right.len())
+            .then_with(|| left.as_bytes().cmp(right.as_bytes()))
+    }
+
+    pub fn serialize(&self, buff: &mut BytesMut) {
+        match self {
+            JsonValue::Null => buff.put_u8(TAG_NULL),
+            JsonValue::Bool(false) => buff.put_u8(TAG_FALSE),
+            JsonValue::Bool(true) => buff.put_u8(TAG_TRUE),
+            JsonValue::Number(n) => {
+                buff.put_u8(TAG_NUMBER);
+                let (digits, scale) = n.as_bigint_and_exponent();
+                buff.put_i64_le(scale);
+                JsonValue::serialize_bytes(buff, &digits.to_signed_bytes_le());
+            }
+            JsonValue::String(s) => {
+                buff.put_u8(TAG_STRING);
+                JsonValue::serialize_bytes(buff, s.as_bytes());
+            }
+            JsonValue::Array(a) => {
+                buff.put_u8(TAG_ARRAY);
+                buff.put_u32_le(JsonValue::serialize_count(a.len()));
+                for v in a {
+                    v.serialize(buff);
+                }
+            }
+            JsonValue::Object(o) => {
+                buff.put_u8(TAG_OBJECT);
+                buff.put_u32_le(JsonValue::serialize_count(o.len()));
+                for (k, v) in o {
+                    JsonValue::serialize_bytes(buff, k.as_bytes());
+                    v.serialize(buff);
+                }
+            }
+        }
+    }
+
+    //Rows are limited to a page so nothing gets near u32::MAX
+    fn serialize_count(count: usize) -> u32 {
+        u32::try_from(count).unwrap_or(u32::MAX)
+    }
+
+    fn serialize_bytes(buff: &mut BytesMut, bytes: &[u8]) {
+        buff.put_u32_le(JsonValue::serialize_count(bytes.len()));
+        buff.extend_from_slice(bytes);
+    }
+
+    pub fn deserialize(buffer: &mut impl Buf) -> Result<JsonValue, SqlTypeError> {
+        JsonValue::deserialize_depth(buffer, 0)
+    }
+
+    fn deserialize_depth(buffer: &mut impl Buf, depth: usize) -> Result<JsonValue, SqlTypeError> {
+        if depth > MAX_DEPTH {
+            return Err(SqlTypeError::BufferTooShort());
+        }
+        if !buffer.has_remaining() {
+            return Err(SqlTypeError::EmptyBuffer());
+        }
+        match buffer.get_u8() {
+            TAG_NULL => Ok(JsonValue::Null),
+            TAG_FALSE => Ok(JsonValue::Bool(false)),
+            TAG_TRUE => Ok(JsonValue::Bool(true)),
+            TAG_NUMBER => {
+                if buffer.remaining() < 8 {
+                    return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+                }
+                let scale = buffer.get_i64_le();
+                let digits = JsonValue::deserialize_bytes(buffer)?;
+                Ok(JsonValue::Number(BigDecimal::new(
+                    BigInt::from_signed_bytes_le(&digits),
+                    scale,
+                )))
+            }
+            TAG_STRING => Ok(JsonValue::String(String::from_utf8(
+                JsonValue::deserialize_bytes(buffer)?,
+            )?)),
+            TAG_ARRAY => {
+                let count = JsonValue::deserialize_count(buffer)?;
+                let mut array = vec![];
+                for _ in 0..count {
+                    array.push(JsonValue::deserialize_depth(buffer, depth + 1)?);
+                }
+                Ok(JsonValue::Array(array))
+            }
+            TAG_OBJECT => {
+                let count = JsonValue::deserialize_count(buffer)?;
+                let mut object = vec![];
+                for _ in 0..count {
+                    let key = String::from_utf8(JsonValue::deserialize_bytes(buffer)?)?;
+                    object.push((key, JsonValue::deserialize_depth(buffer, depth + 1)?));
+                }
+                Ok(JsonValue::Object(object))
+            }
+            tag => Err(SqlTypeError::InvalidJsonbTag(tag)),
+        }
+    }
+
+    fn deserialize_count(buffer: &mut impl Buf) -> Result<usize, SqlTypeError> {
+        if buffer.remaining() < 4 {
+            return Err(SqlTypeError::LengthTooShort(buffer.remaining()));
+        }
+        Ok(buffer.get_u32_le() as usize)
+    }
+
+    fn deserialize_bytes(buffer: &mut impl Buf) -> Result<Vec<u8>, SqlTypeError> {
+        let length = JsonValue::deserialize_count(buffer)?;
+        if length > buffer.remaining() {
+            return Err(SqlTypeError::InvalidStringLength(
+                length,
+                buffer.remaining(),
+            ));
+        }
+        Ok(buffer.copy_to_bytes(length).to_vec())
+    }
+
+    /// The value of a key in an object
+    pub fn get_key(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            //Like postgres the last duplicate key of a json object wins
+            JsonValue::Object(o) => o.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// An element of an array, negative indexes count back from the end
+    pub fn get_index(&self, index: i64) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Array(a) => {
+                let index = if index < 0 {
+                    i64::try_from(a.len()).ok()? + index
+                } else {
+                    index
+                };
+                a.get(usize::try_from(index).ok()?)
+            }
+            _ => None,
+        }
+    }
+
+    /// Follows a path of keys and array indexes, as used by #> and #>>
+    pub fn get_path(&self, path: &[String]) -> Option<&JsonValue> {
+        let mut current = self;
+        for step in path {
+            current = match current {
+                JsonValue::Object(_) => current.get_key(step)?,
+                JsonValue::Array(_) => current.get_index(step.trim().parse().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Whether a key is in an object or a string is an element of an array, the ? operator
+    pub fn has_key(&self, key: &str) -> bool {
+        match self {
+            JsonValue::Object(o) => o.iter().any(|(k, _)| k == key),
+            JsonValue::Array(a) => a
+                .iter()
+                .any(|v| matches!(v, JsonValue::String(s) if s == key)),
+            JsonValue::String(s) => s == key,
+            _ => false,
+        }
+    }
+
+    /// The @> operator. Objects contain objects whose pairs they also contain, arrays contain
+    /// arrays whose elements they each contain somewhere and a top level array also contains
+    /// a bare scalar it holds.
+    pub fn contains(&self, other: &JsonValue) -> bool {
+        match (self, other) {
+            (JsonValue::Array(a), other) if !other.is_container() => a.contains(other),
+            _ => self.contains_nested(other),
+        }
+    }
+
+    fn contains_nested(&self, other: &JsonValue) -> bool {
+        match (self, other) {
+            (JsonValue::Object(_), JsonValue::Object(o)) => o.iter().all(|(k, v)| {
+                self.get_key(k)
+                    .map(|mine| mine.contains_nested(v))
+                    .unwrap_or(false)
+            }),
+            (JsonValue::Array(a), JsonValue::Array(o)) => o
+                .iter()
+                .all(|v| a.iter().any(|mine| mine.contains_nested(v))),
+            (l, r) => !l.is_container() && l == r,
+        }
+    }
+
+    fn is_container(&self) -> bool {
+        matches!(self, JsonValue::Array(_) | JsonValue::Object(_))
+    }
+
+    /// The name given by json_typeof
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "boolean",
+            JsonValue::Number(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "array",
+            JsonValue::Object(_) => "object",
+        }
+    }
+
+    /// The text the ->> and #>> operators give, strings lose their quotes and a json null is
+    /// a sql null
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            JsonValue::Null => None,
+            JsonValue::String(s) => Some(s.clone()),
+            _ => Some(self.to_string()),
+        }
+    }
+
+    /// Postgres' btree order for jsonb: objects sort above arrays, then booleans, numbers,
+    /// strings and null. Bigger containers sort above smaller ones before their contents are
+    /// compared.
+    pub fn compare(&self, other: &JsonValue) -> Ordering {
+        let rank = |v: &JsonValue| match v {
+            JsonValue::Null => 0,
+            JsonValue::String(_) => 1,
+            JsonValue::Number(_) => 2,
+            JsonValue::Bool(_) => 3,
+            JsonValue::Array(_) => 4,
+            JsonValue::Object(_) => 5,
+        };
+        match (self, other) {
+            (JsonValue::Bool(l), JsonValue::Bool(r)) => l.cmp(r),
+            (JsonValue::Number(l), JsonValue::Number(r)) => l.cmp(r),
+            (JsonValue::String(l), JsonValue::String(r)) => l.cmp(r),
+            (JsonValue::Array(l), JsonValue::Array(r)) => l.len().cmp(&r.len()).then_with(|| {
+                l.iter()
+                    .zip(r)
+                    .map(|(l, r)| l.compare(r))
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            }),
+            (JsonValue::Object(l), JsonValue::Object(r)) => l.len().cmp(&r.len()).then_with(|| {
+                l.iter()
+                    .zip(r)
+                    .map(|((lk, lv), (rk, rv))| {
+                        JsonValue::key_cmp(lk, rk).then_with(|| lv.compare(rv))
+                    })
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            }),
+            (l, r) => rank(l).cmp(&rank(r)),
+        }
+    }
+
+    /// Writes a string with json's escapes, the same ones postgres uses
+    pub fn quote(s: &str) -> String {
+        let mut result = String::with_capacity(s.len() + 2);
+        result.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => result.push_str("\\\""),
+                '\\' => result.push_str("\\\\"),
+                '\u{8}' => result.push_str("\\b"),
+                '\u{c}' => result.push_str("\\f"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+                c => result.push(c),
+            }
+        }
+        result.push('"');
+        result
+    }
+}
+
+//Same layout as postgres' jsonb output, a space after every comma and colon
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(true) => write!(f, "true"),
+            JsonValue::Bool(false) => write!(f, "false"),
+            JsonValue::Number(n) => write!(f, "{}", n.to_plain_string()),
+            JsonValue::String(s) => write!(f, "{}", JsonValue::quote(s)),
+            JsonValue::Array(a) => {
+                write!(f, "[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(o) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in o.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", JsonValue::quote(k), v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+//A hand written recursive descent parser, every step gives None on bad input since the error
+//only ever reports the whole text
+struct JsonParser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn parse_document(&mut self) -> Option<JsonValue> {
+        let value = self.parse_value(0)?;
+        self.skip_whitespace();
+        if self.position == self.input.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Option<JsonValue> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.parse_object(depth),
+            b'[' => self.parse_array(depth),
+            b'"' => Some(JsonValue::String(self.parse_string()?)),
+            b't' => self.parse_literal("true", JsonValue::Bool(true)),
+            b'f' => self.parse_literal("false", JsonValue::Bool(false)),
+            b'n' => self.parse_literal("null", JsonValue::Null),
+            _ => self.parse_number(),
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Option<JsonValue> {
+        self.position += 1;
+        let mut pairs = vec![];
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Some(JsonValue::Object(pairs));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek()? != b'"' {
+                return None;
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return None;
+            }
+            pairs.push((key, self.parse_value(depth + 1)?));
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Some(JsonValue::Object(pairs));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Option<JsonValue> {
+        self.position += 1;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Some(JsonValue::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Some(JsonValue::Array(elements));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        self.position += 1;
+        let mut bytes = vec![];
+        loop {
+            let b = self.next()?;
+            match b {
+                b'"' => return String::from_utf8(bytes).ok(),
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return None,
+                    };
+                    let mut encoded = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                }
+                //Control characters have to be escaped
+                0..=0x1f => return None,
+                _ => bytes.push(b),
+            }
+        }
+    }
+
+    //Characters outside the basic plane come as a surrogate pair of escapes. Like postgres
+    //\u0000 is refused since text cannot hold it.
+    fn parse_unicode_escape(&mut self) -> Option<char> {
+        let first = self.parse_hex4()?;
+        let code = match first {
+            0xd800..=0xdbff => {
+                if !(self.eat(b'\\') && self.eat(b'u')) {
+                    return None;
+                }
+                let second = self.parse_hex4()?;
+                if !(0xdc00..=0xdfff).contains(&second) {
+                    return None;
+                }
+                0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+            }
+            0 | 0xdc00..=0xdfff => return None,
+            _ => first,
+        };
+        char::from_u32(code)
+    }
+
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let digits = self.input.get(self.position..self.position + 4)?;
+        let text = std::str::from_utf8(digits).ok()?;
+        if !text.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        self.position += 4;
+        u32::from_str_radix(text, 16).ok()
+    }
+
+    //-?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn parse_number(&mut self) -> Option<JsonValue> {
+        let start = self.position;
+        self.eat(b'-');
+        if !self.eat(b'0') && self.eat_digits() == 0 {
+            return None;
+        }
+        if self.eat(b'.') && self.eat_digits() == 0 {
+            return None;
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            if !self.eat(b'+') {
+                self.eat(b'-');
+            }
+            if self.eat_digits() == 0 {
+                return None;
+            }
+        }
+        let text = std::str::from_utf8(&self.input[start..self.position]).ok()?;
+        let mut value = BigDecimal::from_str(text).ok()?;
+        if value.fractional_digit_count() < 0 {
+            value = value.with_scale(0);
+        }
+        Some(JsonValue::Number(value))
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Option<JsonValue> {
+        if self.input[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn eat_digits(&mut self) -> usize {
+        let start = self.position;
+        while self.peek().map(|b| b.is_ascii_digit()).unwrap_or(false) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.position += 1;
+        Some(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jsonb(s: &str) -> JsonValue {
+        JsonValue::parse_jsonb(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            jsonb(r#" {"b": [1, 2.50, -3e2], "a": {"c": null}, "aa": "x\ty\u00e9\ud83d\ude00"} "#)
+                .to_string(),
+            r#"{"a": {"c": null}, "b": [1, 2.50, -300], "aa": "x\tyé😀"}"#
+        );
+        assert_eq!(jsonb(r#"{"a": 1, "a": 2}"#).to_string(), r#"{"a": 2}"#);
+        assert_eq!(jsonb("true"), JsonValue::Bool(true));
+
+        for bad in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "01",
+            "1.",
+            "-",
+            "\"\\u0000\"",
+            "\"\\ud83d\"",
+            "tru",
+            "[1] 2",
+            "\"a\nb\"",
+            "{'a': 1}",
+        ] {
+            assert!(
+                JsonValue::parse(bad, DeserializeTypes::Json).is_err(),
+                "{} parsed",
+                bad
+            );
+        }
+        assert!(JsonValue::parse(&"[".repeat(MAX_DEPTH + 2), DeserializeTypes::Json).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let value =
+            jsonb(r#"{"a": [true, false, null, 1.5, "s"], "b": {}, "c": -12345678901234567890}"#);
+        let mut buff = BytesMut::new();
+        value.serialize(&mut buff);
+        let mut bytes = buff.freeze();
+        assert_eq!(JsonValue::deserialize(&mut bytes)?, value);
+        assert!(!bytes.has_remaining());
+
+        let mut buff = BytesMut::new();
+        jsonb(r#"{"a": "b"}"#).serialize(&mut buff);
+        assert_eq!(
+            buff.to_vec(),
+            vec![6, 1, 0, 0, 0, 1, 0, 0, 0, b'a', 4, 1, 0, 0, 0, b'b']
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_operators() {
+        let value = jsonb(r#"{"a": {"b": [10, 20, 30]}, "c": "d", "e": null}"#);
+        assert_eq!(
+            value.get_key("c"),
+            Some(&JsonValue::String("d".to_string()))
+        );
+        assert_eq!(value.get_key("x"), None);
+        assert_eq!(
+            value.get_path(&["a".to_string(), "b".to_string(), "-1".to_string()]),
+            Some(&jsonb("30"))
+        );
+        assert_eq!(
+            value.get_path(&["a".to_string(), "b".to_string(), "x".to_string()]),
+            None
+        );
+        assert_eq!(value.get_key("e").and_then(JsonValue::to_text), None);
+        assert_eq!(
+            value.get_key("c").and_then(JsonValue::to_text),
+            Some("d".to_string())
+        );
+
+        assert!(value.contains(&jsonb(r#"{"a": {"b": [30, 10]}}"#)));
+        assert!(!value.contains(&jsonb(r#"{"a": {"b": 10}}"#)));
+        assert!(jsonb(r#"[1, [2, 3]]"#).contains(&jsonb("1")));
+        assert!(jsonb(r#"[1, [2, 3]]"#).contains(&jsonb("[[3]]")));
+        assert!(!jsonb(r#"[1, [2, 3]]"#).contains(&jsonb("3")));
+        assert!(jsonb("1.0").contains(&jsonb("1")));
+
+        assert!(value.has_key("e"));
+        assert!(!value.has_key("b"));
+        assert!(jsonb(r#"["x", 1]"#).has_key("x"));
+    }
+
+    #[test]
+    fn test_compare() {
+        let ordered = [
+            "null",
+            "\"a\"",
+            "\"b\"",
+            "1",
+            "2",
+            "false",
+            "true",
+            "[]",
+            "[2]",
+            "[1, 1]",
+            "{}",
+            "{\"b\": 1}",
+            "{\"aa\": 1}",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(jsonb(pair[0]).compare(&jsonb(pair[1])), Ordering::Less);
+        }
+        assert_eq!(jsonb("1.0").compare(&jsonb("1")), Ordering::Equal);
+    }
+}
@@ -0,0 +1,597 @@
+//! The binary form postgres sends and receives values in, each type's typsend and typreceive.
+//! Binary COPY uses it for every value. See: https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.4
+//!
+//! Everything is big endian. Numbers are their machine form and dates and times are the same
+//! offsets from 2000-01-01 as they are stored here. Text types are their utf8 bytes and jsonb
+//! is a version byte of 1 before its text. A numeric is its base 10000 digits with a weight,
+//! sign and display scale. Arrays and composites carry the oid of each element or field so the
+//! receiver can check them against the column.
+use super::array::ARRAY_MAX_DIMENSIONS;
+use super::{
+    ArrayDimension, BuiltinSqlTypes, DeserializeTypes, Interval, PgErrorCodes, SqlArray,
+    SqlTypeError,
+};
+use bigdecimal::num_bigint::{BigInt, Sign};
+use bigdecimal::BigDecimal;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
+use std::num::TryFromIntError;
+use std::string::FromUtf8Error;
+use thiserror::Error;
+
+const NUMERIC_POS: u16 = 0x0000;
+const NUMERIC_NEG: u16 = 0x4000;
+const NUMERIC_NAN: u16 = 0xC000;
+
+const JSONB_VERSION: u8 = 1;
+
+pub struct PgBinary {}
+
+impl PgBinary {
+    /// The value in the binary form of the type
+    pub fn send(
+        value: &BuiltinSqlTypes,
+        sql_type: &DeserializeTypes,
+    ) -> Result<Bytes, PgBinaryError> {
+        let mut buffer = BytesMut::new();
+        PgBinary::send_into(value, sql_type, &mut buffer)?;
+        Ok(buffer.freeze())
+    }
+
+    /// Reads a value of the type, it must use all of the data. Modifiers such as a varchar's
+    /// length are checked the same as for text.
+    pub fn receive(
+        sql_type: &DeserializeTypes,
+        mut data: Bytes,
+    ) -> Result<BuiltinSqlTypes, PgBinaryError> {
+        let value = PgBinary::receive_from(sql_type, &mut data)?;
+        if data.has_remaining() {
+            return Err(PgBinaryError::IncorrectFormat(sql_type.clone()));
+        }
+        Ok(value)
+    }
+
+    fn send_into(
+        value: &BuiltinSqlTypes,
+        sql_type: &DeserializeTypes,
+        buffer: &mut BytesMut,
+    ) -> Result<(), PgBinaryError> {
+        match (value, sql_type) {
+            (BuiltinSqlTypes::Bool(v), _) => buffer.put_u8(u8::from(*v)),
+            (BuiltinSqlTypes::SmallInt(v), _) => buffer.put_i16(*v),
+            (BuiltinSqlTypes::Integer(v), _) => buffer.put_i32(*v),
+            (BuiltinSqlTypes::BigInt(v), _) => buffer.put_i64(*v),
+            (BuiltinSqlTypes::Real(v), _) => buffer.put_f32(*v),
+            (BuiltinSqlTypes::DoublePrecision(v), _) => buffer.put_f64(*v),
+            (BuiltinSqlTypes::Numeric(v), _) => PgBinary::send_numeric(v, buffer)?,
+            (BuiltinSqlTypes::Text(v), _) | (BuiltinSqlTypes::Json(v), _) => {
+                buffer.put_slice(v.as_bytes())
+            }
+            (BuiltinSqlTypes::Uuid(v), _) => buffer.put_slice(v.as_bytes()),
+            (BuiltinSqlTypes::Date(v), _) => buffer.put_i32(*v),
+            (BuiltinSqlTypes::Time(v), _)
+            | (BuiltinSqlTypes::Timestamp(v), _)
+            | (BuiltinSqlTypes::TimestampTz(v), _) => buffer.put_i64(*v),
+            (BuiltinSqlTypes::Interval(v), _) => {
+                buffer.put_i64(v.microseconds);
+                buffer.put_i32(v.days);
+                buffer.put_i32(v.months);
+            }
+            (BuiltinSqlTypes::Bytea(v), _) => buffer.put_slice(v),
+            (BuiltinSqlTypes::Jsonb(_), _) => {
+                buffer.put_u8(JSONB_VERSION);
+                buffer.put_slice(value.to_string().as_bytes());
+            }
+            (BuiltinSqlTypes::Enum(_, label), _) => buffer.put_slice(label.as_bytes()),
+            (BuiltinSqlTypes::Array(array), DeserializeTypes::Array(element_type)) => {
+                buffer.put_i32(i32::try_from(array.dimensions.len())?);
+                buffer.put_i32(i32::from(array.elements.iter().any(Option::is_none)));
+                buffer.put_u32(element_type.oid());
+                for dimension in array.dimensions.iter() {
+                    buffer.put_i32(i32::try_from(dimension.length)?);
+                    buffer.put_i32(dimension.lower_bound);
+                }
+                for element in array.elements.iter() {
+                    PgBinary::send_field(element.as_ref(), element_type, buffer)?;
+                }
+            }
+            (BuiltinSqlTypes::Composite(values), DeserializeTypes::Composite(t))
+                if values.len() == t.fields.len() =>
+            {
+                buffer.put_i32(i32::try_from(values.len())?);
+                for (value, field) in values.iter().zip(t.fields.iter()) {
+                    buffer.put_u32(field.sql_type.oid());
+                    PgBinary::send_field(value.as_ref(), &field.sql_type, buffer)?;
+                }
+            }
+            _ => return Err(PgBinaryError::TypeMismatch(sql_type.clone())),
+        }
+        Ok(())
+    }
+
+    //A length prefixed value, -1 for null
+    fn send_field(
+        value: Option<&BuiltinSqlTypes>,
+        sql_type: &DeserializeTypes,
+        buffer: &mut BytesMut,
+    ) -> Result<(), PgBinaryError> {
+        match value {
+            Some(v) => {
+                let data = PgBinary::send(v, sql_type)?;
+                buffer.put_i32(i32::try_from(data.len())?);
+                buffer.put(data);
+            }
+            None => buffer.put_i32(-1),
+        }
+        Ok(())
+    }
+
+    fn receive_from(
+        sql_type: &DeserializeTypes,
+        data: &mut Bytes,
+    ) -> Result<BuiltinSqlTypes, PgBinaryError> {
+        let need = |data: &Bytes, len: usize| match data.remaining() < len {
+            true => Err(PgBinaryError::IncorrectFormat(sql_type.clone())),
+            false => Ok(()),
+        };
+        let fixed = |data: &Bytes, len: usize| match data.remaining() == len {
+            true => Ok(()),
+            false => Err(PgBinaryError::IncorrectFormat(sql_type.clone())),
+        };
+
+        match sql_type {
+            DeserializeTypes::Bool => {
+                fixed(data, 1)?;
+                Ok(BuiltinSqlTypes::Bool(data.get_u8() != 0))
+            }
+            DeserializeTypes::SmallInt => {
+                fixed(data, 2)?;
+                Ok(BuiltinSqlTypes::SmallInt(data.get_i16()))
+            }
+            DeserializeTypes::Integer => {
+                fixed(data, 4)?;
+                Ok(BuiltinSqlTypes::Integer(data.get_i32()))
+            }
+            DeserializeTypes::BigInt => {
+                fixed(data, 8)?;
+                Ok(BuiltinSqlTypes::BigInt(data.get_i64()))
+            }
+            DeserializeTypes::Real => {
+                fixed(data, 4)?;
+                Ok(BuiltinSqlTypes::Real(data.get_f32()))
+            }
+            DeserializeTypes::DoublePrecision => {
+                fixed(data, 8)?;
+                Ok(BuiltinSqlTypes::DoublePrecision(data.get_f64()))
+            }
+            DeserializeTypes::Numeric(typmod) => {
+                let value = PgBinary::receive_numeric(sql_type, data)?;
+                Ok(BuiltinSqlTypes::Numeric(
+                    BuiltinSqlTypes::apply_numeric_typmod(value, *typmod)?,
+                ))
+            }
+            DeserializeTypes::Text
+            | DeserializeTypes::VarChar(_)
+            | DeserializeTypes::Char(_)
+            | DeserializeTypes::Json => {
+                let text = String::from_utf8(data.split_to(data.remaining()).to_vec())?;
+                Ok(BuiltinSqlTypes::parse(sql_type.clone(), text)?)
+            }
+            DeserializeTypes::Jsonb => {
+                need(data, 1)?;
+                let version = data.get_u8();
+                if version != JSONB_VERSION {
+                    return Err(PgBinaryError::UnsupportedJsonbVersion(version));
+                }
+                let text = String::from_utf8(data.split_to(data.remaining()).to_vec())?;
+                Ok(BuiltinSqlTypes::parse(sql_type.clone(), text)?)
+            }
+            DeserializeTypes::Uuid => {
+                fixed(data, 16)?;
+                Ok(BuiltinSqlTypes::Uuid(uuid::Uuid::from_u128(
+                    data.get_u128(),
+                )))
+            }
+            DeserializeTypes::Date => {
+                fixed(data, 4)?;
+                Ok(BuiltinSqlTypes::Date(data.get_i32()))
+            }
+            DeserializeTypes::Time => {
+                fixed(data, 8)?;
+                Ok(BuiltinSqlTypes::Time(data.get_i64()))
+            }
+            DeserializeTypes::Timestamp => {
+                fixed(data, 8)?;
+                Ok(BuiltinSqlTypes::Timestamp(data.get_i64()))
+            }
+            DeserializeTypes::TimestampTz => {
+                fixed(data, 8)?;
+                Ok(BuiltinSqlTypes::TimestampTz(data.get_i64()))
+            }
+            DeserializeTypes::Interval => {
+                fixed(data, 16)?;
+                let microseconds = data.get_i64();
+                let days = data.get_i32();
+                let months = data.get_i32();
+                Ok(BuiltinSqlTypes::Interval(Interval::new(
+                    months,
+                    days,
+                    microseconds,
+                )))
+            }
+            DeserializeTypes::Bytea => Ok(BuiltinSqlTypes::Bytea(
+                data.split_to(data.remaining()).to_vec(),
+            )),
+            DeserializeTypes::Enum(t) => {
+                let label = String::from_utf8(data.split_to(data.remaining()).to_vec())?;
+                Ok(t.parse(&label)?)
+            }
+            DeserializeTypes::Array(element_type) => {
+                need(data, 12)?;
+                let dimension_count = usize::try_from(data.get_i32())
+                    .map_err(|_| PgBinaryError::IncorrectFormat(sql_type.clone()))?;
+                if dimension_count > ARRAY_MAX_DIMENSIONS {
+                    return Err(SqlTypeError::ArrayTooManyDimensions(dimension_count).into());
+                }
+                let flags = data.get_i32();
+                if flags != 0 && flags != 1 {
+                    return Err(PgBinaryError::IncorrectFormat(sql_type.clone()));
+                }
+                let element_oid = data.get_u32();
+                if element_oid != element_type.oid() {
+                    return Err(PgBinaryError::WrongElementType(
+                        element_oid,
+                        element_type.oid(),
+                    ));
+                }
+
+                need(data, dimension_count * 8)?;
+                let mut dimensions = Vec::with_capacity(dimension_count);
+                let mut count: usize = 1;
+                for _ in 0..dimension_count {
+                    let length = usize::try_from(data.get_i32())
+                        .map_err(|_| PgBinaryError::IncorrectFormat(sql_type.clone()))?;
+                    let lower_bound = data.get_i32();
+                    count = count
+                        .checked_mul(length)
+                        .ok_or_else(|| PgBinaryError::IncorrectFormat(sql_type.clone()))?;
+                    dimensions.push(ArrayDimension {
+                        length,
+                        lower_bound,
+                    });
+                }
+                if dimension_count == 0 || count == 0 {
+                    return Ok(BuiltinSqlTypes::Array(SqlArray::new(vec![])));
+                }
+
+                let mut elements = vec![];
+                for _ in 0..count {
+                    elements.push(PgBinary::receive_field(element_type, data)?);
+                }
+                Ok(BuiltinSqlTypes::Array(SqlArray {
+                    dimensions,
+                    elements,
+                }))
+            }
+            DeserializeTypes::Composite(t) => {
+                need(data, 4)?;
+                let field_count = data.get_i32();
+                if usize::try_from(field_count).ok() != Some(t.fields.len()) {
+                    return Err(PgBinaryError::WrongFieldCount(
+                        t.name.clone(),
+                        field_count,
+                        t.fields.len(),
+                    ));
+                }
+                let mut values = Vec::with_capacity(t.fields.len());
+                for field in t.fields.iter() {
+                    need(data, 4)?;
+                    let oid = data.get_u32();
+                    if oid != field.sql_type.oid() {
+                        return Err(PgBinaryError::WrongElementType(oid, field.sql_type.oid()));
+                    }
+                    values.push(PgBinary::receive_field(&field.sql_type, data)?);
+                }
+                Ok(BuiltinSqlTypes::Composite(values))
+            }
+        }
+    }
+
+    fn receive_field(
+        sql_type: &DeserializeTypes,
+        data: &mut Bytes,
+    ) -> Result<Option<BuiltinSqlTypes>, PgBinaryError> {
+        if data.remaining() < 4 {
+            return Err(PgBinaryError::IncorrectFormat(sql_type.clone()));
+        }
+        let len = data.get_i32();
+        if len < 0 {
+            return Ok(None);
+        }
+        let len = len as usize;
+        if data.remaining() < len {
+            return Err(PgBinaryError::IncorrectFormat(sql_type.clone()));
+        }
+        Ok(Some(PgBinary::receive(sql_type, data.split_to(len))?))
+    }
+
+    //The digits are grouped in fours either side of the decimal point
+    fn send_numeric(value: &BigDecimal, buffer: &mut BytesMut) -> Result<(), PgBinaryError> {
+        let (digits, scale) = value.as_bigint_and_exponent();
+        let sign = match digits.sign() {
+            Sign::Minus => NUMERIC_NEG,
+            _ => NUMERIC_POS,
+        };
+        let mut text = digits.magnitude().to_string();
+
+        let (integer, fraction, display_scale) = if scale <= 0 {
+            text.extend(std::iter::repeat_n('0', usize::try_from(-scale)?));
+            (text, String::new(), 0)
+        } else {
+            let scale = usize::try_from(scale)?;
+            if text.len() <= scale {
+                text.insert_str(0, &"0".repeat(scale + 1 - text.len()));
+            }
+            let fraction = text.split_off(text.len() - scale);
+            (text, fraction, scale)
+        };
+
+        let mut integer = integer;
+        integer.insert_str(0, &"0".repeat((4 - integer.len() % 4) % 4));
+        let mut fraction = fraction;
+        fraction.push_str(&"0".repeat((4 - fraction.len() % 4) % 4));
+
+        let mut groups: Vec<i16> = integer
+            .as_bytes()
+            .chunks(4)
+            .chain(fraction.as_bytes().chunks(4))
+            .map(|g| g.iter().fold(0, |n, d| n * 10 + i16::from(d - b'0')))
+            .collect();
+        let mut weight = i64::try_from(integer.len() / 4)? - 1;
+        let leading = groups.iter().take_while(|g| **g == 0).count();
+        groups.drain(..leading);
+        weight -= i64::try_from(leading)?;
+        while groups.last() == Some(&0) {
+            groups.pop();
+        }
+
+        let (weight, sign) = match groups.is_empty() {
+            true => (0, NUMERIC_POS),
+            false => (i16::try_from(weight)?, sign),
+        };
+        buffer.put_i16(i16::try_from(groups.len())?);
+        buffer.put_i16(weight);
+        buffer.put_u16(sign);
+        buffer.put_u16(u16::try_from(display_scale)?);
+        for g in groups {
+            buffer.put_i16(g);
+        }
+        Ok(())
+    }
+
+    fn receive_numeric(
+        sql_type: &DeserializeTypes,
+        data: &mut Bytes,
+    ) -> Result<BigDecimal, PgBinaryError> {
+        let invalid = || PgBinaryError::IncorrectFormat(sql_type.clone());
+        if data.remaining() < 8 {
+            return Err(invalid());
+        }
+        let count = usize::try_from(data.get_i16()).map_err(|_| invalid())?;
+        let weight = i64::from(data.get_i16());
+        let sign = data.get_u16();
+        let display_scale = i64::from(data.get_u16());
+        if sign == NUMERIC_NAN {
+            return Err(PgBinaryError::NumericNaN());
+        }
+        if (sign != NUMERIC_POS && sign != NUMERIC_NEG) || data.remaining() != count * 2 {
+            return Err(invalid());
+        }
+
+        let mut digits = BigInt::from(0);
+        for _ in 0..count {
+            let group = data.get_i16();
+            if !(0..10000).contains(&group) {
+                return Err(invalid());
+            }
+            digits = digits * 10000u32 + BigInt::from(group);
+        }
+        if sign == NUMERIC_NEG {
+            digits = -digits;
+        }
+
+        //The last group is worth 10000^(weight - count + 1)
+        let exponent = 4 * (weight - i64::try_from(count)? + 1);
+        Ok(BigDecimal::new(digits, -exponent).with_scale(display_scale))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PgBinaryError {
+    #[error("incorrect binary data format for {0}")]
+    IncorrectFormat(DeserializeTypes),
+    #[error(transparent)]
+    NotUtf8(#[from] FromUtf8Error),
+    #[error("numeric NaN is not supported")]
+    NumericNaN(),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
+    #[error(transparent)]
+    TooLarge(#[from] TryFromIntError),
+    #[error("value is not of type {0}")]
+    TypeMismatch(DeserializeTypes),
+    #[error("unsupported jsonb version number {0}")]
+    UnsupportedJsonbVersion(u8),
+    #[error("wrong element type, got oid {0} but expected {1}")]
+    WrongElementType(u32, u32),
+    #[error("wrong number of columns for {0}, got {1} but expected {2}")]
+    WrongFieldCount(String, i32, usize),
+}
+
+impl PgBinaryError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            PgBinaryError::SqlTypeError(e) => e.pg_error_code(),
+            PgBinaryError::NumericNaN() => PgErrorCodes::FeatureNotSupported,
+            PgBinaryError::TooLarge(_) => PgErrorCodes::ProgramLimitExceeded,
+            _ => PgErrorCodes::InvalidBinaryRepresentation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{CompositeField, CompositeType, EnumType, JsonValue};
+    use super::*;
+    use hex_literal::hex;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    fn roundtrip(
+        value: BuiltinSqlTypes,
+        sql_type: DeserializeTypes,
+    ) -> Result<Bytes, Box<dyn std::error::Error>> {
+        let data = PgBinary::send(&value, &sql_type)?;
+        assert_eq!(PgBinary::receive(&sql_type, data.clone())?, value);
+        Ok(data)
+    }
+
+    #[test]
+    fn test_binary_scalars() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            &roundtrip(BuiltinSqlTypes::Integer(-2), DeserializeTypes::Integer)?[..],
+            hex!("FFFFFFFE")
+        );
+        assert_eq!(
+            &roundtrip(BuiltinSqlTypes::Bool(true), DeserializeTypes::Bool)?[..],
+            hex!("01")
+        );
+        roundtrip(BuiltinSqlTypes::BigInt(i64::MAX), DeserializeTypes::BigInt)?;
+        roundtrip(BuiltinSqlTypes::Real(1.5), DeserializeTypes::Real)?;
+        roundtrip(
+            BuiltinSqlTypes::Text("héllo".to_string()),
+            DeserializeTypes::VarChar(Some(5)),
+        )?;
+        roundtrip(
+            BuiltinSqlTypes::Interval(Interval::new(14, 3, 5_000_000)),
+            DeserializeTypes::Interval,
+        )?;
+        roundtrip(
+            BuiltinSqlTypes::TimestampTz(-1),
+            DeserializeTypes::TimestampTz,
+        )?;
+        roundtrip(
+            BuiltinSqlTypes::Bytea(vec![0, 255]),
+            DeserializeTypes::Bytea,
+        )?;
+        roundtrip(
+            BuiltinSqlTypes::Uuid(uuid::Uuid::new_v4()),
+            DeserializeTypes::Uuid,
+        )?;
+        assert_eq!(
+            &roundtrip(
+                BuiltinSqlTypes::Jsonb(JsonValue::parse_jsonb("[1]")?),
+                DeserializeTypes::Jsonb
+            )?[..],
+            b"\x01[1]"
+        );
+
+        assert!(
+            PgBinary::receive(&DeserializeTypes::Integer, Bytes::from_static(b"\0\0")).is_err()
+        );
+        assert!(PgBinary::receive(
+            &DeserializeTypes::VarChar(Some(2)),
+            Bytes::from_static(b"abc")
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_numeric() -> Result<(), Box<dyn std::error::Error>> {
+        //Same bytes postgres sends for these
+        for (text, expected) in [
+            ("0", &hex!("0000000000000000")[..]),
+            ("1", &hex!("0001000000000000 0001")[..]),
+            (
+                "-12345.670",
+                &hex!("0003 0001 4000 0003 0001 0929 1A2C")[..],
+            ),
+            ("0.0001", &hex!("0001 FFFF 0000 0004 0001")[..]),
+            ("100000000", &hex!("0001 0002 0000 0000 0001")[..]),
+        ] {
+            let value = BuiltinSqlTypes::parse(DeserializeTypes::Numeric(None), text.to_string())?;
+            let data = roundtrip(value, DeserializeTypes::Numeric(None))?;
+            assert_eq!(&data[..], expected, "{}", text);
+        }
+
+        let value = BuiltinSqlTypes::Numeric(BigDecimal::from_str("1.005")?);
+        let data = PgBinary::send(&value, &DeserializeTypes::Numeric(None))?;
+        let typmod = DeserializeTypes::from_str("numeric(4,2)")?;
+        assert_eq!(
+            PgBinary::receive(&typmod, data)?.to_string(),
+            "1.01".to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_user_types() -> Result<(), Box<dyn std::error::Error>> {
+        let mood = Arc::new(EnumType {
+            oid: 16384,
+            array_oid: 16385,
+            name: "mood".to_string(),
+            labels: vec!["sad".to_string(), "happy".to_string()],
+        });
+        let status = DeserializeTypes::Composite(Arc::new(CompositeType {
+            oid: 16386,
+            array_oid: 16387,
+            name: "status".to_string(),
+            fields: vec![
+                CompositeField {
+                    name: "feeling".to_string(),
+                    sql_type: DeserializeTypes::Enum(mood.clone()),
+                },
+                CompositeField {
+                    name: "scores".to_string(),
+                    sql_type: DeserializeTypes::Array(Box::new(DeserializeTypes::Integer)),
+                },
+            ],
+        }));
+
+        roundtrip(
+            BuiltinSqlTypes::Composite(vec![
+                Some(mood.parse("happy")?),
+                Some(BuiltinSqlTypes::Array(SqlArray {
+                    dimensions: vec![ArrayDimension {
+                        length: 3,
+                        lower_bound: 0,
+                    }],
+                    elements: vec![
+                        Some(BuiltinSqlTypes::Integer(1)),
+                        None,
+                        Some(BuiltinSqlTypes::Integer(3)),
+                    ],
+                })),
+            ]),
+            status.clone(),
+        )?;
+        roundtrip(
+            BuiltinSqlTypes::Array(SqlArray::new(vec![])),
+            DeserializeTypes::Array(Box::new(status)),
+        )?;
+
+        let text_array = PgBinary::send(
+            &BuiltinSqlTypes::Array(SqlArray::new(vec![None])),
+            &DeserializeTypes::Array(Box::new(DeserializeTypes::Text)),
+        )?;
+        assert!(matches!(
+            PgBinary::receive(
+                &DeserializeTypes::Array(Box::new(DeserializeTypes::Integer)),
+                text_array
+            ),
+            Err(PgBinaryError::WrongElementType(25, 23))
+        ));
+        Ok(())
+    }
+}
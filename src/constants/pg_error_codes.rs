@@ -4,14 +4,64 @@ use bytes::Bytes;
 
 //https://stackoverflow.com/a/62759252/160208
 pub enum PgErrorCodes {
+    ArraySubscriptError,
+    BadCopyFileFormat,
+    CantChangeRuntimeParam,
+    DataCorrupted,
+    DivisionByZero,
+    DuplicateColumn,
+    DuplicateObject,
+    FeatureNotSupported,
+    InFailedSqlTransaction,
+    InsufficientPrivilege,
+    InvalidBinaryRepresentation,
+    InvalidName,
+    InvalidParameterValue,
+    InvalidTextRepresentation,
+    NumericValueOutOfRange,
+    ObjectInUse,
+    ObjectNotInPrerequisiteState,
+    ProgramLimitExceeded,
+    QueryCanceled,
+    ReadOnlySqlTransaction,
+    StringDataRightTruncation,
+    SyntaxError,
     SystemError,
+    UndefinedFile,
+    UndefinedObject,
+    WrongObjectType,
 }
 
 impl PgErrorCodes {
     pub const fn value(self) -> Bytes {
         use PgErrorCodes::*;
         match self {
+            ArraySubscriptError => Bytes::from_static(b"2202E"),
+            BadCopyFileFormat => Bytes::from_static(b"22P04"),
+            CantChangeRuntimeParam => Bytes::from_static(b"55P02"),
+            DataCorrupted => Bytes::from_static(b"XX001"),
+            DivisionByZero => Bytes::from_static(b"22012"),
+            DuplicateColumn => Bytes::from_static(b"42701"),
+            DuplicateObject => Bytes::from_static(b"42710"),
+            FeatureNotSupported => Bytes::from_static(b"0A000"),
+            InFailedSqlTransaction => Bytes::from_static(b"25P02"),
+            InsufficientPrivilege => Bytes::from_static(b"42501"),
+            InvalidBinaryRepresentation => Bytes::from_static(b"22P03"),
+            InvalidName => Bytes::from_static(b"42602"),
+            InvalidParameterValue => Bytes::from_static(b"22023"),
+            InvalidTextRepresentation => Bytes::from_static(b"22P02"),
+            NumericValueOutOfRange => Bytes::from_static(b"22003"),
+            ObjectInUse => Bytes::from_static(b"55006"),
+            ObjectNotInPrerequisiteState => Bytes::from_static(b"55000"),
+            ProgramLimitExceeded => Bytes::from_static(b"54000"),
+            QueryCanceled => Bytes::from_static(b"57014"),
+            ReadOnlySqlTransaction => Bytes::from_static(b"25006"),
+            StringDataRightTruncation => Bytes::from_static(b"22001"),
+            SyntaxError => Bytes::from_static(b"42601"),
             SystemError => Bytes::from_static(b"58000"),
+            UndefinedFile => Bytes::from_static(b"58P01"),
+            UndefinedObject => Bytes::from_static(b"42704"),
+            WrongObjectType => Bytes::from_static(b"42809"),
         }
     }
 }
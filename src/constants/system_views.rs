@@ -0,0 +1,98 @@
+//! Views of the server's own state, their rows are made up as they are read instead of being
+//! stored. Like the system tables they are found by name before pg_class is looked at.
+
+use hex_literal::hex;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::constants::Nullable;
+
+use super::super::constants::DeserializeTypes;
+use super::super::engine::objects::{Attribute, Table};
+
+#[derive(Copy, Clone, Debug)]
+pub enum SystemViews {
+    PgStatBgwriter, //What the checkpointer and background writer have done
+}
+
+impl SystemViews {
+    pub const VALUES: [SystemViews; 1] = [SystemViews::PgStatBgwriter];
+
+    /// The system view with the id, if it is one
+    pub fn find(table_id: Uuid) -> Option<SystemViews> {
+        SystemViews::VALUES
+            .iter()
+            .copied()
+            .find(|v| v.value().id == table_id)
+    }
+
+    pub fn value(self) -> Arc<Table> {
+        match self {
+            SystemViews::PgStatBgwriter => {
+                let id = Uuid::from_bytes(hex!("7210367B08D84B26B50233E67A6B0FF6"));
+                let column = |column_id, name: &str, sql_type| {
+                    Attribute::new_existing(
+                        Uuid::from_bytes(column_id),
+                        id,
+                        name.to_string(),
+                        sql_type,
+                        Nullable::Null,
+                    )
+                };
+                Arc::new(Table::new_existing(
+                    id,
+                    "pg_stat_bgwriter".to_string(),
+                    vec![
+                        column(
+                            hex!("82325822631A4CAABEB671CD3AFEC82F"),
+                            "checkpoints_timed",
+                            DeserializeTypes::BigInt,
+                        ),
+                        column(
+                            hex!("1882564CEE1349C0A540E28FFC84E94A"),
+                            "checkpoints_req",
+                            DeserializeTypes::BigInt,
+                        ),
+                        //Files instead of buffers, pages are written straight to their file
+                        column(
+                            hex!("AB654BF17EAC4A9699E7F84590CCB5FC"),
+                            "buffers_checkpoint",
+                            DeserializeTypes::BigInt,
+                        ),
+                        column(
+                            hex!("A98EEAF24E67411EB80055AB9EDFFFFA"),
+                            "buffers_clean",
+                            DeserializeTypes::BigInt,
+                        ),
+                        column(
+                            hex!("E64B54D735C34D4BBCABD7AE6CF1162F"),
+                            "maxwritten_clean",
+                            DeserializeTypes::BigInt,
+                        ),
+                        column(
+                            hex!("8CB7E2D62AAC481A9CC1C525A7FF71DD"),
+                            "last_checkpoint",
+                            DeserializeTypes::TimestampTz,
+                        ),
+                    ],
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TableDefinitions;
+
+    #[test]
+    fn test_find_view() {
+        let view = SystemViews::PgStatBgwriter.value();
+        assert!(matches!(
+            SystemViews::find(view.id),
+            Some(SystemViews::PgStatBgwriter)
+        ));
+        assert!(SystemViews::find(TableDefinitions::PgClass.value().id).is_none());
+    }
+}
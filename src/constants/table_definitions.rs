@@ -6,18 +6,116 @@ use uuid::Uuid;
 
 use crate::constants::Nullable;
 
-use super::super::constants::DeserializeTypes;
-use super::super::engine::objects::{Attribute, Table};
+use super::super::constants::{BuiltinSqlTypes, DeserializeTypes};
+use super::super::engine::objects::{Attribute, SqlTuple, Table};
 
 #[derive(Copy, Clone)]
 pub enum TableDefinitions {
     PgAttribute, //Columns
     PgClass,     //Tables
+    PgEnum,      //Enum labels
+    PgNamespace, //Schemas
+    PgType,      //Types
 }
 
 impl TableDefinitions {
-    pub const VALUES: [TableDefinitions; 2] =
-        [TableDefinitions::PgAttribute, TableDefinitions::PgClass];
+    pub const VALUES: [TableDefinitions; 5] = [
+        TableDefinitions::PgAttribute,
+        TableDefinitions::PgClass,
+        TableDefinitions::PgEnum,
+        TableDefinitions::PgNamespace,
+        TableDefinitions::PgType,
+    ];
+
+    /// The schema everything built in lives in
+    pub const PG_CATALOG_OID: i32 = 11;
+    /// The schema tables are made in
+    pub const PUBLIC_OID: i32 = 2200;
+
+    /// The system table with the id, if it is one
+    pub fn find(table_id: Uuid) -> Option<TableDefinitions> {
+        TableDefinitions::VALUES
+            .iter()
+            .copied()
+            .find(|t| t.value().id == table_id)
+    }
+
+    /// The rows initdb writes so the catalogs describe the system tables and builtin types too
+    pub fn bootstrap_rows(self) -> Vec<SqlTuple> {
+        match self {
+            TableDefinitions::PgAttribute => TableDefinitions::VALUES
+                .iter()
+                .flat_map(|t| {
+                    let table = t.value();
+                    table
+                        .attributes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, a)| {
+                            SqlTuple(vec![
+                                Some(BuiltinSqlTypes::Uuid(table.id)),
+                                Some(BuiltinSqlTypes::Text(a.name.clone())),
+                                Some(BuiltinSqlTypes::Integer(a.sql_type.oid() as i32)),
+                                Some(BuiltinSqlTypes::Integer(i as i32)),
+                                Some(BuiltinSqlTypes::Integer(a.sql_type.type_modifier())),
+                                Some(BuiltinSqlTypes::Bool(a.nullable == Nullable::Null)),
+                            ])
+                        })
+                        .collect::<Vec<SqlTuple>>()
+                })
+                .collect(),
+            TableDefinitions::PgClass => TableDefinitions::VALUES
+                .iter()
+                .map(|t| {
+                    let table = t.value();
+                    SqlTuple(vec![
+                        Some(BuiltinSqlTypes::Uuid(table.id)),
+                        Some(BuiltinSqlTypes::Text(table.name.clone())),
+                    ])
+                })
+                .collect(),
+            TableDefinitions::PgEnum => vec![],
+            TableDefinitions::PgNamespace => vec![
+                SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Integer(TableDefinitions::PG_CATALOG_OID)),
+                    Some(BuiltinSqlTypes::Text("pg_catalog".to_string())),
+                ]),
+                SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Integer(TableDefinitions::PUBLIC_OID)),
+                    Some(BuiltinSqlTypes::Text("public".to_string())),
+                ]),
+            ],
+            TableDefinitions::PgType => BuiltinSqlTypes::VALUES
+                .iter()
+                .flat_map(|t| {
+                    let array_type = DeserializeTypes::Array(Box::new(t.clone()));
+                    vec![
+                        TableDefinitions::pg_type_row(t, 'b', None, Some(&array_type)),
+                        TableDefinitions::pg_type_row(&array_type, 'b', Some(t), None),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    /// A row for pg_type, composite types also need their typrelid filled in
+    pub fn pg_type_row(
+        sql_type: &DeserializeTypes,
+        typtype: char,
+        element: Option<&DeserializeTypes>,
+        array: Option<&DeserializeTypes>,
+    ) -> SqlTuple {
+        let oid = |t: &DeserializeTypes| Some(BuiltinSqlTypes::Integer(t.oid() as i32));
+        SqlTuple(vec![
+            oid(sql_type),
+            Some(BuiltinSqlTypes::Text(sql_type.typname())),
+            Some(BuiltinSqlTypes::Text(typtype.to_string())),
+            element.map_or(Some(BuiltinSqlTypes::Integer(0)), oid),
+            array.map_or(Some(BuiltinSqlTypes::Integer(0)), oid),
+            None,
+        ])
+    }
+
     pub fn value(self) -> Arc<Table> {
         match self {
             TableDefinitions::PgClass => Arc::new(Table::new_existing(
@@ -62,7 +160,7 @@ impl TableDefinitions {
                         Uuid::from_bytes(hex!("D36A417A25D44019B8AA91DF9783EDEA")),
                         Uuid::from_bytes(hex!("EE89957F3E9F482C836DDA6C349AC632")),
                         "atttypid".to_string(),
-                        DeserializeTypes::Text, //TODO join to pg_type instead, for now its a string
+                        DeserializeTypes::Integer, //The oid in pg_type
                         Nullable::NotNull,
                     ),
                     Attribute::new_existing(
@@ -73,7 +171,14 @@ impl TableDefinitions {
                         Nullable::NotNull,
                     ),
                     Attribute::new_existing(
-                        Uuid::from_bytes(hex!("73479C7B65EA474DA0CE2812DD0143F9")),
+                        Uuid::from_bytes(hex!("E2ECE38C60BC4766812160CEBFB01190")),
+                        Uuid::from_bytes(hex!("EE89957F3E9F482C836DDA6C349AC632")),
+                        "atttypmod".to_string(),
+                        DeserializeTypes::Integer, //Same encoding as postgres, -1 for none
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("B8344EF479474E21986019C395DE6CEF")),
                         Uuid::from_bytes(hex!("EE89957F3E9F482C836DDA6C349AC632")),
                         "attnotnull".to_string(),
                         DeserializeTypes::Bool,
@@ -81,6 +186,101 @@ impl TableDefinitions {
                     ),
                 ],
             )),
+            TableDefinitions::PgEnum => Arc::new(Table::new_existing(
+                Uuid::from_bytes(hex!("F9F5D36AA7DE4BD599FE9FF5870D2EB2")),
+                "pg_enum".to_string(),
+                vec![
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("15D942C8101D4CE0B542C8C5F5FED824")),
+                        Uuid::from_bytes(hex!("F9F5D36AA7DE4BD599FE9FF5870D2EB2")),
+                        "enumtypid".to_string(),
+                        DeserializeTypes::Integer,
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("30B878F1B2D243BA82C31526D4C8546A")),
+                        Uuid::from_bytes(hex!("F9F5D36AA7DE4BD599FE9FF5870D2EB2")),
+                        "enumsortorder".to_string(),
+                        DeserializeTypes::Integer,
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("28FC58C703C84D6D928F23A49950234D")),
+                        Uuid::from_bytes(hex!("F9F5D36AA7DE4BD599FE9FF5870D2EB2")),
+                        "enumlabel".to_string(),
+                        DeserializeTypes::Text,
+                        Nullable::NotNull,
+                    ),
+                ],
+            )),
+            TableDefinitions::PgNamespace => Arc::new(Table::new_existing(
+                Uuid::from_bytes(hex!("9E233B3742114564B743377829841B4F")),
+                "pg_namespace".to_string(),
+                vec![
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("A827C5B68A814019ACFFB695B2BD6234")),
+                        Uuid::from_bytes(hex!("9E233B3742114564B743377829841B4F")),
+                        "oid".to_string(),
+                        DeserializeTypes::Integer,
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("2EDE32864AF34F9B8C0DC140D5C3EF93")),
+                        Uuid::from_bytes(hex!("9E233B3742114564B743377829841B4F")),
+                        "nspname".to_string(),
+                        DeserializeTypes::Text,
+                        Nullable::NotNull,
+                    ),
+                ],
+            )),
+            TableDefinitions::PgType => Arc::new(Table::new_existing(
+                Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                "pg_type".to_string(),
+                vec![
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("EDCCBC2536E348599B703AA582DFD77C")),
+                        Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                        "oid".to_string(),
+                        DeserializeTypes::Integer,
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("2135104E198C4473A58913871C02FA05")),
+                        Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                        "typname".to_string(),
+                        DeserializeTypes::Text,
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("98CDDA2AC003419A97C62ECD0FC53D86")),
+                        Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                        "typtype".to_string(),
+                        DeserializeTypes::Text, //b for base, e for enum and c for composite
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("780A53A66A47456F835E71884112B2F6")),
+                        Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                        "typelem".to_string(),
+                        DeserializeTypes::Integer, //Element type of an array type, 0 otherwise
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("80209AB0AB5B472CADDC18435B6438D5")),
+                        Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                        "typarray".to_string(),
+                        DeserializeTypes::Integer, //The array type, 0 for array types
+                        Nullable::NotNull,
+                    ),
+                    Attribute::new_existing(
+                        Uuid::from_bytes(hex!("6D52068B94E740158E99F69A73448DED")),
+                        Uuid::from_bytes(hex!("61AC0F3415DD41BB81634B846DD50141")),
+                        "typrelid".to_string(),
+                        DeserializeTypes::Uuid, //The attrelid of a composite's fields
+                        Nullable::Null,
+                    ),
+                ],
+            )),
         }
     }
 }
@@ -0,0 +1,194 @@
+//! The time zone a session uses to read and display timestamp with time zone values.
+//!
+//! Zones are either a fixed offset from UTC or a named IANA zone whose offset depends on the
+//! date because of daylight savings. Fixed offsets are written ISO 8601 style so "-07" is seven
+//! hours west of UTC, postgres' POSIX style names such as "UTC+7" are not supported.
+use chrono::{Duration, NaiveDateTime, Offset, TimeZone as ChronoTimeZone};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimeZone {
+    ///Seconds east of UTC
+    Fixed(i32),
+    Named(Tz),
+}
+
+//Postgres limits offsets to 15:59:59 either side of UTC
+const MAX_OFFSET_SECONDS: i32 = 15 * 3600 + 59 * 60 + 59;
+
+impl TimeZone {
+    /// The offset in seconds east of UTC in effect at the given UTC time
+    pub fn utc_offset(&self, utc: NaiveDateTime) -> i32 {
+        match self {
+            TimeZone::Fixed(o) => *o,
+            TimeZone::Named(tz) => tz.offset_from_utc_datetime(&utc).fix().local_minus_utc(),
+        }
+    }
+
+    /// The offset in seconds east of UTC for a wall clock time in this zone. Like postgres a
+    /// time repeated by a daylight savings change is read as standard time and a time skipped
+    /// over uses the offset from before the change.
+    pub fn local_offset(&self, local: NaiveDateTime) -> i32 {
+        match self {
+            TimeZone::Fixed(o) => *o,
+            TimeZone::Named(tz) => match tz.offset_from_local_datetime(&local) {
+                chrono::LocalResult::Single(o) => o.fix().local_minus_utc(),
+                chrono::LocalResult::Ambiguous(a, b) => {
+                    a.fix().local_minus_utc().min(b.fix().local_minus_utc())
+                }
+                chrono::LocalResult::None => self.utc_offset(local - Duration::days(1)),
+            },
+        }
+    }
+
+    /// Parses a numeric offset such as +05, -0330 or +05:30:15
+    pub fn parse_offset(s: &str) -> Option<i32> {
+        let (sign, rest) = match s.as_bytes().first()? {
+            b'+' => (1, &s[1..]),
+            b'-' => (-1, &s[1..]),
+            _ => return None,
+        };
+
+        let parts: Vec<&str> = if rest.contains(':') {
+            rest.split(':').collect()
+        } else if rest.len() > 2 && rest.len() % 2 == 0 {
+            //Compact forms like 0530 and 053015
+            (0..rest.len())
+                .step_by(2)
+                .map(|i| rest.get(i..i + 2).unwrap_or(""))
+                .collect()
+        } else {
+            vec![rest]
+        };
+        if parts.is_empty()
+            || parts.len() > 3
+            || parts
+                .iter()
+                .any(|p| p.is_empty() || p.len() > 2 || !p.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return None;
+        }
+
+        let mut seconds = 0;
+        for (part, scale) in parts.iter().zip([3600, 60, 1]) {
+            let value: i32 = part.parse().ok()?;
+            if scale != 3600 && value >= 60 {
+                return None;
+            }
+            seconds += value * scale;
+        }
+        if seconds > MAX_OFFSET_SECONDS {
+            return None;
+        }
+        Some(sign * seconds)
+    }
+
+    /// Formats an offset the way postgres prints it after a timestamp, +05, -03:30 or +00:00:15
+    pub fn format_offset(seconds: i32) -> String {
+        let sign = if seconds < 0 { '-' } else { '+' };
+        let abs = seconds.abs();
+        let (hours, minutes, secs) = (abs / 3600, abs / 60 % 60, abs % 60);
+        if secs != 0 {
+            format!("{}{:02}:{:02}:{:02}", sign, hours, minutes, secs)
+        } else if minutes != 0 {
+            format!("{}{:02}:{:02}", sign, hours, minutes)
+        } else {
+            format!("{}{:02}", sign, hours)
+        }
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        TimeZone::Named(Tz::UTC)
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = TimeZoneError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if let Some(offset) = TimeZone::parse_offset(trimmed) {
+            return Ok(TimeZone::Fixed(offset));
+        }
+        if trimmed.eq_ignore_ascii_case("z") {
+            return Ok(TimeZone::Named(Tz::UTC));
+        }
+        if let Ok(tz) = Tz::from_str(trimmed) {
+            return Ok(TimeZone::Named(tz));
+        }
+
+        //Zone names are case insensitive in postgres
+        TZ_VARIANTS
+            .iter()
+            .find(|tz| tz.name().eq_ignore_ascii_case(trimmed))
+            .map(|tz| TimeZone::Named(*tz))
+            .ok_or_else(|| TimeZoneError::UnknownTimeZone(s.to_string()))
+    }
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeZone::Fixed(o) => write!(f, "{}", TimeZone::format_offset(*o)),
+            TimeZone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TimeZoneError {
+    #[error("time zone \"{0}\" not recognized")]
+    UnknownTimeZone(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_zones() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(TimeZone::from_str("utc")?, TimeZone::Named(Tz::UTC));
+        assert_eq!(
+            TimeZone::from_str("america/new_york")?,
+            TimeZone::Named(Tz::America__New_York)
+        );
+        assert_eq!(TimeZone::from_str("-7")?, TimeZone::Fixed(-7 * 3600));
+        assert_eq!(TimeZone::from_str("+0530")?, TimeZone::Fixed(19800));
+        assert_eq!(TimeZone::from_str("+05:30")?, TimeZone::Fixed(19800));
+        assert!(TimeZone::from_str("Mars/Olympus_Mons").is_err());
+        assert!(TimeZone::from_str("+16").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        for z in ["UTC", "Europe/Berlin", "-07", "+05:30", "+00:00:15"] {
+            assert_eq!(TimeZone::from_str(z)?.to_string(), z);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_daylight_savings() {
+        let ny = TimeZone::Named(Tz::America__New_York);
+        assert_eq!(ny.utc_offset(local(2021, 1, 1, 12, 0)), -5 * 3600);
+        assert_eq!(ny.utc_offset(local(2021, 7, 1, 12, 0)), -4 * 3600);
+
+        //1:30 happens twice when the clocks go back, standard time wins
+        assert_eq!(ny.local_offset(local(2021, 11, 7, 1, 30)), -5 * 3600);
+        //2:30 never happens when the clocks go forward, the old offset is used
+        assert_eq!(ny.local_offset(local(2021, 3, 14, 2, 30)), -5 * 3600);
+    }
+}
@@ -0,0 +1,317 @@
+//! Types made with CREATE TYPE, their definitions live in pg_type, pg_enum and pg_attribute.
+//!
+//! An enum value is stored as its label's position in the type as a little endian u32, so
+//! values sort in the order the labels were declared.
+//!
+//! On disk a composite value is:
+//! * A null bitmap, one bit per field with the high bit first and set meaning null
+//! * Each non null field serialized as its own type
+use super::{BuiltinSqlTypes, DeserializeTypes, SqlTypeError, TimeZone};
+use bytes::{Buf, BufMut, BytesMut};
+
+/// The first OID handed out to a user type, the same place postgres starts
+pub const FIRST_USER_OID: u32 = 16384;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumType {
+    pub oid: u32,
+    pub array_oid: u32,
+    pub name: String,
+    ///In sort order
+    pub labels: Vec<String>,
+}
+
+impl EnumType {
+    /// Labels are matched exactly, like postgres there is no trimming or case folding
+    pub fn parse(&self, input: &str) -> Result<BuiltinSqlTypes, SqlTypeError> {
+        match self.labels.iter().position(|l| l == input) {
+            Some(p) => Ok(BuiltinSqlTypes::Enum(p as u32, input.to_string())),
+            None => Err(SqlTypeError::InvalidEnumInput(
+                self.name.clone(),
+                input.to_string(),
+            )),
+        }
+    }
+
+    pub fn label(&self, position: u32) -> Result<BuiltinSqlTypes, SqlTypeError> {
+        match self.labels.get(position as usize) {
+            Some(l) => Ok(BuiltinSqlTypes::Enum(position, l.clone())),
+            None => Err(SqlTypeError::InvalidEnumPosition(
+                self.name.clone(),
+                position,
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositeType {
+    pub oid: u32,
+    pub array_oid: u32,
+    pub name: String,
+    pub fields: Vec<CompositeField>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositeField {
+    pub name: String,
+    pub sql_type: DeserializeTypes,
+}
+
+impl CompositeType {
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.name == name)
+    }
+
+    /// Parses the text form such as (1,"a b",) where an unquoted empty field is null
+    pub fn parse(
+        &self,
+        input: &str,
+        zone: &TimeZone,
+    ) -> Result<Vec<Option<BuiltinSqlTypes>>, SqlTypeError> {
+        let malformed = || SqlTypeError::MalformedRecord(input.to_string());
+        let mut chars = input.trim_start().chars().peekable();
+        if chars.next() != Some('(') {
+            return Err(malformed());
+        }
+
+        let mut values = Vec::with_capacity(self.fields.len());
+        for (i, field) in self.fields.iter().enumerate() {
+            let mut text = String::new();
+            let mut quoted = false;
+            let mut in_quotes = false;
+            loop {
+                let c = chars.next().ok_or_else(malformed)?;
+                match c {
+                    '\\' => text.push(chars.next().ok_or_else(malformed)?),
+                    '"' if !in_quotes => {
+                        in_quotes = true;
+                        quoted = true;
+                    }
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        text.push('"');
+                    }
+                    '"' => in_quotes = false,
+                    ',' | ')' if !in_quotes => {
+                        let last = i + 1 == self.fields.len();
+                        if (c == ')') != last {
+                            return Err(malformed());
+                        }
+                        break;
+                    }
+                    _ => text.push(c),
+                }
+            }
+
+            if text.is_empty() && !quoted {
+                values.push(None);
+            } else {
+                values.push(Some(BuiltinSqlTypes::parse_in_zone(
+                    field.sql_type.clone(),
+                    text,
+                    zone,
+                )?));
+            }
+        }
+
+        if self.fields.is_empty() && chars.next() != Some(')') {
+            return Err(malformed());
+        }
+        if chars.any(|c| !c.is_whitespace()) {
+            return Err(malformed());
+        }
+        Ok(values)
+    }
+
+    /// The text form with each field written by the given function
+    pub fn format(
+        values: &[Option<BuiltinSqlTypes>],
+        field: &dyn Fn(&BuiltinSqlTypes) -> String,
+    ) -> String {
+        let mut output = String::from("(");
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                output.push(',');
+            }
+            if let Some(v) = value {
+                output.push_str(&CompositeType::quote(&field(v)));
+            }
+        }
+        output.push(')');
+        output
+    }
+
+    //Same rules as postgres' record_out, quotes and backslashes are doubled
+    fn quote(text: &str) -> String {
+        let needs_quotes = text.is_empty()
+            || text
+                .chars()
+                .any(|c| matches!(c, '"' | '\\' | '(' | ')' | ',') || c.is_whitespace());
+        if !needs_quotes {
+            return text.to_string();
+        }
+
+        let mut quoted = String::with_capacity(text.len() + 2);
+        quoted.push('"');
+        for c in text.chars() {
+            if c == '"' || c == '\\' {
+                quoted.push(c);
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    pub fn serialize(values: &[Option<BuiltinSqlTypes>], buff: &mut BytesMut) {
+        for chunk in values.chunks(8) {
+            let mut bits = 0;
+            for (i, v) in chunk.iter().enumerate() {
+                if v.is_none() {
+                    bits |= 0x80 >> i;
+                }
+            }
+            buff.put_u8(bits);
+        }
+        for v in values.iter().flatten() {
+            buff.extend_from_slice(&v.serialize());
+        }
+    }
+
+    pub fn deserialize(
+        &self,
+        buffer: &mut impl Buf,
+    ) -> Result<Vec<Option<BuiltinSqlTypes>>, SqlTypeError> {
+        let bitmap_length = self.fields.len().div_ceil(8);
+        if buffer.remaining() < bitmap_length {
+            return Err(SqlTypeError::BufferTooShort());
+        }
+        let bitmap = buffer.copy_to_bytes(bitmap_length);
+
+        let mut values = Vec::with_capacity(self.fields.len());
+        for (i, field) in self.fields.iter().enumerate() {
+            if bitmap[i / 8] & (0x80 >> (i % 8)) != 0 {
+                values.push(None);
+            } else {
+                values.push(Some(BuiltinSqlTypes::deserialize(
+                    field.sql_type.clone(),
+                    &mut *buffer,
+                )?));
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn mood() -> EnumType {
+        EnumType {
+            oid: FIRST_USER_OID,
+            array_oid: FIRST_USER_OID + 1,
+            name: "mood".to_string(),
+            labels: vec!["sad".to_string(), "ok".to_string(), "happy".to_string()],
+        }
+    }
+
+    fn pair() -> CompositeType {
+        CompositeType {
+            oid: FIRST_USER_OID + 2,
+            array_oid: FIRST_USER_OID + 3,
+            name: "pair".to_string(),
+            fields: vec![
+                CompositeField {
+                    name: "a".to_string(),
+                    sql_type: DeserializeTypes::Integer,
+                },
+                CompositeField {
+                    name: "b".to_string(),
+                    sql_type: DeserializeTypes::Text,
+                },
+                CompositeField {
+                    name: "c".to_string(),
+                    sql_type: DeserializeTypes::Enum(Arc::new(mood())),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_enum() -> Result<(), Box<dyn std::error::Error>> {
+        let mood = mood();
+        assert_eq!(
+            mood.parse("ok")?,
+            BuiltinSqlTypes::Enum(1, "ok".to_string())
+        );
+        assert!(matches!(
+            mood.parse("OK"),
+            Err(SqlTypeError::InvalidEnumInput(_, _))
+        ));
+        assert_eq!(
+            mood.label(2)?,
+            BuiltinSqlTypes::Enum(2, "happy".to_string())
+        );
+        assert!(mood.label(3).is_err());
+
+        let sql_type = DeserializeTypes::Enum(Arc::new(mood));
+        let value = BuiltinSqlTypes::parse(sql_type.clone(), "happy".to_string())?;
+        assert!(value.type_matches(&sql_type));
+        assert_eq!(
+            BuiltinSqlTypes::deserialize(sql_type, value.serialize())?,
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_composite_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let sql_type = DeserializeTypes::Composite(Arc::new(pair()));
+        for (input, output) in [
+            ("(1,hello,ok)", "(1,hello,ok)"),
+            ("(,,)", "(,,)"),
+            (" (2,\"a, \"\"b\"\"\",happy) ", "(2,\"a, \"\"b\"\"\",happy)"),
+            ("(3,\"\",)", "(3,\"\",)"),
+            ("(4,a\\)b,sad)", "(4,\"a)b\",sad)"),
+        ] {
+            let value = BuiltinSqlTypes::parse(sql_type.clone(), input.to_string())?;
+            assert!(value.type_matches(&sql_type));
+            assert_eq!(value.to_string(), output);
+            assert_eq!(
+                BuiltinSqlTypes::deserialize(sql_type.clone(), value.serialize())?,
+                value
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_composite() {
+        let pair = pair();
+        let zone = TimeZone::default();
+        for input in [
+            "",
+            "1,a,ok",
+            "(1,a)",
+            "(1,a,ok,)",
+            "(1,a,ok) x",
+            "(1,\"a,ok)",
+        ] {
+            assert!(
+                matches!(
+                    pair.parse(input, &zone),
+                    Err(SqlTypeError::MalformedRecord(_))
+                ),
+                "{}",
+                input
+            );
+        }
+        assert!(matches!(
+            pair.parse("(1,a,meh)", &zone),
+            Err(SqlTypeError::InvalidEnumInput(_, _))
+        ));
+    }
+}
@@ -0,0 +1,19 @@
+//! A logical dump of a server's tables as a SQL script, like pg_dump.
+//! See: https://www.postgresql.org/docs/current/app-pgdump.html
+//!
+//! The dump connects like any client and reads the catalogs and every table inside one read only
+//! transaction block, so it all comes from a single snapshot. The script creates the user types
+//! and tables and then loads each table's rows with COPY, loading it into an empty server makes
+//! the same database.
+
+mod client;
+pub use client::Client;
+pub use client::ClientError;
+
+mod dumper;
+pub use dumper::Dumper;
+pub use dumper::DumperError;
+
+mod restorer;
+pub use restorer::Restorer;
+pub use restorer::RestorerError;
@@ -0,0 +1,171 @@
+//! Just enough of a frontend for the dump, the simple query protocol with text results,
+//! COPY FROM STDIN and COPY TO STDOUT. See: https://www.postgresql.org/docs/current/protocol-flow.html
+use crate::codec::{NetworkFrame, PgCodec};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use std::string::FromUtf8Error;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+/// A row of text values, None for null
+pub type TextRow = Vec<Option<String>>;
+
+pub struct Client {
+    framed: Framed<TcpStream, PgCodec>,
+}
+
+impl Client {
+    /// The address is a host:port to connect to
+    pub async fn connect(address: &str) -> Result<Client, ClientError> {
+        let stream = TcpStream::connect(address).await?;
+        let mut client = Client {
+            framed: Framed::new(stream, PgCodec {}),
+        };
+
+        client.framed.send(Client::startup_message()).await?;
+        client.finish(None, &mut BytesMut::new()).await?;
+        Ok(client)
+    }
+
+    /// Runs the statements, returning the rows of the last one that had any
+    pub async fn query(&mut self, query: &str) -> Result<Vec<TextRow>, ClientError> {
+        self.framed.send(Client::query_message(query)).await?;
+        self.finish(None, &mut BytesMut::new()).await
+    }
+
+    /// Runs a COPY FROM STDIN statement sending it the data
+    pub async fn copy_in(&mut self, query: &str, data: Bytes) -> Result<(), ClientError> {
+        self.framed.send(Client::query_message(query)).await?;
+        self.finish(Some(data), &mut BytesMut::new()).await?;
+        Ok(())
+    }
+
+    /// Runs a COPY TO STDOUT statement returning all of the data it sent
+    pub async fn copy_out(&mut self, query: &str) -> Result<Bytes, ClientError> {
+        let mut copied = BytesMut::new();
+        self.framed.send(Client::query_message(query)).await?;
+        self.finish(None, &mut copied).await?;
+        Ok(copied.freeze())
+    }
+
+    //Reads up to the ready for query, the first server error is kept until then
+    async fn finish(
+        &mut self,
+        mut copy_data: Option<Bytes>,
+        copied: &mut BytesMut,
+    ) -> Result<Vec<TextRow>, ClientError> {
+        let mut rows = vec![];
+        let mut error = None;
+        loop {
+            let frame = match self.framed.next().await {
+                Some(f) => f?,
+                None => return Err(ClientError::Disconnected()),
+            };
+            match frame.message_type {
+                b'Z' => break,
+                b'T' => rows.clear(),
+                b'D' => rows.push(Client::parse_row(frame.payload)?),
+                b'd' => copied.put(frame.payload),
+                b'E' | b'N' if error.is_none() => {
+                    error = Some(Client::parse_error(frame.payload));
+                }
+                b'G' => match copy_data.take() {
+                    Some(data) => {
+                        self.framed.send(NetworkFrame::new(b'd', data)).await?;
+                        self.framed
+                            .send(NetworkFrame::new(b'c', Bytes::new()))
+                            .await?;
+                    }
+                    None => {
+                        let mut reason = BytesMut::new();
+                        reason.put(&b"the dump has no data to send"[..]);
+                        reason.put_u8(b'\0');
+                        self.framed
+                            .send(NetworkFrame::new(b'f', reason.freeze()))
+                            .await?;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(rows),
+        }
+    }
+
+    fn parse_row(mut payload: Bytes) -> Result<TextRow, ClientError> {
+        if payload.remaining() < 2 {
+            return Err(ClientError::MalformedRow());
+        }
+        let count = payload.get_u16();
+        let mut row = Vec::with_capacity(count.into());
+        for _ in 0..count {
+            if payload.remaining() < 4 {
+                return Err(ClientError::MalformedRow());
+            }
+            let len = payload.get_i32();
+            if len < 0 {
+                row.push(None);
+                continue;
+            }
+            let len = len as usize;
+            if payload.remaining() < len {
+                return Err(ClientError::MalformedRow());
+            }
+            row.push(Some(String::from_utf8(payload.split_to(len).to_vec())?));
+        }
+        Ok(row)
+    }
+
+    fn parse_error(payload: Bytes) -> ClientError {
+        let mut code = String::new();
+        let mut message = String::new();
+        for field in payload.split(|b| *b == b'\0') {
+            if let Some((kind, value)) = field.split_first() {
+                let value = String::from_utf8_lossy(value).to_string();
+                match kind {
+                    b'C' => code = value,
+                    b'M' => message = value,
+                    _ => {}
+                }
+            }
+        }
+        ClientError::Server(code, message)
+    }
+
+    fn query_message(query: &str) -> NetworkFrame {
+        let mut buffer = BytesMut::new();
+        buffer.put(query.as_bytes());
+        buffer.put_u8(b'\0');
+        NetworkFrame::new(b'Q', buffer.freeze())
+    }
+
+    //Protocol 3.0, written raw so it has its own length
+    fn startup_message() -> NetworkFrame {
+        let mut params = BytesMut::new();
+        params.put_u32(196608);
+        params.put(&b"user\0feophant\0\0"[..]);
+
+        let mut buffer = BytesMut::new();
+        buffer.put_u32(params.len() as u32 + 4);
+        buffer.put(params);
+        NetworkFrame::new(0, buffer.freeze())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("The server closed the connection")]
+    Disconnected(),
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error("The server sent a malformed data row")]
+    MalformedRow(),
+    #[error(transparent)]
+    NotUtf8(#[from] FromUtf8Error),
+    #[error("The server returned error {0}: {1}")]
+    Server(String, String),
+}
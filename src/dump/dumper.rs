@@ -0,0 +1,283 @@
+//! Writes the script, the user types in the order they were made so each one's fields can use
+//! the ones before it, then the tables and their data.
+//!
+//! Types are written with the names they are parsed from so builtin modifiers such as
+//! varchar lengths and numeric precision survive. The nullable flag in pg_attribute is stored
+//! as null allowed, not as postgres' attnotnull.
+use super::client::{Client, ClientError, TextRow};
+use crate::codec::CopyText;
+use crate::constants::{DeserializeTypes, Nullable, SqlTypeError, TableDefinitions};
+use std::collections::HashMap;
+use std::num::ParseIntError;
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+pub struct Dumper {
+    client: Client,
+}
+
+struct UserType {
+    oid: u32,
+    name: String,
+    kind: String,
+    element: u32,
+    relation: Option<String>,
+}
+
+struct Column {
+    name: String,
+    type_oid: u32,
+    number: i32,
+    typmod: i32,
+    nullable: Nullable,
+}
+
+impl Dumper {
+    pub fn new(client: Client) -> Dumper {
+        Dumper { client }
+    }
+
+    /// Writes the whole database as one consistent snapshot
+    pub async fn dump<W: AsyncWrite + Unpin>(&mut self, out: &mut W) -> Result<(), DumperError> {
+        self.client
+            .query("BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .await?;
+        let result = self.dump_snapshot(out).await;
+        //Nothing was written so the end of the block can't fail it
+        self.client.query("COMMIT").await?;
+        result
+    }
+
+    async fn dump_snapshot<W: AsyncWrite + Unpin>(
+        &mut self,
+        out: &mut W,
+    ) -> Result<(), DumperError> {
+        let classes = self.client.query("select id, name from pg_class").await?;
+        let attributes = self
+            .client
+            .query("select attrelid, attname, atttypid, attnum, atttypmod, attnotnull from pg_attribute")
+            .await?;
+        let types = self
+            .client
+            .query("select oid, typname, typtype, typelem, typrelid from pg_type")
+            .await?;
+        let labels = self
+            .client
+            .query("select enumtypid, enumsortorder, enumlabel from pg_enum")
+            .await?;
+
+        let mut columns: HashMap<String, Vec<Column>> = HashMap::new();
+        for row in attributes {
+            let (relation, column) = Dumper::parse_column(row)?;
+            columns.entry(relation).or_default().push(column);
+        }
+        for c in columns.values_mut() {
+            c.sort_by_key(|c| c.number);
+        }
+
+        let mut user_types = vec![];
+        for row in types {
+            let user_type = Dumper::parse_type(row)?;
+            if user_type.kind != "b" || user_type.element != 0 {
+                user_types.push(user_type);
+            }
+        }
+        user_types.sort_by_key(|t| t.oid);
+        let type_names: HashMap<u32, &UserType> = user_types.iter().map(|t| (t.oid, t)).collect();
+
+        let mut enum_labels: HashMap<u32, Vec<(i32, String)>> = HashMap::new();
+        for row in labels {
+            let (type_oid, order, label) = match &row[..] {
+                [Some(t), Some(o), Some(l)] => (t.parse()?, o.parse()?, l.clone()),
+                _ => return Err(DumperError::MalformedCatalog(row)),
+            };
+            enum_labels
+                .entry(type_oid)
+                .or_default()
+                .push((order, label));
+        }
+
+        let mut tables = vec![];
+        for row in classes {
+            let (id, name) = match &row[..] {
+                [Some(i), Some(n)] => (i.clone(), n.clone()),
+                _ => return Err(DumperError::MalformedCatalog(row)),
+            };
+            let system = Uuid::parse_str(&id)
+                .map(|u| TableDefinitions::find(u).is_some())
+                .unwrap_or(false);
+            if !system {
+                tables.push((name, id));
+            }
+        }
+        tables.sort();
+
+        out.write_all(b"-- FeOphant database dump\n\nBEGIN;\n\n")
+            .await?;
+
+        for user_type in user_types.iter().filter(|t| t.kind != "b") {
+            let definition = match user_type.kind.as_str() {
+                "e" => {
+                    let mut labels = enum_labels.remove(&user_type.oid).unwrap_or_default();
+                    labels.sort_by_key(|l| l.0);
+                    let labels: Vec<String> =
+                        labels.into_iter().map(|(_, l)| Dumper::quote(&l)).collect();
+                    format!("ENUM ({})", labels.join(", "))
+                }
+                "c" => {
+                    let fields = user_type
+                        .relation
+                        .as_ref()
+                        .and_then(|r| columns.get(r))
+                        .ok_or_else(|| DumperError::MissingFields(user_type.name.clone()))?;
+                    let fields = fields
+                        .iter()
+                        .map(|f| {
+                            Ok(format!(
+                                "{} {}",
+                                f.name,
+                                Dumper::type_name(f.type_oid, f.typmod, &type_names)?
+                            ))
+                        })
+                        .collect::<Result<Vec<String>, DumperError>>()?;
+                    format!("({})", fields.join(", "))
+                }
+                k => return Err(DumperError::UnknownTypeKind(k.to_string())),
+            };
+            out.write_all(
+                format!("CREATE TYPE {} AS {};\n", user_type.name, definition).as_bytes(),
+            )
+            .await?;
+        }
+        if user_types.iter().any(|t| t.kind != "b") {
+            out.write_all(b"\n").await?;
+        }
+
+        let empty = vec![];
+        for (name, id) in tables.iter() {
+            let table_columns = columns.get(id).unwrap_or(&empty);
+            let definitions = table_columns
+                .iter()
+                .map(|c| {
+                    let mut definition = format!(
+                        "{} {}",
+                        c.name,
+                        Dumper::type_name(c.type_oid, c.typmod, &type_names)?
+                    );
+                    if c.nullable == Nullable::NotNull {
+                        definition.push_str(" NOT NULL");
+                    }
+                    Ok(definition)
+                })
+                .collect::<Result<Vec<String>, DumperError>>()?;
+            out.write_all(
+                format!("CREATE TABLE {} ({});\n", name, definitions.join(", ")).as_bytes(),
+            )
+            .await?;
+        }
+
+        let text = CopyText::default();
+        for (name, id) in tables.iter() {
+            let names: Vec<&str> = columns
+                .get(id)
+                .unwrap_or(&empty)
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect();
+            if names.is_empty() {
+                continue;
+            }
+            let names = names.join(", ");
+            let rows = self
+                .client
+                .query(&format!("select {} from {}", names, name))
+                .await?;
+
+            out.write_all(format!("\nCOPY {} ({}) FROM stdin;\n", name, names).as_bytes())
+                .await?;
+            for row in rows {
+                out.write_all(text.format_row(&row).as_bytes()).await?;
+            }
+            out.write_all(b"\\.\n").await?;
+        }
+
+        out.write_all(b"\nCOMMIT;\n").await?;
+        out.flush().await?;
+        Ok(())
+    }
+
+    fn parse_column(row: TextRow) -> Result<(String, Column), DumperError> {
+        match &row[..] {
+            [Some(relation), Some(name), Some(type_oid), Some(number), Some(typmod), Some(null)] => {
+                Ok((
+                    relation.clone(),
+                    Column {
+                        name: name.clone(),
+                        type_oid: type_oid.parse()?,
+                        number: number.parse()?,
+                        typmod: typmod.parse()?,
+                        nullable: Nullable::from(null == "t"),
+                    },
+                ))
+            }
+            _ => Err(DumperError::MalformedCatalog(row)),
+        }
+    }
+
+    fn parse_type(row: TextRow) -> Result<UserType, DumperError> {
+        match &row[..] {
+            [Some(oid), Some(name), Some(kind), Some(element), relation] => Ok(UserType {
+                oid: oid.parse()?,
+                name: name.clone(),
+                kind: kind.clone(),
+                element: element.parse()?,
+                relation: relation.clone(),
+            }),
+            _ => Err(DumperError::MalformedCatalog(row)),
+        }
+    }
+
+    //Builtins print the way they are parsed, user types and their arrays use the catalog name
+    fn type_name(
+        oid: u32,
+        typmod: i32,
+        user_types: &HashMap<u32, &UserType>,
+    ) -> Result<String, DumperError> {
+        if let Some(builtin) = DeserializeTypes::from_oid(oid) {
+            return Ok(builtin.with_typmod(typmod)?.to_string());
+        }
+        match user_types.get(&oid) {
+            Some(t) if t.element != 0 => Ok(format!(
+                "{}[]",
+                Dumper::type_name(t.element, -1, user_types)?
+            )),
+            Some(t) => Ok(t.name.clone()),
+            None => Err(DumperError::UnknownType(oid)),
+        }
+    }
+
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DumperError {
+    #[error(transparent)]
+    ClientError(#[from] ClientError),
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error("Unexpected catalog row {0:?}")]
+    MalformedCatalog(TextRow),
+    #[error("No fields found for composite type {0}")]
+    MissingFields(String),
+    #[error(transparent)]
+    ParseIntError(#[from] ParseIntError),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
+    #[error("Unknown type oid {0}")]
+    UnknownType(u32),
+    #[error("Unknown type kind {0}")]
+    UnknownTypeKind(String),
+}
@@ -0,0 +1,80 @@
+//! Loads a script written by the dumper, one statement per line with COPY data following its
+//! statement up to a line of `\.`, the same way psql would run it.
+use super::client::{Client, ClientError};
+use bytes::BytesMut;
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+pub struct Restorer {
+    client: Client,
+}
+
+impl Restorer {
+    pub fn new(client: Client) -> Restorer {
+        Restorer { client }
+    }
+
+    /// Runs each statement of the script in order, stopping at the first error
+    pub async fn restore<R: AsyncBufRead + Unpin>(
+        &mut self,
+        script: R,
+    ) -> Result<usize, RestorerError> {
+        let mut lines = script.lines();
+        let mut statement = String::new();
+        let mut statements = 0;
+        while let Some(line) = lines.next_line().await? {
+            if statement.is_empty() && (line.trim().is_empty() || line.starts_with("--")) {
+                continue;
+            }
+            if !statement.is_empty() {
+                statement.push('\n');
+            }
+            statement.push_str(&line);
+            if !line.trim_end().ends_with(';') {
+                continue;
+            }
+
+            if Restorer::is_copy_in(&statement) {
+                let mut data = BytesMut::new();
+                loop {
+                    let line = lines
+                        .next_line()
+                        .await?
+                        .ok_or(RestorerError::UnterminatedCopy())?;
+                    data.extend_from_slice(line.as_bytes());
+                    data.extend_from_slice(b"\n");
+                    if line == "\\." {
+                        break;
+                    }
+                }
+                self.client.copy_in(&statement, data.freeze()).await?;
+            } else {
+                self.client.query(&statement).await?;
+            }
+            statements += 1;
+            statement.clear();
+        }
+
+        if !statement.trim().is_empty() {
+            return Err(RestorerError::UnterminatedStatement(statement));
+        }
+        Ok(statements)
+    }
+
+    fn is_copy_in(statement: &str) -> bool {
+        let statement = statement.trim_end().trim_end_matches(';').to_lowercase();
+        statement.starts_with("copy ") && statement.ends_with("from stdin")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RestorerError {
+    #[error(transparent)]
+    ClientError(#[from] ClientError),
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error("The script ended inside COPY data")]
+    UnterminatedCopy(),
+    #[error("The script ended inside a statement: {0}")]
+    UnterminatedStatement(String),
+}
@@ -2,15 +2,32 @@ pub mod analyzer;
 pub use analyzer::Analyzer;
 pub use analyzer::AnalyzerError;
 
+pub mod autovacuum;
+use autovacuum::AutoVacuum;
+
+pub mod background_writer;
+use background_writer::BackgroundWriter;
+
+pub mod checkpointer;
+use checkpointer::Checkpointer;
+
 pub mod executor;
 pub use executor::Executor;
 pub use executor::ExecutorError;
 
 pub mod io;
 use futures::pin_mut;
-use io::{IOManager, RowManager, VisibleRowManager};
+use io::{
+    CheckpointKind, DataDirectory, DataDirectoryError, DecodedTransaction, IOManager,
+    IOManagerError, LogicalDecoder, ReplicationSlot, RowManager, VacuumStats, VisibleRowManager,
+    WriteAheadLogError,
+};
 pub mod objects;
-use objects::ParseTree;
+use objects::{
+    Attribute, CopyOutput, CopyTarget, ExpressionContext, ParseExpression, ParseTree,
+    RawCopyCommand, RawSelectCommand, RawSlotFunctionCommand, ServerSettings, SessionSettings,
+    SessionSettingsError, Table,
+};
 
 pub mod planner;
 pub use planner::Planner;
@@ -25,37 +42,111 @@ pub use sql_parser::SqlParser;
 pub use sql_parser::SqlParserError;
 
 pub mod transactions;
-use transactions::{TransactionId, TransactionManager};
+use transactions::{TransactionId, TransactionManager, TransactionManagerError};
 
-use self::objects::QueryResult;
-use crate::engine::objects::TargetEntry;
-use std::ops::Deref;
+use self::objects::{QueryResult, SqlTuple};
+use crate::codec::{CopyFormat, CopyFormatError};
+use crate::constants::{
+    BuiltinSqlTypes, DateTime, DeserializeTypes, Nullable, PgBinary, PgBinaryError, PgErrorCodes,
+    SqlTypeError, SystemViews, TableDefinitions,
+};
+use crate::replication::{format_lsn, parse_lsn};
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 use tokio_stream::StreamExt;
 
+//Rows loaded by COPY are inserted this many at a time
+const COPY_BATCH_ROWS: usize = 1000;
+
 #[derive(Clone, Debug)]
 pub struct Engine {
     analyzer: Analyzer,
     executor: Executor,
+    io_manager: IOManager,
+    tran_manager: TransactionManager,
+    settings: SessionSettings,
 }
 
 impl Engine {
     pub fn new(io_manager: IOManager, tran_manager: TransactionManager) -> Engine {
-        let vis_row_man = VisibleRowManager::new(RowManager::new(io_manager), tran_manager);
+        let vis_row_man =
+            VisibleRowManager::new(RowManager::new(io_manager.clone()), tran_manager.clone());
         Engine {
             analyzer: Analyzer::new(vis_row_man.clone()),
             executor: Executor::new(vis_row_man),
+            io_manager,
+            tran_manager,
+            settings: SessionSettings::default(),
         }
     }
 
+    /// A background worker vacuuming the same storage this engine uses
+    pub fn autovacuum(&self, naptime: Duration) -> AutoVacuum {
+        AutoVacuum::new(self.executor.clone(), self.tran_manager.clone(), naptime)
+    }
+
+    /// Uses the settings the server was started with instead of the defaults
+    pub fn with_server_settings(mut self, server: ServerSettings) -> Engine {
+        self.settings.server = server;
+        self
+    }
+
+    /// A background worker flushing the same storage this engine uses between checkpoints
+    pub fn background_writer(&self) -> BackgroundWriter {
+        BackgroundWriter::new(
+            self.io_manager.clone(),
+            self.settings.server.bgwriter_delay,
+            self.settings.server.bgwriter_lru_maxpages,
+        )
+    }
+
+    /// A background worker checkpointing the same storage this engine uses
+    pub fn checkpointer(&self) -> Checkpointer {
+        Checkpointer::new(
+            self.io_manager.clone(),
+            self.settings.server.checkpoint_timeout,
+        )
+    }
+
+    /// The settings of the session this engine is serving
+    pub fn io_manager(&self) -> &IOManager {
+        &self.io_manager
+    }
+
+    pub fn settings(&self) -> &SessionSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut SessionSettings {
+        &mut self.settings
+    }
+
+    /// Runs every statement in the query in order, stopping at the first error.
     pub async fn process_query(
         &mut self,
         tran_id: TransactionId,
         query: String,
-    ) -> Result<QueryResult, EngineError> {
-        //Parse it - I need to figure out if I should do statement splitting here
-        let parse_tree = SqlParser::parse(&query)?;
+    ) -> Result<Vec<QueryResult>, EngineError> {
+        //Parse it, all statements are parsed before any are run
+        let parse_trees = SqlParser::parse(&query)?;
+
+        let mut results = vec![];
+        for parse_tree in parse_trees {
+            results.push(self.process_statement(tran_id, parse_tree).await?);
+        }
+        Ok(results)
+    }
 
+    pub async fn process_statement(
+        &mut self,
+        tran_id: TransactionId,
+        parse_tree: ParseTree,
+    ) -> Result<QueryResult, EngineError> {
+        if TransactionManager::is_read_only(tran_id) && Engine::writes(&parse_tree) {
+            return Err(EngineError::ReadOnlyTransaction());
+        }
         if Engine::should_bypass_planning(&parse_tree) {
             let output_rows = self.executor.execute_utility(tran_id, parse_tree).await?;
             return Ok(QueryResult {
@@ -64,8 +155,74 @@ impl Engine {
             });
         }
 
+        match parse_tree {
+            ParseTree::Promote => {
+                self.io_manager.promote().await?;
+                self.tran_manager.promote().await?;
+                return Ok(QueryResult {
+                    columns: vec![],
+                    rows: vec![],
+                });
+            }
+            ParseTree::Checkpoint => {
+                self.io_manager
+                    .checkpoint(CheckpointKind::Requested)
+                    .await?;
+                return Ok(QueryResult {
+                    columns: vec![],
+                    rows: vec![],
+                });
+            }
+            ParseTree::Set(set) => {
+                self.settings.set(&set.name, set.value.as_deref())?;
+                return Ok(QueryResult {
+                    columns: vec![],
+                    rows: vec![],
+                });
+            }
+            ParseTree::Show(show) => {
+                let value = self.settings.show(&show.name)?;
+                return Ok(QueryResult {
+                    columns: vec![(
+                        SessionSettings::canonical_name(&show.name)?.to_string(),
+                        DeserializeTypes::Text,
+                    )],
+                    rows: vec![SqlTuple(vec![Some(BuiltinSqlTypes::Text(value))])],
+                });
+            }
+            ParseTree::Repack(repack) => {
+                let result = self.executor.repack(tran_id, repack.table_name).await?;
+                return Ok(Engine::vacuum_result(vec![result]));
+            }
+            ParseTree::Vacuum(vacuum) => {
+                let results = self.executor.vacuum(tran_id, vacuum.table_name).await?;
+                return Ok(Engine::vacuum_result(results));
+            }
+            ParseTree::SlotFunction(call) => {
+                return self.slot_function(call).await;
+            }
+            //Transaction blocks belong to the connection, see ClientProcessor
+            ParseTree::Begin(_) | ParseTree::Commit | ParseTree::Rollback => {
+                return Ok(QueryResult {
+                    columns: vec![],
+                    rows: vec![],
+                });
+            }
+            ParseTree::Copy(_) => return Err(EngineError::CopyWithoutData()),
+            _ => {}
+        }
+
+        //Everything in the statement sees the same transaction start and session settings
+        let context = ExpressionContext {
+            transaction_start: DateTime::from_system_time(
+                self.tran_manager.get_start_time(tran_id).await?,
+            ),
+            time_zone: self.settings.time_zone,
+            bytea_output: self.settings.bytea_output,
+        };
+
         //Analyze it
-        let query_tree = self.analyzer.analyze(tran_id, parse_tree).await?;
+        let query_tree = self.analyzer.analyze(tran_id, parse_tree, &context).await?;
 
         //Rewrite it - noop for right now
         let rewrite_tree = Rewriter::rewrite(query_tree.clone())?;
@@ -75,7 +232,10 @@ impl Engine {
 
         //Execute it, single shot for now
         let mut result = vec![];
-        let execute_stream = self.executor.clone().execute(tran_id, planned_stmt);
+        let execute_stream = self
+            .executor
+            .clone()
+            .execute(tran_id, context, planned_stmt);
         pin_mut!(execute_stream);
 
         while let Some(value) = execute_stream.next().await {
@@ -85,22 +245,378 @@ impl Engine {
         let output_columns = query_tree
             .targets
             .into_iter()
-            .map(|t| match t {
-                TargetEntry::Parameter(p) => p.name,
+            .map(|t| {
+                let sql_type = t.expression.sql_type();
+                (t.name, sql_type)
             })
             .collect();
 
-        return Ok(QueryResult {
+        Ok(QueryResult {
             columns: output_columns,
             rows: result,
-        });
+        })
     }
 
-    fn should_bypass_planning(parse_tree: &ParseTree) -> bool {
-        match parse_tree.deref() {
-            ParseTree::CreateTable(_) => true,
-            _ => false,
+    /// The format the rows of a COPY FROM are in and how many columns each has. The table,
+    /// columns and options are all checked before the client is asked to send anything.
+    pub async fn copy_in_format(
+        &self,
+        tran_id: TransactionId,
+        copy: &RawCopyCommand,
+    ) -> Result<(CopyFormat, usize), EngineError> {
+        if TransactionManager::is_read_only(tran_id) {
+            return Err(EngineError::ReadOnlyTransaction());
+        }
+        let format = CopyFormat::from_options(&copy.options)?;
+        let (table, columns) = self.copy_columns(tran_id, copy).await?;
+        if TableDefinitions::find(table.id).is_some() || SystemViews::find(table.id).is_some() {
+            return Err(EngineError::CopySystemTable(table.name.clone()));
+        }
+
+        //Anything not loaded is null, which the table has to allow
+        if let Some(a) = table
+            .attributes
+            .iter()
+            .enumerate()
+            .find(|(i, a)| !columns.contains(i) && a.nullable == Nullable::NotNull)
+            .map(|(_, a)| a)
+        {
+            return Err(AnalyzerError::MissingColumn(a.clone()).into());
+        }
+        Ok((format, columns.len()))
+    }
+
+    /// Loads the rows of a COPY FROM, returning how many there were. Values are converted from
+    /// the format's text or binary form and inserted in batches instead of a statement each.
+    pub async fn copy_from(
+        &mut self,
+        tran_id: TransactionId,
+        copy: RawCopyCommand,
+        data: Bytes,
+    ) -> Result<usize, EngineError> {
+        let (format, width) = self.copy_in_format(tran_id, &copy).await?;
+        let (table, columns) = self.copy_columns(tran_id, &copy).await?;
+
+        let mut count = 0;
+        let mut batch = Vec::with_capacity(COPY_BATCH_ROWS);
+        for (i, row) in format.parse_rows(data)?.into_iter().enumerate() {
+            if row.len() != width {
+                return Err(EngineError::CopyRowWidth(i + 1, row.len(), width));
+            }
+            let mut values = vec![None; table.attributes.len()];
+            for (field, column) in row.into_iter().zip(columns.iter()) {
+                let sql_type = &table.attributes[*column].sql_type;
+                values[*column] = match field {
+                    Some(f) if format.is_binary() => Some(PgBinary::receive(sql_type, f)?),
+                    Some(f) => Some(BuiltinSqlTypes::parse_in_zone(
+                        sql_type.clone(),
+                        String::from_utf8(f.to_vec())?,
+                        &self.settings.time_zone,
+                    )?),
+                    None => None,
+                };
+            }
+            batch.push(Arc::new(SqlTuple(values)));
+
+            if batch.len() == COPY_BATCH_ROWS {
+                count += self
+                    .executor
+                    .insert_rows(tran_id, table.clone(), std::mem::take(&mut batch))
+                    .await?;
+            }
         }
+        count += self.executor.insert_rows(tran_id, table, batch).await?;
+        Ok(count)
+    }
+
+    /// Loads a COPY FROM a file the server can read
+    pub async fn copy_from_file(
+        &mut self,
+        tran_id: TransactionId,
+        copy: RawCopyCommand,
+    ) -> Result<usize, EngineError> {
+        let path = match &copy.target {
+            CopyTarget::File(f) => f.clone(),
+            _ => return Err(EngineError::CopyWithoutData()),
+        };
+        if !self.settings.server.allow_server_files {
+            return Err(EngineError::CopyFileNotAllowed());
+        }
+        self.copy_in_format(tran_id, &copy).await?;
+        let data = tokio::fs::read(&path)
+            .await
+            .map_err(|e| EngineError::CopyFile(path, e))?;
+        self.copy_from(tran_id, copy, Bytes::from(data)).await
+    }
+
+    /// The rows of a COPY TO STDOUT, read the same as a select of the columns would
+    pub async fn copy_to(
+        &mut self,
+        tran_id: TransactionId,
+        copy: RawCopyCommand,
+    ) -> Result<CopyOutput, EngineError> {
+        let format = CopyFormat::from_options(&copy.options)?;
+        let (table, columns) = self.copy_columns(tran_id, &copy).await?;
+        let attributes: Vec<&Attribute> = columns.iter().map(|c| &table.attributes[*c]).collect();
+        let names: Vec<String> = attributes.iter().map(|a| a.name.clone()).collect();
+
+        let select = RawSelectCommand {
+            columns: names
+                .iter()
+                .map(|n| ParseExpression::Identifier(n.clone()))
+                .collect(),
+            table: Some(table.name.clone()),
+        };
+        let result = self
+            .process_statement(tran_id, ParseTree::Select(select))
+            .await?;
+
+        let mut data = vec![];
+        data.extend(format.header(&names));
+        for row in result.rows.iter() {
+            let mut values = Vec::with_capacity(row.0.len());
+            for (value, attribute) in row.0.iter().zip(attributes.iter()) {
+                values.push(match value {
+                    Some(v) if format.is_binary() => Some(PgBinary::send(v, &attribute.sql_type)?),
+                    Some(v) => Some(Bytes::from(
+                        v.to_string_with(&self.settings.time_zone, self.settings.bytea_output),
+                    )),
+                    None => None,
+                });
+            }
+            data.push(format.format_row(&values)?);
+        }
+        data.extend(format.trailer());
+
+        Ok(CopyOutput {
+            binary: format.is_binary(),
+            columns: columns.len(),
+            rows: result.rows.len(),
+            data,
+        })
+    }
+
+    //The table and the position of each column the COPY covers, in the order they are sent
+    async fn copy_columns(
+        &self,
+        tran_id: TransactionId,
+        copy: &RawCopyCommand,
+    ) -> Result<(Arc<Table>, Vec<usize>), EngineError> {
+        let table = self
+            .analyzer
+            .get_definition(tran_id, copy.table_name.clone())
+            .await?;
+        let names = match &copy.columns {
+            Some(names) => names,
+            None => {
+                let columns = (0..table.attributes.len()).collect();
+                return Ok((table, columns));
+            }
+        };
+
+        let unknown: Vec<String> = names
+            .iter()
+            .filter(|n| !table.attributes.iter().any(|a| &a.name == *n))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            return Err(AnalyzerError::UnknownColumns(unknown).into());
+        }
+        let mut columns = vec![];
+        for name in names {
+            let position = table
+                .attributes
+                .iter()
+                .position(|a| &a.name == name)
+                .unwrap_or_default();
+            if columns.contains(&position) {
+                return Err(EngineError::CopyDuplicateColumn(name.clone()));
+            }
+            columns.push(position);
+        }
+        Ok((table, columns))
+    }
+
+    //Like postgres' functions of the same names, changes are read with the test_decoding plugin
+    async fn slot_function(
+        &mut self,
+        call: RawSlotFunctionCommand,
+    ) -> Result<QueryResult, EngineError> {
+        let args = call
+            .args
+            .iter()
+            .map(Engine::slot_function_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+        let arg = |i: usize| -> Result<String, EngineError> {
+            args.get(i)
+                .cloned()
+                .flatten()
+                .ok_or_else(|| EngineError::BadSlotFunctionArguments(call.function.clone()))
+        };
+        let directory = self.io_manager.slot_directory()?.clone();
+        let text = |t: String| Some(BuiltinSqlTypes::Text(t));
+
+        match call.function.as_str() {
+            "pg_create_logical_replication_slot" => {
+                let slot = directory.create_slot(&arg(0)?, &arg(1)?).await?;
+                Ok(QueryResult {
+                    columns: vec![
+                        ("slot_name".to_string(), DeserializeTypes::Text),
+                        ("lsn".to_string(), DeserializeTypes::Text),
+                    ],
+                    rows: vec![SqlTuple(vec![
+                        text(slot.name),
+                        text(format_lsn(slot.confirmed_flush)),
+                    ])],
+                })
+            }
+            "pg_drop_replication_slot" => {
+                directory.drop_slot(&arg(0)?).await?;
+                Ok(QueryResult {
+                    columns: vec![(call.function, DeserializeTypes::Text)],
+                    rows: vec![SqlTuple(vec![None])],
+                })
+            }
+            _ => {
+                let upto_lsn = match args.get(1).cloned().flatten() {
+                    Some(l) => Some(parse_lsn(&l).ok_or_else(|| {
+                        EngineError::BadSlotFunctionArguments(call.function.clone())
+                    })?),
+                    None => None,
+                };
+                let upto_changes = match args.get(2).cloned().flatten() {
+                    Some(n) => Some(n.parse::<usize>().map_err(|_| {
+                        EngineError::BadSlotFunctionArguments(call.function.clone())
+                    })?),
+                    None => None,
+                };
+                let slot = directory.acquire_slot(&arg(0)?).await?;
+                let consume = call.function == "pg_logical_slot_get_changes";
+                let changes = self
+                    .slot_changes(&directory, &slot, consume, upto_lsn, upto_changes)
+                    .await;
+                directory.release_slot(&slot.name).await;
+                changes
+            }
+        }
+    }
+
+    //Whole transactions are returned, the last one can go past the number of changes asked for
+    async fn slot_changes(
+        &mut self,
+        directory: &DataDirectory,
+        slot: &ReplicationSlot,
+        consume: bool,
+        upto_lsn: Option<u64>,
+        upto_changes: Option<usize>,
+    ) -> Result<QueryResult, EngineError> {
+        if slot.plugin != "test_decoding" {
+            return Err(EngineError::BinaryOutputPlugin(slot.plugin.clone()));
+        }
+
+        let mut decoder = LogicalDecoder::new(slot);
+        let mut rows = vec![];
+        let mut last: Option<DecodedTransaction> = None;
+        for tran in decoder
+            .decode(directory.wal(), &mut self.tran_manager)
+            .await?
+        {
+            if tran.end_lsn <= slot.confirmed_flush {
+                continue;
+            }
+            //Every transaction is logged, reads included, so only the ones that changed rows
+            //are shown. They are still consumed.
+            if tran.changes.is_empty() {
+                last = Some(tran);
+                continue;
+            }
+            if upto_lsn.is_some_and(|u| tran.commit_lsn >= u)
+                || upto_changes.is_some_and(|u| rows.len() >= u)
+            {
+                break;
+            }
+            for (lsn, line) in tran.test_decoding() {
+                rows.push(SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Text(format_lsn(lsn))),
+                    Some(BuiltinSqlTypes::BigInt(tran.tran_id.get_u64() as i64)),
+                    Some(BuiltinSqlTypes::Text(line)),
+                ]));
+            }
+            last = Some(tran);
+        }
+
+        if let (true, Some(last)) = (consume, last) {
+            directory
+                .advance_slot(&slot.name, last.end_lsn, last.restart_lsn)
+                .await?;
+        }
+
+        Ok(QueryResult {
+            columns: vec![
+                ("lsn".to_string(), DeserializeTypes::Text),
+                ("xid".to_string(), DeserializeTypes::BigInt),
+                ("data".to_string(), DeserializeTypes::Text),
+            ],
+            rows,
+        })
+    }
+
+    //Only literals, a cast like '0/0'::pg_lsn is taken as the literal
+    fn slot_function_arg(arg: &ParseExpression) -> Result<Option<String>, EngineError> {
+        match arg {
+            ParseExpression::String(s) | ParseExpression::Number(s) => Ok(Some(s.clone())),
+            ParseExpression::Null() => Ok(None),
+            ParseExpression::Cast(inner, _) => Engine::slot_function_arg(inner),
+            _ => Err(EngineError::BadSlotFunctionArguments(format!("{:?}", arg))),
+        }
+    }
+
+    //One row per table vacuumed or repacked
+    fn vacuum_result(results: Vec<(String, VacuumStats)>) -> QueryResult {
+        let columns = vec![
+            ("table_name".to_string(), DeserializeTypes::Text),
+            ("pages_scanned".to_string(), DeserializeTypes::BigInt),
+            ("rows_removed".to_string(), DeserializeTypes::BigInt),
+            ("rows_remaining".to_string(), DeserializeTypes::BigInt),
+            ("bytes_freed".to_string(), DeserializeTypes::BigInt),
+        ];
+
+        let rows = results
+            .into_iter()
+            .map(|(name, stats)| {
+                SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Text(name)),
+                    Some(BuiltinSqlTypes::BigInt(stats.pages_scanned as i64)),
+                    Some(BuiltinSqlTypes::BigInt(stats.rows_removed as i64)),
+                    Some(BuiltinSqlTypes::BigInt(stats.rows_remaining as i64)),
+                    Some(BuiltinSqlTypes::BigInt(stats.bytes_freed as i64)),
+                ])
+            })
+            .collect();
+
+        QueryResult { columns, rows }
+    }
+
+    //Statements a read only transaction can't run, during recovery that is all of them
+    fn writes(parse_tree: &ParseTree) -> bool {
+        matches!(
+            parse_tree,
+            ParseTree::Copy(_)
+                | ParseTree::CreateTable(_)
+                | ParseTree::CreateType(_)
+                | ParseTree::DropTable(_)
+                | ParseTree::Insert(_)
+                | ParseTree::Repack(_)
+                | ParseTree::SlotFunction(_)
+                | ParseTree::Vacuum(_)
+        )
+    }
+
+    fn should_bypass_planning(parse_tree: &ParseTree) -> bool {
+        matches!(
+            parse_tree,
+            ParseTree::CreateTable(_) | ParseTree::CreateType(_) | ParseTree::DropTable(_)
+        )
     }
 }
 
@@ -108,16 +624,78 @@ impl Engine {
 pub enum EngineError {
     #[error(transparent)]
     AnalyzerError(#[from] AnalyzerError),
+    #[error("Bad arguments to {0}")]
+    BadSlotFunctionArguments(String),
+    #[error("Output plugin {0} produces binary output, changes can only be read as text")]
+    BinaryOutputPlugin(String),
+    #[error("column \"{0}\" specified more than once")]
+    CopyDuplicateColumn(String),
+    #[error("could not read file \"{0}\": {1}")]
+    CopyFile(String, #[source] std::io::Error),
+    #[error("COPY from a file is only allowed if the server is started with allow_server_files on, use COPY FROM STDIN instead")]
+    CopyFileNotAllowed(),
+    #[error(transparent)]
+    CopyFormatError(#[from] CopyFormatError),
+    #[error("COPY row {0} has {1} columns, expected {2}")]
+    CopyRowWidth(usize, usize, usize),
+    #[error("cannot copy into system table {0}")]
+    CopySystemTable(String),
+    #[error("COPY needs a client to send or receive the rows")]
+    CopyWithoutData(),
+    #[error(transparent)]
+    DataDirectoryError(#[from] DataDirectoryError),
     #[error(transparent)]
     ExecutorError(#[from] ExecutorError),
     #[error(transparent)]
+    IOManagerError(#[from] IOManagerError),
+    #[error(transparent)]
     QueryNotUtf8(#[from] std::string::FromUtf8Error),
+    #[error("Cannot change anything in a read-only transaction")]
+    ReadOnlyTransaction(),
     #[error(transparent)]
     RewriterError(#[from] RewriterError),
     #[error(transparent)]
     ParseError(#[from] SqlParserError),
     #[error(transparent)]
+    PgBinaryError(#[from] PgBinaryError),
+    #[error(transparent)]
     PlannerError(#[from] PlannerError),
+    #[error(transparent)]
+    SessionSettingsError(#[from] SessionSettingsError),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
+    #[error(transparent)]
+    TransactionManagerError(#[from] TransactionManagerError),
+    #[error(transparent)]
+    WriteAheadLogError(#[from] WriteAheadLogError),
+}
+
+impl EngineError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            EngineError::AnalyzerError(e) => e.pg_error_code(),
+            EngineError::BadSlotFunctionArguments(_) => PgErrorCodes::InvalidParameterValue,
+            EngineError::BinaryOutputPlugin(_) => PgErrorCodes::FeatureNotSupported,
+            EngineError::CopyDuplicateColumn(_) => PgErrorCodes::DuplicateColumn,
+            EngineError::CopyFile(_, e) if e.kind() == std::io::ErrorKind::NotFound => {
+                PgErrorCodes::UndefinedFile
+            }
+            EngineError::CopyFile(_, _) => PgErrorCodes::SystemError,
+            EngineError::CopyFileNotAllowed() => PgErrorCodes::InsufficientPrivilege,
+            EngineError::CopyFormatError(e) => e.pg_error_code(),
+            EngineError::CopyRowWidth(_, _, _) => PgErrorCodes::BadCopyFileFormat,
+            EngineError::CopySystemTable(_) => PgErrorCodes::InsufficientPrivilege,
+            EngineError::CopyWithoutData() => PgErrorCodes::FeatureNotSupported,
+            EngineError::DataDirectoryError(e) => e.pg_error_code(),
+            EngineError::ExecutorError(e) => e.pg_error_code(),
+            EngineError::IOManagerError(e) => e.pg_error_code(),
+            EngineError::PgBinaryError(e) => e.pg_error_code(),
+            EngineError::ReadOnlyTransaction() => PgErrorCodes::ReadOnlySqlTransaction,
+            EngineError::SessionSettingsError(e) => e.pg_error_code(),
+            EngineError::SqlTypeError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
 }
 
 #[cfg(test)]
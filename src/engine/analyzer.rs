@@ -1,10 +1,15 @@
 //! The analyzer should check that tables and columns exist before allowing a query to proceed.
 //! More features will come I'm sure
 mod definition_lookup;
-use definition_lookup::{DefinitionLookup, DefinitionLookupError};
+pub use definition_lookup::DefinitionLookup;
+pub use definition_lookup::DefinitionLookupError;
 
-use crate::constants::{BuiltinSqlTypes, Nullable, SqlTypeError};
-use crate::engine::objects::{JoinType, SqlTuple, TargetEntry};
+mod expression_analyzer;
+pub use expression_analyzer::ExpressionAnalyzer;
+pub use expression_analyzer::ExpressionAnalyzerError;
+
+use crate::constants::{Nullable, PgErrorCodes, SqlTypeError, SystemViews};
+use crate::engine::objects::{Expression, ExpressionContext, JoinType, SqlTuple, TargetEntry};
 
 use super::io::VisibleRowManager;
 use super::objects::{
@@ -32,35 +37,71 @@ impl Analyzer {
         &self,
         tran_id: TransactionId,
         parse_tree: ParseTree,
+        context: &ExpressionContext,
     ) -> Result<QueryTree, AnalyzerError> {
         match parse_tree {
             ParseTree::Insert(i) => {
-                return self.insert_processing(tran_id, i).await;
+                return self.insert_processing(tran_id, i, context).await;
             }
             ParseTree::Select(i) => {
-                return self.select_processing(tran_id, i).await;
+                return self.select_processing(tran_id, i, context).await;
             }
-            _ => return Err(AnalyzerError::NotImplemented()),
+            _ => Err(AnalyzerError::NotImplemented()),
         }
     }
 
+    /// The table with the name, system tables included
+    pub async fn get_definition(
+        &self,
+        tran_id: TransactionId,
+        name: String,
+    ) -> Result<Arc<Table>, AnalyzerError> {
+        Ok(self.dl.get_definition(tran_id, name).await?)
+    }
+
     async fn insert_processing(
         &self,
         tran_id: TransactionId,
         raw_insert: RawInsertCommand,
+        context: &ExpressionContext,
     ) -> Result<QueryTree, AnalyzerError> {
         let definition = self
             .dl
             .get_definition(tran_id, raw_insert.table_name)
             .await?;
+        if SystemViews::find(definition.id).is_some() {
+            return Err(AnalyzerError::InsertIntoView(definition.name.clone()));
+        }
 
-        let (tbl_cols, val_cols) = Analyzer::validate_columns(
+        let columns = Analyzer::validate_columns(
             definition.clone(),
             raw_insert.provided_columns,
             raw_insert.provided_values,
         )?;
 
-        let anon_tbl = RangeRelation::AnonymousTable(Arc::new(vec![SqlTuple(val_cols)]));
+        //Values are computed against the single empty row of the anonymous table
+        let user_types = self.dl.get_user_types(tran_id).await?;
+        let mut targets = vec![];
+        for (a, value) in columns {
+            let expression = match value {
+                Some(v) => {
+                    ExpressionAnalyzer::analyze_assignment(&[], &user_types, context, v, &a)?
+                }
+                None => Expression::Constant(None, a.sql_type),
+            };
+            if expression.contains_aggregate() {
+                return Err(AnalyzerError::AggregateInValues());
+            }
+            if expression.contains_set_function() {
+                return Err(AnalyzerError::SetFunctionInValues());
+            }
+            targets.push(TargetEntry {
+                name: a.name,
+                expression,
+            });
+        }
+
+        let anon_tbl = RangeRelation::AnonymousTable(Arc::new(vec![SqlTuple(vec![])]));
         let target_tbl = RangeRelation::Table(RangeRelationTable {
             alias: None,
             table: definition.clone(),
@@ -70,12 +111,9 @@ impl Analyzer {
         Ok(QueryTree {
             command_type: CommandType::Insert,
             //Insert columns will be the target
-            targets: tbl_cols
-                .into_iter()
-                .map(|d| TargetEntry::Parameter(d))
-                .collect(),
+            targets,
             range_tables: vec![target_tbl.clone(), anon_tbl.clone()],
-            joins: vec![((JoinType::Inner, target_tbl, anon_tbl))],
+            joins: vec![(JoinType::Inner, target_tbl, anon_tbl)],
         })
     }
 
@@ -83,105 +121,100 @@ impl Analyzer {
         &self,
         tran_id: TransactionId,
         raw_select: RawSelectCommand,
+        context: &ExpressionContext,
     ) -> Result<QueryTree, AnalyzerError> {
-        let definition = self.dl.get_definition(tran_id, raw_select.table).await?;
+        //Without a from clause the targets are computed once against an empty row
+        let (columns, range_table) = match raw_select.table {
+            Some(t) => {
+                let definition = self.dl.get_definition(tran_id, t).await?;
+                (
+                    definition.attributes.clone(),
+                    RangeRelation::Table(RangeRelationTable {
+                        table: definition,
+                        alias: None,
+                    }),
+                )
+            }
+            None => (
+                vec![],
+                RangeRelation::AnonymousTable(Arc::new(vec![SqlTuple(vec![])])),
+            ),
+        };
 
         //Need to valid the columns asked for exist
+        let user_types = self.dl.get_user_types(tran_id).await?;
         let mut targets = vec![];
-        'outer: for rcol in raw_select.columns {
-            for c in definition.attributes.as_slice() {
-                if rcol == c.name {
-                    targets.push(TargetEntry::Parameter(c.clone()));
-                    continue 'outer;
-                }
+        for rcol in raw_select.columns {
+            targets.push(TargetEntry {
+                name: ExpressionAnalyzer::output_name(&rcol),
+                expression: ExpressionAnalyzer::analyze(&columns, &user_types, context, rcol)?,
+            });
+        }
+
+        //Without a group by an aggregate turns the whole input into one row
+        if targets.iter().any(|t| t.expression.contains_aggregate()) {
+            if let Some(c) = targets.iter().find_map(|t| t.expression.ungrouped_column()) {
+                return Err(AnalyzerError::UngroupedColumn(c.name.clone()));
             }
-            return Err(AnalyzerError::UnknownColumn(rcol));
         }
 
         //We should be good to build the query tree if we got here
         Ok(QueryTree {
             command_type: CommandType::Select,
             targets,
-            range_tables: vec![RangeRelation::Table(RangeRelationTable {
-                table: definition,
-                alias: None,
-            })],
+            range_tables: vec![range_table],
             joins: vec![],
         })
     }
 
-    /// This function will sort the columns and values and convert them
+    /// This function will sort the values to match the table's columns, columns without
+    /// a value get None
     fn validate_columns(
         table: Arc<Table>,
         provided_columns: Option<Vec<String>>,
         provided_values: Vec<ParseExpression>,
-    ) -> Result<(Vec<Attribute>, Vec<Option<BuiltinSqlTypes>>), AnalyzerError> {
-        let columns = match provided_columns {
-            Some(pc) => {
-                //Can't assume we got the columns in order so we'll have to reorder to match the table
-                let mut provided_pair: HashMap<String, ParseExpression> =
-                    pc.into_iter().zip(provided_values).collect();
-                let mut result = vec![];
-                for a in table.attributes.clone() {
-                    match provided_pair.get(&a.name) {
-                        Some(ppv) => {
-                            result.push((a.clone(), Some(ppv.clone())));
-                            provided_pair.remove(&a.name);
-                        }
-                        None => match a.nullable {
-                            Nullable::NotNull => return Err(AnalyzerError::MissingColumn(a)),
-                            Nullable::Null => result.push((a, None)),
-                        },
-                    }
-                }
-
-                if !provided_pair.is_empty() {
-                    return Err(AnalyzerError::UnknownColumns(
-                        provided_pair.keys().map(|s| s.clone()).collect(),
-                    ));
-                }
-
-                result
-            }
+    ) -> Result<Vec<(Attribute, Option<ParseExpression>)>, AnalyzerError> {
+        let provided_names = match provided_columns {
+            Some(pc) => pc,
             None => {
                 //Assume we are in order of the table columns
                 table
                     .attributes
-                    .clone()
-                    .into_iter()
-                    .zip(provided_values)
-                    .map(|(a, s)| (a, Some(s)))
+                    .iter()
+                    .take(provided_values.len())
+                    .map(|a| a.name.clone())
                     .collect()
             }
         };
 
-        Analyzer::convert_into_types(columns)
-    }
+        if provided_values.len() != provided_names.len() {
+            return Err(AnalyzerError::ValueVsColumnMismatch(
+                provided_values.len(),
+                provided_names.len(),
+            ));
+        }
 
-    fn convert_into_types(
-        provided: Vec<(Attribute, Option<ParseExpression>)>,
-    ) -> Result<(Vec<Attribute>, Vec<Option<BuiltinSqlTypes>>), AnalyzerError> {
-        let mut tbl_cols = vec![];
-        let mut val_cols = vec![];
-        for (a, s) in provided {
-            match s {
-                Some(s2) => match s2 {
-                    ParseExpression::String(s3) => {
-                        tbl_cols.push(a.clone());
-                        val_cols.push(Some(BuiltinSqlTypes::parse(a.sql_type, s3)?));
-                    }
-                    ParseExpression::Null() => {
-                        tbl_cols.push(a);
-                        val_cols.push(None);
-                    }
+        //Can't assume we got the columns in order so we'll have to reorder to match the table
+        let mut provided_pair: HashMap<String, ParseExpression> =
+            provided_names.into_iter().zip(provided_values).collect();
+        let mut result = vec![];
+        for a in table.attributes.clone() {
+            match provided_pair.remove(&a.name) {
+                Some(ppv) => result.push((a, Some(ppv))),
+                None => match a.nullable {
+                    Nullable::NotNull => return Err(AnalyzerError::MissingColumn(a)),
+                    Nullable::Null => result.push((a, None)),
                 },
-                None => {
-                    tbl_cols.push(a);
-                    val_cols.push(None);
-                }
             }
         }
-        Ok((tbl_cols, val_cols))
+
+        if !provided_pair.is_empty() {
+            return Err(AnalyzerError::UnknownColumns(
+                provided_pair.keys().cloned().collect(),
+            ));
+        }
+
+        Ok(result)
     }
 }
 
@@ -190,6 +223,8 @@ pub enum AnalyzerError {
     #[error(transparent)]
     DefinitionLookupError(#[from] DefinitionLookupError),
     #[error(transparent)]
+    ExpressionAnalyzerError(#[from] ExpressionAnalyzerError),
+    #[error(transparent)]
     SqlTypeError(#[from] SqlTypeError),
     #[error("Provided columns {0:?} does not match the underlying table columns {1:?}")]
     ColumnVsColumnMismatch(Vec<String>, Vec<String>),
@@ -197,10 +232,30 @@ pub enum AnalyzerError {
     ValueVsColumnMismatch(usize, usize),
     #[error("Missing required column {0}")]
     MissingColumn(Attribute),
-    #[error("Unknown column received {0}")]
-    UnknownColumn(String),
     #[error("Unknown columns received {0:?}")]
     UnknownColumns(Vec<String>),
+    #[error("cannot insert into view \"{0}\"")]
+    InsertIntoView(String),
     #[error("Not implemented")]
     NotImplemented(),
+    #[error("aggregate functions are not allowed in VALUES")]
+    AggregateInValues(),
+    #[error("set-returning functions are not allowed in VALUES")]
+    SetFunctionInValues(),
+    #[error(
+        "column \"{0}\" must appear in the GROUP BY clause or be used in an aggregate function"
+    )]
+    UngroupedColumn(String),
+}
+
+impl AnalyzerError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            AnalyzerError::DefinitionLookupError(e) => e.pg_error_code(),
+            AnalyzerError::ExpressionAnalyzerError(e) => e.pg_error_code(),
+            AnalyzerError::SqlTypeError(e) => e.pg_error_code(),
+            AnalyzerError::InsertIntoView(_) => PgErrorCodes::ObjectNotInPrerequisiteState,
+            _ => PgErrorCodes::SystemError,
+        }
+    }
 }
@@ -1,17 +1,20 @@
 //! This command will look up ONLY hardcoded table definitions first,
 //! should be able to fallback to reading new ones off disk
+//!
+//! Types work the same way, the builtin types are known by OID and user types are read from
+//! pg_type with their labels in pg_enum or fields in pg_attribute.
 
 use super::super::super::constants::{
-    BuiltinSqlTypes, DeserializeTypes, SqlTypeError, TableDefinitions,
+    BuiltinSqlTypes, CompositeField, CompositeType, DeserializeTypes, EnumType, SqlTypeError,
+    SystemViews, TableDefinitions,
 };
 use super::super::io::row_formats::{RowData, RowDataError};
 use super::super::io::{VisibleRowManager, VisibleRowManagerError};
 use super::super::objects::{Attribute, Table, TableError};
 use super::super::transactions::TransactionId;
-use crate::constants::Nullable;
+use crate::constants::{Nullable, PgErrorCodes};
 use std::convert::TryFrom;
 use std::num::TryFromIntError;
-use std::str::FromStr;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::pin;
@@ -40,6 +43,11 @@ impl DefinitionLookup {
                 return Ok(i.value());
             }
         }
+        for v in SystemViews::VALUES {
+            if v.value().name == name {
+                return Ok(v.value());
+            }
+        }
 
         //TODO not happy with how many strings there are
         let tbl_row = self.get_table_row(tran_id, name).await?;
@@ -53,15 +61,18 @@ impl DefinitionLookup {
         };
 
         let tbl_columns = self.get_table_columns(tran_id, table_id).await?;
+        let mut user_types = None;
         let mut tbl_attrs = vec![];
         for c in tbl_columns {
-            let c_name = match c.get_column_not_null("attname".to_string())? {
-                BuiltinSqlTypes::Text(t) => t,
-                _ => return Err(DefinitionLookupError::ColumnWrongType()),
-            };
-            let c_type = match c.get_column_not_null("atttypid".to_string())? {
-                BuiltinSqlTypes::Text(t) => t,
-                _ => return Err(DefinitionLookupError::ColumnWrongType()),
+            let (c_name, c_oid, c_typmod) = DefinitionLookup::column_type(&c)?;
+            let c_type = match DeserializeTypes::from_oid(c_oid) {
+                Some(t) => t,
+                None => {
+                    if user_types.is_none() {
+                        user_types = Some(self.get_user_types(tran_id).await?);
+                    }
+                    DefinitionLookup::find_type(c_oid, user_types.as_deref().unwrap_or(&[]))?
+                }
             };
 
             let c_null = match c.get_column_not_null("attnotnull".to_string())? {
@@ -73,7 +84,7 @@ impl DefinitionLookup {
                 //TODO: Oops didn't store the column's id
                 table_id,
                 c_name,
-                DeserializeTypes::from_str(&c_type)?,
+                c_type.with_typmod(c_typmod)?,
                 c_null,
             ));
         }
@@ -83,7 +94,181 @@ impl DefinitionLookup {
         )))
     }
 
-    async fn get_table_row(
+    /// Every table in pg_class, the system tables were put there by initdb
+    pub async fn get_table_names(
+        &self,
+        tran_id: TransactionId,
+    ) -> Result<Vec<String>, DefinitionLookupError> {
+        let mut names = vec![];
+
+        let pg_class = TableDefinitions::PgClass.value();
+        let row_stream = self.vis_row_man.clone().get_stream(tran_id, pg_class);
+        pin!(row_stream);
+        while let Some(row_res) = row_stream.next().await {
+            match row_res?.get_column_not_null("name".to_string())? {
+                BuiltinSqlTypes::Text(t) => names.push(t),
+                _ => return Err(DefinitionLookupError::ColumnWrongType()),
+            }
+        }
+        Ok(names)
+    }
+
+    /// Every type made with CREATE TYPE, in the order they were made
+    pub async fn get_user_types(
+        &self,
+        tran_id: TransactionId,
+    ) -> Result<Vec<DeserializeTypes>, DefinitionLookupError> {
+        let mut type_rows = vec![];
+        let pg_type = TableDefinitions::PgType.value();
+        let row_stream = self.vis_row_man.clone().get_stream(tran_id, pg_type);
+        pin!(row_stream);
+        while let Some(row_res) = row_stream.next().await {
+            let row = row_res?;
+            let oid = DefinitionLookup::get_oid(&row, "oid")?;
+            let typtype = match row.get_column_not_null("typtype".to_string())? {
+                BuiltinSqlTypes::Text(t) => t,
+                _ => return Err(DefinitionLookupError::ColumnWrongType()),
+            };
+            if typtype == "e" || typtype == "c" {
+                type_rows.push((oid, typtype, row));
+            }
+        }
+        if type_rows.is_empty() {
+            return Ok(vec![]);
+        }
+
+        //A type can only use types that existed before it so building them in OID order
+        //means the fields of a composite are always already known
+        type_rows.sort_by_key(|r| r.0);
+        let mut labels = self.get_enum_labels(tran_id).await?;
+        let mut user_types = vec![];
+        for (oid, typtype, row) in type_rows {
+            let name = match row.get_column_not_null("typname".to_string())? {
+                BuiltinSqlTypes::Text(t) => t,
+                _ => return Err(DefinitionLookupError::ColumnWrongType()),
+            };
+            let array_oid = DefinitionLookup::get_oid(&row, "typarray")?;
+
+            if typtype == "e" {
+                let mut type_labels: Vec<(i32, String)> = labels
+                    .iter()
+                    .filter(|l| l.0 == oid)
+                    .map(|l| (l.1, l.2.clone()))
+                    .collect();
+                labels.retain(|l| l.0 != oid);
+                type_labels.sort_by_key(|l| l.0);
+                user_types.push(DeserializeTypes::Enum(Arc::new(EnumType {
+                    oid,
+                    array_oid,
+                    name,
+                    labels: type_labels.into_iter().map(|l| l.1).collect(),
+                })));
+            } else {
+                let typrelid = match row.get_column_not_null("typrelid".to_string())? {
+                    BuiltinSqlTypes::Uuid(u) => u,
+                    _ => return Err(DefinitionLookupError::ColumnWrongType()),
+                };
+                let mut fields = vec![];
+                for c in self.get_table_columns(tran_id, typrelid).await? {
+                    let (f_name, f_oid, f_typmod) = DefinitionLookup::column_type(&c)?;
+                    fields.push(CompositeField {
+                        name: f_name,
+                        sql_type: DefinitionLookup::find_type(f_oid, &user_types)?
+                            .with_typmod(f_typmod)?,
+                    });
+                }
+                user_types.push(DeserializeTypes::Composite(Arc::new(CompositeType {
+                    oid,
+                    array_oid,
+                    name,
+                    fields,
+                })));
+            }
+        }
+        Ok(user_types)
+    }
+
+    /// Resolves a type name the same way CREATE TABLE does
+    pub async fn get_type(
+        &self,
+        tran_id: TransactionId,
+        name: &str,
+    ) -> Result<DeserializeTypes, DefinitionLookupError> {
+        let user_types = self.get_user_types(tran_id).await?;
+        Ok(DeserializeTypes::resolve(name, &user_types)?)
+    }
+
+    /// The builtin or user type with the OID, an array OID gives the array type
+    pub fn find_type(
+        oid: u32,
+        user_types: &[DeserializeTypes],
+    ) -> Result<DeserializeTypes, DefinitionLookupError> {
+        if let Some(t) = DeserializeTypes::from_oid(oid) {
+            return Ok(t);
+        }
+        for t in user_types {
+            if t.oid() == oid {
+                return Ok(t.clone());
+            }
+            if DeserializeTypes::Array(Box::new(t.clone())).oid() == oid {
+                return Ok(DeserializeTypes::Array(Box::new(t.clone())));
+            }
+        }
+        Err(DefinitionLookupError::TypeDoesNotExist(oid))
+    }
+
+    //The name, type oid and type modifier of a pg_attribute row
+    fn column_type(row: &RowData) -> Result<(String, u32, i32), DefinitionLookupError> {
+        let name = match row.get_column_not_null("attname".to_string())? {
+            BuiltinSqlTypes::Text(t) => t,
+            _ => return Err(DefinitionLookupError::ColumnWrongType()),
+        };
+        let oid = DefinitionLookup::get_oid(row, "atttypid")?;
+        let typmod = match row.get_column_not_null("atttypmod".to_string())? {
+            BuiltinSqlTypes::Integer(i) => i,
+            _ => return Err(DefinitionLookupError::ColumnWrongType()),
+        };
+        Ok((name, oid, typmod))
+    }
+
+    //OIDs are unsigned but stored in integer columns like postgres' oid type
+    fn get_oid(row: &RowData, column: &str) -> Result<u32, DefinitionLookupError> {
+        match row.get_column_not_null(column.to_string())? {
+            BuiltinSqlTypes::Integer(i) => Ok(i as u32),
+            _ => Err(DefinitionLookupError::ColumnWrongType()),
+        }
+    }
+
+    //Every row of pg_enum as (enumtypid, enumsortorder, enumlabel)
+    async fn get_enum_labels(
+        &self,
+        tran_id: TransactionId,
+    ) -> Result<Vec<(u32, i32, String)>, DefinitionLookupError> {
+        let mut labels = vec![];
+        let pg_enum = TableDefinitions::PgEnum.value();
+        let row_stream = self.vis_row_man.clone().get_stream(tran_id, pg_enum);
+        pin!(row_stream);
+        while let Some(row_res) = row_stream.next().await {
+            let row = row_res?;
+            let sort_order = match row.get_column_not_null("enumsortorder".to_string())? {
+                BuiltinSqlTypes::Integer(i) => i,
+                _ => return Err(DefinitionLookupError::ColumnWrongType()),
+            };
+            let label = match row.get_column_not_null("enumlabel".to_string())? {
+                BuiltinSqlTypes::Text(t) => t,
+                _ => return Err(DefinitionLookupError::ColumnWrongType()),
+            };
+            labels.push((
+                DefinitionLookup::get_oid(&row, "enumtypid")?,
+                sort_order,
+                label,
+            ));
+        }
+        Ok(labels)
+    }
+
+    /// The table's row in pg_class
+    pub async fn get_table_row(
         &self,
         tran_id: TransactionId,
         name: String,
@@ -102,7 +287,8 @@ impl DefinitionLookup {
         Err(DefinitionLookupError::TableDoesNotExist(name))
     }
 
-    async fn get_table_columns(
+    /// The table's rows in pg_attribute, in column order
+    pub async fn get_table_columns(
         &self,
         tran_id: TransactionId,
         attrelid: Uuid,
@@ -135,10 +321,10 @@ impl DefinitionLookup {
                 .user_data
                 .0
                 .get(col_offset)
-                .ok_or_else(|| DefinitionLookupError::WrongColumnIndex(col_offset))?;
+                .ok_or(DefinitionLookupError::WrongColumnIndex(col_offset))?;
             let not_null_value = wrapped_value
                 .as_ref()
-                .ok_or_else(|| DefinitionLookupError::ColumnNull(col_offset))?;
+                .ok_or(DefinitionLookupError::ColumnNull(col_offset))?;
             match not_null_value {
                 BuiltinSqlTypes::Integer(i) => column_tuples.push((i, c.clone())),
                 _ => return Err(DefinitionLookupError::ColumnWrongType()),
@@ -146,10 +332,10 @@ impl DefinitionLookup {
         }
 
         //Now the columns are good but we need to check for gaps
-        column_tuples.sort_by(|a, b| a.0.cmp(&b.0));
-        for i in 0..column_tuples.len() {
-            let i_u32 = u32::try_from(i)?;
-            if column_tuples[i].0 != &i_u32 {
+        column_tuples.sort_by(|a, b| a.0.cmp(b.0));
+        for (i, (attnum, _)) in column_tuples.iter().enumerate() {
+            let i_i32 = i32::try_from(i)?;
+            if *attnum != &i_i32 {
                 return Err(DefinitionLookupError::ColumnGap(i));
             }
         }
@@ -173,6 +359,8 @@ pub enum DefinitionLookupError {
     ColumnNull(usize),
     #[error("Column wrong type")]
     ColumnWrongType(),
+    #[error("No type with oid {0}")]
+    TypeDoesNotExist(u32),
     #[error("Gap in columns found at {0}")]
     ColumnGap(usize),
     #[error(transparent)]
@@ -187,6 +375,16 @@ pub enum DefinitionLookupError {
     TryFromIntError(#[from] TryFromIntError),
 }
 
+impl DefinitionLookupError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            DefinitionLookupError::SqlTypeError(e) => e.pg_error_code(),
+            DefinitionLookupError::VisibleRowManagerError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -226,11 +424,10 @@ mod tests {
         let tran_id = TransactionId::new(1);
 
         let pg_class_def = aw!(dl.get_definition(tran_id, "something_random".to_string()));
-        match pg_class_def {
-            Ok(_) => assert!(false),
-            Err(DefinitionLookupError::TableDoesNotExist(_)) => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(
+            pg_class_def,
+            Err(DefinitionLookupError::TableDoesNotExist(_))
+        ));
     }
 
     #[test]
@@ -250,7 +447,6 @@ mod tests {
         aw!(dl.get_definition(tran, "foo".to_string()))?;
         aw!(tm.commit_trans(tran))?;
 
-        assert!(true);
         Ok(())
     }
 }
@@ -0,0 +1,1286 @@
+//! Turns a parsed expression into a typed one. Columns are resolved against the row the
+//! expression will be evaluated on and implicit casts are added so every operator sees the
+//! types it works on.
+//!
+//! Quoted literals and nulls have no type of their own, like postgres they take the type of
+//! whatever they are used with. Rows do the same to find their composite type.
+//!
+//! Type names in casts are looked up in the builtin types and then the user types passed in.
+
+use super::super::objects::{
+    Aggregate, Attribute, Expression, ExpressionContext, Function, Operator, OperatorError,
+    ParseExpression, ParseSubscript, Quantifier, SetFunction, Subscript,
+};
+use crate::constants::{BuiltinSqlTypes, DeserializeTypes, PgErrorCodes, SqlTypeError};
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub struct ExpressionAnalyzer {}
+
+impl ExpressionAnalyzer {
+    pub fn analyze(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        expression: ParseExpression,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, expression, None)
+    }
+
+    /// Analyzes an expression that will be stored into the target column, casting if needed
+    pub fn analyze_assignment(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        expression: ParseExpression,
+        target: &Attribute,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        let expr = ExpressionAnalyzer::analyze_with_hint(
+            columns,
+            user_types,
+            context,
+            expression,
+            Some(target.sql_type.clone()),
+        )?;
+        let expr_type = expr.sql_type();
+
+        if expr_type == target.sql_type {
+            Ok(expr)
+        } else if ExpressionAnalyzer::can_cast(&expr_type, &target.sql_type, false) {
+            Ok(Expression::Cast(Box::new(expr), target.sql_type.clone()))
+        } else {
+            Err(ExpressionAnalyzerError::ColumnTypeMismatch(
+                target.name.clone(),
+                target.sql_type.clone(),
+                expr_type,
+            ))
+        }
+    }
+
+    /// The column name postgres would give this expression in a result set
+    pub fn output_name(expression: &ParseExpression) -> String {
+        match expression {
+            //Only the field is used for foo.bar, the same as (foo).bar
+            ParseExpression::Identifier(i) => i.rsplit('.').next().unwrap_or(i).to_string(),
+            ParseExpression::FieldSelect(_, field) => field.clone(),
+            ParseExpression::Cast(e, _) => ExpressionAnalyzer::output_name(e),
+            ParseExpression::Function(name, _) => name.clone(),
+            ParseExpression::Array(_) => "array".to_string(),
+            ParseExpression::Subscript(e, _) => ExpressionAnalyzer::output_name(e),
+            _ => "?column?".to_string(),
+        }
+    }
+
+    fn analyze_with_hint(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        expression: ParseExpression,
+        hint: Option<DeserializeTypes>,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        match expression {
+            ParseExpression::String(s) => {
+                let sql_type = hint.unwrap_or(DeserializeTypes::Text);
+                Ok(Expression::Constant(
+                    Some(BuiltinSqlTypes::parse_in_zone(
+                        sql_type.clone(),
+                        s,
+                        &context.time_zone,
+                    )?),
+                    sql_type,
+                ))
+            }
+            ParseExpression::Null() => Ok(Expression::Constant(
+                None,
+                hint.unwrap_or(DeserializeTypes::Text),
+            )),
+            ParseExpression::Number(n) => ExpressionAnalyzer::number_constant(n),
+            ParseExpression::Bool(b) => Ok(Expression::Constant(
+                Some(BuiltinSqlTypes::Bool(b)),
+                DeserializeTypes::Bool,
+            )),
+            ParseExpression::Identifier(name) => {
+                for (i, c) in columns.iter().enumerate() {
+                    if c.name == name {
+                        return Ok(Expression::Column(i, c.clone()));
+                    }
+                }
+                //Without table names foo.bar can only be the field bar of the column foo
+                if let Some((column, fields)) = name.split_once('.') {
+                    if columns.iter().any(|c| c.name == column) {
+                        let expr = fields
+                            .split('.')
+                            .fold(ParseExpression::Identifier(column.to_string()), |e, f| {
+                                ParseExpression::FieldSelect(Box::new(e), f.to_string())
+                            });
+                        return ExpressionAnalyzer::analyze_with_hint(
+                            columns, user_types, context, expr, hint,
+                        );
+                    }
+                }
+                Err(ExpressionAnalyzerError::UnknownColumn(name))
+            }
+            ParseExpression::Negate(e) => {
+                let expr =
+                    ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, *e, None)?;
+                let sql_type = expr.sql_type();
+                if !sql_type.is_numeric() && sql_type != DeserializeTypes::Interval {
+                    return Err(ExpressionAnalyzerError::PrefixOperatorDoesNotExist(
+                        "-".to_string(),
+                        sql_type,
+                    ));
+                }
+                Ok(Expression::Negate(Box::new(expr)))
+            }
+            ParseExpression::Cast(e, type_name) => {
+                let target = DeserializeTypes::resolve(&type_name, user_types)?;
+                //Strings are cast without their length which is then applied by cutting
+                let cast_type = if target.element_type().is_string() {
+                    target.without_typmod()
+                } else {
+                    target.clone()
+                };
+                let expr = ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    *e,
+                    Some(cast_type.clone()),
+                )?;
+                let expr_type = expr.sql_type();
+                let expr = if expr_type == cast_type {
+                    expr
+                } else if ExpressionAnalyzer::can_cast(&expr_type, &cast_type, true) {
+                    Expression::Cast(Box::new(expr), cast_type.clone())
+                } else {
+                    return Err(ExpressionAnalyzerError::CannotCast(expr_type, target));
+                };
+
+                if cast_type == target {
+                    Ok(expr)
+                } else {
+                    Ok(Expression::Truncate(Box::new(expr), target))
+                }
+            }
+            ParseExpression::Operator(op, left, right) => ExpressionAnalyzer::analyze_operator(
+                columns, user_types, context, op, *left, *right,
+            ),
+            ParseExpression::Array(elements) => {
+                ExpressionAnalyzer::analyze_array(columns, user_types, context, elements, hint)
+            }
+            ParseExpression::Row(fields) => {
+                ExpressionAnalyzer::analyze_row(columns, user_types, context, fields, hint)
+            }
+            ParseExpression::FieldSelect(e, field) => {
+                let expr =
+                    ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, *e, None)?;
+                let composite = match expr.sql_type() {
+                    DeserializeTypes::Composite(t) => t,
+                    t => return Err(ExpressionAnalyzerError::NotComposite(field, t)),
+                };
+                match composite.field_index(&field) {
+                    Some(i) => {
+                        let field_type = composite.fields[i].sql_type.clone();
+                        Ok(Expression::FieldSelect(Box::new(expr), i, field_type))
+                    }
+                    None => Err(ExpressionAnalyzerError::UnknownField(
+                        field,
+                        DeserializeTypes::Composite(composite),
+                    )),
+                }
+            }
+            ParseExpression::Subscript(e, subscripts) => {
+                ExpressionAnalyzer::analyze_subscript(columns, user_types, context, *e, subscripts)
+            }
+            ParseExpression::ArrayComparison(op, quantifier, left, right) => {
+                ExpressionAnalyzer::analyze_array_comparison(
+                    columns, user_types, context, op, quantifier, *left, *right,
+                )
+            }
+            ParseExpression::Function(name, args) => {
+                if let Ok(set_function) = SetFunction::from_str(&name) {
+                    return ExpressionAnalyzer::analyze_set_function(
+                        columns,
+                        user_types,
+                        context,
+                        set_function,
+                        name,
+                        args,
+                    );
+                }
+                if let Ok(aggregate) = Aggregate::from_str(&name) {
+                    return ExpressionAnalyzer::analyze_aggregate(
+                        columns, user_types, context, aggregate, name, args,
+                    );
+                }
+
+                let function = Function::from_str(&name)?;
+                if !function.accepts_arguments(args.len()) {
+                    return Err(ExpressionAnalyzerError::FunctionArguments(name, args.len()));
+                }
+                let argument_type = function.argument_type();
+                let mut analyzed = Vec::with_capacity(args.len());
+                for a in args {
+                    let expr = ExpressionAnalyzer::analyze_with_hint(
+                        columns,
+                        user_types,
+                        context,
+                        a,
+                        argument_type.clone(),
+                    )?;
+                    if argument_type
+                        .as_ref()
+                        .map(|t| *t != expr.sql_type())
+                        .unwrap_or(false)
+                    {
+                        return Err(ExpressionAnalyzerError::FunctionArgumentType(
+                            name,
+                            expr.sql_type(),
+                        ));
+                    }
+                    analyzed.push(expr);
+                }
+                Ok(Expression::Function(function, analyzed))
+            }
+        }
+    }
+
+    fn analyze_aggregate(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        aggregate: Aggregate,
+        name: String,
+        mut args: Vec<ParseExpression>,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        if args.len() != 1 {
+            return Err(ExpressionAnalyzerError::FunctionArguments(name, args.len()));
+        }
+        let argument = ExpressionAnalyzer::analyze_with_hint(
+            columns,
+            user_types,
+            context,
+            args.remove(0),
+            None,
+        )?;
+        if argument.contains_aggregate() {
+            return Err(ExpressionAnalyzerError::NestedAggregate());
+        }
+        if argument.contains_set_function() {
+            return Err(ExpressionAnalyzerError::SetFunctionInAggregate());
+        }
+        Ok(Expression::Aggregate(aggregate, Box::new(argument)))
+    }
+
+    fn analyze_set_function(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        set_function: SetFunction,
+        name: String,
+        mut args: Vec<ParseExpression>,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        if args.len() != 1 {
+            return Err(ExpressionAnalyzerError::FunctionArguments(name, args.len()));
+        }
+        let argument = ExpressionAnalyzer::analyze_with_hint(
+            columns,
+            user_types,
+            context,
+            args.remove(0),
+            None,
+        )?;
+        if !argument.sql_type().is_array() {
+            return Err(ExpressionAnalyzerError::FunctionArgumentType(
+                name,
+                argument.sql_type(),
+            ));
+        }
+        if argument.contains_set_function() {
+            return Err(ExpressionAnalyzerError::NestedSetFunction());
+        }
+        Ok(Expression::SetFunction(set_function, Box::new(argument)))
+    }
+
+    /// Every element is cast to a common type, untyped elements take it from the others or
+    /// from the hint. Elements that are arrays themselves become another dimension.
+    fn analyze_array(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        elements: Vec<ParseExpression>,
+        hint: Option<DeserializeTypes>,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        let element_hint = hint
+            .filter(|h| h.is_array())
+            .map(|h| h.element_type().without_typmod());
+
+        let mut analyzed = Vec::with_capacity(elements.len());
+        let mut common: Option<DeserializeTypes> = None;
+        for e in elements {
+            if ExpressionAnalyzer::is_untyped(&e) {
+                analyzed.push(Err(e));
+                continue;
+            }
+            let sub_hint = element_hint
+                .clone()
+                .map(|h| DeserializeTypes::Array(Box::new(h)));
+            let expr =
+                ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, e, sub_hint)?;
+            let expr_type = expr.sql_type();
+            let element_type = expr_type.element_type();
+            common = match common {
+                None => Some(element_type.without_typmod()),
+                Some(c) => Some(
+                    ExpressionAnalyzer::array_element_type(&c, element_type).ok_or_else(|| {
+                        ExpressionAnalyzerError::ArrayTypeMismatch(c, element_type.clone())
+                    })?,
+                ),
+            };
+            analyzed.push(Ok(expr));
+        }
+
+        let common = match (common, element_hint) {
+            (Some(c), _) => c,
+            (None, Some(h)) => h,
+            (None, None) if analyzed.is_empty() => {
+                return Err(ExpressionAnalyzerError::EmptyArrayType())
+            }
+            (None, None) => DeserializeTypes::Text,
+        };
+
+        let mut result = Vec::with_capacity(analyzed.len());
+        for a in analyzed {
+            let expr = match a {
+                Ok(expr) => expr,
+                Err(e) => ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    e,
+                    Some(common.clone()),
+                )?,
+            };
+            result.push(expr);
+        }
+
+        //Either every element is an array or none of them are
+        let nested = result.iter().filter(|e| e.sql_type().is_array()).count();
+        if nested != 0 && nested != result.len() {
+            return Err(SqlTypeError::ArrayDimensionMismatch().into());
+        }
+        let array_type = DeserializeTypes::Array(Box::new(common.clone()));
+        let result = result
+            .into_iter()
+            .map(|e| {
+                if nested == 0 {
+                    ExpressionAnalyzer::coerce(e, common.clone())
+                } else {
+                    ExpressionAnalyzer::coerce(e, array_type.clone())
+                }
+            })
+            .collect();
+        Ok(Expression::Array(result, array_type))
+    }
+
+    /// Like postgres there are no anonymous record types, a row has to take its composite type
+    /// from where it is used. Each field is cast to its type as if it were stored in a column.
+    fn analyze_row(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        fields: Vec<ParseExpression>,
+        hint: Option<DeserializeTypes>,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        let composite = match hint {
+            Some(DeserializeTypes::Composite(t)) => t,
+            _ => return Err(ExpressionAnalyzerError::RowTypeUnknown()),
+        };
+        let row_type = DeserializeTypes::Composite(composite.clone());
+        if fields.len() != composite.fields.len() {
+            return Err(ExpressionAnalyzerError::RowFieldCount(
+                row_type,
+                fields.len(),
+            ));
+        }
+
+        let mut analyzed = Vec::with_capacity(fields.len());
+        for (f, field) in fields.into_iter().zip(composite.fields.iter()) {
+            let expr = ExpressionAnalyzer::analyze_with_hint(
+                columns,
+                user_types,
+                context,
+                f,
+                Some(field.sql_type.clone()),
+            )?;
+            let expr_type = expr.sql_type();
+            if expr_type == field.sql_type {
+                analyzed.push(expr);
+            } else if ExpressionAnalyzer::can_cast(&expr_type, &field.sql_type, false) {
+                analyzed.push(Expression::Cast(Box::new(expr), field.sql_type.clone()));
+            } else {
+                return Err(ExpressionAnalyzerError::ColumnTypeMismatch(
+                    field.name.clone(),
+                    field.sql_type.clone(),
+                    expr_type,
+                ));
+            }
+        }
+        Ok(Expression::Row(analyzed, row_type))
+    }
+
+    fn analyze_subscript(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        expression: ParseExpression,
+        subscripts: Vec<ParseSubscript>,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        let expr =
+            ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, expression, None)?;
+        if !expr.sql_type().is_array() {
+            return Err(ExpressionAnalyzerError::CannotSubscript(expr.sql_type()));
+        }
+
+        let index = |i: ParseExpression| -> Result<Expression, ExpressionAnalyzerError> {
+            let i = ExpressionAnalyzer::analyze_with_hint(
+                columns,
+                user_types,
+                context,
+                i,
+                Some(DeserializeTypes::Integer),
+            )?;
+            match i.sql_type() {
+                DeserializeTypes::SmallInt | DeserializeTypes::Integer => {
+                    Ok(ExpressionAnalyzer::coerce(i, DeserializeTypes::Integer))
+                }
+                t => Err(ExpressionAnalyzerError::SubscriptType(t)),
+            }
+        };
+
+        let mut analyzed = Vec::with_capacity(subscripts.len());
+        for s in subscripts {
+            analyzed.push(match s {
+                ParseSubscript::Index(i) => Subscript::Index(index(i)?),
+                ParseSubscript::Slice(l, u) => {
+                    Subscript::Slice(l.map(index).transpose()?, u.map(index).transpose()?)
+                }
+            });
+        }
+        Ok(Expression::Subscript(Box::new(expr), analyzed))
+    }
+
+    fn analyze_array_comparison(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        op: String,
+        quantifier: Quantifier,
+        left: ParseExpression,
+        right: ParseExpression,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        let operator = Operator::from_str(&op)?;
+
+        //An untyped array takes the left side as its element type and the other way around
+        let (left, right) = match (
+            ExpressionAnalyzer::is_untyped(&left),
+            ExpressionAnalyzer::is_untyped(&right),
+        ) {
+            (false, true) => {
+                let left = ExpressionAnalyzer::analyze_with_hint(
+                    columns, user_types, context, left, None,
+                )?;
+                let hint = DeserializeTypes::Array(Box::new(left.sql_type().without_typmod()));
+                let right = ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    right,
+                    Some(hint),
+                )?;
+                (left, right)
+            }
+            (true, false) => {
+                let right = ExpressionAnalyzer::analyze_with_hint(
+                    columns, user_types, context, right, None,
+                )?;
+                let hint = right.sql_type().element_type().without_typmod();
+                let left = ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    left,
+                    Some(hint),
+                )?;
+                (left, right)
+            }
+            (false, false) => (
+                ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, left, None)?,
+                ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, right, None)?,
+            ),
+            (true, true) => (
+                ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, left, None)?,
+                ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    right,
+                    Some(DeserializeTypes::Array(Box::new(DeserializeTypes::Text))),
+                )?,
+            ),
+        };
+
+        let right_type = right.sql_type();
+        if !right_type.is_array() {
+            return Err(ExpressionAnalyzerError::ArrayComparisonNeedsArray(
+                op, quantifier,
+            ));
+        }
+        let left_type = left.sql_type();
+        let common =
+            match ExpressionAnalyzer::comparison_type(&left_type, right_type.element_type()) {
+                Some(c) if operator.is_comparison() => c,
+                _ => {
+                    return Err(ExpressionAnalyzerError::OperatorDoesNotExist(
+                        op,
+                        left_type,
+                        right_type.element_type().clone(),
+                    ))
+                }
+            };
+
+        Ok(Expression::ArrayComparison(
+            operator,
+            quantifier,
+            Box::new(ExpressionAnalyzer::coerce(left, common.clone())),
+            Box::new(ExpressionAnalyzer::coerce(
+                right,
+                DeserializeTypes::Array(Box::new(common)),
+            )),
+        ))
+    }
+
+    fn analyze_operator(
+        columns: &[Attribute],
+        user_types: &[DeserializeTypes],
+        context: &ExpressionContext,
+        op: String,
+        left: ParseExpression,
+        right: ParseExpression,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        let operator = Operator::from_str(&op)?;
+
+        //An untyped side takes its type from the other side
+        let (left, right) = match (
+            ExpressionAnalyzer::is_untyped(&left),
+            ExpressionAnalyzer::is_untyped(&right),
+        ) {
+            (false, true) => {
+                let left = ExpressionAnalyzer::analyze_with_hint(
+                    columns, user_types, context, left, None,
+                )?;
+                let hint = ExpressionAnalyzer::untyped_hint(operator, &left.sql_type());
+                let right = ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    right,
+                    Some(hint),
+                )?;
+                (left, right)
+            }
+            (true, false) => {
+                let right = ExpressionAnalyzer::analyze_with_hint(
+                    columns, user_types, context, right, None,
+                )?;
+                let hint = ExpressionAnalyzer::untyped_hint(operator, &right.sql_type());
+                let left = ExpressionAnalyzer::analyze_with_hint(
+                    columns,
+                    user_types,
+                    context,
+                    left,
+                    Some(hint),
+                )?;
+                (left, right)
+            }
+            (_, _) => (
+                ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, left, None)?,
+                ExpressionAnalyzer::analyze_with_hint(columns, user_types, context, right, None)?,
+            ),
+        };
+
+        let left_type = left.sql_type();
+        let right_type = right.sql_type();
+        let (left_target, right_target, result) =
+            match ExpressionAnalyzer::resolve_operator(operator, &left_type, &right_type) {
+                Some(r) => r,
+                None => {
+                    return Err(ExpressionAnalyzerError::OperatorDoesNotExist(
+                        op, left_type, right_type,
+                    ))
+                }
+            };
+
+        Ok(Expression::Operator(
+            operator,
+            Box::new(ExpressionAnalyzer::coerce(left, left_target)),
+            Box::new(ExpressionAnalyzer::coerce(right, right_target)),
+            result,
+        ))
+    }
+
+    //Usually an untyped literal is the same type as the other side, but adding to a point in
+    //time or scaling an interval only makes sense with an interval or a number. Like postgres
+    //subtracting a literal from a timestamp still reads it as a timestamp.
+    fn untyped_hint(operator: Operator, other: &DeserializeTypes) -> DeserializeTypes {
+        match (operator, other) {
+            (Operator::Add, DeserializeTypes::Date) => DeserializeTypes::Integer,
+            (
+                Operator::Add,
+                DeserializeTypes::Time
+                | DeserializeTypes::Timestamp
+                | DeserializeTypes::TimestampTz,
+            ) => DeserializeTypes::Interval,
+            (Operator::Multiply | Operator::Divide, DeserializeTypes::Interval) => {
+                DeserializeTypes::DoublePrecision
+            }
+            //Keys and paths are text, only containment compares two json values
+            (
+                Operator::JsonGet
+                | Operator::JsonGetText
+                | Operator::JsonPath
+                | Operator::JsonPathText
+                | Operator::JsonKeyExists,
+                _,
+            ) => DeserializeTypes::Text,
+            (_, t) => t.without_typmod(),
+        }
+    }
+
+    /// Finds the types the operator will take its operands as and the type it produces
+    fn resolve_operator(
+        operator: Operator,
+        left: &DeserializeTypes,
+        right: &DeserializeTypes,
+    ) -> Option<(DeserializeTypes, DeserializeTypes, DeserializeTypes)> {
+        use DeserializeTypes as T;
+
+        if operator.is_json() {
+            return ExpressionAnalyzer::json_operator_types(operator, left, right);
+        }
+
+        if operator.is_comparison() {
+            let common = ExpressionAnalyzer::comparison_type(left, right)?;
+            return Some((common.clone(), common, T::Bool));
+        }
+
+        if let Some(common) = ExpressionAnalyzer::arithmetic_type(left, right) {
+            if operator == Operator::Modulo && matches!(common, T::Real | T::DoublePrecision) {
+                return None;
+            }
+            return Some((common.clone(), common.clone(), common));
+        }
+
+        let is_integer = |t: &T| matches!(t, T::SmallInt | T::Integer);
+        //Adding an interval to a date gives a timestamp
+        let as_timestamp = |t: &T| {
+            if *t == T::Date {
+                T::Timestamp
+            } else {
+                t.clone()
+            }
+        };
+
+        let resolved = match (operator, left, right) {
+            (Operator::Add | Operator::Subtract, T::Date, r) if is_integer(r) => {
+                (T::Date, T::Integer, T::Date)
+            }
+            (Operator::Add, l, T::Date) if is_integer(l) => (T::Integer, T::Date, T::Date),
+            (Operator::Subtract, T::Date, T::Date) => (T::Date, T::Date, T::Integer),
+            (Operator::Add, T::Date, T::Time) => (T::Date, T::Time, T::Timestamp),
+            (Operator::Add, T::Time, T::Date) => (T::Time, T::Date, T::Timestamp),
+            (Operator::Add | Operator::Subtract, T::Time, T::Interval) => {
+                (T::Time, T::Interval, T::Time)
+            }
+            (Operator::Add, T::Interval, T::Time) => (T::Interval, T::Time, T::Time),
+            (Operator::Subtract, T::Time, T::Time) => (T::Time, T::Time, T::Interval),
+            (Operator::Add | Operator::Subtract, l, T::Interval) if l.is_date_time() => {
+                (as_timestamp(l), T::Interval, as_timestamp(l))
+            }
+            (Operator::Add, T::Interval, r) if r.is_date_time() => {
+                (T::Interval, as_timestamp(r), as_timestamp(r))
+            }
+            (Operator::Subtract, l, r) if l.is_date_time() && r.is_date_time() => {
+                let common = ExpressionAnalyzer::date_time_type(l, r);
+                (common.clone(), common, T::Interval)
+            }
+            (Operator::Add | Operator::Subtract, T::Interval, T::Interval) => {
+                (T::Interval, T::Interval, T::Interval)
+            }
+            (Operator::Multiply, T::Interval, r) if r.is_numeric() => {
+                (T::Interval, T::DoublePrecision, T::Interval)
+            }
+            (Operator::Multiply, l, T::Interval) if l.is_numeric() => {
+                (T::DoublePrecision, T::Interval, T::Interval)
+            }
+            (Operator::Divide, T::Interval, r) if r.is_numeric() => {
+                (T::Interval, T::DoublePrecision, T::Interval)
+            }
+            (_, _, _) => return None,
+        };
+        Some(resolved)
+    }
+
+    //Fields and elements come back as the same kind of json as the left side
+    fn json_operator_types(
+        operator: Operator,
+        left: &DeserializeTypes,
+        right: &DeserializeTypes,
+    ) -> Option<(DeserializeTypes, DeserializeTypes, DeserializeTypes)> {
+        use DeserializeTypes as T;
+
+        let as_text = matches!(operator, Operator::JsonGetText | Operator::JsonPathText);
+        let result = if as_text { T::Text } else { left.clone() };
+        match operator {
+            Operator::JsonGet | Operator::JsonGetText if left.is_json() => {
+                if right.is_string() {
+                    Some((left.clone(), T::Text, result))
+                } else if matches!(right, T::SmallInt | T::Integer) {
+                    Some((left.clone(), T::Integer, result))
+                } else {
+                    None
+                }
+            }
+            Operator::JsonPath | Operator::JsonPathText if left.is_json() && right.is_string() => {
+                Some((left.clone(), T::Text, result))
+            }
+            Operator::JsonContains | Operator::JsonContainedBy
+                if *left == T::Jsonb && *right == T::Jsonb =>
+            {
+                Some((T::Jsonb, T::Jsonb, T::Bool))
+            }
+            Operator::JsonKeyExists if *left == T::Jsonb && right.is_string() => {
+                Some((T::Jsonb, T::Text, T::Bool))
+            }
+            _ => None,
+        }
+    }
+
+    /// Any type compares with itself, numbers compare after promotion and dates and
+    /// timestamps are widened to the more precise of the two. Mixed strings and chars compare
+    /// as text, which drops the padding of the chars. Like postgres json has no comparisons,
+    /// only jsonb.
+    fn comparison_type(
+        left: &DeserializeTypes,
+        right: &DeserializeTypes,
+    ) -> Option<DeserializeTypes> {
+        if *left == DeserializeTypes::Json || *right == DeserializeTypes::Json {
+            return None;
+        }
+        //Arrays compare element by element
+        if left.is_array() || right.is_array() {
+            if !left.is_array() || !right.is_array() {
+                return None;
+            }
+            let common =
+                ExpressionAnalyzer::comparison_type(left.element_type(), right.element_type())?;
+            return Some(DeserializeTypes::Array(Box::new(common)));
+        }
+        let is_char = |t: &DeserializeTypes| matches!(t, DeserializeTypes::Char(_));
+        if left.is_string() && right.is_string() {
+            if left.without_typmod() == right.without_typmod() && !is_char(left) {
+                Some(left.without_typmod())
+            } else {
+                Some(DeserializeTypes::Text)
+            }
+        } else if left.without_typmod() == right.without_typmod() {
+            Some(left.without_typmod())
+        } else if left.is_date_time() && right.is_date_time() {
+            Some(ExpressionAnalyzer::date_time_type(left, right))
+        } else {
+            ExpressionAnalyzer::arithmetic_type(left, right)
+        }
+    }
+
+    //The type both elements of an ARRAY[...] can be cast to, anything with strings is text
+    fn array_element_type(
+        left: &DeserializeTypes,
+        right: &DeserializeTypes,
+    ) -> Option<DeserializeTypes> {
+        if left.without_typmod() == right.without_typmod() {
+            Some(left.without_typmod())
+        } else if left.is_string() && right.is_string() {
+            Some(DeserializeTypes::Text)
+        } else if left.is_date_time() && right.is_date_time() {
+            Some(ExpressionAnalyzer::date_time_type(left, right))
+        } else {
+            ExpressionAnalyzer::arithmetic_type(left, right)
+        }
+    }
+
+    //Dates widen to timestamps which widen to timestamps with time zone
+    fn date_time_type(left: &DeserializeTypes, right: &DeserializeTypes) -> DeserializeTypes {
+        let rank = |t: &DeserializeTypes| match t {
+            DeserializeTypes::Date => 0,
+            DeserializeTypes::Timestamp => 1,
+            _ => 2,
+        };
+        if rank(left) >= rank(right) {
+            left.clone()
+        } else {
+            right.clone()
+        }
+    }
+
+    fn is_untyped(expression: &ParseExpression) -> bool {
+        matches!(
+            expression,
+            ParseExpression::String(_) | ParseExpression::Null() | ParseExpression::Row(_)
+        )
+    }
+
+    //Integers are the smallest of int4, int8 or numeric that holds them, anything with a
+    //decimal point or exponent is numeric
+    fn number_constant(number: String) -> Result<Expression, ExpressionAnalyzerError> {
+        if !number.contains(['.', 'e', 'E']) {
+            if let Ok(i) = number.parse::<i32>() {
+                return Ok(Expression::Constant(
+                    Some(BuiltinSqlTypes::Integer(i)),
+                    DeserializeTypes::Integer,
+                ));
+            }
+            if let Ok(i) = number.parse::<i64>() {
+                return Ok(Expression::Constant(
+                    Some(BuiltinSqlTypes::BigInt(i)),
+                    DeserializeTypes::BigInt,
+                ));
+            }
+        }
+
+        let numeric_type = DeserializeTypes::Numeric(None);
+        let mut value = BigDecimal::from_str(&number)
+            .map_err(|_| SqlTypeError::InvalidInput(numeric_type.clone(), number.clone()))?;
+        if value.fractional_digit_count() < 0 {
+            value = value.with_scale(0);
+        }
+        Ok(Expression::Constant(
+            Some(BuiltinSqlTypes::Numeric(value)),
+            numeric_type,
+        ))
+    }
+
+    /// Finds the type both sides of an arithmetic operator should be cast to, following the
+    /// postgres promotion order smallint < integer < bigint < numeric < double precision.
+    /// Real only survives if both sides are real.
+    fn arithmetic_type(
+        left: &DeserializeTypes,
+        right: &DeserializeTypes,
+    ) -> Option<DeserializeTypes> {
+        if !left.is_numeric() || !right.is_numeric() {
+            return None;
+        }
+
+        let rank = |t: &DeserializeTypes| match t {
+            DeserializeTypes::SmallInt => 0,
+            DeserializeTypes::Integer => 1,
+            DeserializeTypes::BigInt => 2,
+            _ => 3,
+        };
+
+        match (left, right) {
+            (DeserializeTypes::Real, DeserializeTypes::Real) => Some(DeserializeTypes::Real),
+            (DeserializeTypes::Real, _)
+            | (_, DeserializeTypes::Real)
+            | (DeserializeTypes::DoublePrecision, _)
+            | (_, DeserializeTypes::DoublePrecision) => Some(DeserializeTypes::DoublePrecision),
+            (l, r) if rank(l) >= rank(r) => Some(l.without_typmod()),
+            (_, r) => Some(r.without_typmod()),
+        }
+    }
+
+    fn coerce(expression: Expression, target: DeserializeTypes) -> Expression {
+        if expression.sql_type().without_typmod() == target {
+            expression
+        } else {
+            Expression::Cast(Box::new(expression), target)
+        }
+    }
+
+    /// Numbers convert between each other, dates and timestamps convert between each other,
+    /// json and jsonb convert between each other and everything converts to a string. Only explicit casts may parse a string into another
+    /// type. Arrays convert if their elements do.
+    pub fn can_cast(from: &DeserializeTypes, to: &DeserializeTypes, explicit: bool) -> bool {
+        if from.is_array() && to.is_array() {
+            return ExpressionAnalyzer::can_cast(from.element_type(), to.element_type(), explicit);
+        }
+        from.without_typmod() == to.without_typmod()
+            || (from.is_numeric() && to.is_numeric())
+            || (from.is_date_time() && to.is_date_time())
+            || ((*from == DeserializeTypes::Timestamp || *from == DeserializeTypes::TimestampTz)
+                && *to == DeserializeTypes::Time)
+            || (*from == DeserializeTypes::Time && *to == DeserializeTypes::Interval)
+            || (*from == DeserializeTypes::Interval && *to == DeserializeTypes::Time)
+            || (from.is_json() && to.is_json())
+            || to.is_string()
+            || (explicit && from.is_string())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExpressionAnalyzerError {
+    #[error("column \"{0}\" is of type {1} but expression is of type {2}")]
+    ColumnTypeMismatch(String, DeserializeTypes, DeserializeTypes),
+    #[error("cannot cast type {0} to {1}")]
+    CannotCast(DeserializeTypes, DeserializeTypes),
+    #[error("operator does not exist: {1} {0} {2}")]
+    OperatorDoesNotExist(String, DeserializeTypes, DeserializeTypes),
+    #[error("operator does not exist: {0} {1}")]
+    PrefixOperatorDoesNotExist(String, DeserializeTypes),
+    #[error("function {0} does not take {1} arguments")]
+    FunctionArguments(String, usize),
+    #[error("function {0}({1}) does not exist")]
+    FunctionArgumentType(String, DeserializeTypes),
+    #[error("aggregate function calls cannot be nested")]
+    NestedAggregate(),
+    #[error("aggregate function calls cannot contain set-returning function calls")]
+    SetFunctionInAggregate(),
+    #[error("set-returning function calls cannot be nested")]
+    NestedSetFunction(),
+    #[error("ARRAY types {0} and {1} cannot be matched")]
+    ArrayTypeMismatch(DeserializeTypes, DeserializeTypes),
+    #[error("cannot determine type of empty array")]
+    EmptyArrayType(),
+    #[error("cannot subscript type {0} because it is not an array")]
+    CannotSubscript(DeserializeTypes),
+    #[error("array subscript must have type integer, not {0}")]
+    SubscriptType(DeserializeTypes),
+    #[error("op {0} {1} (array) requires array on right side")]
+    ArrayComparisonNeedsArray(String, Quantifier),
+    #[error("could not identify the composite type of the row, cast it to one")]
+    RowTypeUnknown(),
+    #[error("type {0} does not have {1} fields")]
+    RowFieldCount(DeserializeTypes, usize),
+    #[error("column notation .{0} applied to type {1}, which is not a composite type")]
+    NotComposite(String, DeserializeTypes),
+    #[error("column \"{0}\" not found in data type {1}")]
+    UnknownField(String, DeserializeTypes),
+    #[error(transparent)]
+    OperatorError(#[from] OperatorError),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
+    #[error("Unknown column received {0}")]
+    UnknownColumn(String),
+}
+
+impl ExpressionAnalyzerError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            ExpressionAnalyzerError::SqlTypeError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::Nullable;
+    use uuid::Uuid;
+
+    fn columns() -> Vec<Attribute> {
+        vec![
+            Attribute::new(
+                Uuid::new_v4(),
+                "small".to_string(),
+                DeserializeTypes::SmallInt,
+                Nullable::Null,
+            ),
+            Attribute::new(
+                Uuid::new_v4(),
+                "name".to_string(),
+                DeserializeTypes::Text,
+                Nullable::Null,
+            ),
+        ]
+    }
+
+    fn parse_op(op: &str, left: ParseExpression, right: ParseExpression) -> ParseExpression {
+        ParseExpression::Operator(op.to_string(), Box::new(left), Box::new(right))
+    }
+
+    fn analyze(
+        columns: &[Attribute],
+        expression: ParseExpression,
+    ) -> Result<Expression, ExpressionAnalyzerError> {
+        ExpressionAnalyzer::analyze(columns, &[], &ExpressionContext::default(), expression)
+    }
+
+    #[test]
+    fn test_number_literals() -> Result<(), Box<dyn std::error::Error>> {
+        let cols = columns();
+        for (number, expected) in [
+            ("1", DeserializeTypes::Integer),
+            ("-2147483648", DeserializeTypes::Integer),
+            ("2147483648", DeserializeTypes::BigInt),
+            ("99999999999999999999", DeserializeTypes::Numeric(None)),
+            ("1.5", DeserializeTypes::Numeric(None)),
+        ] {
+            let expr = analyze(&cols, ParseExpression::Number(number.to_string()))?;
+            assert_eq!(expr.sql_type(), expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_promotion() -> Result<(), Box<dyn std::error::Error>> {
+        let cols = columns();
+        let small = || ParseExpression::Identifier("small".to_string());
+
+        let expr = analyze(&cols, parse_op("+", small(), small()))?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::SmallInt);
+
+        let expr = analyze(
+            &cols,
+            parse_op("*", small(), ParseExpression::Number("2".to_string())),
+        )?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::Integer);
+
+        let expr = analyze(
+            &cols,
+            parse_op("-", small(), ParseExpression::Number("2.5".to_string())),
+        )?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::Numeric(None));
+
+        let expr = analyze(
+            &cols,
+            parse_op(
+                "/",
+                ParseExpression::Cast(Box::new(small()), "real".to_string()),
+                ParseExpression::Number("2".to_string()),
+            ),
+        )?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::DoublePrecision);
+
+        //The unknown literal picks up the type of the other side
+        let expr = analyze(
+            &cols,
+            parse_op("+", small(), ParseExpression::String("3".to_string())),
+        )?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::SmallInt);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_operators() {
+        let cols = columns();
+        let res = analyze(
+            &cols,
+            parse_op(
+                "+",
+                ParseExpression::Identifier("name".to_string()),
+                ParseExpression::Number("1".to_string()),
+            ),
+        );
+        assert!(matches!(
+            res,
+            Err(ExpressionAnalyzerError::OperatorDoesNotExist(_, _, _))
+        ));
+
+        let res = analyze(
+            &cols,
+            parse_op(
+                "%",
+                ParseExpression::Number("1".to_string()),
+                ParseExpression::Cast(
+                    Box::new(ParseExpression::Number("1".to_string())),
+                    "float8".to_string(),
+                ),
+            ),
+        );
+        assert!(matches!(
+            res,
+            Err(ExpressionAnalyzerError::OperatorDoesNotExist(_, _, _))
+        ));
+
+        let res = analyze(&cols, ParseExpression::Identifier("missing".to_string()));
+        assert!(matches!(
+            res,
+            Err(ExpressionAnalyzerError::UnknownColumn(_))
+        ));
+    }
+
+    #[test]
+    fn test_date_time_operators() -> Result<(), Box<dyn std::error::Error>> {
+        let cols = columns();
+        let literal = |s: &str, t: &str| {
+            ParseExpression::Cast(
+                Box::new(ParseExpression::String(s.to_string())),
+                t.to_string(),
+            )
+        };
+        let untyped = |s: &str| ParseExpression::String(s.to_string());
+
+        for (expr, expected) in [
+            (
+                parse_op("+", literal("2021-01-01", "date"), untyped("1")),
+                DeserializeTypes::Date,
+            ),
+            (
+                parse_op(
+                    "-",
+                    literal("2021-01-01", "date"),
+                    literal("2020-01-01", "date"),
+                ),
+                DeserializeTypes::Integer,
+            ),
+            (
+                parse_op(
+                    "+",
+                    literal("2021-01-01", "date"),
+                    literal("1 day", "interval"),
+                ),
+                DeserializeTypes::Timestamp,
+            ),
+            (
+                parse_op("+", literal("2021-01-01", "date"), literal("12:00", "time")),
+                DeserializeTypes::Timestamp,
+            ),
+            (
+                parse_op(
+                    "+",
+                    ParseExpression::Function("now".to_string(), vec![]),
+                    untyped("1 hour"),
+                ),
+                DeserializeTypes::TimestampTz,
+            ),
+            (
+                parse_op(
+                    "-",
+                    ParseExpression::Function("now".to_string(), vec![]),
+                    untyped("2021-01-01 12:00"),
+                ),
+                DeserializeTypes::Interval,
+            ),
+            (
+                parse_op(
+                    "-",
+                    ParseExpression::Function("now".to_string(), vec![]),
+                    literal("2021-01-01", "date"),
+                ),
+                DeserializeTypes::Interval,
+            ),
+            (
+                parse_op("*", literal("1 day", "interval"), untyped("2")),
+                DeserializeTypes::Interval,
+            ),
+            (
+                parse_op(
+                    "<",
+                    literal("2021-01-01", "date"),
+                    untyped("2021-01-02 12:00"),
+                ),
+                DeserializeTypes::Bool,
+            ),
+            (
+                parse_op(
+                    "=",
+                    ParseExpression::Function("now".to_string(), vec![]),
+                    ParseExpression::Function("current_timestamp".to_string(), vec![]),
+                ),
+                DeserializeTypes::Bool,
+            ),
+        ] {
+            assert_eq!(analyze(&cols, expr)?.sql_type(), expected);
+        }
+
+        //Adding two dates makes no sense
+        let res = analyze(
+            &cols,
+            parse_op(
+                "+",
+                literal("2021-01-01", "date"),
+                literal("2021-01-01", "date"),
+            ),
+        );
+        assert!(matches!(
+            res,
+            Err(ExpressionAnalyzerError::OperatorDoesNotExist(_, _, _))
+        ));
+
+        let res = analyze(&cols, ParseExpression::Function("nope".to_string(), vec![]));
+        assert!(matches!(
+            res,
+            Err(ExpressionAnalyzerError::OperatorError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays() -> Result<(), Box<dyn std::error::Error>> {
+        let cols = columns();
+        let num = |n: &str| ParseExpression::Number(n.to_string());
+        let int_array = DeserializeTypes::Array(Box::new(DeserializeTypes::Integer));
+
+        //Untyped elements follow the typed ones and nested arrays add a dimension
+        let expr = analyze(
+            &cols,
+            ParseExpression::Array(vec![
+                ParseExpression::Array(vec![num("1"), ParseExpression::Null()]),
+                ParseExpression::Array(vec![ParseExpression::String("2".to_string()), num("3")]),
+            ]),
+        )?;
+        assert_eq!(expr.sql_type(), int_array);
+
+        let subscript = |subscripts| {
+            ParseExpression::Subscript(Box::new(ParseExpression::Array(vec![num("1")])), subscripts)
+        };
+        let expr = analyze(&cols, subscript(vec![ParseSubscript::Index(num("1"))]))?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::Integer);
+        let expr = analyze(
+            &cols,
+            subscript(vec![
+                ParseSubscript::Index(num("1")),
+                ParseSubscript::Slice(None, Some(num("2"))),
+            ]),
+        )?;
+        assert_eq!(expr.sql_type(), int_array);
+
+        let expr = analyze(
+            &cols,
+            ParseExpression::ArrayComparison(
+                "=".to_string(),
+                Quantifier::Any,
+                Box::new(ParseExpression::Identifier("small".to_string())),
+                Box::new(ParseExpression::String("{1,2}".to_string())),
+            ),
+        )?;
+        assert_eq!(expr.sql_type(), DeserializeTypes::Bool);
+
+        assert!(matches!(
+            analyze(&cols, ParseExpression::Array(vec![])),
+            Err(ExpressionAnalyzerError::EmptyArrayType())
+        ));
+        assert!(matches!(
+            analyze(
+                &cols,
+                ParseExpression::Array(vec![
+                    num("1"),
+                    ParseExpression::Identifier("name".to_string())
+                ])
+            ),
+            Err(ExpressionAnalyzerError::ArrayTypeMismatch(_, _))
+        ));
+        assert!(matches!(
+            analyze(
+                &cols,
+                ParseExpression::Subscript(
+                    Box::new(num("1")),
+                    vec![ParseSubscript::Index(num("1"))]
+                )
+            ),
+            Err(ExpressionAnalyzerError::CannotSubscript(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Background worker that vacuums every table on a fixed interval, so dead rows are reclaimed
+//! even if nobody runs VACUUM.
+//!
+//! Postgres decides per table based on how many rows have changed, for now every table is
+//! visited each time since a pass over a table with nothing to remove only reads it.
+use super::io::VacuumStats;
+use super::transactions::{TransactionManager, TransactionManagerError};
+use super::{Executor, ExecutorError};
+use log::{debug, warn};
+use std::time::Duration;
+use thiserror::Error;
+
+/// How long the worker sleeps between passes, the same default as postgres' autovacuum_naptime
+pub const AUTOVACUUM_NAPTIME: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub struct AutoVacuum {
+    executor: Executor,
+    tran_manager: TransactionManager,
+    naptime: Duration,
+}
+
+impl AutoVacuum {
+    pub fn new(
+        executor: Executor,
+        tran_manager: TransactionManager,
+        naptime: Duration,
+    ) -> AutoVacuum {
+        AutoVacuum {
+            executor,
+            tran_manager,
+            naptime,
+        }
+    }
+
+    /// Runs forever, meant to be spawned as its own task
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.naptime);
+        //The first tick completes immediately, there is nothing to do at startup
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            //A standby's tables are vacuumed by its primary
+            if self.tran_manager.in_recovery() {
+                continue;
+            }
+            match self.vacuum_all().await {
+                Ok(stats) => debug!("Autovacuum pass done {:?}", stats),
+                Err(e) => warn!("Autovacuum pass failed {}", e),
+            }
+        }
+    }
+
+    /// A single pass over every table in its own transaction
+    pub async fn vacuum_all(&self) -> Result<VacuumStats, AutoVacuumError> {
+        let mut tm = self.tran_manager.clone();
+        let tran_id = tm.start_trans().await?;
+
+        let results = match self.executor.vacuum(tran_id, None).await {
+            Ok(r) => r,
+            Err(e) => {
+                tm.abort_trans(tran_id).await?;
+                return Err(AutoVacuumError::ExecutorError(e));
+            }
+        };
+        tm.commit_trans(tran_id).await?;
+
+        let mut stats = VacuumStats::default();
+        for (_, s) in results {
+            stats += s;
+        }
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AutoVacuumError {
+    #[error(transparent)]
+    ExecutorError(#[from] ExecutorError),
+    #[error(transparent)]
+    TransactionManagerError(#[from] TransactionManagerError),
+}
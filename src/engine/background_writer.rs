@@ -0,0 +1,48 @@
+//! Background worker that flushes the files written since the last checkpoint a few at a time,
+//! so a checkpoint finds most of its work already done instead of stalling on one big flush.
+//!
+//! Postgres' background writer writes dirty shared buffers out ahead of eviction. Every page
+//! change here already goes straight to its file, so what is left to trickle out is the flush
+//! of those files to disk.
+use super::io::IOManager;
+use log::warn;
+use std::time::Duration;
+
+/// How long the worker sleeps between rounds, the same default as postgres' bgwriter_delay
+pub const BGWRITER_DELAY: Duration = Duration::from_millis(200);
+
+/// The most files flushed in a round, the same default as postgres' bgwriter_lru_maxpages
+pub const BGWRITER_LRU_MAXPAGES: usize = 100;
+
+#[derive(Clone, Debug)]
+pub struct BackgroundWriter {
+    io_manager: IOManager,
+    delay: Duration,
+    max_files: usize,
+}
+
+impl BackgroundWriter {
+    pub fn new(io_manager: IOManager, delay: Duration, max_files: usize) -> BackgroundWriter {
+        BackgroundWriter {
+            io_manager,
+            delay,
+            max_files,
+        }
+    }
+
+    /// Runs forever, meant to be spawned as its own task. With no files allowed per round it
+    /// returns straight away like postgres' background writer is turned off.
+    pub async fn run(self) {
+        if self.max_files == 0 {
+            return;
+        }
+        let mut interval = tokio::time::interval(self.delay);
+        loop {
+            interval.tick().await;
+            //Too often to log every round
+            if let Err(e) = self.io_manager.background_write(self.max_files).await {
+                warn!("Background write failed {}", e);
+            }
+        }
+    }
+}
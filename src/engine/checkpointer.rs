@@ -0,0 +1,45 @@
+//! Background worker that checkpoints on a fixed interval. Committed changes are never lost to a
+//! crash since the WAL has them, the interval only bounds how much of it recovery has to redo
+//! and how much has to be kept.
+//!
+//! Each checkpoint moves the point crash recovery replays the WAL from and removes the segments
+//! before it that have been archived.
+//!
+//! Between checkpoints the background writer flushes files a few at a time, so a checkpoint
+//! usually only has what was written since its last round left to flush.
+use super::io::{CheckpointKind, IOManager};
+use log::{debug, warn};
+use std::time::Duration;
+
+/// How long the worker sleeps between checkpoints, the same default as postgres'
+/// checkpoint_timeout
+pub const CHECKPOINT_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug)]
+pub struct Checkpointer {
+    io_manager: IOManager,
+    timeout: Duration,
+}
+
+impl Checkpointer {
+    pub fn new(io_manager: IOManager, timeout: Duration) -> Checkpointer {
+        Checkpointer {
+            io_manager,
+            timeout,
+        }
+    }
+
+    /// Runs forever, meant to be spawned as its own task
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.timeout);
+        //The first tick completes immediately, the first checkpoint is a full timeout in
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self.io_manager.checkpoint(CheckpointKind::Timed).await {
+                Ok(stats) => debug!("Checkpoint done {:?}", stats),
+                Err(e) => warn!("Checkpoint failed {}", e),
+            }
+        }
+    }
+}
@@ -1,15 +1,29 @@
+mod expression_evaluator;
+pub use expression_evaluator::ExpressionEvaluator;
+pub use expression_evaluator::ExpressionEvaluatorError;
+
 use crate::engine::objects::SqlTuple;
 
-use super::super::constants::{BuiltinSqlTypes, TableDefinitions};
-use super::io::{VisibleRowManager, VisibleRowManagerError};
-use super::objects::{Attribute, ParseTree, Plan, PlannedStatement, SqlTupleError, Table};
+use super::super::constants::{
+    BuiltinSqlTypes, CompositeField, CompositeType, DateTime, DeserializeTypes, EnumType,
+    PgErrorCodes, SqlTypeError, SystemViews, TableDefinitions, FIRST_USER_OID,
+};
+use super::analyzer::{DefinitionLookup, DefinitionLookupError};
+use super::io::{VacuumStats, VisibleRowManager, VisibleRowManagerError};
+use super::objects::{
+    Aggregate, Attribute, Expression, ExpressionContext, ParseTree, Plan, PlannedStatement,
+    RawColumn, RawCreateTableCommand, RawCreateTypeCommand, RawDropTableCommand, RawTypeDefinition,
+    SetFunction, SqlTupleError, Table,
+};
 use super::transactions::TransactionId;
 use async_stream::try_stream;
 use futures::stream::Stream;
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::num::TryFromIntError;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -18,51 +32,92 @@ use uuid::Uuid;
 #[derive(Clone, Debug)]
 pub struct Executor {
     vis_row_man: VisibleRowManager,
+    dl: DefinitionLookup,
 }
 
 impl Executor {
     pub fn new(vis_row_man: VisibleRowManager) -> Executor {
-        Executor { vis_row_man }
+        Executor {
+            dl: DefinitionLookup::new(vis_row_man.clone()),
+            vis_row_man,
+        }
     }
 
     pub fn execute(
         self,
         tran_id: TransactionId,
+        context: ExpressionContext,
         plan_tree: PlannedStatement,
     ) -> Pin<Box<dyn Stream<Item = Result<SqlTuple, ExecutorError>> + Send>> {
-        self.execute_plans(tran_id, plan_tree.plan)
+        self.execute_plans(tran_id, context, plan_tree.plan)
     }
 
     fn execute_plans(
         self,
         tran_id: TransactionId,
+        context: ExpressionContext,
         plan: Arc<Plan>,
     ) -> Pin<Box<dyn Stream<Item = Result<SqlTuple, ExecutorError>> + Send>> {
         match plan.as_ref() {
+            Plan::Aggregate(a) => {
+                self.aggregate(tran_id, context, a.aggregates.clone(), a.source.clone())
+            }
             Plan::CartesianJoin(cp) => {
-                self.cartesian_join(tran_id, cp.left.clone(), cp.right.clone())
+                self.cartesian_join(tran_id, context, cp.left.clone(), cp.right.clone())
             }
             Plan::FullTableScan(fts) => {
                 self.full_table_scan(tran_id, fts.table.clone(), fts.columns.clone())
             }
             Plan::ModifyTable(mt) => {
-                self.modify_table(tran_id, mt.table.clone(), mt.source.clone())
+                self.modify_table(tran_id, context, mt.table.clone(), mt.source.clone())
+            }
+            Plan::Projection(p) => {
+                self.projection(tran_id, context, p.targets.clone(), p.source.clone())
+            }
+            Plan::ProjectSet(ps) => {
+                self.project_set(tran_id, context, ps.functions.clone(), ps.source.clone())
             }
             Plan::StaticData(sd) => self.static_data(sd.clone()),
         }
     }
 
+    fn aggregate(
+        self,
+        tran_id: TransactionId,
+        context: ExpressionContext,
+        aggregates: Vec<(Aggregate, Expression)>,
+        source: Arc<Plan>,
+    ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
+        let s = try_stream! {
+            let mut inputs = vec![vec![]; aggregates.len()];
+            for await row in self.execute_plans(tran_id, context, source) {
+                let row = row?;
+                for ((_, argument), input) in aggregates.iter().zip(inputs.iter_mut()) {
+                    input.push(ExpressionEvaluator::evaluate(argument, &row, &context)?);
+                }
+            }
+
+            let mut output = Vec::with_capacity(aggregates.len());
+            for ((aggregate, _), input) in aggregates.iter().zip(inputs) {
+                output.push(ExpressionEvaluator::aggregate(*aggregate, input, &context)?);
+            }
+            yield SqlTuple(output);
+        };
+        Box::pin(s)
+    }
+
     fn cartesian_join(
         self,
         tran_id: TransactionId,
+        context: ExpressionContext,
         left: Arc<Plan>,
         right: Arc<Plan>,
     ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
         let s = try_stream! {
-            for await left_data in self.clone().execute_plans(tran_id, left) {
+            for await left_data in self.clone().execute_plans(tran_id, context, left) {
                 let left_data = left_data?;
 
-                for await right_data in self.clone().execute_plans(tran_id, right.clone()) {
+                for await right_data in self.clone().execute_plans(tran_id, context, right.clone()) {
                     let right_data = right_data?;
 
                     yield SqlTuple::merge(&left_data, &right_data);
@@ -79,30 +134,61 @@ impl Executor {
         columns: Vec<Attribute>,
     ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
         let s = try_stream! {
-            let vis = self.vis_row_man.clone();
+            if let Some(view) = SystemViews::find(table.id) {
+                for row in self.view_rows(view).await {
+                    yield row.filter_map(&table, &columns)?;
+                }
+            } else {
+                let vis = self.vis_row_man.clone();
 
-            for await row in vis.get_stream(tran_id, table.clone()) {
-                let data = row?.user_data.clone();
+                for await row in vis.get_stream(tran_id, table.clone()) {
+                    let data = row?.user_data.clone();
 
-                //Need to rewrite to the column / order needed
-                let requested_row = data.filter_map(&table, &columns)?;
+                    //Need to rewrite to the column / order needed
+                    let requested_row = data.filter_map(&table, &columns)?;
 
-                yield requested_row;
+                    yield requested_row;
+                }
             }
         };
         Box::pin(s)
     }
 
+    //A system view's rows as things are right now
+    async fn view_rows(&self, view: SystemViews) -> Vec<SqlTuple> {
+        match view {
+            SystemViews::PgStatBgwriter => {
+                let stats = self.vis_row_man.io_manager().checkpoint_stats().await;
+                let count = |c: usize| Some(BuiltinSqlTypes::BigInt(c as i64));
+                let last_checkpoint = match stats.last_checkpoint {
+                    0 => None,
+                    secs => Some(BuiltinSqlTypes::TimestampTz(DateTime::from_system_time(
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                    ))),
+                };
+                vec![SqlTuple(vec![
+                    count(stats.checkpoints_timed),
+                    count(stats.checkpoints_requested),
+                    count(stats.files_synced),
+                    count(stats.files_cleaned),
+                    count(stats.maxwritten_clean),
+                    last_checkpoint,
+                ])]
+            }
+        }
+    }
+
     fn modify_table(
         self,
         tran_id: TransactionId,
+        context: ExpressionContext,
         table: Arc<Table>,
         source: Arc<Plan>,
     ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
         let vis = self.vis_row_man.clone();
 
         let s = try_stream! {
-            for await val in self.execute_plans(tran_id, source) {
+            for await val in self.execute_plans(tran_id, context, source) {
                 let unwrapped_val = val?;
                 vis.clone()
                     .insert_row(tran_id, table.clone(), Arc::new(unwrapped_val.clone()))
@@ -113,31 +199,146 @@ impl Executor {
         Box::pin(s)
     }
 
+    fn projection(
+        self,
+        tran_id: TransactionId,
+        context: ExpressionContext,
+        targets: Vec<Expression>,
+        source: Arc<Plan>,
+    ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
+        let s = try_stream! {
+            for await row in self.execute_plans(tran_id, context, source) {
+                let row = row?;
+
+                let mut output = Vec::with_capacity(targets.len());
+                for t in targets.iter() {
+                    output.push(ExpressionEvaluator::evaluate(t, &row, &context)?);
+                }
+
+                yield SqlTuple(output);
+            }
+        };
+        Box::pin(s)
+    }
+
+    fn project_set(
+        self,
+        tran_id: TransactionId,
+        context: ExpressionContext,
+        functions: Vec<(SetFunction, Expression)>,
+        source: Arc<Plan>,
+    ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
+        let s = try_stream! {
+            for await row in self.execute_plans(tran_id, context, source) {
+                let row = row?;
+
+                let mut sets = Vec::with_capacity(functions.len());
+                for (function, argument) in functions.iter() {
+                    let value = ExpressionEvaluator::evaluate(argument, &row, &context)?;
+                    sets.push(ExpressionEvaluator::set_function(*function, value)?);
+                }
+
+                let count = sets.iter().map(Vec::len).max().unwrap_or(0);
+                for i in 0..count {
+                    let mut output = row.0.clone();
+                    output.extend(sets.iter().map(|s| s.get(i).cloned().flatten()));
+                    yield SqlTuple(output);
+                }
+            }
+        };
+        Box::pin(s)
+    }
+
     fn static_data(
         self,
         rows: Arc<Vec<SqlTuple>>,
     ) -> Pin<Box<impl Stream<Item = Result<SqlTuple, ExecutorError>>>> {
         let s = try_stream! {
-            for row in rows.as_ref().into_iter() {
+            for row in rows.as_ref().iter() {
                 yield row.clone();
             }
         };
         Box::pin(s)
     }
 
+    /// Inserts rows already in the table's column order in one batch, for bulk loads like COPY
+    pub async fn insert_rows(
+        &self,
+        tran_id: TransactionId,
+        table: Arc<Table>,
+        rows: Vec<Arc<SqlTuple>>,
+    ) -> Result<usize, ExecutorError> {
+        let count = rows.len();
+        self.vis_row_man
+            .clone()
+            .insert_rows(tran_id, table, rows)
+            .await?;
+        Ok(count)
+    }
+
     //Bypass planning since there isn't anything optimize
     pub async fn execute_utility(
         &self,
         tran_id: TransactionId,
         parse_tree: ParseTree,
     ) -> Result<Vec<SqlTuple>, ExecutorError> {
-        let rm = self.vis_row_man.clone();
+        match parse_tree {
+            ParseTree::CreateTable(t) => self.create_table(tran_id, t).await,
+            ParseTree::CreateType(t) => self.create_type(tran_id, t).await,
+            ParseTree::DropTable(t) => self.drop_table(tran_id, t).await,
+            _ => Err(ExecutorError::NotUtility()),
+        }
+    }
 
-        let create_table = match parse_tree {
-            ParseTree::CreateTable(t) => t,
-            _ => return Err(ExecutorError::NotUtility()),
+    /// Vacuums the named table or every table, returning what was done to each
+    pub async fn vacuum(
+        &self,
+        tran_id: TransactionId,
+        table_name: Option<String>,
+    ) -> Result<Vec<(String, VacuumStats)>, ExecutorError> {
+        let names = match table_name {
+            Some(t) => vec![t],
+            None => self.dl.get_table_names(tran_id).await?,
         };
 
+        let mut results = vec![];
+        for name in names {
+            let table = self.dl.get_definition(tran_id, name.clone()).await?;
+            if SystemViews::find(table.id).is_some() {
+                return Err(ExecutorError::SystemView(name));
+            }
+            results.push((name, self.vis_row_man.vacuum(table).await?));
+        }
+        Ok(results)
+    }
+
+    /// Rewrites the table without its dead rows while it stays in use
+    pub async fn repack(
+        &self,
+        tran_id: TransactionId,
+        table_name: String,
+    ) -> Result<(String, VacuumStats), ExecutorError> {
+        let table = self.dl.get_definition(tran_id, table_name.clone()).await?;
+        if SystemViews::find(table.id).is_some() {
+            return Err(ExecutorError::SystemView(table_name));
+        }
+        Ok((table_name, self.vis_row_man.repack(table).await?))
+    }
+
+    async fn create_table(
+        &self,
+        tran_id: TransactionId,
+        create_table: RawCreateTableCommand,
+    ) -> Result<Vec<SqlTuple>, ExecutorError> {
+        let rm = self.vis_row_man.clone();
+
+        //Make sure every type is real before writing anything
+        let user_types = self.dl.get_user_types(tran_id).await?;
+        let mut column_types = vec![];
+        for c in create_table.provided_columns.iter() {
+            column_types.push(DeserializeTypes::resolve(&c.sql_type, &user_types)?);
+        }
+
         let table_id = Uuid::new_v4();
         let pg_class = TableDefinitions::PgClass.value();
         let table_row = Arc::new(SqlTuple(vec![
@@ -147,27 +348,190 @@ impl Executor {
 
         rm.insert_row(tran_id, pg_class, table_row).await?;
 
+        self.insert_attributes(
+            tran_id,
+            table_id,
+            &create_table.provided_columns,
+            column_types,
+        )
+        .await?;
+        Ok(vec![])
+    }
+
+    /// Removes the table from the catalogs. Its pages are left behind, they can't be removed
+    /// until the transaction commits and nothing does that yet.
+    async fn drop_table(
+        &self,
+        tran_id: TransactionId,
+        drop_table: RawDropTableCommand,
+    ) -> Result<Vec<SqlTuple>, ExecutorError> {
+        let name = drop_table.table_name;
+        if TableDefinitions::VALUES
+            .iter()
+            .any(|t| t.value().name == name)
+        {
+            return Err(ExecutorError::SystemTable(name));
+        }
+        if SystemViews::VALUES.iter().any(|v| v.value().name == name) {
+            return Err(ExecutorError::SystemView(name));
+        }
+
+        let table_row = self.dl.get_table_row(tran_id, name).await?;
+        let table_id = match table_row
+            .get_column_not_null("id".to_string())
+            .map_err(DefinitionLookupError::from)?
+        {
+            BuiltinSqlTypes::Uuid(u) => u,
+            _ => return Err(ExecutorError::Unknown()),
+        };
+        for column in self.dl.get_table_columns(tran_id, table_id).await? {
+            self.vis_row_man
+                .clone()
+                .delete_row(
+                    tran_id,
+                    TableDefinitions::PgAttribute.value(),
+                    column.item_pointer,
+                )
+                .await?;
+        }
+        self.vis_row_man
+            .clone()
+            .delete_row(
+                tran_id,
+                TableDefinitions::PgClass.value(),
+                table_row.item_pointer,
+            )
+            .await?;
+        Ok(vec![])
+    }
+
+    async fn create_type(
+        &self,
+        tran_id: TransactionId,
+        create_type: RawCreateTypeCommand,
+    ) -> Result<Vec<SqlTuple>, ExecutorError> {
+        let rm = self.vis_row_man.clone();
+        let name = create_type.type_name;
+
+        let user_types = self.dl.get_user_types(tran_id).await?;
+        if DeserializeTypes::resolve(&name, &user_types).is_ok() {
+            return Err(ExecutorError::TypeAlreadyExists(name));
+        }
+
+        //Each type takes two OIDs, its own and its array type's
+        let oid = user_types
+            .iter()
+            .map(|t| DeserializeTypes::Array(Box::new(t.clone())).oid() + 1)
+            .max()
+            .unwrap_or(FIRST_USER_OID);
+        let array_oid = oid + 1;
+
+        let (sql_type, typrelid) = match create_type.definition {
+            RawTypeDefinition::Enum(labels) => {
+                let mut seen = HashSet::new();
+                for l in labels.iter() {
+                    if !seen.insert(l) {
+                        return Err(ExecutorError::DuplicateEnumLabel(name, l.clone()));
+                    }
+                }
+
+                let pg_enum = TableDefinitions::PgEnum.value();
+                for (i, label) in labels.iter().enumerate() {
+                    let i_i32 = i32::try_from(i).map_err(ExecutorError::ConversionError)?;
+                    let enum_row = Arc::new(SqlTuple(vec![
+                        Some(BuiltinSqlTypes::Integer(oid as i32)),
+                        Some(BuiltinSqlTypes::Integer(i_i32)),
+                        Some(BuiltinSqlTypes::Text(label.clone())),
+                    ]));
+                    rm.clone()
+                        .insert_row(tran_id, pg_enum.clone(), enum_row)
+                        .await?;
+                }
+
+                let enum_type = EnumType {
+                    oid,
+                    array_oid,
+                    name: name.clone(),
+                    labels,
+                };
+                (DeserializeTypes::Enum(Arc::new(enum_type)), None)
+            }
+            RawTypeDefinition::Composite(fields) => {
+                let mut seen = HashSet::new();
+                let mut field_types = vec![];
+                for f in fields.iter() {
+                    if !seen.insert(&f.name) {
+                        return Err(ExecutorError::DuplicateField(name, f.name.clone()));
+                    }
+                    field_types.push(DeserializeTypes::resolve(&f.sql_type, &user_types)?);
+                }
+
+                //The fields are stored the same way as a table's columns
+                let typrelid = Uuid::new_v4();
+                self.insert_attributes(tran_id, typrelid, &fields, field_types.clone())
+                    .await?;
+
+                let composite_type = CompositeType {
+                    oid,
+                    array_oid,
+                    name: name.clone(),
+                    fields: fields
+                        .into_iter()
+                        .zip(field_types)
+                        .map(|(f, sql_type)| CompositeField {
+                            name: f.name,
+                            sql_type,
+                        })
+                        .collect(),
+                };
+                (
+                    DeserializeTypes::Composite(Arc::new(composite_type)),
+                    Some(typrelid),
+                )
+            }
+        };
+
+        let pg_type = TableDefinitions::PgType.value();
+        let array_type = DeserializeTypes::Array(Box::new(sql_type.clone()));
+        let typtype = match sql_type {
+            DeserializeTypes::Enum(_) => 'e',
+            _ => 'c',
+        };
+        let mut type_row =
+            TableDefinitions::pg_type_row(&sql_type, typtype, None, Some(&array_type));
+        type_row.0[5] = typrelid.map(BuiltinSqlTypes::Uuid);
+        let array_row = TableDefinitions::pg_type_row(&array_type, 'b', Some(&sql_type), None);
+        rm.clone()
+            .insert_row(tran_id, pg_type.clone(), Arc::new(type_row))
+            .await?;
+        rm.insert_row(tran_id, pg_type, Arc::new(array_row)).await?;
+        Ok(vec![])
+    }
+
+    //Writes the pg_attribute rows for a table's columns or a composite type's fields
+    async fn insert_attributes(
+        &self,
+        tran_id: TransactionId,
+        attrelid: Uuid,
+        columns: &[RawColumn],
+        column_types: Vec<DeserializeTypes>,
+    ) -> Result<(), ExecutorError> {
         let pg_attribute = TableDefinitions::PgAttribute.value();
-        for i in 0..create_table.provided_columns.len() {
+        for (i, (column, column_type)) in columns.iter().zip(column_types).enumerate() {
             let rm = self.vis_row_man.clone();
-            let i_u32 = u32::try_from(i).map_err(ExecutorError::ConversionError)?;
+            let i_i32 = i32::try_from(i).map_err(ExecutorError::ConversionError)?;
             let table_row = Arc::new(SqlTuple(vec![
-                Some(BuiltinSqlTypes::Uuid(table_id)),
-                Some(BuiltinSqlTypes::Text(
-                    create_table.provided_columns[i].name.clone(),
-                )),
-                Some(BuiltinSqlTypes::Text(
-                    //TODO we did not validate that it is a real type
-                    create_table.provided_columns[i].sql_type.clone(),
-                )),
-                Some(BuiltinSqlTypes::Integer(i_u32)),
-                Some(BuiltinSqlTypes::Bool(create_table.provided_columns[i].null)),
+                Some(BuiltinSqlTypes::Uuid(attrelid)),
+                Some(BuiltinSqlTypes::Text(column.name.clone())),
+                Some(BuiltinSqlTypes::Integer(column_type.oid() as i32)),
+                Some(BuiltinSqlTypes::Integer(i_i32)),
+                Some(BuiltinSqlTypes::Integer(column_type.type_modifier())),
+                Some(BuiltinSqlTypes::Bool(column.null)),
             ]));
-            rm.clone()
-                .insert_row(tran_id, pg_attribute.clone(), table_row)
+            rm.insert_row(tran_id, pg_attribute.clone(), table_row)
                 .await?;
         }
-        Ok(vec![])
+        Ok(())
     }
 }
 
@@ -175,14 +539,47 @@ impl Executor {
 pub enum ExecutorError {
     #[error("Not a utility statement")]
     NotUtility(),
+    #[error("type \"{0}\" already exists")]
+    TypeAlreadyExists(String),
+    #[error("enum {0} has the label \"{1}\" more than once")]
+    DuplicateEnumLabel(String, String),
+    #[error("type {0} has the field \"{1}\" more than once")]
+    DuplicateField(String, String),
+    #[error("permission denied: \"{0}\" is a system catalog")]
+    SystemTable(String),
+    #[error("\"{0}\" is a view")]
+    SystemView(String),
+    #[error(transparent)]
+    DefinitionLookupError(#[from] DefinitionLookupError),
+    #[error(transparent)]
+    ExpressionEvaluatorError(#[from] ExpressionEvaluatorError),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
     #[error(transparent)]
     SqlTupleError(#[from] SqlTupleError),
     #[error(transparent)]
     VisibleRowManagerError(#[from] VisibleRowManagerError),
-    #[error("Unable to convert usize to u32")]
+    #[error("Unable to convert usize to i32")]
     ConversionError(#[from] TryFromIntError),
     #[error("Recursive Plans Not Allowed")]
     RecursionNotAllowed(),
     #[error("Unknown")]
     Unknown(),
 }
+
+impl ExecutorError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            ExecutorError::ExpressionEvaluatorError(e) => e.pg_error_code(),
+            ExecutorError::SqlTypeError(e) => e.pg_error_code(),
+            ExecutorError::DefinitionLookupError(e) => e.pg_error_code(),
+            ExecutorError::TypeAlreadyExists(_) => PgErrorCodes::DuplicateObject,
+            ExecutorError::DuplicateEnumLabel(_, _) => PgErrorCodes::InvalidParameterValue,
+            ExecutorError::DuplicateField(_, _) => PgErrorCodes::DuplicateColumn,
+            ExecutorError::SystemTable(_) => PgErrorCodes::InsufficientPrivilege,
+            ExecutorError::SystemView(_) => PgErrorCodes::WrongObjectType,
+            ExecutorError::VisibleRowManagerError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
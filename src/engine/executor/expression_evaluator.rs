@@ -0,0 +1,1748 @@
+//! Evaluates analyzed expressions against a row. The analyzer has already cast both sides of
+//! every operator to the same type so the math here only deals with matching types.
+//!
+//! Null in means null out for every operator and cast.
+//!
+//! The date and time operators are the exception to matching types, the analyzer picks the
+//! type of each side from postgres' operator list, for example date + integer. The json
+//! operators are the same, a json value on the left and a key, index or path on the right.
+//!
+//! Arrays compare element by element with nulls after everything else, the same as postgres'
+//! btree ordering.
+
+use super::super::objects::{
+    Aggregate, Expression, ExpressionContext, Function, Operator, Quantifier, SetFunction,
+    SqlTuple, Subscript,
+};
+use crate::constants::{
+    ArrayDimension, BuiltinSqlTypes, DateTime, DeserializeTypes, Interval, JsonValue, PgErrorCodes,
+    SqlArray, SqlTypeError,
+};
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::num_traits::{PrimInt, Signed};
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::SystemTime;
+use thiserror::Error;
+
+//Division results get at least this many significant digits, same as postgres
+const NUMERIC_MIN_SIG_DIGITS: i64 = 16;
+const NUMERIC_MAX_DISPLAY_SCALE: i64 = 1000;
+
+pub struct ExpressionEvaluator {}
+
+impl ExpressionEvaluator {
+    pub fn evaluate(
+        expression: &Expression,
+        row: &SqlTuple,
+        context: &ExpressionContext,
+    ) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        match expression {
+            Expression::Constant(value, _) => Ok(value.clone()),
+            Expression::Column(index, _) => row
+                .0
+                .get(*index)
+                .cloned()
+                .ok_or(ExpressionEvaluatorError::MissingColumn(*index)),
+            Expression::Negate(e) => match ExpressionEvaluator::evaluate(e, row, context)? {
+                Some(value) => Ok(Some(ExpressionEvaluator::negate(value)?)),
+                None => Ok(None),
+            },
+            Expression::Cast(e, target) => match ExpressionEvaluator::evaluate(e, row, context)? {
+                //Like postgres the padding of a char is not kept when it becomes another string
+                Some(BuiltinSqlTypes::Text(t))
+                    if matches!(e.sql_type(), DeserializeTypes::Char(_))
+                        && !matches!(target, DeserializeTypes::Char(_)) =>
+                {
+                    Ok(Some(ExpressionEvaluator::cast(
+                        BuiltinSqlTypes::Text(t.trim_end_matches(' ').to_string()),
+                        target.clone(),
+                        context,
+                    )?))
+                }
+                Some(value) => Ok(Some(ExpressionEvaluator::cast(
+                    value,
+                    target.clone(),
+                    context,
+                )?)),
+                None => Ok(None),
+            },
+            Expression::Truncate(e, target) => {
+                match ExpressionEvaluator::evaluate(e, row, context)? {
+                    Some(value) => Ok(Some(ExpressionEvaluator::truncate(value, target)?)),
+                    None => Ok(None),
+                }
+            }
+            Expression::Operator(op, left, right, result_type) => {
+                let left = ExpressionEvaluator::evaluate(left, row, context)?;
+                let right = ExpressionEvaluator::evaluate(right, row, context)?;
+                match (left, right) {
+                    (Some(l), Some(r)) if op.is_json() => {
+                        ExpressionEvaluator::json_operator(*op, l, r)
+                    }
+                    (Some(l), Some(r)) if op.is_comparison() => Ok(Some(BuiltinSqlTypes::Bool(
+                        ExpressionEvaluator::compare(*op, l, r)?,
+                    ))),
+                    (Some(l), Some(r)) => Ok(Some(ExpressionEvaluator::operator(
+                        *op,
+                        l,
+                        r,
+                        result_type.clone(),
+                        context,
+                    )?)),
+                    (_, _) => Ok(None),
+                }
+            }
+            Expression::Function(f, args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for a in args {
+                    values.push(ExpressionEvaluator::evaluate(a, row, context)?);
+                }
+                if f.is_strict() && values.iter().any(Option::is_none) {
+                    return Ok(None);
+                }
+                ExpressionEvaluator::function(*f, values, context)
+            }
+            Expression::Aggregate(a, _) => Err(ExpressionEvaluatorError::UnexpectedAggregate(*a)),
+            Expression::SetFunction(f, _) => {
+                Err(ExpressionEvaluatorError::UnexpectedSetFunction(*f))
+            }
+            Expression::Array(elements, _) => {
+                let nested = elements.iter().any(|e| e.sql_type().is_array());
+                let mut values = Vec::with_capacity(elements.len());
+                for e in elements {
+                    values.push(ExpressionEvaluator::evaluate(e, row, context)?);
+                }
+                if !nested {
+                    return Ok(Some(BuiltinSqlTypes::Array(SqlArray::new(values))));
+                }
+                //Like postgres null sub arrays are left out instead of being an error
+                let mut arrays = vec![];
+                for v in values.into_iter().flatten() {
+                    match v {
+                        BuiltinSqlTypes::Array(a) => arrays.push(a),
+                        _ => {
+                            return Err(ExpressionEvaluatorError::CannotCast(
+                                v,
+                                expression.sql_type(),
+                            ))
+                        }
+                    }
+                }
+                Ok(Some(BuiltinSqlTypes::Array(SqlArray::stack(arrays)?)))
+            }
+            Expression::Subscript(e, subscripts) => {
+                ExpressionEvaluator::subscript(e, subscripts, row, context)
+            }
+            Expression::ArrayComparison(op, quantifier, left, right) => {
+                let left = ExpressionEvaluator::evaluate(left, row, context)?;
+                let right = match ExpressionEvaluator::evaluate(right, row, context)? {
+                    Some(BuiltinSqlTypes::Array(a)) => a,
+                    Some(value) => {
+                        return Err(ExpressionEvaluatorError::InvalidOperand(
+                            quantifier.to_string(),
+                            value,
+                        ))
+                    }
+                    None => return Ok(None),
+                };
+                ExpressionEvaluator::array_comparison(*op, *quantifier, left, right)
+            }
+            Expression::Row(fields, _) => {
+                let mut values = Vec::with_capacity(fields.len());
+                for f in fields {
+                    values.push(ExpressionEvaluator::evaluate(f, row, context)?);
+                }
+                Ok(Some(BuiltinSqlTypes::Composite(values)))
+            }
+            Expression::FieldSelect(e, index, _) => {
+                match ExpressionEvaluator::evaluate(e, row, context)? {
+                    Some(BuiltinSqlTypes::Composite(values)) => {
+                        Ok(values.get(*index).cloned().flatten())
+                    }
+                    Some(value) => Err(ExpressionEvaluatorError::InvalidOperand(
+                        format!("field {}", index),
+                        value,
+                    )),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    //A null subscript makes the whole result null, out of range subscripts are also null
+    fn subscript(
+        expression: &Expression,
+        subscripts: &[Subscript],
+        row: &SqlTuple,
+        context: &ExpressionContext,
+    ) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        let array = match ExpressionEvaluator::evaluate(expression, row, context)? {
+            Some(BuiltinSqlTypes::Array(a)) => a,
+            Some(value) => {
+                return Err(ExpressionEvaluatorError::InvalidOperand(
+                    "[]".to_string(),
+                    value,
+                ))
+            }
+            None => return Ok(None),
+        };
+
+        let index = |e: &Expression| -> Result<Option<i64>, ExpressionEvaluatorError> {
+            match ExpressionEvaluator::evaluate(e, row, context)? {
+                Some(BuiltinSqlTypes::Integer(i)) => Ok(Some(i.into())),
+                Some(value) => Err(ExpressionEvaluatorError::InvalidOperand(
+                    "[]".to_string(),
+                    value,
+                )),
+                None => Ok(None),
+            }
+        };
+
+        //Once one subscript is a slice every plain subscript n is read as 1:n
+        let is_slice = subscripts
+            .iter()
+            .any(|s| matches!(s, Subscript::Slice(_, _)));
+        let mut indexes = vec![];
+        let mut bounds = vec![];
+        for s in subscripts {
+            match s {
+                Subscript::Index(e) => match index(e)? {
+                    Some(i) => {
+                        indexes.push(i);
+                        bounds.push((Some(1), Some(i)));
+                    }
+                    None => return Ok(None),
+                },
+                Subscript::Slice(lower, upper) => {
+                    let bound = |e: &Option<Expression>| match e {
+                        Some(e) => index(e).map(|i| i.map(Some)),
+                        None => Ok(Some(None)),
+                    };
+                    match (bound(lower)?, bound(upper)?) {
+                        (Some(l), Some(u)) => bounds.push((l, u)),
+                        (_, _) => return Ok(None),
+                    }
+                }
+            }
+        }
+
+        if is_slice {
+            Ok(Some(BuiltinSqlTypes::Array(array.slice(&bounds))))
+        } else {
+            Ok(array.get(&indexes).cloned())
+        }
+    }
+
+    /// ANY is true if the comparison is true for an element, ALL if it is true for every
+    /// element. Null elements make an otherwise false ANY or true ALL null.
+    fn array_comparison(
+        op: Operator,
+        quantifier: Quantifier,
+        left: Option<BuiltinSqlTypes>,
+        right: SqlArray,
+    ) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        let empty_result = quantifier == Quantifier::All;
+        if right.elements.is_empty() {
+            return Ok(Some(BuiltinSqlTypes::Bool(empty_result)));
+        }
+        let left = match left {
+            Some(l) => l,
+            None => return Ok(None),
+        };
+
+        let mut saw_null = false;
+        for element in right.elements {
+            match element {
+                Some(e) => {
+                    if ExpressionEvaluator::compare(op, left.clone(), e)? != empty_result {
+                        return Ok(Some(BuiltinSqlTypes::Bool(!empty_result)));
+                    }
+                }
+                None => saw_null = true,
+            }
+        }
+        if saw_null {
+            Ok(None)
+        } else {
+            Ok(Some(BuiltinSqlTypes::Bool(empty_result)))
+        }
+    }
+
+    //An explicit cast to varchar(n) or char(n), or an array of them
+    fn truncate(
+        value: BuiltinSqlTypes,
+        target: &DeserializeTypes,
+    ) -> Result<BuiltinSqlTypes, ExpressionEvaluatorError> {
+        match value {
+            BuiltinSqlTypes::Text(t) => Ok(BuiltinSqlTypes::Text(
+                BuiltinSqlTypes::apply_length_typmod(t, target.clone(), true)?,
+            )),
+            BuiltinSqlTypes::Array(a) if target.is_array() => {
+                let mut elements = Vec::with_capacity(a.elements.len());
+                for e in a.elements {
+                    elements.push(
+                        e.map(|e| ExpressionEvaluator::truncate(e, target.element_type()))
+                            .transpose()?,
+                    );
+                }
+                Ok(BuiltinSqlTypes::Array(SqlArray {
+                    dimensions: a.dimensions,
+                    elements,
+                }))
+            }
+            _ => Err(ExpressionEvaluatorError::CannotCast(value, target.clone())),
+        }
+    }
+
+    pub fn cast(
+        value: BuiltinSqlTypes,
+        target: DeserializeTypes,
+        context: &ExpressionContext,
+    ) -> Result<BuiltinSqlTypes, ExpressionEvaluatorError> {
+        if let (BuiltinSqlTypes::Array(a), DeserializeTypes::Array(element)) = (&value, &target) {
+            let mut elements = Vec::with_capacity(a.elements.len());
+            for e in &a.elements {
+                elements.push(match e {
+                    Some(e) => Some(ExpressionEvaluator::cast(
+                        e.clone(),
+                        *element.clone(),
+                        context,
+                    )?),
+                    None => None,
+                });
+            }
+            return Ok(BuiltinSqlTypes::Array(SqlArray {
+                dimensions: a.dimensions.clone(),
+                elements,
+            }));
+        }
+        if target.is_string() {
+            let text = match value {
+                BuiltinSqlTypes::Text(t) => t,
+                _ => value.to_string_with(&context.time_zone, context.bytea_output),
+            };
+            return Ok(BuiltinSqlTypes::Text(BuiltinSqlTypes::apply_length_typmod(
+                text, target, false,
+            )?));
+        }
+        if let BuiltinSqlTypes::Text(t) = value {
+            return Ok(BuiltinSqlTypes::parse_in_zone(
+                target,
+                t,
+                &context.time_zone,
+            )?);
+        }
+
+        match target {
+            DeserializeTypes::SmallInt => {
+                let i = ExpressionEvaluator::to_i64(value, target.clone())?;
+                Ok(BuiltinSqlTypes::SmallInt(i16::try_from(i).map_err(
+                    |_| ExpressionEvaluatorError::OutOfRange(target),
+                )?))
+            }
+            DeserializeTypes::Integer => {
+                let i = ExpressionEvaluator::to_i64(value, target.clone())?;
+                Ok(BuiltinSqlTypes::Integer(i32::try_from(i).map_err(
+                    |_| ExpressionEvaluatorError::OutOfRange(target),
+                )?))
+            }
+            DeserializeTypes::BigInt => Ok(BuiltinSqlTypes::BigInt(ExpressionEvaluator::to_i64(
+                value, target,
+            )?)),
+            DeserializeTypes::Real => {
+                let f = ExpressionEvaluator::to_f64(value, target.clone())?;
+                let r = f as f32;
+                if r.is_infinite() && f.is_finite() {
+                    return Err(ExpressionEvaluatorError::OutOfRange(target));
+                }
+                Ok(BuiltinSqlTypes::Real(r))
+            }
+            DeserializeTypes::DoublePrecision => Ok(BuiltinSqlTypes::DoublePrecision(
+                ExpressionEvaluator::to_f64(value, target)?,
+            )),
+            DeserializeTypes::Numeric(typmod) => {
+                let n = match value {
+                    BuiltinSqlTypes::SmallInt(i) => BigDecimal::from(i),
+                    BuiltinSqlTypes::Integer(i) => BigDecimal::from(i),
+                    BuiltinSqlTypes::BigInt(i) => BigDecimal::from(i),
+                    BuiltinSqlTypes::Real(f) => ExpressionEvaluator::float_to_numeric(f.into())?,
+                    BuiltinSqlTypes::DoublePrecision(f) => {
+                        ExpressionEvaluator::float_to_numeric(f)?
+                    }
+                    BuiltinSqlTypes::Numeric(n) => n,
+                    _ => return Err(ExpressionEvaluatorError::CannotCast(value, target)),
+                };
+                Ok(BuiltinSqlTypes::Numeric(
+                    BuiltinSqlTypes::apply_numeric_typmod(n, typmod)?,
+                ))
+            }
+            DeserializeTypes::Date
+            | DeserializeTypes::Time
+            | DeserializeTypes::Timestamp
+            | DeserializeTypes::TimestampTz
+            | DeserializeTypes::Interval => {
+                ExpressionEvaluator::cast_date_time(value, target, context)
+            }
+            DeserializeTypes::Json => match value {
+                BuiltinSqlTypes::Jsonb(v) => Ok(BuiltinSqlTypes::Json(v.to_string())),
+                _ => Err(ExpressionEvaluatorError::CannotCast(value, target)),
+            },
+            DeserializeTypes::Jsonb => match value {
+                BuiltinSqlTypes::Json(t) => Ok(BuiltinSqlTypes::Jsonb(JsonValue::parse_jsonb(&t)?)),
+                _ => Err(ExpressionEvaluatorError::CannotCast(value, target)),
+            },
+            _ => {
+                if value.type_matches(&target) {
+                    Ok(value)
+                } else {
+                    Err(ExpressionEvaluatorError::CannotCast(value, target))
+                }
+            }
+        }
+    }
+
+    //Timestamps without a zone are wall clock times in the session's zone
+    fn cast_date_time(
+        value: BuiltinSqlTypes,
+        target: DeserializeTypes,
+        context: &ExpressionContext,
+    ) -> Result<BuiltinSqlTypes, ExpressionEvaluatorError> {
+        let zone = &context.time_zone;
+        let result = match (&value, &target) {
+            (BuiltinSqlTypes::Timestamp(t), DeserializeTypes::Date) => {
+                DateTime::timestamp_to_date(*t).map(BuiltinSqlTypes::Date)
+            }
+            (BuiltinSqlTypes::TimestampTz(t), DeserializeTypes::Date) => {
+                DateTime::utc_to_local(*t, zone)
+                    .and_then(DateTime::timestamp_to_date)
+                    .map(BuiltinSqlTypes::Date)
+            }
+            (BuiltinSqlTypes::Timestamp(t), DeserializeTypes::Time) => {
+                DateTime::timestamp_to_time(*t).map(BuiltinSqlTypes::Time)
+            }
+            (BuiltinSqlTypes::TimestampTz(t), DeserializeTypes::Time) => {
+                DateTime::utc_to_local(*t, zone)
+                    .and_then(DateTime::timestamp_to_time)
+                    .map(BuiltinSqlTypes::Time)
+            }
+            (BuiltinSqlTypes::Interval(i), DeserializeTypes::Time) => {
+                Some(BuiltinSqlTypes::Time(DateTime::add_interval_to_time(0, i)))
+            }
+            (BuiltinSqlTypes::Date(d), DeserializeTypes::Timestamp) => {
+                DateTime::date_to_timestamp(*d).map(BuiltinSqlTypes::Timestamp)
+            }
+            (BuiltinSqlTypes::TimestampTz(t), DeserializeTypes::Timestamp) => {
+                DateTime::utc_to_local(*t, zone).map(BuiltinSqlTypes::Timestamp)
+            }
+            (BuiltinSqlTypes::Date(d), DeserializeTypes::TimestampTz) => {
+                DateTime::date_to_timestamp(*d)
+                    .and_then(|t| DateTime::local_to_utc(t, zone))
+                    .map(BuiltinSqlTypes::TimestampTz)
+            }
+            (BuiltinSqlTypes::Timestamp(t), DeserializeTypes::TimestampTz) => {
+                DateTime::local_to_utc(*t, zone).map(BuiltinSqlTypes::TimestampTz)
+            }
+            (BuiltinSqlTypes::Time(t), DeserializeTypes::Interval) => {
+                Some(BuiltinSqlTypes::Interval(Interval::new(0, 0, *t)))
+            }
+            (_, _) => {
+                if value.type_matches(&target) {
+                    return Ok(value);
+                }
+                return Err(ExpressionEvaluatorError::CannotCast(value, target));
+            }
+        };
+        result.ok_or(ExpressionEvaluatorError::OutOfRange(target))
+    }
+
+    //Floats round half to even like postgres' rint, numerics round half away from zero
+    fn to_i64(
+        value: BuiltinSqlTypes,
+        target: DeserializeTypes,
+    ) -> Result<i64, ExpressionEvaluatorError> {
+        let out_of_range = ExpressionEvaluatorError::OutOfRange(target.clone());
+        match value {
+            BuiltinSqlTypes::SmallInt(i) => Ok(i.into()),
+            BuiltinSqlTypes::Integer(i) => Ok(i.into()),
+            BuiltinSqlTypes::BigInt(i) => Ok(i),
+            BuiltinSqlTypes::Real(f) => ExpressionEvaluator::float_to_i64(f.into(), target),
+            BuiltinSqlTypes::DoublePrecision(f) => ExpressionEvaluator::float_to_i64(f, target),
+            BuiltinSqlTypes::Numeric(n) => n
+                .with_scale_round(0, RoundingMode::HalfUp)
+                .to_i64()
+                .ok_or(out_of_range),
+            _ => Err(ExpressionEvaluatorError::CannotCast(value, target)),
+        }
+    }
+
+    fn float_to_i64(f: f64, target: DeserializeTypes) -> Result<i64, ExpressionEvaluatorError> {
+        let rounded = f.round_ties_even();
+        //i64::MAX is not representable as a float, the closest is 2^63 which is out of range
+        if rounded.is_nan() || rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 {
+            return Err(ExpressionEvaluatorError::OutOfRange(target));
+        }
+        Ok(rounded as i64)
+    }
+
+    fn to_f64(
+        value: BuiltinSqlTypes,
+        target: DeserializeTypes,
+    ) -> Result<f64, ExpressionEvaluatorError> {
+        match value {
+            BuiltinSqlTypes::SmallInt(i) => Ok(i.into()),
+            BuiltinSqlTypes::Integer(i) => Ok(i.into()),
+            BuiltinSqlTypes::BigInt(i) => Ok(i as f64),
+            BuiltinSqlTypes::Real(f) => Ok(f.into()),
+            BuiltinSqlTypes::DoublePrecision(f) => Ok(f),
+            BuiltinSqlTypes::Numeric(ref n) => {
+                let f = n
+                    .to_f64()
+                    .ok_or_else(|| ExpressionEvaluatorError::OutOfRange(target.clone()))?;
+                if f.is_infinite() {
+                    return Err(ExpressionEvaluatorError::OutOfRange(target));
+                }
+                Ok(f)
+            }
+            _ => Err(ExpressionEvaluatorError::CannotCast(value, target)),
+        }
+    }
+
+    //Going through the shortest text form keeps 0.1 as 0.1 instead of its binary expansion
+    fn float_to_numeric(f: f64) -> Result<BigDecimal, ExpressionEvaluatorError> {
+        if !f.is_finite() {
+            return Err(ExpressionEvaluatorError::CannotConvertToNumeric(f));
+        }
+        let mut value = BigDecimal::from_str(&f.to_string())
+            .map_err(|_| ExpressionEvaluatorError::CannotConvertToNumeric(f))?;
+        if value.fractional_digit_count() < 0 {
+            value = value.with_scale(0);
+        }
+        Ok(value)
+    }
+
+    fn negate(value: BuiltinSqlTypes) -> Result<BuiltinSqlTypes, ExpressionEvaluatorError> {
+        match value {
+            BuiltinSqlTypes::SmallInt(i) => Ok(BuiltinSqlTypes::SmallInt(i.checked_neg().ok_or(
+                ExpressionEvaluatorError::OutOfRange(DeserializeTypes::SmallInt),
+            )?)),
+            BuiltinSqlTypes::Integer(i) => Ok(BuiltinSqlTypes::Integer(i.checked_neg().ok_or(
+                ExpressionEvaluatorError::OutOfRange(DeserializeTypes::Integer),
+            )?)),
+            BuiltinSqlTypes::BigInt(i) => Ok(BuiltinSqlTypes::BigInt(i.checked_neg().ok_or(
+                ExpressionEvaluatorError::OutOfRange(DeserializeTypes::BigInt),
+            )?)),
+            BuiltinSqlTypes::Real(f) => Ok(BuiltinSqlTypes::Real(-f)),
+            BuiltinSqlTypes::DoublePrecision(f) => Ok(BuiltinSqlTypes::DoublePrecision(-f)),
+            BuiltinSqlTypes::Numeric(n) => Ok(BuiltinSqlTypes::Numeric(-n)),
+            BuiltinSqlTypes::Interval(i) => Ok(BuiltinSqlTypes::Interval(i.checked_neg().ok_or(
+                ExpressionEvaluatorError::OutOfRange(DeserializeTypes::Interval),
+            )?)),
+            _ => Err(ExpressionEvaluatorError::InvalidOperand(
+                "-".to_string(),
+                value,
+            )),
+        }
+    }
+
+    fn function(
+        function: Function,
+        args: Vec<Option<BuiltinSqlTypes>>,
+        context: &ExpressionContext,
+    ) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        let start = context.transaction_start;
+        let local = || {
+            DateTime::utc_to_local(start, &context.time_zone)
+                .ok_or(ExpressionEvaluatorError::OutOfRange(function.sql_type()))
+        };
+        let result = match function {
+            Function::Now => Ok(BuiltinSqlTypes::TimestampTz(start)),
+            Function::ClockTimestamp => Ok(BuiltinSqlTypes::TimestampTz(
+                DateTime::from_system_time(SystemTime::now()),
+            )),
+            Function::CurrentDate => Ok(BuiltinSqlTypes::Date(
+                DateTime::timestamp_to_date(local()?)
+                    .ok_or(ExpressionEvaluatorError::OutOfRange(DeserializeTypes::Date))?,
+            )),
+            Function::LocalTime => Ok(BuiltinSqlTypes::Time(
+                DateTime::timestamp_to_time(local()?)
+                    .ok_or(ExpressionEvaluatorError::OutOfRange(DeserializeTypes::Time))?,
+            )),
+            Function::LocalTimestamp => Ok(BuiltinSqlTypes::Timestamp(local()?)),
+            Function::JsonBuildObject => {
+                let mut pairs = vec![];
+                for (i, pair) in args.chunks(2).enumerate() {
+                    let key = ExpressionEvaluator::json_object_key(i, &pair[0], context)?;
+                    pairs.push(format!(
+                        "{} : {}",
+                        JsonValue::quote(&key),
+                        ExpressionEvaluator::to_json_text(&pair[1], context)?
+                    ));
+                }
+                Ok(BuiltinSqlTypes::Json(format!("{{{}}}", pairs.join(", "))))
+            }
+            Function::JsonbBuildObject => {
+                let mut pairs = vec![];
+                for (i, pair) in args.chunks(2).enumerate() {
+                    pairs.push((
+                        ExpressionEvaluator::json_object_key(i, &pair[0], context)?,
+                        ExpressionEvaluator::to_json_value(&pair[1], context)?,
+                    ));
+                }
+                Ok(BuiltinSqlTypes::Jsonb(
+                    JsonValue::Object(pairs).into_jsonb(),
+                ))
+            }
+            Function::JsonBuildArray => Ok(BuiltinSqlTypes::Json(
+                ExpressionEvaluator::json_array_text(&args, context)?,
+            )),
+            Function::JsonbBuildArray => Ok(BuiltinSqlTypes::Jsonb(JsonValue::Array(
+                args.iter()
+                    .map(|a| ExpressionEvaluator::to_json_value(a, context))
+                    .collect::<Result<_, _>>()?,
+            ))),
+            Function::ToJson => Ok(BuiltinSqlTypes::Json(ExpressionEvaluator::to_json_text(
+                &args[0], context,
+            )?)),
+            Function::ToJsonb => Ok(BuiltinSqlTypes::Jsonb(ExpressionEvaluator::to_json_value(
+                &args[0], context,
+            )?)),
+            Function::JsonTypeof | Function::JsonbTypeof => {
+                let value = ExpressionEvaluator::to_json_value(&args[0], context)?;
+                Ok(BuiltinSqlTypes::Text(value.type_name().to_string()))
+            }
+        };
+        result.map(Some)
+    }
+
+    /// Combines an aggregate's argument from every input row, with no rows the result is null
+    pub fn aggregate(
+        aggregate: Aggregate,
+        values: Vec<Option<BuiltinSqlTypes>>,
+        context: &ExpressionContext,
+    ) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        if values.is_empty() {
+            return Ok(None);
+        }
+        match aggregate {
+            Aggregate::JsonAgg => Ok(Some(BuiltinSqlTypes::Json(
+                ExpressionEvaluator::json_array_text(&values, context)?,
+            ))),
+            Aggregate::JsonbAgg => Ok(Some(BuiltinSqlTypes::Jsonb(JsonValue::Array(
+                values
+                    .iter()
+                    .map(|v| ExpressionEvaluator::to_json_value(v, context))
+                    .collect::<Result<_, _>>()?,
+            )))),
+        }
+    }
+
+    /// The values of a set function, a null argument gives no values at all
+    pub fn set_function(
+        function: SetFunction,
+        argument: Option<BuiltinSqlTypes>,
+    ) -> Result<Vec<Option<BuiltinSqlTypes>>, ExpressionEvaluatorError> {
+        match (function, argument) {
+            (SetFunction::Unnest, Some(BuiltinSqlTypes::Array(a))) => Ok(a.elements),
+            (SetFunction::Unnest, Some(value)) => Err(ExpressionEvaluatorError::InvalidOperand(
+                function.to_string(),
+                value,
+            )),
+            (_, None) => Ok(vec![]),
+        }
+    }
+
+    fn json_object_key(
+        index: usize,
+        key: &Option<BuiltinSqlTypes>,
+        context: &ExpressionContext,
+    ) -> Result<String, ExpressionEvaluatorError> {
+        match key {
+            Some(BuiltinSqlTypes::Text(t)) => Ok(t.clone()),
+            Some(k) => Ok(k.to_string_with(&context.time_zone, context.bytea_output)),
+            None => Err(ExpressionEvaluatorError::NullJsonKey(index * 2 + 1)),
+        }
+    }
+
+    //Json arrays are written out the way postgres' json functions do, not like jsonb
+    fn json_array_text(
+        values: &[Option<BuiltinSqlTypes>],
+        context: &ExpressionContext,
+    ) -> Result<String, ExpressionEvaluatorError> {
+        let mut elements = vec![];
+        for v in values {
+            elements.push(ExpressionEvaluator::to_json_text(v, context)?);
+        }
+        Ok(format!("[{}]", elements.join(", ")))
+    }
+
+    //Json values are kept exactly as written, anything else is converted
+    fn to_json_text(
+        value: &Option<BuiltinSqlTypes>,
+        context: &ExpressionContext,
+    ) -> Result<String, ExpressionEvaluatorError> {
+        match value {
+            Some(BuiltinSqlTypes::Json(t)) => Ok(t.clone()),
+            _ => Ok(ExpressionEvaluator::to_json_value(value, context)?.to_string()),
+        }
+    }
+
+    /// Converts a sql value the way to_jsonb does. Numbers and booleans keep their json types,
+    /// timestamps use the ISO 8601 form and everything else becomes its text.
+    fn to_json_value(
+        value: &Option<BuiltinSqlTypes>,
+        context: &ExpressionContext,
+    ) -> Result<JsonValue, ExpressionEvaluatorError> {
+        let value = match value {
+            Some(v) => v,
+            None => return Ok(JsonValue::Null),
+        };
+        let json = match value {
+            BuiltinSqlTypes::Bool(b) => JsonValue::Bool(*b),
+            BuiltinSqlTypes::SmallInt(i) => JsonValue::Number(BigDecimal::from(*i)),
+            BuiltinSqlTypes::Integer(i) => JsonValue::Number(BigDecimal::from(*i)),
+            BuiltinSqlTypes::BigInt(i) => JsonValue::Number(BigDecimal::from(*i)),
+            BuiltinSqlTypes::Numeric(n) => JsonValue::Number(n.clone()),
+            //Json has no NaN or infinity so those stay strings
+            BuiltinSqlTypes::Real(f) if f.is_finite() => {
+                JsonValue::Number(ExpressionEvaluator::float_to_numeric((*f).into())?)
+            }
+            BuiltinSqlTypes::DoublePrecision(f) if f.is_finite() => {
+                JsonValue::Number(ExpressionEvaluator::float_to_numeric(*f)?)
+            }
+            BuiltinSqlTypes::Text(t) => JsonValue::String(t.clone()),
+            BuiltinSqlTypes::Json(t) => JsonValue::parse_jsonb(t)?,
+            BuiltinSqlTypes::Jsonb(v) => v.clone(),
+            BuiltinSqlTypes::Array(a) => {
+                ExpressionEvaluator::json_array(&a.dimensions, &a.elements, context)?
+            }
+            BuiltinSqlTypes::Timestamp(_) | BuiltinSqlTypes::TimestampTz(_) => JsonValue::String(
+                value
+                    .to_string_with(&context.time_zone, context.bytea_output)
+                    .replacen(' ', "T", 1),
+            ),
+            _ => JsonValue::String(value.to_string_with(&context.time_zone, context.bytea_output)),
+        };
+        Ok(json)
+    }
+
+    //Multidimensional arrays become json arrays of arrays
+    fn json_array(
+        dimensions: &[ArrayDimension],
+        elements: &[Option<BuiltinSqlTypes>],
+        context: &ExpressionContext,
+    ) -> Result<JsonValue, ExpressionEvaluatorError> {
+        let mut values = vec![];
+        match dimensions {
+            [] => {}
+            [_] => {
+                for e in elements {
+                    values.push(ExpressionEvaluator::to_json_value(e, context)?);
+                }
+            }
+            [_, inner @ ..] => {
+                let size = inner.iter().map(|d| d.length).product::<usize>().max(1);
+                for chunk in elements.chunks(size) {
+                    values.push(ExpressionEvaluator::json_array(inner, chunk, context)?);
+                }
+            }
+        }
+        Ok(JsonValue::Array(values))
+    }
+
+    /// The json operators, a missing field or element is null rather than an error
+    fn json_operator(
+        op: Operator,
+        left: BuiltinSqlTypes,
+        right: BuiltinSqlTypes,
+    ) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        let (value, is_jsonb) = match left {
+            BuiltinSqlTypes::Jsonb(v) => (v, true),
+            BuiltinSqlTypes::Json(t) => (JsonValue::parse(&t, DeserializeTypes::Json)?, false),
+            _ => {
+                return Err(ExpressionEvaluatorError::MismatchedOperands(
+                    op, left, right,
+                ))
+            }
+        };
+
+        let found = match (op, &right) {
+            (Operator::JsonContains, BuiltinSqlTypes::Jsonb(r)) => {
+                return Ok(Some(BuiltinSqlTypes::Bool(value.contains(r))))
+            }
+            (Operator::JsonContainedBy, BuiltinSqlTypes::Jsonb(r)) => {
+                return Ok(Some(BuiltinSqlTypes::Bool(r.contains(&value))))
+            }
+            (Operator::JsonKeyExists, BuiltinSqlTypes::Text(k)) => {
+                return Ok(Some(BuiltinSqlTypes::Bool(value.has_key(k))))
+            }
+            (Operator::JsonGet | Operator::JsonGetText, BuiltinSqlTypes::Text(k)) => {
+                value.get_key(k)
+            }
+            (Operator::JsonGet | Operator::JsonGetText, BuiltinSqlTypes::Integer(i)) => {
+                value.get_index((*i).into())
+            }
+            (Operator::JsonPath | Operator::JsonPathText, BuiltinSqlTypes::Text(p)) => {
+                value.get_path(&ExpressionEvaluator::json_path(p)?)
+            }
+            (_, _) => {
+                return Err(ExpressionEvaluatorError::MismatchedOperands(
+                    op,
+                    if is_jsonb {
+                        BuiltinSqlTypes::Jsonb(value)
+                    } else {
+                        BuiltinSqlTypes::Json(value.to_string())
+                    },
+                    right,
+                ))
+            }
+        };
+
+        Ok(match op {
+            Operator::JsonGetText | Operator::JsonPathText => found
+                .and_then(JsonValue::to_text)
+                .map(BuiltinSqlTypes::Text),
+            _ if is_jsonb => found.map(|v| BuiltinSqlTypes::Jsonb(v.clone())),
+            _ => found.map(|v| BuiltinSqlTypes::Json(v.to_string())),
+        })
+    }
+
+    //Paths are written as a text array such as {a,0,"b c"}
+    fn json_path(path: &str) -> Result<Vec<String>, SqlTypeError> {
+        let malformed = || SqlTypeError::MalformedArray(path.to_string());
+        let inner = path
+            .trim()
+            .strip_prefix('{')
+            .and_then(|p| p.strip_suffix('}'))
+            .ok_or_else(malformed)?;
+        if inner.trim().is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut steps = vec![];
+        let mut chars = inner.chars().peekable();
+        loop {
+            while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+                chars.next();
+            }
+            let mut step = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next().ok_or_else(malformed)? {
+                        '"' => break,
+                        '\\' => step.push(chars.next().ok_or_else(malformed)?),
+                        c => step.push(c),
+                    }
+                }
+                while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+                    chars.next();
+                }
+            } else {
+                while let Some(c) = chars.peek() {
+                    if *c == ',' {
+                        break;
+                    }
+                    if *c == '"' || *c == '{' || *c == '}' {
+                        return Err(malformed());
+                    }
+                    step.push(*c);
+                    chars.next();
+                }
+                step = step.trim_end().to_string();
+                if step.is_empty() {
+                    return Err(malformed());
+                }
+            }
+            steps.push(step);
+            match chars.next() {
+                Some(',') => continue,
+                None => return Ok(steps),
+                Some(_) => return Err(malformed()),
+            }
+        }
+    }
+
+    /// Both sides are the same type once the analyzer is done with them
+    fn compare(
+        op: Operator,
+        left: BuiltinSqlTypes,
+        right: BuiltinSqlTypes,
+    ) -> Result<bool, ExpressionEvaluatorError> {
+        let ordering = match ExpressionEvaluator::ordering(&left, &right) {
+            Some(o) => o,
+            None => {
+                return Err(ExpressionEvaluatorError::MismatchedOperands(
+                    op, left, right,
+                ))
+            }
+        };
+
+        Ok(match op {
+            Operator::Equal => ordering == Ordering::Equal,
+            Operator::NotEqual => ordering != Ordering::Equal,
+            Operator::LessThan => ordering == Ordering::Less,
+            Operator::LessEqual => ordering != Ordering::Greater,
+            Operator::GreaterThan => ordering == Ordering::Greater,
+            Operator::GreaterEqual => ordering != Ordering::Less,
+            _ => return Err(ExpressionEvaluatorError::UnexpectedOperator(op)),
+        })
+    }
+
+    //None if the two values are not the same type
+    fn ordering(left: &BuiltinSqlTypes, right: &BuiltinSqlTypes) -> Option<Ordering> {
+        let ordering = match (left, right) {
+            (BuiltinSqlTypes::Bool(l), BuiltinSqlTypes::Bool(r)) => l.cmp(r),
+            (BuiltinSqlTypes::SmallInt(l), BuiltinSqlTypes::SmallInt(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Integer(l), BuiltinSqlTypes::Integer(r)) => l.cmp(r),
+            (BuiltinSqlTypes::BigInt(l), BuiltinSqlTypes::BigInt(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Real(l), BuiltinSqlTypes::Real(r)) => {
+                ExpressionEvaluator::float_cmp((*l).into(), (*r).into())
+            }
+            (BuiltinSqlTypes::DoublePrecision(l), BuiltinSqlTypes::DoublePrecision(r)) => {
+                ExpressionEvaluator::float_cmp(*l, *r)
+            }
+            (BuiltinSqlTypes::Numeric(l), BuiltinSqlTypes::Numeric(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Text(l), BuiltinSqlTypes::Text(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Uuid(l), BuiltinSqlTypes::Uuid(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Bytea(l), BuiltinSqlTypes::Bytea(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Jsonb(l), BuiltinSqlTypes::Jsonb(r)) => l.compare(r),
+            (BuiltinSqlTypes::Date(l), BuiltinSqlTypes::Date(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Time(l), BuiltinSqlTypes::Time(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Timestamp(l), BuiltinSqlTypes::Timestamp(r)) => l.cmp(r),
+            (BuiltinSqlTypes::TimestampTz(l), BuiltinSqlTypes::TimestampTz(r)) => l.cmp(r),
+            (BuiltinSqlTypes::Interval(l), BuiltinSqlTypes::Interval(r)) => {
+                l.cmp_value().cmp(&r.cmp_value())
+            }
+            (BuiltinSqlTypes::Array(l), BuiltinSqlTypes::Array(r)) => {
+                return ExpressionEvaluator::array_ordering(l, r)
+            }
+            //Enums sort by the order their labels were declared in
+            (BuiltinSqlTypes::Enum(l, _), BuiltinSqlTypes::Enum(r, _)) => l.cmp(r),
+            (BuiltinSqlTypes::Composite(l), BuiltinSqlTypes::Composite(r)) => {
+                ExpressionEvaluator::elements_ordering(l, r)?.then(l.len().cmp(&r.len()))
+            }
+            (_, _) => return None,
+        };
+        Some(ordering)
+    }
+
+    //Elements first, then the element count, the number of dimensions and their bounds
+    fn array_ordering(left: &SqlArray, right: &SqlArray) -> Option<Ordering> {
+        let ordering = ExpressionEvaluator::elements_ordering(&left.elements, &right.elements)?;
+        if ordering != Ordering::Equal {
+            return Some(ordering);
+        }
+        let bounds = |a: &SqlArray| {
+            a.dimensions
+                .iter()
+                .map(|d| (d.length, d.lower_bound))
+                .collect::<Vec<_>>()
+        };
+        Some(
+            left.elements
+                .len()
+                .cmp(&right.elements.len())
+                .then(left.dimensions.len().cmp(&right.dimensions.len()))
+                .then_with(|| bounds(left).cmp(&bounds(right))),
+        )
+    }
+
+    //Compares pairwise until one differs, nulls sort after every value
+    fn elements_ordering(
+        left: &[Option<BuiltinSqlTypes>],
+        right: &[Option<BuiltinSqlTypes>],
+    ) -> Option<Ordering> {
+        for (l, r) in left.iter().zip(right.iter()) {
+            let ordering = match (l, r) {
+                (Some(l), Some(r)) => ExpressionEvaluator::ordering(l, r)?,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            if ordering != Ordering::Equal {
+                return Some(ordering);
+            }
+        }
+        Some(Ordering::Equal)
+    }
+
+    //Like postgres NaN equals itself and sorts above every other value
+    fn float_cmp(left: f64, right: f64) -> Ordering {
+        match (left.is_nan(), right.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => left.partial_cmp(&right).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    fn operator(
+        op: Operator,
+        left: BuiltinSqlTypes,
+        right: BuiltinSqlTypes,
+        result_type: DeserializeTypes,
+        context: &ExpressionContext,
+    ) -> Result<BuiltinSqlTypes, ExpressionEvaluatorError> {
+        let out_of_range = || ExpressionEvaluatorError::OutOfRange(result_type.clone());
+        let subtract = op == Operator::Subtract;
+        //Subtracting an interval is adding its negation
+        let signed = |i: &Interval| -> Result<Interval, ExpressionEvaluatorError> {
+            if subtract {
+                i.checked_neg().ok_or_else(out_of_range)
+            } else {
+                Ok(*i)
+            }
+        };
+
+        let result = match (op, &left, &right) {
+            (
+                Operator::Add | Operator::Subtract,
+                BuiltinSqlTypes::Date(d),
+                BuiltinSqlTypes::Integer(i),
+            )
+            | (Operator::Add, BuiltinSqlTypes::Integer(i), BuiltinSqlTypes::Date(d)) => {
+                if DateTime::is_infinite_date(*d) {
+                    BuiltinSqlTypes::Date(*d)
+                } else {
+                    let days = if subtract { i.checked_neg() } else { Some(*i) };
+                    BuiltinSqlTypes::Date(
+                        days.and_then(|i| d.checked_add(i))
+                            .and_then(DateTime::check_date)
+                            .ok_or_else(out_of_range)?,
+                    )
+                }
+            }
+            (Operator::Subtract, BuiltinSqlTypes::Date(l), BuiltinSqlTypes::Date(r)) => {
+                if DateTime::is_infinite_date(*l) || DateTime::is_infinite_date(*r) {
+                    return Err(ExpressionEvaluatorError::InfiniteSubtraction("dates"));
+                }
+                BuiltinSqlTypes::Integer(l.checked_sub(*r).ok_or_else(out_of_range)?)
+            }
+            (Operator::Add, BuiltinSqlTypes::Date(d), BuiltinSqlTypes::Time(t))
+            | (Operator::Add, BuiltinSqlTypes::Time(t), BuiltinSqlTypes::Date(d)) => {
+                let midnight = DateTime::date_to_timestamp(*d).ok_or_else(out_of_range)?;
+                if DateTime::is_infinite(midnight) {
+                    BuiltinSqlTypes::Timestamp(midnight)
+                } else {
+                    BuiltinSqlTypes::Timestamp(
+                        midnight
+                            .checked_add(*t)
+                            .and_then(|ts| DateTime::timestamp_to_naive(ts).map(|_| ts))
+                            .ok_or_else(out_of_range)?,
+                    )
+                }
+            }
+            (
+                Operator::Add | Operator::Subtract,
+                BuiltinSqlTypes::Time(t),
+                BuiltinSqlTypes::Interval(i),
+            )
+            | (Operator::Add, BuiltinSqlTypes::Interval(i), BuiltinSqlTypes::Time(t)) => {
+                BuiltinSqlTypes::Time(DateTime::add_interval_to_time(*t, &signed(i)?))
+            }
+            (Operator::Subtract, BuiltinSqlTypes::Time(l), BuiltinSqlTypes::Time(r)) => {
+                BuiltinSqlTypes::Interval(Interval::new(0, 0, l - r))
+            }
+            (
+                Operator::Add | Operator::Subtract,
+                BuiltinSqlTypes::Timestamp(t),
+                BuiltinSqlTypes::Interval(i),
+            )
+            | (Operator::Add, BuiltinSqlTypes::Interval(i), BuiltinSqlTypes::Timestamp(t)) => {
+                BuiltinSqlTypes::Timestamp(
+                    DateTime::add_interval(*t, &signed(i)?).ok_or_else(out_of_range)?,
+                )
+            }
+            (
+                Operator::Add | Operator::Subtract,
+                BuiltinSqlTypes::TimestampTz(t),
+                BuiltinSqlTypes::Interval(i),
+            )
+            | (Operator::Add, BuiltinSqlTypes::Interval(i), BuiltinSqlTypes::TimestampTz(t)) => {
+                BuiltinSqlTypes::TimestampTz(
+                    DateTime::add_interval_in_zone(*t, &signed(i)?, &context.time_zone)
+                        .ok_or_else(out_of_range)?,
+                )
+            }
+            (Operator::Subtract, BuiltinSqlTypes::Timestamp(l), BuiltinSqlTypes::Timestamp(r))
+            | (
+                Operator::Subtract,
+                BuiltinSqlTypes::TimestampTz(l),
+                BuiltinSqlTypes::TimestampTz(r),
+            ) => {
+                if DateTime::is_infinite(*l) || DateTime::is_infinite(*r) {
+                    return Err(ExpressionEvaluatorError::InfiniteSubtraction("timestamps"));
+                }
+                BuiltinSqlTypes::Interval(
+                    DateTime::subtract_timestamps(*l, *r).ok_or_else(out_of_range)?,
+                )
+            }
+            (
+                Operator::Add | Operator::Subtract,
+                BuiltinSqlTypes::Interval(l),
+                BuiltinSqlTypes::Interval(r),
+            ) => BuiltinSqlTypes::Interval(l.checked_add(&signed(r)?).ok_or_else(out_of_range)?),
+            (
+                Operator::Multiply,
+                BuiltinSqlTypes::Interval(i),
+                BuiltinSqlTypes::DoublePrecision(f),
+            )
+            | (
+                Operator::Multiply,
+                BuiltinSqlTypes::DoublePrecision(f),
+                BuiltinSqlTypes::Interval(i),
+            ) => BuiltinSqlTypes::Interval(i.checked_mul(*f).ok_or_else(out_of_range)?),
+            (
+                Operator::Divide,
+                BuiltinSqlTypes::Interval(i),
+                BuiltinSqlTypes::DoublePrecision(f),
+            ) => {
+                if *f == 0.0 {
+                    return Err(ExpressionEvaluatorError::DivisionByZero());
+                }
+                BuiltinSqlTypes::Interval(i.checked_div(*f).ok_or_else(out_of_range)?)
+            }
+            (_, _, _) => return ExpressionEvaluator::arithmetic(op, left, right),
+        };
+        Ok(result)
+    }
+
+    fn arithmetic(
+        op: Operator,
+        left: BuiltinSqlTypes,
+        right: BuiltinSqlTypes,
+    ) -> Result<BuiltinSqlTypes, ExpressionEvaluatorError> {
+        match (left, right) {
+            (BuiltinSqlTypes::SmallInt(l), BuiltinSqlTypes::SmallInt(r)) => {
+                Ok(BuiltinSqlTypes::SmallInt(
+                    ExpressionEvaluator::integer_arithmetic(op, l, r, DeserializeTypes::SmallInt)?,
+                ))
+            }
+            (BuiltinSqlTypes::Integer(l), BuiltinSqlTypes::Integer(r)) => {
+                Ok(BuiltinSqlTypes::Integer(
+                    ExpressionEvaluator::integer_arithmetic(op, l, r, DeserializeTypes::Integer)?,
+                ))
+            }
+            (BuiltinSqlTypes::BigInt(l), BuiltinSqlTypes::BigInt(r)) => {
+                Ok(BuiltinSqlTypes::BigInt(
+                    ExpressionEvaluator::integer_arithmetic(op, l, r, DeserializeTypes::BigInt)?,
+                ))
+            }
+            (BuiltinSqlTypes::Real(l), BuiltinSqlTypes::Real(r)) => {
+                let result = ExpressionEvaluator::float_arithmetic(
+                    op,
+                    l.into(),
+                    r.into(),
+                    DeserializeTypes::Real,
+                )? as f32;
+                if result.is_infinite() && l.is_finite() && r.is_finite() {
+                    return Err(ExpressionEvaluatorError::OutOfRange(DeserializeTypes::Real));
+                }
+                Ok(BuiltinSqlTypes::Real(result))
+            }
+            (BuiltinSqlTypes::DoublePrecision(l), BuiltinSqlTypes::DoublePrecision(r)) => Ok(
+                BuiltinSqlTypes::DoublePrecision(ExpressionEvaluator::float_arithmetic(
+                    op,
+                    l,
+                    r,
+                    DeserializeTypes::DoublePrecision,
+                )?),
+            ),
+            (BuiltinSqlTypes::Numeric(l), BuiltinSqlTypes::Numeric(r)) => Ok(
+                BuiltinSqlTypes::Numeric(ExpressionEvaluator::numeric_arithmetic(op, l, r)?),
+            ),
+            (l, r) => Err(ExpressionEvaluatorError::MismatchedOperands(op, l, r)),
+        }
+    }
+
+    fn integer_arithmetic<T: PrimInt + Signed>(
+        op: Operator,
+        left: T,
+        right: T,
+        sql_type: DeserializeTypes,
+    ) -> Result<T, ExpressionEvaluatorError> {
+        let result = match op {
+            Operator::Add => left.checked_add(&right),
+            Operator::Subtract => left.checked_sub(&right),
+            Operator::Multiply => left.checked_mul(&right),
+            Operator::Divide => {
+                if right.is_zero() {
+                    return Err(ExpressionEvaluatorError::DivisionByZero());
+                }
+                left.checked_div(&right)
+            }
+            Operator::Modulo => {
+                if right.is_zero() {
+                    return Err(ExpressionEvaluatorError::DivisionByZero());
+                }
+                //MIN % -1 overflows in the cpu even though the answer is simply zero
+                if right == -T::one() {
+                    Some(T::zero())
+                } else {
+                    Some(left % right)
+                }
+            }
+            _ => return Err(ExpressionEvaluatorError::UnexpectedOperator(op)),
+        };
+        result.ok_or(ExpressionEvaluatorError::OutOfRange(sql_type))
+    }
+
+    fn float_arithmetic(
+        op: Operator,
+        left: f64,
+        right: f64,
+        sql_type: DeserializeTypes,
+    ) -> Result<f64, ExpressionEvaluatorError> {
+        let result = match op {
+            Operator::Add => left + right,
+            Operator::Subtract => left - right,
+            Operator::Multiply => left * right,
+            Operator::Divide => {
+                if right == 0.0 {
+                    return Err(ExpressionEvaluatorError::DivisionByZero());
+                }
+                left / right
+            }
+            Operator::Modulo => {
+                return Err(ExpressionEvaluatorError::InvalidOperand(
+                    op.to_string(),
+                    BuiltinSqlTypes::DoublePrecision(left),
+                ))
+            }
+            _ => return Err(ExpressionEvaluatorError::UnexpectedOperator(op)),
+        };
+        if result.is_infinite() && left.is_finite() && right.is_finite() {
+            return Err(ExpressionEvaluatorError::OutOfRange(sql_type));
+        }
+        Ok(result)
+    }
+
+    fn numeric_arithmetic(
+        op: Operator,
+        left: BigDecimal,
+        right: BigDecimal,
+    ) -> Result<BigDecimal, ExpressionEvaluatorError> {
+        match op {
+            Operator::Add => Ok(left + right),
+            Operator::Subtract => Ok(left - right),
+            Operator::Multiply => Ok(left * right),
+            Operator::Divide => ExpressionEvaluator::numeric_divide(&left, &right),
+            Operator::Modulo => {
+                if right.is_zero() {
+                    return Err(ExpressionEvaluatorError::DivisionByZero());
+                }
+                let scale = left
+                    .fractional_digit_count()
+                    .max(right.fractional_digit_count());
+                let (l, _) = left.with_scale(scale).into_bigint_and_exponent();
+                let (r, _) = right.with_scale(scale).into_bigint_and_exponent();
+                Ok(BigDecimal::new(l % r, scale))
+            }
+            _ => Err(ExpressionEvaluatorError::UnexpectedOperator(op)),
+        }
+    }
+
+    //The quotient is rounded to a scale picked the same way postgres' select_div_scale does
+    fn numeric_divide(
+        left: &BigDecimal,
+        right: &BigDecimal,
+    ) -> Result<BigDecimal, ExpressionEvaluatorError> {
+        if right.is_zero() {
+            return Err(ExpressionEvaluatorError::DivisionByZero());
+        }
+
+        let (left_weight, left_first) = ExpressionEvaluator::nbase_weight(left);
+        let (right_weight, right_first) = ExpressionEvaluator::nbase_weight(right);
+        let mut quotient_weight = left_weight - right_weight;
+        if left_first <= right_first {
+            quotient_weight -= 1;
+        }
+        let result_scale = (NUMERIC_MIN_SIG_DIGITS - quotient_weight * 4)
+            .max(left.fractional_digit_count())
+            .max(right.fractional_digit_count())
+            .clamp(0, NUMERIC_MAX_DISPLAY_SCALE);
+
+        //left / right * 10^scale done in integers so the only rounding is the final step
+        let (left_digits, left_scale) = left.as_bigint_and_exponent();
+        let (right_digits, right_scale) = right.as_bigint_and_exponent();
+        let shift = right_scale - left_scale + result_scale;
+        let (numerator, denominator) = if shift >= 0 {
+            (
+                left_digits * ExpressionEvaluator::power_of_ten(shift),
+                right_digits,
+            )
+        } else {
+            (
+                left_digits,
+                right_digits * ExpressionEvaluator::power_of_ten(-shift),
+            )
+        };
+
+        let mut quotient = &numerator / &denominator;
+        let remainder = &numerator % &denominator;
+        if remainder.abs() * 2 >= denominator.abs() {
+            if numerator.is_negative() != denominator.is_negative() {
+                quotient -= 1;
+            } else {
+                quotient += 1;
+            }
+        }
+
+        Ok(BigDecimal::new(quotient, result_scale))
+    }
+
+    //Postgres stores numerics in base 10000 digits, the division scale depends on the weight
+    //and value of the leading one
+    fn nbase_weight(value: &BigDecimal) -> (i64, i64) {
+        if value.is_zero() {
+            return (0, 0);
+        }
+        let weight = value.order_of_magnitude().div_euclid(4);
+        let (digits, scale) = value.abs().into_bigint_and_exponent();
+        let shift = -scale - weight * 4;
+        let first = if shift >= 0 {
+            digits * ExpressionEvaluator::power_of_ten(shift)
+        } else {
+            digits / ExpressionEvaluator::power_of_ten(-shift)
+        };
+        (weight, first.to_i64().unwrap_or(0))
+    }
+
+    fn power_of_ten(exponent: i64) -> BigInt {
+        BigInt::from(10).pow(u32::try_from(exponent).unwrap_or(u32::MAX))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExpressionEvaluatorError {
+    #[error("cannot cast {0} to {1}")]
+    CannotCast(BuiltinSqlTypes, DeserializeTypes),
+    #[error("cannot convert {0} to numeric")]
+    CannotConvertToNumeric(f64),
+    #[error("division by zero")]
+    DivisionByZero(),
+    #[error("cannot subtract infinite {0}")]
+    InfiniteSubtraction(&'static str),
+    #[error("operator {0} cannot be applied to {1}")]
+    InvalidOperand(String, BuiltinSqlTypes),
+    #[error("operator {0} got mismatched operands {1} and {2}")]
+    MismatchedOperands(Operator, BuiltinSqlTypes, BuiltinSqlTypes),
+    #[error("Column index {0} is not in the row")]
+    MissingColumn(usize),
+    #[error("{0} out of range")]
+    OutOfRange(DeserializeTypes),
+    #[error("operator {0} is not supported here")]
+    UnexpectedOperator(Operator),
+    #[error("aggregate function {0} is not allowed here")]
+    UnexpectedAggregate(Aggregate),
+    #[error("set-returning function {0} is not allowed here")]
+    UnexpectedSetFunction(SetFunction),
+    #[error("argument {0}: key must not be null")]
+    NullJsonKey(usize),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
+}
+
+impl ExpressionEvaluatorError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            ExpressionEvaluatorError::DivisionByZero() => PgErrorCodes::DivisionByZero,
+            ExpressionEvaluatorError::OutOfRange(_) => PgErrorCodes::NumericValueOutOfRange,
+            ExpressionEvaluatorError::SqlTypeError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ByteaOutput;
+
+    fn constant(value: BuiltinSqlTypes, sql_type: DeserializeTypes) -> Box<Expression> {
+        Box::new(Expression::Constant(Some(value), sql_type))
+    }
+
+    fn numeric(value: &str) -> Box<Expression> {
+        constant(
+            BuiltinSqlTypes::Numeric(BigDecimal::from_str(value).unwrap()),
+            DeserializeTypes::Numeric(None),
+        )
+    }
+
+    fn integer(value: i32) -> Box<Expression> {
+        constant(BuiltinSqlTypes::Integer(value), DeserializeTypes::Integer)
+    }
+
+    //Arithmetic on matching types produces the same type
+    fn operator(op: Operator, left: Box<Expression>, right: Box<Expression>) -> Expression {
+        let sql_type = left.sql_type();
+        Expression::Operator(op, left, right, sql_type)
+    }
+
+    fn eval(expression: Expression) -> Result<Option<BuiltinSqlTypes>, ExpressionEvaluatorError> {
+        ExpressionEvaluator::evaluate(
+            &expression,
+            &SqlTuple(vec![]),
+            &ExpressionContext::default(),
+        )
+    }
+
+    fn eval_string(expression: Expression) -> String {
+        eval(expression).unwrap().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_integer_math() {
+        assert_eq!(
+            eval_string(operator(Operator::Divide, integer(-7), integer(2))),
+            "-3"
+        );
+        assert_eq!(
+            eval_string(operator(Operator::Modulo, integer(-7), integer(2))),
+            "-1"
+        );
+        assert_eq!(
+            eval_string(operator(Operator::Modulo, integer(i32::MIN), integer(-1))),
+            "0"
+        );
+        assert!(matches!(
+            eval(operator(Operator::Add, integer(i32::MAX), integer(1))),
+            Err(ExpressionEvaluatorError::OutOfRange(
+                DeserializeTypes::Integer
+            ))
+        ));
+        assert!(matches!(
+            eval(operator(Operator::Divide, integer(1), integer(0))),
+            Err(ExpressionEvaluatorError::DivisionByZero())
+        ));
+        assert!(matches!(
+            eval(Expression::Negate(integer(i32::MIN))),
+            Err(ExpressionEvaluatorError::OutOfRange(
+                DeserializeTypes::Integer
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_null_propagation() {
+        assert_eq!(
+            eval(operator(
+                Operator::Add,
+                integer(1),
+                Box::new(Expression::Constant(None, DeserializeTypes::Integer))
+            ))
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_numeric_math() {
+        assert_eq!(
+            eval_string(operator(Operator::Add, numeric("1.5"), numeric("2.25"))),
+            "3.75"
+        );
+        assert_eq!(
+            eval_string(operator(
+                Operator::Multiply,
+                numeric("1.5"),
+                numeric("2.25")
+            )),
+            "3.375"
+        );
+        assert_eq!(
+            eval_string(operator(Operator::Divide, numeric("1"), numeric("3"))),
+            "0.33333333333333333333"
+        );
+        assert_eq!(
+            eval_string(operator(Operator::Divide, numeric("10"), numeric("4"))),
+            "2.5000000000000000"
+        );
+        assert_eq!(
+            eval_string(operator(Operator::Divide, numeric("-2"), numeric("3"))),
+            "-0.66666666666666666667"
+        );
+        assert_eq!(
+            eval_string(operator(Operator::Modulo, numeric("7.5"), numeric("2"))),
+            "1.5"
+        );
+    }
+
+    #[test]
+    fn test_float_math() {
+        let double = |f: f64| {
+            constant(
+                BuiltinSqlTypes::DoublePrecision(f),
+                DeserializeTypes::DoublePrecision,
+            )
+        };
+        assert_eq!(
+            eval_string(operator(Operator::Divide, double(1.0), double(4.0))),
+            "0.25"
+        );
+        assert!(matches!(
+            eval(operator(Operator::Multiply, double(1e300), double(1e300))),
+            Err(ExpressionEvaluatorError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_casts() {
+        let cast = |value: Box<Expression>, target: DeserializeTypes| {
+            eval(Expression::Cast(value, target))
+        };
+        let double = |f: f64| {
+            constant(
+                BuiltinSqlTypes::DoublePrecision(f),
+                DeserializeTypes::DoublePrecision,
+            )
+        };
+
+        assert_eq!(
+            cast(double(2.5), DeserializeTypes::Integer).unwrap(),
+            Some(BuiltinSqlTypes::Integer(2))
+        );
+        assert_eq!(
+            cast(numeric("2.5"), DeserializeTypes::Integer).unwrap(),
+            Some(BuiltinSqlTypes::Integer(3))
+        );
+        assert!(matches!(
+            cast(integer(40000), DeserializeTypes::SmallInt),
+            Err(ExpressionEvaluatorError::OutOfRange(
+                DeserializeTypes::SmallInt
+            ))
+        ));
+        assert_eq!(
+            cast(double(0.1), DeserializeTypes::Numeric(None))
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "0.1"
+        );
+        assert_eq!(
+            cast(integer(-1), DeserializeTypes::Text).unwrap(),
+            Some(BuiltinSqlTypes::Text("-1".to_string()))
+        );
+        assert!(matches!(
+            cast(
+                constant(
+                    BuiltinSqlTypes::Text("abc".to_string()),
+                    DeserializeTypes::Text
+                ),
+                DeserializeTypes::BigInt
+            ),
+            Err(ExpressionEvaluatorError::SqlTypeError(_))
+        ));
+    }
+
+    fn typed(value: &str, sql_type: DeserializeTypes) -> Box<Expression> {
+        constant(
+            BuiltinSqlTypes::parse(sql_type.clone(), value.to_string()).unwrap(),
+            sql_type,
+        )
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let double = |f: f64| {
+            constant(
+                BuiltinSqlTypes::DoublePrecision(f),
+                DeserializeTypes::DoublePrecision,
+            )
+        };
+        let compare = |op: Operator, left: Box<Expression>, right: Box<Expression>| {
+            eval(Expression::Operator(
+                op,
+                left,
+                right,
+                DeserializeTypes::Bool,
+            ))
+            .unwrap()
+            .unwrap()
+        };
+
+        assert_eq!(
+            compare(Operator::LessThan, integer(1), integer(2)),
+            BuiltinSqlTypes::Bool(true)
+        );
+        assert_eq!(
+            compare(Operator::NotEqual, integer(1), integer(1)),
+            BuiltinSqlTypes::Bool(false)
+        );
+        //NaN is equal to itself and bigger than everything else
+        assert_eq!(
+            compare(Operator::Equal, double(f64::NAN), double(f64::NAN)),
+            BuiltinSqlTypes::Bool(true)
+        );
+        assert_eq!(
+            compare(
+                Operator::GreaterThan,
+                double(f64::NAN),
+                double(f64::INFINITY)
+            ),
+            BuiltinSqlTypes::Bool(true)
+        );
+        //A month and thirty days are the same length
+        assert_eq!(
+            compare(
+                Operator::Equal,
+                typed("1 mon", DeserializeTypes::Interval),
+                typed("30 days", DeserializeTypes::Interval)
+            ),
+            BuiltinSqlTypes::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_date_time_math() {
+        let math = |op: Operator,
+                    left: Box<Expression>,
+                    right: Box<Expression>,
+                    result: DeserializeTypes| {
+            eval(Expression::Operator(op, left, right, result))
+        };
+        let date = |s: &str| typed(s, DeserializeTypes::Date);
+        let interval = |s: &str| typed(s, DeserializeTypes::Interval);
+        let timestamp = |s: &str| typed(s, DeserializeTypes::Timestamp);
+
+        assert_eq!(
+            math(
+                Operator::Add,
+                date("2021-02-28"),
+                integer(1),
+                DeserializeTypes::Date
+            )
+            .unwrap()
+            .unwrap()
+            .to_string(),
+            "2021-03-01"
+        );
+        assert_eq!(
+            math(
+                Operator::Subtract,
+                date("2021-03-01"),
+                date("2020-03-01"),
+                DeserializeTypes::Integer
+            )
+            .unwrap(),
+            Some(BuiltinSqlTypes::Integer(365))
+        );
+        assert_eq!(
+            math(
+                Operator::Add,
+                timestamp("2021-01-31 12:00"),
+                interval("1 mon"),
+                DeserializeTypes::Timestamp
+            )
+            .unwrap()
+            .unwrap()
+            .to_string(),
+            "2021-02-28 12:00:00"
+        );
+        assert_eq!(
+            math(
+                Operator::Subtract,
+                timestamp("2021-01-02 13:00"),
+                timestamp("2021-01-01 12:00"),
+                DeserializeTypes::Interval
+            )
+            .unwrap()
+            .unwrap()
+            .to_string(),
+            "1 day 01:00:00"
+        );
+        assert_eq!(
+            math(
+                Operator::Divide,
+                interval("1 day"),
+                constant(
+                    BuiltinSqlTypes::DoublePrecision(4.0),
+                    DeserializeTypes::DoublePrecision
+                ),
+                DeserializeTypes::Interval
+            )
+            .unwrap()
+            .unwrap()
+            .to_string(),
+            "06:00:00"
+        );
+        assert!(matches!(
+            math(
+                Operator::Subtract,
+                date("infinity"),
+                date("2021-01-01"),
+                DeserializeTypes::Integer
+            ),
+            Err(ExpressionEvaluatorError::InfiniteSubtraction(_))
+        ));
+        assert!(matches!(
+            math(
+                Operator::Add,
+                date("262142-12-31"),
+                integer(1),
+                DeserializeTypes::Date
+            ),
+            Err(ExpressionEvaluatorError::OutOfRange(DeserializeTypes::Date))
+        ));
+    }
+
+    #[test]
+    fn test_time_zone_casts() {
+        let context = ExpressionContext {
+            transaction_start: 0,
+            time_zone: "America/New_York".parse().unwrap(),
+            bytea_output: ByteaOutput::Hex,
+        };
+        let cast = |value: Box<Expression>, target: DeserializeTypes| {
+            ExpressionEvaluator::evaluate(
+                &Expression::Cast(value, target),
+                &SqlTuple(vec![]),
+                &context,
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        let date = |s: &str| typed(s, DeserializeTypes::Date);
+
+        //Midnight UTC on 2000-01-01 is the evening before in New York
+        assert_eq!(
+            cast(
+                constant(
+                    BuiltinSqlTypes::TimestampTz(0),
+                    DeserializeTypes::TimestampTz
+                ),
+                DeserializeTypes::Date
+            )
+            .to_string(),
+            "1999-12-31"
+        );
+        assert_eq!(
+            cast(
+                constant(
+                    BuiltinSqlTypes::TimestampTz(0),
+                    DeserializeTypes::TimestampTz
+                ),
+                DeserializeTypes::Text
+            ),
+            BuiltinSqlTypes::Text("1999-12-31 19:00:00-05".to_string())
+        );
+        assert_eq!(
+            cast(date("2000-01-01"), DeserializeTypes::TimestampTz),
+            BuiltinSqlTypes::TimestampTz(5 * 3_600_000_000)
+        );
+        assert_eq!(
+            ExpressionEvaluator::evaluate(
+                &Expression::Function(Function::LocalTimestamp, vec![]),
+                &SqlTuple(vec![]),
+                &context
+            )
+            .unwrap(),
+            Some(BuiltinSqlTypes::Timestamp(-5 * 3_600_000_000))
+        );
+    }
+}
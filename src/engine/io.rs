@@ -1,17 +1,76 @@
-mod index_formats;
+mod checkpoint_stats;
+pub use checkpoint_stats::CheckpointKind;
+pub use checkpoint_stats::CheckpointStats;
+
+mod control_file;
+pub use control_file::ClusterState;
+pub use control_file::ControlFile;
+pub use control_file::ControlFileError;
+
+mod data_directory;
+pub use data_directory::BackupFile;
+pub use data_directory::BaseBackup;
+pub use data_directory::DataDirectory;
+pub use data_directory::DataDirectoryError;
+
+mod free_space_manager;
+pub use free_space_manager::FreeSpaceManager;
+pub use free_space_manager::FreeSpaceManagerError;
+
+pub mod index_formats;
 
 mod io_manager;
+pub use io_manager::ForkNumber;
 pub use io_manager::IOManager;
 pub use io_manager::IOManagerError;
 
-mod page_formats;
+mod lock_manager;
+pub use lock_manager::LockManager;
+
+mod logical_decoder;
+pub use logical_decoder::DecodedTransaction;
+pub use logical_decoder::LogicalDecoder;
+
+pub mod page_formats;
 
 pub mod row_formats;
 
+mod replication_slot;
+pub use replication_slot::ReplicationSlot;
+pub use replication_slot::ReplicationSlotError;
+
+mod row_change;
+pub use row_change::ChangeColumn;
+pub use row_change::RowChange;
+pub use row_change::RowChangeKind;
+
 mod row_manager;
+pub use row_manager::RepackState;
 pub use row_manager::RowManager;
 pub use row_manager::RowManagerError;
 
+mod vacuum_stats;
+pub use vacuum_stats::VacuumStats;
+
+mod verify_checksums;
+pub use verify_checksums::verify_checksums;
+pub use verify_checksums::verify_directory_checksums;
+pub use verify_checksums::ChecksumFailure;
+pub use verify_checksums::VerifyChecksumsError;
+
+mod visibility_map;
+pub use visibility_map::VisibilityBits;
+pub use visibility_map::VisibilityMap;
+pub use visibility_map::VisibilityMapError;
+
 mod visible_row_manager;
 pub use visible_row_manager::VisibleRowManager;
 pub use visible_row_manager::VisibleRowManagerError;
+
+mod write_ahead_log;
+pub use write_ahead_log::RecoveryTarget;
+pub use write_ahead_log::WalEntry;
+pub use write_ahead_log::WalRecord;
+pub use write_ahead_log::WriteAheadLog;
+pub use write_ahead_log::WriteAheadLogError;
+pub use write_ahead_log::SEGMENT_SIZE;
@@ -0,0 +1,25 @@
+//! What the checkpointer and background writer have done since startup, shown by
+//! pg_stat_bgwriter like postgres
+
+/// Why a checkpoint was made
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckpointKind {
+    ///The checkpointer's timeout came around
+    Timed,
+    ///Someone asked for it, CHECKPOINT or a shutdown
+    Requested,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CheckpointStats {
+    pub checkpoints_timed: usize,
+    pub checkpoints_requested: usize,
+    ///Relation files flushed to disk, a file written between two checkpoints counts once
+    pub files_synced: usize,
+    ///Files the background writer flushed ahead of a checkpoint
+    pub files_cleaned: usize,
+    ///Rounds the background writer stopped at bgwriter_lru_maxpages with files still unflushed
+    pub maxwritten_clean: usize,
+    ///Seconds since the unix epoch, 0 if there hasn't been a checkpoint
+    pub last_checkpoint: u64,
+}
@@ -0,0 +1,188 @@
+//! Settings fixed when the database is created, everything else needs them before any page can be read.
+//! Postgres' equivalent is pg_control: https://www.postgresql.org/docs/current/app-pgcontroldata.html
+//!
+//! On disk the control file is:
+//! * The magic bytes "FEOPHANT"
+//! * Format version as a little endian u32
+//! * Page size in bytes as a little endian u32
+//! * Flags as a little endian u32, bit 0 is set when data checksums are on
+//! * The cluster state as a little endian u32, see ClusterState
+//! * When the last checkpoint finished as little endian u64 seconds since the unix epoch
+//! * Where redo starts after that checkpoint, the LSN as a little endian u64
+use super::page_formats::{PageSize, PageSizeError};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"FEOPHANT";
+const VERSION: u32 = 4;
+const LENGTH: usize = 40;
+const DATA_CHECKSUMS: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ControlFile {
+    pub version: u32,
+    pub page_size: PageSize,
+    pub data_checksums: bool,
+    pub state: ClusterState,
+    pub last_checkpoint: u64,
+    ///Every change before this in the write ahead log is on disk
+    pub checkpoint_lsn: u64,
+}
+
+/// If the server using the cluster stopped cleanly, see postgres' DBState
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClusterState {
+    ShutDown,
+    InProduction,
+}
+
+impl ClusterState {
+    fn value(self) -> u32 {
+        match self {
+            ClusterState::ShutDown => 1,
+            ClusterState::InProduction => 2,
+        }
+    }
+}
+
+impl ControlFile {
+    /// What initdb records for a new database
+    pub fn new(page_size: PageSize) -> ControlFile {
+        ControlFile {
+            version: VERSION,
+            page_size,
+            data_checksums: false,
+            state: ClusterState::ShutDown,
+            last_checkpoint: 0,
+            checkpoint_lsn: 0,
+        }
+    }
+
+    /// Turns on page checksums, like initdb --data-checksums this can't be changed later
+    pub fn with_checksums(mut self) -> ControlFile {
+        self.data_checksums = true;
+        self
+    }
+
+    pub fn serialize(&self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(LENGTH);
+        buffer.put_slice(MAGIC);
+        buffer.put_u32_le(self.version);
+        buffer.put_u32_le(self.page_size.bytes() as u32);
+        buffer.put_u32_le(if self.data_checksums {
+            DATA_CHECKSUMS
+        } else {
+            0
+        });
+        buffer.put_u32_le(self.state.value());
+        buffer.put_u64_le(self.last_checkpoint);
+        buffer.put_u64_le(self.checkpoint_lsn);
+        buffer.freeze()
+    }
+
+    pub fn parse(buffer: &mut impl Buf) -> Result<ControlFile, ControlFileError> {
+        if buffer.remaining() < LENGTH {
+            return Err(ControlFileError::BufferTooShort(LENGTH, buffer.remaining()));
+        }
+
+        let mut magic = [0; 8];
+        buffer.copy_to_slice(&mut magic);
+        if &magic != MAGIC {
+            return Err(ControlFileError::BadMagic());
+        }
+
+        let version = buffer.get_u32_le();
+        if version != VERSION {
+            return Err(ControlFileError::UnsupportedVersion(version));
+        }
+
+        let page_size = PageSize::try_from(buffer.get_u32_le() as usize)?;
+        let data_checksums = buffer.get_u32_le() & DATA_CHECKSUMS == DATA_CHECKSUMS;
+        let state = match buffer.get_u32_le() {
+            1 => ClusterState::ShutDown,
+            2 => ClusterState::InProduction,
+            s => return Err(ControlFileError::UnknownState(s)),
+        };
+        let last_checkpoint = buffer.get_u64_le();
+        let checkpoint_lsn = buffer.get_u64_le();
+        Ok(ControlFile {
+            version,
+            page_size,
+            data_checksums,
+            state,
+            last_checkpoint,
+            checkpoint_lsn,
+        })
+    }
+}
+
+impl Default for ControlFile {
+    fn default() -> Self {
+        ControlFile::new(PageSize::default())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ControlFileError {
+    #[error("Not enough control file data need {0} got {1}")]
+    BufferTooShort(usize, usize),
+    #[error("Not a feophant control file")]
+    BadMagic(),
+    #[error("Control file version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("Unknown cluster state {0}")]
+    UnknownState(u32),
+    #[error(transparent)]
+    PageSizeError(#[from] PageSizeError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_file_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        for page_size in PageSize::VALUES.iter() {
+            let control = ControlFile::new(*page_size);
+            let mut serial = control.serialize();
+            assert_eq!(ControlFile::parse(&mut serial)?, control);
+
+            let mut control = control.with_checksums();
+            control.state = ClusterState::InProduction;
+            control.last_checkpoint = 1_634_000_000;
+            control.checkpoint_lsn = 0x1_0000_0020;
+            let mut serial = control.serialize();
+            assert_eq!(ControlFile::parse(&mut serial)?, control);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_control_file_bad() {
+        let mut serial = BytesMut::from(&ControlFile::default().serialize()[..]);
+        serial[12] = 1;
+        assert!(matches!(
+            ControlFile::parse(&mut serial.clone().freeze()),
+            Err(ControlFileError::PageSizeError(_))
+        ));
+
+        let mut bad_state = BytesMut::from(&ControlFile::default().serialize()[..]);
+        bad_state[20] = 9;
+        assert!(matches!(
+            ControlFile::parse(&mut bad_state.freeze()),
+            Err(ControlFileError::UnknownState(9))
+        ));
+
+        serial[0] = b'X';
+        assert!(matches!(
+            ControlFile::parse(&mut serial.freeze()),
+            Err(ControlFileError::BadMagic())
+        ));
+
+        assert!(matches!(
+            ControlFile::parse(&mut Bytes::from_static(b"FEOPHANT")),
+            Err(ControlFileError::BufferTooShort(40, 8))
+        ));
+    }
+}
@@ -0,0 +1,1304 @@
+//! The files a cluster keeps on disk, see https://www.postgresql.org/docs/current/storage-file-layout.html
+//!
+//! * global/feophant_control is the control file. initdb writes it last so a directory without one
+//!   was never finished.
+//! * global/feophant_filenode_map has the relations whose files were swapped with another's, as a
+//!   line of the relation's id and the file's for each. Like postgres' pg_filenode.map.
+//! * base/ has a file for each relation's fork, named by the relation's id, or its file's if it is
+//!   in the filenode map, with "_fsm" and "_vm" added for the free space and visibility map forks.
+//!   Page n is at n times the page size.
+//! * pg_xact is the status of every transaction, see the transaction manager.
+//! * pg_wal is the write ahead log, see WriteAheadLog.
+//! * pg_replslot has a file for each logical replication slot, see ReplicationSlot. The log a
+//!   slot still needs is kept until it is confirmed.
+//! * backup_label is only in a base backup that hasn't been started yet, it has the LSN the
+//!   backup started at.
+//!
+//! Every change is logged and the log flushed before the change is written through, files are
+//! only flushed to disk by sync. Opening the directory redoes everything logged since the last
+//! checkpoint, so nothing is lost by a crash.
+use super::{
+    ClusterState, ControlFile, ControlFileError, ForkNumber, RecoveryTarget, ReplicationSlot,
+    ReplicationSlotError, WalEntry, WalRecord, WriteAheadLog, WriteAheadLogError, SEGMENT_SIZE,
+};
+use crate::constants::PgErrorCodes;
+use crate::engine::transactions::TransactionStatus;
+use bytes::Bytes;
+use log::info;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+const BACKUP_LABEL: &str = "backup_label";
+const BASE_DIR: &str = "base";
+const GLOBAL_DIR: &str = "global";
+const CONTROL_FILE: &str = "feophant_control";
+const FILENODE_MAP: &str = "feophant_filenode_map";
+const SLOT_DIR: &str = "pg_replslot";
+const WAL_DIR: &str = "pg_wal";
+const XACT_FILE: &str = "pg_xact";
+
+pub type Relations = HashMap<(Uuid, ForkNumber), Vec<Bytes>>;
+
+//Relation ids to the id of the file they are in, only those that aren't their own
+type Filenodes = HashMap<Uuid, Uuid>;
+
+/// Where a base backup's log starts and ends, like the result of postgres' pg_backup_stop
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BaseBackup {
+    pub start_lsn: u64,
+    pub stop_lsn: u64,
+    ///Relation files copied, the log and pg_xact aren't counted
+    pub files: usize,
+}
+
+/// A file of a base backup, named relative to the data directory with / between its parts
+pub type BackupFile = (String, Bytes);
+
+#[derive(Clone, Debug)]
+pub struct DataDirectory {
+    path: PathBuf,
+    page_size: usize,
+    //Held while the control file is rewritten so two writers can't interleave
+    control_file: Arc<Mutex<ControlFile>>,
+    //Files written since the last sync
+    unsynced: Arc<Mutex<HashSet<PathBuf>>>,
+    //Written out by sync, the swaps since the last checkpoint are redone from the log
+    filenodes: Arc<Mutex<Filenodes>>,
+    wal: WriteAheadLog,
+    //The checkpoint a running base backup redoes from, the log after it has to be kept
+    backup_hold: Arc<Mutex<Option<u64>>>,
+    slots: Arc<Mutex<BTreeMap<String, ReplicationSlot>>>,
+}
+
+impl DataDirectory {
+    /// Creates the layout in a directory that is missing or empty, writing the relations given
+    pub async fn initdb(
+        path: &Path,
+        control_file: ControlFile,
+        relations: &Relations,
+    ) -> Result<DataDirectory, DataDirectoryError> {
+        if DataDirectory::has_entries(path).await? {
+            return Err(DataDirectoryError::NotEmpty(path.to_path_buf()));
+        }
+        fs::create_dir_all(path.join(BASE_DIR)).await?;
+        fs::create_dir_all(path.join(GLOBAL_DIR)).await?;
+        fs::create_dir_all(path.join(SLOT_DIR)).await?;
+
+        let page_size = control_file.page_size.bytes();
+        let mut written = HashSet::new();
+        for ((id, fork), pages) in relations.iter() {
+            let file = DataDirectory::relation_path(path, *id, *fork);
+            for (page_num, page) in pages.iter().enumerate() {
+                DataDirectory::write_page_file(&file, page_size, page_num, page).await?;
+            }
+            written.insert(file);
+        }
+        fs::write(path.join(XACT_FILE), []).await?;
+        let wal = WriteAheadLog::create(&path.join(WAL_DIR)).await?;
+
+        let directory = DataDirectory {
+            path: path.to_path_buf(),
+            page_size,
+            control_file: Arc::new(Mutex::new(control_file)),
+            unsynced: Arc::new(Mutex::new(written)),
+            filenodes: Arc::new(Mutex::new(HashMap::new())),
+            wal,
+            backup_hold: Arc::new(Mutex::new(None)),
+            slots: Arc::new(Mutex::new(BTreeMap::new())),
+        };
+        directory.sync().await?;
+        directory.write_control_file(control_file).await?;
+        Ok(directory)
+    }
+
+    /// Opens a directory made by initdb, redoing the changes logged since the last checkpoint
+    /// and reading every relation. Completed log segments are copied to the archive if given.
+    pub async fn open(
+        path: &Path,
+        archive: Option<PathBuf>,
+    ) -> Result<(DataDirectory, Relations), DataDirectoryError> {
+        let control_file = DataDirectory::read_control_file(path).await?;
+        let page_size = control_file.page_size.bytes();
+        let wal_path = path.join(WAL_DIR);
+
+        let entries =
+            WriteAheadLog::read(std::slice::from_ref(&wal_path), control_file.checkpoint_lsn)
+                .await?;
+        let backup_start = DataDirectory::read_backup_label(path).await?;
+        if let Some(start) = backup_start {
+            DataDirectory::backup_end(&entries, start)?;
+        }
+        if !entries.is_empty() {
+            info!("Redoing {} changes in {}", entries.len(), path.display());
+        }
+        let mut filenodes = DataDirectory::read_filenode_map(path).await?;
+        let redone = DataDirectory::redo(path, page_size, &entries, &mut filenodes).await?;
+
+        //After a segment switch the new segment is still empty
+        let end = entries
+            .last()
+            .map_or(control_file.checkpoint_lsn, |e| e.end())
+            .max(
+                WriteAheadLog::segments(&wal_path)
+                    .await?
+                    .last()
+                    .map_or(0, |s| s * SEGMENT_SIZE),
+            );
+        let directory = DataDirectory {
+            path: path.to_path_buf(),
+            page_size,
+            control_file: Arc::new(Mutex::new(control_file)),
+            unsynced: Arc::new(Mutex::new(redone)),
+            filenodes: Arc::new(Mutex::new(filenodes.clone())),
+            wal: WriteAheadLog::open(&wal_path, end, archive).await?,
+            backup_hold: Arc::new(Mutex::new(None)),
+            slots: Arc::new(Mutex::new(DataDirectory::read_slots(path).await?)),
+        };
+        if let Some(start) = backup_start {
+            info!("Base backup from {} is consistent", start);
+            fs::remove_file(path.join(BACKUP_LABEL)).await?;
+        }
+
+        let relations =
+            DataDirectory::read_relations(path, directory.page_size, &filenodes).await?;
+        Ok((directory, relations))
+    }
+
+    /// Reads every relation of a cluster that isn't running without changing anything, so it
+    /// has to have been shut down cleanly since nothing logged is redone.
+    pub async fn read_offline(path: &Path) -> Result<(ControlFile, Relations), DataDirectoryError> {
+        let control_file = DataDirectory::read_control_file(path).await?;
+        if control_file.state != ClusterState::ShutDown {
+            return Err(DataDirectoryError::NotShutDown(path.to_path_buf()));
+        }
+        let filenodes = DataDirectory::read_filenode_map(path).await?;
+        let relations =
+            DataDirectory::read_relations(path, control_file.page_size.bytes(), &filenodes).await?;
+        Ok((control_file, relations))
+    }
+
+    /// Point in time recovery, brings a copy of a data directory forward to the target by
+    /// redoing the archived log. Segments missing from the archive are looked for in the copy's
+    /// own pg_wal. Returns the LSN recovery stopped at.
+    ///
+    /// There are no timelines, the recovered cluster's log starts at a new segment past
+    /// everything in the archive so it can't be confused with the changes that were skipped.
+    pub async fn recover(
+        path: &Path,
+        archive: &Path,
+        target: RecoveryTarget,
+    ) -> Result<u64, DataDirectoryError> {
+        let mut control_file = DataDirectory::read_control_file(path).await?;
+        let wal_path = path.join(WAL_DIR);
+
+        let before_backup = match target {
+            RecoveryTarget::Lsn(lsn) => lsn < control_file.checkpoint_lsn,
+            RecoveryTarget::Time(time) => {
+                time < SystemTime::UNIX_EPOCH + Duration::from_secs(control_file.last_checkpoint)
+            }
+            RecoveryTarget::TransactionId(_) => false,
+        };
+        if before_backup {
+            return Err(DataDirectoryError::TargetBeforeBackup(target));
+        }
+
+        let entries = WriteAheadLog::read(
+            &[archive.to_path_buf(), wal_path.clone()],
+            control_file.checkpoint_lsn,
+        )
+        .await?;
+        let stop = target
+            .stop_at(&entries)
+            .ok_or(DataDirectoryError::TargetNotReached(target))?;
+        let backup_start = DataDirectory::read_backup_label(path).await?;
+        if let Some(start) = backup_start {
+            if stop <= DataDirectory::backup_end(&entries, start)? {
+                return Err(DataDirectoryError::TargetBeforeBackup(target));
+            }
+        }
+        info!(
+            "Recovering {} by redoing {} of {} changes",
+            path.display(),
+            stop,
+            entries.len()
+        );
+        let mut filenodes = DataDirectory::read_filenode_map(path).await?;
+        let redone = DataDirectory::redo(
+            path,
+            control_file.page_size.bytes(),
+            &entries[..stop],
+            &mut filenodes,
+        )
+        .await?;
+        let stopped_at = entries
+            .get(stop)
+            .map_or_else(|| entries.last().map_or(0, |e| e.end()), |e| e.lsn)
+            .max(control_file.checkpoint_lsn);
+
+        let mut last_segment = control_file.checkpoint_lsn / SEGMENT_SIZE;
+        for directory in [archive.to_path_buf(), wal_path.clone()].iter() {
+            if let Some(s) = WriteAheadLog::segments(directory).await?.last() {
+                last_segment = last_segment.max(*s);
+            }
+        }
+        for s in WriteAheadLog::segments(&wal_path).await? {
+            fs::remove_file(WriteAheadLog::segment_path(&wal_path, s)).await?;
+        }
+        let start = (last_segment + 1) * SEGMENT_SIZE;
+
+        let directory = DataDirectory {
+            path: path.to_path_buf(),
+            page_size: control_file.page_size.bytes(),
+            control_file: Arc::new(Mutex::new(control_file)),
+            unsynced: Arc::new(Mutex::new(redone)),
+            filenodes: Arc::new(Mutex::new(filenodes)),
+            wal: WriteAheadLog::open(&wal_path, start, None).await?,
+            backup_hold: Arc::new(Mutex::new(None)),
+            slots: Arc::new(Mutex::new(BTreeMap::new())),
+        };
+        directory.sync().await?;
+        control_file.checkpoint_lsn = start;
+        directory.write_control_file(control_file).await?;
+        if backup_start.is_some() {
+            fs::remove_file(path.join(BACKUP_LABEL)).await?;
+        }
+        Ok(stopped_at)
+    }
+
+    pub async fn control_file(&self) -> ControlFile {
+        *self.control_file.lock().await
+    }
+
+    /// Replaces the control file, written to the side first so it is never seen half written
+    pub async fn write_control_file(
+        &self,
+        control_file: ControlFile,
+    ) -> Result<(), DataDirectoryError> {
+        let mut current = self.control_file.lock().await;
+
+        let control_path = self.path.join(GLOBAL_DIR).join(CONTROL_FILE);
+        let temp_path = control_path.with_extension("tmp");
+        let mut file = fs::File::create(&temp_path).await?;
+        file.write_all(&control_file.serialize()).await?;
+        file.sync_all().await?;
+        fs::rename(&temp_path, &control_path).await?;
+
+        *current = control_file;
+        Ok(())
+    }
+
+    /// Where the transaction manager keeps its statuses
+    pub fn xact_path(&self) -> PathBuf {
+        self.path.join(XACT_FILE)
+    }
+
+    pub fn wal(&self) -> &WriteAheadLog {
+        &self.wal
+    }
+
+    /// Logs the page and then writes it. An unlogged write skips the log, a crash can leave the
+    /// file with this page or the one before it so it is only for changes that are safe to lose.
+    pub async fn write_page(
+        &self,
+        id: Uuid,
+        fork: ForkNumber,
+        page_num: usize,
+        page: &Bytes,
+        logged: bool,
+    ) -> Result<(), DataDirectoryError> {
+        let _delay = self.wal.delay_checkpoint().await;
+        let file = self.relation_file(id).await;
+        if logged {
+            self.wal
+                .append(&WalRecord::PageImage {
+                    relation: file,
+                    fork,
+                    page_num,
+                    page: page.clone(),
+                })
+                .await?;
+            self.wal.flush().await?;
+        }
+
+        let path = DataDirectory::relation_path(&self.path, file, fork);
+        DataDirectory::write_page_file(&path, self.page_size, page_num, page).await?;
+
+        //Only after the write so a sync that misses it leaves it for the next one
+        self.unsynced.lock().await.insert(path);
+        Ok(())
+    }
+
+    /// Flushes every file written since the last sync to disk, returning how many there were.
+    /// pg_xact is always flushed and isn't counted.
+    pub async fn sync(&self) -> Result<usize, DataDirectoryError> {
+        let pending: Vec<PathBuf> = self.unsynced.lock().await.drain().collect();
+        DataDirectory::sync_files(&pending).await?;
+        fs::File::open(self.xact_path()).await?.sync_all().await?;
+        self.write_filenode_map().await?;
+
+        //Files made, renamed or removed are only durable once their directory is flushed too
+        #[cfg(unix)]
+        fs::File::open(self.path.join(BASE_DIR))
+            .await?
+            .sync_all()
+            .await?;
+        Ok(pending.len())
+    }
+
+    /// Flushes up to the limit of the files written since the last sync, returning how many it
+    /// did and if any are still left. The rest stay for the next call or checkpoint.
+    ///
+    /// It must not run at the same time as sync, a checkpoint that misses a file being flushed
+    /// here could finish before that file is on disk.
+    pub async fn sync_some(&self, limit: usize) -> Result<(usize, bool), DataDirectoryError> {
+        let (pending, left) = {
+            let mut unsynced = self.unsynced.lock().await;
+            let pending: Vec<PathBuf> = unsynced.iter().take(limit).cloned().collect();
+            for path in pending.iter() {
+                unsynced.remove(path);
+            }
+            (pending, !unsynced.is_empty())
+        };
+        if let Err(e) = DataDirectory::sync_files(&pending).await {
+            //Left for the checkpoint to retry
+            self.unsynced.lock().await.extend(pending);
+            return Err(e);
+        }
+        Ok((pending.len(), left))
+    }
+
+    async fn sync_files(paths: &[PathBuf]) -> Result<(), DataDirectoryError> {
+        for path in paths {
+            match fs::File::open(path).await {
+                Ok(file) => file.sync_all().await?,
+                //Dropped since it was written
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Exchanges the files of two relations, so each has the other's pages. However big they
+    /// are it is one log record and a change to the filenode map, which the next sync writes out.
+    pub async fn swap_relations(&self, left: Uuid, right: Uuid) -> Result<(), DataDirectoryError> {
+        let _delay = self.wal.delay_checkpoint().await;
+        let mut filenodes = self.filenodes.lock().await;
+        let record = WalRecord::SwapRelations {
+            left,
+            left_file: DataDirectory::file_of(&filenodes, right),
+            right,
+            right_file: DataDirectory::file_of(&filenodes, left),
+        };
+        self.wal.append(&record).await?;
+        self.wal.flush().await?;
+        DataDirectory::map_files(&mut filenodes, &record);
+        Ok(())
+    }
+
+    /// The id of the file a relation's pages are in, its own unless it was swapped
+    pub async fn relation_file(&self, id: Uuid) -> Uuid {
+        DataDirectory::file_of(&*self.filenodes.lock().await, id)
+    }
+
+    /// The relation whose pages are in the file, the reverse of relation_file
+    pub async fn file_relation(&self, file: Uuid) -> Uuid {
+        DataDirectory::relation_of(&*self.filenodes.lock().await, file)
+    }
+
+    pub async fn drop_relation(&self, id: Uuid) -> Result<(), DataDirectoryError> {
+        let _delay = self.wal.delay_checkpoint().await;
+        let mut filenodes = self.filenodes.lock().await;
+        let file = DataDirectory::file_of(&filenodes, id);
+        self.wal
+            .append(&WalRecord::DropRelation { relation: file })
+            .await?;
+        self.wal.flush().await?;
+        filenodes.retain(|_, f| *f != file);
+
+        let mut unsynced = self.unsynced.lock().await;
+        for fork in ForkNumber::VALUES.iter() {
+            unsynced.remove(&DataDirectory::relation_path(&self.path, file, *fork));
+        }
+        DataDirectory::remove_files(&self.path, file).await
+    }
+
+    /// Makes a change read from a primary's log, logging it at the same LSN first like any other
+    pub async fn replay(&self, entry: &WalEntry) -> Result<(), DataDirectoryError> {
+        self.wal.receive(entry).await?;
+        self.wal.flush().await?;
+        let written = DataDirectory::redo(
+            &self.path,
+            self.page_size,
+            std::slice::from_ref(entry),
+            &mut *self.filenodes.lock().await,
+        )
+        .await?;
+        self.unsynced.lock().await.extend(written);
+        Ok(())
+    }
+
+    /// Removes the archived log before the LSN, keeping what a running base backup or a
+    /// replication slot still needs
+    pub async fn remove_wal_before(&self, lsn: u64) -> Result<usize, DataDirectoryError> {
+        let hold = *self.backup_hold.lock().await;
+        let mut lsn = hold.map_or(lsn, |h| h.min(lsn));
+        for slot in self.slots.lock().await.values() {
+            lsn = lsn.min(slot.restart_lsn);
+        }
+        Ok(self.wal.remove_before(lsn).await?)
+    }
+
+    /// Makes a logical replication slot that decodes the transactions starting from now on
+    pub async fn create_slot(
+        &self,
+        name: &str,
+        plugin: &str,
+    ) -> Result<ReplicationSlot, DataDirectoryError> {
+        let mut slots = self.slots.lock().await;
+        if slots.contains_key(name) {
+            return Err(DataDirectoryError::SlotExists(name.to_string()));
+        }
+        let slot = ReplicationSlot::new(name, plugin, self.wal.end_lsn().await)?;
+        self.write_slot(&slot).await?;
+        slots.insert(name.to_string(), slot.clone());
+        Ok(slot)
+    }
+
+    pub async fn drop_slot(&self, name: &str) -> Result<(), DataDirectoryError> {
+        let mut slots = self.slots.lock().await;
+        match slots.get(name) {
+            Some(s) if s.active => return Err(DataDirectoryError::SlotActive(name.to_string())),
+            Some(_) => {}
+            None => return Err(DataDirectoryError::SlotNotFound(name.to_string())),
+        }
+        fs::remove_file(self.path.join(SLOT_DIR).join(name)).await?;
+        #[cfg(unix)]
+        fs::File::open(self.path.join(SLOT_DIR))
+            .await?
+            .sync_all()
+            .await?;
+        slots.remove(name);
+        Ok(())
+    }
+
+    pub async fn slots(&self) -> Vec<ReplicationSlot> {
+        self.slots.lock().await.values().cloned().collect()
+    }
+
+    /// Marks the slot as being read from, only one consumer can use a slot at a time
+    pub async fn acquire_slot(&self, name: &str) -> Result<ReplicationSlot, DataDirectoryError> {
+        let mut slots = self.slots.lock().await;
+        let slot = slots
+            .get_mut(name)
+            .ok_or_else(|| DataDirectoryError::SlotNotFound(name.to_string()))?;
+        if slot.active {
+            return Err(DataDirectoryError::SlotActive(name.to_string()));
+        }
+        slot.active = true;
+        Ok(slot.clone())
+    }
+
+    pub async fn release_slot(&self, name: &str) {
+        if let Some(slot) = self.slots.lock().await.get_mut(name) {
+            slot.active = false;
+        }
+    }
+
+    /// Records that the consumer has everything committed before confirmed_flush, the log before
+    /// restart_lsn is then no longer needed. Slots never move backwards.
+    pub async fn advance_slot(
+        &self,
+        name: &str,
+        confirmed_flush: u64,
+        restart_lsn: u64,
+    ) -> Result<ReplicationSlot, DataDirectoryError> {
+        let mut slots = self.slots.lock().await;
+        let slot = slots
+            .get_mut(name)
+            .ok_or_else(|| DataDirectoryError::SlotNotFound(name.to_string()))?;
+        if confirmed_flush <= slot.confirmed_flush && restart_lsn <= slot.restart_lsn {
+            return Ok(slot.clone());
+        }
+        let mut advanced = slot.clone();
+        advanced.confirmed_flush = advanced.confirmed_flush.max(confirmed_flush);
+        advanced.restart_lsn = advanced.restart_lsn.max(restart_lsn);
+        self.write_slot(&advanced).await?;
+        *slot = advanced.clone();
+        Ok(advanced)
+    }
+
+    //Written to the side first like the control file
+    async fn write_slot(&self, slot: &ReplicationSlot) -> Result<(), DataDirectoryError> {
+        let slot_path = self.path.join(SLOT_DIR).join(&slot.name);
+        let temp_path = slot_path.with_extension("tmp");
+        DataDirectory::write_synced(&temp_path, &slot.serialize()).await?;
+        fs::rename(&temp_path, &slot_path).await?;
+        #[cfg(unix)]
+        fs::File::open(self.path.join(SLOT_DIR))
+            .await?
+            .sync_all()
+            .await?;
+        Ok(())
+    }
+
+    //Directories made before slots existed don't have pg_replslot yet
+    async fn read_slots(
+        path: &Path,
+    ) -> Result<BTreeMap<String, ReplicationSlot>, DataDirectoryError> {
+        let slot_path = path.join(SLOT_DIR);
+        fs::create_dir_all(&slot_path).await?;
+        let mut slots = BTreeMap::new();
+        let mut entries = fs::read_dir(&slot_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            //A crash while writing one
+            if name.ends_with(".tmp") {
+                fs::remove_file(entry.path()).await?;
+                continue;
+            }
+            let contents = fs::read(entry.path()).await?;
+            let slot = ReplicationSlot::parse(&name, &mut Bytes::from(contents))?;
+            slots.insert(name, slot);
+        }
+        Ok(slots)
+    }
+
+    /// Reads a copy of the directory while changes keep being made, like pg_basebackup.
+    ///
+    /// The files read can be torn or from different moments, so the log from the last
+    /// checkpoint until the backup's end record is included too. Opening the copy redoes it, and
+    /// the backup_label left in the copy makes opening fail if the log doesn't reach the end.
+    /// The files are in the order to write them, the control file last like initdb.
+    pub async fn base_backup(&self) -> Result<(BaseBackup, Vec<BackupFile>), DataDirectoryError> {
+        let mut control_file = {
+            let mut hold = self.backup_hold.lock().await;
+            if hold.is_some() {
+                return Err(DataDirectoryError::BackupInProgress());
+            }
+            let control_file = self.control_file().await;
+            *hold = Some(control_file.checkpoint_lsn);
+            control_file
+        };
+        let result = self.read_backup(&mut control_file).await;
+        *self.backup_hold.lock().await = None;
+        result
+    }
+
+    async fn read_backup(
+        &self,
+        control_file: &mut ControlFile,
+    ) -> Result<(BaseBackup, Vec<BackupFile>), DataDirectoryError> {
+        let start_lsn = self.wal.append(&WalRecord::BackupStart).await?;
+        self.wal.flush().await?;
+
+        let mut backup_files = vec![];
+        let mut entries = fs::read_dir(self.path.join(BASE_DIR)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = format!("{}/{}", BASE_DIR, entry.file_name().to_string_lossy());
+            if let Some(contents) = DataDirectory::read_file(&entry.path()).await? {
+                backup_files.push((name, contents));
+            }
+        }
+        let files = backup_files.len();
+        for name in [
+            XACT_FILE.to_string(),
+            format!("{}/{}", GLOBAL_DIR, FILENODE_MAP),
+        ] {
+            if let Some(contents) = DataDirectory::read_file(&self.path.join(&name)).await? {
+                backup_files.push((name, contents));
+            }
+        }
+
+        self.wal
+            .append(&WalRecord::BackupEnd { start: start_lsn })
+            .await?;
+        let stop_lsn = self.wal.end_lsn().await;
+        //Finishes the segment so the backup's whole log gets archived
+        self.wal.switch_segment().await?;
+
+        let wal_path = self.path.join(WAL_DIR);
+        let segments = control_file.checkpoint_lsn / SEGMENT_SIZE..=(stop_lsn - 1) / SEGMENT_SIZE;
+        for segment in WriteAheadLog::segments(&wal_path).await? {
+            if !segments.contains(&segment) {
+                continue;
+            }
+            let path = WriteAheadLog::segment_path(&wal_path, segment);
+            if let Some(contents) = DataDirectory::read_file(&path).await? {
+                let name = format!(
+                    "{}/{}",
+                    WAL_DIR,
+                    path.file_name().unwrap().to_string_lossy()
+                );
+                backup_files.push((name, contents));
+            }
+        }
+
+        let label = format!(
+            "START WAL LOCATION: {}\nCHECKPOINT LOCATION: {}\n",
+            start_lsn, control_file.checkpoint_lsn
+        );
+        backup_files.push((BACKUP_LABEL.to_string(), Bytes::from(label)));
+        control_file.state = ClusterState::InProduction;
+        backup_files.push((
+            format!("{}/{}", GLOBAL_DIR, CONTROL_FILE),
+            control_file.serialize(),
+        ));
+
+        let backup = BaseBackup {
+            start_lsn,
+            stop_lsn,
+            files,
+        };
+        Ok((backup, backup_files))
+    }
+
+    /// Makes the directory a base backup is written to, it must be missing or empty
+    pub async fn create_backup_target(target: &Path) -> Result<(), DataDirectoryError> {
+        if DataDirectory::has_entries(target).await? {
+            return Err(DataDirectoryError::NotEmpty(target.to_path_buf()));
+        }
+        fs::create_dir_all(target).await?;
+        Ok(())
+    }
+
+    /// Writes one of a base backup's files, refusing names that would leave the target
+    pub async fn write_backup_file(
+        target: &Path,
+        name: &str,
+        contents: &[u8],
+    ) -> Result<(), DataDirectoryError> {
+        let parts: Vec<_> = name.split('/').collect();
+        let bad = |p: &&str| {
+            p.is_empty() || *p == "." || *p == ".." || p.contains(std::path::is_separator)
+        };
+        if parts.iter().any(bad) {
+            return Err(DataDirectoryError::BadBackupFile(name.to_string()));
+        }
+
+        let path = parts
+            .iter()
+            .fold(target.to_path_buf(), |p, part| p.join(part));
+        let parent = path.parent().unwrap_or(target);
+        fs::create_dir_all(parent).await?;
+        DataDirectory::write_synced(&path, contents).await?;
+        #[cfg(unix)]
+        fs::File::open(parent).await?.sync_all().await?;
+        Ok(())
+    }
+
+    //Makes the logged changes again, returning the files written
+    async fn redo(
+        path: &Path,
+        page_size: usize,
+        entries: &[WalEntry],
+        filenodes: &mut Filenodes,
+    ) -> Result<HashSet<PathBuf>, DataDirectoryError> {
+        let mut written = HashSet::new();
+        let mut statuses = vec![];
+        for entry in entries {
+            match &entry.record {
+                WalRecord::PageImage {
+                    relation,
+                    fork,
+                    page_num,
+                    page,
+                } => {
+                    let file = DataDirectory::relation_path(path, *relation, *fork);
+                    DataDirectory::write_page_file(&file, page_size, *page_num, page).await?;
+                    written.insert(file);
+                }
+                WalRecord::Truncate {
+                    relation,
+                    fork,
+                    pages,
+                } => {
+                    let file = DataDirectory::relation_path(path, *relation, *fork);
+                    DataDirectory::truncate_file(&file, page_size, *pages).await?;
+                    written.insert(file);
+                }
+                WalRecord::DropRelation { relation } => {
+                    DataDirectory::remove_files(path, *relation).await?;
+                    filenodes.retain(|_, f| f != relation);
+                }
+                WalRecord::SwapRelations { .. } => {
+                    DataDirectory::map_files(filenodes, &entry.record)
+                }
+                WalRecord::BackupStart | WalRecord::BackupEnd { .. } | WalRecord::RowChange(_) => {}
+                WalRecord::TransactionStatus { tran_id, status } => {
+                    //pg_xact starts with the first transaction
+                    statuses.push((tran_id.get_u64().saturating_sub(1), status.serialize()));
+                }
+            }
+        }
+        if !statuses.is_empty() {
+            DataDirectory::write_statuses(&path.join(XACT_FILE), &statuses).await?;
+        }
+        Ok(written)
+    }
+
+    //Transactions skipped over never finished, so they are aborted
+    async fn write_statuses(path: &Path, statuses: &[(u64, u8)]) -> Result<(), DataDirectoryError> {
+        let mut file = OpenOptions::new().write(true).open(path).await?;
+        let mut len = file.metadata().await?.len();
+        for (index, status) in statuses {
+            if *index > len {
+                file.seek(SeekFrom::Start(len)).await?;
+                file.write_all(&vec![
+                    TransactionStatus::Aborted.serialize();
+                    (index - len) as usize
+                ])
+                .await?;
+            }
+            file.seek(SeekFrom::Start(*index)).await?;
+            file.write_all(&[*status]).await?;
+            len = len.max(index + 1);
+        }
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn read_control_file(path: &Path) -> Result<ControlFile, DataDirectoryError> {
+        let control_path = path.join(GLOBAL_DIR).join(CONTROL_FILE);
+        let control_bytes = match fs::read(&control_path).await {
+            Ok(b) => b,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if DataDirectory::has_entries(path).await? {
+                    return Err(DataDirectoryError::HalfInitialized(path.to_path_buf()));
+                }
+                return Err(DataDirectoryError::NotInitialized(path.to_path_buf()));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let control_file = ControlFile::parse(&mut Bytes::from(control_bytes))?;
+
+        for required in [XACT_FILE, WAL_DIR] {
+            if fs::metadata(path.join(required)).await.is_err() {
+                return Err(DataDirectoryError::HalfInitialized(path.to_path_buf()));
+            }
+        }
+        Ok(control_file)
+    }
+
+    async fn write_page_file(
+        path: &Path,
+        page_size: usize,
+        page_num: usize,
+        page: &Bytes,
+    ) -> Result<(), DataDirectoryError> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await?;
+        file.seek(SeekFrom::Start((page_num * page_size) as u64))
+            .await?;
+        file.write_all(page).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn truncate_file(
+        path: &Path,
+        page_size: usize,
+        pages: usize,
+    ) -> Result<(), DataDirectoryError> {
+        if pages == 0 {
+            return match fs::remove_file(path).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await?;
+        file.set_len((pages * page_size) as u64).await?;
+        Ok(())
+    }
+
+    async fn remove_files(path: &Path, id: Uuid) -> Result<(), DataDirectoryError> {
+        for fork in ForkNumber::VALUES.iter() {
+            match fs::remove_file(DataDirectory::relation_path(path, id, *fork)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn file_of(filenodes: &Filenodes, id: Uuid) -> Uuid {
+        *filenodes.get(&id).unwrap_or(&id)
+    }
+
+    fn relation_of(filenodes: &Filenodes, file: Uuid) -> Uuid {
+        filenodes
+            .iter()
+            .find(|(_, f)| **f == file)
+            .map_or(file, |(id, _)| *id)
+    }
+
+    //Sets the files a swap gives the two relations, the same however many times it is redone
+    fn map_files(filenodes: &mut Filenodes, record: &WalRecord) {
+        if let WalRecord::SwapRelations {
+            left,
+            left_file,
+            right,
+            right_file,
+        } = record
+        {
+            for (id, file) in [(left, left_file), (right, right_file)] {
+                if id == file {
+                    filenodes.remove(id);
+                } else {
+                    filenodes.insert(*id, *file);
+                }
+            }
+        }
+    }
+
+    //Written to the side first like the control file, missing is the same as empty
+    async fn write_filenode_map(&self) -> Result<(), DataDirectoryError> {
+        let contents: String = self
+            .filenodes
+            .lock()
+            .await
+            .iter()
+            .map(|(id, file)| format!("{} {}\n", id.to_hyphenated(), file.to_hyphenated()))
+            .collect();
+        let map_path = self.path.join(GLOBAL_DIR).join(FILENODE_MAP);
+        let temp_path = map_path.with_extension("tmp");
+        DataDirectory::write_synced(&temp_path, contents.as_bytes()).await?;
+        fs::rename(&temp_path, &map_path).await?;
+        Ok(())
+    }
+
+    async fn read_filenode_map(path: &Path) -> Result<Filenodes, DataDirectoryError> {
+        let map_path = path.join(GLOBAL_DIR).join(FILENODE_MAP);
+        let contents = match fs::read_to_string(&map_path).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        contents
+            .lines()
+            .map(|l| {
+                let (id, file) = l.split_once(' ')?;
+                Some((Uuid::parse_str(id).ok()?, Uuid::parse_str(file).ok()?))
+            })
+            .collect::<Option<Filenodes>>()
+            .ok_or(DataDirectoryError::BadFilenodeMap(map_path))
+    }
+
+    fn relation_path(path: &Path, id: Uuid, fork: ForkNumber) -> PathBuf {
+        let suffix = match fork {
+            ForkNumber::Main => "",
+            ForkNumber::FreeSpace => "_fsm",
+            ForkNumber::VisibilityMap => "_vm",
+        };
+        path.join(BASE_DIR)
+            .join(format!("{}{}", id.to_hyphenated(), suffix))
+    }
+
+    async fn read_relations(
+        path: &Path,
+        page_size: usize,
+        filenodes: &Filenodes,
+    ) -> Result<Relations, DataDirectoryError> {
+        let mut relations = HashMap::new();
+        let mut entries = fs::read_dir(path.join(BASE_DIR)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (file, fork) = DataDirectory::parse_file_name(&name)
+                .ok_or_else(|| DataDirectoryError::UnknownFile(entry.path()))?;
+
+            let contents = Bytes::from(fs::read(entry.path()).await?);
+            if contents.len() % page_size != 0 {
+                return Err(DataDirectoryError::PartialPage(entry.path()));
+            }
+            let pages = (0..contents.len() / page_size)
+                .map(|p| contents.slice(p * page_size..(p + 1) * page_size))
+                .collect();
+            relations.insert((DataDirectory::relation_of(filenodes, file), fork), pages);
+        }
+        Ok(relations)
+    }
+
+    fn parse_file_name(name: &str) -> Option<(Uuid, ForkNumber)> {
+        let (id, fork) = match name.split_once('_') {
+            Some((id, "fsm")) => (id, ForkNumber::FreeSpace),
+            Some((id, "vm")) => (id, ForkNumber::VisibilityMap),
+            Some(_) => return None,
+            None => (name, ForkNumber::Main),
+        };
+        Uuid::parse_str(id).ok().map(|id| (id, fork))
+    }
+
+    //The LSN a base backup started at, if this is a copy that hasn't been opened yet
+    async fn read_backup_label(path: &Path) -> Result<Option<u64>, DataDirectoryError> {
+        let label = match fs::read_to_string(path.join(BACKUP_LABEL)).await {
+            Ok(l) => l,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        label
+            .lines()
+            .find_map(|l| l.strip_prefix("START WAL LOCATION: "))
+            .and_then(|l| l.parse().ok())
+            .map(Some)
+            .ok_or_else(|| DataDirectoryError::BadBackupLabel(path.join(BACKUP_LABEL)))
+    }
+
+    //Where the backup's end record is in the entries
+    fn backup_end(entries: &[WalEntry], start: u64) -> Result<usize, DataDirectoryError> {
+        entries
+            .iter()
+            .position(|e| e.record == WalRecord::BackupEnd { start })
+            .ok_or(DataDirectoryError::BackupIncomplete(start))
+    }
+
+    //None if the file was removed before it could be read
+    async fn read_file(path: &Path) -> Result<Option<Bytes>, DataDirectoryError> {
+        match fs::read(path).await {
+            Ok(contents) => Ok(Some(Bytes::from(contents))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write_synced(path: &Path, contents: &[u8]) -> Result<(), DataDirectoryError> {
+        let mut file = fs::File::create(path).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+        Ok(())
+    }
+
+    async fn has_entries(path: &Path) -> Result<bool, DataDirectoryError> {
+        match fs::read_dir(path).await {
+            Ok(mut entries) => Ok(entries.next_entry().await?.is_some()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DataDirectoryError {
+    #[error("{0} is not empty, initdb needs a new directory")]
+    NotEmpty(PathBuf),
+    #[error("{0} has not been initialized, run initdb first")]
+    NotInitialized(PathBuf),
+    #[error("{0} was not completely initialized, remove it and run initdb again")]
+    HalfInitialized(PathBuf),
+    #[error("{0} was not shut down cleanly, start and stop the server first")]
+    NotShutDown(PathBuf),
+    #[error("{0} is not a relation file")]
+    UnknownFile(PathBuf),
+    #[error("{0} ends with a partial page")]
+    PartialPage(PathBuf),
+    #[error("Incompatible control file: {0}")]
+    ControlFileError(#[from] ControlFileError),
+    #[error("A base backup is already running")]
+    BackupInProgress(),
+    #[error("The log ends before the base backup started at {0} finished, it isn't consistent")]
+    BackupIncomplete(u64),
+    #[error("{0} is not a file of a base backup")]
+    BadBackupFile(String),
+    #[error("{0} is not a filenode map")]
+    BadFilenodeMap(PathBuf),
+    #[error("{0} is not a backup label")]
+    BadBackupLabel(PathBuf),
+    #[error("Recovery target {0:?} is before the backup was taken")]
+    TargetBeforeBackup(RecoveryTarget),
+    #[error("The log ended before reaching recovery target {0:?}")]
+    TargetNotReached(RecoveryTarget),
+    #[error("Replication slot \"{0}\" already exists")]
+    SlotExists(String),
+    #[error("Replication slot \"{0}\" does not exist")]
+    SlotNotFound(String),
+    #[error("Replication slot \"{0}\" is being read from by another consumer")]
+    SlotActive(String),
+    #[error(transparent)]
+    ReplicationSlotError(#[from] ReplicationSlotError),
+    #[error(transparent)]
+    WriteAheadLogError(#[from] WriteAheadLogError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+impl DataDirectoryError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            DataDirectoryError::SlotExists(_) => PgErrorCodes::DuplicateObject,
+            DataDirectoryError::SlotNotFound(_) => PgErrorCodes::UndefinedObject,
+            DataDirectoryError::SlotActive(_) => PgErrorCodes::ObjectInUse,
+            DataDirectoryError::ReplicationSlotError(ReplicationSlotError::BadName(_)) => {
+                PgErrorCodes::InvalidName
+            }
+            DataDirectoryError::ReplicationSlotError(ReplicationSlotError::UnknownPlugin(_)) => {
+                PgErrorCodes::UndefinedObject
+            }
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::page_formats::PageSize;
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_initdb_open() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("data");
+        let control_file = ControlFile::new(PageSize::Kb4);
+        let id = Uuid::new_v4();
+        let other = Uuid::new_v4();
+
+        let mut relations = HashMap::new();
+        relations.insert(
+            (id, ForkNumber::Main),
+            vec![Bytes::from(vec![1; 4096]), Bytes::from(vec![2; 4096])],
+        );
+        let directory = aw!(DataDirectory::initdb(&path, control_file, &relations))?;
+        assert!(matches!(
+            aw!(DataDirectory::initdb(&path, control_file, &relations)),
+            Err(DataDirectoryError::NotEmpty(_))
+        ));
+
+        aw!(directory.write_page(id, ForkNumber::Main, 1, &Bytes::from(vec![3; 4096]), true))?;
+        aw!(directory.write_page(
+            other,
+            ForkNumber::VisibilityMap,
+            0,
+            &Bytes::from(vec![4; 4096]),
+            true
+        ))?;
+
+        //Only the filenode map changes, each keeps writing to the file it was given
+        aw!(directory.swap_relations(id, other))?;
+        assert_eq!(aw!(directory.relation_file(id)), other);
+        assert_eq!(aw!(directory.file_relation(id)), other);
+        aw!(directory.write_page(
+            other,
+            ForkNumber::Main,
+            2,
+            &Bytes::from(vec![5; 4096]),
+            true
+        ))?;
+        assert_eq!(aw!(directory.sync())?, 2);
+        assert_eq!(aw!(directory.sync())?, 0);
+
+        let (reopened, relations) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(aw!(reopened.control_file()), control_file);
+        assert_eq!(
+            relations.get(&(other, ForkNumber::Main)),
+            Some(&vec![
+                Bytes::from(vec![1; 4096]),
+                Bytes::from(vec![3; 4096]),
+                Bytes::from(vec![5; 4096])
+            ])
+        );
+        assert_eq!(
+            relations.get(&(id, ForkNumber::VisibilityMap)),
+            Some(&vec![Bytes::from(vec![4; 4096])])
+        );
+        assert_eq!(relations.len(), 2);
+
+        //Dropping the relation takes its file and forgets it was swapped
+        aw!(reopened.drop_relation(other))?;
+        assert!(!DataDirectory::relation_path(&path, id, ForkNumber::Main).exists());
+        aw!(reopened.sync())?;
+        let (reopened, relations) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(relations.len(), 1);
+        assert_eq!(aw!(reopened.file_relation(id)), id);
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_redo() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("data");
+        let left = Uuid::new_v4();
+        let right = Uuid::new_v4();
+        let mut relations = HashMap::new();
+        relations.insert((left, ForkNumber::Main), vec![Bytes::from(vec![1; 4096])]);
+        relations.insert((right, ForkNumber::Main), vec![Bytes::from(vec![2; 4096])]);
+        let directory = aw!(DataDirectory::initdb(
+            &path,
+            ControlFile::new(PageSize::Kb4),
+            &relations
+        ))?;
+
+        //Crashes before a sync writes the filenode map out
+        aw!(directory.swap_relations(left, right))?;
+        aw!(directory.write_page(left, ForkNumber::Main, 1, &Bytes::from(vec![3; 4096]), true))?;
+        aw!(directory.swap_relations(left, right))?;
+        aw!(directory.swap_relations(left, right))?;
+        assert!(std::fs::read(path.join(GLOBAL_DIR).join(FILENODE_MAP))?.is_empty());
+
+        let (reopened, relations) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(
+            relations.get(&(left, ForkNumber::Main)),
+            Some(&vec![
+                Bytes::from(vec![2; 4096]),
+                Bytes::from(vec![3; 4096])
+            ])
+        );
+        assert_eq!(
+            relations.get(&(right, ForkNumber::Main)),
+            Some(&vec![Bytes::from(vec![1; 4096])])
+        );
+
+        //Written out it is the same, even redone again on top
+        aw!(reopened.sync())?;
+        let (_, again) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(again, relations);
+        Ok(())
+    }
+
+    #[test]
+    fn test_redo_after_crash() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("data");
+        let id = Uuid::new_v4();
+        let directory = aw!(DataDirectory::initdb(
+            &path,
+            ControlFile::new(PageSize::Kb4),
+            &HashMap::new()
+        ))?;
+        aw!(directory.write_page(id, ForkNumber::Main, 0, &Bytes::from(vec![1; 4096]), true))?;
+        aw!(directory.write_page(id, ForkNumber::Main, 1, &Bytes::from(vec![2; 4096]), true))?;
+
+        //The second write never made it to disk
+        let file = DataDirectory::relation_path(&path, id, ForkNumber::Main);
+        std::fs::write(&file, vec![1; 4096])?;
+
+        let (reopened, relations) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(
+            relations.get(&(id, ForkNumber::Main)),
+            Some(&vec![
+                Bytes::from(vec![1; 4096]),
+                Bytes::from(vec![2; 4096])
+            ])
+        );
+        assert_eq!(aw!(reopened.sync())?, 1);
+        assert_eq!(
+            aw!(reopened.wal().end_lsn()),
+            aw!(directory.wal().end_lsn())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_replication_slots() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("data");
+        let directory = aw!(DataDirectory::initdb(
+            &path,
+            ControlFile::new(PageSize::Kb4),
+            &HashMap::new()
+        ))?;
+        aw!(directory.wal().append(&WalRecord::BackupStart))?;
+        let slot = aw!(directory.create_slot("indexer", "test_decoding"))?;
+        assert_eq!(slot.restart_lsn, aw!(directory.wal().end_lsn()));
+        assert!(matches!(
+            aw!(directory.create_slot("indexer", "pgoutput")),
+            Err(DataDirectoryError::SlotExists(_))
+        ));
+
+        //Only one consumer at a time and never while in use
+        aw!(directory.acquire_slot("indexer"))?;
+        assert!(matches!(
+            aw!(directory.acquire_slot("indexer")),
+            Err(DataDirectoryError::SlotActive(_))
+        ));
+        assert!(matches!(
+            aw!(directory.drop_slot("indexer")),
+            Err(DataDirectoryError::SlotActive(_))
+        ));
+        aw!(directory.release_slot("indexer"));
+
+        //The slot holds on to the log it hasn't confirmed
+        aw!(directory.wal().switch_segment())?;
+        aw!(directory.wal().append(&WalRecord::BackupStart))?;
+        aw!(directory.wal().switch_segment())?;
+        aw!(directory.wal().append(&WalRecord::BackupStart))?;
+        let end = aw!(directory.wal().end_lsn());
+        assert_eq!(aw!(directory.remove_wal_before(end))?, 0);
+        aw!(directory.advance_slot("indexer", end, SEGMENT_SIZE))?;
+        assert_eq!(aw!(directory.remove_wal_before(end))?, 1);
+
+        //Slots never move backwards
+        let slot = aw!(directory.advance_slot("indexer", 0, 0))?;
+        assert_eq!(
+            (slot.confirmed_flush, slot.restart_lsn),
+            (end, SEGMENT_SIZE)
+        );
+
+        let (reopened, _) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(aw!(reopened.slots()), vec![slot]);
+        aw!(reopened.drop_slot("indexer"))?;
+        assert!(matches!(
+            aw!(reopened.drop_slot("indexer")),
+            Err(DataDirectoryError::SlotNotFound(_))
+        ));
+        let (reopened, _) = aw!(DataDirectory::open(&path, None))?;
+        assert_eq!(aw!(reopened.slots()), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_refuses() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        assert!(matches!(
+            aw!(DataDirectory::open(&tmp.path().join("missing"), None)),
+            Err(DataDirectoryError::NotInitialized(_))
+        ));
+
+        //initdb stopped before writing the control file
+        let half = tmp.path().join("half");
+        std::fs::create_dir_all(half.join(BASE_DIR))?;
+        assert!(matches!(
+            aw!(DataDirectory::open(&half, None)),
+            Err(DataDirectoryError::HalfInitialized(_))
+        ));
+
+        //Someone else's or a newer version's directory
+        let path = tmp.path().join("data");
+        aw!(DataDirectory::initdb(
+            &path,
+            ControlFile::new(PageSize::Kb4),
+            &HashMap::new()
+        ))?;
+        let control_path = path.join(GLOBAL_DIR).join(CONTROL_FILE);
+        let mut control = std::fs::read(&control_path)?;
+        control[8] = 99;
+        std::fs::write(&control_path, control)?;
+        assert!(matches!(
+            aw!(DataDirectory::open(&path, None)),
+            Err(DataDirectoryError::ControlFileError(
+                ControlFileError::UnsupportedVersion(99)
+            ))
+        ));
+        Ok(())
+    }
+}
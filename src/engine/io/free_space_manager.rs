@@ -0,0 +1,295 @@
+//! Tracks roughly how much room each page of a table has so inserts don't have to try every page.
+//! Loosely based on: https://www.postgresql.org/docs/current/storage-fsm.html
+//!
+//! Every main page gets a one byte category, its free space in 256ths of a page rounded down. The
+//! categories are written to the table's free space fork, a page worth of them to each fork page. On
+//! top of that sits an in memory max tree so finding the first page with enough room is O(log n).
+//!
+//! The map is only a hint, callers must still check the page and report back what they found.
+use super::super::objects::Table;
+use super::{ForkNumber, IOManager, IOManagerError};
+use bytes::{Bytes, BytesMut};
+use std::cmp::max;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const CATEGORIES: usize = 256;
+
+#[derive(Clone, Debug)]
+pub struct FreeSpaceManager {
+    io_manager: IOManager,
+    trees: Arc<RwLock<HashMap<Uuid, FreeSpaceTree>>>,
+}
+
+impl FreeSpaceManager {
+    pub fn new(io_manager: IOManager) -> FreeSpaceManager {
+        FreeSpaceManager {
+            io_manager,
+            trees: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// The first page recorded as having at least needed bytes free
+    pub async fn find_page(
+        &self,
+        table: Arc<Table>,
+        needed: usize,
+    ) -> Result<Option<usize>, FreeSpaceManagerError> {
+        let category = needed.div_ceil(self.unit());
+        if category >= CATEGORIES {
+            return Ok(None);
+        }
+
+        self.load(table.clone()).await?;
+        let trees = self.trees.read().await;
+        Ok(trees.get(&table.id).and_then(|t| t.search(category as u8)))
+    }
+
+    /// Records how much free space a page has now
+    pub async fn update(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+        free_space: usize,
+    ) -> Result<(), FreeSpaceManagerError> {
+        let category = (free_space / self.unit()).min(CATEGORIES - 1) as u8;
+
+        self.load(table.clone()).await?;
+        {
+            let mut trees = self.trees.write().await;
+            if let Some(t) = trees.get_mut(&table.id) {
+                if t.get(page_num) == Some(category) {
+                    return Ok(());
+                }
+                t.set(page_num, category);
+            }
+        }
+
+        self.write_category(table, page_num, category).await
+    }
+
+    /// Drops what is known about the table, the next use will read its fork again
+    pub async fn forget(&self, table: Arc<Table>) {
+        self.trees.write().await.remove(&table.id);
+    }
+
+    fn unit(&self) -> usize {
+        self.io_manager.page_size().bytes() / CATEGORIES
+    }
+
+    //Builds the tree from the fork if this is the first time the table has been seen
+    async fn load(&self, table: Arc<Table>) -> Result<(), FreeSpaceManagerError> {
+        if self.trees.read().await.contains_key(&table.id) {
+            return Ok(());
+        }
+
+        let page_size = self.io_manager.page_size().bytes();
+        let main_pages = self
+            .io_manager
+            .page_count(table.clone(), ForkNumber::Main)
+            .await;
+
+        let mut tree = FreeSpaceTree::new();
+        let mut page_num = 0;
+        while page_num < main_pages {
+            let fork_page = self
+                .io_manager
+                .get_fork_page(table.clone(), ForkNumber::FreeSpace, page_num / page_size)
+                .await;
+            //Pages the fork has never heard of are assumed full until someone reports otherwise
+            let category = fork_page.map_or(0, |p| p[page_num % page_size]);
+            tree.set(page_num, category);
+            page_num += 1;
+        }
+
+        self.trees.write().await.entry(table.id).or_insert(tree);
+        Ok(())
+    }
+
+    async fn write_category(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+        category: u8,
+    ) -> Result<(), FreeSpaceManagerError> {
+        let page_size = self.io_manager.page_size().bytes();
+        let fork_page_num = page_num / page_size;
+
+        while self
+            .io_manager
+            .page_count(table.clone(), ForkNumber::FreeSpace)
+            .await
+            <= fork_page_num
+        {
+            self.io_manager
+                .add_fork_page(
+                    table.clone(),
+                    ForkNumber::FreeSpace,
+                    Bytes::from(vec![0; page_size]),
+                )
+                .await?;
+        }
+
+        let fork_page = self
+            .io_manager
+            .get_fork_page(table.clone(), ForkNumber::FreeSpace, fork_page_num)
+            .await
+            .ok_or(FreeSpaceManagerError::MissingForkPage(fork_page_num))?;
+        let mut fork_page = BytesMut::from(&fork_page[..]);
+        fork_page[page_num % page_size] = category;
+
+        self.io_manager
+            .update_fork_page(
+                table,
+                ForkNumber::FreeSpace,
+                fork_page.freeze(),
+                fork_page_num,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+//Max tree stored as an array, node i has children 2i and 2i + 1 and the leaves start at capacity
+#[derive(Clone, Debug)]
+struct FreeSpaceTree {
+    leaves: usize,
+    nodes: Vec<u8>,
+}
+
+impl FreeSpaceTree {
+    fn new() -> FreeSpaceTree {
+        FreeSpaceTree {
+            leaves: 0,
+            nodes: vec![0; 2],
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.nodes.len() / 2
+    }
+
+    fn get(&self, leaf: usize) -> Option<u8> {
+        if leaf >= self.leaves {
+            return None;
+        }
+        Some(self.nodes[self.capacity() + leaf])
+    }
+
+    fn set(&mut self, leaf: usize, category: u8) {
+        if leaf >= self.capacity() {
+            self.grow(leaf + 1);
+        }
+        self.leaves = max(self.leaves, leaf + 1);
+
+        let mut i = self.capacity() + leaf;
+        self.nodes[i] = category;
+        while i > 1 {
+            i /= 2;
+            self.nodes[i] = max(self.nodes[2 * i], self.nodes[2 * i + 1]);
+        }
+    }
+
+    //Leftmost leaf with at least the category
+    fn search(&self, category: u8) -> Option<usize> {
+        if self.leaves == 0 || self.nodes[1] < category {
+            return None;
+        }
+
+        let mut i = 1;
+        while i < self.capacity() {
+            i = if self.nodes[2 * i] >= category {
+                2 * i
+            } else {
+                2 * i + 1
+            };
+        }
+        Some(i - self.capacity())
+    }
+
+    fn grow(&mut self, needed: usize) {
+        let old_capacity = self.capacity();
+        let capacity = needed.next_power_of_two();
+
+        let mut nodes = vec![0; capacity * 2];
+        nodes[capacity..capacity + old_capacity].copy_from_slice(&self.nodes[old_capacity..]);
+        for i in (1..capacity).rev() {
+            nodes[i] = max(nodes[2 * i], nodes[2 * i + 1]);
+        }
+        self.nodes = nodes;
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FreeSpaceManagerError {
+    #[error(transparent)]
+    IOManagerError(#[from] IOManagerError),
+    #[error("Free space fork page {0} is missing")]
+    MissingForkPage(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::page_formats::PageSize;
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_tree() {
+        let mut tree = FreeSpaceTree::new();
+        assert_eq!(tree.search(0), None);
+
+        for i in 0..100 {
+            tree.set(i, (i % 10) as u8);
+        }
+        assert_eq!(tree.search(5), Some(5));
+        assert_eq!(tree.search(9), Some(9));
+        assert_eq!(tree.search(10), None);
+
+        tree.set(5, 0);
+        assert_eq!(tree.search(5), Some(6));
+        tree.set(150, 200);
+        assert_eq!(tree.search(10), Some(150));
+        assert_eq!(tree.get(149), Some(0));
+        assert_eq!(tree.get(151), None);
+    }
+
+    #[test]
+    fn test_find_and_reload() -> Result<(), Box<dyn std::error::Error>> {
+        let io_manager = IOManager::initdb(PageSize::Kb4);
+        let table = Arc::new(Table::new("fsm".to_string(), vec![]));
+        for _ in 0..5000 {
+            aw!(io_manager.add_page(table.clone(), Bytes::from(vec![0; 4096])))?;
+        }
+
+        let fsm = FreeSpaceManager::new(io_manager.clone());
+        assert_eq!(aw!(fsm.find_page(table.clone(), 1))?, None);
+
+        aw!(fsm.update(table.clone(), 4500, 2000))?;
+        //Categories round down so this is recorded as 96 bytes
+        aw!(fsm.update(table.clone(), 4200, 100))?;
+        assert_eq!(aw!(fsm.find_page(table.clone(), 96))?, Some(4200));
+        assert_eq!(aw!(fsm.find_page(table.clone(), 97))?, Some(4500));
+        assert_eq!(aw!(fsm.find_page(table.clone(), 2000))?, Some(4500));
+        assert_eq!(aw!(fsm.find_page(table.clone(), 2001))?, None);
+        assert_eq!(aw!(fsm.find_page(table.clone(), 5000))?, None);
+
+        //A new map over the same storage should rebuild from the fork
+        assert_eq!(
+            aw!(io_manager.page_count(table.clone(), ForkNumber::FreeSpace)),
+            2
+        );
+        let reloaded = FreeSpaceManager::new(io_manager);
+        assert_eq!(aw!(reloaded.find_page(table.clone(), 96))?, Some(4200));
+        assert_eq!(aw!(reloaded.find_page(table.clone(), 97))?, Some(4500));
+        Ok(())
+    }
+}
@@ -1,2 +1,6 @@
 mod btree_data;
+pub use btree_data::BTreeBranch;
+pub use btree_data::BTreeLeaf;
 pub use btree_data::BTreeNode;
+pub use btree_data::BTreePage;
+pub use btree_data::NodeType;
@@ -1,39 +1,397 @@
-//! Eventually this will handle reading / writing pages from disk but for now, hashmap + vector!
+//! Every page is kept in a hashmap + vector, when the cluster has a data directory each change is
+//! also written through to it.
 //!
 //! Was stupid with the implementation, should have supported an append api only since vector only works that way
 use async_stream::stream;
 use bytes::Bytes;
 use futures::stream::Stream;
+use log::warn;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::SystemTime;
 use std::vec::Vec;
 use thiserror::Error;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, Mutex, OwnedMutexGuard, RwLock};
 use uuid::Uuid;
 
 use super::super::objects::Table;
+use super::super::transactions::TransactionId;
+use super::page_formats::{PageData, PageSize, UInt15};
+use super::row_formats::{InfoMask, ItemPointer, RowData};
+use super::{
+    BackupFile, BaseBackup, CheckpointKind, CheckpointStats, ClusterState, ControlFile,
+    DataDirectory, DataDirectoryError, RowChange, WalEntry, WalRecord, WriteAheadLogError,
+};
+use crate::constants::{PgErrorCodes, TableDefinitions};
+
+//Made the bootstrap rows, it is always aborted for the transaction manager so they are frozen
+const BOOTSTRAP_TRANSACTION: TransactionId = TransactionId::new(1);
+
+type PageStore = Arc<RwLock<HashMap<(Uuid, ForkNumber), Vec<Bytes>>>>;
+type RelationLocks = Arc<Mutex<HashMap<Uuid, Arc<Mutex<()>>>>>;
 
 #[derive(Clone, Debug)]
 pub struct IOManager {
-    data: Arc<RwLock<HashMap<Uuid, Vec<Bytes>>>>, //Yes this is the naive implementation
+    control_file: ControlFile,
+    data: PageStore, //Yes this is the naive implementation
+    directory: Option<DataDirectory>,
+    //Also held while a checkpoint or background write runs so only one runs at a time
+    checkpoint_stats: Arc<Mutex<CheckpointStats>>,
+    //True while this is a standby, its pages only change by replaying its primary's log
+    recovery: Arc<watch::Sender<bool>>,
+    //Held while a relation's pages are written to disk, so the data is only locked to read or
+    //change the pages in memory and writes to one relation land in the order they are made
+    relation_locks: RelationLocks,
+}
+
+/// Each table is made up of forks, separate sets of pages with their own purpose.
+/// See: https://www.postgresql.org/docs/current/storage-file-layout.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ForkNumber {
+    ///The rows themselves
+    Main,
+    ///How much room each main page has, see the free space manager
+    FreeSpace,
+    ///Which main pages need no visibility checks, see the visibility map
+    VisibilityMap,
+}
+
+impl ForkNumber {
+    pub const VALUES: [ForkNumber; 3] = [
+        ForkNumber::Main,
+        ForkNumber::FreeSpace,
+        ForkNumber::VisibilityMap,
+    ];
+
+    /// How the fork is written in the write ahead log
+    pub fn serialize(self) -> u8 {
+        match self {
+            ForkNumber::Main => 0,
+            ForkNumber::FreeSpace => 1,
+            ForkNumber::VisibilityMap => 2,
+        }
+    }
+
+    pub fn parse(value: u8) -> Option<ForkNumber> {
+        ForkNumber::VALUES.get(usize::from(value)).copied()
+    }
+}
+
+impl Default for IOManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl IOManager {
     pub fn new() -> IOManager {
+        IOManager::initdb(PageSize::default())
+    }
+
+    /// Creates an empty database, the page size can't be changed afterwards
+    pub fn initdb(page_size: PageSize) -> IOManager {
+        IOManager::initdb_with(ControlFile::new(page_size))
+    }
+
+    /// Creates an empty database using every setting of the control file
+    pub fn initdb_with(control_file: ControlFile) -> IOManager {
         IOManager {
-            data: Arc::new(RwLock::new(HashMap::new())),
+            control_file,
+            data: Arc::new(RwLock::new(IOManager::bootstrap(control_file))),
+            directory: None,
+            checkpoint_stats: Arc::new(Mutex::new(CheckpointStats::default())),
+            recovery: Arc::new(watch::channel(false).0),
+            relation_locks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Creates a cluster in a new data directory, see DataDirectory for the layout
+    pub async fn initdb_directory(
+        path: &Path,
+        control_file: ControlFile,
+    ) -> Result<IOManager, IOManagerError> {
+        let relations = IOManager::bootstrap(control_file);
+        let directory = DataDirectory::initdb(path, control_file, &relations).await?;
+        Ok(IOManager {
+            control_file,
+            data: Arc::new(RwLock::new(relations)),
+            directory: Some(directory),
+            checkpoint_stats: Arc::new(Mutex::new(CheckpointStats::default())),
+            recovery: Arc::new(watch::channel(false).0),
+            relation_locks: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Starts using a cluster made by initdb_directory, it is marked in use until shutdown
+    pub async fn open(path: &Path) -> Result<IOManager, IOManagerError> {
+        IOManager::open_with_archive(path, None).await
+    }
+
+    /// Like open, also copying each completed write ahead log segment to the archive directory
+    pub async fn open_with_archive(
+        path: &Path,
+        archive: Option<PathBuf>,
+    ) -> Result<IOManager, IOManagerError> {
+        let (directory, relations) = DataDirectory::open(path, archive).await?;
+
+        let mut control_file = directory.control_file().await;
+        if control_file.state != ClusterState::ShutDown {
+            warn!(
+                "{} was not shut down cleanly, the last changes may be incomplete",
+                path.display()
+            );
+        }
+        control_file.state = ClusterState::InProduction;
+        directory.write_control_file(control_file).await?;
+
+        Ok(IOManager {
+            control_file,
+            data: Arc::new(RwLock::new(relations)),
+            directory: Some(directory),
+            checkpoint_stats: Arc::new(Mutex::new(CheckpointStats::default())),
+            recovery: Arc::new(watch::channel(false).0),
+            relation_locks: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Checkpoints and records that the cluster was stopped cleanly, nothing should be changed
+    /// afterwards. The last write ahead log segment is completed so it is archived, unless this
+    /// is a standby whose log has to keep matching its primary's.
+    pub async fn shutdown(&self) -> Result<(), IOManagerError> {
+        if let Some(directory) = &self.directory {
+            if !self.in_recovery() {
+                directory.wal().switch_segment().await?;
+            }
+        }
+        self.checkpoint(CheckpointKind::Requested).await?;
+        if let Some(directory) = &self.directory {
+            let mut control_file = directory.control_file().await;
+            control_file.state = ClusterState::ShutDown;
+            directory.write_control_file(control_file).await?;
+        }
+        Ok(())
+    }
+
+    /// Flushes every change made so far to disk and records in the control file that redo can
+    /// start from here, the write ahead log before that is removed once archived.
+    ///
+    /// Returns the stats of every checkpoint since startup, including this one.
+    pub async fn checkpoint(
+        &self,
+        kind: CheckpointKind,
+    ) -> Result<CheckpointStats, IOManagerError> {
+        let mut stats = self.checkpoint_stats.lock().await;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        if let Some(directory) = &self.directory {
+            //Every change logged before the redo point has reached its file
+            let redo = directory.wal().redo_point().await;
+            stats.files_synced += directory.sync().await?;
+
+            let mut control_file = directory.control_file().await;
+            control_file.last_checkpoint = now;
+            control_file.checkpoint_lsn = redo;
+            directory.write_control_file(control_file).await?;
+            directory.remove_wal_before(redo).await?;
+        }
+
+        match kind {
+            CheckpointKind::Timed => stats.checkpoints_timed += 1,
+            CheckpointKind::Requested => stats.checkpoints_requested += 1,
+        }
+        stats.last_checkpoint = now;
+        Ok(*stats)
+    }
+
+    /// One round of the background writer, flushing up to the limit of the files written since
+    /// the last checkpoint so the next one has less to do.
+    ///
+    /// Returns the stats since startup, including this round.
+    pub async fn background_write(&self, limit: usize) -> Result<CheckpointStats, IOManagerError> {
+        //Holding the stats keeps a checkpoint from finishing while files are being flushed here
+        let mut stats = self.checkpoint_stats.lock().await;
+        if let Some(directory) = &self.directory {
+            let (cleaned, left) = directory.sync_some(limit).await?;
+            stats.files_cleaned += cleaned;
+            if left && cleaned == limit {
+                stats.maxwritten_clean += 1;
+            }
+        }
+        Ok(*stats)
+    }
+
+    /// Reads a copy of the data directory while changes keep being made, starting with a
+    /// checkpoint so the copy has little to redo
+    pub async fn base_backup(&self) -> Result<(BaseBackup, Vec<BackupFile>), IOManagerError> {
+        let directory = self
+            .directory
+            .as_ref()
+            .ok_or_else(IOManagerError::NoDataDirectory)?;
+        self.checkpoint(CheckpointKind::Requested).await?;
+        Ok(directory.base_backup().await?)
+    }
+
+    /// Makes this a hot standby, from now on pages only change by replay and everything else
+    /// that would change one fails
+    pub async fn start_recovery(&self) -> Result<(), IOManagerError> {
+        if self.directory.is_none() {
+            return Err(IOManagerError::NoDataDirectory());
+        }
+        self.recovery.send_replace(true);
+        Ok(())
+    }
+
+    /// Like postgres' pg_is_in_recovery
+    pub fn in_recovery(&self) -> bool {
+        *self.recovery.borrow()
+    }
+
+    /// Follows whether this is still a standby
+    pub fn watch_recovery(&self) -> watch::Receiver<bool> {
+        self.recovery.subscribe()
+    }
+
+    /// Stops replaying and starts accepting changes, then checkpoints so a crash doesn't redo
+    /// past the promotion
+    pub async fn promote(&self) -> Result<(), IOManagerError> {
+        {
+            //Waits for a replay in progress to finish
+            let _write_lock = self.data.write().await;
+            if !self.in_recovery() {
+                return Err(IOManagerError::NotInRecovery());
+            }
+            self.recovery.send_replace(false);
+        }
+        self.checkpoint(CheckpointKind::Requested).await?;
+        Ok(())
+    }
+
+    /// Makes a change read from the primary's log, both on disk and to the pages in memory
+    pub async fn replay(&self, entry: &WalEntry) -> Result<(), IOManagerError> {
+        let mut write_lock = self.data.write().await;
+        let directory = match (&self.directory, self.in_recovery()) {
+            (Some(d), true) => d,
+            _ => return Err(IOManagerError::NotInRecovery()),
+        };
+        //Logged against files, a drop forgets whose file it was so this is looked up first
+        let owner = match &entry.record {
+            WalRecord::PageImage { relation, .. }
+            | WalRecord::Truncate { relation, .. }
+            | WalRecord::DropRelation { relation } => directory.file_relation(*relation).await,
+            _ => Uuid::nil(),
+        };
+        directory.replay(entry).await?;
+
+        match &entry.record {
+            WalRecord::PageImage {
+                fork,
+                page_num,
+                page,
+                ..
+            } => {
+                let pages = write_lock.entry((owner, *fork)).or_default();
+                if pages.len() <= *page_num {
+                    let empty = Bytes::from(vec![0; self.control_file.page_size.bytes()]);
+                    pages.resize(*page_num + 1, empty);
+                }
+                pages[*page_num] = page.clone();
+            }
+            WalRecord::Truncate { fork, pages, .. } => {
+                if *pages == 0 {
+                    write_lock.remove(&(owner, *fork));
+                } else if let Some(p) = write_lock.get_mut(&(owner, *fork)) {
+                    p.truncate(*pages);
+                }
+            }
+            WalRecord::DropRelation { .. } => {
+                for fork in ForkNumber::VALUES.iter() {
+                    write_lock.remove(&(owner, *fork));
+                }
+            }
+            WalRecord::SwapRelations { left, right, .. } => {
+                IOManager::swap_forks(&mut write_lock, *left, *right)
+            }
+            WalRecord::TransactionStatus { .. }
+            | WalRecord::BackupStart
+            | WalRecord::BackupEnd { .. }
+            | WalRecord::RowChange(_) => {}
+        }
+        Ok(())
+    }
+
+    pub async fn checkpoint_stats(&self) -> CheckpointStats {
+        *self.checkpoint_stats.lock().await
+    }
+
+    /// The data directory the cluster is stored in, if it isn't only in memory
+    pub fn directory(&self) -> Option<&DataDirectory> {
+        self.directory.as_ref()
+    }
+
+    /// The pages of the system tables holding their bootstrap rows.
+    ///
+    /// Like postgres the rows are made by the bootstrap transaction and are frozen, so they are
+    /// visible to everyone without the transaction manager knowing about them.
+    fn bootstrap(control_file: ControlFile) -> HashMap<(Uuid, ForkNumber), Vec<Bytes>> {
+        let mut data = HashMap::new();
+        for definition in TableDefinitions::VALUES.iter() {
+            let table = definition.value();
+            let mut pages = vec![];
+            let mut page = PageData::new(0, control_file.page_size);
+            for user_data in definition.bootstrap_rows() {
+                let mut row = RowData::new(
+                    table.clone(),
+                    BOOTSTRAP_TRANSACTION,
+                    None,
+                    ItemPointer::new(0, UInt15::new(0).unwrap()),
+                    Arc::new(user_data),
+                )
+                .expect("Bootstrap rows must match their table");
+                row.hints = InfoMask::XMIN_FROZEN;
+
+                if !page.can_fit(row.serialize().len()) {
+                    pages.push(page);
+                    page = PageData::new(pages.len(), control_file.page_size);
+                }
+                page.insert(row)
+                    .expect("Bootstrap rows must fit on an empty page");
+            }
+            pages.push(page);
+
+            let serialized = pages
+                .iter_mut()
+                .map(|p| {
+                    p.set_all_visible(true);
+                    p.set_checksum(control_file.data_checksums);
+                    p.serialize()
+                })
+                .collect();
+            data.insert((table.id, ForkNumber::Main), serialized);
         }
+        data
+    }
+
+    pub fn control_file(&self) -> ControlFile {
+        self.control_file
+    }
+
+    pub fn page_size(&self) -> PageSize {
+        self.control_file.page_size
     }
 
     //Extracted the actual logic into its own method so I could implement stream
     async fn get_page_int(
-        d: Arc<RwLock<HashMap<Uuid, Vec<Bytes>>>>,
+        d: PageStore,
         table: Arc<Table>,
+        fork: ForkNumber,
         offset: usize,
     ) -> Option<Bytes> {
         let read_lock = d.read().await;
 
-        let value = read_lock.get(&table.id)?;
+        let value = read_lock.get(&(table.id, fork))?;
 
         let page = value.get(offset)?;
         let copy = page.slice(0..page.len());
@@ -41,57 +399,269 @@ impl IOManager {
     }
 
     pub async fn get_page(&self, table: Arc<Table>, offset: usize) -> Option<Bytes> {
-        IOManager::get_page_int(self.data.clone(), table, offset).await
+        self.get_fork_page(table, ForkNumber::Main, offset).await
+    }
+
+    pub async fn get_fork_page(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
+        offset: usize,
+    ) -> Option<Bytes> {
+        IOManager::get_page_int(self.data.clone(), table, fork, offset).await
+    }
+
+    pub async fn page_count(&self, table: Arc<Table>, fork: ForkNumber) -> usize {
+        let read_lock = self.data.read().await;
+        read_lock.get(&(table.id, fork)).map_or(0, |v| v.len())
     }
 
     pub fn get_stream(&self, table: Arc<Table>) -> impl Stream<Item = Bytes> {
+        self.get_fork_stream(table, ForkNumber::Main)
+    }
+
+    /// Streams the pages as they were when the stream was made, later changes are not seen
+    pub fn get_fork_stream(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
+    ) -> impl Stream<Item = Bytes> {
         let data = self.data.clone();
         stream! {
-            let mut page_num = 0;
-            loop {
-                match IOManager::get_page_int(data.clone(), table.clone(), page_num).await {
-                    Some(p) => {
-                        yield p;
-                    },
-                    None => {
-                        return ();
-                    }
-                }
-                page_num += 1;
+            let pages = data.read().await.get(&(table.id, fork)).cloned().unwrap_or_default();
+            for p in pages {
+                yield p;
             }
         }
     }
 
-    pub async fn add_page(&self, table: Arc<Table>, page: Bytes) {
+    pub async fn add_page(&self, table: Arc<Table>, page: Bytes) -> Result<(), IOManagerError> {
+        self.add_fork_page(table, ForkNumber::Main, page).await
+    }
+
+    pub async fn add_fork_page(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
+        page: Bytes,
+    ) -> Result<(), IOManagerError> {
+        self.add_written_page(table, fork, page, true).await
+    }
+
+    /// Adds a page without logging it, only for forks whose contents are safe to lose
+    pub async fn add_fork_page_unlogged(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
+        page: Bytes,
+    ) -> Result<(), IOManagerError> {
+        self.add_written_page(table, fork, page, false).await
+    }
+
+    pub async fn update_page(
+        &self,
+        table: Arc<Table>,
+        page: Bytes,
+        offset: usize,
+    ) -> Result<(), IOManagerError> {
+        self.update_fork_page(table, ForkNumber::Main, page, offset)
+            .await
+    }
+
+    pub async fn update_fork_page(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
+        page: Bytes,
+        offset: usize,
+    ) -> Result<(), IOManagerError> {
+        self.update_written_page(table, fork, page, offset, true)
+            .await
+    }
+
+    /// Replaces a page without logging it, like postgres' MarkBufferDirtyHint. Only for changes
+    /// that are safe to lose such as hint bits, a crash can leave either version on disk.
+    pub async fn update_page_unlogged(
+        &self,
+        table: Arc<Table>,
+        page: Bytes,
+        offset: usize,
+    ) -> Result<(), IOManagerError> {
+        self.update_fork_page_unlogged(table, ForkNumber::Main, page, offset)
+            .await
+    }
+
+    pub async fn update_fork_page_unlogged(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
+        page: Bytes,
+        offset: usize,
+    ) -> Result<(), IOManagerError> {
+        self.update_written_page(table, fork, page, offset, false)
+            .await
+    }
+
+    /// Every relation that has pages in the fork, with the pages as they are right now
+    pub async fn fork_snapshot(&self, fork: ForkNumber) -> Vec<(Uuid, Vec<Bytes>)> {
+        let read_lock = self.data.read().await;
+        read_lock
+            .iter()
+            .filter(|((_, f), _)| *f == fork)
+            .map(|((id, _), pages)| (*id, pages.clone()))
+            .collect()
+    }
+
+    /// Exchanges every fork of the two relations in one step. On disk only the filenode map
+    /// changes, so nothing is copied while the lock is held and a failure changes nothing.
+    pub async fn swap_relations(
+        &self,
+        left: Arc<Table>,
+        right: Arc<Table>,
+    ) -> Result<(), IOManagerError> {
+        //Always in the same order so two swaps can't wait on each other
+        let (first, second) = if left.id < right.id {
+            (left.id, right.id)
+        } else {
+            (right.id, left.id)
+        };
+        let _first = self.lock_relation(first).await;
+        let _second = self.lock_relation(second).await;
+        let mut write_lock = self.data.write().await;
+        self.check_writable()?;
+        if let Some(directory) = &self.directory {
+            directory.swap_relations(left.id, right.id).await?;
+        }
+        IOManager::swap_forks(&mut write_lock, left.id, right.id);
+        Ok(())
+    }
+
+    /// Removes every fork of the relation
+    pub async fn drop_relation(&self, table: Arc<Table>) -> Result<(), IOManagerError> {
+        let _relation = self.lock_relation(table.id).await;
         let mut write_lock = self.data.write().await;
+        self.check_writable()?;
+        if let Some(directory) = &self.directory {
+            directory.drop_relation(table.id).await?;
+        }
 
-        match write_lock.get_mut(&table.id) {
-            Some(v) => v.push(page),
-            None => {
-                let vec_holder = vec![page];
-                write_lock.insert(table.id, vec_holder);
-            }
+        for fork in ForkNumber::VALUES.iter() {
+            write_lock.remove(&(table.id, *fork));
         }
+        self.relation_locks.lock().await.remove(&table.id);
+        Ok(())
     }
 
-    pub async fn update_page(
+    /// Logs a row change for logical decoding, only a cluster with a write ahead log keeps them
+    pub async fn log_row_change(&self, change: RowChange) -> Result<(), IOManagerError> {
+        self.check_writable()?;
+        if let Some(directory) = &self.directory {
+            directory
+                .wal()
+                .append(&WalRecord::RowChange(change))
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub fn logs_row_changes(&self) -> bool {
+        self.directory.is_some()
+    }
+
+    /// Replication slots are kept with the log, so only a cluster with a data directory has
+    /// them and a standby can't make or decode from one
+    pub fn slot_directory(&self) -> Result<&DataDirectory, IOManagerError> {
+        self.check_writable()?;
+        self.directory
+            .as_ref()
+            .ok_or_else(IOManagerError::NoDataDirectory)
+    }
+
+    //Written to disk before the page is in memory, so a failed write changes nothing and the
+    //data isn't locked while the log is flushed
+    async fn add_written_page(
         &self,
         table: Arc<Table>,
+        fork: ForkNumber,
+        page: Bytes,
+        logged: bool,
+    ) -> Result<(), IOManagerError> {
+        let _relation = self.lock_relation(table.id).await;
+        let page_num = {
+            let read_lock = self.data.read().await;
+            self.check_writable()?;
+            read_lock.get(&(table.id, fork)).map_or(0, |p| p.len())
+        };
+        if let Some(directory) = &self.directory {
+            directory
+                .write_page(table.id, fork, page_num, &page, logged)
+                .await?;
+        }
+
+        let mut write_lock = self.data.write().await;
+        write_lock.entry((table.id, fork)).or_default().push(page);
+        Ok(())
+    }
+
+    async fn update_written_page(
+        &self,
+        table: Arc<Table>,
+        fork: ForkNumber,
         page: Bytes,
         offset: usize,
+        logged: bool,
     ) -> Result<(), IOManagerError> {
+        let _relation = self.lock_relation(table.id).await;
+        {
+            let read_lock = self.data.read().await;
+            self.check_writable()?;
+            match read_lock.get(&(table.id, fork)) {
+                None => return Err(IOManagerError::NoSuchTable(table.name.clone())),
+                Some(p) if p.len() <= offset => return Err(IOManagerError::InvalidPage(offset)),
+                Some(_) => {}
+            }
+        }
+        if let Some(directory) = &self.directory {
+            directory
+                .write_page(table.id, fork, offset, &page, logged)
+                .await?;
+        }
+
         let mut write_lock = self.data.write().await;
+        if let Some(p) = write_lock.get_mut(&(table.id, fork)) {
+            p[offset] = page;
+        }
+        Ok(())
+    }
 
-        let value = write_lock.get_mut(&table.id);
-        if value.is_none() {
-            return Err(IOManagerError::NoSuchTable(table.name.clone()));
+    async fn lock_relation(&self, id: Uuid) -> OwnedMutexGuard<()> {
+        let lock = self
+            .relation_locks
+            .lock()
+            .await
+            .entry(id)
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    fn swap_forks(data: &mut HashMap<(Uuid, ForkNumber), Vec<Bytes>>, left: Uuid, right: Uuid) {
+        for fork in ForkNumber::VALUES.iter() {
+            let left_pages = data.remove(&(left, *fork));
+            let right_pages = data.remove(&(right, *fork));
+            if let Some(p) = left_pages {
+                data.insert((right, *fork), p);
+            }
+            if let Some(p) = right_pages {
+                data.insert((left, *fork), p);
+            }
         }
+    }
 
-        let existing_value = value.unwrap();
-        if existing_value.len() < offset {
-            return Err(IOManagerError::InvalidPage(offset));
+    fn check_writable(&self) -> Result<(), IOManagerError> {
+        if self.in_recovery() {
+            return Err(IOManagerError::InRecovery());
         }
-        existing_value[offset] = page;
         Ok(())
     }
 }
@@ -102,14 +672,36 @@ pub enum IOManagerError {
     NoSuchTable(String),
     #[error("Invalid Page number {0}")]
     InvalidPage(usize),
+    #[error("The cluster is only kept in memory")]
+    NoDataDirectory(),
+    #[error("Cannot change anything during recovery, this is a standby")]
+    InRecovery(),
+    #[error("Recovery is not in progress, this is not a standby")]
+    NotInRecovery(),
+    #[error(transparent)]
+    DataDirectoryError(#[from] DataDirectoryError),
+    #[error(transparent)]
+    WriteAheadLogError(#[from] WriteAheadLogError),
+}
+
+impl IOManagerError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            IOManagerError::NoDataDirectory() => PgErrorCodes::FeatureNotSupported,
+            IOManagerError::DataDirectoryError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     #![allow(unused_must_use)]
     use super::super::super::objects::Table;
+    use super::super::{WriteAheadLog, SEGMENT_SIZE};
     use super::*;
     use bytes::{BufMut, BytesMut};
+    use futures::stream::StreamExt;
 
     //Async testing help can be found here: https://blog.x5ff.xyz/blog/async-tests-tokio-rust/
     macro_rules! aw {
@@ -156,4 +748,116 @@ mod tests {
         assert_eq!(buf_2.clone(), check_2.clone());
         assert_ne!(buf_1.clone(), check_2.clone());
     }
+
+    #[test]
+    fn test_stream_snapshot() {
+        let pm = IOManager::new();
+        let table = Arc::new(Table::new("test".to_string(), Vec::new()));
+        aw!(pm.add_page(table.clone(), get_bytes(1)));
+
+        let stream = pm.get_stream(table.clone());
+        futures::pin_mut!(stream);
+        let first = aw!(stream.next());
+
+        aw!(pm.add_page(table.clone(), get_bytes(2)));
+        assert_eq!(first, Some(get_bytes(1)));
+        assert_eq!(aw!(stream.next()), None);
+    }
+
+    #[test]
+    fn test_write_locks_relation() {
+        let pm = IOManager::new();
+        let busy = Arc::new(Table::new("busy".to_string(), Vec::new()));
+        let other = Arc::new(Table::new("other".to_string(), Vec::new()));
+        aw!(pm.add_page(busy.clone(), get_bytes(1)));
+
+        //While one relation is being written the rest can still be read and changed
+        let relation = aw!(pm.lock_relation(busy.id));
+        assert!(pm.data.try_write().is_ok());
+        aw!(pm.add_page(other.clone(), get_bytes(2)));
+        assert_eq!(aw!(pm.get_page(busy.clone(), 0)), Some(get_bytes(1)));
+        assert_eq!(aw!(pm.get_page(other, 0)), Some(get_bytes(2)));
+        drop(relation);
+
+        aw!(pm.update_page(busy.clone(), get_bytes(3), 0));
+        assert_eq!(aw!(pm.get_page(busy, 0)), Some(get_bytes(3)));
+    }
+
+    #[test]
+    fn test_swap_drop() {
+        let pm = IOManager::new();
+        let left = Arc::new(Table::new("left".to_string(), Vec::new()));
+        let right = Arc::new(Table::new("right".to_string(), Vec::new()));
+
+        aw!(pm.add_page(left.clone(), get_bytes(1)));
+        aw!(pm.add_fork_page(left.clone(), ForkNumber::FreeSpace, get_bytes(2)));
+        aw!(pm.add_page(right.clone(), get_bytes(3)));
+
+        aw!(pm.swap_relations(left.clone(), right.clone()));
+        assert_eq!(aw!(pm.get_page(left.clone(), 0)), Some(get_bytes(3)));
+        assert_eq!(aw!(pm.get_page(right.clone(), 0)), Some(get_bytes(1)));
+        assert_eq!(aw!(pm.page_count(left.clone(), ForkNumber::FreeSpace)), 0);
+        assert_eq!(
+            aw!(pm.get_fork_page(right.clone(), ForkNumber::FreeSpace, 0)),
+            Some(get_bytes(2))
+        );
+
+        let snapshot = aw!(pm.fork_snapshot(ForkNumber::FreeSpace));
+        assert_eq!(snapshot, vec![(right.id, vec![get_bytes(2)])]);
+
+        aw!(pm.drop_relation(right.clone()));
+        assert_eq!(aw!(pm.page_count(right.clone(), ForkNumber::Main)), 0);
+        assert_eq!(aw!(pm.page_count(left, ForkNumber::Main)), 1);
+    }
+
+    #[test]
+    fn test_swap_fails() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("data");
+        let pm = aw!(IOManager::initdb_directory(
+            &path,
+            ControlFile::new(PageSize::Kb4)
+        ))?;
+        let left = Arc::new(Table::new("left".to_string(), Vec::new()));
+        let right = Arc::new(Table::new("right".to_string(), Vec::new()));
+        aw!(pm.add_page(left.clone(), get_bytes(1)))?;
+        aw!(pm.add_fork_page(left.clone(), ForkNumber::FreeSpace, get_bytes(2)))?;
+        aw!(pm.add_page(right.clone(), get_bytes(3)))?;
+
+        //Fills the log's segment so the swap's record needs the next one, which can't be made
+        let wal = pm.directory().unwrap().wal();
+        let empty = WalRecord::PageImage {
+            relation: Uuid::new_v4(),
+            fork: ForkNumber::Main,
+            page_num: 0,
+            page: Bytes::new(),
+        };
+        let start = aw!(wal.end_lsn());
+        aw!(wal.append(&empty))?;
+        let header = aw!(wal.end_lsn()) - start;
+        let left_over = SEGMENT_SIZE - aw!(wal.end_lsn()) % SEGMENT_SIZE;
+        aw!(wal.append(&WalRecord::PageImage {
+            relation: Uuid::new_v4(),
+            fork: ForkNumber::Main,
+            page_num: 0,
+            page: Bytes::from(vec![0; (left_over - header - 1) as usize]),
+        }))?;
+        let segment = aw!(wal.end_lsn()) / SEGMENT_SIZE;
+        std::fs::create_dir(WriteAheadLog::segment_path(
+            &path.join("pg_wal"),
+            segment + 1,
+        ))?;
+
+        assert!(aw!(pm.swap_relations(left.clone(), right.clone())).is_err());
+        assert_eq!(aw!(pm.get_page(left.clone(), 0)), Some(get_bytes(1)));
+        assert_eq!(
+            aw!(pm.get_fork_page(left.clone(), ForkNumber::FreeSpace, 0)),
+            Some(get_bytes(2))
+        );
+        assert_eq!(aw!(pm.get_page(right.clone(), 0)), Some(get_bytes(3)));
+        let directory = pm.directory().unwrap();
+        assert_eq!(aw!(directory.relation_file(left.id)), left.id);
+        assert_eq!(aw!(directory.relation_file(right.id)), right.id);
+        Ok(())
+    }
 }
@@ -0,0 +1,85 @@
+//! Relation level locks. Anything changing a relation's pages holds its lock shared for the length
+//! of the change, repack holds it exclusive while it swaps the relation's storage.
+//!
+//! Scans don't lock, they read the pages as they were when the scan started. Writing back hint
+//! bits takes the lock exclusive but only if it is free, otherwise the hints are dropped.
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Default)]
+pub struct LockManager {
+    locks: Arc<RwLock<HashMap<Uuid, Arc<RwLock<()>>>>>,
+}
+
+impl LockManager {
+    pub fn new() -> LockManager {
+        LockManager::default()
+    }
+
+    pub async fn shared(&self, relation: Uuid) -> OwnedRwLockReadGuard<()> {
+        self.get_lock(relation).await.read_owned().await
+    }
+
+    pub async fn exclusive(&self, relation: Uuid) -> OwnedRwLockWriteGuard<()> {
+        self.get_lock(relation).await.write_owned().await
+    }
+
+    /// The exclusive lock if nothing else holds the relation's lock right now
+    pub async fn try_exclusive(&self, relation: Uuid) -> Option<OwnedRwLockWriteGuard<()>> {
+        self.get_lock(relation).await.try_write_owned().ok()
+    }
+
+    async fn get_lock(&self, relation: Uuid) -> Arc<RwLock<()>> {
+        if let Some(l) = self.locks.read().await.get(&relation) {
+            return l.clone();
+        }
+        self.locks
+            .write()
+            .await
+            .entry(relation)
+            .or_default()
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_exclusive_waits() {
+        aw!(async {
+            let lm = LockManager::new();
+            let relation = Uuid::new_v4();
+
+            let shared_1 = lm.shared(relation).await;
+            let shared_2 = lm.shared(relation).await;
+            let _other = lm.exclusive(Uuid::new_v4()).await;
+
+            let lm_2 = lm.clone();
+            let exclusive = tokio::spawn(async move {
+                lm_2.exclusive(relation).await;
+            });
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(!exclusive.is_finished());
+            drop(shared_1);
+            drop(shared_2);
+            exclusive.await.unwrap();
+
+            let shared = lm.shared(relation).await;
+            assert!(lm.try_exclusive(relation).await.is_none());
+            drop(shared);
+            assert!(lm.try_exclusive(relation).await.is_some());
+        });
+    }
+}
@@ -0,0 +1,294 @@
+//! Turns the write ahead log back into the transactions that committed, like postgres' logical
+//! decoding. See: https://www.postgresql.org/docs/current/logicaldecoding-explanation.html
+//!
+//! Row changes are held per transaction until its commit record is read, then handed out in
+//! commit order with every change it made. Aborted transactions are thrown away. A slot only
+//! decodes transactions that started after it was made, the log is its consistent point.
+//!
+//! Each decoded transaction says where decoding would have to restart to see it again, the
+//! start of the oldest transaction still open when it committed. Confirming a transaction moves
+//! the slot to that restart point so a transaction spanning the confirmation isn't lost.
+use super::{
+    ReplicationSlot, RowChange, RowChangeKind, WalRecord, WriteAheadLog, WriteAheadLogError,
+};
+use crate::engine::transactions::{TransactionId, TransactionManager, TransactionStatus};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedTransaction {
+    pub tran_id: TransactionId,
+    ///Where the transaction's start was logged
+    pub begin_lsn: u64,
+    ///Where the commit was logged
+    pub commit_lsn: u64,
+    ///Just past the commit, once a consumer has this the transaction is confirmed
+    pub end_lsn: u64,
+    pub committed: SystemTime,
+    ///Each change in the order it was made with where it was logged
+    pub changes: Vec<(u64, RowChange)>,
+    ///Where a slot has to restart decoding after confirming this transaction
+    pub restart_lsn: u64,
+}
+
+impl DecodedTransaction {
+    /// The transaction the way the test_decoding plugin writes it, an LSN and line for the
+    /// begin, each change and the commit
+    pub fn test_decoding(&self) -> Vec<(u64, String)> {
+        let tran_id = self.tran_id.get_u64();
+        let mut lines = vec![(self.begin_lsn, format!("BEGIN {}", tran_id))];
+        for (lsn, change) in self.changes.iter() {
+            lines.push((*lsn, DecodedTransaction::test_decoding_change(change)));
+        }
+        lines.push((self.commit_lsn, format!("COMMIT {}", tran_id)));
+        lines
+    }
+
+    fn test_decoding_change(change: &RowChange) -> String {
+        let mut line = format!("table public.{}: ", change.relation_name);
+        match change.kind {
+            RowChangeKind::Insert => line.push_str("INSERT:"),
+            RowChangeKind::Update => line.push_str("UPDATE: old-key:"),
+            RowChangeKind::Delete => line.push_str("DELETE:"),
+        }
+        let rows = match change.kind {
+            RowChangeKind::Insert => vec![&change.new],
+            RowChangeKind::Update => vec![&change.old, &change.new],
+            RowChangeKind::Delete => vec![&change.old],
+        };
+        for (i, row) in rows.into_iter().enumerate() {
+            if i > 0 {
+                line.push_str(" new-tuple:");
+            }
+            let values = match row {
+                Some(v) => v,
+                None => continue,
+            };
+            for (column, value) in change.columns.iter().zip(values.iter()) {
+                line.push_str(&format!(" {}[{}]:", column.name, column.type_name));
+                match value {
+                    None => line.push_str("null"),
+                    Some(v) => match column.type_oid {
+                        //bool
+                        16 => line.push_str(if v == "t" { "true" } else { "false" }),
+                        //Numbers aren't quoted
+                        20 | 21 | 23 | 700 | 701 | 1700 => line.push_str(v),
+                        _ => line.push_str(&format!("'{}'", v.replace('\'', "''"))),
+                    },
+                }
+            }
+        }
+        line
+    }
+}
+
+pub struct LogicalDecoder {
+    ///Transactions that have started but not finished by their id, with where they started
+    open: BTreeMap<u64, (u64, Vec<(u64, RowChange)>)>,
+    ///Where the next read of the log starts
+    read: u64,
+}
+
+impl LogicalDecoder {
+    /// Decodes from the slot's restart point, the transactions the slot has already confirmed
+    /// are still returned so the caller has to skip them
+    pub fn new(slot: &ReplicationSlot) -> LogicalDecoder {
+        LogicalDecoder {
+            open: BTreeMap::new(),
+            read: slot.restart_lsn,
+        }
+    }
+
+    /// The transactions that committed in the log flushed since the last call, in commit order
+    pub async fn decode(
+        &mut self,
+        wal: &WriteAheadLog,
+        tran_manager: &mut TransactionManager,
+    ) -> Result<Vec<DecodedTransaction>, WriteAheadLogError> {
+        let mut decoded = vec![];
+        for entry in wal.read_since(self.read).await? {
+            self.read = entry.end();
+            match entry.record {
+                WalRecord::TransactionStatus { tran_id, status } => match status {
+                    TransactionStatus::InProgress => {
+                        self.open.insert(tran_id.get_u64(), (entry.lsn, vec![]));
+                    }
+                    TransactionStatus::Aborted => {
+                        self.open.remove(&tran_id.get_u64());
+                    }
+                    TransactionStatus::Commited => {
+                        //Started before the slot existed
+                        let (begin_lsn, changes) = match self.open.remove(&tran_id.get_u64()) {
+                            Some(o) => o,
+                            None => continue,
+                        };
+                        self.remove_crashed(tran_manager).await;
+                        let restart_lsn = self
+                            .open
+                            .values()
+                            .map(|(begin, _)| *begin)
+                            .min()
+                            .unwrap_or_else(|| entry.end());
+                        decoded.push(DecodedTransaction {
+                            tran_id,
+                            begin_lsn,
+                            commit_lsn: entry.lsn,
+                            end_lsn: entry.end(),
+                            committed: entry.written,
+                            changes,
+                            restart_lsn,
+                        });
+                    }
+                },
+                WalRecord::RowChange(change) => {
+                    if let Some((_, changes)) = self.open.get_mut(&change.tran_id.get_u64()) {
+                        changes.push((entry.lsn, change));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(decoded)
+    }
+
+    //A crash aborts whatever was running without logging it, so they would hold the restart
+    //point back forever
+    async fn remove_crashed(&mut self, tran_manager: &mut TransactionManager) {
+        let mut crashed = vec![];
+        for tran_id in self.open.keys() {
+            if let Ok(TransactionStatus::Aborted) =
+                tran_manager.get_status(TransactionId::new(*tran_id)).await
+            {
+                crashed.push(*tran_id);
+            }
+        }
+        for tran_id in crashed {
+            self.open.remove(&tran_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{BuiltinSqlTypes, DeserializeTypes, Nullable};
+    use crate::engine::objects::{Attribute, SqlTuple, Table};
+    use uuid::Uuid;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn status(tran_id: u64, status: TransactionStatus) -> WalRecord {
+        WalRecord::TransactionStatus {
+            tran_id: TransactionId::new(tran_id),
+            status,
+        }
+    }
+
+    fn change(
+        tran_id: u64,
+        kind: RowChangeKind,
+        old: Option<&str>,
+        new: Option<&str>,
+    ) -> WalRecord {
+        let id = Uuid::new_v4();
+        let table = Table::new_existing(
+            id,
+            "people".to_string(),
+            vec![
+                Attribute::new(
+                    id,
+                    "name".to_string(),
+                    DeserializeTypes::Text,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    id,
+                    "age".to_string(),
+                    DeserializeTypes::Integer,
+                    Nullable::Null,
+                ),
+            ],
+        );
+        let row = |name: &str| {
+            SqlTuple(vec![
+                Some(BuiltinSqlTypes::Text(name.to_string())),
+                Some(BuiltinSqlTypes::Integer(42)),
+            ])
+        };
+        let old = old.map(row);
+        let new = new.map(row);
+        WalRecord::RowChange(RowChange::new(
+            kind,
+            TransactionId::new(tran_id),
+            &table,
+            old.as_ref(),
+            new.as_ref(),
+        ))
+    }
+
+    #[test]
+    fn test_logical_decoding() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let wal = aw!(WriteAheadLog::create(tmp.path()))?;
+        let mut tm = TransactionManager::new();
+
+        //Started before the slot so it is never decoded
+        aw!(wal.append(&status(1, TransactionStatus::InProgress)))?;
+        let slot = ReplicationSlot::new("test", "test_decoding", aw!(wal.end_lsn()))?;
+        let mut decoder = LogicalDecoder::new(&slot);
+        aw!(wal.append(&change(1, RowChangeKind::Insert, None, Some("Early"))))?;
+
+        let begin = aw!(wal.append(&status(2, TransactionStatus::InProgress)))?;
+        aw!(wal.append(&status(3, TransactionStatus::InProgress)))?;
+        aw!(wal.append(&change(2, RowChangeKind::Insert, None, Some("O'Brien"))))?;
+        aw!(wal.append(&change(3, RowChangeKind::Insert, None, Some("Aborted"))))?;
+        aw!(wal.append(&status(1, TransactionStatus::Commited)))?;
+        aw!(wal.append(&status(3, TransactionStatus::Aborted)))?;
+        let late_begin = aw!(wal.append(&status(4, TransactionStatus::InProgress)))?;
+        aw!(wal.append(&change(
+            2,
+            RowChangeKind::Update,
+            Some("O'Brien"),
+            Some("Bob")
+        )))?;
+        aw!(wal.append(&change(2, RowChangeKind::Delete, Some("Bob"), None)))?;
+        let commit = aw!(wal.append(&status(2, TransactionStatus::Commited)))?;
+        aw!(wal.flush())?;
+
+        let decoded = aw!(decoder.decode(&wal, &mut tm))?;
+        assert_eq!(decoded.len(), 1);
+        let tran = &decoded[0];
+        assert_eq!(tran.tran_id, TransactionId::new(2));
+        assert_eq!(tran.begin_lsn, begin);
+        assert_eq!(tran.commit_lsn, commit);
+        //Transaction 4 is still open
+        assert_eq!(tran.restart_lsn, late_begin);
+
+        let lines: Vec<String> = tran.test_decoding().into_iter().map(|(_, l)| l).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "BEGIN 2".to_string(),
+                "table public.people: INSERT: name[text]:'O''Brien' age[integer]:42".to_string(),
+                "table public.people: UPDATE: old-key: name[text]:'O''Brien' age[integer]:42 new-tuple: name[text]:'Bob' age[integer]:42".to_string(),
+                "table public.people: DELETE: name[text]:'Bob' age[integer]:42".to_string(),
+                "COMMIT 2".to_string(),
+            ]
+        );
+
+        //Nothing new
+        assert_eq!(aw!(decoder.decode(&wal, &mut tm))?, vec![]);
+
+        aw!(wal.append(&status(4, TransactionStatus::Commited)))?;
+        aw!(wal.flush())?;
+        let decoded = aw!(decoder.decode(&wal, &mut tm))?;
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].changes, vec![]);
+        assert_eq!(decoded[0].restart_lsn, decoded[0].end_lsn);
+        Ok(())
+    }
+}
@@ -1,15 +1,22 @@
 mod item_id_data;
 pub use item_id_data::ItemIdData;
 pub use item_id_data::ItemIdDataError;
+pub use item_id_data::ItemIdFlags;
 
 mod page_data;
 pub use page_data::PageData;
 pub use page_data::PageDataError;
 
 mod page_header;
+pub use page_header::PageFlags;
 pub use page_header::PageHeader;
 pub use page_header::PageHeaderError;
+pub use page_header::PAGE_LAYOUT_VERSION;
 
-mod uint12;
-pub use uint12::UInt12;
-pub use uint12::UInt12Error;
+mod page_size;
+pub use page_size::PageSize;
+pub use page_size::PageSizeError;
+
+mod uint15;
+pub use uint15::UInt15;
+pub use uint15::UInt15Error;
@@ -1,44 +1,113 @@
 //! Pointer type to indicate where an item is inside a page
 //! See here for doc: https://www.postgresql.org/docs/current/storage-page-layout.html
-use super::UInt12;
+use super::UInt15;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::mem::size_of;
 use std::ops::Range;
 use thiserror::Error;
 
+/// Stored the same way it is on disk, the high bits of offset and length hold the flags like
+/// postgres' lp_flags.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ItemIdData {
-    offset: UInt12,
-    pub length: UInt12,
+    offset: u16,
+    length: u16,
 }
 
+/// The state of a line pointer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ItemIdFlags {
+    ///Free to be used by the next insert
+    Unused,
+    ///Points at a row
+    Normal,
+    ///Stands in for the dead start of a HOT chain, the offset is the line pointer the chain now starts at
+    Redirect,
+    ///The row is gone but something may still point here, can't be reused until that is cleaned up
+    Dead,
+}
+
+const FLAG_BIT: u16 = 0x8000;
+
 impl ItemIdData {
-    pub fn new(offset: UInt12, length: UInt12) -> ItemIdData {
-        ItemIdData { offset, length }
+    pub fn new(offset: UInt15, length: UInt15) -> ItemIdData {
+        ItemIdData::with_flags(offset, length, ItemIdFlags::Normal)
+    }
+
+    pub fn unused() -> ItemIdData {
+        ItemIdData::with_flags(
+            UInt15::new(0).unwrap(),
+            UInt15::new(0).unwrap(),
+            ItemIdFlags::Unused,
+        )
+    }
+
+    pub fn redirect(target: UInt15) -> ItemIdData {
+        ItemIdData::with_flags(target, UInt15::new(0).unwrap(), ItemIdFlags::Redirect)
+    }
+
+    pub fn dead() -> ItemIdData {
+        ItemIdData::with_flags(
+            UInt15::new(0).unwrap(),
+            UInt15::new(0).unwrap(),
+            ItemIdFlags::Dead,
+        )
+    }
+
+    fn with_flags(offset: UInt15, length: UInt15, flags: ItemIdFlags) -> ItemIdData {
+        let (offset_flag, length_flag) = match flags {
+            ItemIdFlags::Unused => (0, 0),
+            ItemIdFlags::Normal => (0, FLAG_BIT),
+            ItemIdFlags::Redirect => (FLAG_BIT, 0),
+            ItemIdFlags::Dead => (FLAG_BIT, FLAG_BIT),
+        };
+        ItemIdData {
+            offset: offset.to_u16() | offset_flag,
+            length: length.to_u16() | length_flag,
+        }
+    }
+
+    pub fn flags(&self) -> ItemIdFlags {
+        match (self.offset & FLAG_BIT != 0, self.length & FLAG_BIT != 0) {
+            (false, false) => ItemIdFlags::Unused,
+            (false, true) => ItemIdFlags::Normal,
+            (true, false) => ItemIdFlags::Redirect,
+            (true, true) => ItemIdFlags::Dead,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        (self.length & !FLAG_BIT) as usize
+    }
+
+    /// Where a redirect sends the chain
+    pub fn redirect_target(&self) -> Option<UInt15> {
+        match self.flags() {
+            ItemIdFlags::Redirect => UInt15::new(self.offset & !FLAG_BIT),
+            _ => None,
+        }
     }
 
     pub fn get_range(&self) -> Range<usize> {
-        let offset_usize = self.offset.to_u16() as usize;
-        let length_usize = self.length.to_u16() as usize;
-        offset_usize..(offset_usize + length_usize)
+        let offset_usize = (self.offset & !FLAG_BIT) as usize;
+        offset_usize..(offset_usize + self.length())
     }
 
     pub fn serialize(&self) -> Bytes {
         let mut buf = BytesMut::with_capacity(size_of::<ItemIdData>());
-        buf.put_u16_le(self.offset.to_u16());
-        buf.put_u16_le(self.length.to_u16());
+        buf.put_u16_le(self.offset);
+        buf.put_u16_le(self.length);
         buf.freeze()
     }
 
     pub fn parse(buffer: &mut impl Buf) -> Result<Self, ItemIdDataError> {
-        if buffer.remaining() < 4 {
+        if buffer.remaining() < size_of::<ItemIdData>() {
             return Err(ItemIdDataError::InsufficentData(buffer.remaining()));
         }
-        let offset =
-            UInt12::new(buffer.get_u16_le()).ok_or_else(ItemIdDataError::OffsetTooLarge)?;
-        let length =
-            UInt12::new(buffer.get_u16_le()).ok_or_else(ItemIdDataError::LengthTooLarge)?;
-        Ok(ItemIdData { offset, length })
+        Ok(ItemIdData {
+            offset: buffer.get_u16_le(),
+            length: buffer.get_u16_le(),
+        })
     }
 }
 
@@ -46,10 +115,6 @@ impl ItemIdData {
 pub enum ItemIdDataError {
     #[error("Not enough data has {0} bytes")]
     InsufficentData(usize),
-    #[error("Offset is too large")]
-    OffsetTooLarge(),
-    #[error("Length is too large")]
-    LengthTooLarge(),
 }
 
 #[cfg(test)]
@@ -58,11 +123,55 @@ mod tests {
 
     #[test]
     fn test_roundtrip() {
-        let test = ItemIdData::new(UInt12::new(1).unwrap(), UInt12::new(2).unwrap());
+        let test = ItemIdData::new(UInt15::new(1).unwrap(), UInt15::new(2).unwrap());
         let mut test_serial = test.serialize();
         let test_rt = ItemIdData::parse(&mut test_serial).unwrap();
 
-        let test_new = ItemIdData::new(UInt12::new(1).unwrap(), UInt12::new(2).unwrap());
+        let test_new = ItemIdData::new(UInt15::new(1).unwrap(), UInt15::new(2).unwrap());
         assert_eq!(test_rt, test_new);
     }
+
+    #[test]
+    fn test_flags_roundtrip() {
+        for test in [
+            ItemIdData::unused(),
+            ItemIdData::new(UInt15::new(0x7FFF).unwrap(), UInt15::new(0x7FFF).unwrap()),
+            ItemIdData::redirect(UInt15::new(12).unwrap()),
+            ItemIdData::dead(),
+        ] {
+            let mut test_serial = test.serialize();
+            let test_rt = ItemIdData::parse(&mut test_serial).unwrap();
+            assert_eq!(test_rt, test);
+        }
+
+        assert_eq!(
+            ItemIdData::redirect(UInt15::new(12).unwrap()).redirect_target(),
+            UInt15::new(12)
+        );
+        assert_eq!(ItemIdData::dead().redirect_target(), None);
+        assert_eq!(size_of::<ItemIdData>(), 4);
+
+        //A page of zeros is all unused
+        let mut zeros = Bytes::from_static(&[0, 0, 0, 0]);
+        assert_eq!(ItemIdData::parse(&mut zeros).unwrap(), ItemIdData::unused());
+    }
+
+    #[test]
+    fn test_every_page_size() {
+        use super::super::PageSize;
+        use std::convert::TryFrom;
+
+        //A single item filling everything after the header
+        for page_size in PageSize::VALUES.iter() {
+            let length = page_size.bytes() - 8;
+            let test = ItemIdData::new(
+                UInt15::try_from(page_size.bytes() - length).unwrap(),
+                UInt15::try_from(length).unwrap(),
+            );
+            let mut test_serial = test.serialize();
+            let test_rt = ItemIdData::parse(&mut test_serial).unwrap();
+            assert_eq!(test_rt, test);
+            assert_eq!(test_rt.get_range(), 8..page_size.bytes());
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use super::super::super::objects::Table;
 use super::super::row_formats::{ItemPointer, RowData, RowDataError};
-use super::{ItemIdData, ItemIdDataError, PageHeader, PageHeaderError, UInt12, UInt12Error};
+use super::{
+    ItemIdData, ItemIdDataError, ItemIdFlags, PageFlags, PageHeader, PageHeaderError, PageSize,
+    PageSizeError, UInt15, UInt15Error,
+};
 use async_stream::stream;
 use bytes::{BufMut, Bytes, BytesMut};
 use futures::stream::Stream;
@@ -11,70 +14,262 @@ use thiserror::Error;
 
 pub struct PageData {
     page: usize,
+    page_size: PageSize,
     page_header: PageHeader,
     item_ids: Vec<ItemIdData>,
     //TODO debating if I should defer parsing until later
-    rows: Vec<RowData>,
+    //Only normal line pointers have a row
+    rows: Vec<Option<RowData>>,
+    //Pages parsed with a checksum keep one when written back
+    checksums: bool,
 }
 
 impl PageData {
-    pub fn new(page: usize) -> PageData {
+    pub fn new(page: usize, page_size: PageSize) -> PageData {
         PageData {
             page,
-            page_header: PageHeader::new(),
+            page_size,
+            page_header: PageHeader::new(page_size),
             item_ids: vec![],
             rows: vec![],
+            checksums: false,
         }
     }
 
+    /// If the page is written with a checksum, turned on for every page when initdb enabled them
+    pub fn has_checksum(&self) -> bool {
+        self.checksums
+    }
+
+    pub fn set_checksum(&mut self, checksums: bool) {
+        self.checksums = checksums;
+    }
+
     //fast check if there is still space in this page
     pub fn can_fit(&self, row_data_size: usize) -> bool {
-        self.page_header.can_fit(row_data_size)
+        match self.first_unused() {
+            Some(_) => self.page_header.get_free_space() >= row_data_size,
+            None => self.page_header.can_fit(row_data_size),
+        }
+    }
+
+    pub fn free_space(&self) -> usize {
+        self.page_header.get_free_space()
+    }
+
+    /// If every row is visible to every transaction, any change to the page's rows clears this
+    pub fn all_visible(&self) -> bool {
+        self.page_header
+            .get_flags()
+            .contains(PageFlags::ALL_VISIBLE)
+    }
+
+    pub fn set_all_visible(&mut self, all_visible: bool) {
+        let mut flags = self.page_header.get_flags();
+        flags.set(PageFlags::ALL_VISIBLE, all_visible);
+        self.page_header.set_flags(flags);
     }
 
     //TODO debating if this should be row_data or bytes
     pub fn insert(&mut self, mut row_data: RowData) -> Result<ItemPointer, PageDataError> {
+        //Unused line pointers are filled before new ones are added
+        let slot = self.first_unused().unwrap_or(self.rows.len());
+
         //Insert rewrites the row's location, update will not
-        let item_pointer = ItemPointer::new(self.page, UInt12::try_from(self.rows.len())?);
+        let item_pointer = ItemPointer::new(self.page, UInt15::try_from(slot)?);
         row_data.item_pointer = item_pointer;
 
         let row_data_len = row_data.serialize().len();
-
-        let item_data = self.page_header.add_item(row_data_len)?;
-        self.item_ids.push(item_data);
-        self.rows.push(row_data);
+        self.set_all_visible(false);
+
+        if slot < self.rows.len() {
+            self.item_ids[slot] = self.page_header.reuse_item(row_data_len)?;
+            self.rows[slot] = Some(row_data);
+        } else {
+            let item_data = self.page_header.add_item(row_data_len)?;
+            self.item_ids.push(item_data);
+            self.rows.push(Some(row_data));
+        }
         Ok(item_pointer)
     }
 
-    pub fn update(&mut self, row_data: RowData, row_count: UInt12) -> Result<(), PageDataError> {
+    pub fn update(&mut self, row_data: RowData, row_count: UInt15) -> Result<(), PageDataError> {
         let row_data_len = row_data.serialize().len();
-        let row_count = row_count.to_usize();
-        if row_count > self.item_ids.len() - 1 || row_count > self.rows.len() - 1 {
-            return Err(PageDataError::IndexOutofBounds(
-                row_count,
-                self.item_ids.len(),
-                self.rows.len(),
-            ));
-        }
+        let row_count = self.check_used(row_count)?;
 
         let iid = &self.item_ids[row_count];
-        if iid.length.to_usize() != row_data_len {
+        if iid.length() != row_data_len {
             return Err(PageDataError::UpdateChangedLength(
-                iid.length.to_usize(),
+                iid.length(),
                 row_data_len,
             ));
         }
 
-        self.rows[row_count] = row_data;
+        self.rows[row_count] = Some(row_data);
+        self.set_all_visible(false);
+        Ok(())
+    }
+
+    /// Marks the row's line pointer as unused, its space is only reclaimed by compact
+    pub fn remove_row(&mut self, row_count: UInt15) -> Result<RowData, PageDataError> {
+        let row_count = self.check_used(row_count)?;
+
+        self.item_ids[row_count] = ItemIdData::unused();
+        self.set_all_visible(false);
+        self.rows[row_count]
+            .take()
+            .ok_or(PageDataError::UnusedRow(row_count))
+    }
+
+    /// Removes the dead rows given, without losing the way to the rows still alive.
+    ///
+    /// An index only knows the line pointer at the start of a HOT chain, so when that row is dead
+    /// the line pointer becomes a redirect to the oldest version still alive, or dead if there is
+    /// none. Dead heap only rows are only reached through their chain so their line pointers become
+    /// unused. Returns how many rows were removed.
+    pub fn prune(&mut self, dead: &[UInt15]) -> Result<usize, PageDataError> {
+        let is_dead = |c: &UInt15| dead.contains(c);
+        let mut removed = 0;
+
+        let roots: Vec<UInt15> = self
+            .item_ids
+            .iter()
+            .zip(self.rows.iter())
+            .enumerate()
+            .filter(|(_, (item, row))| match item.flags() {
+                ItemIdFlags::Redirect => true,
+                ItemIdFlags::Normal => row.as_ref().map(|r| !r.heap_only).unwrap_or(false),
+                _ => false,
+            })
+            .filter_map(|(i, _)| UInt15::try_from(i).ok())
+            .collect();
+
+        for root in roots {
+            let chain: Vec<UInt15> = self.hot_chain(root).iter().map(|(c, _)| *c).collect();
+            let first_live = chain.iter().position(|c| !is_dead(c));
+            let last_live = chain.iter().rposition(|c| !is_dead(c));
+
+            //The dead versions before the first live one and after the last, a dead version
+            //between live ones is still needed to get from one to the other
+            let (before, after) = match (first_live, last_live) {
+                (Some(f), Some(l)) => (&chain[..f], &chain[l + 1..]),
+                _ => (&chain[..], &chain[chain.len()..]),
+            };
+            for count in before.iter().chain(after.iter()) {
+                self.remove_row(*count)?;
+                removed += 1;
+            }
+
+            if let Some(l) = last_live {
+                if !after.is_empty() {
+                    let mut row = self.rows[chain[l].to_usize()]
+                        .clone()
+                        .ok_or(PageDataError::UnusedRow(chain[l].to_usize()))?;
+                    row.hot_updated = false;
+                    self.update(row, chain[l])?;
+                }
+            }
+
+            //The root's line pointer is still needed by whatever points at the chain
+            match first_live {
+                Some(0) => {}
+                Some(f) => self.item_ids[root.to_usize()] = ItemIdData::redirect(chain[f]),
+                None => self.item_ids[root.to_usize()] = ItemIdData::dead(),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Makes dead line pointers unused so they can be used again.
+    //TODO once indexes exist this can only happen after their entries for these rows are removed
+    pub fn reclaim_dead(&mut self) {
+        for item in self.item_ids.iter_mut() {
+            if item.flags() == ItemIdFlags::Dead {
+                *item = ItemIdData::unused();
+            }
+        }
+        self.set_all_visible(false);
+    }
+
+    /// Moves the remaining rows together so the space of removed rows can be used again.
+    ///
+    /// Rows keep their line pointers since other rows point at them, only unused line pointers at
+    /// the end of the page are dropped.
+    pub fn compact(&mut self) -> Result<(), PageDataError> {
+        let all_visible = self.all_visible();
+        while let Some(ItemIdFlags::Unused) = self.item_ids.last().map(|i| i.flags()) {
+            self.rows.pop();
+            self.item_ids.pop();
+        }
+
+        let mut page_header = PageHeader::new(self.page_size);
+        let mut item_ids = Vec::with_capacity(self.rows.len());
+        for (item, row) in self.item_ids.iter().zip(self.rows.iter()) {
+            match row {
+                Some(r) => item_ids.push(page_header.add_item(r.serialize().len())?),
+                //Line pointers without storage are kept as is
+                None => {
+                    page_header.add_item(0)?;
+                    item_ids.push(*item);
+                }
+            }
+        }
+
+        self.page_header = page_header;
+        self.item_ids = item_ids;
+        self.set_all_visible(all_visible);
         Ok(())
     }
 
-    pub fn get_row(&self, count: UInt12) -> Option<&RowData> {
-        self.rows.get(count.to_usize())
+    pub fn get_row(&self, count: UInt15) -> Option<&RowData> {
+        self.rows.get(count.to_usize()).and_then(Option::as_ref)
+    }
+
+    pub fn get_item(&self, count: UInt15) -> Option<&ItemIdData> {
+        self.item_ids.get(count.to_usize())
+    }
+
+    /// The versions of a row reachable from a line pointer, oldest first.
+    ///
+    /// A redirect is followed to the row it stands in for, then each HOT updated row leads to the
+    /// heap only row that replaced it. The next version must have been made by the transaction that
+    /// ended the last one, otherwise its line pointer has been reused and the chain stops.
+    pub fn hot_chain(&self, count: UInt15) -> Vec<(UInt15, &RowData)> {
+        let mut chain: Vec<(UInt15, &RowData)> = vec![];
+        let mut next = match self.get_item(count) {
+            Some(item) => item.redirect_target().unwrap_or(count),
+            None => return chain,
+        };
+
+        while let Some(row) = self.get_row(next) {
+            if let Some((_, prev)) = chain.last() {
+                let follows = row.heap_only && prev.max == Some(row.min);
+                //The length check guards against a loop
+                if !follows || chain.len() >= self.rows.len() {
+                    break;
+                }
+            }
+            chain.push((next, row));
+
+            if !row.hot_updated || row.item_pointer.page != self.page {
+                break;
+            }
+            next = row.item_pointer.count;
+        }
+        chain
+    }
+
+    /// Every row on the page with the line pointer it is at, a row's own item pointer may have
+    /// been changed to point at a newer version of it.
+    pub fn get_rows(&self) -> impl Iterator<Item = (UInt15, &RowData)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| Some((UInt15::try_from(i).ok()?, r.as_ref()?)))
     }
 
     pub fn get_stream(&self) -> impl Stream<Item = RowData> {
-        let rows_clone = self.rows.clone();
+        let rows_clone: Vec<RowData> = self.rows.iter().flatten().cloned().collect();
         stream! {
             for row in rows_clone.iter() {
                 yield row.clone();
@@ -83,7 +278,7 @@ impl PageData {
     }
 
     pub fn serialize(&self) -> Bytes {
-        let mut buffer = BytesMut::with_capacity((UInt12::max().to_u16() + 1).into());
+        let mut buffer = BytesMut::with_capacity(self.page_size.bytes());
 
         buffer.put(self.page_header.serialize());
 
@@ -92,13 +287,16 @@ impl PageData {
             buffer.put(item.serialize());
         }
 
-        //Fill the free space
-        let free_space = vec![0; self.page_header.get_free_space()];
-        buffer.extend_from_slice(&free_space);
+        //Zero the rest then place each row where its item points
+        buffer.resize(self.page_size.bytes(), 0);
+        for (item, row) in self.item_ids.iter().zip(self.rows.iter()) {
+            if let Some(r) = row {
+                buffer[item.get_range()].copy_from_slice(&r.serialize());
+            }
+        }
 
-        //Write items in reverse order
-        for value in self.rows.iter().rev() {
-            buffer.put(value.serialize());
+        if self.checksums {
+            PageHeader::write_checksum(&mut buffer, self.page);
         }
 
         buffer.freeze()
@@ -106,12 +304,27 @@ impl PageData {
 
     pub fn parse(table: Arc<Table>, page: usize, buffer: Bytes) -> Result<PageData, PageDataError> {
         //Note since we need random access, everything MUST work off slices otherwise counts will be off
+        let page_size = PageSize::try_from(buffer.len())?;
 
         let mut page_header_slice = buffer.slice(0..mem::size_of::<PageHeader>());
         let page_header = PageHeader::parse(&mut page_header_slice)?;
 
+        //Checked before anything else is read since a corrupt page can point anywhere
+        let checksums = page_header.get_checksum() != 0;
+        if checksums {
+            let expected = PageHeader::compute_checksum(&buffer, page);
+            if expected != page_header.get_checksum() {
+                return Err(PageDataError::ChecksumFailure(
+                    table.name.clone(),
+                    page,
+                    expected,
+                    page_header.get_checksum(),
+                ));
+            }
+        }
+
         let mut item_ids: Vec<ItemIdData> = Vec::with_capacity(page_header.get_item_count());
-        let mut rows: Vec<RowData> = Vec::with_capacity(page_header.get_item_count());
+        let mut rows: Vec<Option<RowData>> = Vec::with_capacity(page_header.get_item_count());
         for i in 0..page_header.get_item_count() {
             let iid_lower_offset =
                 mem::size_of::<PageHeader>() + (mem::size_of::<ItemIdData>() * i);
@@ -120,21 +333,45 @@ impl PageData {
             let mut iid_slice = buffer.slice(iid_lower_offset..iid_upper_offset);
             let iid = ItemIdData::parse(&mut iid_slice)?;
 
-            let row_slice = buffer.slice(iid.get_range());
-            let row = RowData::parse(table.clone(), row_slice)?;
+            if iid.flags() == ItemIdFlags::Normal {
+                let row_slice = buffer.slice(iid.get_range());
+                rows.push(Some(RowData::parse(table.clone(), row_slice)?));
+            } else {
+                rows.push(None);
+            }
             item_ids.push(iid);
-            rows.push(row);
         }
 
         Ok(PageData {
             page,
+            page_size,
             page_header,
             item_ids,
             rows,
+            checksums,
         })
     }
 
-    //Todo implement updates, just unsure if it should be here
+    fn first_unused(&self) -> Option<usize> {
+        self.item_ids
+            .iter()
+            .position(|i| i.flags() == ItemIdFlags::Unused)
+    }
+
+    fn check_used(&self, row_count: UInt15) -> Result<usize, PageDataError> {
+        let row_count = row_count.to_usize();
+        if row_count >= self.item_ids.len() || row_count >= self.rows.len() {
+            return Err(PageDataError::IndexOutofBounds(
+                row_count,
+                self.item_ids.len(),
+                self.rows.len(),
+            ));
+        }
+        if self.rows[row_count].is_none() {
+            return Err(PageDataError::UnusedRow(row_count));
+        }
+        Ok(row_count)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -146,11 +383,17 @@ pub enum PageDataError {
     #[error(transparent)]
     RowDataParseError(#[from] RowDataError),
     #[error(transparent)]
-    UInt12Error(#[from] UInt12Error),
+    UInt15Error(#[from] UInt15Error),
+    #[error(transparent)]
+    PageSizeError(#[from] PageSizeError),
     #[error("Row {0} does not exist to update we have {1}:{2} rows")]
     IndexOutofBounds(usize, usize, usize),
+    #[error("Row {0} has been removed")]
+    UnusedRow(usize),
     #[error("Updates cannot change row length! Old: {0} New: {1}")]
     UpdateChangedLength(usize, usize),
+    #[error("Invalid page {1} of relation {0}, calculated checksum {2} but expected {3}")]
+    ChecksumFailure(String, usize, u16, u16),
 }
 
 #[cfg(test)]
@@ -173,7 +416,7 @@ mod tests {
     }
 
     fn get_item_pointer(row_num: usize) -> ItemPointer {
-        ItemPointer::new(0, UInt12::new(row_num as u16).unwrap())
+        ItemPointer::new(0, UInt15::new(row_num as u16).unwrap())
     }
 
     fn get_table() -> Arc<Table> {
@@ -217,7 +460,7 @@ mod tests {
             ])),
         ).unwrap());
 
-        let mut pg = PageData::new(0);
+        let mut pg = PageData::new(0, PageSize::default());
         for r in rows.clone() {
             assert!(pg.insert(r.clone()).is_ok());
         }
@@ -229,6 +472,46 @@ mod tests {
         assert_eq!(rows, result_rows);
     }
 
+    #[test]
+    fn test_page_data_checksum() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_table();
+        let row = RowData::new(
+            table.clone(),
+            TransactionId::new(0xDEADBEEF),
+            None,
+            get_item_pointer(0),
+            Arc::new(SqlTuple(vec![
+                Some(BuiltinSqlTypes::Text("checked".to_string())),
+                None,
+                Some(BuiltinSqlTypes::Text("summed".to_string())),
+            ])),
+        )?;
+
+        let mut pg = PageData::new(2, PageSize::default());
+        pg.insert(row)?;
+        assert_eq!(PageHeader::parse(&mut pg.serialize())?.get_checksum(), 0);
+
+        pg.set_checksum(true);
+        let serial = pg.serialize();
+        let pg_parsed = PageData::parse(table.clone(), 2, serial.clone())?;
+        assert!(pg_parsed.has_checksum());
+        assert_eq!(pg_parsed.serialize(), serial);
+
+        //A single flipped bit anywhere is caught, as is a page read from the wrong place
+        let mut corrupt = BytesMut::from(&serial[..]);
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0x10;
+        assert!(matches!(
+            PageData::parse(table.clone(), 2, corrupt.freeze()),
+            Err(PageDataError::ChecksumFailure(_, 2, _, _))
+        ));
+        assert!(matches!(
+            PageData::parse(table, 3, serial),
+            Err(PageDataError::ChecksumFailure(_, 3, _, _))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_page_data_roundtrip_two_rows() {
         let table = get_table();
@@ -253,7 +536,7 @@ mod tests {
         ])),
         ).unwrap());
 
-        let mut pg = PageData::new(0);
+        let mut pg = PageData::new(0, PageSize::default());
         for r in rows.clone() {
             assert!(pg.insert(r.clone()).is_ok());
         }
@@ -280,7 +563,7 @@ mod tests {
             ])),
         ).unwrap();
 
-        let mut pg = PageData::new(0);
+        let mut pg = PageData::new(0, PageSize::default());
         let rip = pg.insert(row.clone());
         assert!(rip.is_ok());
 
@@ -294,4 +577,206 @@ mod tests {
         let result_rows: Vec<RowData> = aw!(pg.get_stream().collect());
         assert_eq!(row, result_rows[0]);
     }
+
+    #[test]
+    fn test_page_data_every_size() {
+        let table = get_table();
+
+        for page_size in PageSize::VALUES.iter() {
+            let row = RowData::new(
+                table.clone(),
+                TransactionId::new(1),
+                None,
+                get_item_pointer(0),
+                Arc::new(SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Text("x".repeat(100))),
+                    None,
+                    Some(BuiltinSqlTypes::Text("fill".to_string())),
+                ])),
+            )
+            .unwrap();
+            let row_len = row.serialize().len();
+
+            let mut pg = PageData::new(0, *page_size);
+            let mut rows = vec![];
+            while pg.can_fit(row_len) {
+                let mut r = row.clone();
+                r.item_pointer = pg.insert(row.clone()).unwrap();
+                rows.push(r);
+            }
+            assert_eq!(
+                rows.len(),
+                (page_size.bytes() - mem::size_of::<PageHeader>())
+                    / (row_len + mem::size_of::<ItemIdData>())
+            );
+
+            let serial = pg.serialize();
+            assert_eq!(serial.len(), page_size.bytes());
+
+            let pg_parsed = PageData::parse(table.clone(), 0, serial.clone()).unwrap();
+            let result_rows: Vec<RowData> = aw!(pg_parsed.get_stream().collect());
+            assert_eq!(rows, result_rows);
+
+            assert!(matches!(
+                PageData::parse(table.clone(), 0, serial.slice(1..)),
+                Err(PageDataError::PageSizeError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_page_data_remove_compact() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_table();
+        let make_row = |text: &str| {
+            RowData::new(
+                table.clone(),
+                TransactionId::new(1),
+                None,
+                get_item_pointer(0),
+                Arc::new(SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Text(text.to_string())),
+                    None,
+                    Some(BuiltinSqlTypes::Text("x".repeat(50))),
+                ])),
+            )
+        };
+
+        let mut pg = PageData::new(0, PageSize::default());
+        let mut pointers = vec![];
+        for text in ["first", "second", "third", "fourth"] {
+            pointers.push(pg.insert(make_row(text)?)?);
+        }
+        let full_free = pg.free_space();
+
+        let removed = pg.remove_row(pointers[1].count)?;
+        let removed_len = removed.serialize().len();
+        assert!(pg.get_row(pointers[1].count).is_none());
+        assert!(matches!(
+            pg.remove_row(pointers[1].count),
+            Err(PageDataError::UnusedRow(1))
+        ));
+        pg.remove_row(pointers[3].count)?;
+        assert_eq!(pg.free_space(), full_free); //Nothing reclaimed until compacted
+
+        //The trailing line pointer goes away, the one in the middle stays for reuse
+        pg.compact()?;
+        assert_eq!(
+            pg.free_space(),
+            full_free + removed_len * 2 + mem::size_of::<ItemIdData>()
+        );
+        assert_eq!(pg.get_rows().count(), 2);
+
+        let serial = pg.serialize();
+        let mut pg_parsed = PageData::parse(table.clone(), 0, serial)?;
+        assert_eq!(pg_parsed.free_space(), pg.free_space());
+        let result_rows: Vec<RowData> = aw!(pg_parsed.get_stream().collect());
+        assert_eq!(
+            result_rows,
+            vec![
+                pg.get_row(pointers[0].count).unwrap().clone(),
+                pg.get_row(pointers[2].count).unwrap().clone()
+            ]
+        );
+
+        let reused = pg_parsed.insert(make_row("fifth")?)?;
+        assert_eq!(reused, pointers[1]);
+        let appended = pg_parsed.insert(make_row("sixth")?)?;
+        assert_eq!(appended, pointers[3]);
+
+        let pg_parsed = PageData::parse(table.clone(), 0, pg_parsed.serialize())?;
+        let texts: Vec<_> = pg_parsed
+            .get_rows()
+            .map(|(_, r)| r.user_data.0[0].clone())
+            .collect();
+        assert_eq!(
+            texts,
+            ["first", "fifth", "third", "sixth"]
+                .iter()
+                .map(|t| Some(BuiltinSqlTypes::Text(t.to_string())))
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_page_data_hot_prune() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_table();
+        let make_row = |text: &str, min: u64, max: Option<u64>| {
+            RowData::new(
+                table.clone(),
+                TransactionId::new(min),
+                max.map(TransactionId::new),
+                get_item_pointer(0),
+                Arc::new(SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Text(text.to_string())),
+                    None,
+                    Some(BuiltinSqlTypes::Text("x".repeat(50))),
+                ])),
+            )
+        };
+
+        //Each version ends with the transaction that made the next, the last one was aborted
+        let mut pg = PageData::new(0, PageSize::default());
+        let mut pointers = vec![];
+        for (i, (text, min, max)) in [
+            ("v0", 2, Some(3)),
+            ("v1", 3, Some(4)),
+            ("v2", 4, Some(5)),
+            ("v3", 5, None),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut row = make_row(text, *min, *max)?;
+            row.heap_only = i > 0;
+            pointers.push(pg.insert(row)?);
+        }
+        for pair in pointers.windows(2) {
+            let mut row = pg.get_row(pair[0].count).unwrap().clone();
+            row.item_pointer = pair[1];
+            row.hot_updated = true;
+            pg.update(row, pair[0].count)?;
+        }
+        let other = pg.insert(make_row("other", 2, None)?)?;
+
+        let root = pointers[0].count;
+        assert_eq!(pg.hot_chain(root).len(), 4);
+        assert_eq!(pg.hot_chain(other.count).len(), 1);
+
+        //The dead front and back of the chain go, the root stays as a redirect
+        let removed = pg.prune(&[pointers[0].count, pointers[1].count, pointers[3].count])?;
+        assert_eq!(removed, 3);
+        pg.reclaim_dead();
+        pg.compact()?;
+        assert_eq!(pg.get_item(root).unwrap().flags(), ItemIdFlags::Redirect);
+        let chain: Vec<_> = pg.hot_chain(root).iter().map(|(c, _)| *c).collect();
+        assert_eq!(chain, vec![pointers[2].count]);
+        assert!(!pg.get_row(pointers[2].count).unwrap().hot_updated);
+
+        let mut pg = PageData::parse(table.clone(), 0, pg.serialize())?;
+        assert_eq!(
+            pg.get_item(root).unwrap().redirect_target(),
+            Some(pointers[2].count)
+        );
+        assert_eq!(pg.get_rows().count(), 2);
+
+        //Only the heap only line pointers are free for reuse
+        assert_eq!(pg.insert(make_row("new", 6, None)?)?, pointers[1]);
+        assert_eq!(pg.insert(make_row("new", 6, None)?)?, pointers[3]);
+
+        //A line pointer reused by something else doesn't continue the chain
+        let mut row = pg.get_row(pointers[2].count).unwrap().clone();
+        row.item_pointer = pointers[3];
+        row.hot_updated = true;
+        pg.update(row, pointers[2].count)?;
+        assert_eq!(pg.hot_chain(root).len(), 1);
+
+        //With nothing left alive the root is dead until reclaimed
+        assert_eq!(pg.prune(&[pointers[2].count])?, 1);
+        assert_eq!(pg.get_item(root).unwrap().flags(), ItemIdFlags::Dead);
+        assert!(pg.hot_chain(root).is_empty());
+        pg.reclaim_dead();
+        assert_eq!(pg.get_item(root).unwrap().flags(), ItemIdFlags::Unused);
+        Ok(())
+    }
 }
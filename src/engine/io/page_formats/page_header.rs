@@ -1,25 +1,92 @@
 //! See https://www.postgresql.org/docs/current/storage-page-layout.html for reference documentation
 //! I'm only implementing enough for my needs until proven otherwise
-use super::{ItemIdData, UInt12, UInt12Error};
+//!
+//! On disk the header is lower, upper, flags, checksum and the layout version as little endian u16s.
+//! The layout version covers the header, line pointers and rows, bump it whenever any of them change.
+use super::{ItemIdData, PageSize, UInt15, UInt15Error};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::convert::TryFrom;
 use std::mem::size_of;
+use std::ops::Range;
 use thiserror::Error;
 
+//Where pd_checksum sits in the serialized header
+const CHECKSUM_RANGE: Range<usize> = 6..8;
+
+pub const PAGE_LAYOUT_VERSION: u16 = 1;
+
+//FNV-1a, see https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
 #[derive(Debug, PartialEq)]
 pub struct PageHeader {
-    pd_lower: UInt12,
-    pd_upper: UInt12,
+    pd_lower: UInt15,
+    pd_upper: UInt15,
+    pd_flags: PageFlags,
+    pd_checksum: u16,
+    pd_version: u16,
+}
+
+bitflags! {
+    pub struct PageFlags: u16 {
+        ///Every row on the page is visible to every transaction, kept in step with the visibility map
+        const ALL_VISIBLE = 0b00000001;
+    }
 }
 
 impl PageHeader {
-    pub fn new() -> PageHeader {
+    pub fn new(page_size: PageSize) -> PageHeader {
         PageHeader {
-            pd_lower: UInt12::new((size_of::<PageHeader>()) as u16).unwrap(),
-            pd_upper: UInt12::max(),
+            pd_lower: UInt15::new((size_of::<PageHeader>()) as u16).unwrap(),
+            pd_upper: UInt15::try_from(page_size.bytes() - 1).unwrap(),
+            pd_flags: PageFlags::empty(),
+            pd_checksum: 0,
+            pd_version: PAGE_LAYOUT_VERSION,
         }
     }
 
+    /// The checksum read from the page, zero if it was written without one
+    pub fn get_checksum(&self) -> u16 {
+        self.pd_checksum
+    }
+
+    /// Computes the checksum of a serialized page, the stored checksum is treated as zero.
+    ///
+    /// Like postgres the page number is mixed in so a page written to the wrong place is caught and
+    /// the result is never zero, that is left to mean no checksum.
+    pub fn compute_checksum(page: &[u8], page_num: usize) -> u16 {
+        let mut hash = FNV_OFFSET_BASIS;
+        for (i, b) in page.iter().enumerate() {
+            let b = if CHECKSUM_RANGE.contains(&i) { 0 } else { *b };
+            hash = (hash ^ u32::from(b)).wrapping_mul(FNV_PRIME);
+        }
+        hash ^= page_num as u32;
+        ((hash % u32::from(u16::MAX)) + 1) as u16
+    }
+
+    /// Reads the checksum of a serialized page without parsing the rest of the header
+    pub fn stored_checksum(page: &[u8]) -> u16 {
+        match page.get(CHECKSUM_RANGE) {
+            Some(b) => u16::from_le_bytes([b[0], b[1]]),
+            None => 0,
+        }
+    }
+
+    /// Stores the checksum of a serialized page into it
+    pub fn write_checksum(page: &mut [u8], page_num: usize) {
+        let checksum = PageHeader::compute_checksum(page, page_num);
+        page[CHECKSUM_RANGE].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    pub fn get_flags(&self) -> PageFlags {
+        self.pd_flags
+    }
+
+    pub fn set_flags(&mut self, flags: PageFlags) {
+        self.pd_flags = flags;
+    }
+
     pub fn get_item_count(&self) -> usize {
         let lower: usize = self.pd_lower.to_u16().into();
         (lower - size_of::<PageHeader>()) / size_of::<ItemIdData>()
@@ -44,21 +111,37 @@ impl PageHeader {
             return Err(PageHeaderError::InsufficentFreeSpace());
         }
 
-        let row_u12 = UInt12::try_from(row_size)?;
+        let row_u15 = UInt15::try_from(row_size)?;
 
-        self.pd_lower += UInt12::try_from(size_of::<ItemIdData>())?;
-        self.pd_upper -= row_u12;
+        self.pd_lower = UInt15::try_from(self.pd_lower.to_usize() + size_of::<ItemIdData>())?;
+        self.pd_upper -= row_u15;
 
         //Need to increment the offset by 1 since the pointer is now pointing a free space
-        let item_offset = self.pd_upper + UInt12::new(1).unwrap();
+        let item_offset = self.pd_upper + UInt15::new(1).unwrap();
+
+        Ok(ItemIdData::new(item_offset, row_u15))
+    }
+
+    /// Makes room for a row behind a line pointer that already exists but is unused
+    pub fn reuse_item(&mut self, row_size: usize) -> Result<ItemIdData, PageHeaderError> {
+        if self.get_free_space() < row_size {
+            return Err(PageHeaderError::InsufficentFreeSpace());
+        }
 
-        Ok(ItemIdData::new(item_offset, row_u12))
+        let row_u15 = UInt15::try_from(row_size)?;
+        self.pd_upper -= row_u15;
+        let item_offset = self.pd_upper + UInt15::new(1).unwrap();
+
+        Ok(ItemIdData::new(item_offset, row_u15))
     }
 
     pub fn serialize(&self) -> Bytes {
         let mut buf = BytesMut::with_capacity(size_of::<PageHeader>());
         buf.put(self.pd_lower.serialize());
         buf.put(self.pd_upper.serialize());
+        buf.put_u16_le(self.pd_flags.bits());
+        buf.put_u16_le(self.pd_checksum);
+        buf.put_u16_le(self.pd_version);
         buf.freeze()
     }
 
@@ -66,9 +149,21 @@ impl PageHeader {
         if buffer.remaining() < size_of::<PageHeader>() {
             return Err(PageHeaderError::InsufficentData(buffer.remaining()));
         }
-        let pd_lower = UInt12::parse(buffer)?;
-        let pd_upper = UInt12::parse(buffer)?;
-        Ok(PageHeader { pd_lower, pd_upper })
+        let pd_lower = UInt15::parse(buffer)?;
+        let pd_upper = UInt15::parse(buffer)?;
+        let pd_flags = PageFlags::from_bits_truncate(buffer.get_u16_le()); //Ignoring unused bits
+        let pd_checksum = buffer.get_u16_le();
+        let pd_version = buffer.get_u16_le();
+        if pd_version != PAGE_LAYOUT_VERSION {
+            return Err(PageHeaderError::UnsupportedVersion(pd_version));
+        }
+        Ok(PageHeader {
+            pd_lower,
+            pd_upper,
+            pd_flags,
+            pd_checksum,
+            pd_version,
+        })
     }
 }
 
@@ -76,14 +171,16 @@ impl PageHeader {
 pub enum PageHeaderError {
     #[error("Not enough space to add")]
     InsufficentFreeSpace(),
-    #[error("Value for u12 too large")]
-    TooLarge(#[from] UInt12Error),
+    #[error("Value for u15 too large")]
+    TooLarge(#[from] UInt15Error),
     #[error("Not enough data has {0} bytes")]
     InsufficentData(usize),
     #[error("Lower offset is too large")]
     LowerOffsetTooLarge(),
     #[error("Upper offset is too large")]
     UpperOffsetTooLarge(),
+    #[error("Page layout version {0} is not supported")]
+    UnsupportedVersion(u16),
 }
 
 #[cfg(test)]
@@ -92,52 +189,127 @@ mod tests {
 
     #[test]
     fn test_roundtrip() {
-        let test = PageHeader::new();
-        let mut test_serial = test.serialize();
-        let test_rt = PageHeader::parse(&mut test_serial).unwrap();
+        for page_size in PageSize::VALUES.iter() {
+            let test = PageHeader::new(*page_size);
+            let mut test_serial = test.serialize();
+            let test_rt = PageHeader::parse(&mut test_serial).unwrap();
+
+            let test_new = PageHeader::new(*page_size);
+            assert_eq!(test_rt, test_new);
+
+            let mut test = test_new;
+            test.set_flags(PageFlags::ALL_VISIBLE);
+            let test_rt = PageHeader::parse(&mut test.serialize()).unwrap();
+            assert_eq!(test_rt.get_flags(), PageFlags::ALL_VISIBLE);
 
-        let test_new = PageHeader::new();
-        assert_eq!(test_rt, test_new);
+            let mut serial = BytesMut::from(&test.serialize()[..]);
+            serial[8] = 0xFF;
+            assert!(matches!(
+                PageHeader::parse(&mut serial.freeze()),
+                Err(PageHeaderError::UnsupportedVersion(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut page = PageHeader::new(PageSize::Kb4).serialize().to_vec();
+        page.resize(PageSize::Kb4.bytes(), 0);
+
+        PageHeader::write_checksum(&mut page, 3);
+        let header = PageHeader::parse(&mut &page[..]).unwrap();
+        assert_ne!(header.get_checksum(), 0);
+        assert_eq!(
+            PageHeader::compute_checksum(&page, 3),
+            header.get_checksum()
+        );
+
+        //Wrong place or a flipped bit
+        assert_ne!(
+            PageHeader::compute_checksum(&page, 4),
+            header.get_checksum()
+        );
+        page[100] ^= 1;
+        assert_ne!(
+            PageHeader::compute_checksum(&page, 3),
+            header.get_checksum()
+        );
     }
 
     #[test]
     fn test_initial_freespace() {
-        let test = PageHeader::new();
+        for page_size in PageSize::VALUES.iter() {
+            let test = PageHeader::new(*page_size);
 
-        let default_free_space: usize =
-            (UInt12::max().to_u16() as usize) + 1 - size_of::<PageHeader>();
-        let found_free_space = test.get_free_space();
-        assert_eq!(found_free_space, default_free_space);
+            let default_free_space: usize = page_size.bytes() - size_of::<PageHeader>();
+            let found_free_space = test.get_free_space();
+            assert_eq!(found_free_space, default_free_space);
+        }
     }
 
     #[test]
     fn test_item_count() {
-        let mut test = PageHeader::new();
+        for page_size in PageSize::VALUES.iter() {
+            let mut test = PageHeader::new(*page_size);
 
-        test.add_item(5).unwrap();
-        test.add_item(5).unwrap();
+            test.add_item(5).unwrap();
+            test.add_item(5).unwrap();
 
-        assert_eq!(test.get_item_count(), 2);
+            assert_eq!(test.get_item_count(), 2);
 
-        let remain_free = (UInt12::max().to_u16() as usize) + 1 //Initial
-            - size_of::<PageHeader>() //Header
-            - (size_of::<ItemIdData>() * 2) //Two items
-            - 10; //Their data
-        assert_eq!(test.get_free_space(), remain_free)
+            let remain_free = page_size.bytes() //Initial
+                - size_of::<PageHeader>() //Header
+                - (size_of::<ItemIdData>() * 2) //Two items
+                - 10; //Their data
+            assert_eq!(test.get_free_space(), remain_free)
+        }
     }
 
     #[test]
     fn test_too_big() {
-        let mut test = PageHeader::new();
+        for page_size in PageSize::VALUES.iter() {
+            let mut test = PageHeader::new(*page_size);
 
-        let needed = (UInt12::max().to_u16() as usize) + 1
-            - size_of::<PageHeader>()
-            - size_of::<ItemIdData>();
-        test.add_item(needed).unwrap(); //Should be maxed out
+            let needed = page_size.bytes() - size_of::<PageHeader>() - size_of::<ItemIdData>();
+            assert!(!test.can_fit(needed + 1)); //Can't go past the end of the page
+            test.add_item(needed).unwrap(); //Should be maxed out
 
-        assert_eq!(test.get_item_count(), 1); //Should have an item
-        assert_eq!(test.get_free_space(), 0); //Should be full
-        assert!(!test.can_fit(1)); //Should not be able to store a tiny item
-        assert!(test.add_item(0).is_err()); //Adding more should fail
+            assert_eq!(test.get_item_count(), 1); //Should have an item
+            assert_eq!(test.get_free_space(), 0); //Should be full
+            assert!(!test.can_fit(1)); //Should not be able to store a tiny item
+            assert!(test.add_item(0).is_err()); //Adding more should fail
+        }
+    }
+
+    #[test]
+    fn test_max_items() {
+        for page_size in PageSize::VALUES.iter() {
+            let mut test = PageHeader::new(*page_size);
+
+            let max_items =
+                (page_size.bytes() - size_of::<PageHeader>()) / (size_of::<ItemIdData>() + 1);
+            for _ in 0..max_items {
+                test.add_item(1).unwrap();
+            }
+
+            assert_eq!(test.get_item_count(), max_items);
+            assert!(test.add_item(1).is_err());
+        }
+    }
+
+    #[test]
+    fn test_reuse_item() {
+        for page_size in PageSize::VALUES.iter() {
+            let mut test = PageHeader::new(*page_size);
+            test.add_item(5).unwrap();
+
+            let free = test.get_free_space();
+            test.reuse_item(10).unwrap();
+            assert_eq!(test.get_item_count(), 1); //No new line pointer
+            assert_eq!(test.get_free_space(), free - 10);
+
+            assert!(test.reuse_item(free - 10).is_ok()); //Can use every last byte
+            assert!(test.reuse_item(1).is_err());
+        }
     }
 }
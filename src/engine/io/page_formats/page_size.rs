@@ -0,0 +1,74 @@
+//! The size of every page in the database, it is picked when the database is created and can't change after.
+//! Same range postgres supports with --with-blocksize, see: https://www.postgresql.org/docs/current/install-make.html
+use std::convert::TryFrom;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PageSize {
+    Kb4,
+    Kb8,
+    Kb16,
+    Kb32,
+}
+
+impl PageSize {
+    pub const VALUES: [PageSize; 4] =
+        [PageSize::Kb4, PageSize::Kb8, PageSize::Kb16, PageSize::Kb32];
+
+    pub fn bytes(self) -> usize {
+        match self {
+            PageSize::Kb4 => 4096,
+            PageSize::Kb8 => 8192,
+            PageSize::Kb16 => 16384,
+            PageSize::Kb32 => 32768,
+        }
+    }
+}
+
+/// 8kb like postgres
+impl Default for PageSize {
+    fn default() -> Self {
+        PageSize::Kb8
+    }
+}
+
+impl TryFrom<usize> for PageSize {
+    type Error = PageSizeError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        PageSize::VALUES
+            .iter()
+            .find(|p| p.bytes() == value)
+            .copied()
+            .ok_or(PageSizeError::Unsupported(value))
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}kB", self.bytes() / 1024)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PageSizeError {
+    #[error("Unsupported page size {0}, must be 4096, 8192, 16384 or 32768")]
+    Unsupported(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_size_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        for size in PageSize::VALUES.iter() {
+            assert_eq!(PageSize::try_from(size.bytes())?, *size);
+        }
+        assert_eq!(PageSize::default().to_string(), "8kB");
+        assert!(PageSize::try_from(4095).is_err());
+        assert!(PageSize::try_from(65536).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//Wide enough to address anything in the largest supported page, the top bit of the u16 is left spare
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use thiserror::Error;
+
+const MAX_VALUE: u16 = 0x7FFF;
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd)]
+pub struct UInt15(u16);
+
+impl UInt15 {
+    fn is_in_range(val: u16) -> bool {
+        val <= MAX_VALUE
+    }
+
+    fn clamp(val: u16) -> u16 {
+        if val > MAX_VALUE {
+            return MAX_VALUE;
+        }
+        // Otherwise return val itself
+        val
+    }
+
+    pub fn new(val: u16) -> Option<UInt15> {
+        if UInt15::is_in_range(val) {
+            Some(UInt15(val))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        self.0
+    }
+
+    pub fn to_usize(self) -> usize {
+        usize::from(self.0)
+    }
+
+    pub fn max() -> UInt15 {
+        UInt15(MAX_VALUE)
+    }
+
+    pub fn serialize(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(mem::size_of::<u16>());
+        buf.put_u16_le(self.0);
+        buf.freeze()
+    }
+
+    pub fn parse(buffer: &mut impl Buf) -> Result<Self, UInt15Error> {
+        if buffer.remaining() < mem::size_of::<u16>() {
+            return Err(UInt15Error::InsufficentData(buffer.remaining()));
+        }
+
+        let raw_value = buffer.get_u16_le();
+
+        let value = UInt15::new(raw_value).ok_or(UInt15Error::ValueTooLargeU16(raw_value))?;
+
+        Ok(value)
+    }
+}
+
+impl Add for UInt15 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        UInt15(UInt15::clamp(self.0.saturating_add(other.0)))
+    }
+}
+
+impl AddAssign for UInt15 {
+    fn add_assign(&mut self, other: Self) {
+        *self = UInt15(UInt15::clamp(self.0.saturating_add(other.0)))
+    }
+}
+
+impl Sub for UInt15 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self::Output {
+        UInt15(UInt15::clamp(self.0.saturating_sub(other.0)))
+    }
+}
+
+impl SubAssign for UInt15 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = UInt15(UInt15::clamp(self.0.saturating_sub(other.0)))
+    }
+}
+
+impl TryFrom<usize> for UInt15 {
+    type Error = UInt15Error;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        if value > MAX_VALUE as usize {
+            return Err(UInt15Error::ValueTooLargeUSize(value));
+        }
+
+        Ok(UInt15(value as u16))
+    }
+}
+
+impl fmt::Display for UInt15 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UInt15Error {
+    #[error("Not enough data to parse, got {0}")]
+    InsufficentData(usize),
+    #[error("usize too large for UInt15 got {0}")]
+    ValueTooLargeUSize(usize),
+    #[error("u16 too large for UInt15 got {0}")]
+    ValueTooLargeU16(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normal() {
+        let test = UInt15::new(1).unwrap();
+
+        assert_eq!(test.to_u16(), 1);
+    }
+
+    #[test]
+    fn test_math() {
+        let mut test = UInt15::new(1).unwrap();
+
+        test += UInt15::new(1).unwrap();
+        test -= UInt15::new(1).unwrap();
+
+        assert_eq!(test.to_u16(), 1);
+    }
+
+    #[test]
+    fn test_subtraction() {
+        let left = UInt15::new(10).unwrap();
+        let right = UInt15::new(5).unwrap();
+
+        let result = left - right;
+
+        assert_eq!(result, right);
+    }
+
+    #[test]
+    fn test_usize() {
+        let large: usize = 400;
+        let test = UInt15::try_from(large).unwrap();
+
+        assert_eq!(test.to_u16(), 400);
+    }
+
+    #[test]
+    fn test_fail_usize() {
+        let large: usize = 40000;
+        let test = UInt15::try_from(large);
+
+        assert!(test.is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(UInt15::try_from(32767).unwrap(), UInt15::max());
+        assert!(UInt15::try_from(32768).is_err());
+        assert!(UInt15::new(0x8000).is_none());
+
+        let mut buffer = bytes::Bytes::from_static(&[0x00, 0x80]);
+        assert!(matches!(
+            UInt15::parse(&mut buffer),
+            Err(UInt15Error::ValueTooLargeU16(0x8000))
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! A logical replication slot, how far a consumer of decoded changes has got. The data directory
+//! keeps each one in pg_replslot and holds on to the log it still needs.
+//! See: https://www.postgresql.org/docs/current/logicaldecoding-explanation.html
+//!
+//! On disk a slot is:
+//! * The magic bytes "FEOPSLOT"
+//! * Format version as a little endian u32
+//! * Restart LSN as a little endian u64
+//! * Confirmed flush LSN as a little endian u64
+//! * The output plugin's name as a little endian u32 length followed by utf8
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"FEOPSLOT";
+const VERSION: u32 = 1;
+const HEADER_LENGTH: usize = 8 + 4 + 8 + 8 + 4;
+///Like postgres' NAMEDATALEN
+const MAX_NAME_LENGTH: usize = 63;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicationSlot {
+    pub name: String,
+    ///pgoutput or test_decoding, changes are sent in the format of the plugin they are read with
+    pub plugin: String,
+    ///Decoding starts here, no transaction the consumer hasn't confirmed started before it
+    pub restart_lsn: u64,
+    ///Transactions that committed before this have been confirmed by the consumer
+    pub confirmed_flush: u64,
+    ///A consumer is reading from the slot, this isn't saved
+    pub active: bool,
+}
+
+impl ReplicationSlot {
+    pub const PLUGINS: [&'static str; 2] = ["pgoutput", "test_decoding"];
+
+    /// A new slot starts decoding with the transactions that start after the LSN
+    pub fn new(
+        name: &str,
+        plugin: &str,
+        lsn: u64,
+    ) -> Result<ReplicationSlot, ReplicationSlotError> {
+        ReplicationSlot::check_name(name)?;
+        if !ReplicationSlot::PLUGINS.contains(&plugin) {
+            return Err(ReplicationSlotError::UnknownPlugin(plugin.to_string()));
+        }
+        Ok(ReplicationSlot {
+            name: name.to_string(),
+            plugin: plugin.to_string(),
+            restart_lsn: lsn,
+            confirmed_flush: lsn,
+            active: false,
+        })
+    }
+
+    /// Like postgres only lower case letters, numbers and underscores, the name is a file name
+    pub fn check_name(name: &str) -> Result<(), ReplicationSlotError> {
+        if name.is_empty()
+            || name.len() > MAX_NAME_LENGTH
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(ReplicationSlotError::BadName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(HEADER_LENGTH + self.plugin.len());
+        buffer.put_slice(MAGIC);
+        buffer.put_u32_le(VERSION);
+        buffer.put_u64_le(self.restart_lsn);
+        buffer.put_u64_le(self.confirmed_flush);
+        buffer.put_u32_le(self.plugin.len() as u32);
+        buffer.put_slice(self.plugin.as_bytes());
+        buffer.freeze()
+    }
+
+    pub fn parse(
+        name: &str,
+        buffer: &mut impl Buf,
+    ) -> Result<ReplicationSlot, ReplicationSlotError> {
+        ReplicationSlot::check_name(name)?;
+        if buffer.remaining() < HEADER_LENGTH {
+            return Err(ReplicationSlotError::BufferTooShort(
+                HEADER_LENGTH,
+                buffer.remaining(),
+            ));
+        }
+
+        let mut magic = [0; 8];
+        buffer.copy_to_slice(&mut magic);
+        if &magic != MAGIC {
+            return Err(ReplicationSlotError::BadMagic());
+        }
+        let version = buffer.get_u32_le();
+        if version != VERSION {
+            return Err(ReplicationSlotError::UnsupportedVersion(version));
+        }
+
+        let restart_lsn = buffer.get_u64_le();
+        let confirmed_flush = buffer.get_u64_le();
+        let plugin_length = buffer.get_u32_le() as usize;
+        if buffer.remaining() < plugin_length {
+            return Err(ReplicationSlotError::BufferTooShort(
+                plugin_length,
+                buffer.remaining(),
+            ));
+        }
+        let plugin = String::from_utf8(buffer.copy_to_bytes(plugin_length).to_vec())?;
+        Ok(ReplicationSlot {
+            name: name.to_string(),
+            plugin,
+            restart_lsn,
+            confirmed_flush,
+            active: false,
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ReplicationSlotError {
+    #[error("Not enough replication slot data need {0} got {1}")]
+    BufferTooShort(usize, usize),
+    #[error("Not a feophant replication slot")]
+    BadMagic(),
+    #[error("Replication slot version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error(
+        "Replication slot name \"{0}\" can only have lower case letters, numbers and underscores"
+    )]
+    BadName(String),
+    #[error("Output plugin \"{0}\" is unknown, only pgoutput and test_decoding are supported")]
+    UnknownPlugin(String),
+    #[error(transparent)]
+    PluginNotUtf8(#[from] std::string::FromUtf8Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_slot_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut slot = ReplicationSlot::new("search_indexer", "pgoutput", 0x1000)?;
+        slot.confirmed_flush = 0x2000;
+        let mut serial = slot.serialize();
+        assert_eq!(ReplicationSlot::parse("search_indexer", &mut serial)?, slot);
+
+        assert!(matches!(
+            ReplicationSlot::new("../escape", "pgoutput", 0),
+            Err(ReplicationSlotError::BadName(_))
+        ));
+        assert!(matches!(
+            ReplicationSlot::new("indexer", "wal2json", 0),
+            Err(ReplicationSlotError::UnknownPlugin(_))
+        ));
+        assert!(matches!(
+            ReplicationSlot::parse("indexer", &mut Bytes::from_static(b"FEOPHANT")),
+            Err(ReplicationSlotError::BufferTooShort(_, 8))
+        ));
+        Ok(())
+    }
+}
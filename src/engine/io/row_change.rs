@@ -0,0 +1,257 @@
+//! A row inserted, updated or deleted in a user table, logged so logical decoding can turn the
+//! write ahead log back into changes. Like postgres with wal_level = logical the record carries
+//! what decoding needs, here that is the table's name, its columns and whole rows as text.
+//! Decoding never has to look at the catalogs so a change still decodes after its table is
+//! altered or dropped.
+//!
+//! Encoded little endian like the rest of the log:
+//! * A u8 kind, 1 for insert, 2 for update and 3 for delete
+//! * The transaction as a u64 and the relation's id
+//! * The relation's name, then a u16 count of columns each with its name, type name and type oid
+//! * The old row and then the new row, each a u8 flag for present followed by a value per column.
+//!   A value is a u8 flag for not null followed by its text.
+//!
+//! Text is a u32 length followed by utf8.
+use crate::constants::BuiltinSqlTypes;
+use crate::engine::objects::{SqlTuple, Table};
+use crate::engine::transactions::TransactionId;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RowChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeColumn {
+    pub name: String,
+    pub type_name: String,
+    pub type_oid: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowChange {
+    pub kind: RowChangeKind,
+    pub tran_id: TransactionId,
+    pub relation: Uuid,
+    pub relation_name: String,
+    pub columns: Vec<ChangeColumn>,
+    ///The row before an update or delete
+    pub old: Option<Vec<Option<String>>>,
+    ///The row after an insert or update
+    pub new: Option<Vec<Option<String>>>,
+}
+
+impl RowChange {
+    pub fn new(
+        kind: RowChangeKind,
+        tran_id: TransactionId,
+        table: &Table,
+        old: Option<&SqlTuple>,
+        new: Option<&SqlTuple>,
+    ) -> RowChange {
+        RowChange {
+            kind,
+            tran_id,
+            relation: table.id,
+            relation_name: table.name.clone(),
+            columns: table
+                .attributes
+                .iter()
+                .map(|a| ChangeColumn {
+                    name: a.name.clone(),
+                    type_name: a.sql_type.to_string(),
+                    type_oid: a.sql_type.oid(),
+                })
+                .collect(),
+            old: old.map(RowChange::text_values),
+            new: new.map(RowChange::text_values),
+        }
+    }
+
+    pub fn serialize(&self, buffer: &mut BytesMut) {
+        buffer.put_u8(match self.kind {
+            RowChangeKind::Insert => 1,
+            RowChangeKind::Update => 2,
+            RowChangeKind::Delete => 3,
+        });
+        buffer.put_u64_le(self.tran_id.get_u64());
+        buffer.put_slice(self.relation.as_bytes());
+        RowChange::put_text(buffer, &self.relation_name);
+        buffer.put_u16_le(self.columns.len() as u16);
+        for c in self.columns.iter() {
+            RowChange::put_text(buffer, &c.name);
+            RowChange::put_text(buffer, &c.type_name);
+            buffer.put_u32_le(c.type_oid);
+        }
+        for row in [&self.old, &self.new] {
+            match row {
+                Some(values) => {
+                    buffer.put_u8(1);
+                    for v in values {
+                        match v {
+                            Some(v) => {
+                                buffer.put_u8(1);
+                                RowChange::put_text(buffer, v);
+                            }
+                            None => buffer.put_u8(0),
+                        }
+                    }
+                }
+                None => buffer.put_u8(0),
+            }
+        }
+    }
+
+    /// None if the buffer is too short or not utf8
+    pub fn parse(buffer: &mut Bytes) -> Option<RowChange> {
+        if buffer.remaining() < 1 + 8 + 16 {
+            return None;
+        }
+        let kind = match buffer.get_u8() {
+            1 => RowChangeKind::Insert,
+            2 => RowChangeKind::Update,
+            3 => RowChangeKind::Delete,
+            _ => return None,
+        };
+        let tran_id = TransactionId::new(buffer.get_u64_le());
+        let relation = Uuid::from_slice(&buffer.split_to(16)).ok()?;
+        let relation_name = RowChange::get_text(buffer)?;
+
+        if buffer.remaining() < 2 {
+            return None;
+        }
+        let mut columns = vec![];
+        for _ in 0..buffer.get_u16_le() {
+            let name = RowChange::get_text(buffer)?;
+            let type_name = RowChange::get_text(buffer)?;
+            if buffer.remaining() < 4 {
+                return None;
+            }
+            columns.push(ChangeColumn {
+                name,
+                type_name,
+                type_oid: buffer.get_u32_le(),
+            });
+        }
+
+        let old = RowChange::get_row(buffer, columns.len())?;
+        let new = RowChange::get_row(buffer, columns.len())?;
+        Some(RowChange {
+            kind,
+            tran_id,
+            relation,
+            relation_name,
+            columns,
+            old,
+            new,
+        })
+    }
+
+    //Written the way a client sees them with the default settings
+    fn text_values(tuple: &SqlTuple) -> Vec<Option<String>> {
+        tuple
+            .0
+            .iter()
+            .map(|v| v.as_ref().map(BuiltinSqlTypes::to_string))
+            .collect()
+    }
+
+    fn put_text(buffer: &mut BytesMut, text: &str) {
+        buffer.put_u32_le(text.len() as u32);
+        buffer.put_slice(text.as_bytes());
+    }
+
+    fn get_text(buffer: &mut Bytes) -> Option<String> {
+        if buffer.remaining() < 4 {
+            return None;
+        }
+        let len = buffer.get_u32_le() as usize;
+        if buffer.remaining() < len {
+            return None;
+        }
+        String::from_utf8(buffer.split_to(len).to_vec()).ok()
+    }
+
+    //None if it can't be parsed, Some(None) if the row isn't there
+    fn get_row(buffer: &mut Bytes, columns: usize) -> Option<Option<Vec<Option<String>>>> {
+        if !buffer.has_remaining() {
+            return None;
+        }
+        if buffer.get_u8() == 0 {
+            return Some(None);
+        }
+        let mut values = Vec::with_capacity(columns);
+        for _ in 0..columns {
+            if !buffer.has_remaining() {
+                return None;
+            }
+            if buffer.get_u8() == 0 {
+                values.push(None);
+            } else {
+                values.push(Some(RowChange::get_text(buffer)?));
+            }
+        }
+        Some(Some(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{DeserializeTypes, Nullable};
+    use crate::engine::objects::Attribute;
+
+    #[test]
+    fn test_row_change_roundtrip() {
+        let id = Uuid::new_v4();
+        let table = Table::new_existing(
+            id,
+            "people".to_string(),
+            vec![
+                Attribute::new(
+                    id,
+                    "name".to_string(),
+                    DeserializeTypes::Text,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    id,
+                    "age".to_string(),
+                    DeserializeTypes::Integer,
+                    Nullable::Null,
+                ),
+            ],
+        );
+        let old = SqlTuple(vec![Some(BuiltinSqlTypes::Text("Bob".to_string())), None]);
+        let new = SqlTuple(vec![
+            Some(BuiltinSqlTypes::Text("Bob's".to_string())),
+            Some(BuiltinSqlTypes::Integer(42)),
+        ]);
+
+        let change = RowChange::new(
+            RowChangeKind::Update,
+            TransactionId::new(7),
+            &table,
+            Some(&old),
+            Some(&new),
+        );
+        assert_eq!(change.columns[1].type_name, "integer");
+        assert_eq!(change.columns[1].type_oid, 23);
+        assert_eq!(
+            change.new,
+            Some(vec![Some("Bob's".to_string()), Some("42".to_string())])
+        );
+
+        let mut buffer = BytesMut::new();
+        change.serialize(&mut buffer);
+        let mut buffer = buffer.freeze();
+        assert_eq!(RowChange::parse(&mut buffer), Some(change));
+        assert!(!buffer.has_remaining());
+
+        assert_eq!(RowChange::parse(&mut Bytes::from_static(&[1, 2, 3])), None);
+    }
+}
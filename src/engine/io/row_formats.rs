@@ -11,3 +11,8 @@ pub use null_mask::NullMask;
 mod row_data;
 pub use row_data::RowData;
 pub use row_data::RowDataError;
+
+mod toast_pointer;
+pub use toast_pointer::ExternalToast;
+pub use toast_pointer::ToastPointer;
+pub use toast_pointer::ToastPointerError;
@@ -2,7 +2,26 @@
 //! See here: https://doxygen.postgresql.org/htup__details_8h_source.html
 
 bitflags! {
-    pub struct InfoMask: u8 {
+    pub struct InfoMask: u16 {
         const HAS_NULL = 0b00000001;
+        const HAS_TOAST = 0b00000010;
+        ///A newer version of this row is on the same page, reached through item_pointer
+        const HOT_UPDATED = 0b00000100;
+        ///No index points at this row, it is only found by following a HOT chain
+        const HEAP_ONLY = 0b00001000;
+        ///Hint bits, what the clog said about min and max the last time they were looked up.
+        ///They are only set once the answer can't change.
+        const XMIN_COMMITTED = 0b00010000;
+        const XMIN_INVALID = 0b00100000;
+        const XMAX_COMMITTED = 0b01000000;
+        const XMAX_INVALID = 0b10000000;
+        const XMIN_HINTS = Self::XMIN_COMMITTED.bits | Self::XMIN_INVALID.bits;
+        ///Both min bits means frozen like postgres, min committed before anything still running
+        ///started so it will never need looking up again
+        const XMIN_FROZEN = Self::XMIN_HINTS.bits;
+        const XMAX_HINTS = Self::XMAX_COMMITTED.bits | Self::XMAX_INVALID.bits;
+        const HINTS = Self::XMIN_HINTS.bits | Self::XMAX_HINTS.bits;
+        ///Max is set, otherwise its space in the row is only kept so it can be set in place
+        const HAS_XMAX = 0b1_00000000;
     }
 }
@@ -1,62 +1,87 @@
 //! Item Pointers tell a row where the latest version of itself might be stored.
 //! Details here: https://www.postgresql.org/docs/current/storage-page-layout.html look at t_ctid
 //!
-//! We will be treating this a little different since our size will be based on usize
-use super::super::page_formats::{UInt12, UInt12Error};
+//! On disk the page is a little endian u32 like postgres' BlockNumber, so a relation can't grow past
+//! MAX_PAGE pages whatever the pointer width of the build.
+use super::super::page_formats::{UInt15, UInt15Error};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::fmt;
-use std::mem;
 use thiserror::Error;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct ItemPointer {
     pub page: usize,
-    pub count: UInt12,
+    pub count: UInt15,
 }
 
 impl ItemPointer {
-    pub fn new(page: usize, count: UInt12) -> ItemPointer {
+    pub const MAX_PAGE: usize = u32::MAX as usize;
+    pub const SERIALIZED_SIZE: usize = 6;
+
+    pub fn new(page: usize, count: UInt15) -> ItemPointer {
         ItemPointer { page, count }
     }
 
     pub fn serialize(&self) -> Bytes {
-        let mut buffer = BytesMut::with_capacity(mem::size_of::<ItemPointer>());
+        let mut buffer = BytesMut::with_capacity(ItemPointer::SERIALIZED_SIZE);
 
-        buffer.put_slice(&self.page.to_le_bytes());
+        //The row manager never makes a page past MAX_PAGE
+        buffer.put_u32_le(self.page as u32);
         buffer.put(self.count.serialize());
 
         buffer.freeze()
     }
 
     pub fn parse(buffer: &mut impl Buf) -> Result<Self, ItemPointerError> {
-        if buffer.remaining() < mem::size_of::<usize>() {
+        if buffer.remaining() < ItemPointer::SERIALIZED_SIZE {
             return Err(ItemPointerError::BufferTooShort(
-                mem::size_of::<usize>(),
+                ItemPointer::SERIALIZED_SIZE,
                 buffer.remaining(),
             ));
         }
 
-        let mut raw_page = [0; mem::size_of::<usize>()];
-        buffer.copy_to_slice(&mut raw_page);
-        let page = usize::from_le_bytes(raw_page);
+        let page = buffer.get_u32_le() as usize;
 
-        let count = UInt12::parse(buffer)?;
+        let count = UInt15::parse(buffer)?;
         Ok(ItemPointer::new(page, count))
     }
 }
 
 impl fmt::Display for ItemPointer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\tItemPointer\n")?;
-        write!(f, "\tPage: {}\n", self.page)?;
-        write!(f, "\tCount: {}\n", self.count)
+        writeln!(f, "\tItemPointer")?;
+        writeln!(f, "\tPage: {}", self.page)?;
+        writeln!(f, "\tCount: {}", self.count)
     }
 }
 
 #[derive(Debug, Error)]
 pub enum ItemPointerError {
-    #[error("Not enough space to parse usize need {0} got {1}")]
+    #[error("Not enough space to parse an item pointer need {0} got {1}")]
     BufferTooShort(usize, usize),
     #[error(transparent)]
-    U12ParseError(#[from] UInt12Error),
+    U15ParseError(#[from] UInt15Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::page_formats::{ItemIdData, PageHeader, PageSize};
+    use super::*;
+    use std::convert::TryFrom;
+    use std::mem;
+
+    #[test]
+    fn test_item_pointer_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        //The last line pointer any page size could hold
+        for page_size in PageSize::VALUES.iter() {
+            let max_count = (page_size.bytes() - mem::size_of::<PageHeader>())
+                / mem::size_of::<ItemIdData>()
+                - 1;
+            let test = ItemPointer::new(ItemPointer::MAX_PAGE, UInt15::try_from(max_count)?);
+            let mut serial = test.serialize();
+            assert_eq!(serial.len(), ItemPointer::SERIALIZED_SIZE);
+            assert_eq!(ItemPointer::parse(&mut serial)?, test);
+        }
+        Ok(())
+    }
 }
@@ -8,42 +8,25 @@ pub struct NullMask {}
 
 impl NullMask {
     pub fn serialize(input: &SqlTuple) -> Bytes {
-        if input.0.len() == 0 {
+        let flags: Vec<bool> = input.0.iter().map(|c| c.is_none()).collect();
+        NullMask::serialize_flags(&flags)
+    }
+
+    /// Packs the flags high bit first, returns nothing if none are set
+    pub fn serialize_flags(input: &[bool]) -> Bytes {
+        if !input.iter().any(|f| *f) {
             return Bytes::new();
         }
 
-        let mut buffer = BytesMut::new();
-        let mut any_null = false;
-
-        let mut value: u8 = 0;
-        let mut mask: u8 = 0x80;
-        let mut i = 0;
-        loop {
-            if input.0[i].is_none() {
-                value |= mask;
-                any_null = true;
-            }
-
-            if (i + 1) == input.0.len() {
-                if (i + 1) % 8 != 0 {
-                    buffer.put_u8(value);
+        let mut buffer = BytesMut::with_capacity(input.len().div_ceil(8));
+        for chunk in input.chunks(8) {
+            let mut value: u8 = 0;
+            for (i, f) in chunk.iter().enumerate() {
+                if *f {
+                    value |= 0x80 >> i;
                 }
-                break;
-            }
-
-            if (i + 1) % 8 == 0 && i > 0 {
-                buffer.put_u8(value);
-                value = 0;
-                mask = 0x80;
-            } else {
-                mask >>= 1;
             }
-
-            i += 1;
-        }
-
-        if !any_null {
-            return Bytes::new();
+            buffer.put_u8(value);
         }
         buffer.freeze()
     }
@@ -155,4 +138,14 @@ mod tests {
 
         assert_eq!(end, parse);
     }
+
+    #[test]
+    fn test_flags_roundtrip() {
+        let flags = vec![false, false, true, false, false, false, false, false, true];
+        let result = NullMask::serialize_flags(&flags);
+        assert_eq!(hex!("20 80").to_vec(), result.to_vec());
+        assert_eq!(NullMask::parse(&result, 9), flags);
+
+        assert_eq!(NullMask::serialize_flags(&[false; 9]), Bytes::new());
+    }
 }
@@ -4,13 +4,20 @@
 //!
 //! TODO Need to chew on if I should split the meta data and user data between two types
 //!
+//! On disk every field is fixed width and little endian so any build can read it:
+//! * min as a u64
+//! * max as a u64, only meaningful with InfoMask::HAS_XMAX so it can be set in place
+//! * item pointer as a u32 page and a u16 count
+//! * info mask as a u16
+//! * null and toast masks when the info mask says there are any
+//! * each column that isn't null
 use crate::constants::Nullable;
 use crate::engine::objects::SqlTuple;
 
 use super::super::super::super::constants::{BuiltinSqlTypes, DeserializeTypes, SqlTypeError};
 use super::super::super::objects::Table;
 use super::super::super::transactions::TransactionId;
-use super::{InfoMask, ItemPointer, ItemPointerError, NullMask};
+use super::{InfoMask, ItemPointer, ItemPointerError, NullMask, ToastPointer, ToastPointerError};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::fmt;
 use std::mem;
@@ -18,7 +25,7 @@ use std::sync::Arc;
 use thiserror::Error;
 
 ///Holds information about a particular row in a table as well as metadata.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct RowData {
     ///Table that the row belongs to
     table: Arc<Table>,
@@ -30,6 +37,29 @@ pub struct RowData {
     pub item_pointer: ItemPointer,
     ///Columns stored in this row
     pub user_data: Arc<SqlTuple>,
+    ///Columns whose value is compressed or stored out of line, one entry per column.
+    ///The pointer is what gets written, user_data is None for these columns until the row manager rebuilds them.
+    pub toast: Vec<Option<ToastPointer>>,
+    ///The next version of this row is a heap only row on the same page
+    pub hot_updated: bool,
+    ///This row was made by a HOT update so only the chain leads to it
+    pub heap_only: bool,
+    ///Only the hint bits of the mask, a cache of the clog for min and max so they don't need to
+    ///be looked up again. They are not part of the row so equality ignores them.
+    pub hints: InfoMask,
+}
+
+impl PartialEq for RowData {
+    fn eq(&self, other: &Self) -> bool {
+        self.table == other.table
+            && self.min == other.min
+            && self.max == other.max
+            && self.item_pointer == other.item_pointer
+            && self.user_data == other.user_data
+            && self.toast == other.toast
+            && self.hot_updated == other.hot_updated
+            && self.heap_only == other.heap_only
+    }
 }
 
 impl RowData {
@@ -40,24 +70,38 @@ impl RowData {
         item_pointer: ItemPointer,
         user_data: Arc<SqlTuple>,
     ) -> Result<RowData, RowDataError> {
-        if table.attributes.len() != user_data.0.len() {
+        let toast = vec![None; table.attributes.len()];
+        RowData::new_toasted(table, min, max, item_pointer, user_data, toast)
+    }
+
+    pub fn new_toasted(
+        table: Arc<Table>,
+        min: TransactionId,
+        max: Option<TransactionId>,
+        item_pointer: ItemPointer,
+        user_data: Arc<SqlTuple>,
+        toast: Vec<Option<ToastPointer>>,
+    ) -> Result<RowData, RowDataError> {
+        if table.attributes.len() != user_data.0.len() || table.attributes.len() != toast.len() {
             return Err(RowDataError::TableRowSizeMismatch(
                 table.attributes.len(),
                 user_data.0.len(),
             ));
         }
-        for (data, column) in user_data.0.iter().zip(table.attributes.clone()) {
+        for ((data, column), pointer) in
+            user_data.0.iter().zip(table.attributes.clone()).zip(&toast)
+        {
             match data {
                 Some(d) => {
-                    if !d.type_matches(column.sql_type) {
+                    if !d.type_matches(&column.sql_type) {
                         return Err(RowDataError::TableRowTypeMismatch(
                             d.clone(),
-                            column.sql_type,
+                            column.sql_type.clone(),
                         ));
                     }
                 }
                 None => {
-                    if column.nullable != Nullable::Null {
+                    if column.nullable != Nullable::Null && pointer.is_none() {
                         return Err(RowDataError::UnexpectedNull(column.name));
                     }
                 }
@@ -70,9 +114,21 @@ impl RowData {
             max,
             item_pointer,
             user_data,
+            toast,
+            hot_updated: false,
+            heap_only: false,
+            hints: InfoMask::empty(),
         })
     }
 
+    /// True if any column needs to be rebuilt from its toast pointer
+    pub fn needs_detoast(&self) -> bool {
+        self.toast
+            .iter()
+            .zip(self.user_data.0.iter())
+            .any(|(t, d)| t.is_some() && d.is_none())
+    }
+
     pub fn get_column(&self, name: String) -> Result<Option<BuiltinSqlTypes>, RowDataError> {
         for i in 0..self.table.attributes.len() {
             if self.table.attributes[i].name == name {
@@ -88,7 +144,7 @@ impl RowData {
             if self.table.attributes[i].name == name {
                 let data = self.user_data.0[i]
                     .as_ref()
-                    .ok_or_else(|| RowDataError::UnexpectedNull(name))?;
+                    .ok_or(RowDataError::UnexpectedNull(name))?;
                 return Ok(data.clone());
             }
         }
@@ -99,31 +155,47 @@ impl RowData {
     pub fn serialize(&self) -> Bytes {
         let mut buffer = BytesMut::new();
         buffer.put_u64_le(self.min.get_u64());
-        buffer.put_u64_le(self.max.unwrap_or(TransactionId::new(0)).get_u64());
+        buffer.put_u64_le(self.max.map_or(0, |m| m.get_u64()));
 
         buffer.put(self.item_pointer.serialize());
 
+        let nulls: Vec<bool> = self
+            .user_data
+            .0
+            .iter()
+            .zip(self.toast.iter())
+            .map(|(d, t)| d.is_none() && t.is_none())
+            .collect();
+        let toasted: Vec<bool> = self.toast.iter().map(|t| t.is_some()).collect();
+
         let mut mask = InfoMask::empty();
-        for i in self.user_data.0.iter() {
-            match i {
-                Some(_) => {}
-                None => {
-                    mask = InfoMask::HAS_NULL;
-                }
-            }
+        if nulls.contains(&true) {
+            mask |= InfoMask::HAS_NULL;
+        }
+        if toasted.contains(&true) {
+            mask |= InfoMask::HAS_TOAST;
+        }
+        if self.hot_updated {
+            mask |= InfoMask::HOT_UPDATED;
         }
-        buffer.put_u8(mask.bits());
+        if self.heap_only {
+            mask |= InfoMask::HEAP_ONLY;
+        }
+        if self.max.is_some() {
+            mask |= InfoMask::HAS_XMAX;
+        }
+        mask |= self.hints & InfoMask::HINTS;
+        buffer.put_u16_le(mask.bits());
 
-        let nulls = NullMask::serialize(&self.user_data);
-        buffer.put(nulls);
+        buffer.put(NullMask::serialize_flags(&nulls));
+        buffer.put(NullMask::serialize_flags(&toasted));
 
-        for data in &self.user_data.0 {
-            if data.is_none() {
-                continue;
+        for (data, pointer) in self.user_data.0.iter().zip(self.toast.iter()) {
+            match (data, pointer) {
+                (_, Some(p)) => buffer.put(p.serialize()),
+                (Some(d), None) => buffer.put(d.serialize()),
+                (None, None) => {}
             }
-
-            let data_bytes = data.as_ref().unwrap().serialize();
-            buffer.extend_from_slice(&data_bytes);
         }
 
         buffer.freeze()
@@ -144,49 +216,76 @@ impl RowData {
                 row_buffer.remaining(),
             ));
         }
-        let max_temp = row_buffer.get_u64_le();
-        let max = match max_temp {
-            0 => None,
-            _ => Some(TransactionId::new(max_temp)),
-        };
+        let max_temp = TransactionId::new(row_buffer.get_u64_le());
 
         let item_pointer = ItemPointer::parse(&mut row_buffer)?;
 
-        let null_mask = RowData::get_null_mask(table.clone(), &mut row_buffer)?;
+        if row_buffer.remaining() < mem::size_of::<InfoMask>() {
+            return Err(RowDataError::MissingInfoMaskData(
+                mem::size_of::<InfoMask>(),
+                row_buffer.remaining(),
+            ));
+        }
+        let mask = InfoMask::from_bits_truncate(row_buffer.get_u16_le()); //Ignoring unused bits
+        let max = if mask.contains(InfoMask::HAS_XMAX) {
+            Some(max_temp)
+        } else {
+            None
+        };
+
+        let null_mask = RowData::get_mask(
+            table.clone(),
+            mask.contains(InfoMask::HAS_NULL),
+            &mut row_buffer,
+        )?;
+        let toast_mask = RowData::get_mask(
+            table.clone(),
+            mask.contains(InfoMask::HAS_TOAST),
+            &mut row_buffer,
+        )?;
 
         let mut user_data = SqlTuple(vec![]);
-        for (column, mask) in table.attributes.iter().zip(null_mask.iter()) {
-            if *mask {
+        let mut toast = vec![];
+        for ((column, null), toasted) in table
+            .attributes
+            .iter()
+            .zip(null_mask.iter())
+            .zip(toast_mask.iter())
+        {
+            if *toasted {
                 user_data.0.push(None);
+                toast.push(Some(ToastPointer::parse(&mut row_buffer)?));
+            } else if *null {
+                user_data.0.push(None);
+                toast.push(None);
             } else {
                 user_data.0.push(Some(BuiltinSqlTypes::deserialize(
-                    column.sql_type,
+                    column.sql_type.clone(),
                     &mut row_buffer,
                 )?));
+                toast.push(None);
             }
         }
 
-        RowData::new(table, min, max, item_pointer, Arc::new(user_data))
+        let mut row =
+            RowData::new_toasted(table, min, max, item_pointer, Arc::new(user_data), toast)?;
+        row.hot_updated = mask.contains(InfoMask::HOT_UPDATED);
+        row.heap_only = mask.contains(InfoMask::HEAP_ONLY);
+        row.hints = mask & InfoMask::HINTS;
+        Ok(row)
     }
 
-    //Gets the null mask, if it doesn't exist it will return a vector of all not nulls
-    fn get_null_mask(
+    //Gets a column bit mask, if it isn't flagged as present it will return a vector of all false
+    fn get_mask(
         table: Arc<Table>,
+        present: bool,
         mut row_buffer: impl Buf,
     ) -> Result<Vec<bool>, RowDataError> {
-        if row_buffer.remaining() < mem::size_of::<InfoMask>() {
-            return Err(RowDataError::MissingInfoMaskData(
-                mem::size_of::<TransactionId>(),
-                row_buffer.remaining(),
-            ));
-        }
-
-        let mask = InfoMask::from_bits_truncate(row_buffer.get_u8()); //Ignoring unused bits
-        if !mask.contains(InfoMask::HAS_NULL) {
+        if !present {
             return Ok(vec![false; table.attributes.len()]);
         }
 
-        let columns_rounded = (table.attributes.len() + 7) / 8; //From https://users.rust-lang.org/t/solved-rust-round-usize-to-nearest-multiple-of-8/25549
+        let columns_rounded = table.attributes.len().div_ceil(8); //From https://users.rust-lang.org/t/solved-rust-round-usize-to-nearest-multiple-of-8/25549
         if row_buffer.remaining() < columns_rounded {
             return Err(RowDataError::MissingNullMaskData(
                 columns_rounded,
@@ -194,25 +293,26 @@ impl RowData {
             ));
         }
 
-        let null_mask_raw = row_buffer.copy_to_bytes(columns_rounded);
-        Ok(NullMask::parse(&null_mask_raw, table.attributes.len()))
+        let mask_raw = row_buffer.copy_to_bytes(columns_rounded);
+        Ok(NullMask::parse(&mask_raw, table.attributes.len()))
     }
 }
 
 impl fmt::Display for RowData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "RowData\n")?;
-        write!(f, "\tTable: {}\n", self.table.name)?;
-        write!(f, "\tMin Tran: {}\n", self.min)?;
+        writeln!(f, "RowData")?;
+        writeln!(f, "\tTable: {}", self.table.name)?;
+        writeln!(f, "\tMin Tran: {}", self.min)?;
         match self.max {
-            Some(m) => write!(f, "\tMax Tran: {}\n", m),
-            None => write!(f, "\tMax Tran: Unset\n"),
+            Some(m) => writeln!(f, "\tMax Tran: {}", m),
+            None => writeln!(f, "\tMax Tran: Unset"),
         }?;
-        write!(f, "\t{}\n", self.item_pointer)?;
-        for column in &self.user_data.0 {
-            match column {
-                Some(c) => write!(f, "\t{}\n", c),
-                None => write!(f, "\tNull\n"),
+        writeln!(f, "\t{}", self.item_pointer)?;
+        for (column, pointer) in self.user_data.0.iter().zip(self.toast.iter()) {
+            match (column, pointer) {
+                (Some(c), _) => writeln!(f, "\t{}", c),
+                (None, Some(p)) => writeln!(f, "\tToast {}", p),
+                (None, None) => writeln!(f, "\tNull"),
             }?;
         }
         Ok(())
@@ -237,6 +337,8 @@ pub enum RowDataError {
     ColumnParseError(#[from] SqlTypeError),
     #[error(transparent)]
     ItemPointerError(#[from] ItemPointerError),
+    #[error(transparent)]
+    ToastPointerError(#[from] ToastPointerError),
     #[error("Column named {0} does not exist")]
     ColumnDoesNotExist(String),
     #[error("Column null when ask not to be {0}")]
@@ -248,11 +350,11 @@ mod tests {
     use crate::constants::Nullable;
 
     use super::super::super::super::objects::Attribute;
-    use super::super::super::page_formats::UInt12;
+    use super::super::super::page_formats::UInt15;
     use super::*;
 
     fn get_item_pointer() -> ItemPointer {
-        ItemPointer::new(0, UInt12::new(0).unwrap())
+        ItemPointer::new(0, UInt15::new(0).unwrap())
     }
 
     #[test]
@@ -279,8 +381,26 @@ mod tests {
         .unwrap();
 
         let test_serial = test.serialize();
-        let test_parse = RowData::parse(table, test_serial).unwrap();
+        let test_serial_len = test_serial.len();
+        let test_parse = RowData::parse(table.clone(), test_serial).unwrap();
         assert_eq!(test, test_parse);
+
+        let mut hot = test;
+        hot.hot_updated = true;
+        hot.heap_only = true;
+        let hot_parse = RowData::parse(table.clone(), hot.serialize()).unwrap();
+        assert_eq!(hot, hot_parse);
+
+        hot.hints = InfoMask::XMIN_COMMITTED | InfoMask::XMAX_INVALID;
+        let hint_parse = RowData::parse(table.clone(), hot.serialize()).unwrap();
+        assert_eq!(hint_parse.hints, hot.hints);
+
+        //Setting max in place keeps the length, and every transaction id survives
+        hot.max = Some(TransactionId::new(0));
+        let max_serial = hot.serialize();
+        assert_eq!(max_serial.len(), test_serial_len);
+        let max_parse = RowData::parse(table, max_serial).unwrap();
+        assert_eq!(max_parse.max, Some(TransactionId::new(0)));
     }
 
     #[test]
@@ -472,4 +592,58 @@ mod tests {
             BuiltinSqlTypes::Text("this is a test".to_string())
         );
     }
+
+    #[test]
+    fn test_row_data_toast_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let table = Arc::new(Table::new(
+            "test_table".to_string(),
+            vec![
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    "header".to_string(),
+                    DeserializeTypes::Text,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    "header2".to_string(),
+                    DeserializeTypes::Text,
+                    Nullable::Null,
+                ),
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    "header3".to_string(),
+                    DeserializeTypes::Text,
+                    Nullable::NotNull,
+                ),
+            ],
+        ));
+
+        let big = BuiltinSqlTypes::Text("test ".repeat(100));
+        let toast = vec![
+            Some(ToastPointer::Compressed(ToastPointer::compress(
+                &big.serialize(),
+            ))),
+            None,
+            Some(ToastPointer::External(super::super::ExternalToast::new(
+                5000, 4000, true,
+            )?)),
+        ];
+        let test = RowData::new_toasted(
+            table.clone(),
+            TransactionId::new(1),
+            None,
+            get_item_pointer(),
+            Arc::new(SqlTuple(vec![Some(big.clone()), None, Some(big)])),
+            toast.clone(),
+        )?;
+        assert!(!test.needs_detoast());
+
+        let test_parse = RowData::parse(table, test.serialize())?;
+        assert!(test_parse.needs_detoast());
+        assert_eq!(test_parse.toast, toast);
+        assert_eq!(test_parse.user_data.0, vec![None, None, None]);
+        assert_eq!(test_parse.serialize(), test.serialize());
+        Ok(())
+    }
 }
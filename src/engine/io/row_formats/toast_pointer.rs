@@ -0,0 +1,243 @@
+//! Stands in for a column value that was too large to store in its row as is.
+//! Loosely based on: https://www.postgresql.org/docs/current/storage-toast.html
+//!
+//! The value being pointed to is always the column's normal serialized form, so rebuilding it is just
+//! deserializing the bytes once they are put back together.
+//!
+//! On disk a pointer is a tag byte followed by:
+//! * Compressed - u32 length then the lz4 compressed value
+//! * External / External Compressed - chunk_id uuid, raw size u32, stored size u32
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryFrom;
+use std::fmt;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToastPointer {
+    ///Compressed but still kept in the row
+    Compressed(Bytes),
+    ///Split into chunks in the table's toast relation
+    External(ExternalToast),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExternalToast {
+    ///Shared by all the chunks holding this value
+    pub chunk_id: Uuid,
+    ///Size once rebuilt and decompressed
+    pub raw_size: u32,
+    ///Size of the chunks put back together
+    pub stored_size: u32,
+    pub compressed: bool,
+}
+
+const TAG_COMPRESSED: u8 = 1;
+const TAG_EXTERNAL: u8 = 2;
+const TAG_EXTERNAL_COMPRESSED: u8 = 3;
+
+impl ToastPointer {
+    pub fn serialize(&self) -> Bytes {
+        let mut buffer = BytesMut::new();
+        match self {
+            ToastPointer::Compressed(data) => {
+                buffer.put_u8(TAG_COMPRESSED);
+                buffer.put_u32_le(data.len() as u32);
+                buffer.extend_from_slice(data);
+            }
+            ToastPointer::External(e) => {
+                if e.compressed {
+                    buffer.put_u8(TAG_EXTERNAL_COMPRESSED);
+                } else {
+                    buffer.put_u8(TAG_EXTERNAL);
+                }
+                buffer.extend_from_slice(e.chunk_id.as_bytes());
+                buffer.put_u32_le(e.raw_size);
+                buffer.put_u32_le(e.stored_size);
+            }
+        }
+        buffer.freeze()
+    }
+
+    pub fn parse(buffer: &mut impl Buf) -> Result<ToastPointer, ToastPointerError> {
+        if !buffer.has_remaining() {
+            return Err(ToastPointerError::BufferTooShort(1, 0));
+        }
+
+        match buffer.get_u8() {
+            TAG_COMPRESSED => {
+                ToastPointer::check_remaining(buffer, 4)?;
+                let length = buffer.get_u32_le() as usize;
+                ToastPointer::check_remaining(buffer, length)?;
+                Ok(ToastPointer::Compressed(buffer.copy_to_bytes(length)))
+            }
+            tag @ TAG_EXTERNAL | tag @ TAG_EXTERNAL_COMPRESSED => {
+                ToastPointer::check_remaining(buffer, 24)?;
+                let mut raw_id = [0; 16];
+                buffer.copy_to_slice(&mut raw_id);
+                Ok(ToastPointer::External(ExternalToast {
+                    chunk_id: Uuid::from_bytes(raw_id),
+                    raw_size: buffer.get_u32_le(),
+                    stored_size: buffer.get_u32_le(),
+                    compressed: tag == TAG_EXTERNAL_COMPRESSED,
+                }))
+            }
+            tag => Err(ToastPointerError::UnknownTag(tag)),
+        }
+    }
+
+    fn check_remaining(buffer: &impl Buf, needed: usize) -> Result<(), ToastPointerError> {
+        if buffer.remaining() < needed {
+            return Err(ToastPointerError::BufferTooShort(
+                needed,
+                buffer.remaining(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn compress(raw: &[u8]) -> Bytes {
+        Bytes::from(lz4_flex::compress_prepend_size(raw))
+    }
+
+    pub fn decompress(stored: &[u8]) -> Result<Bytes, ToastPointerError> {
+        Ok(Bytes::from(lz4_flex::decompress_size_prepended(stored)?))
+    }
+
+    /// Puts a value's serialized form back together from what the pointer holds, for external values
+    /// the chunks must be supplied in order.
+    pub fn rebuild(&self, chunks: &[Bytes]) -> Result<Bytes, ToastPointerError> {
+        match self {
+            ToastPointer::Compressed(data) => ToastPointer::decompress(data),
+            ToastPointer::External(e) => {
+                let mut stored = BytesMut::with_capacity(e.stored_size as usize);
+                for c in chunks {
+                    stored.extend_from_slice(c);
+                }
+                if stored.len() != e.stored_size as usize {
+                    return Err(ToastPointerError::SizeMismatch(
+                        e.chunk_id,
+                        e.stored_size as usize,
+                        stored.len(),
+                    ));
+                }
+
+                let raw = if e.compressed {
+                    ToastPointer::decompress(&stored)?
+                } else {
+                    stored.freeze()
+                };
+                if raw.len() != e.raw_size as usize {
+                    return Err(ToastPointerError::SizeMismatch(
+                        e.chunk_id,
+                        e.raw_size as usize,
+                        raw.len(),
+                    ));
+                }
+                Ok(raw)
+            }
+        }
+    }
+
+    /// Bytes this takes up in its row
+    pub fn stored_len(&self) -> usize {
+        match self {
+            ToastPointer::Compressed(data) => 5 + data.len(),
+            ToastPointer::External(_) => 25,
+        }
+    }
+}
+
+impl ExternalToast {
+    pub fn new(
+        raw_size: usize,
+        stored_size: usize,
+        compressed: bool,
+    ) -> Result<Self, ToastPointerError> {
+        Ok(ExternalToast {
+            chunk_id: Uuid::new_v4(),
+            raw_size: u32::try_from(raw_size)?,
+            stored_size: u32::try_from(stored_size)?,
+            compressed,
+        })
+    }
+}
+
+impl fmt::Display for ToastPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToastPointer::Compressed(data) => write!(f, "Compressed {} bytes", data.len()),
+            ToastPointer::External(e) => write!(
+                f,
+                "External {} {} bytes stored as {}",
+                e.chunk_id, e.raw_size, e.stored_size
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ToastPointerError {
+    #[error("Not enough toast pointer data need {0} got {1}")]
+    BufferTooShort(usize, usize),
+    #[error("Unknown toast pointer tag {0}")]
+    UnknownTag(u8),
+    #[error("Toast value {0} expected {1} bytes got {2}")]
+    SizeMismatch(Uuid, usize, usize),
+    #[error("Value too large to toast")]
+    ValueTooLarge(#[from] std::num::TryFromIntError),
+    #[error(transparent)]
+    DecompressError(#[from] lz4_flex::block::DecompressError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressed_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let raw = "feophant ".repeat(500);
+        let pointer = ToastPointer::Compressed(ToastPointer::compress(raw.as_bytes()));
+        assert!(pointer.stored_len() < raw.len());
+
+        let mut serial = pointer.serialize();
+        assert_eq!(serial.len(), pointer.stored_len());
+        let parsed = ToastPointer::parse(&mut serial)?;
+        assert_eq!(parsed, pointer);
+        assert_eq!(parsed.rebuild(&[])?, Bytes::from(raw));
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let raw = Bytes::from((0..3000).map(|i| (i % 251) as u8).collect::<Vec<u8>>());
+        let pointer = ToastPointer::External(ExternalToast::new(raw.len(), raw.len(), false)?);
+
+        let mut serial = pointer.serialize();
+        assert_eq!(serial.len(), pointer.stored_len());
+        let parsed = ToastPointer::parse(&mut serial)?;
+        assert_eq!(parsed, pointer);
+
+        let chunks = vec![raw.slice(0..1000), raw.slice(1000..2000), raw.slice(2000..)];
+        assert_eq!(parsed.rebuild(&chunks)?, raw);
+        assert!(matches!(
+            parsed.rebuild(&chunks[0..2]),
+            Err(ToastPointerError::SizeMismatch(_, 3000, 2000))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_pointer() {
+        let mut buffer = Bytes::from_static(&[9]);
+        assert!(matches!(
+            ToastPointer::parse(&mut buffer),
+            Err(ToastPointerError::UnknownTag(9))
+        ));
+        let mut buffer = Bytes::from_static(&[TAG_EXTERNAL, 1, 2]);
+        assert!(matches!(
+            ToastPointer::parse(&mut buffer),
+            Err(ToastPointerError::BufferTooShort(24, 2))
+        ));
+    }
+}
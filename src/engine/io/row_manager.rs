@@ -1,25 +1,96 @@
 use super::super::objects::Table;
 use super::super::transactions::TransactionId;
-use super::page_formats::{PageData, PageDataError, UInt12};
-use super::row_formats::{ItemPointer, RowData, RowDataError};
-use super::{IOManager, IOManagerError};
+use super::page_formats::{ItemIdData, PageData, PageDataError, PageHeader, UInt15};
+use super::row_formats::{
+    ExternalToast, InfoMask, ItemPointer, RowData, RowDataError, ToastPointer, ToastPointerError,
+};
+use super::{
+    ForkNumber, FreeSpaceManager, FreeSpaceManagerError, IOManager, IOManagerError, LockManager,
+    RowChange, RowChangeKind, VacuumStats, VisibilityBits, VisibilityMap, VisibilityMapError,
+};
+use crate::constants::{BuiltinSqlTypes, PgErrorCodes, SqlTypeError, TableDefinitions};
 use crate::engine::objects::SqlTuple;
 use async_stream::try_stream;
-use futures::stream::Stream;
+use bytes::Bytes;
+use futures::pin_mut;
+use futures::stream::{Stream, StreamExt};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::mem::size_of;
+
 use std::sync::Arc;
 use thiserror::Error;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+//Rows serialized larger than a quarter page get toasted like postgres
+const TOAST_TUPLES_PER_PAGE: usize = 4;
+
+//Room left in a chunk's quarter of the page for its row header, item id and other columns
+const TOAST_CHUNK_OVERHEAD: usize = 64;
+
+//Values this small can't be made meaningfully smaller
+const TOAST_MIN_VALUE_SIZE: usize = 32;
+
+//Repack replays the log without blocking writers until there is no more than this left
+const REPACK_LOG_BATCH: usize = 64;
+
+//Relations being repacked and the log relation their changes are recorded in
+type RepackLogs = Arc<RwLock<HashMap<Uuid, Arc<Table>>>>;
 
 /// The row manager is a mapper between rows and pages on disk.
 ///
 /// It operates at the lowest lever, no visibility checks are done.
+///
+/// Rows too large for a page are toasted on the way in, first by compressing their largest values
+/// and then by moving them out to chunks in the table's toast relation. The chunks are written with
+/// the same transaction as their row and deleted along with it, so the row's visibility covers them.
+///
+/// Every page written here has its free space reported to the free space manager, which is how
+/// inserts pick a page. Changing a page also clears its bits in the visibility map. Bulk loads
+/// go through insert_rows, which fills a page with many rows before writing it.
+///
+/// Changes hold the relation's lock shared, while a relation is being repacked the location of
+/// every row changed is also written to its repack log.
+///
+/// Rows inserted, updated or deleted in user tables are also logged whole for logical decoding,
+/// see RowChange.
 #[derive(Clone, Debug)]
 pub struct RowManager {
     io_manager: IOManager,
+    free_space_manager: FreeSpaceManager,
+    lock_manager: LockManager,
+    repack_logs: RepackLogs,
+    visibility_map: VisibilityMap,
+}
+
+/// A repack in progress, see RowManager::start_repack
+#[derive(Debug)]
+pub struct RepackState {
+    table: Arc<Table>,
+    copy: Arc<Table>,
+    log: Arc<Table>,
+    //Where each row of the table was copied to and the transaction that inserted it
+    copied: HashMap<ItemPointer, (ItemPointer, TransactionId)>,
+    //How many log entries have been applied
+    replayed: usize,
 }
 
 impl RowManager {
     pub fn new(io_manager: IOManager) -> RowManager {
-        RowManager { io_manager }
+        RowManager {
+            free_space_manager: FreeSpaceManager::new(io_manager.clone()),
+            visibility_map: VisibilityMap::new(io_manager.clone()),
+            io_manager,
+            lock_manager: LockManager::new(),
+            repack_logs: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// The storage the rows are kept in
+    pub fn io_manager(&self) -> &IOManager {
+        &self.io_manager
     }
 
     pub async fn insert_row(
@@ -28,9 +99,59 @@ impl RowManager {
         table: Arc<Table>,
         user_data: Arc<SqlTuple>,
     ) -> Result<ItemPointer, RowManagerError> {
-        //let io_mut = self.io_manager.write().await;
-        RowManager::insert_row_internal(self.io_manager.clone(), current_tran_id, table, user_data)
-            .await
+        let row = self
+            .toast_row(current_tran_id, table.clone(), user_data.clone())
+            .await?;
+        let row_pointer = self.insert_row_internal(table.clone(), row).await?;
+        self.log_row_change(
+            RowChangeKind::Insert,
+            current_tran_id,
+            &table,
+            None,
+            Some(&user_data),
+        )
+        .await?;
+        Ok(row_pointer)
+    }
+
+    /// Inserts many rows at once for bulk loads like COPY. The relation is locked once and each
+    /// page is filled with as many of the rows as fit before it is written, instead of every row
+    /// reading and writing its page. The pointers are returned in the order of the rows.
+    pub async fn insert_rows(
+        self,
+        current_tran_id: TransactionId,
+        table: Arc<Table>,
+        user_data: Vec<Arc<SqlTuple>>,
+    ) -> Result<Vec<ItemPointer>, RowManagerError> {
+        let mut rows = Vec::with_capacity(user_data.len());
+        for data in user_data.iter() {
+            rows.push(
+                self.toast_row(current_tran_id, table.clone(), data.clone())
+                    .await?,
+            );
+        }
+
+        let row_pointers = {
+            let _lock = self.lock_manager.shared(table.id).await;
+            let row_pointers = self.insert_rows_unlocked(table.clone(), rows).await?;
+            for row_pointer in row_pointers.iter() {
+                self.log_change(current_tran_id, table.clone(), *row_pointer)
+                    .await?;
+            }
+            row_pointers
+        };
+
+        for data in user_data.iter() {
+            self.log_row_change(
+                RowChangeKind::Insert,
+                current_tran_id,
+                &table,
+                None,
+                Some(data),
+            )
+            .await?;
+        }
+        Ok(row_pointers)
     }
 
     //Note this is a logical delete
@@ -41,26 +162,40 @@ impl RowManager {
         table: Arc<Table>,
         row_pointer: ItemPointer,
     ) -> Result<(), RowManagerError> {
-        let (mut page, mut row) = self.get(table.clone(), row_pointer).await?;
+        let lock = self.lock_manager.shared(table.id).await;
+        let (mut page, mut row) = self.get_raw(table.clone(), row_pointer).await?;
 
-        if row.max.is_some() {
-            return Err(RowManagerError::AlreadyDeleted(
-                row_pointer.count,
-                row.max.unwrap(),
-            ));
+        if let Some(max) = row.max {
+            return Err(RowManagerError::AlreadyDeleted(row_pointer.count, max));
         }
 
+        let old_row = row.clone();
         row.max = Some(current_tran_id);
+        row.hints.remove(InfoMask::XMAX_HINTS);
+        let toast = row.toast.clone();
 
         page.update(row, row_pointer.count)?;
 
-        self.io_manager
-            .update_page(table, page.serialize(), row_pointer.page)
+        self.write_page(table.clone(), &page, row_pointer.page)
             .await?;
-        Ok(())
+        self.log_change(current_tran_id, table.clone(), row_pointer)
+            .await?;
+        self.log_row_change(
+            RowChangeKind::Delete,
+            current_tran_id,
+            &table,
+            Some(old_row),
+            None,
+        )
+        .await?;
+        drop(lock);
+
+        self.delete_toast(current_tran_id, table, &toast).await
     }
 
     //Note this is an insert new row, delete old row operation
+    //If the new version fits on the old page and no indexed column changed it is a heap only
+    //tuple, found by following the old version instead of through its own index entries
     pub async fn update_row(
         &mut self,
         current_tran_id: TransactionId,
@@ -68,67 +203,120 @@ impl RowManager {
         row_pointer: ItemPointer,
         new_user_data: Arc<SqlTuple>,
     ) -> Result<(), RowManagerError> {
+        let lock = self.lock_manager.shared(table.id).await;
+
         //First get the current row so we have it for the update/delete
-        let (mut old_page, mut old_row) = self.get(table.clone(), row_pointer).await?;
+        let (mut old_page, mut old_row) = self.get_raw(table.clone(), row_pointer).await?;
 
-        if old_row.max.is_some() {
-            return Err(RowManagerError::AlreadyDeleted(
-                row_pointer.count,
-                old_row.max.unwrap(),
-            ));
+        if let Some(max) = old_row.max {
+            return Err(RowManagerError::AlreadyDeleted(row_pointer.count, max));
         }
 
-        //Serialize with a dummy pointer so we can evaluate space needed for the new row
-        let new_row = RowData::new(
-            table.clone(),
-            current_tran_id,
-            None,
-            ItemPointer::new(0, UInt12::new(0).unwrap()),
-            new_user_data.clone(),
-        )?;
+        let before = old_row.clone();
+
+        //Toasted with a dummy pointer so we can evaluate space needed for the new row
+        let mut new_row = self
+            .toast_row(current_tran_id, table.clone(), new_user_data.clone())
+            .await?;
         let new_row_len = new_row.serialize().len();
 
         //Prefer using the old page if possible
-        let new_row_pointer;
-        if old_page.can_fit(new_row_len) {
-            new_row_pointer = old_page.insert(new_row)?;
+        let new_row_pointer = if old_page.can_fit(new_row_len) {
+            let hot = !RowManager::changes_indexed_columns(&old_row, &new_row);
+            old_row.hot_updated = hot;
+            new_row.heap_only = hot;
+            old_page.insert(new_row)?
         } else {
-            new_row_pointer = RowManager::insert_row_internal(
-                self.io_manager.clone(),
-                current_tran_id,
-                table.clone(),
-                new_user_data,
-            )
-            .await?;
-        }
+            self.insert_row_unlocked(table.clone(), new_row).await?
+        };
 
         old_row.max = Some(current_tran_id);
+        old_row.hints.remove(InfoMask::XMAX_HINTS);
         old_row.item_pointer = new_row_pointer;
+        let old_toast = old_row.toast.clone();
 
         old_page.update(old_row, row_pointer.count)?;
 
-        self.io_manager
-            .update_page(table, old_page.serialize(), row_pointer.page)
+        self.write_page(table.clone(), &old_page, row_pointer.page)
+            .await?;
+        self.log_change(current_tran_id, table.clone(), row_pointer)
+            .await?;
+        self.log_change(current_tran_id, table.clone(), new_row_pointer)
             .await?;
+        self.log_row_change(
+            RowChangeKind::Update,
+            current_tran_id,
+            &table,
+            Some(before),
+            Some(&new_user_data),
+        )
+        .await?;
+        drop(lock);
 
-        return Ok(());
+        self.delete_toast(current_tran_id, table, &old_toast).await
+    }
+
+    /// Gets the versions of a row reachable from row_pointer, see PageData::hot_chain.
+    ///
+    /// This is how a row is found from an index entry, the rows are as stored so only use get to
+    /// fetch the version wanted.
+    pub async fn get_chain(
+        &self,
+        table: Arc<Table>,
+        row_pointer: ItemPointer,
+    ) -> Result<Vec<(ItemPointer, RowData)>, RowManagerError> {
+        let page = self
+            .get_page(table, row_pointer.page)
+            .await?
+            .ok_or(RowManagerError::NonExistentPage(row_pointer.page))?;
+        let chain: Vec<(ItemPointer, RowData)> = page
+            .hot_chain(row_pointer.count)
+            .into_iter()
+            .map(|(c, r)| (ItemPointer::new(row_pointer.page, c), r.clone()))
+            .collect();
+        if chain.is_empty() {
+            return Err(RowManagerError::NonExistentRow(
+                row_pointer.count,
+                row_pointer.page,
+            ));
+        }
+        Ok(chain)
     }
 
     pub async fn get(
         &self,
         table: Arc<Table>,
         row_pointer: ItemPointer,
+    ) -> Result<(PageData, RowData), RowManagerError> {
+        let (page, row) = self.get_raw(table.clone(), row_pointer).await?;
+        let row = RowManager::detoast(self.io_manager.clone(), table, row).await?;
+        Ok((page, row))
+    }
+
+    //Gets the row as stored, toasted values are left as their pointers
+    async fn get_raw(
+        &self,
+        table: Arc<Table>,
+        row_pointer: ItemPointer,
     ) -> Result<(PageData, RowData), RowManagerError> {
         let page_bytes = self
             .io_manager
             .get_page(table.clone(), row_pointer.page)
             .await
-            .ok_or_else(|| RowManagerError::NonExistentPage(row_pointer.page))?;
-        let page = PageData::parse(table.clone(), row_pointer.page, page_bytes)?;
+            .ok_or(RowManagerError::NonExistentPage(row_pointer.page))?;
+        let page = RowManager::parse_page(
+            &self.io_manager,
+            table.clone(),
+            row_pointer.page,
+            page_bytes,
+        )?;
 
         let row = page
             .get_row(row_pointer.count)
-            .ok_or_else(|| RowManagerError::NonExistentRow(row_pointer.count, row_pointer.page))?
+            .ok_or(RowManagerError::NonExistentRow(
+                row_pointer.count,
+                row_pointer.page,
+            ))?
             .clone();
 
         Ok((page, row))
@@ -138,11 +326,22 @@ impl RowManager {
     pub fn get_stream(
         self,
         table: Arc<Table>,
+    ) -> impl Stream<Item = Result<RowData, RowManagerError>> {
+        try_stream! {
+            for await row in RowManager::get_stream_raw(self.io_manager.clone(), table.clone()) {
+                yield RowManager::detoast(self.io_manager.clone(), table.clone(), row?).await?;
+            }
+        }
+    }
+
+    fn get_stream_raw(
+        io_manager: IOManager,
+        table: Arc<Table>,
     ) -> impl Stream<Item = Result<RowData, RowManagerError>> {
         try_stream! {
             let mut page_num = 0;
-            for await page_bytes in self.io_manager.get_stream(table.clone()) {
-                let page = PageData::parse(table.clone(), page_num, page_bytes)?;
+            for await page_bytes in io_manager.get_stream(table.clone()) {
+                let page = RowManager::parse_page(&io_manager, table.clone(), page_num, page_bytes)?;
                 for await row in page.get_stream() {
                     yield row;
                 }
@@ -151,67 +350,894 @@ impl RowManager {
         }
     }
 
+    /// Parses a page, when checksums are on a page without one has been corrupted
+    fn parse_page(
+        io_manager: &IOManager,
+        table: Arc<Table>,
+        page_num: usize,
+        page_bytes: Bytes,
+    ) -> Result<PageData, RowManagerError> {
+        let page = PageData::parse(table.clone(), page_num, page_bytes.clone())?;
+        if io_manager.control_file().data_checksums && !page.has_checksum() {
+            return Err(RowManagerError::PageDataError(
+                PageDataError::ChecksumFailure(
+                    table.name.clone(),
+                    page_num,
+                    PageHeader::compute_checksum(&page_bytes, page_num),
+                    0,
+                ),
+            ));
+        }
+        Ok(page)
+    }
+
+    /// Every page of the relation as stored with its page number, see get_stream
+    pub fn get_page_stream(
+        &self,
+        table: Arc<Table>,
+    ) -> impl Stream<Item = Result<(usize, PageData), RowManagerError>> {
+        let io_manager = self.io_manager.clone();
+        try_stream! {
+            let mut page_num = 0;
+            for await page_bytes in io_manager.get_stream(table.clone()) {
+                yield (page_num, RowManager::parse_page(&io_manager, table.clone(), page_num, page_bytes)?);
+                page_num += 1;
+            }
+        }
+    }
+
+    /// Rebuilds the toasted values of a row from get_page or get_page_stream
+    pub async fn detoast_row(
+        &self,
+        table: Arc<Table>,
+        row: RowData,
+    ) -> Result<RowData, RowManagerError> {
+        RowManager::detoast(self.io_manager.clone(), table, row).await
+    }
+
+    /// Saves the hint bits found for rows on a page, given by line pointer and the row they were
+    /// worked out for.
+    ///
+    /// Hints only apply while the row still has the min or max they are about. Like postgres this
+    /// change isn't logged, losing it only costs a lookup, so if anything is changing the relation
+    /// the hints are dropped rather than waiting.
+    pub async fn set_hints(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+        hinted: &[(UInt15, RowData)],
+    ) -> Result<(), RowManagerError> {
+        //A standby's pages have to stay exactly as its primary wrote them
+        if hinted.is_empty() || self.io_manager.in_recovery() {
+            return Ok(());
+        }
+        let _lock = match self.lock_manager.try_exclusive(table.id).await {
+            Some(l) => l,
+            None => return Ok(()),
+        };
+        let mut page = match self.get_page(table.clone(), page_num).await? {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        //Hints don't change what is visible
+        let all_visible = page.all_visible();
+        let mut changed = false;
+        for (count, seen) in hinted {
+            let mut row = match page.get_row(*count) {
+                Some(r) => r.clone(),
+                None => continue,
+            };
+            let hints = row.hints;
+            if row.min == seen.min {
+                row.hints |= seen.hints & InfoMask::XMIN_HINTS;
+            }
+            if row.max.is_some() && row.max == seen.max {
+                row.hints |= seen.hints & InfoMask::XMAX_HINTS;
+            }
+            if row.hints != hints {
+                page.update(row, *count)?;
+                changed = true;
+            }
+        }
+
+        if changed {
+            page.set_all_visible(all_visible);
+            self.io_manager
+                .update_page_unlogged(table, page.serialize(), page_num)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// What the visibility map has for a page, this is what an index only scan would check
+    pub async fn get_visibility(&self, table: Arc<Table>, page_num: usize) -> VisibilityBits {
+        self.visibility_map.get(table, page_num).await
+    }
+
+    /// Marks a page all visible in the page itself and the visibility map, along with all frozen
+    /// if every row on it is frozen. Returns if it was marked.
+    ///
+    /// The page must hold just the rows it was checked with, given by line pointer. Like set_hints
+    /// nothing is marked if the relation is busy and nothing is logged, a page that loses the mark
+    /// in a crash is just checked again.
+    pub async fn set_all_visible(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+        checked: &[(UInt15, RowData)],
+    ) -> Result<bool, RowManagerError> {
+        let _lock = match self.lock_manager.try_exclusive(table.id).await {
+            Some(l) => l,
+            None => return Ok(false),
+        };
+        let mut page = match self.get_page(table.clone(), page_num).await? {
+            Some(p) => p,
+            None => return Ok(false),
+        };
+        if !page.get_rows().eq(checked.iter().map(|(c, r)| (*c, r))) {
+            return Ok(false);
+        }
+
+        let mut bits = VisibilityBits::ALL_VISIBLE;
+        if page
+            .get_rows()
+            .all(|(_, r)| r.hints.contains(InfoMask::XMIN_FROZEN))
+        {
+            bits |= VisibilityBits::ALL_FROZEN;
+        }
+        if !page.all_visible() {
+            page.set_all_visible(true);
+            self.io_manager
+                .update_page_unlogged(table.clone(), page.serialize(), page_num)
+                .await?;
+        }
+        self.visibility_map.set(table, page_num, bits).await?;
+        Ok(true)
+    }
+
+    /// Gets a page as stored, toasted values are left as their pointers
+    pub async fn get_page(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+    ) -> Result<Option<PageData>, RowManagerError> {
+        match self.io_manager.get_page(table.clone(), page_num).await {
+            Some(page_bytes) => Ok(Some(RowManager::parse_page(
+                &self.io_manager,
+                table,
+                page_num,
+                page_bytes,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Prunes dead rows from a page, compacts it and records the space freed.
+    ///
+    /// Dead rows are given by line pointer and how they were found, the page is read again here so
+    /// a row is skipped if its line pointer has since been reused or the relation repacked.
+    /// HOT chains stay reachable from their first line pointer, see PageData::prune.
+    pub async fn vacuum_page(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+        dead: &[(UInt15, RowData)],
+    ) -> Result<VacuumStats, RowManagerError> {
+        let _lock = self.lock_manager.shared(table.id).await;
+        let mut page = match self.get_page(table.clone(), page_num).await? {
+            Some(p) => p,
+            //Repacked since the page was read
+            None => return Ok(VacuumStats::default()),
+        };
+        let free_before = page.free_space();
+
+        let mut stats = VacuumStats {
+            pages_scanned: 1,
+            ..Default::default()
+        };
+        let still_dead: Vec<UInt15> = dead
+            .iter()
+            .filter(|(count, row)| page.get_row(*count) == Some(row))
+            .map(|(count, _)| *count)
+            .collect();
+        stats.rows_removed = page.prune(&still_dead)?;
+        stats.rows_remaining = page.get_rows().count();
+
+        if stats.rows_removed > 0 {
+            page.reclaim_dead();
+            page.compact()?;
+            stats.bytes_freed = page.free_space() - free_before;
+            self.write_page(table, &page, page_num).await?;
+        }
+        Ok(stats)
+    }
+
     async fn insert_row_internal(
-        io_manager: IOManager,
+        &self,
+        table: Arc<Table>,
+        row: RowData,
+    ) -> Result<ItemPointer, RowManagerError> {
+        let _lock = self.lock_manager.shared(table.id).await;
+        let min = row.min;
+        let row_pointer = self.insert_row_unlocked(table.clone(), row).await?;
+        self.log_change(min, table, row_pointer).await?;
+        Ok(row_pointer)
+    }
+
+    //The caller must hold the table's lock and log the change
+    async fn insert_row_unlocked(
+        &self,
+        table: Arc<Table>,
+        row: RowData,
+    ) -> Result<ItemPointer, RowManagerError> {
+        let row_len = row.serialize().len();
+        let needed = row_len + size_of::<ItemIdData>();
+
+        while let Some(page_num) = self
+            .free_space_manager
+            .find_page(table.clone(), needed)
+            .await?
+        {
+            let page_bytes = self
+                .io_manager
+                .get_page(table.clone(), page_num)
+                .await
+                .ok_or(RowManagerError::NonExistentPage(page_num))?;
+            let mut page =
+                RowManager::parse_page(&self.io_manager, table.clone(), page_num, page_bytes)?;
+            if page.can_fit(row_len) {
+                let new_row_pointer = page.insert(row)?;
+                self.write_page(table, &page, page_num).await?;
+                return Ok(new_row_pointer);
+            }
+
+            //The map was out of date, correct it and look again
+            self.free_space_manager
+                .update(table.clone(), page_num, page.free_space())
+                .await?;
+        }
+
+        let page_num = self
+            .io_manager
+            .page_count(table.clone(), ForkNumber::Main)
+            .await;
+        if page_num > ItemPointer::MAX_PAGE {
+            return Err(RowManagerError::RelationFull(table.name.clone()));
+        }
+        let mut new_page = PageData::new(page_num, self.io_manager.page_size());
+        new_page.set_checksum(self.io_manager.control_file().data_checksums);
+        if !new_page.can_fit(row_len) {
+            return Err(RowManagerError::RowTooLarge(row_len));
+        }
+        let new_row_pointer = new_page.insert(row)?;
+        self.io_manager
+            .add_page(table.clone(), new_page.serialize())
+            .await?;
+        self.free_space_manager
+            .update(table, page_num, new_page.free_space())
+            .await?;
+        Ok(new_row_pointer)
+    }
+
+    //The caller must hold the table's lock and log the changes
+    async fn insert_rows_unlocked(
+        &self,
+        table: Arc<Table>,
+        rows: Vec<RowData>,
+    ) -> Result<Vec<ItemPointer>, RowManagerError> {
+        let mut held = None;
+        let result = self.fill_pages(table.clone(), rows, &mut held).await;
+        if result.is_err() {
+            //The held page was never written, give its space back to the free space map
+            if let Some((page_num, free_space, _)) = held {
+                self.free_space_manager
+                    .update(table, page_num, free_space)
+                    .await?;
+            }
+        }
+        result
+    }
+
+    //Inserts the rows a page at a time, the page being filled is left in held along with its
+    //free space as stored until it is written back
+    async fn fill_pages(
+        &self,
+        table: Arc<Table>,
+        rows: Vec<RowData>,
+        held: &mut Option<(usize, usize, PageData)>,
+    ) -> Result<Vec<ItemPointer>, RowManagerError> {
+        let mut row_pointers = Vec::with_capacity(rows.len());
+        for row in rows {
+            let row_len = row.serialize().len();
+            if let Some((_, _, page)) = held.as_mut() {
+                if page.can_fit(row_len) {
+                    row_pointers.push(page.insert(row)?);
+                    continue;
+                }
+            }
+            if let Some((page_num, _, page)) = held.as_ref() {
+                self.write_page(table.clone(), page, *page_num).await?;
+                *held = None;
+            }
+
+            let (page_num, mut page) = self.hold_page(table.clone(), row_len).await?;
+            let free_space = page.free_space();
+            row_pointers.push(page.insert(row)?);
+            *held = Some((page_num, free_space, page));
+        }
+        if let Some((page_num, _, page)) = held.as_ref() {
+            self.write_page(table, page, *page_num).await?;
+            *held = None;
+        }
+        Ok(row_pointers)
+    }
+
+    //A page the row fits on for a bulk insert to fill. New pages are added right away so the
+    //page number is taken, and either way the page is marked full in the free space map until
+    //it is written back so other inserts look elsewhere. If it never is written the caller
+    //restores its free space.
+    async fn hold_page(
+        &self,
+        table: Arc<Table>,
+        row_len: usize,
+    ) -> Result<(usize, PageData), RowManagerError> {
+        let needed = row_len + size_of::<ItemIdData>();
+        while let Some(page_num) = self
+            .free_space_manager
+            .find_page(table.clone(), needed)
+            .await?
+        {
+            let page_bytes = self
+                .io_manager
+                .get_page(table.clone(), page_num)
+                .await
+                .ok_or(RowManagerError::NonExistentPage(page_num))?;
+            let page =
+                RowManager::parse_page(&self.io_manager, table.clone(), page_num, page_bytes)?;
+            if page.can_fit(row_len) {
+                self.free_space_manager.update(table, page_num, 0).await?;
+                return Ok((page_num, page));
+            }
+
+            //The map was out of date, correct it and look again
+            self.free_space_manager
+                .update(table.clone(), page_num, page.free_space())
+                .await?;
+        }
+
+        let page_num = self
+            .io_manager
+            .page_count(table.clone(), ForkNumber::Main)
+            .await;
+        if page_num > ItemPointer::MAX_PAGE {
+            return Err(RowManagerError::RelationFull(table.name.clone()));
+        }
+        let mut new_page = PageData::new(page_num, self.io_manager.page_size());
+        new_page.set_checksum(self.io_manager.control_file().data_checksums);
+        if !new_page.can_fit(row_len) {
+            return Err(RowManagerError::RowTooLarge(row_len));
+        }
+        self.io_manager
+            .add_page(table.clone(), new_page.serialize())
+            .await?;
+        self.free_space_manager.update(table, page_num, 0).await?;
+        Ok((page_num, new_page))
+    }
+
+    //True if the new version needs its own index entries, which rules out a HOT update
+    //TODO there are no indexes yet, once there are compare the values of their columns
+    fn changes_indexed_columns(_old_row: &RowData, _new_row: &RowData) -> bool {
+        false
+    }
+
+    //Writes back an existing page and records its free space
+    async fn write_page(
+        &self,
+        table: Arc<Table>,
+        page: &PageData,
+        page_num: usize,
+    ) -> Result<(), RowManagerError> {
+        self.io_manager
+            .update_page(table.clone(), page.serialize(), page_num)
+            .await?;
+        self.free_space_manager
+            .update(table.clone(), page_num, page.free_space())
+            .await?;
+        if !page.all_visible() {
+            self.visibility_map.clear(table, page_num).await?;
+        }
+        Ok(())
+    }
+
+    /// Builds the row to store, compressing and moving values out of line until it is under the threshold.
+    ///
+    /// The returned row still has all of its values, the pointers are only what gets written to disk.
+    async fn toast_row(
+        &self,
         current_tran_id: TransactionId,
         table: Arc<Table>,
         user_data: Arc<SqlTuple>,
-    ) -> Result<ItemPointer, RowManagerError> {
+    ) -> Result<RowData, RowManagerError> {
+        let threshold = self.io_manager.page_size().bytes() / TOAST_TUPLES_PER_PAGE;
+        let chunk_size = threshold - TOAST_CHUNK_OVERHEAD;
+
         //Serialize with a dummy pointer so we can evaluate space needed
-        let row = RowData::new(
+        let mut row = RowData::new(
             table.clone(),
             current_tran_id,
             None,
-            ItemPointer::new(0, UInt12::new(0).unwrap()),
-            user_data,
+            ItemPointer::new(0, UInt15::new(0).unwrap()),
+            user_data.clone(),
         )?;
-        let row_len = row.serialize().len();
+        if row.serialize().len() <= threshold {
+            return Ok(row);
+        }
 
-        let mut page_num = 0;
-        loop {
-            let page_bytes = io_manager.get_page(table.clone(), page_num).await;
-            match page_bytes {
-                Some(p) => {
-                    let mut page = PageData::parse(table.clone(), page_num, p)?;
-                    if page.can_fit(row_len) {
-                        let new_row_pointer = page.insert(row)?;
-                        let new_page_bytes = page.serialize();
-                        io_manager
-                            .update_page(table, new_page_bytes, page_num)
-                            .await?;
-                        return Ok(new_row_pointer);
-                    } else {
-                        page_num += 1;
-                        continue;
+        let mut candidates: Vec<(usize, Bytes)> = user_data
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.as_ref().map(|d| (i, d.serialize())))
+            .filter(|(_, raw)| raw.len() > TOAST_MIN_VALUE_SIZE)
+            .collect();
+        candidates.sort_by_key(|(_, raw)| Reverse(raw.len()));
+
+        //First pass, compress in place
+        for (i, raw) in candidates.iter() {
+            if row.serialize().len() <= threshold {
+                return Ok(row);
+            }
+            let compressed = ToastPointer::Compressed(ToastPointer::compress(raw));
+            if compressed.stored_len() < raw.len() {
+                row.toast[*i] = Some(compressed);
+            }
+        }
+
+        //Second pass, move the largest remaining values out to chunks
+        let stored_len = |row: &RowData, i: usize, raw: &Bytes| match &row.toast[i] {
+            Some(p) => p.stored_len(),
+            None => raw.len(),
+        };
+        candidates.sort_by_key(|(i, raw)| Reverse(stored_len(&row, *i, raw)));
+
+        let toast_table = Arc::new(table.toast_table());
+        for (i, raw) in candidates.iter() {
+            if row.serialize().len() <= threshold {
+                break;
+            }
+            let (stored, compressed) = match &row.toast[*i] {
+                Some(ToastPointer::Compressed(c)) => (c.clone(), true),
+                _ => (raw.clone(), false),
+            };
+            let pointer = ExternalToast::new(raw.len(), stored.len(), compressed)?;
+
+            for (seq, chunk) in stored.chunks(chunk_size).enumerate() {
+                let chunk_row = RowData::new(
+                    toast_table.clone(),
+                    current_tran_id,
+                    None,
+                    ItemPointer::new(0, UInt15::new(0).unwrap()),
+                    Arc::new(SqlTuple(vec![
+                        Some(BuiltinSqlTypes::Uuid(pointer.chunk_id)),
+                        Some(BuiltinSqlTypes::Integer(seq as i32)),
+                        Some(BuiltinSqlTypes::Bytea(chunk.to_vec())),
+                    ])),
+                )?;
+                self.insert_row_internal(toast_table.clone(), chunk_row)
+                    .await?;
+            }
+
+            row.toast[*i] = Some(ToastPointer::External(pointer));
+        }
+
+        Ok(row)
+    }
+
+    /// Rebuilds any values the row only has toast pointers for
+    async fn detoast(
+        io_manager: IOManager,
+        table: Arc<Table>,
+        mut row: RowData,
+    ) -> Result<RowData, RowManagerError> {
+        if !row.needs_detoast() {
+            return Ok(row);
+        }
+
+        let mut user_data = row.user_data.0.clone();
+        for (i, pointer) in row.toast.iter().enumerate() {
+            let pointer = match pointer {
+                Some(p) if user_data[i].is_none() => p,
+                _ => continue,
+            };
+
+            let chunks = match pointer {
+                ToastPointer::External(e) => {
+                    RowManager::get_chunks(io_manager.clone(), table.clone(), row.min, e.chunk_id)
+                        .await?
+                }
+                ToastPointer::Compressed(_) => vec![],
+            };
+            let raw = pointer.rebuild(&chunks)?;
+            user_data[i] = Some(BuiltinSqlTypes::deserialize(
+                table.attributes[i].sql_type.clone(),
+                raw,
+            )?);
+        }
+
+        row.user_data = Arc::new(SqlTuple(user_data));
+        Ok(row)
+    }
+
+    //Chunks are only ever written by the transaction that wrote their row
+    //TODO this is a full scan of the toast relation, it needs an index on chunk_id
+    async fn get_chunks(
+        io_manager: IOManager,
+        table: Arc<Table>,
+        min: TransactionId,
+        chunk_id: Uuid,
+    ) -> Result<Vec<Bytes>, RowManagerError> {
+        let toast_table = Arc::new(table.toast_table());
+        let target = Some(BuiltinSqlTypes::Uuid(chunk_id));
+
+        let mut chunks = vec![];
+        let rows = RowManager::get_stream_raw(io_manager, toast_table);
+        pin_mut!(rows);
+        while let Some(row) = rows.next().await {
+            let row = row?;
+            if row.min != min || row.user_data.0[0] != target {
+                continue;
+            }
+            match (&row.user_data.0[1], &row.user_data.0[2]) {
+                (Some(BuiltinSqlTypes::Integer(seq)), Some(BuiltinSqlTypes::Bytea(data))) => {
+                    chunks.push((*seq, Bytes::from(data.clone())))
+                }
+                _ => return Err(RowManagerError::MalformedToastChunk(chunk_id)),
+            }
+        }
+
+        chunks.sort_by_key(|(seq, _)| *seq);
+        for (expected, (seq, _)) in chunks.iter().enumerate() {
+            if *seq as usize != expected {
+                return Err(RowManagerError::MalformedToastChunk(chunk_id));
+            }
+        }
+        Ok(chunks.into_iter().map(|(_, c)| c).collect())
+    }
+
+    /// Starts recording which rows of the relation change and makes the relation its rows are
+    /// copied into. A repack goes:
+    /// * start_repack
+    /// * repack_row for every row still needed, pages come from get_page
+    /// * finish_repack to replay the changes made in the meantime and swap in the copy's storage
+    ///
+    /// If anything fails along the way abort_repack cleans up.
+    pub async fn start_repack(&self, table: Arc<Table>) -> Result<RepackState, RowManagerError> {
+        let mut repack_logs = self.repack_logs.write().await;
+        if repack_logs.contains_key(&table.id) {
+            return Err(RowManagerError::AlreadyRepacking(table.name.clone()));
+        }
+
+        let log = Arc::new(table.repack_log_table());
+        repack_logs.insert(table.id, log.clone());
+        Ok(RepackState {
+            copy: Arc::new(Table::new(table.name.clone(), table.attributes.clone())),
+            table,
+            log,
+            copied: HashMap::new(),
+            replayed: 0,
+        })
+    }
+
+    /// Copies the row found at row_pointer into the new relation as is, except HOT chains are not
+    /// kept since each row lands wherever there is room
+    pub async fn repack_row(
+        &self,
+        state: &mut RepackState,
+        row_pointer: ItemPointer,
+        mut row: RowData,
+    ) -> Result<(), RowManagerError> {
+        row.hot_updated = false;
+        row.heap_only = false;
+        let min = row.min;
+        let new_pointer = self.insert_row_unlocked(state.copy.clone(), row).await?;
+        state.copied.insert(row_pointer, (new_pointer, min));
+        Ok(())
+    }
+
+    /// Catches the copy up with the log while writers carry on, then blocks them only for the
+    /// last few changes and the swap.
+    ///
+    /// The stats cover the relation's pages before, the rows copied and the pages saved.
+    pub async fn finish_repack(
+        &self,
+        mut state: RepackState,
+    ) -> Result<VacuumStats, RowManagerError> {
+        let result = self.swap_repack(&mut state).await;
+        //Either way the copy and log are done with, after a swap the copy holds the old storage
+        let ended = self.end_repack(state).await;
+        let stats = result?;
+        ended?;
+        Ok(stats)
+    }
+
+    /// Stops recording changes and throws away the copy
+    pub async fn abort_repack(&self, state: RepackState) -> Result<(), RowManagerError> {
+        self.end_repack(state).await
+    }
+
+    async fn swap_repack(&self, state: &mut RepackState) -> Result<VacuumStats, RowManagerError> {
+        while self.replay_log(state).await? > REPACK_LOG_BATCH {}
+
+        let lock = self.lock_manager.exclusive(state.table.id).await;
+        self.replay_log(state).await?;
+
+        let pages_before = self
+            .io_manager
+            .page_count(state.table.clone(), ForkNumber::Main)
+            .await;
+        let pages_after = self
+            .io_manager
+            .page_count(state.copy.clone(), ForkNumber::Main)
+            .await;
+        self.io_manager
+            .swap_relations(state.table.clone(), state.copy.clone())
+            .await?;
+        self.free_space_manager.forget(state.table.clone()).await;
+        self.repack_logs.write().await.remove(&state.table.id);
+        drop(lock);
+
+        Ok(VacuumStats {
+            pages_scanned: pages_before,
+            rows_removed: 0,
+            rows_remaining: state.copied.len(),
+            bytes_freed: pages_before.saturating_sub(pages_after)
+                * self.io_manager.page_size().bytes(),
+        })
+    }
+
+    async fn end_repack(&self, state: RepackState) -> Result<(), RowManagerError> {
+        self.repack_logs.write().await.remove(&state.table.id);
+        for relation in [state.copy.clone(), state.log.clone()] {
+            self.io_manager.drop_relation(relation.clone()).await?;
+            self.free_space_manager.forget(relation).await;
+        }
+        Ok(())
+    }
+
+    //Records which row changed if its relation is being repacked, must be called after the change
+    //is written while still holding the relation's lock
+    async fn log_change(
+        &self,
+        current_tran_id: TransactionId,
+        table: Arc<Table>,
+        row_pointer: ItemPointer,
+    ) -> Result<(), RowManagerError> {
+        let log = match self.repack_logs.read().await.get(&table.id) {
+            Some(l) => l.clone(),
+            None => return Ok(()),
+        };
+
+        let entry = RowData::new(
+            log.clone(),
+            current_tran_id,
+            None,
+            ItemPointer::new(0, UInt15::new(0).unwrap()),
+            Arc::new(SqlTuple(vec![
+                Some(BuiltinSqlTypes::BigInt(row_pointer.page as i64)),
+                Some(BuiltinSqlTypes::Integer(row_pointer.count.to_u16().into())),
+            ])),
+        )?;
+
+        //Writers only share the relation, the log needs them one at a time
+        let _lock = self.lock_manager.exclusive(log.id).await;
+        self.insert_row_unlocked(log, entry).await?;
+        Ok(())
+    }
+
+    //Logs a change for logical decoding with the old row rebuilt in full, like postgres the
+    //catalogs aren't decoded
+    async fn log_row_change(
+        &self,
+        kind: RowChangeKind,
+        current_tran_id: TransactionId,
+        table: &Arc<Table>,
+        old: Option<RowData>,
+        new: Option<&SqlTuple>,
+    ) -> Result<(), RowManagerError> {
+        if !self.io_manager.logs_row_changes() || TableDefinitions::find(table.id).is_some() {
+            return Ok(());
+        }
+        let old = match old {
+            Some(row) => Some(self.detoast_row(table.clone(), row).await?.user_data),
+            None => None,
+        };
+        let change = RowChange::new(kind, current_tran_id, table, old.as_deref(), new);
+        Ok(self.io_manager.log_row_change(change).await?)
+    }
+
+    //Applies the log entries not yet seen, returning how many there were.
+    //The log is only appended to with rows of one size so it reads back in the order written.
+    async fn replay_log(&self, state: &mut RepackState) -> Result<usize, RowManagerError> {
+        let mut changes = vec![];
+        let entries = RowManager::get_stream_raw(self.io_manager.clone(), state.log.clone());
+        pin_mut!(entries);
+        let mut index = 0;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if index >= state.replayed {
+                match (&entry.user_data.0[0], &entry.user_data.0[1]) {
+                    (
+                        Some(BuiltinSqlTypes::BigInt(page)),
+                        Some(BuiltinSqlTypes::Integer(count)),
+                    ) => {
+                        let count = u16::try_from(*count)
+                            .ok()
+                            .and_then(UInt15::new)
+                            .ok_or(RowManagerError::MalformedRepackLog(index))?;
+                        changes.push(ItemPointer::new(*page as usize, count));
                     }
+                    _ => return Err(RowManagerError::MalformedRepackLog(index)),
                 }
-                None => {
-                    let mut new_page = PageData::new(page_num);
-                    let new_row_pointer = new_page.insert(row)?; //TODO Will NOT handle overly large rows
-                    io_manager.add_page(table, new_page.serialize()).await;
-                    return Ok(new_row_pointer);
+            }
+            index += 1;
+        }
+
+        for row_pointer in changes.iter() {
+            self.replay_change(state, *row_pointer).await?;
+            state.replayed += 1;
+        }
+        Ok(changes.len())
+    }
+
+    //Brings the copy of a changed row up to date, or copies it if it is new
+    async fn replay_change(
+        &self,
+        state: &mut RepackState,
+        row_pointer: ItemPointer,
+    ) -> Result<(), RowManagerError> {
+        let row = match self.get_page(state.table.clone(), row_pointer.page).await? {
+            Some(page) => match page.get_row(row_pointer.count) {
+                Some(r) => r.clone(),
+                //Vacuumed since
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        match state.copied.get(&row_pointer) {
+            //Rows are only changed in place by setting their max, which doesn't change their length
+            Some((new_pointer, min)) if *min == row.min => {
+                let new_pointer = *new_pointer;
+                let mut page = self
+                    .get_page(state.copy.clone(), new_pointer.page)
+                    .await?
+                    .ok_or(RowManagerError::NonExistentPage(new_pointer.page))?;
+                let mut row = row;
+                row.item_pointer = new_pointer;
+                row.hot_updated = false;
+                row.heap_only = false;
+                page.update(row, new_pointer.count)?;
+                self.write_page(state.copy.clone(), &page, new_pointer.page)
+                    .await
+            }
+            //New or its line pointer was reused
+            _ => self.repack_row(state, row_pointer, row).await,
+        }
+    }
+
+    /// Marks the chunks of a deleted row's out of line values as deleted by the same transaction
+    async fn delete_toast(
+        &self,
+        current_tran_id: TransactionId,
+        table: Arc<Table>,
+        toast: &[Option<ToastPointer>],
+    ) -> Result<(), RowManagerError> {
+        let chunk_ids: Vec<BuiltinSqlTypes> = toast
+            .iter()
+            .filter_map(|t| match t {
+                Some(ToastPointer::External(e)) => Some(BuiltinSqlTypes::Uuid(e.chunk_id)),
+                _ => None,
+            })
+            .collect();
+        if chunk_ids.is_empty() {
+            return Ok(());
+        }
+
+        let toast_table = Arc::new(table.toast_table());
+        let _lock = self.lock_manager.shared(toast_table.id).await;
+        let mut page_num = 0;
+        let mut changed_rows = vec![];
+        while let Some(page_bytes) = self
+            .io_manager
+            .get_page(toast_table.clone(), page_num)
+            .await
+        {
+            let mut page = RowManager::parse_page(
+                &self.io_manager,
+                toast_table.clone(),
+                page_num,
+                page_bytes,
+            )?;
+            let rows: Vec<RowData> = page.get_stream().collect().await;
+
+            let mut changed = false;
+            for mut row in rows {
+                let owned = row.user_data.0[0]
+                    .as_ref()
+                    .is_some_and(|id| chunk_ids.contains(id));
+                if owned && row.max.is_none() {
+                    row.max = Some(current_tran_id);
+                    let count = row.item_pointer.count;
+                    page.update(row, count)?;
+                    changed_rows.push(ItemPointer::new(page_num, count));
+                    changed = true;
                 }
             }
+
+            if changed {
+                self.write_page(toast_table.clone(), &page, page_num)
+                    .await?;
+            }
+            page_num += 1;
+        }
+
+        for row_pointer in changed_rows {
+            self.log_change(current_tran_id, toast_table.clone(), row_pointer)
+                .await?;
         }
+        Ok(())
     }
 }
 
 #[derive(Error, Debug)]
 pub enum RowManagerError {
+    #[error("{0} is already being repacked")]
+    AlreadyRepacking(String),
+    #[error("Repack log entry {0} is malformed")]
+    MalformedRepackLog(usize),
     #[error(transparent)]
     PageDataError(#[from] PageDataError),
     #[error(transparent)]
     IOManagerError(#[from] IOManagerError),
     #[error(transparent)]
+    FreeSpaceManagerError(#[from] FreeSpaceManagerError),
+    #[error(transparent)]
+    VisibilityMapError(#[from] VisibilityMapError),
+    #[error(transparent)]
     RowDataError(#[from] RowDataError),
     #[error("Page {0} does not exist")]
     NonExistentPage(usize),
     #[error("Row {0} in Page {1} does not exist")]
-    NonExistentRow(UInt12, usize),
+    NonExistentRow(UInt15, usize),
     #[error("Row {0} already deleted in {1}")]
-    AlreadyDeleted(UInt12, TransactionId),
+    AlreadyDeleted(UInt15, TransactionId),
     #[error("Row {0} is not visible")]
     NotVisibleRow(RowData),
+    #[error("Row of {0} bytes is too large to store even after toasting")]
+    RowTooLarge(usize),
+    #[error("{0} has no room for another page")]
+    RelationFull(String),
+    #[error("Toast chunks for {0} are missing or malformed")]
+    MalformedToastChunk(Uuid),
+    #[error(transparent)]
+    ToastPointerError(#[from] ToastPointerError),
+    #[error(transparent)]
+    SqlTypeError(#[from] SqlTypeError),
+}
+
+impl RowManagerError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            RowManagerError::RowTooLarge(_) | RowManagerError::RelationFull(_) => {
+                PgErrorCodes::ProgramLimitExceeded
+            }
+            RowManagerError::PageDataError(PageDataError::ChecksumFailure(_, _, _, _)) => {
+                PgErrorCodes::DataCorrupted
+            }
+            _ => PgErrorCodes::SystemError,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -222,6 +1248,8 @@ mod tests {
     use super::*;
     use crate::constants::BuiltinSqlTypes;
     use crate::constants::Nullable;
+    use crate::engine::io::page_formats::PageSize;
+    use crate::engine::io::{ControlFile, WalRecord};
     use futures::pin_mut;
     use futures::stream::StreamExt;
 
@@ -296,6 +1324,79 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_row_manager_insert_rows() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_table();
+        let io_manager = IOManager::initdb(PageSize::Kb4);
+        let rm = RowManager::new(io_manager.clone());
+        let tran_id = TransactionId::new(1);
+
+        let first =
+            aw!(rm
+                .clone()
+                .insert_row(tran_id, table.clone(), get_row("single".to_string())))?;
+        let rows: Vec<Arc<SqlTuple>> = (0..200).map(|i| get_row(i.to_string())).collect();
+        let pointers = aw!(rm.clone().insert_rows(tran_id, table.clone(), rows.clone()))?;
+        assert_eq!(pointers.len(), rows.len());
+
+        //The page with room is filled first, then new pages one after another
+        assert_eq!(pointers[0].page, first.page);
+        assert!(pointers.windows(2).all(|w| w[0].page <= w[1].page));
+        for (pointer, row) in pointers.iter().zip(rows.iter()) {
+            assert_eq!(&aw!(rm.get(table.clone(), *pointer))?.1.user_data, row);
+        }
+
+        //Every page written reports its real free space once it is let go
+        let page_count = aw!(io_manager.page_count(table.clone(), ForkNumber::Main));
+        assert_eq!(pointers.last().unwrap().page, page_count - 1);
+        let row_len = aw!(rm.get(table.clone(), first))?.1.serialize().len();
+        assert_eq!(
+            aw!(rm
+                .free_space_manager
+                .find_page(table.clone(), row_len + size_of::<ItemIdData>()))?,
+            Some(page_count - 1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_manager_insert_rows_failure() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("data");
+        let table = get_table();
+        let io_manager = aw!(IOManager::initdb_directory(
+            &path,
+            ControlFile::new(PageSize::Kb4)
+        ))?;
+        let rm = RowManager::new(io_manager);
+        let tran_id = TransactionId::new(1);
+
+        let first =
+            aw!(rm
+                .clone()
+                .insert_row(tran_id, table.clone(), get_row("single".to_string())))?;
+        let row_len = aw!(rm.get(table.clone(), first))?.1.serialize().len();
+        let needed = row_len + size_of::<ItemIdData>();
+        assert_eq!(
+            aw!(rm.free_space_manager.find_page(table.clone(), needed))?,
+            Some(first.page)
+        );
+
+        //Writing the held page back fails since its file can't be opened any more
+        let file = path.join("base").join(table.id.to_hyphenated().to_string());
+        std::fs::remove_file(&file)?;
+        std::fs::create_dir(&file)?;
+        let rows: Vec<Arc<SqlTuple>> = (0..3).map(|i| get_row(i.to_string())).collect();
+        assert!(aw!(rm.clone().insert_rows(tran_id, table.clone(), rows)).is_err());
+
+        //The page is not left looking full
+        assert_eq!(
+            aw!(rm.free_space_manager.find_page(table.clone(), needed))?,
+            Some(first.page)
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_row_manager_crud() {
         let table = get_table();
@@ -319,4 +1420,430 @@ mod tests {
             .delete_row(tran_id_2, table.clone(), insert_pointer));
         assert!(delete_res.is_ok());
     }
+
+    //Bytes that lz4 can't do anything with
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state: u32 = 12345;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn get_toast_table() -> Arc<Table> {
+        Arc::new(Table::new(
+            "toast_test".to_string(),
+            vec![
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    "small".to_string(),
+                    DeserializeTypes::Integer,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    "text".to_string(),
+                    DeserializeTypes::Text,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    "bytes".to_string(),
+                    DeserializeTypes::Bytea,
+                    Nullable::Null,
+                ),
+            ],
+        ))
+    }
+
+    #[test]
+    fn test_row_manager_toast() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_toast_table();
+        let rm = RowManager::new(IOManager::initdb(PageSize::Kb4));
+        let tran_id = TransactionId::new(1);
+
+        let rows = [
+            //Compresses enough to stay in the row
+            Arc::new(SqlTuple(vec![
+                Some(BuiltinSqlTypes::Integer(1)),
+                Some(BuiltinSqlTypes::Text("feophant ".repeat(1000))),
+                None,
+            ])),
+            //Compressed and then moved out
+            Arc::new(SqlTuple(vec![
+                Some(BuiltinSqlTypes::Integer(2)),
+                Some(BuiltinSqlTypes::Text(
+                    noise(2000)
+                        .iter()
+                        .map(|b| format!("{:02x} feophant ", b))
+                        .collect(),
+                )),
+                None,
+            ])),
+            //Moved out as is
+            Arc::new(SqlTuple(vec![
+                Some(BuiltinSqlTypes::Integer(3)),
+                Some(BuiltinSqlTypes::Text("small".to_string())),
+                Some(BuiltinSqlTypes::Bytea(noise(10000))),
+            ])),
+        ];
+
+        let mut pointers = vec![];
+        for row in rows.iter() {
+            pointers.push(aw!(rm.clone().insert_row(
+                tran_id,
+                table.clone(),
+                row.clone()
+            ))?);
+        }
+
+        let stored: Vec<RowData> = aw!(rm
+            .clone()
+            .get_stream(table.clone())
+            .map(Result::unwrap)
+            .collect());
+        assert_eq!(stored.len(), 3);
+        for (row, expected) in stored.iter().zip(rows.iter()) {
+            assert_eq!(&row.user_data, expected);
+        }
+        assert!(matches!(
+            stored[0].toast[1],
+            Some(ToastPointer::Compressed(_))
+        ));
+        assert!(matches!(
+            stored[1].toast[1],
+            Some(ToastPointer::External(ExternalToast {
+                compressed: true,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            stored[2].toast[2],
+            Some(ToastPointer::External(ExternalToast {
+                compressed: false,
+                ..
+            }))
+        ));
+
+        let (_, row) = aw!(rm.get(table.clone(), pointers[2]))?;
+        assert_eq!(row.user_data, rows[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_manager_toast_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_toast_table();
+        let toast_table = Arc::new(table.toast_table());
+        let rm = RowManager::new(IOManager::initdb(PageSize::Kb4));
+
+        let row = Arc::new(SqlTuple(vec![
+            Some(BuiltinSqlTypes::Integer(1)),
+            Some(BuiltinSqlTypes::Text("small".to_string())),
+            Some(BuiltinSqlTypes::Bytea(noise(5000))),
+        ]));
+        let pointer = aw!(rm
+            .clone()
+            .insert_row(TransactionId::new(1), table.clone(), row))?;
+
+        let chunks: Vec<RowData> = aw!(rm
+            .clone()
+            .get_stream(toast_table.clone())
+            .map(Result::unwrap)
+            .collect());
+        assert_eq!(chunks.len(), 6);
+        assert!(chunks
+            .iter()
+            .all(|c| c.min == TransactionId::new(1) && c.max.is_none()));
+
+        aw!(rm
+            .clone()
+            .delete_row(TransactionId::new(2), table.clone(), pointer))?;
+
+        let chunks: Vec<RowData> = aw!(rm
+            .clone()
+            .get_stream(toast_table.clone())
+            .map(Result::unwrap)
+            .collect());
+        assert_eq!(chunks.len(), 6);
+        assert!(chunks.iter().all(|c| c.max == Some(TransactionId::new(2))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_manager_row_too_large() {
+        let attributes = (0..200)
+            .map(|i| {
+                Attribute::new(
+                    uuid::Uuid::new_v4(),
+                    format!("c{}", i),
+                    DeserializeTypes::Bytea,
+                    Nullable::NotNull,
+                )
+            })
+            .collect();
+        let table = Arc::new(Table::new("wide".to_string(), attributes));
+        let row = Arc::new(SqlTuple(
+            (0..200)
+                .map(|_| Some(BuiltinSqlTypes::Bytea(noise(100))))
+                .collect(),
+        ));
+
+        let rm = RowManager::new(IOManager::initdb(PageSize::Kb4));
+        assert!(matches!(
+            aw!(rm.insert_row(TransactionId::new(1), table, row)),
+            Err(RowManagerError::RowTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_row_manager_every_page_size() -> Result<(), Box<dyn std::error::Error>> {
+        for page_size in PageSize::VALUES.iter() {
+            let table = get_toast_table();
+            let io_manager = IOManager::initdb(*page_size);
+            let rm = RowManager::new(io_manager.clone());
+            let tran_id = TransactionId::new(1);
+
+            //Just under the toast threshold is stored as is, twice the page goes out of line
+            let rows = [
+                Arc::new(SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Integer(1)),
+                    Some(BuiltinSqlTypes::Text("small".to_string())),
+                    Some(BuiltinSqlTypes::Bytea(noise(page_size.bytes() / 4 - 100))),
+                ])),
+                Arc::new(SqlTuple(vec![
+                    Some(BuiltinSqlTypes::Integer(2)),
+                    Some(BuiltinSqlTypes::Text("small".to_string())),
+                    Some(BuiltinSqlTypes::Bytea(noise(page_size.bytes() * 2))),
+                ])),
+            ];
+            for _ in 0..20 {
+                for row in rows.iter() {
+                    aw!(rm.clone().insert_row(tran_id, table.clone(), row.clone()))?;
+                }
+            }
+
+            let stored: Vec<RowData> = aw!(rm
+                .clone()
+                .get_stream(table.clone())
+                .map(Result::unwrap)
+                .collect());
+            assert_eq!(stored.len(), 40);
+            //Rows land wherever there is space so match them up by id
+            for row in stored.iter() {
+                let toasted = row.user_data.0[0] == Some(BuiltinSqlTypes::Integer(2));
+                assert_eq!(row.user_data, rows[toasted as usize]);
+                assert_eq!(row.toast[2].is_some(), toasted);
+            }
+
+            //Every page written is the chosen size
+            let pages: Vec<Bytes> = aw!(io_manager.get_stream(table.clone()).collect());
+            assert!(pages.iter().all(|p| p.len() == page_size.bytes()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_manager_free_space() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_table();
+        let io_manager = IOManager::initdb(PageSize::Kb4);
+        let rm = RowManager::new(io_manager.clone());
+        let tran_id = TransactionId::new(1);
+
+        let mut pointers = vec![];
+        for _ in 0..200 {
+            pointers.push(aw!(rm.clone().insert_row(
+                tran_id,
+                table.clone(),
+                get_row("test".to_string())
+            ))?);
+        }
+
+        //Pages fill in order, only the last has room left
+        let page_count = aw!(io_manager.page_count(table.clone(), ForkNumber::Main));
+        assert_eq!(pointers.last().unwrap().page, page_count - 1);
+        let row_len = aw!(rm.get(table.clone(), pointers[0]))?.1.serialize().len();
+        let needed = row_len + size_of::<ItemIdData>();
+        assert_eq!(
+            aw!(rm.free_space_manager.find_page(table.clone(), needed))?,
+            Some(page_count - 1)
+        );
+
+        //A map that is wrong gets corrected instead of trusted
+        while aw!(rm.free_space_manager.find_page(table.clone(), needed))? == Some(page_count - 1) {
+            aw!(rm
+                .clone()
+                .insert_row(tran_id, table.clone(), get_row("test".to_string())))?;
+        }
+        //The map rounds down so a page it skips can still have a little room, use it all up first
+        let mut full = aw!(rm.get_page(table.clone(), 0))?.unwrap();
+        let filler = aw!(rm.get(table.clone(), pointers[0]))?.1;
+        while full.can_fit(row_len) {
+            full.insert(filler.clone())?;
+        }
+        aw!(io_manager.update_page(table.clone(), full.serialize(), 0))?;
+        aw!(rm.free_space_manager.update(table.clone(), 0, 4000))?;
+        let pointer =
+            aw!(rm
+                .clone()
+                .insert_row(tran_id, table.clone(), get_row("test".to_string())))?;
+        assert_eq!(pointer.page, page_count);
+        assert_eq!(
+            aw!(rm.free_space_manager.find_page(table.clone(), needed))?,
+            Some(page_count)
+        );
+
+        //A fresh row manager picks the map up from the free space fork
+        let reopened = RowManager::new(io_manager);
+        let pointer =
+            aw!(reopened
+                .clone()
+                .insert_row(tran_id, table.clone(), get_row("test".to_string())))?;
+        assert_eq!(pointer.page, page_count);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_manager_logs_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let table = get_table();
+        let io_manager = aw!(IOManager::initdb_directory(
+            &tmp.path().join("data"),
+            ControlFile::new(PageSize::Kb4)
+        ))?;
+        let wal = io_manager.directory().unwrap().wal().clone();
+        let start = aw!(wal.end_lsn());
+        let mut rm = RowManager::new(io_manager);
+        let tran_id = TransactionId::new(1);
+
+        let pointer =
+            aw!(rm
+                .clone()
+                .insert_row(tran_id, table.clone(), get_row("first".to_string())))?;
+        aw!(rm.update_row(
+            tran_id,
+            table.clone(),
+            pointer,
+            get_row("second".to_string())
+        ))?;
+        let (_, updated) = aw!(rm.get(table.clone(), pointer))?;
+        aw!(rm
+            .clone()
+            .delete_row(tran_id, table.clone(), updated.item_pointer))?;
+        aw!(wal.flush())?;
+
+        let changes: Vec<RowChange> = aw!(wal.read_since(start))?
+            .into_iter()
+            .filter_map(|e| match e.record {
+                WalRecord::RowChange(c) => Some(c),
+                _ => None,
+            })
+            .collect();
+        let kinds: Vec<RowChangeKind> = changes.iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RowChangeKind::Insert,
+                RowChangeKind::Update,
+                RowChangeKind::Delete
+            ]
+        );
+        let first = |row: &Option<Vec<Option<String>>>| row.as_ref().unwrap()[0].clone();
+        assert_eq!(first(&changes[0].new), Some("first".to_string()));
+        assert_eq!(first(&changes[1].old), Some("first".to_string()));
+        assert_eq!(first(&changes[1].new), Some("second".to_string()));
+        assert_eq!(first(&changes[2].old), Some("second".to_string()));
+        assert_eq!(changes[2].new, None);
+        assert!(changes.iter().all(|c| c.relation_name == "test_table"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_manager_repack() -> Result<(), Box<dyn std::error::Error>> {
+        let table = get_table();
+        let io_manager = IOManager::initdb(PageSize::Kb4);
+        let rm = RowManager::new(io_manager.clone());
+        let tran_id = TransactionId::new(1);
+        let deleter = TransactionId::new(2);
+
+        let mut pointers = vec![];
+        for i in 0..100 {
+            pointers.push(aw!(rm.clone().insert_row(
+                tran_id,
+                table.clone(),
+                get_row(i.to_string())
+            ))?);
+        }
+        for p in pointers.iter().step_by(2) {
+            aw!(rm.clone().delete_row(deleter, table.clone(), *p))?;
+        }
+        let pages_before = aw!(io_manager.page_count(table.clone(), ForkNumber::Main));
+
+        //Deleted rows stand in for dead ones
+        let mut state = aw!(rm.start_repack(table.clone()))?;
+        assert!(matches!(
+            aw!(rm.start_repack(table.clone())),
+            Err(RowManagerError::AlreadyRepacking(_))
+        ));
+        for page_num in 0..pages_before {
+            let page = aw!(rm.get_page(table.clone(), page_num))?.unwrap();
+            for (count, row) in page.get_rows() {
+                if row.max.is_none() {
+                    aw!(rm.repack_row(&mut state, ItemPointer::new(page_num, count), row.clone()))?;
+                }
+            }
+
+            //Changes made part way through still make it over
+            if page_num == 0 {
+                aw!(rm.clone().delete_row(deleter, table.clone(), pointers[99]))?;
+                aw!(rm
+                    .clone()
+                    .insert_row(tran_id, table.clone(), get_row("new".to_string())))?;
+            }
+        }
+
+        let stats = aw!(rm.finish_repack(state))?;
+        assert_eq!(stats.pages_scanned, pages_before);
+        assert_eq!(stats.rows_remaining, 51);
+        let pages_after = aw!(io_manager.page_count(table.clone(), ForkNumber::Main));
+        assert!(pages_after < pages_before);
+        assert_eq!(
+            stats.bytes_freed,
+            (pages_before - pages_after) * PageSize::Kb4.bytes()
+        );
+        assert!(aw!(rm.repack_logs.read()).is_empty());
+
+        let rows: Vec<RowData> = aw!(rm
+            .clone()
+            .get_stream(table.clone())
+            .map(Result::unwrap)
+            .collect());
+        assert_eq!(rows.len(), 51);
+        let mut names: Vec<String> = rows
+            .iter()
+            .filter(|r| r.max.is_none())
+            .map(|r| match &r.user_data.0[0] {
+                Some(BuiltinSqlTypes::Text(t)) => t.clone(),
+                _ => panic!("Wrong type"),
+            })
+            .collect();
+        names.sort();
+        let mut expected: Vec<String> = (1..99).step_by(2).map(|i| i.to_string()).collect();
+        expected.push("new".to_string());
+        expected.sort();
+        assert_eq!(names, expected);
+
+        //Writes after the repack land in the new storage
+        aw!(rm
+            .clone()
+            .insert_row(tran_id, table.clone(), get_row("after".to_string())))?;
+        let rows: Vec<RowData> = aw!(rm
+            .clone()
+            .get_stream(table.clone())
+            .map(Result::unwrap)
+            .collect());
+        assert_eq!(rows.len(), 52);
+        Ok(())
+    }
 }
@@ -0,0 +1,20 @@
+//! What a vacuum found and reclaimed, added up across pages and relations
+use std::ops::AddAssign;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VacuumStats {
+    pub pages_scanned: usize,
+    pub rows_removed: usize,
+    ///Live rows and dead ones that a running transaction could still see
+    pub rows_remaining: usize,
+    pub bytes_freed: usize,
+}
+
+impl AddAssign for VacuumStats {
+    fn add_assign(&mut self, other: Self) {
+        self.pages_scanned += other.pages_scanned;
+        self.rows_removed += other.rows_removed;
+        self.rows_remaining += other.rows_remaining;
+        self.bytes_freed += other.bytes_freed;
+    }
+}
@@ -0,0 +1,153 @@
+//! Checks every page checksum without reading any rows, like postgres' pg_checksums --check.
+//!
+//! Only the main fork holds pages in the page format, the other forks are checked by their users.
+use super::page_formats::PageHeader;
+use super::{DataDirectory, DataDirectoryError, ForkNumber, IOManager};
+use bytes::Bytes;
+use std::fmt;
+use std::path::Path;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// A page whose stored checksum doesn't match its contents
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChecksumFailure {
+    pub relation: Uuid,
+    pub page: usize,
+    pub calculated: u16,
+    pub stored: u16,
+}
+
+impl fmt::Display for ChecksumFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid page {} of relation {}, calculated checksum {} but expected {}",
+            self.page, self.relation, self.calculated, self.stored
+        )
+    }
+}
+
+/// Scans every relation, returning the pages that failed ordered by relation and page
+pub async fn verify_checksums(
+    io_manager: &IOManager,
+) -> Result<Vec<ChecksumFailure>, VerifyChecksumsError> {
+    if !io_manager.control_file().data_checksums {
+        return Err(VerifyChecksumsError::ChecksumsDisabled());
+    }
+    Ok(check_relations(
+        io_manager.fork_snapshot(ForkNumber::Main).await,
+    ))
+}
+
+/// The same scan over the files of a cluster that is shut down, like running pg_checksums
+/// against a stopped server. Nothing in the directory is changed.
+pub async fn verify_directory_checksums(
+    path: &Path,
+) -> Result<Vec<ChecksumFailure>, VerifyChecksumsError> {
+    let (control_file, relations) = DataDirectory::read_offline(path).await?;
+    if !control_file.data_checksums {
+        return Err(VerifyChecksumsError::ChecksumsDisabled());
+    }
+    Ok(check_relations(
+        relations
+            .into_iter()
+            .filter(|((_, fork), _)| *fork == ForkNumber::Main)
+            .map(|((id, _), pages)| (id, pages))
+            .collect(),
+    ))
+}
+
+fn check_relations(mut relations: Vec<(Uuid, Vec<Bytes>)>) -> Vec<ChecksumFailure> {
+    relations.sort_by_key(|(id, _)| *id);
+
+    let mut failures = vec![];
+    for (relation, pages) in relations {
+        for (page, page_bytes) in pages.iter().enumerate() {
+            let calculated = PageHeader::compute_checksum(page_bytes, page);
+            let stored = PageHeader::stored_checksum(page_bytes);
+            if calculated != stored {
+                failures.push(ChecksumFailure {
+                    relation,
+                    page,
+                    calculated,
+                    stored,
+                });
+            }
+        }
+    }
+    failures
+}
+
+#[derive(Debug, Error)]
+pub enum VerifyChecksumsError {
+    #[error("Data checksums were not enabled when the database was created")]
+    ChecksumsDisabled(),
+    #[error(transparent)]
+    DataDirectoryError(#[from] DataDirectoryError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::objects::{Attribute, Table};
+    use super::super::super::transactions::TransactionId;
+    use super::super::page_formats::PageSize;
+    use super::super::{ControlFile, RowManager};
+    use super::*;
+    use crate::constants::BuiltinSqlTypes;
+    use crate::constants::{DeserializeTypes, Nullable};
+    use crate::engine::objects::SqlTuple;
+    use bytes::BytesMut;
+    use std::sync::Arc;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_verify_checksums() -> Result<(), Box<dyn std::error::Error>> {
+        assert!(matches!(
+            aw!(verify_checksums(&IOManager::new())),
+            Err(VerifyChecksumsError::ChecksumsDisabled())
+        ));
+
+        let io_manager = IOManager::initdb_with(ControlFile::new(PageSize::Kb4).with_checksums());
+        let rm = RowManager::new(io_manager.clone());
+        let table = Arc::new(Table::new(
+            "checked".to_string(),
+            vec![Attribute::new(
+                Uuid::new_v4(),
+                "value".to_string(),
+                DeserializeTypes::Text,
+                Nullable::NotNull,
+            )],
+        ));
+
+        //Enough rows for a few pages
+        for i in 0..200 {
+            let value = format!("row number {} {}", i, "padding".repeat(10));
+            aw!(rm.clone().insert_row(
+                TransactionId::new(2),
+                table.clone(),
+                Arc::new(SqlTuple(vec![Some(BuiltinSqlTypes::Text(value))])),
+            ))?;
+        }
+        let pages = aw!(io_manager.page_count(table.clone(), ForkNumber::Main));
+        assert!(pages > 2);
+        assert_eq!(aw!(verify_checksums(&io_manager))?, vec![]);
+
+        let page = aw!(io_manager.get_page(table.clone(), 1)).unwrap();
+        let mut corrupt = BytesMut::from(&page[..]);
+        corrupt[2000] ^= 0x01;
+        aw!(io_manager.update_page(table.clone(), corrupt.freeze(), 1))?;
+
+        let failures = aw!(verify_checksums(&io_manager))?;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].relation, table.id);
+        assert_eq!(failures[0].page, 1);
+        assert_eq!(failures[0].stored, PageHeader::stored_checksum(&page));
+        Ok(())
+    }
+}
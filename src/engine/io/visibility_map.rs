@@ -0,0 +1,200 @@
+//! Tracks which pages of a table only hold rows every transaction can see.
+//! Loosely based on: https://www.postgresql.org/docs/current/storage-vm.html
+//!
+//! Every main page gets two bits in the table's visibility map fork, four pages to a byte:
+//! * All visible - no row on the page needs a visibility check
+//! * All frozen - on top of that every row is frozen, so vacuum has nothing to do on the page
+//!
+//! Only vacuum sets the bits, any change to a page clears them. The page carries its own all visible
+//! flag as well so a scan can trust the copy of the page it read, the map is for deciding without
+//! reading the page at all. Once indexes exist this is what lets an index only scan skip the heap.
+//!
+//! Setting bits isn't logged, a crash that loses them only means the pages are checked again.
+//! Clearing them is, a bit left set would hide a change.
+use super::super::objects::Table;
+use super::{ForkNumber, IOManager, IOManagerError};
+use bytes::{Bytes, BytesMut};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+bitflags! {
+    pub struct VisibilityBits: u8 {
+        const ALL_VISIBLE = 0b01;
+        const ALL_FROZEN = 0b10;
+    }
+}
+
+const BITS_PER_PAGE: usize = 2;
+const PAGES_PER_BYTE: usize = 8 / BITS_PER_PAGE;
+
+#[derive(Clone, Debug)]
+pub struct VisibilityMap {
+    io_manager: IOManager,
+    //Changing a page's bits rewrites the fork page shared with its neighbors, one at a time
+    writing: Arc<Mutex<()>>,
+}
+
+impl VisibilityMap {
+    pub fn new(io_manager: IOManager) -> VisibilityMap {
+        VisibilityMap {
+            io_manager,
+            writing: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// The bits for a page, pages the map has never heard of have none set
+    pub async fn get(&self, table: Arc<Table>, page_num: usize) -> VisibilityBits {
+        let (fork_page_num, byte, shift) = self.locate(page_num);
+        match self
+            .io_manager
+            .get_fork_page(table, ForkNumber::VisibilityMap, fork_page_num)
+            .await
+        {
+            Some(p) => VisibilityBits::from_bits_truncate(p[byte] >> shift),
+            None => VisibilityBits::empty(),
+        }
+    }
+
+    /// Replaces the bits for a page, all frozen is only kept alongside all visible
+    pub async fn set(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+        mut bits: VisibilityBits,
+    ) -> Result<(), VisibilityMapError> {
+        if !bits.contains(VisibilityBits::ALL_VISIBLE) {
+            bits = VisibilityBits::empty();
+        }
+        let _writing = self.writing.lock().await;
+        let current = self.get(table.clone(), page_num).await;
+        if current == bits {
+            return Ok(());
+        }
+        let logged = !bits.contains(current);
+
+        let page_size = self.io_manager.page_size().bytes();
+        let (fork_page_num, byte, shift) = self.locate(page_num);
+        while self
+            .io_manager
+            .page_count(table.clone(), ForkNumber::VisibilityMap)
+            .await
+            <= fork_page_num
+        {
+            let empty = Bytes::from(vec![0; page_size]);
+            if logged {
+                self.io_manager
+                    .add_fork_page(table.clone(), ForkNumber::VisibilityMap, empty)
+                    .await?;
+            } else {
+                self.io_manager
+                    .add_fork_page_unlogged(table.clone(), ForkNumber::VisibilityMap, empty)
+                    .await?;
+            }
+        }
+
+        let fork_page = self
+            .io_manager
+            .get_fork_page(table.clone(), ForkNumber::VisibilityMap, fork_page_num)
+            .await
+            .ok_or(VisibilityMapError::MissingForkPage(fork_page_num))?;
+        let mut fork_page = BytesMut::from(&fork_page[..]);
+        let mask = VisibilityBits::all().bits() << shift;
+        fork_page[byte] = (fork_page[byte] & !mask) | (bits.bits() << shift);
+
+        if logged {
+            self.io_manager
+                .update_fork_page(
+                    table,
+                    ForkNumber::VisibilityMap,
+                    fork_page.freeze(),
+                    fork_page_num,
+                )
+                .await?;
+        } else {
+            self.io_manager
+                .update_fork_page_unlogged(
+                    table,
+                    ForkNumber::VisibilityMap,
+                    fork_page.freeze(),
+                    fork_page_num,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Clears both bits, for when a page changes
+    pub async fn clear(
+        &self,
+        table: Arc<Table>,
+        page_num: usize,
+    ) -> Result<(), VisibilityMapError> {
+        self.set(table, page_num, VisibilityBits::empty()).await
+    }
+
+    //Fork page, byte in it and bit shift for a main page
+    fn locate(&self, page_num: usize) -> (usize, usize, usize) {
+        let pages_per_fork_page = self.io_manager.page_size().bytes() * PAGES_PER_BYTE;
+        let within = page_num % pages_per_fork_page;
+        (
+            page_num / pages_per_fork_page,
+            within / PAGES_PER_BYTE,
+            (within % PAGES_PER_BYTE) * BITS_PER_PAGE,
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum VisibilityMapError {
+    #[error(transparent)]
+    IOManagerError(#[from] IOManagerError),
+    #[error("Visibility map fork page {0} is missing")]
+    MissingForkPage(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::page_formats::PageSize;
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_set_clear() -> Result<(), Box<dyn std::error::Error>> {
+        let io_manager = IOManager::initdb(PageSize::Kb4);
+        let table = Arc::new(Table::new("vm".to_string(), vec![]));
+        let vm = VisibilityMap::new(io_manager.clone());
+        assert_eq!(aw!(vm.get(table.clone(), 5)), VisibilityBits::empty());
+
+        aw!(vm.set(table.clone(), 5, VisibilityBits::ALL_VISIBLE))?;
+        aw!(vm.set(table.clone(), 6, VisibilityBits::all()))?;
+        //Frozen without visible doesn't make sense
+        aw!(vm.set(table.clone(), 7, VisibilityBits::ALL_FROZEN))?;
+        assert_eq!(aw!(vm.get(table.clone(), 4)), VisibilityBits::empty());
+        assert_eq!(aw!(vm.get(table.clone(), 5)), VisibilityBits::ALL_VISIBLE);
+        assert_eq!(aw!(vm.get(table.clone(), 6)), VisibilityBits::all());
+        assert_eq!(aw!(vm.get(table.clone(), 7)), VisibilityBits::empty());
+
+        aw!(vm.clear(table.clone(), 5))?;
+        assert_eq!(aw!(vm.get(table.clone(), 5)), VisibilityBits::empty());
+        assert_eq!(aw!(vm.get(table.clone(), 6)), VisibilityBits::all());
+
+        //Far enough out to need another fork page
+        aw!(vm.set(table.clone(), 4096 * 4 + 1, VisibilityBits::ALL_VISIBLE))?;
+        assert_eq!(
+            aw!(io_manager.page_count(table.clone(), ForkNumber::VisibilityMap)),
+            2
+        );
+        assert_eq!(
+            aw!(vm.get(table.clone(), 4096 * 4 + 1)),
+            VisibilityBits::ALL_VISIBLE
+        );
+        assert_eq!(aw!(vm.get(table, 1)), VisibilityBits::empty());
+        Ok(())
+    }
+}
@@ -2,20 +2,22 @@
 //! See here for basic discussion: http://www.interdb.jp/pg/pgsql05.html#_5.6.
 //!
 //! If you need to bypass this, go down a layer
+use crate::constants::PgErrorCodes;
 use crate::engine::objects::SqlTuple;
 
 use super::super::objects::Table;
 use super::super::transactions::{
-    TransactionId, TransactionManager, TransactionManagerError, TransactionStatus,
+    TransactionId, TransactionManager, TransactionManagerError, TransactionSnapshot,
+    TransactionStatus,
 };
 use super::{
-    page_formats::PageData,
-    row_formats::{ItemPointer, RowData},
-    RowManager, RowManagerError,
+    page_formats::{PageData, UInt15},
+    row_formats::{InfoMask, ItemPointer, RowData},
+    IOManager, RepackState, RowManager, RowManagerError, VacuumStats, VisibilityBits,
 };
 use async_stream::try_stream;
 use futures::stream::Stream;
-use log::debug;
+use log::{debug, warn};
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -33,6 +35,11 @@ impl VisibleRowManager {
         }
     }
 
+    /// The storage the rows are kept in
+    pub fn io_manager(&self) -> &IOManager {
+        self.row_manager.io_manager()
+    }
+
     pub async fn insert_row(
         self,
         current_tran_id: TransactionId,
@@ -45,18 +52,74 @@ impl VisibleRowManager {
             .map_err(VisibleRowManagerError::RowManagerError)
     }
 
+    pub async fn insert_rows(
+        self,
+        current_tran_id: TransactionId,
+        table: Arc<Table>,
+        user_data: Vec<Arc<SqlTuple>>,
+    ) -> Result<Vec<ItemPointer>, VisibleRowManagerError> {
+        self.row_manager
+            .insert_rows(current_tran_id, table, user_data)
+            .await
+            .map_err(VisibleRowManagerError::RowManagerError)
+    }
+
+    pub async fn delete_row(
+        self,
+        current_tran_id: TransactionId,
+        table: Arc<Table>,
+        row_pointer: ItemPointer,
+    ) -> Result<(), VisibleRowManagerError> {
+        self.row_manager
+            .delete_row(current_tran_id, table, row_pointer)
+            .await
+            .map_err(VisibleRowManagerError::RowManagerError)
+    }
+
+    /// Gets the version of the row at row_pointer this transaction can see, following its HOT
+    /// chain so a pointer from an index still finds rows updated since.
     pub async fn get(
         &self,
         tran_id: TransactionId,
         table: Arc<Table>,
         row_pointer: ItemPointer,
     ) -> Result<(PageData, RowData), VisibleRowManagerError> {
-        let (page, row) = self.row_manager.get(table, row_pointer).await?;
+        let chain = self
+            .row_manager
+            .get_chain(table.clone(), row_pointer)
+            .await?;
+
+        let snapshot = self.tran_manager.get_snapshot(tran_id).await;
+        let mut found = None;
+        let mut hinted = vec![];
+        for (pointer, row) in chain.iter() {
+            let mut row = row.clone();
+            let before = row.hints;
+            let visible = VisibleRowManager::is_visible(
+                self.tran_manager.clone(),
+                tran_id,
+                snapshot.as_ref(),
+                &mut row,
+            )
+            .await?;
+            VisibleRowManager::note_hints(&mut hinted, pointer.count, before, &row);
+            if visible {
+                found = Some(*pointer);
+                break;
+            }
+        }
+        self.row_manager
+            .set_hints(table.clone(), row_pointer.page, &hinted)
+            .await?;
+        if let Some(pointer) = found {
+            return Ok(self.row_manager.get(table, pointer).await?);
+        }
 
-        if VisibleRowManager::is_visible(self.tran_manager.clone(), tran_id, &row).await? {
-            Ok((page, row))
-        } else {
-            Err(VisibleRowManagerError::NotVisibleRow(row))
+        match chain.into_iter().last() {
+            Some((_, row)) => Err(VisibleRowManagerError::NotVisibleRow(row)),
+            None => Err(VisibleRowManagerError::RowManagerError(
+                RowManagerError::NonExistentRow(row_pointer.count, row_pointer.page),
+            )),
         }
     }
 
@@ -68,62 +131,359 @@ impl VisibleRowManager {
     ) -> impl Stream<Item = Result<RowData, VisibleRowManagerError>> {
         try_stream! {
             let tm = self.tran_manager;
+            let snapshot = tm.get_snapshot(tran_id).await;
+
+            for await page in self.row_manager.get_page_stream(table.clone()) {
+                let (page_num, page) = page?;
+                //A snapshot can be older than whatever made the page all visible
+                if page.all_visible() && snapshot.is_none() {
+                    for (_, row) in page.get_rows() {
+                        yield self.row_manager.detoast_row(table.clone(), row.clone()).await?;
+                    }
+                    continue;
+                }
+
+                let mut hinted = vec![];
+                for (count, row) in page.get_rows() {
+                    let mut row = row.clone();
+                    let before = row.hints;
+                    let visible = VisibleRowManager::is_visible(tm.clone(), tran_id, snapshot.as_ref(), &mut row).await?;
+                    VisibleRowManager::note_hints(&mut hinted, count, before, &row);
+                    if visible {
+                        debug!("Found visible row {:?}", row);
+                        yield self.row_manager.detoast_row(table.clone(), row).await?;
+                    } else {
+                        debug!("Found not visible row {:?}", row);
+                    }
+                }
+                self.row_manager.set_hints(table.clone(), page_num, &hinted).await?;
+            }
+        }
+    }
+
+    /// Removes the rows of a table and its toast relation that no transaction can see anymore.
+    ///
+    /// A row is dead if the transaction that inserted it aborted or the one that deleted it
+    /// committed before the oldest transaction still running started.
+    ///
+    /// Rows everyone can see are frozen and pages with only those are marked all visible, pages
+    /// already all frozen are skipped.
+    pub async fn vacuum(&self, table: Arc<Table>) -> Result<VacuumStats, VisibleRowManagerError> {
+        let horizon = self.tran_manager.oldest_active().await?;
+
+        let mut stats = self.vacuum_relation(horizon, table.clone()).await?;
+        stats += self
+            .vacuum_relation(horizon, Arc::new(table.toast_table()))
+            .await?;
+        Ok(stats)
+    }
+
+    async fn vacuum_relation(
+        &self,
+        horizon: TransactionId,
+        table: Arc<Table>,
+    ) -> Result<VacuumStats, VisibleRowManagerError> {
+        let mut stats = VacuumStats::default();
+        let mut page_num = 0;
+        while let Some(page) = self.row_manager.get_page(table.clone(), page_num).await? {
+            let frozen = self
+                .row_manager
+                .get_visibility(table.clone(), page_num)
+                .await
+                .contains(VisibilityBits::ALL_FROZEN);
+            if page.all_visible() && frozen {
+                stats.rows_remaining += page.get_rows().count();
+                page_num += 1;
+                continue;
+            }
+
+            let mut dead = vec![];
+            let mut live = vec![];
+            let mut hinted = vec![];
+            let mut all_visible = true;
+            for (count, row) in page.get_rows() {
+                let mut row = row.clone();
+                let before = row.hints;
+                if VisibleRowManager::is_dead(self.tran_manager.clone(), horizon, &mut row).await? {
+                    dead.push((count, row));
+                } else {
+                    all_visible &= VisibleRowManager::is_all_visible(
+                        self.tran_manager.clone(),
+                        horizon,
+                        &mut row,
+                    )
+                    .await?;
+                    VisibleRowManager::note_hints(&mut hinted, count, before, &row);
+                    live.push((count, row));
+                }
+            }
+
+            stats += self
+                .row_manager
+                .vacuum_page(table.clone(), page_num, &dead)
+                .await?;
+            self.row_manager
+                .set_hints(table.clone(), page_num, &hinted)
+                .await?;
+            if all_visible {
+                self.row_manager
+                    .set_all_visible(table.clone(), page_num, &live)
+                    .await?;
+            }
+            page_num += 1;
+        }
+        debug!("Vacuumed {} {:?}", table.name, stats);
+        Ok(stats)
+    }
+
+    /// Rewrites a table and its toast relation with only the rows someone could still see, writers
+    /// are only blocked for the moment each relation's storage is swapped.
+    ///
+    /// Rows move, item pointers from before the repack no longer point at the same rows.
+    //TODO once indexes exist they need rebuilding the same way, built alongside the copy and swapped with it
+    pub async fn repack(&self, table: Arc<Table>) -> Result<VacuumStats, VisibleRowManagerError> {
+        let horizon = self.tran_manager.oldest_active().await?;
+
+        let mut stats = self.repack_relation(horizon, table.clone()).await?;
+        stats += self
+            .repack_relation(horizon, Arc::new(table.toast_table()))
+            .await?;
+        Ok(stats)
+    }
+
+    async fn repack_relation(
+        &self,
+        horizon: TransactionId,
+        table: Arc<Table>,
+    ) -> Result<VacuumStats, VisibleRowManagerError> {
+        let mut state = self.row_manager.start_repack(table.clone()).await?;
+        let rows_removed = match self
+            .copy_live_rows(horizon, table.clone(), &mut state)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                //The copy failing is what matters, a failed clean up only leaves unused files
+                if let Err(abort) = self.row_manager.abort_repack(state).await {
+                    warn!("Unable to clean up the repack of {}: {}", table.name, abort);
+                }
+                return Err(e);
+            }
+        };
 
-            for await row in self.row_manager.get_stream(table) {
-                let unwrap_row = row?;
-                if VisibleRowManager::is_visible(tm.clone(), tran_id, &unwrap_row).await? {
-                    debug!("Found visible row {:?}", unwrap_row);
-                    yield unwrap_row;
+        let mut stats = self.row_manager.finish_repack(state).await?;
+        stats.rows_removed = rows_removed;
+        debug!("Repacked {} {:?}", table.name, stats);
+        Ok(stats)
+    }
+
+    //Returns how many dead rows were left behind
+    async fn copy_live_rows(
+        &self,
+        horizon: TransactionId,
+        table: Arc<Table>,
+        state: &mut RepackState,
+    ) -> Result<usize, VisibleRowManagerError> {
+        let mut rows_removed = 0;
+        let mut page_num = 0;
+        while let Some(page) = self.row_manager.get_page(table.clone(), page_num).await? {
+            for (count, row) in page.get_rows() {
+                let mut row = row.clone();
+                if VisibleRowManager::is_dead(self.tran_manager.clone(), horizon, &mut row).await? {
+                    rows_removed += 1;
                 } else {
-                    debug!("Found not visible row {:?}", unwrap_row);
+                    self.row_manager
+                        .repack_row(state, ItemPointer::new(page_num, count), row)
+                        .await?;
                 }
             }
+            page_num += 1;
+        }
+        Ok(rows_removed)
+    }
+
+    async fn is_dead(
+        mut tm: TransactionManager,
+        horizon: TransactionId,
+        row_data: &mut RowData,
+    ) -> Result<bool, VisibleRowManagerError> {
+        let min = row_data.min;
+        if VisibleRowManager::get_status(&mut tm, min, &mut row_data.hints, InfoMask::XMIN_HINTS)
+            .await?
+            == TransactionStatus::Aborted
+        {
+            return Ok(true);
+        }
+
+        match row_data.max {
+            Some(m) if m < horizon => Ok(VisibleRowManager::get_status(
+                &mut tm,
+                m,
+                &mut row_data.hints,
+                InfoMask::XMAX_HINTS,
+            )
+            .await?
+                == TransactionStatus::Commited),
+            _ => Ok(false),
+        }
+    }
+
+    //If the row will be visible to everything running now or later, freezing it if so
+    async fn is_all_visible(
+        mut tm: TransactionManager,
+        horizon: TransactionId,
+        row_data: &mut RowData,
+    ) -> Result<bool, VisibleRowManagerError> {
+        let min = row_data.min;
+        if min >= horizon
+            || VisibleRowManager::get_status(
+                &mut tm,
+                min,
+                &mut row_data.hints,
+                InfoMask::XMIN_HINTS,
+            )
+            .await?
+                != TransactionStatus::Commited
+        {
+            return Ok(false);
+        }
+        row_data.hints |= InfoMask::XMIN_FROZEN;
+
+        match row_data.max {
+            Some(m) => Ok(VisibleRowManager::get_status(
+                &mut tm,
+                m,
+                &mut row_data.hints,
+                InfoMask::XMAX_HINTS,
+            )
+            .await?
+                == TransactionStatus::Aborted),
+            None => Ok(true),
         }
     }
 
     //TODO I want to find a way to NOT depend on tm
+    //Sets the hint bits for whatever it had to look up
     async fn is_visible(
         mut tm: TransactionManager,
         tran_id: TransactionId,
-        row_data: &RowData,
+        snapshot: Option<&TransactionSnapshot>,
+        row_data: &mut RowData,
     ) -> Result<bool, VisibleRowManagerError> {
+        if let Some(s) = snapshot {
+            return VisibleRowManager::is_visible_in_snapshot(tm, s, row_data).await;
+        }
         if row_data.min == tran_id {
             match row_data.max {
-                Some(m) => {
-                    if m == tran_id {
-                        return Ok(false);
-                    } else {
-                        //In the future for us since min cannot be greater than max
-                        return Ok(true);
-                    }
-                }
+                Some(m) if m == tran_id => return Ok(false),
+                //In the future for us since min cannot be greater than max
+                Some(_) => return Ok(true),
                 None => return Ok(true),
             }
         }
 
-        //TODO check hint bits
         if row_data.min > tran_id {
             return Ok(false);
         }
 
-        if tm.get_status(row_data.min).await? != TransactionStatus::Commited {
+        let min = row_data.min;
+        if VisibleRowManager::get_status(&mut tm, min, &mut row_data.hints, InfoMask::XMIN_HINTS)
+            .await?
+            != TransactionStatus::Commited
+        {
             return Ok(false);
         }
 
         match row_data.max {
+            //Deleted by us
+            Some(m) if m == tran_id => Ok(false),
             Some(m) => {
                 if m > tran_id {
                     return Ok(true);
                 }
-                if tm.get_status(m).await? != TransactionStatus::Commited {
-                    return Ok(true);
-                } else {
-                    return Ok(false);
-                }
-            }
-            None => {
-                return Ok(true);
+                Ok(VisibleRowManager::get_status(
+                    &mut tm,
+                    m,
+                    &mut row_data.hints,
+                    InfoMask::XMAX_HINTS,
+                )
+                .await?
+                    != TransactionStatus::Commited)
             }
+            None => Ok(true),
+        }
+    }
+
+    //Read only transactions never change rows themselves. The snapshot is checked before the hint
+    //bits, they could have been set by a commit the snapshot is from before.
+    async fn is_visible_in_snapshot(
+        mut tm: TransactionManager,
+        snapshot: &TransactionSnapshot,
+        row_data: &mut RowData,
+    ) -> Result<bool, VisibleRowManagerError> {
+        let min = row_data.min;
+        if snapshot.is_running(min)
+            || VisibleRowManager::get_status(
+                &mut tm,
+                min,
+                &mut row_data.hints,
+                InfoMask::XMIN_HINTS,
+            )
+            .await?
+                != TransactionStatus::Commited
+        {
+            return Ok(false);
+        }
+
+        match row_data.max {
+            Some(m) if !snapshot.is_running(m) => Ok(VisibleRowManager::get_status(
+                &mut tm,
+                m,
+                &mut row_data.hints,
+                InfoMask::XMAX_HINTS,
+            )
+            .await?
+                != TransactionStatus::Commited),
+            _ => Ok(true),
+        }
+    }
+
+    //A transaction's status from the row's hint bits, only asking the transaction manager if they
+    //aren't set yet. which is XMIN_HINTS or XMAX_HINTS depending on the transaction asked about.
+    async fn get_status(
+        tm: &mut TransactionManager,
+        tran_id: TransactionId,
+        hints: &mut InfoMask,
+        which: InfoMask,
+    ) -> Result<TransactionStatus, VisibleRowManagerError> {
+        let committed = which & (InfoMask::XMIN_COMMITTED | InfoMask::XMAX_COMMITTED);
+        let invalid = which & (InfoMask::XMIN_INVALID | InfoMask::XMAX_INVALID);
+        if hints.contains(committed) {
+            return Ok(TransactionStatus::Commited);
+        }
+        if hints.contains(invalid) {
+            return Ok(TransactionStatus::Aborted);
+        }
+
+        let status = tm.get_status(tran_id).await?;
+        match status {
+            TransactionStatus::Commited => *hints |= committed,
+            TransactionStatus::Aborted => *hints |= invalid,
+            //Could still go either way
+            TransactionStatus::InProgress => {}
+        }
+        Ok(status)
+    }
+
+    //Keeps the row to write back if checking it set any hint bits
+    fn note_hints(
+        hinted: &mut Vec<(UInt15, RowData)>,
+        count: UInt15,
+        before: InfoMask,
+        row: &RowData,
+    ) {
+        if row.hints != before {
+            hinted.push((count, row.clone()));
         }
     }
 }
@@ -139,3 +499,12 @@ pub enum VisibleRowManagerError {
     #[error(transparent)]
     TransactionManagerError(#[from] TransactionManagerError),
 }
+
+impl VisibleRowManagerError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            VisibleRowManagerError::RowManagerError(e) => e.pg_error_code(),
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
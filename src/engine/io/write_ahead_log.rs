@@ -0,0 +1,872 @@
+//! Every change to a data directory's files is appended here before it is made, so after a crash
+//! or when restoring a base backup the changes can be made again.
+//! See: https://www.postgresql.org/docs/current/wal-intro.html
+//!
+//! Records hold whole pages instead of what changed in them, so redoing one is copying the page
+//! back and redoing one twice is harmless.
+//!
+//! Pages are logged against the file they are in, not the relation, see DataDirectory's filenode
+//! map. The two are the same until a relation has its files swapped with another's.
+//!
+//! The log is split into segments of SEGMENT_SIZE bytes in pg_wal, named by their number in hex.
+//! A log sequence number (LSN) is a byte position in the log. A record never crosses into the
+//! next segment, one that doesn't fit starts the next segment instead.
+//!
+//! Each record is:
+//! * Length of the whole record as a little endian u32
+//! * FNV-1a checksum of everything after it as a little endian u32
+//! * Its LSN as a little endian u64
+//! * When it was written as little endian u64 microseconds since the unix epoch
+//! * A u8 kind and the fields of that kind, see WalRecord
+use super::{ForkNumber, RowChange};
+use crate::engine::transactions::{TransactionId, TransactionStatus};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use log::warn;
+use std::convert::TryFrom;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{watch, Mutex, RwLock, RwLockReadGuard};
+use uuid::Uuid;
+
+/// The same default as postgres' wal_segment_size
+pub const SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+const HEADER_SIZE: usize = 4 + 4 + 8 + 8;
+
+//FNV-1a, see https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// A change to the files of a data directory
+#[derive(Clone, Debug, PartialEq)]
+pub enum WalRecord {
+    ///The new contents of a page, written past the end of the fork it extends it
+    PageImage {
+        relation: Uuid,
+        fork: ForkNumber,
+        page_num: usize,
+        page: Bytes,
+    },
+    ///The fork cut down to this many pages, removed entirely at zero
+    Truncate {
+        relation: Uuid,
+        fork: ForkNumber,
+        pages: usize,
+    },
+    ///Every fork of the relation removed
+    DropRelation { relation: Uuid },
+    ///A transaction started, committed or aborted
+    TransactionStatus {
+        tran_id: TransactionId,
+        status: TransactionStatus,
+    },
+    ///A base backup started copying files, nothing to redo
+    BackupStart,
+    ///The base backup that started at the LSN is done copying files, a copy of it is only
+    ///consistent once redo gets here
+    BackupEnd { start: u64 },
+    ///A row changed in a user table, only read by logical decoding so there is nothing to redo
+    RowChange(RowChange),
+    ///The two relations exchanged files, from now on left's pages are in left_file and right's
+    ///in right_file
+    SwapRelations {
+        left: Uuid,
+        left_file: Uuid,
+        right: Uuid,
+        right_file: Uuid,
+    },
+}
+
+impl WalRecord {
+    fn serialize(&self, buffer: &mut BytesMut) {
+        match self {
+            WalRecord::PageImage {
+                relation,
+                fork,
+                page_num,
+                page,
+            } => {
+                buffer.put_u8(1);
+                buffer.put_slice(relation.as_bytes());
+                buffer.put_u8(fork.serialize());
+                buffer.put_u64_le(*page_num as u64);
+                buffer.put_slice(page);
+            }
+            WalRecord::Truncate {
+                relation,
+                fork,
+                pages,
+            } => {
+                buffer.put_u8(2);
+                buffer.put_slice(relation.as_bytes());
+                buffer.put_u8(fork.serialize());
+                buffer.put_u64_le(*pages as u64);
+            }
+            WalRecord::DropRelation { relation } => {
+                buffer.put_u8(3);
+                buffer.put_slice(relation.as_bytes());
+            }
+            WalRecord::TransactionStatus { tran_id, status } => {
+                buffer.put_u8(4);
+                buffer.put_u64_le(tran_id.get_u64());
+                buffer.put_u8(status.serialize());
+            }
+            WalRecord::BackupStart => buffer.put_u8(5),
+            WalRecord::BackupEnd { start } => {
+                buffer.put_u8(6);
+                buffer.put_u64_le(*start);
+            }
+            WalRecord::RowChange(change) => {
+                buffer.put_u8(7);
+                change.serialize(buffer);
+            }
+            WalRecord::SwapRelations {
+                left,
+                left_file,
+                right,
+                right_file,
+            } => {
+                buffer.put_u8(8);
+                for id in [left, left_file, right, right_file] {
+                    buffer.put_slice(id.as_bytes());
+                }
+            }
+        }
+    }
+
+    fn parse(buffer: &mut Bytes) -> Option<WalRecord> {
+        fn uuid(buffer: &mut Bytes) -> Option<Uuid> {
+            if buffer.remaining() < 16 {
+                return None;
+            }
+            Uuid::from_slice(&buffer.split_to(16)).ok()
+        }
+
+        if !buffer.has_remaining() {
+            return None;
+        }
+        match buffer.get_u8() {
+            1 => {
+                let relation = uuid(buffer)?;
+                if buffer.remaining() < 9 {
+                    return None;
+                }
+                let fork = ForkNumber::parse(buffer.get_u8())?;
+                let page_num = usize::try_from(buffer.get_u64_le()).ok()?;
+                Some(WalRecord::PageImage {
+                    relation,
+                    fork,
+                    page_num,
+                    page: buffer.split_off(0),
+                })
+            }
+            2 => {
+                let relation = uuid(buffer)?;
+                if buffer.remaining() < 9 {
+                    return None;
+                }
+                let fork = ForkNumber::parse(buffer.get_u8())?;
+                let pages = usize::try_from(buffer.get_u64_le()).ok()?;
+                Some(WalRecord::Truncate {
+                    relation,
+                    fork,
+                    pages,
+                })
+            }
+            3 => Some(WalRecord::DropRelation {
+                relation: uuid(buffer)?,
+            }),
+            4 => {
+                if buffer.remaining() < 9 {
+                    return None;
+                }
+                let tran_id = TransactionId::new(buffer.get_u64_le());
+                let status = TransactionStatus::parse(buffer.get_u8())?;
+                Some(WalRecord::TransactionStatus { tran_id, status })
+            }
+            5 => Some(WalRecord::BackupStart),
+            6 => {
+                if buffer.remaining() < 8 {
+                    return None;
+                }
+                Some(WalRecord::BackupEnd {
+                    start: buffer.get_u64_le(),
+                })
+            }
+            7 => Some(WalRecord::RowChange(RowChange::parse(buffer)?)),
+            8 => Some(WalRecord::SwapRelations {
+                left: uuid(buffer)?,
+                left_file: uuid(buffer)?,
+                right: uuid(buffer)?,
+                right_file: uuid(buffer)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A record as it was read back from the log
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalEntry {
+    pub lsn: u64,
+    pub length: u64,
+    pub written: SystemTime,
+    pub record: WalRecord,
+}
+
+impl WalEntry {
+    fn new(lsn: u64, written: SystemTime, record: WalRecord) -> WalEntry {
+        let mut body = BytesMut::new();
+        record.serialize(&mut body);
+        WalEntry {
+            lsn,
+            length: (HEADER_SIZE + body.len()) as u64,
+            written,
+            record,
+        }
+    }
+
+    /// Where the next record starts, unless this was the last one in its segment
+    pub fn end(&self) -> u64 {
+        self.lsn + self.length
+    }
+
+    /// The entry as it is stored in its segment
+    pub fn serialize(&self) -> Bytes {
+        let mut checked = BytesMut::with_capacity(self.length as usize - 8);
+        checked.put_u64_le(self.lsn);
+        checked.put_u64_le(
+            self.written
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_micros() as u64),
+        );
+        self.record.serialize(&mut checked);
+
+        let mut buffer = BytesMut::with_capacity(self.length as usize);
+        buffer.put_u32_le(self.length as u32);
+        buffer.put_u32_le(WriteAheadLog::checksum(&checked));
+        buffer.put_slice(&checked);
+        buffer.freeze()
+    }
+
+    /// Takes an entry off the front of the buffer, None if it is torn or corrupt
+    pub fn parse(buffer: &mut Bytes) -> Option<WalEntry> {
+        if buffer.remaining() < HEADER_SIZE {
+            return None;
+        }
+        let length = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+        if length < HEADER_SIZE || length > buffer.remaining() {
+            return None;
+        }
+        let mut record = buffer.slice(..length);
+        record.advance(4);
+        let checksum = record.get_u32_le();
+        if checksum != WriteAheadLog::checksum(&record) {
+            return None;
+        }
+        let lsn = record.get_u64_le();
+        let written = SystemTime::UNIX_EPOCH + Duration::from_micros(record.get_u64_le());
+        let record = WalRecord::parse(&mut record)?;
+        buffer.advance(length);
+        Some(WalEntry {
+            lsn,
+            length: length as u64,
+            written,
+            record,
+        })
+    }
+}
+
+/// Where point in time recovery stops, like postgres' recovery_target settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecoveryTarget {
+    ///Before the first record at or past the LSN
+    Lsn(u64),
+    ///After the record of the transaction committing or aborting
+    TransactionId(TransactionId),
+    ///Before the first record written after the time
+    Time(SystemTime),
+}
+
+impl RecoveryTarget {
+    /// How many of the entries to redo, None if the log ends before the target
+    pub fn stop_at(&self, entries: &[WalEntry]) -> Option<usize> {
+        for (i, e) in entries.iter().enumerate() {
+            match self {
+                RecoveryTarget::Lsn(lsn) if e.lsn >= *lsn => return Some(i),
+                RecoveryTarget::Time(time) if e.written > *time => return Some(i),
+                RecoveryTarget::TransactionId(target) => {
+                    if let WalRecord::TransactionStatus { tran_id, status } = e.record {
+                        if tran_id == *target && status != TransactionStatus::InProgress {
+                            return Some(i + 1);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        match self {
+            RecoveryTarget::Lsn(lsn) if entries.last().map(|e| e.end()) == Some(*lsn) => {
+                Some(entries.len())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WriteAheadLog {
+    path: PathBuf,
+    ///Where completed segments are copied to, like postgres' archive_command
+    archive: Option<PathBuf>,
+    writer: Arc<Mutex<WalWriter>>,
+    //Everything before it is on disk, what the WAL senders wait on
+    flushed: Arc<watch::Sender<u64>>,
+    //Held from logging a change until it is applied, like postgres' DELAY_CHKPT_START
+    checkpoint_delay: Arc<RwLock<()>>,
+}
+
+#[derive(Debug)]
+struct WalWriter {
+    segment: u64,
+    offset: u64,
+    file: File,
+}
+
+impl WriteAheadLog {
+    /// Makes the empty log of a new data directory
+    pub async fn create(path: &Path) -> Result<WriteAheadLog, WriteAheadLogError> {
+        fs::create_dir_all(path).await?;
+        WriteAheadLog::open(path, 0, None).await
+    }
+
+    /// Starts appending at the LSN, anything already past it is a torn record and is dropped
+    pub async fn open(
+        path: &Path,
+        end: u64,
+        archive: Option<PathBuf>,
+    ) -> Result<WriteAheadLog, WriteAheadLogError> {
+        let segment = end / SEGMENT_SIZE;
+        let offset = end % SEGMENT_SIZE;
+        let file = WriteAheadLog::open_segment(path, segment).await?;
+        file.set_len(offset).await?;
+        if let Some(a) = &archive {
+            fs::create_dir_all(a).await?;
+        }
+
+        let wal = WriteAheadLog {
+            path: path.to_path_buf(),
+            archive,
+            writer: Arc::new(Mutex::new(WalWriter {
+                segment,
+                offset,
+                file,
+            })),
+            flushed: Arc::new(watch::channel(end).0),
+            checkpoint_delay: Arc::new(RwLock::new(())),
+        };
+        wal.archive_completed(segment).await;
+        Ok(wal)
+    }
+
+    /// Appends the record returning its LSN, it isn't on disk until the next flush
+    pub async fn append(&self, record: &WalRecord) -> Result<u64, WriteAheadLogError> {
+        let mut writer = self.writer.lock().await;
+        let mut entry = WalEntry::new(
+            writer.segment * SEGMENT_SIZE + writer.offset,
+            SystemTime::now(),
+            record.clone(),
+        );
+        if entry.length > SEGMENT_SIZE {
+            return Err(WriteAheadLogError::RecordTooLarge(entry.length));
+        }
+        if writer.offset + entry.length > SEGMENT_SIZE {
+            self.next_segment(&mut writer).await?;
+            entry.lsn = writer.segment * SEGMENT_SIZE;
+        }
+
+        WriteAheadLog::write_entry(&mut writer, &entry).await?;
+        Ok(entry.lsn)
+    }
+
+    /// Writes an entry read from another cluster's log at the same LSN, so a standby's log
+    /// matches its primary's
+    pub async fn receive(&self, entry: &WalEntry) -> Result<(), WriteAheadLogError> {
+        let mut writer = self.writer.lock().await;
+        while writer.segment < entry.lsn / SEGMENT_SIZE {
+            self.next_segment(&mut writer).await?;
+        }
+        writer.offset = entry.lsn % SEGMENT_SIZE;
+        WriteAheadLog::write_entry(&mut writer, entry).await
+    }
+
+    async fn write_entry(
+        writer: &mut WalWriter,
+        entry: &WalEntry,
+    ) -> Result<(), WriteAheadLogError> {
+        let offset = writer.offset;
+        writer.file.seek(std::io::SeekFrom::Start(offset)).await?;
+        writer.file.write_all(&entry.serialize()).await?;
+        writer.file.flush().await?;
+        writer.offset += entry.length;
+        Ok(())
+    }
+
+    /// Makes every record appended so far durable
+    pub async fn flush(&self) -> Result<(), WriteAheadLogError> {
+        let writer = self.writer.lock().await;
+        writer.file.sync_data().await?;
+        self.flushed
+            .send_replace(writer.segment * SEGMENT_SIZE + writer.offset);
+        Ok(())
+    }
+
+    /// Follows the end of what has been flushed
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.flushed.subscribe()
+    }
+
+    /// Where the next record will go
+    pub async fn end_lsn(&self) -> u64 {
+        let writer = self.writer.lock().await;
+        writer.segment * SEGMENT_SIZE + writer.offset
+    }
+
+    /// Held while a change is logged and then applied, so a checkpoint can't pick a redo point
+    /// between the two and sync the files before the change reaches them
+    pub async fn delay_checkpoint(&self) -> RwLockReadGuard<'_, ()> {
+        self.checkpoint_delay.read().await
+    }
+
+    /// Where a checkpoint's redo starts, once every change already logged has been applied
+    pub async fn redo_point(&self) -> u64 {
+        let _delay = self.checkpoint_delay.write().await;
+        self.end_lsn().await
+    }
+
+    /// Finishes the current segment early so it is archived, like pg_switch_wal
+    pub async fn switch_segment(&self) -> Result<(), WriteAheadLogError> {
+        let mut writer = self.writer.lock().await;
+        if writer.offset > 0 {
+            self.next_segment(&mut writer).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the segments only holding records before the LSN, once they are archived.
+    /// Returns how many were removed.
+    pub async fn remove_before(&self, lsn: u64) -> Result<usize, WriteAheadLogError> {
+        let mut removed = 0;
+        for segment in WriteAheadLog::segments(&self.path).await? {
+            if segment >= lsn / SEGMENT_SIZE {
+                continue;
+            }
+            if let Some(a) = &self.archive {
+                if !WriteAheadLog::segment_path(a, segment).exists() {
+                    continue;
+                }
+            }
+            fs::remove_file(WriteAheadLog::segment_path(&self.path, segment)).await?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Reads every record from the LSN on, each segment is taken from the first directory that
+    /// has it. Stops at the first missing segment or the first torn record.
+    pub async fn read(
+        directories: &[PathBuf],
+        start: u64,
+    ) -> Result<Vec<WalEntry>, WriteAheadLogError> {
+        let mut entries = vec![];
+        let mut segment = start / SEGMENT_SIZE;
+        'segments: loop {
+            for d in directories {
+                let contents = match fs::read(WriteAheadLog::segment_path(d, segment)).await {
+                    Ok(c) => Bytes::from(c),
+                    Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                let (mut read, complete) = WriteAheadLog::parse_segment(segment, contents);
+                read.retain(|e| e.lsn >= start);
+                entries.append(&mut read);
+                if !complete {
+                    break 'segments;
+                }
+                segment += 1;
+                continue 'segments;
+            }
+            break;
+        }
+        Ok(entries)
+    }
+
+    /// The flushed records from the LSN on, for sending to a standby. Fails if the segment
+    /// holding the LSN is gone from both this log and its archive.
+    pub async fn read_since(&self, start: u64) -> Result<Vec<WalEntry>, WriteAheadLogError> {
+        let mut directories = vec![self.path.clone()];
+        directories.extend(self.archive.iter().cloned());
+        let segment = start / SEGMENT_SIZE;
+        if !directories
+            .iter()
+            .any(|d| WriteAheadLog::segment_path(d, segment).exists())
+        {
+            return Err(WriteAheadLogError::SegmentRemoved(segment));
+        }
+
+        let flushed = *self.flushed.borrow();
+        let mut entries = WriteAheadLog::read(&directories, start).await?;
+        entries.retain(|e| e.end() <= flushed);
+        Ok(entries)
+    }
+
+    /// The numbers of the segments in a directory, in order
+    pub async fn segments(path: &Path) -> Result<Vec<u64>, WriteAheadLogError> {
+        let mut segments = vec![];
+        let mut entries = match fs::read_dir(path).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(segments),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.len() == 16 {
+                if let Ok(s) = u64::from_str_radix(&name, 16) {
+                    segments.push(s);
+                }
+            }
+        }
+        segments.sort_unstable();
+        Ok(segments)
+    }
+
+    pub fn segment_path(directory: &Path, segment: u64) -> PathBuf {
+        directory.join(format!("{:016X}", segment))
+    }
+
+    //A segment is complete if it ended because the next record didn't fit, instead of at a
+    //torn record
+    fn parse_segment(segment: u64, mut contents: Bytes) -> (Vec<WalEntry>, bool) {
+        let mut entries = vec![];
+        let mut offset = 0;
+        while contents.has_remaining() {
+            match WalEntry::parse(&mut contents) {
+                Some(e) if e.lsn == segment * SEGMENT_SIZE + offset => {
+                    offset += e.length;
+                    entries.push(e);
+                }
+                _ => return (entries, false),
+            }
+        }
+        (entries, offset > 0)
+    }
+
+    async fn next_segment(&self, writer: &mut WalWriter) -> Result<(), WriteAheadLogError> {
+        writer.file.sync_all().await?;
+        self.flushed
+            .send_replace((writer.segment + 1) * SEGMENT_SIZE);
+        let completed = writer.segment;
+        writer.segment += 1;
+        writer.offset = 0;
+        writer.file = WriteAheadLog::open_segment(&self.path, writer.segment).await?;
+        WriteAheadLog::sync_directory(&self.path).await?;
+        self.archive_completed(completed + 1).await;
+        Ok(())
+    }
+
+    //Copies every segment before the one given that isn't archived yet, a failure is retried
+    //the next time a segment completes
+    async fn archive_completed(&self, current: u64) {
+        let archive = match &self.archive {
+            Some(a) => a,
+            None => return,
+        };
+        let segments = match WriteAheadLog::segments(&self.path).await {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Unable to list the WAL segments to archive: {}", e);
+                return;
+            }
+        };
+        for segment in segments.into_iter().filter(|s| *s < current) {
+            let target = WriteAheadLog::segment_path(archive, segment);
+            if target.exists() {
+                continue;
+            }
+            if let Err(e) = WriteAheadLog::archive_segment(&self.path, archive, segment).await {
+                warn!("Unable to archive WAL segment {}: {}", target.display(), e);
+            }
+        }
+    }
+
+    async fn archive_segment(
+        path: &Path,
+        archive: &Path,
+        segment: u64,
+    ) -> Result<(), WriteAheadLogError> {
+        let target = WriteAheadLog::segment_path(archive, segment);
+        let temp = target.with_extension("tmp");
+        fs::copy(WriteAheadLog::segment_path(path, segment), &temp).await?;
+        File::open(&temp).await?.sync_all().await?;
+        fs::rename(&temp, &target).await?;
+        WriteAheadLog::sync_directory(archive).await
+    }
+
+    async fn open_segment(path: &Path, segment: u64) -> Result<File, WriteAheadLogError> {
+        Ok(OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(WriteAheadLog::segment_path(path, segment))
+            .await?)
+    }
+
+    async fn sync_directory(path: &Path) -> Result<(), WriteAheadLogError> {
+        #[cfg(unix)]
+        File::open(path).await?.sync_all().await?;
+        #[cfg(not(unix))]
+        let _ = path;
+        Ok(())
+    }
+
+    fn checksum(data: &[u8]) -> u32 {
+        data.iter().fold(FNV_OFFSET_BASIS, |hash, b| {
+            (hash ^ u32::from(*b)).wrapping_mul(FNV_PRIME)
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum WriteAheadLogError {
+    #[error("A record of {0} bytes won't fit in a WAL segment")]
+    RecordTooLarge(u64),
+    #[error("WAL segment {0:X} has already been removed")]
+    SegmentRemoved(u64),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn records() -> Vec<WalRecord> {
+        vec![
+            WalRecord::PageImage {
+                relation: Uuid::new_v4(),
+                fork: ForkNumber::VisibilityMap,
+                page_num: 3,
+                page: Bytes::from(vec![7; 4096]),
+            },
+            WalRecord::Truncate {
+                relation: Uuid::new_v4(),
+                fork: ForkNumber::Main,
+                pages: 2,
+            },
+            WalRecord::DropRelation {
+                relation: Uuid::new_v4(),
+            },
+            WalRecord::TransactionStatus {
+                tran_id: TransactionId::new(42),
+                status: TransactionStatus::Commited,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_wal_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pg_wal");
+        let wal = aw!(WriteAheadLog::create(&path))?;
+
+        let mut records = records();
+        //Past what fits in 32 bits
+        records.push(WalRecord::Truncate {
+            relation: Uuid::new_v4(),
+            fork: ForkNumber::Main,
+            pages: 1 << 33,
+        });
+        records.push(WalRecord::SwapRelations {
+            left: Uuid::new_v4(),
+            left_file: Uuid::new_v4(),
+            right: Uuid::new_v4(),
+            right_file: Uuid::new_v4(),
+        });
+        let mut lsns = vec![];
+        for r in &records {
+            lsns.push(aw!(wal.append(r))?);
+        }
+        aw!(wal.flush())?;
+        assert_eq!(lsns[0], 0);
+
+        let entries = aw!(WriteAheadLog::read(std::slice::from_ref(&path), 0))?;
+        assert_eq!(
+            entries.iter().map(|e| e.record.clone()).collect::<Vec<_>>(),
+            records
+        );
+        assert_eq!(entries.iter().map(|e| e.lsn).collect::<Vec<_>>(), lsns);
+        assert_eq!(entries[5].end(), aw!(wal.end_lsn()));
+
+        let later = aw!(WriteAheadLog::read(std::slice::from_ref(&path), lsns[2]))?;
+        assert_eq!(later.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wal_torn_tail() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pg_wal");
+        let wal = aw!(WriteAheadLog::create(&path))?;
+        for r in records() {
+            aw!(wal.append(&r))?;
+        }
+        aw!(wal.flush())?;
+        let end = aw!(wal.end_lsn());
+
+        //A crash in the middle of writing the last record
+        let segment = WriteAheadLog::segment_path(&path, 0);
+        let mut contents = std::fs::read(&segment)?;
+        contents.truncate(contents.len() - 3);
+        std::fs::write(&segment, &contents)?;
+
+        let entries = aw!(WriteAheadLog::read(std::slice::from_ref(&path), 0))?;
+        assert_eq!(entries.len(), 3);
+
+        //Appending again overwrites it
+        let wal = aw!(WriteAheadLog::open(&path, entries[2].end(), None))?;
+        let lsn = aw!(wal.append(&records()[3]))?;
+        assert_eq!(lsn, entries[2].end());
+        assert_eq!(aw!(wal.end_lsn()), end);
+        assert_eq!(aw!(WriteAheadLog::read(&[path], 0))?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_delay() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let wal = aw!(WriteAheadLog::create(&tmp.path().join("pg_wal")))?;
+
+        //A redo point can't be picked between logging a change and applying it
+        let delay = aw!(wal.delay_checkpoint());
+        aw!(wal.append(&records()[3]))?;
+        assert!(wal.checkpoint_delay.try_write().is_err());
+        drop(delay);
+        assert_eq!(aw!(wal.redo_point()), aw!(wal.end_lsn()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wal_archive() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pg_wal");
+        let archive = tmp.path().join("archive");
+        aw!(WriteAheadLog::create(&path))?;
+        let wal = aw!(WriteAheadLog::open(&path, 0, Some(archive.clone())))?;
+
+        aw!(wal.append(&records()[0]))?;
+        aw!(wal.switch_segment())?;
+        assert_eq!(aw!(wal.end_lsn()), SEGMENT_SIZE);
+        let second = aw!(wal.append(&records()[1]))?;
+        assert_eq!(second, SEGMENT_SIZE);
+        assert_eq!(aw!(WriteAheadLog::segments(&archive))?, vec![0]);
+
+        //The archive has the first segment and pg_wal the rest
+        assert_eq!(aw!(wal.remove_before(second))?, 1);
+        assert_eq!(aw!(WriteAheadLog::segments(&path))?, vec![1]);
+        let entries = aw!(WriteAheadLog::read(&[archive, path], 0))?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].lsn, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wal_receive() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let primary_path = tmp.path().join("primary");
+        let standby_path = tmp.path().join("standby");
+        let primary = aw!(WriteAheadLog::create(&primary_path))?;
+        let standby = aw!(WriteAheadLog::create(&standby_path))?;
+        let mut flushed = primary.subscribe();
+
+        //Only what has been flushed is sent
+        aw!(primary.append(&records()[0]))?;
+        assert_eq!(aw!(primary.read_since(0))?, vec![]);
+        aw!(primary.flush())?;
+        assert!(flushed.has_changed()?);
+        assert_eq!(*flushed.borrow_and_update(), aw!(primary.end_lsn()));
+        aw!(primary.switch_segment())?;
+        for r in &records()[1..] {
+            aw!(primary.append(r))?;
+        }
+        aw!(primary.flush())?;
+
+        let sent = aw!(primary.read_since(0))?;
+        assert_eq!(sent.len(), 4);
+        for e in &sent {
+            let mut serialized = e.serialize();
+            let received = WalEntry::parse(&mut serialized).unwrap();
+            assert_eq!(&received, e);
+            assert!(!serialized.has_remaining());
+            aw!(standby.receive(&received))?;
+        }
+        aw!(standby.flush())?;
+
+        assert_eq!(aw!(standby.end_lsn()), aw!(primary.end_lsn()));
+        assert_eq!(aw!(WriteAheadLog::read(&[standby_path], 0))?, sent);
+        assert_eq!(aw!(primary.read_since(sent[1].lsn))?.len(), 3);
+
+        aw!(primary.remove_before(sent[1].lsn))?;
+        assert!(matches!(
+            aw!(primary.read_since(0)),
+            Err(WriteAheadLogError::SegmentRemoved(0))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovery_target() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pg_wal");
+        let wal = aw!(WriteAheadLog::create(&path))?;
+        for r in records() {
+            aw!(wal.append(&r))?;
+        }
+        let entries = aw!(WriteAheadLog::read(&[path], 0))?;
+
+        assert_eq!(
+            RecoveryTarget::Lsn(entries[1].lsn).stop_at(&entries),
+            Some(1)
+        );
+        assert_eq!(
+            RecoveryTarget::Lsn(entries[3].end()).stop_at(&entries),
+            Some(4)
+        );
+        assert_eq!(
+            RecoveryTarget::Lsn(entries[3].end() + 1).stop_at(&entries),
+            None
+        );
+        assert_eq!(
+            RecoveryTarget::TransactionId(TransactionId::new(42)).stop_at(&entries),
+            Some(4)
+        );
+        assert_eq!(
+            RecoveryTarget::TransactionId(TransactionId::new(43)).stop_at(&entries),
+            None
+        );
+        assert_eq!(
+            RecoveryTarget::Time(entries[0].written - Duration::from_micros(1)).stop_at(&entries),
+            Some(0)
+        );
+        Ok(())
+    }
+}
@@ -5,23 +5,54 @@ mod table;
 pub use table::Table;
 pub use table::TableError;
 
+mod copy_output;
+pub use copy_output::CopyOutput;
+
+mod expression;
+pub use expression::Aggregate;
+pub use expression::Expression;
+pub use expression::Function;
+pub use expression::Operator;
+pub use expression::OperatorError;
+pub use expression::Quantifier;
+pub use expression::SetFunction;
+pub use expression::Subscript;
+
+mod expression_context;
+pub use expression_context::ExpressionContext;
+
 mod parse_expression;
 pub use parse_expression::ParseExpression;
+pub use parse_expression::ParseSubscript;
 
 mod parse_tree;
+pub use parse_tree::CopyTarget;
 pub use parse_tree::ParseTree;
+pub use parse_tree::RawBeginCommand;
 pub use parse_tree::RawColumn;
+pub use parse_tree::RawCopyCommand;
 pub use parse_tree::RawCreateTableCommand;
+pub use parse_tree::RawCreateTypeCommand;
+pub use parse_tree::RawDropTableCommand;
 pub use parse_tree::RawInsertCommand;
+pub use parse_tree::RawRepackCommand;
 pub use parse_tree::RawSelectCommand;
+pub use parse_tree::RawSetCommand;
+pub use parse_tree::RawShowCommand;
+pub use parse_tree::RawSlotFunctionCommand;
+pub use parse_tree::RawTypeDefinition;
+pub use parse_tree::RawVacuumCommand;
 
 mod planned_statement;
+pub use planned_statement::AggregatePlan;
 pub use planned_statement::CartesianJoin;
 pub use planned_statement::FullTableScan;
 pub use planned_statement::ModifyTablePlan;
 pub use planned_statement::Plan;
 pub use planned_statement::PlannedCommon;
 pub use planned_statement::PlannedStatement;
+pub use planned_statement::ProjectSetPlan;
+pub use planned_statement::ProjectionPlan;
 
 mod query_result;
 pub use query_result::QueryResult;
@@ -32,7 +63,16 @@ pub use query_tree::JoinType;
 pub use query_tree::QueryTree;
 pub use query_tree::RangeRelation;
 pub use query_tree::RangeRelationTable;
+pub use query_tree::SortType;
 pub use query_tree::TargetEntry;
+pub use query_tree::WhereEntry;
+
+mod server_settings;
+pub use server_settings::ServerSettings;
+
+mod session_settings;
+pub use session_settings::SessionSettings;
+pub use session_settings::SessionSettingsError;
 
 mod sql_tuple;
 pub use sql_tuple::SqlTuple;
@@ -69,8 +69,8 @@ mod tests {
             Nullable::NotNull,
         );
         let test_existing = Attribute::new_existing(
-            test.id.clone(),
-            test.pg_class_id.clone(),
+            test.id,
+            test.pg_class_id,
             test.name.clone(),
             DeserializeTypes::Text,
             test.nullable,
@@ -0,0 +1,12 @@
+use bytes::Bytes;
+
+/// The rows of a COPY TO STDOUT already in the format asked for
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopyOutput {
+    pub binary: bool,
+    pub columns: usize,
+    ///How many rows there were, not counting any header
+    pub rows: usize,
+    ///Each is sent as its own copy data, the header if there is one, every row then the trailer
+    pub data: Vec<Bytes>,
+}
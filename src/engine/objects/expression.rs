@@ -0,0 +1,462 @@
+//! Expressions after the analyzer has resolved columns and types, ready to be evaluated
+use super::Attribute;
+use crate::constants::{BuiltinSqlTypes, DeserializeTypes};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    ///A constant value and its type, a None value is a typed null
+    Constant(Option<BuiltinSqlTypes>, DeserializeTypes),
+    ///Column of the input row by position
+    Column(usize, Attribute),
+    ///Both operands have already been cast to the types the operator works on, the last field
+    ///is the type it produces
+    Operator(Operator, Box<Expression>, Box<Expression>, DeserializeTypes),
+    Negate(Box<Expression>),
+    Cast(Box<Expression>, DeserializeTypes),
+    ///An explicit cast to varchar(n) or char(n), unlike storing into a column the value is cut
+    ///to fit instead of being an error
+    Truncate(Box<Expression>, DeserializeTypes),
+    Function(Function, Vec<Expression>),
+    ///Only valid in the targets of a select, the planner moves these into an aggregate plan
+    Aggregate(Aggregate, Box<Expression>),
+    ///ARRAY[...] and the array type it produces, nested arrays are stacked into more dimensions
+    Array(Vec<Expression>, DeserializeTypes),
+    Subscript(Box<Expression>, Vec<Subscript>),
+    ///Both sides have been cast so the left matches the element type of the right array
+    ArrayComparison(Operator, Quantifier, Box<Expression>, Box<Expression>),
+    ///Only valid in the targets of a select, the planner moves these into a project set plan
+    SetFunction(SetFunction, Box<Expression>),
+    ///ROW(...) and the composite type it makes, each field has been cast to its type
+    Row(Vec<Expression>, DeserializeTypes),
+    ///A field of a composite value by position and the field's type
+    FieldSelect(Box<Expression>, usize, DeserializeTypes),
+}
+
+impl Expression {
+    /// The type this expression will produce when evaluated
+    pub fn sql_type(&self) -> DeserializeTypes {
+        match self {
+            Expression::Constant(_, t) => t.clone(),
+            Expression::Column(_, a) => a.sql_type.clone(),
+            Expression::Operator(_, _, _, t) => t.clone(),
+            Expression::Negate(e) => e.sql_type().without_typmod(),
+            Expression::Cast(_, t) => t.clone(),
+            Expression::Truncate(_, t) => t.clone(),
+            Expression::Function(f, _) => f.sql_type(),
+            Expression::Aggregate(a, _) => a.sql_type(),
+            Expression::Array(_, t) => t.clone(),
+            Expression::Subscript(e, subscripts) => {
+                if subscripts
+                    .iter()
+                    .any(|s| matches!(s, Subscript::Slice(_, _)))
+                {
+                    e.sql_type()
+                } else {
+                    e.sql_type().element_type().clone()
+                }
+            }
+            Expression::ArrayComparison(_, _, _, _) => DeserializeTypes::Bool,
+            Expression::SetFunction(f, e) => f.sql_type(&e.sql_type()),
+            Expression::Row(_, t) => t.clone(),
+            Expression::FieldSelect(_, _, t) => t.clone(),
+        }
+    }
+
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            Expression::Aggregate(_, _) => true,
+            _ => self.children().iter().any(|c| c.contains_aggregate()),
+        }
+    }
+
+    pub fn contains_set_function(&self) -> bool {
+        match self {
+            Expression::SetFunction(_, _) => true,
+            _ => self.children().iter().any(|c| c.contains_set_function()),
+        }
+    }
+
+    /// The first column used outside of an aggregate, once a select has an aggregate every
+    /// column must be inside one
+    pub fn ungrouped_column(&self) -> Option<&Attribute> {
+        match self {
+            Expression::Column(_, a) => Some(a),
+            Expression::Aggregate(_, _) => None,
+            _ => self
+                .children()
+                .into_iter()
+                .find_map(|c| c.ungrouped_column()),
+        }
+    }
+
+    /// Rebuilds this expression with every direct child replaced by the result of the function
+    pub fn map_children(self, f: &mut impl FnMut(Expression) -> Expression) -> Expression {
+        let mut boxed = |e: Box<Expression>| Box::new(f(*e));
+        match self {
+            Expression::Constant(_, _) | Expression::Column(_, _) => self,
+            Expression::Operator(op, l, r, t) => {
+                let l = boxed(l);
+                Expression::Operator(op, l, boxed(r), t)
+            }
+            Expression::Negate(e) => Expression::Negate(boxed(e)),
+            Expression::Cast(e, t) => Expression::Cast(boxed(e), t),
+            Expression::Truncate(e, t) => Expression::Truncate(boxed(e), t),
+            Expression::Aggregate(a, e) => Expression::Aggregate(a, boxed(e)),
+            Expression::SetFunction(sf, e) => Expression::SetFunction(sf, boxed(e)),
+            Expression::FieldSelect(e, i, t) => Expression::FieldSelect(boxed(e), i, t),
+            Expression::ArrayComparison(op, q, l, r) => {
+                let l = boxed(l);
+                Expression::ArrayComparison(op, q, l, boxed(r))
+            }
+            Expression::Function(func, args) => {
+                Expression::Function(func, args.into_iter().map(f).collect())
+            }
+            Expression::Array(elements, t) => {
+                Expression::Array(elements.into_iter().map(f).collect(), t)
+            }
+            Expression::Row(fields, t) => Expression::Row(fields.into_iter().map(f).collect(), t),
+            Expression::Subscript(e, subscripts) => {
+                let e = Box::new(f(*e));
+                let subscripts = subscripts
+                    .into_iter()
+                    .map(|s| match s {
+                        Subscript::Index(i) => Subscript::Index(f(i)),
+                        Subscript::Slice(l, u) => Subscript::Slice(l.map(&mut *f), u.map(&mut *f)),
+                    })
+                    .collect();
+                Expression::Subscript(e, subscripts)
+            }
+        }
+    }
+
+    fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Constant(_, _) | Expression::Column(_, _) => vec![],
+            Expression::Operator(_, l, r, _) => vec![l, r],
+            Expression::Negate(e)
+            | Expression::Cast(e, _)
+            | Expression::Truncate(e, _)
+            | Expression::Aggregate(_, e)
+            | Expression::SetFunction(_, e)
+            | Expression::FieldSelect(e, _, _) => vec![e],
+            Expression::Function(_, args)
+            | Expression::Array(args, _)
+            | Expression::Row(args, _) => args.iter().collect(),
+            Expression::Subscript(e, subscripts) => {
+                let mut children = vec![e.as_ref()];
+                for s in subscripts {
+                    match s {
+                        Subscript::Index(i) => children.push(i),
+                        Subscript::Slice(l, u) => children.extend(l.iter().chain(u.iter())),
+                    }
+                }
+                children
+            }
+            Expression::ArrayComparison(_, _, l, r) => vec![l, r],
+        }
+    }
+}
+
+///Every subscript has been cast to an integer
+#[derive(Clone, Debug, PartialEq)]
+pub enum Subscript {
+    Index(Expression),
+    Slice(Option<Expression>, Option<Expression>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quantifier {
+    Any,
+    All,
+}
+
+impl fmt::Display for Quantifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quantifier::Any => write!(f, "ANY"),
+            Quantifier::All => write!(f, "ALL"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+    ///->, a json object's field or array's element
+    JsonGet,
+    ///->>, same as -> but as text
+    JsonGetText,
+    ///#>, the json value at a path of fields and elements
+    JsonPath,
+    ///#>>, same as #> but as text
+    JsonPathText,
+    ///@>, the left jsonb contains the right
+    JsonContains,
+    ///<@, the left jsonb is contained by the right
+    JsonContainedBy,
+    ///?, the jsonb has the key or string element
+    JsonKeyExists,
+}
+
+impl Operator {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Operator::Equal
+                | Operator::NotEqual
+                | Operator::LessThan
+                | Operator::LessEqual
+                | Operator::GreaterThan
+                | Operator::GreaterEqual
+        )
+    }
+
+    pub fn is_json(&self) -> bool {
+        matches!(
+            self,
+            Operator::JsonGet
+                | Operator::JsonGetText
+                | Operator::JsonPath
+                | Operator::JsonPathText
+                | Operator::JsonContains
+                | Operator::JsonContainedBy
+                | Operator::JsonKeyExists
+        )
+    }
+}
+
+impl FromStr for Operator {
+    type Err = OperatorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "+" => Ok(Operator::Add),
+            "-" => Ok(Operator::Subtract),
+            "*" => Ok(Operator::Multiply),
+            "/" => Ok(Operator::Divide),
+            "%" => Ok(Operator::Modulo),
+            "=" => Ok(Operator::Equal),
+            "<>" | "!=" => Ok(Operator::NotEqual),
+            "<" => Ok(Operator::LessThan),
+            "<=" => Ok(Operator::LessEqual),
+            ">" => Ok(Operator::GreaterThan),
+            ">=" => Ok(Operator::GreaterEqual),
+            "->" => Ok(Operator::JsonGet),
+            "->>" => Ok(Operator::JsonGetText),
+            "#>" => Ok(Operator::JsonPath),
+            "#>>" => Ok(Operator::JsonPathText),
+            "@>" => Ok(Operator::JsonContains),
+            "<@" => Ok(Operator::JsonContainedBy),
+            "?" => Ok(Operator::JsonKeyExists),
+            _ => Err(OperatorError::UnknownOperator(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Subtract => write!(f, "-"),
+            Operator::Multiply => write!(f, "*"),
+            Operator::Divide => write!(f, "/"),
+            Operator::Modulo => write!(f, "%"),
+            Operator::Equal => write!(f, "="),
+            Operator::NotEqual => write!(f, "<>"),
+            Operator::LessThan => write!(f, "<"),
+            Operator::LessEqual => write!(f, "<="),
+            Operator::GreaterThan => write!(f, ">"),
+            Operator::GreaterEqual => write!(f, ">="),
+            Operator::JsonGet => write!(f, "->"),
+            Operator::JsonGetText => write!(f, "->>"),
+            Operator::JsonPath => write!(f, "#>"),
+            Operator::JsonPathText => write!(f, "#>>"),
+            Operator::JsonContains => write!(f, "@>"),
+            Operator::JsonContainedBy => write!(f, "<@"),
+            Operator::JsonKeyExists => write!(f, "?"),
+        }
+    }
+}
+
+///Builtin functions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function {
+    ///The start of the current transaction, also known as current_timestamp
+    Now,
+    ///The actual current time, it changes during a statement
+    ClockTimestamp,
+    CurrentDate,
+    LocalTime,
+    LocalTimestamp,
+    ///Alternating keys and values of any type
+    JsonBuildObject,
+    JsonbBuildObject,
+    ///Elements of any type
+    JsonBuildArray,
+    JsonbBuildArray,
+    ToJson,
+    ToJsonb,
+    JsonTypeof,
+    JsonbTypeof,
+}
+
+impl Function {
+    pub fn sql_type(&self) -> DeserializeTypes {
+        match self {
+            Function::Now | Function::ClockTimestamp => DeserializeTypes::TimestampTz,
+            Function::CurrentDate => DeserializeTypes::Date,
+            Function::LocalTime => DeserializeTypes::Time,
+            Function::LocalTimestamp => DeserializeTypes::Timestamp,
+            Function::JsonBuildObject | Function::JsonBuildArray | Function::ToJson => {
+                DeserializeTypes::Json
+            }
+            Function::JsonbBuildObject | Function::JsonbBuildArray | Function::ToJsonb => {
+                DeserializeTypes::Jsonb
+            }
+            Function::JsonTypeof | Function::JsonbTypeof => DeserializeTypes::Text,
+        }
+    }
+
+    pub fn accepts_arguments(&self, count: usize) -> bool {
+        match self {
+            Function::Now
+            | Function::ClockTimestamp
+            | Function::CurrentDate
+            | Function::LocalTime
+            | Function::LocalTimestamp => count == 0,
+            Function::JsonBuildObject | Function::JsonbBuildObject => count.is_multiple_of(2),
+            Function::JsonBuildArray | Function::JsonbBuildArray => true,
+            Function::ToJson | Function::ToJsonb | Function::JsonTypeof | Function::JsonbTypeof => {
+                count == 1
+            }
+        }
+    }
+
+    /// The type every argument must be, None means any type is accepted
+    pub fn argument_type(&self) -> Option<DeserializeTypes> {
+        match self {
+            Function::JsonTypeof => Some(DeserializeTypes::Json),
+            Function::JsonbTypeof => Some(DeserializeTypes::Jsonb),
+            _ => None,
+        }
+    }
+
+    /// Strict functions give null for a null argument without being run
+    pub fn is_strict(&self) -> bool {
+        matches!(
+            self,
+            Function::ToJson | Function::ToJsonb | Function::JsonTypeof | Function::JsonbTypeof
+        )
+    }
+}
+
+impl FromStr for Function {
+    type Err = OperatorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "now" | "current_timestamp" | "transaction_timestamp" => Ok(Function::Now),
+            "clock_timestamp" => Ok(Function::ClockTimestamp),
+            "current_date" => Ok(Function::CurrentDate),
+            "localtime" => Ok(Function::LocalTime),
+            "localtimestamp" => Ok(Function::LocalTimestamp),
+            "json_build_object" => Ok(Function::JsonBuildObject),
+            "jsonb_build_object" => Ok(Function::JsonbBuildObject),
+            "json_build_array" => Ok(Function::JsonBuildArray),
+            "jsonb_build_array" => Ok(Function::JsonbBuildArray),
+            "to_json" => Ok(Function::ToJson),
+            "to_jsonb" => Ok(Function::ToJsonb),
+            "json_typeof" => Ok(Function::JsonTypeof),
+            "jsonb_typeof" => Ok(Function::JsonbTypeof),
+            _ => Err(OperatorError::UnknownFunction(s.to_string())),
+        }
+    }
+}
+
+///Aggregate functions, they take one argument and combine it across every input row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    JsonAgg,
+    JsonbAgg,
+}
+
+impl Aggregate {
+    pub fn sql_type(&self) -> DeserializeTypes {
+        match self {
+            Aggregate::JsonAgg => DeserializeTypes::Json,
+            Aggregate::JsonbAgg => DeserializeTypes::Jsonb,
+        }
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = OperatorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json_agg" => Ok(Aggregate::JsonAgg),
+            "jsonb_agg" => Ok(Aggregate::JsonbAgg),
+            _ => Err(OperatorError::UnknownFunction(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Aggregate::JsonAgg => write!(f, "json_agg"),
+            Aggregate::JsonbAgg => write!(f, "jsonb_agg"),
+        }
+    }
+}
+
+///Functions that return a set of rows instead of a single value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetFunction {
+    ///One row per array element, in storage order no matter the dimensions
+    Unnest,
+}
+
+impl SetFunction {
+    pub fn sql_type(&self, argument: &DeserializeTypes) -> DeserializeTypes {
+        match self {
+            SetFunction::Unnest => argument.element_type().clone(),
+        }
+    }
+}
+
+impl FromStr for SetFunction {
+    type Err = OperatorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "unnest" => Ok(SetFunction::Unnest),
+            _ => Err(OperatorError::UnknownFunction(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SetFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetFunction::Unnest => write!(f, "unnest"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum OperatorError {
+    #[error("Unknown operator {0}")]
+    UnknownOperator(String),
+    #[error("function {0}() does not exist")]
+    UnknownFunction(String),
+}
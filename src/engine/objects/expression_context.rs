@@ -0,0 +1,12 @@
+//! State from outside of the query that evaluating an expression can depend on
+use crate::constants::{ByteaOutput, TimeZone};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExpressionContext {
+    ///When the current transaction started as a timestamp with time zone, what now() returns
+    pub transaction_start: i64,
+    ///The session's zone, timestamps with time zone are read and shown in it
+    pub time_zone: TimeZone,
+    ///How bytea values are written when cast to text
+    pub bytea_output: ByteaOutput,
+}
@@ -1,5 +1,41 @@
+use super::Quantifier;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum ParseExpression {
+    ///A quoted literal, its type is unknown until it is used
     String(String),
+    ///An unquoted numeric literal
+    Number(String),
+    Bool(bool),
     Null(),
+    Identifier(String),
+    ///Binary operator, the operator name followed by the left and right sides
+    Operator(String, Box<ParseExpression>, Box<ParseExpression>),
+    Negate(Box<ParseExpression>),
+    ///Expression and the name of the type to cast it to
+    Cast(Box<ParseExpression>, String),
+    ///Function name and its arguments
+    Function(String, Vec<ParseExpression>),
+    ///ARRAY[...], nested brackets inside are also parsed as arrays
+    Array(Vec<ParseExpression>),
+    ///An expression followed by one or more subscripts, foo[1][2:3]
+    Subscript(Box<ParseExpression>, Vec<ParseSubscript>),
+    ///A comparison against every element of an array, foo = ANY(bar)
+    ArrayComparison(
+        String,
+        Quantifier,
+        Box<ParseExpression>,
+        Box<ParseExpression>,
+    ),
+    ///ROW(...) or a parenthesized list of more than one expression
+    Row(Vec<ParseExpression>),
+    ///A field of a composite value, (foo).bar
+    FieldSelect(Box<ParseExpression>, String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseSubscript {
+    Index(ParseExpression),
+    ///Either side can be left out to run to that end of the array
+    Slice(Option<ParseExpression>, Option<ParseExpression>),
 }
@@ -2,9 +2,48 @@ use super::ParseExpression;
 
 #[derive(Clone, Debug)]
 pub enum ParseTree {
+    Begin(RawBeginCommand),
+    Checkpoint,
+    Commit,
+    Copy(RawCopyCommand),
     CreateTable(RawCreateTableCommand),
+    CreateType(RawCreateTypeCommand),
+    DropTable(RawDropTableCommand),
     Insert(RawInsertCommand),
+    Promote,
+    Repack(RawRepackCommand),
+    Rollback,
     Select(RawSelectCommand),
+    Set(RawSetCommand),
+    Show(RawShowCommand),
+    SlotFunction(RawSlotFunctionCommand),
+    Vacuum(RawVacuumCommand),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawBeginCommand {
+    ///Read only transactions see a snapshot from when they started
+    pub read_only: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawCopyCommand {
+    pub table_name: String,
+    ///None means every column in order
+    pub columns: Option<Vec<String>>,
+    pub target: CopyTarget,
+    ///Lowercase option names with their value if one was given
+    pub options: Vec<(String, Option<String>)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CopyTarget {
+    ///COPY FROM STDIN, the client sends the rows
+    Stdin,
+    ///COPY TO STDOUT, the rows are sent to the client
+    Stdout,
+    ///COPY FROM a file the server reads
+    File(String),
 }
 
 #[derive(Clone, Debug)]
@@ -13,6 +52,25 @@ pub struct RawCreateTableCommand {
     pub provided_columns: Vec<RawColumn>,
 }
 
+#[derive(Clone, Debug)]
+pub struct RawCreateTypeCommand {
+    pub type_name: String,
+    pub definition: RawTypeDefinition,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawDropTableCommand {
+    pub table_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RawTypeDefinition {
+    ///The labels in sort order
+    Enum(Vec<String>),
+    ///The fields, they are always nullable
+    Composite(Vec<RawColumn>),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct RawColumn {
     pub name: String,
@@ -27,9 +85,39 @@ pub struct RawInsertCommand {
     pub provided_values: Vec<ParseExpression>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawRepackCommand {
+    pub table_name: String,
+}
+
 //TODO This is VERY bare bones, will be radically changed once more is implemented
 #[derive(Clone, Debug, PartialEq)]
 pub struct RawSelectCommand {
-    pub columns: Vec<String>,
-    pub table: String,
+    pub columns: Vec<ParseExpression>,
+    pub table: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawSetCommand {
+    pub name: String,
+    ///None means DEFAULT
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawShowCommand {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawSlotFunctionCommand {
+    ///One of the replication slot functions, lower case
+    pub function: String,
+    pub args: Vec<ParseExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawVacuumCommand {
+    ///None means every table
+    pub table_name: Option<String>,
 }
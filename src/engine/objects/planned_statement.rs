@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use super::{Attribute, SqlTuple, Table};
+use super::{Aggregate, Attribute, Expression, SetFunction, SqlTuple, Table};
 
 pub struct PlannedStatement {
     pub common: PlannedCommon,
@@ -10,12 +10,22 @@ pub struct PlannedStatement {
 pub struct PlannedCommon {}
 
 pub enum Plan {
+    Aggregate(AggregatePlan),
     CartesianJoin(CartesianJoin),
     FullTableScan(FullTableScan),
     ModifyTable(ModifyTablePlan),
+    Projection(ProjectionPlan),
+    ProjectSet(ProjectSetPlan),
     StaticData(Arc<Vec<SqlTuple>>),
 }
 
+///Consumes every row of the source and gives back a single row holding each aggregate's result
+pub struct AggregatePlan {
+    ///Each aggregate and the argument it is computed from, evaluated against the source rows
+    pub aggregates: Vec<(Aggregate, Expression)>,
+    pub source: Arc<Plan>,
+}
+
 pub struct CartesianJoin {
     ///Output columns from this plan
     //pub columns: Vec<Attribute>,
@@ -38,3 +48,17 @@ pub struct ModifyTablePlan {
     pub table: Arc<Table>,
     pub source: Arc<Plan>,
 }
+
+pub struct ProjectionPlan {
+    ///Evaluated against every row of the source to produce the output columns
+    pub targets: Vec<Expression>,
+    pub source: Arc<Plan>,
+}
+
+///Appends each set function's values to the source row, one output row per value. Functions
+///that run out before the longest one give nulls, like postgres.
+pub struct ProjectSetPlan {
+    ///Each set function and its argument, evaluated against the source rows
+    pub functions: Vec<(SetFunction, Expression)>,
+    pub source: Arc<Plan>,
+}
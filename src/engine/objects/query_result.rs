@@ -1,7 +1,8 @@
 use super::SqlTuple;
+use crate::constants::DeserializeTypes;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct QueryResult {
-    pub columns: Vec<String>,
+    pub columns: Vec<(String, DeserializeTypes)>,
     pub rows: Vec<SqlTuple>,
 }
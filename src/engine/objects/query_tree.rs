@@ -1,6 +1,6 @@
 //! Is the result of the parse tree post validation
 //! See here: https://www.postgresql.org/docs/current/querytree.html
-use super::Attribute;
+use super::Expression;
 use super::SqlTuple;
 use super::Table;
 use std::sync::Arc;
@@ -60,8 +60,9 @@ pub struct RangeRelationTable {
 //relations in the range table, a parameter, or an expression tree
 //made of function calls, constants, variables, operators, etc.
 #[derive(Clone, Debug)]
-pub enum TargetEntry {
-    Parameter(Attribute),
+pub struct TargetEntry {
+    pub name: String,
+    pub expression: Expression,
 }
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,175 @@
+//! Settings fixed for as long as the server runs, given on the command line with -c. SHOW reads
+//! them like any other setting but SET can't change them.
+use super::SessionSettingsError;
+use crate::engine::background_writer::{BGWRITER_DELAY, BGWRITER_LRU_MAXPAGES};
+use crate::engine::checkpointer::CHECKPOINT_TIMEOUT;
+use std::time::Duration;
+
+//The units a time setting can be given in, smallest first
+const TIME_UNITS: [(&str, u64); 5] = [
+    ("ms", 1),
+    ("s", 1000),
+    ("min", 60_000),
+    ("h", 3_600_000),
+    ("d", 86_400_000),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerSettings {
+    ///How long the checkpointer waits between checkpoints
+    pub checkpoint_timeout: Duration,
+    ///How long the background writer sleeps between rounds
+    pub bgwriter_delay: Duration,
+    ///The most files the background writer flushes in a round, 0 turns it off
+    pub bgwriter_lru_maxpages: usize,
+    ///If COPY can read files on the server. There are no roles to grant postgres'
+    ///pg_read_server_files to, so it is all clients or none.
+    pub allow_server_files: bool,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            checkpoint_timeout: CHECKPOINT_TIMEOUT,
+            bgwriter_delay: BGWRITER_DELAY,
+            bgwriter_lru_maxpages: BGWRITER_LRU_MAXPAGES,
+            allow_server_files: false,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Changes a setting before the server starts, times without a unit are in the setting's
+    /// own unit like postgres
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SessionSettingsError> {
+        let name = ServerSettings::canonical_name(name)
+            .ok_or_else(|| SessionSettingsError::UnknownSetting(name.to_string()))?;
+        let invalid = || SessionSettingsError::InvalidValue(name.to_string(), value.to_string());
+        match name {
+            "checkpoint_timeout" => {
+                self.checkpoint_timeout = ServerSettings::parse_time(value, 1000)
+                    .filter(|t| (Duration::from_secs(30)..=Duration::from_secs(86_400)).contains(t))
+                    .ok_or_else(invalid)?;
+            }
+            "bgwriter_delay" => {
+                self.bgwriter_delay = ServerSettings::parse_time(value, 1)
+                    .filter(|t| (Duration::from_millis(10)..=Duration::from_secs(10)).contains(t))
+                    .ok_or_else(invalid)?;
+            }
+            "bgwriter_lru_maxpages" => {
+                self.bgwriter_lru_maxpages = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|p| *p <= 1_073_741_823)
+                    .ok_or_else(invalid)?;
+            }
+            "allow_server_files" => {
+                self.allow_server_files = match value.trim().to_lowercase().as_str() {
+                    "on" | "true" | "yes" | "1" => true,
+                    "off" | "false" | "no" | "0" => false,
+                    _ => return Err(invalid()),
+                };
+            }
+            _ => return Err(SessionSettingsError::UnknownSetting(name.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn show(&self, name: &str) -> Result<String, SessionSettingsError> {
+        match ServerSettings::canonical_name(name) {
+            Some("checkpoint_timeout") => Ok(ServerSettings::format_time(self.checkpoint_timeout)),
+            Some("bgwriter_delay") => Ok(ServerSettings::format_time(self.bgwriter_delay)),
+            Some("bgwriter_lru_maxpages") => Ok(self.bgwriter_lru_maxpages.to_string()),
+            Some("allow_server_files") if self.allow_server_files => Ok("on".to_string()),
+            Some("allow_server_files") => Ok("off".to_string()),
+            _ => Err(SessionSettingsError::UnknownSetting(name.to_string())),
+        }
+    }
+
+    /// How postgres spells the setting, if it is one of these
+    pub fn canonical_name(name: &str) -> Option<&'static str> {
+        match name.to_lowercase().as_str() {
+            "checkpoint_timeout" => Some("checkpoint_timeout"),
+            "bgwriter_delay" => Some("bgwriter_delay"),
+            "bgwriter_lru_maxpages" => Some("bgwriter_lru_maxpages"),
+            "allow_server_files" => Some("allow_server_files"),
+            _ => None,
+        }
+    }
+
+    //A number followed by an optional unit, like 5min
+    fn parse_time(value: &str, default_unit: u64) -> Option<Duration> {
+        let value = value.trim();
+        let digits = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(digits);
+        let unit = match unit.trim() {
+            "" => default_unit,
+            u => TIME_UNITS.iter().find(|(n, _)| *n == u)?.1,
+        };
+        let millis = number.parse::<u64>().ok()?.checked_mul(unit)?;
+        Some(Duration::from_millis(millis))
+    }
+
+    //In the largest unit that keeps it whole, like postgres shows them
+    fn format_time(time: Duration) -> String {
+        let millis = time.as_millis() as u64;
+        let (name, unit) = TIME_UNITS
+            .iter()
+            .rev()
+            .find(|(_, u)| millis.is_multiple_of(*u))
+            .unwrap_or(&TIME_UNITS[0]);
+        format!("{}{}", millis / unit, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_settings() -> Result<(), Box<dyn std::error::Error>> {
+        let mut settings = ServerSettings::default();
+        assert_eq!(settings.show("checkpoint_timeout")?, "5min");
+        assert_eq!(settings.show("BGWRITER_DELAY")?, "200ms");
+        assert_eq!(settings.show("bgwriter_lru_maxpages")?, "100");
+
+        settings.set("checkpoint_timeout", "90")?;
+        assert_eq!(settings.checkpoint_timeout, Duration::from_secs(90));
+        assert_eq!(settings.show("checkpoint_timeout")?, "90s");
+        settings.set("checkpoint_timeout", "1h")?;
+        assert_eq!(settings.show("checkpoint_timeout")?, "1h");
+        settings.set("bgwriter_delay", "1s")?;
+        assert_eq!(settings.bgwriter_delay, Duration::from_millis(1000));
+        settings.set("bgwriter_lru_maxpages", "0")?;
+        assert_eq!(settings.bgwriter_lru_maxpages, 0);
+        assert_eq!(settings.show("allow_server_files")?, "off");
+        settings.set("Allow_Server_Files", "ON")?;
+        assert!(settings.allow_server_files);
+        assert_eq!(settings.show("allow_server_files")?, "on");
+
+        for (name, value) in [
+            ("checkpoint_timeout", "10"),
+            ("checkpoint_timeout", "2d"),
+            ("checkpoint_timeout", "5 weeks"),
+            ("bgwriter_delay", "-1"),
+            ("bgwriter_delay", "5ms"),
+            ("bgwriter_lru_maxpages", "lots"),
+            ("allow_server_files", "maybe"),
+        ] {
+            assert!(matches!(
+                settings.set(name, value),
+                Err(SessionSettingsError::InvalidValue(_, _))
+            ));
+        }
+        assert_eq!(settings.show("checkpoint_timeout")?, "1h");
+
+        assert!(matches!(
+            settings.set("work_mem", "4MB"),
+            Err(SessionSettingsError::UnknownSetting(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! Settings a client can change for its own connection with SET and read back with SHOW
+use super::ServerSettings;
+use crate::constants::{ByteaOutput, PgErrorCodes, TimeZone};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Clone, Debug, Default)]
+pub struct SessionSettings {
+    pub time_zone: TimeZone,
+    pub bytea_output: ByteaOutput,
+    ///Shown with the rest but fixed when the server starts
+    pub server: ServerSettings,
+}
+
+impl SessionSettings {
+    /// Changes a setting, no value puts it back to the default
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<(), SessionSettingsError> {
+        match SessionSettings::canonical_name(name)? {
+            "TimeZone" => {
+                self.time_zone = match value {
+                    Some(v) => TimeZone::from_str(v).map_err(|_| {
+                        SessionSettingsError::InvalidValue("TimeZone".to_string(), v.to_string())
+                    })?,
+                    None => TimeZone::default(),
+                };
+            }
+            "bytea_output" => {
+                self.bytea_output = match value {
+                    Some(v) => ByteaOutput::from_str(v).map_err(|_| {
+                        SessionSettingsError::InvalidValue(
+                            "bytea_output".to_string(),
+                            v.to_string(),
+                        )
+                    })?,
+                    None => ByteaOutput::default(),
+                };
+            }
+            server => return Err(SessionSettingsError::CantChangeNow(server.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn show(&self, name: &str) -> Result<String, SessionSettingsError> {
+        match SessionSettings::canonical_name(name)? {
+            "TimeZone" => Ok(self.time_zone.to_string()),
+            "bytea_output" => Ok(self.bytea_output.to_string()),
+            server => self.server.show(server),
+        }
+    }
+
+    /// Setting names are case insensitive, this is how postgres spells them when reporting
+    /// them to the client
+    pub fn canonical_name(name: &str) -> Result<&'static str, SessionSettingsError> {
+        match name.to_lowercase().as_str() {
+            "timezone" => Ok("TimeZone"),
+            "bytea_output" => Ok("bytea_output"),
+            _ => ServerSettings::canonical_name(name)
+                .ok_or_else(|| SessionSettingsError::UnknownSetting(name.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SessionSettingsError {
+    #[error("parameter \"{0}\" cannot be changed without restarting the server")]
+    CantChangeNow(String),
+    #[error("invalid value for parameter \"{0}\": \"{1}\"")]
+    InvalidValue(String, String),
+    #[error("unrecognized configuration parameter \"{0}\"")]
+    UnknownSetting(String),
+}
+
+impl SessionSettingsError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            SessionSettingsError::CantChangeNow(_) => PgErrorCodes::CantChangeRuntimeParam,
+            SessionSettingsError::InvalidValue(_, _) => PgErrorCodes::InvalidParameterValue,
+            SessionSettingsError::UnknownSetting(_) => PgErrorCodes::UndefinedObject,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() -> Result<(), Box<dyn std::error::Error>> {
+        let mut settings = SessionSettings::default();
+        assert_eq!(settings.show("TimeZone")?, "UTC");
+
+        settings.set("timezone", Some("america/chicago"))?;
+        assert_eq!(settings.show("TIMEZONE")?, "America/Chicago");
+
+        assert!(matches!(
+            settings.set("TimeZone", Some("Not/AZone")),
+            Err(SessionSettingsError::InvalidValue(_, _))
+        ));
+        assert_eq!(settings.show("timezone")?, "America/Chicago");
+
+        settings.set("timezone", None)?;
+        assert_eq!(settings.show("timezone")?, "UTC");
+
+        assert_eq!(settings.show("bytea_output")?, "hex");
+        settings.set("BYTEA_OUTPUT", Some("Escape"))?;
+        assert_eq!(settings.show("bytea_output")?, "escape");
+        assert!(settings.set("bytea_output", Some("base64")).is_err());
+
+        assert_eq!(settings.show("Checkpoint_Timeout")?, "5min");
+        assert_eq!(
+            SessionSettings::canonical_name("BGWRITER_DELAY")?,
+            "bgwriter_delay"
+        );
+        assert!(matches!(
+            settings.set("checkpoint_timeout", Some("1min")),
+            Err(SessionSettingsError::CantChangeNow(_))
+        ));
+        assert!(settings.set("bgwriter_delay", None).is_err());
+
+        assert!(matches!(
+            settings.show("work_mem"),
+            Err(SessionSettingsError::UnknownSetting(_))
+        ));
+        Ok(())
+    }
+}
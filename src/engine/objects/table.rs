@@ -1,6 +1,7 @@
 //! Postgres doc: https://www.postgresql.org/docs/current/catalog-pg-class.html
 
 use super::Attribute;
+use crate::constants::{DeserializeTypes, Nullable};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -33,6 +34,60 @@ impl Table {
 
         Err(TableError::ColumnDoesNotExist(name))
     }
+
+    /// The relation holding the chunks of this table's out of line values.
+    ///
+    /// Its id is this table's id with every bit flipped, the version bits mean it can never match a random uuid.
+    pub fn toast_table(&self) -> Table {
+        let id = Uuid::from_u128(!self.id.as_u128());
+        Table::new_existing(
+            id,
+            format!("pg_toast_{}", self.id),
+            vec![
+                Attribute::new(
+                    id,
+                    "chunk_id".to_string(),
+                    DeserializeTypes::Uuid,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    id,
+                    "chunk_seq".to_string(),
+                    DeserializeTypes::Integer,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    id,
+                    "chunk_data".to_string(),
+                    DeserializeTypes::Bytea,
+                    Nullable::NotNull,
+                ),
+            ],
+        )
+    }
+
+    /// A new relation to record which rows of this one changed while it is being repacked
+    pub fn repack_log_table(&self) -> Table {
+        let id = Uuid::new_v4();
+        Table::new_existing(
+            id,
+            format!("pg_repack_log_{}", self.id),
+            vec![
+                Attribute::new(
+                    id,
+                    "page".to_string(),
+                    DeserializeTypes::BigInt,
+                    Nullable::NotNull,
+                ),
+                Attribute::new(
+                    id,
+                    "count".to_string(),
+                    DeserializeTypes::Integer,
+                    Nullable::NotNull,
+                ),
+            ],
+        )
+    }
 }
 
 #[derive(Error, Debug)]
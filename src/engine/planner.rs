@@ -1,26 +1,23 @@
 //! The planner takes a parsed query and makes it into a set of commands that can be sequentially executed.
 use super::objects::{
-    CommandType, JoinType, ModifyTablePlan, Plan, PlannedCommon, PlannedStatement, QueryTree,
-    RangeRelation,
+    Aggregate, AggregatePlan, Attribute, CommandType, Expression, JoinType, ModifyTablePlan, Plan,
+    PlannedCommon, PlannedStatement, ProjectSetPlan, ProjectionPlan, QueryTree, RangeRelation,
+    SetFunction,
 };
-use crate::engine::objects::{FullTableScan, TargetEntry};
+use crate::constants::Nullable;
+use crate::engine::objects::FullTableScan;
 use std::sync::Arc;
 use thiserror::Error;
+use uuid::Uuid;
 
 pub struct Planner {}
 
 impl Planner {
     pub fn plan(query_tree: QueryTree) -> Result<PlannedStatement, PlannerError> {
         match query_tree.command_type {
-            CommandType::Insert => {
-                return Planner::plan_insert(query_tree);
-            }
-            CommandType::Select => {
-                return Planner::plan_select(query_tree);
-            }
-            _ => {
-                return Err(PlannerError::NotImplemented());
-            }
+            CommandType::Insert => Planner::plan_insert(query_tree),
+            CommandType::Select => Planner::plan_select(query_tree),
+            _ => Err(PlannerError::NotImplemented()),
         }
     }
 
@@ -32,54 +29,94 @@ impl Planner {
         let join = query_tree
             .joins
             .first()
-            .ok_or_else(|| PlannerError::TooManyJoins(query_tree.joins.len()))?;
+            .ok_or(PlannerError::TooManyJoins(query_tree.joins.len()))?;
 
         match join {
             (JoinType::Inner, RangeRelation::Table(t), RangeRelation::AnonymousTable(at)) => {
-                return Ok(PlannedStatement {
+                //The values are computed from the anonymous table's rows
+                let targets = query_tree
+                    .targets
+                    .iter()
+                    .map(|t| t.expression.clone())
+                    .collect();
+                Ok(PlannedStatement {
                     common: PlannedCommon {},
                     plan: Arc::new(Plan::ModifyTable(ModifyTablePlan {
                         table: t.table.clone(),
-                        source: Arc::new(Plan::StaticData(at.clone())),
+                        source: Arc::new(Plan::Projection(ProjectionPlan {
+                            targets,
+                            source: Arc::new(Plan::StaticData(at.clone())),
+                        })),
                     })),
-                });
+                })
             }
-            (_, _, _) => return Err(PlannerError::NotImplemented()),
+            (_, _, _) => Err(PlannerError::NotImplemented()),
         }
     }
 
     fn plan_select(query_tree: QueryTree) -> Result<PlannedStatement, PlannerError> {
         //TODO I'm ignoring joins at the moment
 
-        let mut targets = vec![];
-        for t in query_tree.targets {
-            match t {
-                TargetEntry::Parameter(p) => targets.push(p),
-            }
-        }
+        let targets: Vec<Expression> = query_tree
+            .targets
+            .into_iter()
+            .map(|t| t.expression)
+            .collect();
 
+        //Each source also gives how many columns wide its rows are
         let mut unjoined = vec![];
         for rr in query_tree.range_tables {
             match rr {
                 RangeRelation::Table(rrt) => {
-                    unjoined.push(Arc::new(Plan::FullTableScan(FullTableScan {
-                        columns: targets.clone(), //TODO I know not every table needs every column
-                        table: rrt.table,
-                    })));
+                    //TODO once indexes exist pick an index only scan when one covers every column
+                    //referenced, it only visits heap pages the visibility map doesn't have as all visible
+                    let width = rrt.table.attributes.len();
+                    unjoined.push((
+                        Arc::new(Plan::FullTableScan(FullTableScan {
+                            columns: rrt.table.attributes.clone(), //TODO I know not every table needs every column
+                            table: rrt.table,
+                        })),
+                        width,
+                    ));
                 }
                 RangeRelation::AnonymousTable(anon_tbl) => {
-                    unjoined.push(Arc::new(Plan::StaticData(anon_tbl.clone())));
+                    let width = anon_tbl.first().map(|r| r.0.len()).unwrap_or(0);
+                    unjoined.push((Arc::new(Plan::StaticData(anon_tbl.clone())), width));
                 }
             }
         }
 
-        if unjoined.len() == 0 {
-            return Err(PlannerError::NoDataProvided());
+        if unjoined.is_empty() {
+            Err(PlannerError::NoDataProvided())
         } else if unjoined.len() == 1 {
-            return Ok(PlannedStatement {
+            let (mut source, mut width) = unjoined[0].clone();
+            let mut targets = targets;
+
+            //Aggregates are computed first, the targets then read their results as columns
+            if targets.iter().any(Expression::contains_aggregate) {
+                let mut aggregates = vec![];
+                targets = targets
+                    .into_iter()
+                    .map(|t| Planner::extract_aggregates(t, &mut aggregates))
+                    .collect();
+                width = aggregates.len();
+                source = Arc::new(Plan::Aggregate(AggregatePlan { aggregates, source }));
+            }
+
+            //Set functions come next, their values are added after the source's columns
+            if targets.iter().any(Expression::contains_set_function) {
+                let mut functions = vec![];
+                targets = targets
+                    .into_iter()
+                    .map(|t| Planner::extract_set_functions(t, width, &mut functions))
+                    .collect();
+                source = Arc::new(Plan::ProjectSet(ProjectSetPlan { functions, source }));
+            }
+
+            Ok(PlannedStatement {
                 common: PlannedCommon {},
-                plan: unjoined[0].clone(),
-            });
+                plan: Arc::new(Plan::Projection(ProjectionPlan { targets, source })),
+            })
         } else {
             //let cart_joins = return Ok(PlannedStatement {
             //    common: PlannedCommon {},
@@ -88,7 +125,45 @@ impl Planner {
             //        source: unjoined,
             //    })),
             //});
-            return Err(PlannerError::NotImplemented());
+            Err(PlannerError::NotImplemented())
+        }
+    }
+
+    //Swaps every aggregate for a column of the aggregate plan's output row
+    fn extract_aggregates(
+        expression: Expression,
+        aggregates: &mut Vec<(Aggregate, Expression)>,
+    ) -> Expression {
+        match expression {
+            Expression::Aggregate(a, argument) => {
+                let column =
+                    Attribute::new(Uuid::nil(), a.to_string(), a.sql_type(), Nullable::Null);
+                aggregates.push((a, *argument));
+                Expression::Column(aggregates.len() - 1, column)
+            }
+            _ => expression.map_children(&mut |e| Planner::extract_aggregates(e, aggregates)),
+        }
+    }
+
+    //Swaps every set function for a column after the source row's columns
+    fn extract_set_functions(
+        expression: Expression,
+        width: usize,
+        functions: &mut Vec<(SetFunction, Expression)>,
+    ) -> Expression {
+        match expression {
+            Expression::SetFunction(f, argument) => {
+                let column = Attribute::new(
+                    Uuid::nil(),
+                    f.to_string(),
+                    f.sql_type(&argument.sql_type()),
+                    Nullable::Null,
+                );
+                functions.push((f, *argument));
+                Expression::Column(width + functions.len() - 1, column)
+            }
+            _ => expression
+                .map_children(&mut |e| Planner::extract_set_functions(e, width, functions)),
         }
     }
 }
@@ -1,43 +1,113 @@
 //! Top Level of the sql parsing engine
 
+mod checkpoint;
 mod common;
+mod copy;
 mod create;
+mod drop;
 mod insert;
+mod promote;
+mod repack;
+mod replication_slot;
 mod select;
+mod set;
+mod show;
+mod transaction;
+mod vacuum;
 
 use self::select::parse_select;
 
 use super::objects::ParseTree;
-use create::parse_create_table;
+use checkpoint::parse_checkpoint;
+use common::maybe_take_whitespace;
+use copy::parse_copy;
+use create::{parse_create_table, parse_create_type};
+use drop::parse_drop_table;
 use insert::parse_insert;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::{all_consuming, complete, opt};
+use nom::combinator::{all_consuming, eof, map};
 use nom::error::{convert_error, ContextError, ParseError, VerboseError};
-use nom::sequence::tuple;
+use nom::multi::many0;
+use nom::sequence::{preceded, terminated, tuple};
 use nom::Finish;
 use nom::IResult;
+use promote::parse_promote;
+use repack::parse_repack;
+use replication_slot::parse_slot_function;
+use set::parse_set;
+use show::parse_show;
 use thiserror::Error;
+use transaction::{parse_begin, parse_commit, parse_rollback};
+use vacuum::parse_vacuum;
 
 pub struct SqlParser {}
 
 impl SqlParser {
-    pub fn parse(input: &str) -> Result<ParseTree, SqlParserError> {
+    /// Parses a query string into its statements, a query string with no statements
+    /// (such as "" or ";") will return an empty list.
+    pub fn parse(input: &str) -> Result<Vec<ParseTree>, SqlParserError> {
         match SqlParser::nom_parse::<VerboseError<&str>>(input).finish() {
-            Ok((_, cmd)) => Ok(cmd),
+            Ok((_, cmds)) => Ok(cmds),
             Err(e) => Err(SqlParserError::ParseError(convert_error(input, e))),
         }
     }
 
     fn nom_parse<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
         input: &'a str,
-    ) -> IResult<&'a str, ParseTree, E> {
-        //TODO Had to remove all consuming since it was throwing EOF issues
-        let (input, (result, _)) = complete(tuple((
-            alt((parse_create_table, parse_insert, parse_select)),
-            opt(tag(";")),
+    ) -> IResult<&'a str, Vec<ParseTree>, E> {
+        let (input, (statements, _)) = all_consuming(tuple((
+            many0(SqlParser::parse_statement_or_empty),
+            maybe_take_whitespace,
         )))(input)?;
-        Ok((input, result))
+        Ok((input, statements.into_iter().flatten().collect()))
+    }
+
+    //Statements are separated by semicolons, the last one may skip it
+    fn parse_statement_or_empty<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+        input: &'a str,
+    ) -> IResult<&'a str, Option<ParseTree>, E> {
+        alt((
+            map(SqlParser::match_semicolon, |_| None),
+            map(
+                terminated(
+                    preceded(
+                        maybe_take_whitespace,
+                        alt((
+                            parse_begin,
+                            parse_checkpoint,
+                            parse_commit,
+                            parse_copy,
+                            parse_create_table,
+                            parse_create_type,
+                            parse_drop_table,
+                            parse_insert,
+                            parse_promote,
+                            //Before select since these are written as one
+                            parse_slot_function,
+                            parse_select,
+                            parse_set,
+                            parse_show,
+                            //Before vacuum so VACUUM FULL CONCURRENTLY isn't taken as a vacuum
+                            parse_repack,
+                            parse_rollback,
+                            parse_vacuum,
+                        )),
+                    ),
+                    alt((
+                        SqlParser::match_semicolon,
+                        preceded(maybe_take_whitespace, eof),
+                    )),
+                ),
+                Some,
+            ),
+        ))(input)
+    }
+
+    fn match_semicolon<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+        input: &'a str,
+    ) -> IResult<&'a str, &'a str, E> {
+        preceded(maybe_take_whitespace, tag(";"))(input)
     }
 }
 
@@ -48,3 +118,41 @@ pub enum SqlParserError {
     #[error("Got an incomplete on {0} which shouldn't be possible")]
     Incomplete(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiple_statements() -> Result<(), Box<dyn std::error::Error>> {
+        let test =
+            "create table foo (bar text); insert into foo values('baz');\nselect bar from foo";
+
+        let result = SqlParser::parse(test)?;
+
+        assert_eq!(result.len(), 3);
+        assert!(matches!(result[0], ParseTree::CreateTable(_)));
+        assert!(matches!(result[1], ParseTree::Insert(_)));
+        assert!(matches!(result[2], ParseTree::Select(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_statements() -> Result<(), Box<dyn std::error::Error>> {
+        assert!(SqlParser::parse("")?.is_empty());
+        assert!(SqlParser::parse(" ; ;\n")?.is_empty());
+
+        let result = SqlParser::parse(";select bar from foo;;")?;
+        assert_eq!(result.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_garbage() {
+        assert!(SqlParser::parse("vacuumfoo").is_err());
+        assert!(SqlParser::parse("checkpoint now").is_err());
+        assert!(SqlParser::parse("repack").is_err());
+        assert!(SqlParser::parse("select bar from foo garbage").is_err());
+        assert!(SqlParser::parse("select bar from foo; garbage").is_err());
+    }
+}
@@ -0,0 +1,29 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-checkpoint.html
+
+use crate::engine::objects::ParseTree;
+
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::map;
+use nom::error::{ContextError, ParseError};
+use nom::IResult;
+
+pub(super) fn parse_checkpoint<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    map(tag_no_case("checkpoint"), |_| ParseTree::Checkpoint)(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let (output, value) = parse_checkpoint::<VerboseError<&str>>("CHECKPOINT")?;
+        assert_eq!(output.len(), 0);
+        assert!(matches!(value, ParseTree::Checkpoint));
+        Ok(())
+    }
+}
@@ -1,18 +1,18 @@
 use nom::branch::alt;
 use nom::bytes::complete::{escaped_transform, is_a, tag, tag_no_case};
-use nom::character::complete::{alphanumeric1, digit1, multispace0, multispace1, none_of};
-use nom::combinator::{cut, map_parser, recognize};
+use nom::character::complete::{char, digit0, digit1, multispace0, multispace1, none_of, one_of};
+use nom::combinator::{cut, map, map_parser, opt, recognize};
 use nom::error::{ContextError, ParseError};
-use nom::multi::{many0, separated_list0, separated_list1};
-use nom::sequence::{delimited, tuple};
+use nom::multi::{fold_many0, many0, many1, separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded, tuple};
 use nom::IResult;
 
-use crate::engine::objects::ParseExpression;
+use crate::engine::objects::{ParseExpression, ParseSubscript, Quantifier};
 
 pub(super) fn parse_sql_identifier<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, &'a str, E> {
-    is_a("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.")(input)
+    is_a("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789._")(input)
 }
 
 // This parser is designed to capture valid postgres expressions and values
@@ -20,15 +20,279 @@ pub(super) fn parse_sql_identifier<'a, E: ParseError<&'a str> + ContextError<&'a
 // * 'foo'
 // * 'foo bar'
 // * 1
+// * -1.5e3
+// * bar * (2 + baz)
+// * '1.5'::numeric(5,2)
+// * now() - '1 day' < current_date
+// * data -> 'tags' ->> 0 = 'red'
+// * ARRAY[1, 2][1:1] <> ANY('{{1},{2}}')
+// Precedence follows postgres: casts and subscripts bind tightest, then unary minus, then * / %, then + -,
+// then the other operators such as the json ones and last the comparisons which do not chain
 // Fancier expressions will be evolved in over time
-// Will consume all input so be careful!
 pub(super) fn parse_expression<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, ParseExpression, E> {
-    cut(alt((parse_sql_string, parse_sql_integer, parse_sql_null)))(input)
+    cut(parse_comparison)(input)
 }
 
-fn parse_sql_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+fn parse_comparison<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, (left, right)) = tuple((
+        parse_other_operator,
+        opt(tuple((
+            alt((
+                tag("<="),
+                tag(">="),
+                tag("<>"),
+                map(tag("!="), |_| "<>"),
+                tag("="),
+                tag("<"),
+                tag(">"),
+            )),
+            alt((
+                map(parse_quantified, |(q, right)| (Some(q), right)),
+                map(parse_other_operator, |right| (None, right)),
+            )),
+        ))),
+    ))(input)?;
+
+    match right {
+        Some((op, (Some(quantifier), right))) => Ok((
+            input,
+            ParseExpression::ArrayComparison(
+                op.to_string(),
+                quantifier,
+                Box::new(left),
+                Box::new(right),
+            ),
+        )),
+        Some((op, (None, right))) => Ok((
+            input,
+            ParseExpression::Operator(op.to_string(), Box::new(left), Box::new(right)),
+        )),
+        None => Ok((input, left)),
+    }
+}
+
+//The right side of foo = ANY(bar), SOME is the standard's other spelling of ANY
+fn parse_quantified<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (Quantifier, ParseExpression), E> {
+    let (input, (_, quantifier, _, _, expr, _, _)) = tuple((
+        maybe_take_whitespace,
+        alt((
+            map(tag_no_case("any"), |_| Quantifier::Any),
+            map(tag_no_case("some"), |_| Quantifier::Any),
+            map(tag_no_case("all"), |_| Quantifier::All),
+        )),
+        maybe_take_whitespace,
+        match_open_paren,
+        parse_comparison,
+        match_close_paren,
+        maybe_take_whitespace,
+    ))(input)?;
+    Ok((input, (quantifier, expr)))
+}
+
+fn parse_other_operator<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, first) = parse_additive(input)?;
+    fold_many0(
+        tuple((
+            alt((
+                tag("->>"),
+                tag("->"),
+                tag("#>>"),
+                tag("#>"),
+                tag("@>"),
+                tag("<@"),
+                tag("?"),
+            )),
+            parse_additive,
+        )),
+        first,
+        |left, (op, right)| {
+            ParseExpression::Operator(op.to_string(), Box::new(left), Box::new(right))
+        },
+    )(input)
+}
+
+fn parse_additive<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, first) = parse_multiplicative(input)?;
+    fold_many0(
+        tuple((alt((tag("+"), tag("-"))), parse_multiplicative)),
+        first,
+        |left, (op, right)| {
+            ParseExpression::Operator(op.to_string(), Box::new(left), Box::new(right))
+        },
+    )(input)
+}
+
+fn parse_multiplicative<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, first) = parse_unary(input)?;
+    fold_many0(
+        tuple((alt((tag("*"), tag("/"), tag("%"))), parse_unary)),
+        first,
+        |left, (op, right)| {
+            ParseExpression::Operator(op.to_string(), Box::new(left), Box::new(right))
+        },
+    )(input)
+}
+
+fn parse_unary<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    alt((
+        map(
+            preceded(tuple((maybe_take_whitespace, tag("-"))), parse_unary),
+            |expr| match expr {
+                //Like postgres, fold the sign into numeric constants so -2147483648 is still an integer
+                ParseExpression::Number(n) => match n.strip_prefix('-') {
+                    Some(positive) => ParseExpression::Number(positive.to_string()),
+                    None => ParseExpression::Number(format!("-{}", n)),
+                },
+                _ => ParseExpression::Negate(Box::new(expr)),
+            },
+        ),
+        preceded(tuple((maybe_take_whitespace, tag("+"))), parse_unary),
+        parse_postfix,
+    ))(input)
+}
+
+fn parse_postfix<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, first) = parse_primary(input)?;
+    fold_many0(
+        alt((
+            map(
+                delimited(
+                    tag("::"),
+                    preceded(maybe_take_whitespace, parse_type_name),
+                    maybe_take_whitespace,
+                ),
+                Postfix::Cast,
+            ),
+            map(parse_subscript, Postfix::Subscript),
+            map(
+                delimited(tag("."), parse_sql_identifier, maybe_take_whitespace),
+                Postfix::Field,
+            ),
+        )),
+        first,
+        |expr, postfix| match (expr, postfix) {
+            (expr, Postfix::Cast(type_name)) => ParseExpression::Cast(Box::new(expr), type_name),
+            //foo[1][2] is a single two dimensional subscript, not two one dimensional ones
+            (ParseExpression::Subscript(inner, mut subscripts), Postfix::Subscript(s)) => {
+                subscripts.push(s);
+                ParseExpression::Subscript(inner, subscripts)
+            }
+            (expr, Postfix::Subscript(s)) => ParseExpression::Subscript(Box::new(expr), vec![s]),
+            //The identifier parser takes the dots too so (foo).bar.baz arrives in one piece
+            (expr, Postfix::Field(names)) => names.split('.').fold(expr, |e, name| {
+                ParseExpression::FieldSelect(Box::new(e), name.to_string())
+            }),
+        },
+    )(input)
+}
+
+enum Postfix<'a> {
+    Cast(String),
+    Subscript(ParseSubscript),
+    Field(&'a str),
+}
+
+fn parse_subscript<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseSubscript, E> {
+    let (input, (_, lower, upper, _, _)) = tuple((
+        tag("["),
+        opt(parse_comparison),
+        opt(preceded(tag(":"), opt(parse_comparison))),
+        tuple((maybe_take_whitespace, tag("]"))),
+        maybe_take_whitespace,
+    ))(input)?;
+
+    match (lower, upper) {
+        (Some(index), None) => Ok((input, ParseSubscript::Index(index))),
+        (lower, Some(upper)) => Ok((input, ParseSubscript::Slice(lower, upper))),
+        (None, None) => Err(nom::Err::Error(E::from_error_kind(
+            input,
+            nom::error::ErrorKind::Tag,
+        ))),
+    }
+}
+
+fn parse_primary<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    alt((
+        parse_sql_string,
+        parse_sql_number,
+        parse_parenthesized,
+        parse_array_constructor,
+        parse_sql_keyword_or_identifier,
+    ))(input)
+}
+
+fn parse_array_constructor<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    preceded(
+        tuple((
+            maybe_take_whitespace,
+            tag_no_case("array"),
+            maybe_take_whitespace,
+        )),
+        parse_array_elements,
+    )(input)
+}
+
+//Inside of ARRAY[...] the nested arrays can leave off the keyword
+fn parse_array_elements<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, (_, elements, _)) = tuple((
+        tag("["),
+        separated_list0(
+            match_comma,
+            alt((
+                delimited(
+                    maybe_take_whitespace,
+                    parse_array_elements,
+                    maybe_take_whitespace,
+                ),
+                parse_comparison,
+            )),
+        ),
+        tuple((maybe_take_whitespace, tag("]"), maybe_take_whitespace)),
+    ))(input)?;
+    Ok((input, ParseExpression::Array(elements)))
+}
+
+fn parse_parenthesized<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseExpression, E> {
+    let (input, (_, _, mut exprs, _, _)) = tuple((
+        maybe_take_whitespace,
+        match_open_paren,
+        separated_list1(match_comma, parse_comparison),
+        match_close_paren,
+        maybe_take_whitespace,
+    ))(input)?;
+    if exprs.len() == 1 {
+        return Ok((input, exprs.remove(0)));
+    }
+    Ok((input, ParseExpression::Row(exprs)))
+}
+
+pub(super) fn parse_sql_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, ParseExpression, E> {
     //Code from here: https://stackoverflow.com/a/58520871
@@ -43,23 +307,143 @@ fn parse_sql_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     Ok((input, ParseExpression::String(sql_value)))
 }
 
-fn parse_sql_integer<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+fn parse_sql_number<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, ParseExpression, E> {
-    let (input, (_, num, _)) =
-        tuple((maybe_take_whitespace, digit1, maybe_take_whitespace))(input)?;
-    Ok((input, ParseExpression::String(num.to_string())))
+    let (input, (_, num, _)) = tuple((
+        maybe_take_whitespace,
+        recognize(tuple((
+            alt((
+                recognize(tuple((digit1, opt(tuple((char('.'), digit0)))))),
+                recognize(tuple((char('.'), digit1))),
+            )),
+            opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+        ))),
+        maybe_take_whitespace,
+    ))(input)?;
+    Ok((input, ParseExpression::Number(num.to_string())))
 }
 
-fn parse_sql_null<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+fn parse_sql_keyword_or_identifier<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, ParseExpression, E> {
-    let (input, (_, _, _)) = tuple((
+    let (input, (_, name, _, arguments)) = tuple((
         maybe_take_whitespace,
-        tag_no_case("null"),
+        parse_sql_identifier,
         maybe_take_whitespace,
+        opt(delimited(
+            match_open_paren,
+            separated_list0(match_comma, parse_comparison),
+            tuple((
+                maybe_take_whitespace,
+                match_close_paren,
+                maybe_take_whitespace,
+            )),
+        )),
+    ))(input)?;
+
+    if let Some(args) = arguments {
+        if name.eq_ignore_ascii_case("row") {
+            return Ok((input, ParseExpression::Row(args)));
+        }
+        return Ok((input, ParseExpression::Function(name.to_lowercase(), args)));
+    }
+
+    let lower = name.to_lowercase();
+    let expr = match lower.as_str() {
+        "null" => ParseExpression::Null(),
+        "true" => ParseExpression::Bool(true),
+        "false" => ParseExpression::Bool(false),
+        //The SQL standard spells these functions without parentheses
+        "current_timestamp" | "current_date" | "localtime" | "localtimestamp" => {
+            ParseExpression::Function(lower, vec![])
+        }
+        _ => ParseExpression::Identifier(name.to_string()),
+    };
+    Ok((input, expr))
+}
+
+/// Parses a type name such as "integer", "double precision" or "numeric(10, 2)" into a
+/// normalized string that DeserializeTypes can understand. Array types such as "int[3][]" or
+/// "text array" all become "integer[]" or "text[]" since postgres ignores the declared sizes.
+pub(super) fn parse_type_name<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, String, E> {
+    let (input, (name, array)) = tuple((parse_scalar_type_name, opt(parse_array_suffix)))(input)?;
+    match array {
+        Some(_) => Ok((input, format!("{}[]", name))),
+        None => Ok((input, name)),
+    }
+}
+
+fn parse_array_suffix<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (), E> {
+    let bounds = || {
+        delimited(
+            tuple((maybe_take_whitespace, tag("["), maybe_take_whitespace)),
+            opt(digit1),
+            tuple((maybe_take_whitespace, tag("]"))),
+        )
+    };
+    alt((
+        map(many1(bounds()), |_| ()),
+        map(
+            tuple((take_whitespace, match_words(&["array"]), opt(bounds()))),
+            |_| (),
+        ),
+    ))(input)
+}
+
+fn parse_scalar_type_name<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, String, E> {
+    let (input, (name, modifiers)) = tuple((
+        alt((
+            match_words(&["double", "precision"]),
+            match_words(&["timestamp", "with", "time", "zone"]),
+            match_words(&["timestamp", "without", "time", "zone"]),
+            match_words(&["time", "without", "time", "zone"]),
+            match_words(&["character", "varying"]),
+            match_words(&["char", "varying"]),
+            map(parse_sql_identifier, |n: &str| n.to_lowercase()),
+        )),
+        opt(delimited(
+            tuple((maybe_take_whitespace, match_open_paren)),
+            separated_list1(
+                match_comma,
+                delimited(maybe_take_whitespace, digit1, maybe_take_whitespace),
+            ),
+            match_close_paren,
+        )),
     ))(input)?;
-    Ok((input, ParseExpression::Null()))
+
+    match modifiers {
+        Some(m) => Ok((input, format!("{}({})", name, m.join(",")))),
+        None => Ok((input, name)),
+    }
+}
+
+//Matches a type name made of several words, giving back the words with single spaces
+pub(super) fn match_words<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    words: &'static [&'static str],
+) -> impl FnMut(&'a str) -> IResult<&'a str, String, E> {
+    move |mut input: &'a str| {
+        for (i, word) in words.iter().enumerate() {
+            if i > 0 {
+                input = take_whitespace(input)?.0;
+            }
+            input = tag_no_case(*word)(input)?.0;
+        }
+        //The last word must not just be the start of a longer identifier
+        if let Ok((_, _)) = parse_sql_identifier::<E>(input) {
+            return Err(nom::Err::Error(E::from_error_kind(
+                input,
+                nom::error::ErrorKind::Tag,
+            )));
+        }
+        Ok((input, words.join(" ")))
+    }
 }
 
 pub(super) fn parse_column_names<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
@@ -76,8 +460,11 @@ pub(super) fn parse_column_names<'a, E: ParseError<&'a str> + ContextError<&'a s
 pub(super) fn match_column_name<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, String, E> {
-    let (input, (_, name, _)) =
-        tuple((maybe_take_whitespace, alphanumeric1, maybe_take_whitespace))(input)?;
+    let (input, (_, name, _)) = tuple((
+        maybe_take_whitespace,
+        parse_sql_identifier,
+        maybe_take_whitespace,
+    ))(input)?;
     Ok((input, name.to_string()))
 }
 
@@ -144,4 +531,267 @@ mod tests {
         assert_eq!(output.len(), 0);
         assert_eq!(expected, value);
     }
+
+    fn parse(input: &str) -> ParseExpression {
+        match parse_expression::<VerboseError<&str>>(input) {
+            Ok((output, value)) => {
+                assert_eq!(output.len(), 0);
+                value
+            }
+            Err(e) => panic!("Failed to parse {} {:?}", input, e),
+        }
+    }
+
+    fn op(op: &str, left: ParseExpression, right: ParseExpression) -> ParseExpression {
+        ParseExpression::Operator(op.to_string(), Box::new(left), Box::new(right))
+    }
+
+    fn num(n: &str) -> ParseExpression {
+        ParseExpression::Number(n.to_string())
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse("1"), num("1"));
+        assert_eq!(parse(" -1.5e3 "), num("-1.5e3"));
+        assert_eq!(parse(".5"), num(".5"));
+        assert_eq!(parse("- -2"), num("2"));
+    }
+
+    #[test]
+    fn test_parse_keywords() {
+        assert_eq!(parse("NULL"), ParseExpression::Null());
+        assert_eq!(parse("true"), ParseExpression::Bool(true));
+        assert_eq!(
+            parse("nullable"),
+            ParseExpression::Identifier("nullable".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        assert_eq!(
+            parse("1 + 2 * 3 - 4"),
+            op(
+                "-",
+                op("+", num("1"), op("*", num("2"), num("3"))),
+                num("4")
+            )
+        );
+        assert_eq!(
+            parse("(1+2)*bar"),
+            op(
+                "*",
+                op("+", num("1"), num("2")),
+                ParseExpression::Identifier("bar".to_string())
+            )
+        );
+        assert_eq!(
+            parse("-bar % 2"),
+            op(
+                "%",
+                ParseExpression::Negate(Box::new(ParseExpression::Identifier("bar".to_string()))),
+                num("2")
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_cast() {
+        assert_eq!(
+            parse("'1.5'::numeric( 5, 2 )::Double Precision"),
+            ParseExpression::Cast(
+                Box::new(ParseExpression::Cast(
+                    Box::new(ParseExpression::String("1.5".to_string())),
+                    "numeric(5,2)".to_string()
+                )),
+                "double precision".to_string()
+            )
+        );
+        //Casts bind tighter than the minus sign
+        assert_eq!(
+            parse("-1::text"),
+            ParseExpression::Negate(Box::new(ParseExpression::Cast(
+                Box::new(num("1")),
+                "text".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_parse_comparison() {
+        assert_eq!(
+            parse("bar + 1 >= 2"),
+            op(
+                ">=",
+                op(
+                    "+",
+                    ParseExpression::Identifier("bar".to_string()),
+                    num("1")
+                ),
+                num("2")
+            )
+        );
+        assert_eq!(parse("1 != 2"), op("<>", num("1"), num("2")));
+        //Comparisons don't chain, the second one is left for the caller to reject
+        let (rest, _) = parse_expression::<VerboseError<&str>>("1 < 2 < 3").unwrap();
+        assert_eq!(rest, "< 3");
+    }
+
+    #[test]
+    fn test_parse_json_operators() {
+        let s = |v: &str| ParseExpression::String(v.to_string());
+        let data = || ParseExpression::Identifier("data".to_string());
+        assert_eq!(
+            parse("data->'a'->>0 = 'x'"),
+            op("=", op("->>", op("->", data(), s("a")), num("0")), s("x"))
+        );
+        assert_eq!(parse("data #> '{a,b}'"), op("#>", data(), s("{a,b}")));
+        assert_eq!(
+            parse("data ->> 1 + 1"),
+            op("->>", data(), op("+", num("1"), num("1")))
+        );
+        assert_eq!(parse("data @> '{}' "), op("@>", data(), s("{}")));
+        assert_eq!(parse("data <@ '{}'"), op("<@", data(), s("{}")));
+        assert_eq!(parse("data <= 1"), op("<=", data(), num("1")));
+        assert_eq!(parse("data ? 'k'"), op("?", data(), s("k")));
+        assert_eq!(parse("data #>> '{}'"), op("#>>", data(), s("{}")));
+        assert_eq!(parse("data->-1"), op("->", data(), num("-1")));
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        assert_eq!(
+            parse("now() - '1 day'"),
+            op(
+                "-",
+                ParseExpression::Function("now".to_string(), vec![]),
+                ParseExpression::String("1 day".to_string())
+            )
+        );
+        assert_eq!(
+            parse("CURRENT_TIMESTAMP"),
+            ParseExpression::Function("current_timestamp".to_string(), vec![])
+        );
+        assert_eq!(
+            parse("Foo ( 1, bar )"),
+            ParseExpression::Function(
+                "foo".to_string(),
+                vec![num("1"), ParseExpression::Identifier("bar".to_string())]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_multi_word_types() {
+        assert_eq!(
+            parse("'2021-01-01'::Timestamp With  Time Zone"),
+            ParseExpression::Cast(
+                Box::new(ParseExpression::String("2021-01-01".to_string())),
+                "timestamp with time zone".to_string()
+            )
+        );
+        assert_eq!(
+            parse("'12:00'::time"),
+            ParseExpression::Cast(
+                Box::new(ParseExpression::String("12:00".to_string())),
+                "time".to_string()
+            )
+        );
+        assert_eq!(
+            parse("'abc'::Character Varying(10)"),
+            ParseExpression::Cast(
+                Box::new(ParseExpression::String("abc".to_string())),
+                "character varying(10)".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_arrays() {
+        let id = |v: &str| ParseExpression::Identifier(v.to_string());
+        assert_eq!(
+            parse("ARRAY[1, [2]] "),
+            ParseExpression::Array(vec![num("1"), ParseExpression::Array(vec![num("2")])])
+        );
+        assert_eq!(parse("array[]"), ParseExpression::Array(vec![]));
+        assert_eq!(parse("array_col"), id("array_col"));
+        assert_eq!(
+            parse("foo[1][2:][:3]::text"),
+            ParseExpression::Cast(
+                Box::new(ParseExpression::Subscript(
+                    Box::new(id("foo")),
+                    vec![
+                        ParseSubscript::Index(num("1")),
+                        ParseSubscript::Slice(Some(num("2")), None),
+                        ParseSubscript::Slice(None, Some(num("3"))),
+                    ]
+                )),
+                "text".to_string()
+            )
+        );
+        assert_eq!(
+            parse("'{1}'::int[3][] = ANY (foo)"),
+            ParseExpression::ArrayComparison(
+                "=".to_string(),
+                Quantifier::Any,
+                Box::new(ParseExpression::Cast(
+                    Box::new(ParseExpression::String("{1}".to_string())),
+                    "int[]".to_string()
+                )),
+                Box::new(id("foo"))
+            )
+        );
+        assert_eq!(
+            parse("1 <> all(foo)"),
+            ParseExpression::ArrayComparison(
+                "<>".to_string(),
+                Quantifier::All,
+                Box::new(num("1")),
+                Box::new(id("foo"))
+            )
+        );
+        assert_eq!(
+            parse("'{}'::Character Varying(2) Array"),
+            ParseExpression::Cast(
+                Box::new(ParseExpression::String("{}".to_string())),
+                "character varying(2)[]".to_string()
+            )
+        );
+        //Without parentheses any is just a column
+        assert_eq!(parse("1 = any"), op("=", num("1"), id("any")));
+    }
+
+    #[test]
+    fn test_parse_rows() {
+        let id = |v: &str| ParseExpression::Identifier(v.to_string());
+        let field =
+            |e: ParseExpression, f: &str| ParseExpression::FieldSelect(Box::new(e), f.to_string());
+
+        assert_eq!(
+            parse("ROW(1, 'a')"),
+            ParseExpression::Row(vec![num("1"), ParseExpression::String("a".to_string())])
+        );
+        assert_eq!(parse("row()"), ParseExpression::Row(vec![]));
+        assert_eq!(
+            parse("(1, foo)"),
+            ParseExpression::Row(vec![num("1"), id("foo")])
+        );
+        assert_eq!(parse("(1)"), num("1"));
+        assert_eq!(parse("(foo).bar"), field(id("foo"), "bar"));
+        assert_eq!(
+            parse("(foo).bar.baz"),
+            field(field(id("foo"), "bar"), "baz")
+        );
+        assert_eq!(
+            parse("(foo[1]).bar"),
+            field(
+                ParseExpression::Subscript(
+                    Box::new(id("foo")),
+                    vec![ParseSubscript::Index(num("1"))]
+                ),
+                "bar"
+            )
+        );
+    }
 }
@@ -0,0 +1,159 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-copy.html
+//! Rows are loaded from the client or a file on the server and sent to the client. Only the
+//! parenthesized option list is supported, not the older WITH CSV HEADER spelling.
+
+use crate::engine::objects::{CopyTarget, ParseExpression, ParseTree, RawCopyCommand};
+
+use super::common::{
+    match_close_paren, match_comma, match_open_paren, match_words, maybe_take_whitespace,
+    parse_column_names, parse_sql_identifier, parse_sql_string, take_whitespace,
+};
+use nom::branch::alt;
+use nom::combinator::{cut, map, map_opt, opt};
+use nom::error::{ContextError, ParseError};
+use nom::multi::separated_list1;
+use nom::sequence::{preceded, terminated, tuple};
+use nom::IResult;
+
+pub(super) fn parse_copy<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, (_, table_name, columns, _, target, options))) = tuple((
+        match_words(&["copy"]),
+        cut(tuple((
+            take_whitespace,
+            parse_sql_identifier,
+            opt(preceded(maybe_take_whitespace, parse_column_names)),
+            maybe_take_whitespace,
+            parse_copy_target,
+            opt(preceded(maybe_take_whitespace, parse_copy_options)),
+        ))),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::Copy(RawCopyCommand {
+            table_name: table_name.to_string(),
+            columns,
+            target,
+            options: options.unwrap_or_default(),
+        }),
+    ))
+}
+
+fn parse_copy_target<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, CopyTarget, E> {
+    alt((
+        preceded(
+            tuple((match_words(&["from"]), take_whitespace)),
+            alt((
+                map(match_words(&["stdin"]), |_| CopyTarget::Stdin),
+                map_opt(parse_sql_string, |s| match s {
+                    ParseExpression::String(s) => Some(CopyTarget::File(s)),
+                    _ => None,
+                }),
+            )),
+        ),
+        map(
+            tuple((
+                match_words(&["to"]),
+                take_whitespace,
+                match_words(&["stdout"]),
+            )),
+            |_| CopyTarget::Stdout,
+        ),
+    ))(input)
+}
+
+//[WITH] (name [value], ...)
+fn parse_copy_options<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Vec<(String, Option<String>)>, E> {
+    let (input, (_, _, options, _)) = tuple((
+        opt(terminated(match_words(&["with"]), maybe_take_whitespace)),
+        match_open_paren,
+        separated_list1(match_comma, parse_copy_option),
+        match_close_paren,
+    ))(input)?;
+    Ok((input, options))
+}
+
+fn parse_copy_option<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (String, Option<String>), E> {
+    let (input, (_, name, value, _)) = tuple((
+        maybe_take_whitespace,
+        parse_sql_identifier,
+        opt(preceded(
+            take_whitespace,
+            alt((
+                map_opt(parse_sql_string, |s| match s {
+                    ParseExpression::String(s) => Some(s),
+                    _ => None,
+                }),
+                map(parse_sql_identifier, str::to_string),
+            )),
+        )),
+        maybe_take_whitespace,
+    ))(input)?;
+    Ok((input, (name.to_lowercase(), value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    fn options(list: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        list.iter()
+            .map(|(n, v)| (n.to_string(), v.map(|v| v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_copy_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for (test, columns, target, expected_options) in [
+            ("copy foo from stdin", None, CopyTarget::Stdin, vec![]),
+            (
+                "COPY foo (bar, baz) FROM STDIN",
+                Some(vec!["bar".to_string(), "baz".to_string()]),
+                CopyTarget::Stdin,
+                vec![],
+            ),
+            (
+                "copy foo to stdout with (FORMAT csv, header, null '')",
+                None,
+                CopyTarget::Stdout,
+                options(&[
+                    ("format", Some("csv")),
+                    ("header", None),
+                    ("null", Some("")),
+                ]),
+            ),
+            (
+                "copy foo (bar) from '/tmp/it''s.csv' (delimiter ';', header true)",
+                Some(vec!["bar".to_string()]),
+                CopyTarget::File("/tmp/it's.csv".to_string()),
+                options(&[("delimiter", Some(";")), ("header", Some("true"))]),
+            ),
+        ] {
+            let (output, value) = parse_copy::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0, "{}", test);
+            match value {
+                ParseTree::Copy(c) => {
+                    assert_eq!(c.table_name, "foo");
+                    assert_eq!(c.columns, columns);
+                    assert_eq!(c.target, target);
+                    assert_eq!(c.options, expected_options);
+                }
+                _ => panic!("Wrong type"),
+            }
+        }
+        assert!(parse_copy::<VerboseError<&str>>("copy foo to stdin").is_err());
+        assert!(parse_copy::<VerboseError<&str>>("copy foo from stdout").is_err());
+        assert!(parse_copy::<VerboseError<&str>>("copy foo to '/tmp/foo'").is_err());
+        Ok(())
+    }
+}
@@ -5,6 +5,8 @@ use nom::IResult;
 
 mod create_table;
 pub(super) use create_table::parse_create_table;
+mod create_type;
+pub(super) use create_type::parse_create_type;
 use nom::sequence::tuple;
 
 pub(super) fn match_create<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
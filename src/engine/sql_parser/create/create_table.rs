@@ -6,7 +6,7 @@ use crate::engine::objects::{ParseTree, RawColumn};
 use super::super::super::objects::RawCreateTableCommand;
 use super::super::common::{
     match_close_paren, match_comma, match_open_paren, maybe_take_whitespace, parse_sql_identifier,
-    take_whitespace,
+    parse_type_name, take_whitespace,
 };
 use super::match_create;
 use nom::bytes::complete::tag_no_case;
@@ -61,7 +61,7 @@ fn match_column_attribute<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
         maybe_take_whitespace,
         parse_sql_identifier,
         take_whitespace,
-        parse_sql_identifier,
+        parse_type_name,
         maybe_take_whitespace,
         is_null,
         maybe_take_whitespace,
@@ -70,7 +70,7 @@ fn match_column_attribute<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
         input,
         RawColumn {
             name: name.to_string(),
-            sql_type: sql_type.to_string(),
+            sql_type,
             null: is_null,
         },
     ))
@@ -139,6 +139,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_type_modifiers() -> Result<(), Box<dyn std::error::Error>> {
+        let test_string = "create table foo (bar numeric(10, 2) not null, baz double precision)";
+
+        let (_, result) = parse_create_table::<VerboseError<&str>>(test_string)?;
+
+        let result = match result {
+            ParseTree::CreateTable(c) => c,
+            _ => panic!("Wrong type"),
+        };
+
+        let columns = vec![
+            RawColumn {
+                name: "bar".to_string(),
+                sql_type: "numeric(10,2)".to_string(),
+                null: false,
+            },
+            RawColumn {
+                name: "baz".to_string(),
+                sql_type: "double precision".to_string(),
+                null: true,
+            },
+        ];
+        assert_eq!(columns, result.provided_columns);
+        Ok(())
+    }
+
     #[test]
     fn test_nullable_columns() -> Result<(), Box<dyn std::error::Error>> {
         let test_string = "create table foo (bar text, test text null)";
@@ -0,0 +1,157 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-createtype.html
+//! Only enum and composite types are supported
+
+use crate::engine::objects::{ParseExpression, ParseTree, RawColumn, RawTypeDefinition};
+
+use super::super::super::objects::RawCreateTypeCommand;
+use super::super::common::{
+    match_close_paren, match_comma, match_open_paren, maybe_take_whitespace, parse_sql_identifier,
+    parse_sql_string, parse_type_name, take_whitespace,
+};
+use super::match_create;
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::{cut, map};
+use nom::error::{ContextError, ParseError};
+use nom::multi::{separated_list0, separated_list1};
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
+
+pub fn parse_create_type<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, _, (_, type_name, _, _, _, definition))) = tuple((
+        match_create,
+        tag_no_case("type"),
+        cut(tuple((
+            take_whitespace,
+            parse_sql_identifier,
+            take_whitespace,
+            tag_no_case("as"),
+            maybe_take_whitespace,
+            alt((match_enum, match_composite)),
+        ))),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::CreateType(RawCreateTypeCommand {
+            type_name: type_name.to_lowercase(),
+            definition,
+        }),
+    ))
+}
+
+fn match_enum<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, RawTypeDefinition, E> {
+    let (input, (_, _, _, labels, _)) = tuple((
+        tag_no_case("enum"),
+        maybe_take_whitespace,
+        match_open_paren,
+        separated_list0(
+            match_comma,
+            map(parse_sql_string, |s| match s {
+                ParseExpression::String(s) => s,
+                _ => unreachable!(),
+            }),
+        ),
+        preceded(maybe_take_whitespace, match_close_paren),
+    ))(input)?;
+    Ok((input, RawTypeDefinition::Enum(labels)))
+}
+
+fn match_composite<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, RawTypeDefinition, E> {
+    let (input, (_, fields, _)) = tuple((
+        match_open_paren,
+        separated_list1(match_comma, match_field),
+        match_close_paren,
+    ))(input)?;
+    Ok((input, RawTypeDefinition::Composite(fields)))
+}
+
+fn match_field<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, RawColumn, E> {
+    let (input, (_, name, _, sql_type, _)) = tuple((
+        maybe_take_whitespace,
+        parse_sql_identifier,
+        take_whitespace,
+        parse_type_name,
+        maybe_take_whitespace,
+    ))(input)?;
+    Ok((
+        input,
+        RawColumn {
+            name: name.to_string(),
+            sql_type,
+            null: true,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn test_enum() -> Result<(), Box<dyn std::error::Error>> {
+        let test_string = "create type Mood as enum ('sad', 'it''s ok','happy')";
+
+        let (_, result) = parse_create_type::<VerboseError<&str>>(test_string)?;
+
+        let result = match result {
+            ParseTree::CreateType(c) => c,
+            _ => panic!("Wrong type"),
+        };
+
+        assert_eq!("mood", result.type_name);
+        assert_eq!(
+            RawTypeDefinition::Enum(vec![
+                "sad".to_string(),
+                "it's ok".to_string(),
+                "happy".to_string()
+            ]),
+            result.definition
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_composite() -> Result<(), Box<dyn std::error::Error>> {
+        let test_string = "create type pair as (a integer, b numeric(5,2)[])";
+
+        let (_, result) = parse_create_type::<VerboseError<&str>>(test_string)?;
+
+        let result = match result {
+            ParseTree::CreateType(c) => c,
+            _ => panic!("Wrong type"),
+        };
+
+        assert_eq!("pair", result.type_name);
+        assert_eq!(
+            RawTypeDefinition::Composite(vec![
+                RawColumn {
+                    name: "a".to_string(),
+                    sql_type: "integer".to_string(),
+                    null: true,
+                },
+                RawColumn {
+                    name: "b".to_string(),
+                    sql_type: "numeric(5,2)[]".to_string(),
+                    null: true,
+                },
+            ]),
+            result.definition
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_a_type() {
+        assert!(parse_create_type::<VerboseError<&str>>("create type foo as range").is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-droptable.html
+//! Only a single table can be dropped, without IF EXISTS or CASCADE
+
+use crate::engine::objects::{ParseTree, RawDropTableCommand};
+
+use super::common::{parse_sql_identifier, take_whitespace};
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::cut;
+use nom::error::{ContextError, ParseError};
+use nom::sequence::tuple;
+use nom::IResult;
+
+pub(super) fn parse_drop_table<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, _, _, (_, table_name))) = tuple((
+        tag_no_case("drop"),
+        take_whitespace,
+        tag_no_case("table"),
+        cut(tuple((take_whitespace, parse_sql_identifier))),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::DropTable(RawDropTableCommand {
+            table_name: table_name.to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_drop_table_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let (output, value) = parse_drop_table::<VerboseError<&str>>("DROP TABLE foo")?;
+        assert_eq!(output.len(), 0);
+        match value {
+            ParseTree::DropTable(d) => assert_eq!(d.table_name, "foo"),
+            _ => panic!("Wrong type"),
+        }
+
+        assert!(parse_drop_table::<VerboseError<&str>>("drop table").is_err());
+        Ok(())
+    }
+}
@@ -96,7 +96,7 @@ mod tests {
             ]),
             provided_values: vec![
                 ParseExpression::String("stuff and things".to_string()),
-                ParseExpression::String("2".to_string()),
+                ParseExpression::Number("2".to_string()),
             ],
         };
         assert_eq!(expected, value);
@@ -0,0 +1,29 @@
+//! Ends recovery on a standby, postgres does the same with pg_promote()
+
+use crate::engine::objects::ParseTree;
+
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::map;
+use nom::error::{ContextError, ParseError};
+use nom::IResult;
+
+pub(super) fn parse_promote<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    map(tag_no_case("promote"), |_| ParseTree::Promote)(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_promote_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let (output, value) = parse_promote::<VerboseError<&str>>("PROMOTE")?;
+        assert_eq!(output.len(), 0);
+        assert!(matches!(value, ParseTree::Promote));
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Rewrites a table without its dead rows while it stays in use, like pg_repack: https://reorg.github.io/pg_repack/
+//! VACUUM FULL CONCURRENTLY is accepted as another name for it
+
+use crate::engine::objects::{ParseTree, RawRepackCommand};
+
+use super::common::{parse_sql_identifier, take_whitespace};
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::{cut, map};
+use nom::error::{ContextError, ParseError};
+use nom::sequence::tuple;
+use nom::IResult;
+
+pub(super) fn parse_repack<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, _, table_name)) = tuple((
+        alt((
+            map(tag_no_case("repack"), |_| ()),
+            map(
+                tuple((
+                    tag_no_case("vacuum"),
+                    take_whitespace,
+                    tag_no_case("full"),
+                    take_whitespace,
+                    tag_no_case("concurrently"),
+                )),
+                |_| (),
+            ),
+        )),
+        take_whitespace,
+        cut(parse_sql_identifier),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::Repack(RawRepackCommand {
+            table_name: table_name.to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_repack_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for test in ["repack foo", "VACUUM FULL CONCURRENTLY foo"] {
+            let (output, value) = parse_repack::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0);
+            match value {
+                ParseTree::Repack(r) => assert_eq!(r.table_name, "foo"),
+                _ => panic!("Wrong type"),
+            }
+        }
+
+        assert!(parse_repack::<VerboseError<&str>>("vacuum foo").is_err());
+        assert!(parse_repack::<VerboseError<&str>>("repack").is_err());
+        Ok(())
+    }
+}
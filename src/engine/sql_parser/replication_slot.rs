@@ -0,0 +1,90 @@
+//! Postgres manages and reads logical replication slots with functions called from a select:
+//! SELECT * FROM pg_logical_slot_get_changes('my_slot', NULL, NULL)
+//! There are no set returning functions here so only these calls are recognized, with or
+//! without the SELECT * FROM.
+
+use crate::engine::objects::{ParseTree, RawSlotFunctionCommand};
+
+use super::common::{
+    match_close_paren, match_comma, match_open_paren, maybe_take_whitespace, parse_expression,
+    take_whitespace,
+};
+use super::select::{match_from, match_select};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case};
+use nom::combinator::{cut, opt};
+use nom::error::{ContextError, ParseError};
+use nom::multi::separated_list0;
+use nom::sequence::tuple;
+use nom::IResult;
+
+pub(super) fn parse_slot_function<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, _, function, _, _, (args, _, _))) = tuple((
+        match_select,
+        opt(tuple((tag("*"), take_whitespace, match_from))),
+        alt((
+            tag_no_case("pg_create_logical_replication_slot"),
+            tag_no_case("pg_drop_replication_slot"),
+            tag_no_case("pg_logical_slot_get_changes"),
+            tag_no_case("pg_logical_slot_peek_changes"),
+        )),
+        maybe_take_whitespace,
+        match_open_paren,
+        cut(tuple((
+            separated_list0(match_comma, parse_expression),
+            maybe_take_whitespace,
+            match_close_paren,
+        ))),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::SlotFunction(RawSlotFunctionCommand {
+            function: function.to_lowercase(),
+            args,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+    use crate::engine::objects::ParseExpression;
+
+    #[test]
+    fn test_slot_function_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let (output, value) = parse_slot_function::<VerboseError<&str>>(
+            "SELECT * FROM pg_logical_slot_get_changes('indexer', NULL, 10 )",
+        )?;
+        assert_eq!(output.len(), 0);
+        match value {
+            ParseTree::SlotFunction(f) => {
+                assert_eq!(f.function, "pg_logical_slot_get_changes");
+                assert_eq!(
+                    f.args,
+                    vec![
+                        ParseExpression::String("indexer".to_string()),
+                        ParseExpression::Null(),
+                        ParseExpression::Number("10".to_string())
+                    ]
+                );
+            }
+            _ => panic!("Wrong type"),
+        }
+
+        let (_, value) = parse_slot_function::<VerboseError<&str>>(
+            "select PG_CREATE_LOGICAL_REPLICATION_SLOT('indexer', 'test_decoding')",
+        )?;
+        assert!(
+            matches!(value, ParseTree::SlotFunction(f) if f.function == "pg_create_logical_replication_slot")
+        );
+
+        assert!(parse_slot_function::<VerboseError<&str>>("select now()").is_err());
+        assert!(parse_slot_function::<VerboseError<&str>>("select * from foo").is_err());
+        Ok(())
+    }
+}
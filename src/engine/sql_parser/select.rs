@@ -1,6 +1,6 @@
 use nom::{
     bytes::complete::tag_no_case,
-    combinator::cut,
+    combinator::{cut, opt},
     error::{ContextError, ParseError},
     multi::separated_list0,
     sequence::tuple,
@@ -10,24 +10,23 @@ use nom::{
 use crate::engine::objects::{ParseTree, RawSelectCommand};
 
 use super::common::{
-    match_column_name, match_comma, maybe_take_whitespace, parse_sql_identifier, take_whitespace,
+    match_comma, maybe_take_whitespace, parse_expression, parse_sql_identifier, take_whitespace,
 };
 
 pub(super) fn parse_select<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, ParseTree, E> {
-    let (input, (_, (columns, _, _, table))) = tuple((
+    let (input, (_, (columns, _, table))) = tuple((
         match_select,
         cut(tuple((
-            separated_list0(match_comma, match_column_name),
+            separated_list0(match_comma, parse_expression),
             maybe_take_whitespace,
-            match_from,
-            parse_sql_identifier,
+            opt(tuple((match_from, parse_sql_identifier))),
         ))),
     ))(input)?;
 
     let raw_sel = RawSelectCommand {
-        table: table.to_string(),
+        table: table.map(|(_, t)| t.to_string()),
         columns,
     };
 
@@ -52,7 +51,7 @@ pub(super) fn match_from<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
 mod tests {
     use nom::error::VerboseError;
 
-    use crate::engine::objects::RawSelectCommand;
+    use crate::engine::objects::{ParseExpression, RawSelectCommand};
 
     use super::*;
 
@@ -69,8 +68,39 @@ mod tests {
         assert_eq!(output.len(), 0);
 
         let expected = RawSelectCommand {
-            table: "baz".to_string(),
-            columns: vec!["foo".to_string(), "bar".to_string()],
+            table: Some("baz".to_string()),
+            columns: vec![
+                ParseExpression::Identifier("foo".to_string()),
+                ParseExpression::Identifier("bar".to_string()),
+            ],
+        };
+        assert_eq!(expected, value);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_without_from() -> Result<(), Box<dyn std::error::Error>> {
+        let test = "select 1 + 2, -3";
+
+        let (output, value) = parse_select::<VerboseError<&str>>(test)?;
+
+        let value = match value {
+            ParseTree::Select(s) => s,
+            _ => panic!("Wrong type"),
+        };
+        assert_eq!(output.len(), 0);
+
+        let expected = RawSelectCommand {
+            table: None,
+            columns: vec![
+                ParseExpression::Operator(
+                    "+".to_string(),
+                    Box::new(ParseExpression::Number("1".to_string())),
+                    Box::new(ParseExpression::Number("2".to_string())),
+                ),
+                ParseExpression::Number("-3".to_string()),
+            ],
         };
         assert_eq!(expected, value);
 
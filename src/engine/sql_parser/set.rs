@@ -0,0 +1,125 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-set.html
+//! Only session level settings are supported
+
+use crate::engine::objects::{ParseExpression, ParseTree, RawSetCommand};
+
+use super::common::{
+    maybe_take_whitespace, parse_sql_identifier, parse_sql_string, take_whitespace,
+};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case};
+use nom::character::complete::{char, digit1, one_of};
+use nom::combinator::{cut, map, opt, recognize};
+use nom::error::{ContextError, ParseError};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::IResult;
+
+pub(super) fn parse_set<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, (name, value))) = tuple((
+        match_set,
+        cut(alt((
+            //SET TIME ZONE is the SQL standard spelling of SET timezone
+            map(
+                tuple((
+                    match_time_zone,
+                    take_whitespace,
+                    alt((map(tag_no_case("local"), |_| None), parse_setting_value)),
+                )),
+                |(_, _, value)| ("timezone".to_string(), value),
+            ),
+            map(
+                tuple((
+                    parse_sql_identifier,
+                    maybe_take_whitespace,
+                    alt((tag("="), terminated(tag_no_case("to"), take_whitespace))),
+                    maybe_take_whitespace,
+                    parse_setting_value,
+                )),
+                |(name, _, _, _, value)| (name.to_string(), value),
+            ),
+        ))),
+    ))(input)?;
+
+    Ok((input, ParseTree::Set(RawSetCommand { name, value })))
+}
+
+fn match_set<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (), E> {
+    let (input, _) = tuple((
+        tag_no_case("set"),
+        take_whitespace,
+        opt(tuple((tag_no_case("session"), take_whitespace))),
+    ))(input)?;
+    Ok((input, ()))
+}
+
+pub(super) fn match_time_zone<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (), E> {
+    let (input, _) = tuple((tag_no_case("time"), take_whitespace, tag_no_case("zone")))(input)?;
+    Ok((input, ()))
+}
+
+//A quoted string, a possibly signed number or a bare word, DEFAULT gives None
+fn parse_setting_value<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Option<String>, E> {
+    alt((
+        map(parse_sql_string, |s| match s {
+            ParseExpression::String(s) => Some(s),
+            _ => None,
+        }),
+        map(
+            recognize(tuple((
+                opt(one_of("+-")),
+                digit1,
+                opt(preceded(char('.'), digit1)),
+            ))),
+            |n: &str| Some(n.to_string()),
+        ),
+        map(parse_sql_identifier, |i: &str| {
+            if i.eq_ignore_ascii_case("default") {
+                None
+            } else {
+                Some(i.to_string())
+            }
+        }),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    fn set(name: &str, value: Option<&str>) -> ParseTree {
+        ParseTree::Set(RawSetCommand {
+            name: name.to_string(),
+            value: value.map(|v| v.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_set_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for (test, expected) in [
+            (
+                "set timezone = 'America/New_York'",
+                set("timezone", Some("America/New_York")),
+            ),
+            ("SET SESSION TimeZone TO UTC", set("TimeZone", Some("UTC"))),
+            ("set time zone -7", set("timezone", Some("-7"))),
+            ("set time zone local", set("timezone", None)),
+            ("set timezone to default", set("timezone", None)),
+        ] {
+            let (output, value) = parse_set::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0, "{}", test);
+            assert_eq!(format!("{:?}", value), format!("{:?}", expected));
+        }
+        assert!(parse_set::<VerboseError<&str>>("set timezone").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-show.html
+
+use crate::engine::objects::{ParseTree, RawShowCommand};
+
+use super::common::{parse_sql_identifier, take_whitespace};
+use super::set::match_time_zone;
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::{cut, map};
+use nom::error::{ContextError, ParseError};
+use nom::sequence::tuple;
+use nom::IResult;
+
+pub(super) fn parse_show<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, _, name)) = tuple((
+        tag_no_case("show"),
+        take_whitespace,
+        cut(alt((
+            map(match_time_zone, |_| "timezone".to_string()),
+            map(parse_sql_identifier, |n: &str| n.to_string()),
+        ))),
+    ))(input)?;
+
+    Ok((input, ParseTree::Show(RawShowCommand { name })))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_show_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for (test, expected) in [
+            ("show TimeZone", "TimeZone"),
+            ("SHOW TIME ZONE", "timezone"),
+        ] {
+            let (output, value) = parse_show::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0);
+            match value {
+                ParseTree::Show(s) => assert_eq!(s.name, expected),
+                _ => panic!("Wrong type"),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-begin.html
+//! Also COMMIT, ROLLBACK and their other spellings. Every isolation level behaves the same, only
+//! READ ONLY changes anything.
+
+use crate::engine::objects::{ParseTree, RawBeginCommand};
+
+use super::common::{match_comma, match_words, maybe_take_whitespace, take_whitespace};
+use nom::branch::alt;
+use nom::combinator::{map, opt};
+use nom::error::{ContextError, ParseError};
+use nom::multi::many0;
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
+
+pub(super) fn parse_begin<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, modes)) = tuple((
+        alt((
+            map(
+                tuple((match_words(&["begin"]), opt(match_noise_word))),
+                |_| (),
+            ),
+            map(match_words(&["start", "transaction"]), |_| ()),
+        )),
+        many0(preceded(
+            alt((
+                map(tuple((maybe_take_whitespace, match_comma)), |_| ()),
+                map(take_whitespace, |_| ()),
+            )),
+            preceded(maybe_take_whitespace, match_transaction_mode),
+        )),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::Begin(RawBeginCommand {
+            read_only: modes.into_iter().flatten().last().unwrap_or(false),
+        }),
+    ))
+}
+
+pub(super) fn parse_commit<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    map(
+        tuple((
+            alt((match_words(&["commit"]), match_words(&["end"]))),
+            opt(match_noise_word),
+        )),
+        |_| ParseTree::Commit,
+    )(input)
+}
+
+pub(super) fn parse_rollback<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    map(
+        tuple((
+            alt((match_words(&["rollback"]), match_words(&["abort"]))),
+            opt(match_noise_word),
+        )),
+        |_| ParseTree::Rollback,
+    )(input)
+}
+
+fn match_noise_word<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, String, E> {
+    preceded(
+        take_whitespace,
+        alt((match_words(&["work"]), match_words(&["transaction"]))),
+    )(input)
+}
+
+//Whether the mode makes the transaction read only, None if it says nothing about that
+fn match_transaction_mode<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Option<bool>, E> {
+    alt((
+        map(
+            tuple((
+                match_words(&["isolation", "level"]),
+                take_whitespace,
+                alt((
+                    match_words(&["serializable"]),
+                    match_words(&["repeatable", "read"]),
+                    match_words(&["read", "committed"]),
+                    match_words(&["read", "uncommitted"]),
+                )),
+            )),
+            |_| None,
+        ),
+        map(match_words(&["read", "only"]), |_| Some(true)),
+        map(match_words(&["read", "write"]), |_| Some(false)),
+        map(match_words(&["not", "deferrable"]), |_| None),
+        map(match_words(&["deferrable"]), |_| None),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_begin_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for (test, read_only) in [
+            ("begin", false),
+            ("BEGIN WORK", false),
+            ("start transaction read only", true),
+            ("begin isolation level repeatable read, read only", true),
+            ("begin transaction read only read write", false),
+        ] {
+            let (output, value) = parse_begin::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0);
+            match value {
+                ParseTree::Begin(b) => assert_eq!(b.read_only, read_only, "{}", test),
+                _ => panic!("Wrong type"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_rollback_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for test in ["commit", "END TRANSACTION", "commit work"] {
+            let (output, value) = parse_commit::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0);
+            assert!(matches!(value, ParseTree::Commit));
+        }
+        for test in ["rollback", "ABORT", "rollback transaction"] {
+            let (output, value) = parse_rollback::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0);
+            assert!(matches!(value, ParseTree::Rollback));
+        }
+        assert!(parse_commit::<VerboseError<&str>>("committed").is_err());
+        Ok(())
+    }
+}
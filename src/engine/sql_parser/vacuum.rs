@@ -0,0 +1,47 @@
+//! Format here: https://www.postgresql.org/docs/current/sql-vacuum.html
+//! Only the plain form is supported, without a table every table is vacuumed
+
+use crate::engine::objects::{ParseTree, RawVacuumCommand};
+
+use super::common::{parse_sql_identifier, take_whitespace};
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::opt;
+use nom::error::{ContextError, ParseError};
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
+
+pub(super) fn parse_vacuum<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, ParseTree, E> {
+    let (input, (_, table_name)) = tuple((
+        tag_no_case("vacuum"),
+        opt(preceded(take_whitespace, parse_sql_identifier)),
+    ))(input)?;
+
+    Ok((
+        input,
+        ParseTree::Vacuum(RawVacuumCommand {
+            table_name: table_name.map(|t| t.to_string()),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseError;
+
+    use super::*;
+
+    #[test]
+    fn test_vacuum_parser() -> Result<(), Box<dyn std::error::Error>> {
+        for (test, expected) in [("vacuum", None), ("VACUUM foo", Some("foo".to_string()))] {
+            let (output, value) = parse_vacuum::<VerboseError<&str>>(test)?;
+            assert_eq!(output.len(), 0);
+            match value {
+                ParseTree::Vacuum(v) => assert_eq!(v.table_name, expected),
+                _ => panic!("Wrong type"),
+            }
+        }
+        Ok(())
+    }
+}
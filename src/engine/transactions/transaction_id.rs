@@ -8,7 +8,7 @@ use thiserror::Error;
 pub struct TransactionId(u64);
 
 impl TransactionId {
-    pub fn new(value: u64) -> TransactionId {
+    pub const fn new(value: u64) -> TransactionId {
         TransactionId(value)
     }
 
@@ -1,37 +1,141 @@
 //! This is the interface to transaction visability (clog in postgres).
-use super::{TransactionId, TransactionIdError, TransactionStatus};
+//!
+//! When the cluster has a data directory every status is also kept in its pg_xact file, a byte per
+//! transaction starting from the first, and logged in the write ahead log. A transaction has
+//! committed once its commit record is flushed.
+//!
+//! On a hot standby the statuses only change by replaying the primary's log. Transactions started
+//! there are read only, each gets a snapshot of the statuses instead of an id of its own, see
+//! https://www.postgresql.org/docs/current/hot-standby.html
+//!
+//! A read only transaction block on a primary gets the same kind of snapshot, so everything it
+//! reads is as of when it started.
+use super::super::io::{DataDirectory, WalRecord, WriteAheadLog, WriteAheadLogError};
+use super::{TransactionId, TransactionIdError, TransactionSnapshot, TransactionStatus};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::SystemTime;
 use thiserror::Error;
-use tokio::sync::RwLock;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, RwLock};
 
 #[derive(Clone, Debug)]
 pub struct TransactionManager {
     tran_min: TransactionId, //Used to index the know transactions array
     known_trans: Arc<RwLock<Vec<TransactionStatus>>>,
+    start_times: Arc<RwLock<Vec<SystemTime>>>, //Indexed the same as known_trans
+    lookups: Arc<AtomicUsize>,                 //How many times get_status has been called
+    xact_file: Option<Arc<Mutex<File>>>,
+    wal: Option<WriteAheadLog>,
+    recovery: Arc<AtomicBool>,
+    //The read only transactions started during recovery or by start_snapshot, by id
+    readers: Arc<RwLock<HashMap<u64, (TransactionSnapshot, SystemTime)>>>,
+    next_reader: Arc<AtomicU64>,
+}
+
+//Read only transactions are numbered from here so they are past every real transaction
+const FIRST_READER: u64 = 1 << 62;
+
+impl Default for TransactionManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionManager {
     pub fn new() -> TransactionManager {
         let tran_min = TransactionId::new(1); //Must start at 1 since 0 is used for active rows
         let known_trans = Arc::new(RwLock::new(vec![TransactionStatus::Aborted])); //First transaction will be cancelled
+        let start_times = Arc::new(RwLock::new(vec![SystemTime::UNIX_EPOCH]));
         TransactionManager {
             tran_min,
             known_trans,
+            start_times,
+            lookups: Arc::new(AtomicUsize::new(0)),
+            xact_file: None,
+            wal: None,
+            recovery: Arc::new(AtomicBool::new(false)),
+            readers: Arc::new(RwLock::new(HashMap::new())),
+            next_reader: Arc::new(AtomicU64::new(FIRST_READER)),
         }
     }
 
+    /// Loads the statuses kept in the data directory's pg_xact file, an empty file is a new
+    /// cluster. Transactions still in progress were stopped by a crash so are aborted.
+    pub async fn open(
+        directory: &DataDirectory,
+    ) -> Result<TransactionManager, TransactionManagerError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(directory.xact_path())
+            .await?;
+        let mut raw = vec![];
+        file.read_to_end(&mut raw).await?;
+
+        let mut tm = TransactionManager::new();
+        if !raw.is_empty() {
+            let mut statuses = Vec::with_capacity(raw.len());
+            for (i, r) in raw.iter().enumerate() {
+                match TransactionStatus::parse(*r) {
+                    Some(TransactionStatus::InProgress) => {
+                        statuses.push(TransactionStatus::Aborted)
+                    }
+                    Some(s) => statuses.push(s),
+                    None => return Err(TransactionManagerError::CorruptStatus(i, *r)),
+                }
+            }
+            tm.start_times = Arc::new(RwLock::new(vec![SystemTime::UNIX_EPOCH; statuses.len()]));
+            tm.known_trans = Arc::new(RwLock::new(statuses));
+        }
+
+        let serialized: Vec<u8> = tm
+            .known_trans
+            .read()
+            .await
+            .iter()
+            .map(|s| s.serialize())
+            .collect();
+        file.seek(SeekFrom::Start(0)).await?;
+        file.write_all(&serialized).await?;
+        file.sync_data().await?;
+
+        tm.xact_file = Some(Arc::new(Mutex::new(file)));
+        tm.wal = Some(directory.wal().clone());
+        Ok(tm)
+    }
+
+    /// During recovery the transaction is read only and sees the statuses as they are now
     pub async fn start_trans(&mut self) -> Result<TransactionId, TransactionManagerError> {
+        if self.in_recovery() {
+            return self.start_reader().await;
+        }
         let mut known_trans = self.known_trans.write().await;
 
         known_trans.push(TransactionStatus::InProgress);
+        self.start_times.write().await.push(SystemTime::now());
+        let tran_id = self.tran_min.checked_add(known_trans.len() - 1)?;
+        self.write_status(tran_id, TransactionStatus::InProgress)
+            .await?;
+
+        Ok(tran_id)
+    }
 
-        Ok(self.tran_min.checked_add(known_trans.len() - 1)?)
+    /// A read only transaction that sees the statuses as they are now for its whole life
+    pub async fn start_snapshot(&self) -> Result<TransactionId, TransactionManagerError> {
+        self.start_reader().await
     }
 
     pub async fn get_status(
         &mut self,
         tran_id: TransactionId,
     ) -> Result<TransactionStatus, TransactionManagerError> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
         if tran_id < self.tran_min {
             return Err(TransactionManagerError::TooOld(tran_id, self.tran_min));
         }
@@ -51,11 +155,150 @@ impl TransactionManager {
         Ok(known_trans[index])
     }
 
+    /// How many statuses have been looked up, rows' hint bits are there to keep this down
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    /// The oldest transaction still in progress, or the next one to start if none are. Every row
+    /// deleted by a transaction that committed before this is invisible to everyone. A read only
+    /// transaction holds it back to the oldest transaction its snapshot saw running.
+    pub async fn oldest_active(&self) -> Result<TransactionId, TransactionManagerError> {
+        let known_trans = self.known_trans.read().await;
+        let index = known_trans
+            .iter()
+            .position(|t| *t == TransactionStatus::InProgress)
+            .unwrap_or(known_trans.len());
+        let oldest = self.tran_min.checked_add(index)?;
+        Ok(self
+            .readers
+            .read()
+            .await
+            .values()
+            .map(|(s, _)| s.min)
+            .fold(oldest, |o, m| if m < o { m } else { o }))
+    }
+
+    /// When the transaction started, this is what now() reports for the whole transaction
+    pub async fn get_start_time(
+        &self,
+        tran_id: TransactionId,
+    ) -> Result<SystemTime, TransactionManagerError> {
+        if let Some((_, start)) = self.readers.read().await.get(&tran_id.get_u64()) {
+            return Ok(*start);
+        }
+        if tran_id < self.tran_min {
+            return Err(TransactionManagerError::TooOld(tran_id, self.tran_min));
+        }
+
+        let start_times = self.start_times.read().await;
+        let index = tran_id.checked_sub(self.tran_min)?;
+
+        start_times
+            .get(index)
+            .copied()
+            .ok_or(TransactionManagerError::InTheFuture(
+                tran_id,
+                self.tran_min,
+                start_times.len(),
+            ))
+    }
+
+    /// Marks the cluster as a hot standby, see replay_status
+    pub fn start_recovery(&self) {
+        self.recovery.store(true, Ordering::SeqCst);
+    }
+
+    pub fn in_recovery(&self) -> bool {
+        self.recovery.load(Ordering::SeqCst)
+    }
+
+    /// Ends recovery, the primary's transactions still in progress will never finish so they
+    /// are aborted
+    pub async fn promote(&mut self) -> Result<(), TransactionManagerError> {
+        let mut known_trans = self.known_trans.write().await;
+        for (index, status) in known_trans.iter_mut().enumerate() {
+            if *status == TransactionStatus::InProgress {
+                *status = TransactionStatus::Aborted;
+                self.write_status(self.tran_min.checked_add(index)?, *status)
+                    .await?;
+            }
+        }
+        self.recovery.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Sets a status read from the primary's log, the data directory has already written it to
+    /// pg_xact. Transactions skipped over never finished, so they are aborted.
+    pub async fn replay_status(
+        &self,
+        tran_id: TransactionId,
+        status: TransactionStatus,
+    ) -> Result<(), TransactionManagerError> {
+        let index = tran_id.checked_sub(self.tran_min)?;
+        let mut known_trans = self.known_trans.write().await;
+        if known_trans.len() <= index {
+            known_trans.resize(index + 1, TransactionStatus::Aborted);
+            self.start_times
+                .write()
+                .await
+                .resize(index + 1, SystemTime::UNIX_EPOCH);
+        }
+        known_trans[index] = status;
+        Ok(())
+    }
+
+    /// The snapshot of a read only transaction started during recovery
+    pub async fn get_snapshot(&self, tran_id: TransactionId) -> Option<TransactionSnapshot> {
+        if !TransactionManager::is_read_only(tran_id) {
+            return None;
+        }
+        self.readers
+            .read()
+            .await
+            .get(&tran_id.get_u64())
+            .map(|(s, _)| s.clone())
+    }
+
+    /// Read only transactions can't change anything, they don't have a real id
+    pub fn is_read_only(tran_id: TransactionId) -> bool {
+        tran_id.get_u64() >= FIRST_READER
+    }
+
+    async fn start_reader(&self) -> Result<TransactionId, TransactionManagerError> {
+        let known_trans = self.known_trans.read().await;
+        let max = self.tran_min.checked_add(known_trans.len())?;
+        let mut in_range = vec![];
+        for (index, status) in known_trans.iter().enumerate() {
+            if *status == TransactionStatus::InProgress {
+                in_range.push(self.tran_min.checked_add(index)?);
+            }
+        }
+        let snapshot = TransactionSnapshot {
+            min: in_range.first().copied().unwrap_or(max),
+            max,
+            in_range,
+        };
+
+        let tran_id = TransactionId::new(self.next_reader.fetch_add(1, Ordering::SeqCst));
+        self.readers
+            .write()
+            .await
+            .insert(tran_id.get_u64(), (snapshot, SystemTime::now()));
+        Ok(tran_id)
+    }
+
     async fn update_trans(
         &mut self,
         tran_id: TransactionId,
         new_status: TransactionStatus,
     ) -> Result<(), TransactionManagerError> {
+        if TransactionManager::is_read_only(tran_id) {
+            return match self.readers.write().await.remove(&tran_id.get_u64()) {
+                Some(_) => Ok(()),
+                None => Err(TransactionManagerError::ReaderFinished(tran_id)),
+            };
+        }
         if tran_id < self.tran_min {
             return Err(TransactionManagerError::TooOld(tran_id, self.tran_min));
         }
@@ -79,11 +322,41 @@ impl TransactionManager {
             ));
         }
 
+        self.write_status(tran_id, new_status).await?;
         known_trans[index] = new_status;
 
         Ok(())
     }
 
+    //Logged before pg_xact is written like postgres, a checkpoint waits for both so its redo
+    //point is never between them
+    async fn write_status(
+        &self,
+        tran_id: TransactionId,
+        status: TransactionStatus,
+    ) -> Result<(), TransactionManagerError> {
+        let index = tran_id.checked_sub(self.tran_min)?;
+        let _delay = match &self.wal {
+            Some(wal) => {
+                let delay = wal.delay_checkpoint().await;
+                wal.append(&WalRecord::TransactionStatus { tran_id, status })
+                    .await?;
+                if status == TransactionStatus::Commited {
+                    wal.flush().await?;
+                }
+                Some(delay)
+            }
+            None => None,
+        };
+        if let Some(xact_file) = &self.xact_file {
+            let mut file = xact_file.lock().await;
+            file.seek(SeekFrom::Start(index as u64)).await?;
+            file.write_all(&[status.serialize()]).await?;
+            file.flush().await?;
+        }
+        Ok(())
+    }
+
     pub async fn commit_trans(
         &mut self,
         tran_id: TransactionId,
@@ -98,11 +371,6 @@ impl TransactionManager {
     ) -> Result<(), TransactionManagerError> {
         self.update_trans(tran_id, TransactionStatus::Aborted).await
     }
-
-    //TODO work on figuring out how to save / load this
-    pub fn serialize() {}
-
-    pub fn parse() {}
 }
 
 #[derive(Error, Debug)]
@@ -115,12 +383,22 @@ pub enum TransactionManagerError {
     InTheFuture(TransactionId, TransactionId, usize),
     #[error("Transaction Id {0} not in progress, found {1}")]
     NotInProgress(TransactionId, TransactionStatus),
+    #[error("Read only transaction {0} has already finished")]
+    ReaderFinished(TransactionId),
+    #[error("Transaction status {1} at {0} is not valid")]
+    CorruptStatus(usize, u8),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    WriteAheadLogError(#[from] WriteAheadLogError),
 }
 
 #[cfg(test)]
 mod tests {
     #![allow(unused_must_use)]
+    use super::super::super::io::ControlFile;
     use super::*;
+    use std::collections::HashMap;
 
     //Async testing help can be found here: https://blog.x5ff.xyz/blog/async-tests-tokio-rust/
     macro_rules! aw {
@@ -171,4 +449,107 @@ mod tests {
             TransactionStatus::Aborted
         );
     }
+
+    #[test]
+    fn tran_man_start_times() -> Result<(), Box<dyn std::error::Error>> {
+        let mut tm = TransactionManager::new();
+        let tran1 = aw!(tm.start_trans())?;
+        let tran2 = aw!(tm.start_trans())?;
+
+        let start1 = aw!(tm.get_start_time(tran1))?;
+        assert!(start1 <= aw!(tm.get_start_time(tran2))?);
+
+        //Finishing the transaction doesn't change when it started
+        aw!(tm.commit_trans(tran1))?;
+        assert_eq!(aw!(tm.get_start_time(tran1))?, start1);
+
+        assert!(aw!(tm.get_start_time(tran2.checked_add(1)?)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn tran_man_open() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let directory = aw!(DataDirectory::initdb(
+            tmp.path(),
+            ControlFile::default(),
+            &HashMap::new()
+        ))?;
+        let path = directory.xact_path();
+
+        let mut tm = aw!(TransactionManager::open(&directory))?;
+        let committed = aw!(tm.start_trans())?;
+        let aborted = aw!(tm.start_trans())?;
+        let running = aw!(tm.start_trans())?;
+        aw!(tm.commit_trans(committed))?;
+        aw!(tm.abort_trans(aborted))?;
+
+        let mut reopened = aw!(TransactionManager::open(&directory))?;
+        assert_eq!(
+            aw!(reopened.get_status(committed))?,
+            TransactionStatus::Commited
+        );
+        assert_eq!(
+            aw!(reopened.get_status(aborted))?,
+            TransactionStatus::Aborted
+        );
+        assert_eq!(
+            aw!(reopened.get_status(running))?,
+            TransactionStatus::Aborted
+        );
+        assert_eq!(aw!(reopened.start_trans())?, running.checked_add(1)?);
+
+        std::fs::write(&path, [3, 7])?;
+        assert!(matches!(
+            aw!(TransactionManager::open(&directory)),
+            Err(TransactionManagerError::CorruptStatus(1, 7))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn tran_man_oldest_active() -> Result<(), Box<dyn std::error::Error>> {
+        let mut tm = TransactionManager::new();
+        let tran1 = aw!(tm.start_trans())?;
+        let tran2 = aw!(tm.start_trans())?;
+        assert_eq!(aw!(tm.oldest_active())?, tran1);
+
+        aw!(tm.commit_trans(tran1))?;
+        assert_eq!(aw!(tm.oldest_active())?, tran2);
+
+        //With nothing running the horizon is the next transaction
+        aw!(tm.abort_trans(tran2))?;
+        assert_eq!(aw!(tm.oldest_active())?, tran2.checked_add(1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn tran_man_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+        let mut tm = TransactionManager::new();
+        let running = aw!(tm.start_trans())?;
+        let reader = aw!(tm.start_snapshot())?;
+        assert!(TransactionManager::is_read_only(reader));
+
+        //The reader keeps seeing running as in progress after it commits
+        aw!(tm.commit_trans(running))?;
+        let snapshot = aw!(tm.get_snapshot(reader)).unwrap();
+        assert!(snapshot.is_running(running));
+        assert_eq!(aw!(tm.oldest_active())?, running);
+
+        aw!(tm.commit_trans(reader))?;
+        assert_eq!(aw!(tm.oldest_active())?, running.checked_add(1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn tran_man_lookups() -> Result<(), Box<dyn std::error::Error>> {
+        let mut tm = TransactionManager::new();
+        let tran = aw!(tm.start_trans())?;
+        assert_eq!(tm.lookups(), 0);
+
+        aw!(tm.get_status(tran))?;
+        aw!(tm.clone().get_status(tran))?;
+        assert_eq!(tm.lookups(), 2); //Shared by clones
+        Ok(())
+    }
 }
@@ -8,3 +8,29 @@ pub struct TransactionSnapshot {
     pub max: TransactionId,
     pub in_range: Vec<TransactionId>,
 }
+
+impl TransactionSnapshot {
+    /// Whether the transaction hadn't finished when the snapshot was taken, so nothing it did
+    /// is seen even if it has finished since
+    pub fn is_running(&self, tran_id: TransactionId) -> bool {
+        tran_id >= self.max || (tran_id >= self.min && self.in_range.contains(&tran_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_running() {
+        let snapshot = TransactionSnapshot {
+            min: TransactionId::new(3),
+            max: TransactionId::new(6),
+            in_range: vec![TransactionId::new(3), TransactionId::new(5)],
+        };
+        let running: Vec<_> = (1..8)
+            .filter(|t| snapshot.is_running(TransactionId::new(*t)))
+            .collect();
+        assert_eq!(running, vec![3, 5, 6, 7]);
+    }
+}
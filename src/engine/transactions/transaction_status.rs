@@ -9,6 +9,26 @@ pub enum TransactionStatus {
     //SUB_COMMITTED, Not implementing until I need it
 }
 
+impl TransactionStatus {
+    /// How the status is stored in pg_xact
+    pub fn serialize(self) -> u8 {
+        match self {
+            TransactionStatus::InProgress => 1,
+            TransactionStatus::Commited => 2,
+            TransactionStatus::Aborted => 3,
+        }
+    }
+
+    pub fn parse(value: u8) -> Option<TransactionStatus> {
+        match value {
+            1 => Some(TransactionStatus::InProgress),
+            2 => Some(TransactionStatus::Commited),
+            3 => Some(TransactionStatus::Aborted),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for TransactionStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
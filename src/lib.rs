@@ -11,5 +11,7 @@ extern crate simplelog;
 //Application Imports/Exports
 pub mod codec;
 pub mod constants;
+pub mod dump;
 pub mod engine;
 pub mod processor;
+pub mod replication;
@@ -4,14 +4,133 @@
 extern crate log;
 
 extern crate simplelog;
-use feophantlib::codec::{NetworkFrame, PgCodec};
-use feophantlib::engine::{io::IOManager, transactions::TransactionManager, Engine};
-use feophantlib::processor::ClientProcessor;
-use futures::sink::SinkExt;
-use futures::stream::StreamExt;
+use feophantlib::engine::{
+    autovacuum::AUTOVACUUM_NAPTIME,
+    io::{
+        page_formats::PageSize, verify_directory_checksums, ControlFile, DataDirectory, IOManager,
+        RecoveryTarget,
+    },
+    objects::ServerSettings,
+    transactions::{TransactionId, TransactionManager},
+    Engine,
+};
+use feophantlib::processor::handle_connection;
+use feophantlib::replication::WalReceiver;
 use simplelog::{ColorChoice, CombinedLogger, Config, LevelFilter, TermLogger, TerminalMode};
+use std::convert::TryFrom;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime};
 use tokio::net::TcpListener;
-use tokio_util::codec::Framed;
+
+const USAGE: &str =
+    "Usage: feophant-server [-p <port>] [-c <name>=<value>]...
+                       [-D <dir> [--archive <dir>] [--standby <host:port>]]
+       feophant-server --init <dir> [--data-checksums] [--page-size <kB>]
+       feophant-server --recover <dir> --archive <dir> <target>
+       feophant-server --verify-checksums <dir>
+  With no options everything is kept in memory and lost when the server stops.
+  --init <dir>              Create a new cluster in the directory and exit
+  --data-checksums          Have the new cluster checksum every page
+  --page-size <kB>          The new cluster's page size, one of 4, 8, 16 or 32, 8 if not given
+  -p <port>                 The port to listen on, 50000 if not given
+  -c <name>=<value>         Set a server setting like checkpoint_timeout, SHOW reads it back.
+                            allow_server_files=on lets every client COPY from the server's files
+  -D <dir>                  Serve the cluster in the directory
+  --archive <dir>           Where completed write ahead log segments are copied to, or restored from
+  --standby <host:port>     Serve the cluster read only while following the primary, until PROMOTE
+  --recover <dir>           Bring a copy of a cluster forward to the target using the archive and exit
+  --verify-checksums <dir>  Check every page of a cluster that is shut down, failing if any are bad
+  The target is one of:
+  --target-lsn <lsn>        Stop before the change at the log position
+  --target-xid <xid>        Stop after the transaction commits or aborts
+  --target-time <secs>      Stop at the time, in seconds since the unix epoch";
+
+enum Mode {
+    Memory,
+    Init(PathBuf, PageSize, bool),
+    Serve(PathBuf, Option<PathBuf>, Option<String>),
+    Recover(PathBuf, PathBuf, RecoveryTarget),
+    VerifyChecksums(PathBuf),
+}
+
+//Each -c name=value in the order given
+type SettingArgs = Vec<(String, String)>;
+
+fn parse_args(args: &[String]) -> Option<(Mode, u16, SettingArgs)> {
+    let mut port = None;
+    let mut settings = vec![];
+    let mut standby = None;
+    let mut init = None;
+    let mut serve = None;
+    let mut recover = None;
+    let mut checksums = false;
+    let mut page_size = None;
+    let mut verify = None;
+    let mut archive = None;
+    let mut target = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-p" => port = Some(args.next()?.parse().ok()?),
+            "-c" => {
+                let (name, value) = args.next()?.split_once('=')?;
+                settings.push((name.to_string(), value.to_string()));
+            }
+            "--standby" => standby = Some(args.next()?.to_string()),
+            "--init" => init = Some(PathBuf::from(args.next()?)),
+            "--data-checksums" => checksums = true,
+            "--page-size" => {
+                let kb: usize = args.next()?.parse().ok()?;
+                page_size = Some(PageSize::try_from(kb.checked_mul(1024)?).ok()?);
+            }
+            "-D" => serve = Some(PathBuf::from(args.next()?)),
+            "--archive" => archive = Some(PathBuf::from(args.next()?)),
+            "--recover" => recover = Some(PathBuf::from(args.next()?)),
+            "--verify-checksums" => verify = Some(PathBuf::from(args.next()?)),
+            "--target-lsn" => target = Some(RecoveryTarget::Lsn(args.next()?.parse().ok()?)),
+            "--target-xid" => {
+                let xid = args.next()?.parse().ok()?;
+                target = Some(RecoveryTarget::TransactionId(TransactionId::new(xid)));
+            }
+            "--target-time" => {
+                let secs = args.next()?.parse().ok()?;
+                target = Some(RecoveryTarget::Time(
+                    SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                ));
+            }
+            _ => return None,
+        }
+    }
+
+    let serves = port.is_some() || standby.is_some() || !settings.is_empty();
+    let creates = checksums || page_size.is_some();
+    let mode = match (init, serve, recover, verify) {
+        (None, None, None, None)
+            if !creates && archive.is_none() && target.is_none() && standby.is_none() =>
+        {
+            Mode::Memory
+        }
+        (Some(dir), None, None, None) if archive.is_none() && target.is_none() && !serves => {
+            Mode::Init(dir, page_size.unwrap_or_default(), checksums)
+        }
+        (None, Some(dir), None, None) if !creates && target.is_none() => {
+            Mode::Serve(dir, archive, standby)
+        }
+        (None, None, Some(dir), None) if !creates && !serves => {
+            Mode::Recover(dir, archive?, target?)
+        }
+        (None, None, None, Some(dir))
+            if !creates && !serves && archive.is_none() && target.is_none() =>
+        {
+            Mode::VerifyChecksums(dir)
+        }
+        _ => return None,
+    };
+    Some((mode, port.unwrap_or(50000), settings))
+}
 
 #[tokio::main]
 async fn main() {
@@ -25,13 +144,106 @@ async fn main() {
 
     info!("Welcome to FeOphant!");
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (mode, port, settings) = match parse_args(&args) {
+        Some(m) => m,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let mut server_settings = ServerSettings::default();
+    for (name, value) in settings {
+        if let Err(e) = server_settings.set(&name, &value) {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
+
     //Start the services first
-    let io_manager = IOManager::new();
-    let transaction_manager = TransactionManager::new();
-    let engine = Engine::new(io_manager, transaction_manager.clone());
+    let (io_manager, transaction_manager) = match mode {
+        Mode::Memory => (IOManager::new(), TransactionManager::new()),
+        Mode::Init(dir, page_size, checksums) => {
+            let mut control_file = ControlFile::new(page_size);
+            if checksums {
+                control_file = control_file.with_checksums();
+            }
+            let result = match IOManager::initdb_directory(&dir, control_file).await {
+                Ok(io_manager) => TransactionManager::open(io_manager.directory().unwrap())
+                    .await
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match result {
+                Ok(_) => info!("Created a new cluster in {}", dir.display()),
+                Err(e) => {
+                    error!("Unable to create a cluster in {}: {}", dir.display(), e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Mode::Recover(dir, archive, target) => {
+            match DataDirectory::recover(&dir, &archive, target).await {
+                Ok(lsn) => info!("Recovered {} up to LSN {}", dir.display(), lsn),
+                Err(e) => {
+                    error!("Unable to recover {}: {}", dir.display(), e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Mode::VerifyChecksums(dir) => {
+            let failures = match verify_directory_checksums(&dir).await {
+                Ok(f) => f,
+                Err(e) => {
+                    error!("Unable to check {}: {}", dir.display(), e);
+                    process::exit(1);
+                }
+            };
+            for failure in &failures {
+                error!("{}", failure);
+            }
+            if !failures.is_empty() {
+                error!("{} bad pages in {}", failures.len(), dir.display());
+                process::exit(1);
+            }
+            info!("Every page checksum in {} is correct", dir.display());
+            return;
+        }
+        Mode::Serve(dir, archive, standby) => {
+            let opened = match IOManager::open_with_archive(&dir, archive).await {
+                Ok(io_manager) => TransactionManager::open(io_manager.directory().unwrap())
+                    .await
+                    .map(|tm| (io_manager, tm))
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            let (io_manager, tm) = match opened {
+                Ok(o) => o,
+                Err(e) => {
+                    error!("Unable to use the cluster in {}: {}", dir.display(), e);
+                    process::exit(1);
+                }
+            };
+            if let Some(primary) = standby {
+                if let Err(e) = io_manager.start_recovery().await {
+                    error!("Unable to start as a standby: {}", e);
+                    process::exit(1);
+                }
+                tm.start_recovery();
+                info!("Running as a hot standby of {}", primary);
+                tokio::spawn(WalReceiver::new(primary, io_manager.clone(), tm.clone()).run());
+            }
+            (io_manager, tm)
+        }
+    };
+    let engine = Engine::new(io_manager.clone(), transaction_manager.clone())
+        .with_server_settings(server_settings);
+    tokio::spawn(engine.autovacuum(AUTOVACUUM_NAPTIME).run());
+    tokio::spawn(engine.background_writer().run());
+    tokio::spawn(engine.checkpointer().run());
 
-    //Bind to a fixed port
-    let port: u32 = 50000;
     let listener = TcpListener::bind(format!("{}{}", "127.0.0.1:", port))
         .await
         .unwrap();
@@ -39,36 +251,23 @@ async fn main() {
     info!("Up and listening on port {}", port);
 
     loop {
-        let (stream, client_addr) = listener.accept().await.unwrap();
+        let (stream, client_addr) = tokio::select! {
+            accepted = listener.accept() => accepted.unwrap(),
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutting down");
+                if let Err(e) = io_manager.shutdown().await {
+                    error!("Unable to record the shutdown: {}", e);
+                }
+                return;
+            }
+        };
 
         info!("Got a connection from {}", client_addr);
 
-        let tm = transaction_manager.clone();
-        let eng = engine.clone();
-        tokio::spawn(async move {
-            let codec = PgCodec {};
-            let (mut sink, mut input) = Framed::new(stream, codec).split();
-
-            let mut process = ClientProcessor::new(eng, tm);
-            while let Some(Ok(event)) = input.next().await {
-                let responses: Vec<NetworkFrame> = match process.process(event).await {
-                    Ok(responses) => responses,
-                    Err(e) => {
-                        warn!("Had a processing error {}", e);
-                        break;
-                    }
-                };
-
-                for response in responses {
-                    match sink.send(response).await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            warn!("Unable to send response {}", e);
-                            break;
-                        }
-                    }
-                }
-            }
-        });
+        tokio::spawn(handle_connection(
+            stream,
+            engine.clone(),
+            transaction_manager.clone(),
+        ));
     }
 }
@@ -1,7 +1,11 @@
 //! This module covers taking a message and parsing it into a command object
 
+mod client_connection;
+pub use client_connection::handle_connection;
+
 mod client_processor;
 pub use client_processor::ClientProcessor;
+pub use client_processor::ReplicationStart;
 
 pub mod ssl_and_gssapi_parser;
 pub mod startup_parser;
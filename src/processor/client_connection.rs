@@ -0,0 +1,68 @@
+//! Runs a client's connection, answering each message it sends until it goes away. A replication
+//! connection that starts streaming is handed over to a WalSender or LogicalSender for the rest
+//! of its life.
+
+use super::{ClientProcessor, ReplicationStart};
+use crate::codec::{NetworkFrame, PgCodec};
+use crate::engine::transactions::TransactionManager;
+use crate::engine::Engine;
+use crate::replication::{format_lsn, LogicalSender, WalSender};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+pub async fn handle_connection(stream: TcpStream, engine: Engine, tm: TransactionManager) {
+    let directory = engine.io_manager().directory().cloned();
+    let codec = PgCodec {};
+    let (mut sink, mut input) = Framed::new(stream, codec).split();
+
+    let mut process = ClientProcessor::new(engine, tm.clone());
+    while let Some(Ok(event)) = input.next().await {
+        let responses: Vec<NetworkFrame> = match process.process(event).await {
+            Ok(responses) => responses,
+            Err(e) => {
+                warn!("Had a processing error {}", e);
+                break;
+            }
+        };
+
+        for response in responses {
+            match sink.send(response).await {
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Unable to send response {}", e);
+                    break;
+                }
+            }
+        }
+
+        match (process.take_replication_start(), &directory) {
+            (Some(ReplicationStart::Physical(start)), Some(directory)) => {
+                info!("Streaming the write ahead log from {}", format_lsn(start));
+                if let Err(e) = WalSender::new(directory.wal().clone())
+                    .run(start, &mut sink, &mut input)
+                    .await
+                {
+                    warn!("Stopped streaming the write ahead log {}", e);
+                }
+                break;
+            }
+            (Some(ReplicationStart::Logical(slot, start)), Some(directory)) => {
+                info!("Streaming slot {} from {}", slot.name, format_lsn(start));
+                if let Err(e) = LogicalSender::new(directory.clone(), tm.clone())
+                    .run(slot, start, &mut sink, &mut input)
+                    .await
+                {
+                    warn!("Stopped streaming a replication slot {}", e);
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    if let Err(e) = process.disconnect().await {
+        warn!("Unable to abort the connection's transaction {}", e);
+    }
+}
@@ -1,16 +1,62 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use std::collections::VecDeque;
 use thiserror::Error;
 
-use super::super::engine::transactions::{TransactionManager, TransactionManagerError};
-use super::super::engine::{Engine, EngineError};
+use super::super::engine::io::{DataDirectoryError, IOManagerError, ReplicationSlot};
+use super::super::engine::objects::{
+    CopyTarget, ParseTree, RawCopyCommand, SessionSettings, SqlTuple,
+};
+use super::super::engine::transactions::{
+    TransactionId, TransactionManager, TransactionManagerError,
+};
+use super::super::engine::{Engine, EngineError, SqlParser};
 use super::ssl_and_gssapi_parser;
 use super::startup_parser;
 use crate::codec::{NetworkFrame, NetworkFrameError};
-use crate::constants::{PgErrorCodes, PgErrorLevels};
+use crate::constants::{BuiltinSqlTypes, DeserializeTypes, PgErrorCodes, PgErrorLevels};
+use crate::replication::{format_lsn, ReplicationCommand, ReplicationMessage};
+
+/// What a START_REPLICATION asked to stream
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplicationStart {
+    ///The log from the LSN on
+    Physical(u64),
+    ///The transactions of a slot, already acquired, that end after the LSN
+    Logical(ReplicationSlot, u64),
+}
+
+/// Where the connection is in a transaction block, BEGIN starts one that lasts across queries
+/// until COMMIT or ROLLBACK
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TransactionBlock {
+    Idle,
+    Open(TransactionId),
+    ///A statement failed so its transaction was aborted, everything else is refused until the
+    ///block is ended
+    Failed,
+}
+
+/// A COPY FROM STDIN waiting for the client to send its rows, COPY FROM a file and COPY TO
+/// STDOUT are done as soon as they are run
+struct CopyIn {
+    tran_id: TransactionId,
+    command: RawCopyCommand,
+    data: BytesMut,
+    ///The statements after the COPY in the same query, they run once it is done
+    remaining: VecDeque<ParseTree>,
+}
 
 pub struct ClientProcessor {
     engine: Engine,
     transaction_manager: TransactionManager,
+    ///Set by the startup message, allows replication commands as well as SQL
+    replication: bool,
+    ///What a START_REPLICATION asked to stream, see take_replication_start
+    replication_start: Option<ReplicationStart>,
+    block: TransactionBlock,
+    ///The transaction of the query being run outside a block, committed once it is done
+    implicit: Option<TransactionId>,
+    copy_in: Option<CopyIn>,
 }
 
 impl ClientProcessor {
@@ -18,9 +64,25 @@ impl ClientProcessor {
         ClientProcessor {
             engine,
             transaction_manager,
+            replication: false,
+            replication_start: None,
+            block: TransactionBlock::Idle,
+            implicit: None,
+            copy_in: None,
         }
     }
 
+    /// A client leaving in the middle of a transaction block or COPY loses what it did
+    pub async fn disconnect(&mut self) -> Result<(), ClientProcessorError> {
+        self.abort_all().await
+    }
+
+    /// Once a replication connection has asked to stream it stops being a series of queries, the
+    /// caller hands it to a WalSender or LogicalSender
+    pub fn take_replication_start(&mut self) -> Option<ReplicationStart> {
+        self.replication_start.take()
+    }
+
     pub async fn process(
         &mut self,
         frame: NetworkFrame,
@@ -44,31 +106,39 @@ impl ClientProcessor {
             //We should also check for configured authentication methods... maybe later!
             //   we're just going to let them in so we can get further on message parsing.
             info!("Just going to let {:?} in", message.get("user"));
-            return Ok(vec![
-                NetworkFrame::authentication_ok(),
-                NetworkFrame::ready_for_query(),
-            ]);
+
+            self.replication = matches!(
+                message.get("replication").map(|r| r.as_str()),
+                Some("true" | "on" | "yes" | "1")
+            );
+
+            if let Some(tz) = message.get("TimeZone") {
+                if let Err(e) = self.engine.settings_mut().set("TimeZone", Some(tz)) {
+                    warn!("Ignoring the startup time zone {}", e);
+                }
+            }
+
+            let mut frames = vec![NetworkFrame::authentication_ok()];
+            frames.append(&mut self.parameter_statuses());
+            frames.push(NetworkFrame::ready_for_query());
+            return Ok(frames);
         }
 
         //Support basic query
         if frame.message_type == b'Q' {
             debug!("Got query {:?}", payload_buff);
 
-            let result = match self.process_single_query(payload_buff).await {
-                Ok(o) => o,
-                Err(e) => {
-                    return Ok(vec![
-                        NetworkFrame::error_response(
-                            PgErrorLevels::Error,
-                            PgErrorCodes::SystemError,
-                            e.to_string(),
-                        ),
-                        NetworkFrame::ready_for_query(),
-                    ]);
+            if self.replication {
+                if let Some(frames) = self.process_replication_command(payload_buff).await {
+                    return Ok(frames);
                 }
-            };
+            }
+            return self.process_simple_query(payload_buff).await;
+        }
 
-            return Ok(result);
+        //The copy sub-protocol, these are ignored once a copy has failed like postgres
+        if matches!(frame.message_type, b'd' | b'c' | b'f' | b'H' | b'S') {
+            return self.process_copy_in(frame).await;
         }
 
         warn!(
@@ -82,56 +152,757 @@ impl ClientProcessor {
         )])
     }
 
-    async fn process_single_query(
+    /// None if the query isn't a replication command. Replication can only stream from a cluster
+    /// with a write ahead log.
+    async fn process_replication_command(
+        &mut self,
+        payload_buff: &[u8],
+    ) -> Option<Vec<NetworkFrame>> {
+        let query_buff = match payload_buff.split_last() {
+            Some((0, q)) => q,
+            _ => payload_buff,
+        };
+        let command = ReplicationCommand::parse(std::str::from_utf8(query_buff).ok()?)?;
+
+        let mut frames = vec![];
+        if let Err(e) = self.run_replication_command(command, &mut frames).await {
+            frames.push(NetworkFrame::error_response(
+                PgErrorLevels::Error,
+                e.pg_error_code(),
+                e.to_string(),
+            ));
+        } else if self.replication_start.is_some() {
+            //The stream takes over
+            return Some(frames);
+        }
+        frames.push(NetworkFrame::ready_for_query());
+        Some(frames)
+    }
+
+    async fn run_replication_command(
+        &mut self,
+        command: ReplicationCommand,
+        frames: &mut Vec<NetworkFrame>,
+    ) -> Result<(), ClientProcessorError> {
+        let io_manager = self.engine.io_manager();
+        let text = |t: String| Some(BuiltinSqlTypes::Text(t));
+        match command {
+            ReplicationCommand::IdentifySystem => {
+                let directory = io_manager
+                    .directory()
+                    .ok_or(ClientProcessorError::NoWriteAheadLog())?;
+                //There is no system identifier and only ever one timeline
+                frames.push(NetworkFrame::row_description(vec![
+                    ("systemid".to_string(), DeserializeTypes::Text),
+                    ("timeline".to_string(), DeserializeTypes::Integer),
+                    ("xlogpos".to_string(), DeserializeTypes::Text),
+                    ("dbname".to_string(), DeserializeTypes::Text),
+                ])?);
+                frames.append(&mut NetworkFrame::data_rows(
+                    vec![SqlTuple(vec![
+                        None,
+                        Some(BuiltinSqlTypes::Integer(1)),
+                        text(format_lsn(directory.wal().end_lsn().await)),
+                        text("feophant".to_string()),
+                    ])],
+                    self.engine.settings(),
+                )?);
+                frames.push(NetworkFrame::command_complete(
+                    "IDENTIFY_SYSTEM".to_string(),
+                ));
+            }
+            ReplicationCommand::BaseBackup => {
+                let (backup, files) = io_manager.base_backup().await?;
+                frames.push(NetworkFrame::copy_out_response(true, 0)?);
+                for (name, contents) in files {
+                    frames.push(ReplicationMessage::BackupFile { name, contents }.to_frame());
+                }
+                frames.push(NetworkFrame::new(b'c', Bytes::new()));
+                frames.push(NetworkFrame::row_description(vec![
+                    ("start_lsn".to_string(), DeserializeTypes::Text),
+                    ("stop_lsn".to_string(), DeserializeTypes::Text),
+                    ("files".to_string(), DeserializeTypes::BigInt),
+                ])?);
+                frames.append(&mut NetworkFrame::data_rows(
+                    vec![SqlTuple(vec![
+                        text(format_lsn(backup.start_lsn)),
+                        text(format_lsn(backup.stop_lsn)),
+                        Some(BuiltinSqlTypes::BigInt(backup.files as i64)),
+                    ])],
+                    self.engine.settings(),
+                )?);
+                frames.push(NetworkFrame::command_complete("BASE_BACKUP".to_string()));
+            }
+            ReplicationCommand::StartReplication(start) => {
+                if io_manager.directory().is_none() {
+                    return Err(ClientProcessorError::NoWriteAheadLog());
+                }
+                self.replication_start = Some(ReplicationStart::Physical(start));
+            }
+            ReplicationCommand::CreateReplicationSlot { slot, plugin } => {
+                let slot = io_manager
+                    .slot_directory()?
+                    .create_slot(&slot, &plugin)
+                    .await?;
+                frames.push(NetworkFrame::row_description(vec![
+                    ("slot_name".to_string(), DeserializeTypes::Text),
+                    ("consistent_point".to_string(), DeserializeTypes::Text),
+                    ("snapshot_name".to_string(), DeserializeTypes::Text),
+                    ("output_plugin".to_string(), DeserializeTypes::Text),
+                ])?);
+                frames.append(&mut NetworkFrame::data_rows(
+                    vec![SqlTuple(vec![
+                        text(slot.name),
+                        text(format_lsn(slot.confirmed_flush)),
+                        None,
+                        text(slot.plugin),
+                    ])],
+                    self.engine.settings(),
+                )?);
+                frames.push(NetworkFrame::command_complete(
+                    "CREATE_REPLICATION_SLOT".to_string(),
+                ));
+            }
+            ReplicationCommand::DropReplicationSlot(slot) => {
+                io_manager.slot_directory()?.drop_slot(&slot).await?;
+                frames.push(NetworkFrame::command_complete(
+                    "DROP_REPLICATION_SLOT".to_string(),
+                ));
+            }
+            ReplicationCommand::StartLogicalReplication { slot, start } => {
+                let slot = io_manager.slot_directory()?.acquire_slot(&slot).await?;
+                self.replication_start = Some(ReplicationStart::Logical(slot, start));
+            }
+        }
+        Ok(())
+    }
+
+    /// A simple query can hold multiple statements, they are run in order inside a single
+    /// implicit transaction unless they are in a transaction block. The first error aborts the
+    /// transaction and skips the rest.
+    async fn process_simple_query(
         &mut self,
         payload_buff: &[u8],
     ) -> Result<Vec<NetworkFrame>, ClientProcessorError> {
-        //Convert to utf8
-        let query_str = String::from_utf8(payload_buff.to_vec())?;
+        let mut frames = vec![];
 
-        let txid = self.transaction_manager.start_trans().await?;
+        let result = self.run_simple_query(payload_buff, &mut frames).await;
+        self.finish_query(result, frames).await
+    }
 
-        let query_res = match self.engine.process_query(txid, query_str).await {
-            Ok(o) => {
-                self.transaction_manager.commit_trans(txid).await?;
-                o
+    /// The rows of a COPY FROM STDIN arrive as copy data until the client says it is done or
+    /// gives up
+    async fn process_copy_in(
+        &mut self,
+        frame: NetworkFrame,
+    ) -> Result<Vec<NetworkFrame>, ClientProcessorError> {
+        let copy_in = match self.copy_in.as_mut() {
+            Some(c) => c,
+            None => return Ok(vec![]),
+        };
+        let result = match frame.message_type {
+            b'd' => {
+                copy_in.data.extend_from_slice(&frame.payload);
+                return Ok(vec![]);
             }
-            Err(e) => {
-                self.transaction_manager.abort_trans(txid).await?;
-                return Err(ClientProcessorError::EngineError(e));
+            b'c' => {
+                let mut frames = vec![];
+                let result = self.run_copy_in(&mut frames).await;
+                return self.finish_query(result, frames).await;
             }
+            b'f' => {
+                let message = String::from_utf8_lossy(&frame.payload);
+                Err(ClientProcessorError::CopyFailed(
+                    message.trim_end_matches('\0').to_string(),
+                ))
+            }
+            //Flush and sync mean nothing until the copy is over
+            _ => return Ok(vec![]),
         };
+        self.finish_query(result, vec![]).await
+    }
 
-        let mut frames = vec![];
-        if query_res.columns.len() > 0 {
-            frames.push(NetworkFrame::row_description(query_res.columns)?);
+    //Ends the query with its error if it had one, unless it is waiting for COPY data
+    async fn finish_query(
+        &mut self,
+        result: Result<(), ClientProcessorError>,
+        mut frames: Vec<NetworkFrame>,
+    ) -> Result<Vec<NetworkFrame>, ClientProcessorError> {
+        if let Err(e) = result {
+            self.fail().await?;
+            frames.push(NetworkFrame::error_response(
+                PgErrorLevels::Error,
+                e.pg_error_code(),
+                e.to_string(),
+            ));
+        } else if self.copy_in.is_some() {
+            return Ok(frames);
+        }
+
+        let status = match self.block {
+            TransactionBlock::Idle => b'I',
+            TransactionBlock::Open(_) => b'T',
+            TransactionBlock::Failed => b'E',
+        };
+        frames.push(NetworkFrame::ready_for_query_in(status));
+        Ok(frames)
+    }
+
+    //After an error the query's transaction is aborted, in a block that fails the block
+    async fn fail(&mut self) -> Result<(), ClientProcessorError> {
+        let in_block = self.block != TransactionBlock::Idle;
+        self.abort_all().await?;
+        if in_block {
+            self.block = TransactionBlock::Failed;
+        }
+        Ok(())
+    }
+
+    async fn abort_all(&mut self) -> Result<(), ClientProcessorError> {
+        self.copy_in = None;
+        if let Some(tran_id) = self.implicit.take() {
+            self.transaction_manager.abort_trans(tran_id).await?;
+        }
+        if let TransactionBlock::Open(tran_id) = self.block {
+            self.transaction_manager.abort_trans(tran_id).await?;
+        }
+        self.block = TransactionBlock::Idle;
+        Ok(())
+    }
+
+    async fn run_copy_in(
+        &mut self,
+        frames: &mut Vec<NetworkFrame>,
+    ) -> Result<(), ClientProcessorError> {
+        let copy_in = match self.copy_in.take() {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        let rows = self
+            .engine
+            .copy_from(copy_in.tran_id, copy_in.command, copy_in.data.freeze())
+            .await?;
+        frames.push(NetworkFrame::command_complete(format!("COPY {}", rows)));
+        self.run_statements(copy_in.remaining, frames).await
+    }
+
+    async fn run_simple_query(
+        &mut self,
+        payload_buff: &[u8],
+        frames: &mut Vec<NetworkFrame>,
+    ) -> Result<(), ClientProcessorError> {
+        //The query string is null terminated
+        let query_buff = match payload_buff.split_last() {
+            Some((0, q)) => q,
+            _ => payload_buff,
+        };
+
+        //Convert to utf8
+        let query_str = String::from_utf8(query_buff.to_vec())?;
+
+        let statements = SqlParser::parse(&query_str).map_err(EngineError::ParseError)?;
+        if statements.is_empty() {
+            frames.push(NetworkFrame::empty_query_response());
+            return Ok(());
+        }
+
+        self.run_statements(statements.into(), frames).await
+    }
+
+    //Stops early at a COPY FROM STDIN, run_copy_in carries on once its rows have been sent
+    async fn run_statements(
+        &mut self,
+        mut statements: VecDeque<ParseTree>,
+        frames: &mut Vec<NetworkFrame>,
+    ) -> Result<(), ClientProcessorError> {
+        while let Some(statement) = statements.pop_front() {
+            match (statement, self.block) {
+                (ParseTree::Begin(begin), TransactionBlock::Idle) => {
+                    //Statements before the BEGIN in the same query join the block
+                    let tran_id = match self.implicit.take() {
+                        Some(t) => t,
+                        None if begin.read_only => {
+                            self.transaction_manager.start_snapshot().await?
+                        }
+                        None => self.transaction_manager.start_trans().await?,
+                    };
+                    self.block = TransactionBlock::Open(tran_id);
+                    frames.push(NetworkFrame::command_complete("BEGIN".to_string()));
+                }
+                (ParseTree::Begin(_), TransactionBlock::Open(_)) => {
+                    frames.push(NetworkFrame::command_complete("BEGIN".to_string()));
+                }
+                (ParseTree::Commit, block) => {
+                    let tag = match block {
+                        TransactionBlock::Open(tran_id) => {
+                            self.transaction_manager.commit_trans(tran_id).await?;
+                            "COMMIT"
+                        }
+                        TransactionBlock::Failed => "ROLLBACK",
+                        TransactionBlock::Idle => "COMMIT",
+                    };
+                    self.block = TransactionBlock::Idle;
+                    frames.push(NetworkFrame::command_complete(tag.to_string()));
+                }
+                (ParseTree::Rollback, block) => {
+                    if let TransactionBlock::Open(tran_id) = block {
+                        self.transaction_manager.abort_trans(tran_id).await?;
+                    }
+                    self.block = TransactionBlock::Idle;
+                    frames.push(NetworkFrame::command_complete("ROLLBACK".to_string()));
+                }
+                (_, TransactionBlock::Failed) => {
+                    return Err(ClientProcessorError::InFailedTransaction());
+                }
+                (ParseTree::Copy(command), _) => {
+                    let tran_id = self.current_transaction().await?;
+                    match command.target {
+                        CopyTarget::Stdin => {
+                            let (format, columns) =
+                                self.engine.copy_in_format(tran_id, &command).await?;
+                            frames
+                                .push(NetworkFrame::copy_in_response(format.is_binary(), columns)?);
+                            self.copy_in = Some(CopyIn {
+                                tran_id,
+                                command,
+                                data: BytesMut::new(),
+                                remaining: statements,
+                            });
+                            return Ok(());
+                        }
+                        CopyTarget::File(_) => {
+                            let rows = self.engine.copy_from_file(tran_id, command).await?;
+                            frames.push(NetworkFrame::command_complete(format!("COPY {}", rows)));
+                        }
+                        CopyTarget::Stdout => {
+                            let output = self.engine.copy_to(tran_id, command).await?;
+                            frames.push(NetworkFrame::copy_out_response(
+                                output.binary,
+                                output.columns,
+                            )?);
+                            for data in output.data {
+                                frames.push(NetworkFrame::new(b'd', data));
+                            }
+                            frames.push(NetworkFrame::new(b'c', Bytes::new()));
+                            frames.push(NetworkFrame::command_complete(format!(
+                                "COPY {}",
+                                output.rows
+                            )));
+                        }
+                    }
+                }
+                (statement, _) => {
+                    let tran_id = self.current_transaction().await?;
+                    self.run_statement(tran_id, statement, frames).await?;
+                }
+            }
         }
+
+        if let Some(tran_id) = self.implicit.take() {
+            self.transaction_manager.commit_trans(tran_id).await?;
+        }
+        Ok(())
+    }
+
+    //The block's transaction or the query's, started the first time it is needed
+    async fn current_transaction(&mut self) -> Result<TransactionId, ClientProcessorError> {
+        if let TransactionBlock::Open(tran_id) = self.block {
+            return Ok(tran_id);
+        }
+        match self.implicit {
+            Some(tran_id) => Ok(tran_id),
+            None => {
+                let tran_id = self.transaction_manager.start_trans().await?;
+                self.implicit = Some(tran_id);
+                Ok(tran_id)
+            }
+        }
+    }
+
+    async fn run_statement(
+        &mut self,
+        txid: TransactionId,
+        statement: ParseTree,
+        frames: &mut Vec<NetworkFrame>,
+    ) -> Result<(), ClientProcessorError> {
+        let command_tag = ClientProcessor::command_tag(&statement);
+        let returns_rows = matches!(
+            statement,
+            ParseTree::Repack(_)
+                | ParseTree::Select(_)
+                | ParseTree::Show(_)
+                | ParseTree::SlotFunction(_)
+                | ParseTree::Vacuum(_)
+        );
+        let sets_time_zone = matches!(&statement, ParseTree::Set(s)
+            if SessionSettings::canonical_name(&s.name).ok() == Some("TimeZone"));
+
+        let query_res = self.engine.process_statement(txid, statement).await?;
+
         let results_rows = query_res.rows.len();
-        if results_rows > 0 {
-            frames.append(&mut NetworkFrame::data_rows(query_res.rows)?);
+        if returns_rows {
+            frames.push(NetworkFrame::row_description(query_res.columns)?);
+            frames.append(&mut NetworkFrame::data_rows(
+                query_res.rows,
+                self.engine.settings(),
+            )?);
         }
 
-        frames.push(NetworkFrame::command_complete(format!(
-            "SELECT {}",
-            results_rows
-        )));
+        frames.push(NetworkFrame::command_complete(match command_tag {
+            CommandTag::Fixed(t) => t.to_string(),
+            CommandTag::Counted(t) => format!("{} {}", t, results_rows),
+        }));
 
-        frames.push(NetworkFrame::ready_for_query());
+        if sets_time_zone {
+            frames.push(NetworkFrame::parameter_status(
+                "TimeZone",
+                &self.engine.settings().time_zone.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
 
-        return Ok(frames);
+    fn command_tag(statement: &ParseTree) -> CommandTag {
+        match statement {
+            ParseTree::Begin(_) => CommandTag::Fixed("BEGIN"),
+            ParseTree::Checkpoint => CommandTag::Fixed("CHECKPOINT"),
+            ParseTree::Commit => CommandTag::Fixed("COMMIT"),
+            ParseTree::Copy(_) => CommandTag::Counted("COPY"),
+            ParseTree::CreateTable(_) => CommandTag::Fixed("CREATE TABLE"),
+            ParseTree::CreateType(_) => CommandTag::Fixed("CREATE TYPE"),
+            ParseTree::DropTable(_) => CommandTag::Fixed("DROP TABLE"),
+            ParseTree::Insert(_) => CommandTag::Counted("INSERT 0"),
+            ParseTree::Promote => CommandTag::Fixed("PROMOTE"),
+            ParseTree::Repack(_) => CommandTag::Fixed("REPACK"),
+            ParseTree::Rollback => CommandTag::Fixed("ROLLBACK"),
+            ParseTree::Select(_) => CommandTag::Counted("SELECT"),
+            ParseTree::Set(_) => CommandTag::Fixed("SET"),
+            ParseTree::Show(_) => CommandTag::Fixed("SHOW"),
+            ParseTree::SlotFunction(_) => CommandTag::Counted("SELECT"),
+            ParseTree::Vacuum(_) => CommandTag::Fixed("VACUUM"),
+        }
     }
+
+    /// The settings clients expect to be told about after authenticating. Only the time zone
+    /// can change, the rest describe the fixed way dates and intervals are written.
+    fn parameter_statuses(&self) -> Vec<NetworkFrame> {
+        vec![
+            NetworkFrame::parameter_status("DateStyle", "ISO, MDY"),
+            NetworkFrame::parameter_status("IntervalStyle", "postgres"),
+            NetworkFrame::parameter_status(
+                "TimeZone",
+                &self.engine.settings().time_zone.to_string(),
+            ),
+            NetworkFrame::parameter_status("integer_datetimes", "on"),
+        ]
+    }
+}
+
+///Command tags either are static or include the number of rows affected
+enum CommandTag {
+    Fixed(&'static str),
+    Counted(&'static str),
 }
 
 #[derive(Error, Debug)]
 pub enum ClientProcessorError {
     #[error("Malformed Startup Packet")]
     BadStartup(),
+    #[error("COPY from stdin failed: {0}")]
+    CopyFailed(String),
+    #[error("current transaction is aborted, commands ignored until end of transaction block")]
+    InFailedTransaction(),
+    #[error("Replication needs a data directory")]
+    NoWriteAheadLog(),
+    #[error(transparent)]
+    DataDirectoryError(#[from] DataDirectoryError),
     #[error(transparent)]
     EngineError(#[from] EngineError),
     #[error(transparent)]
+    IOManagerError(#[from] IOManagerError),
+    #[error(transparent)]
     NetworkFrameError(#[from] NetworkFrameError),
     #[error(transparent)]
     QueryNotUtf8(#[from] std::string::FromUtf8Error),
     #[error(transparent)]
     TransactionManagerError(#[from] TransactionManagerError),
 }
+
+impl ClientProcessorError {
+    pub fn pg_error_code(&self) -> PgErrorCodes {
+        match self {
+            ClientProcessorError::CopyFailed(_) => PgErrorCodes::QueryCanceled,
+            ClientProcessorError::DataDirectoryError(e) => e.pg_error_code(),
+            ClientProcessorError::EngineError(e) => e.pg_error_code(),
+            ClientProcessorError::IOManagerError(e) => e.pg_error_code(),
+            ClientProcessorError::InFailedTransaction() => PgErrorCodes::InFailedSqlTransaction,
+            ClientProcessorError::NoWriteAheadLog() => PgErrorCodes::FeatureNotSupported,
+            _ => PgErrorCodes::SystemError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::engine::io::IOManager;
+    use super::*;
+    use bytes::Bytes;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn get_processor() -> ClientProcessor {
+        let tm = TransactionManager::new();
+        let engine = Engine::new(IOManager::new(), tm.clone());
+        ClientProcessor::new(engine, tm)
+    }
+
+    fn simple_query(query: &'static [u8]) -> NetworkFrame {
+        NetworkFrame::new(b'Q', Bytes::from_static(query))
+    }
+
+    fn message_types(frames: &[NetworkFrame]) -> Vec<u8> {
+        frames.iter().map(|f| f.message_type).collect()
+    }
+
+    #[test]
+    fn test_empty_query() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(b"\0")))?;
+        assert_eq!(message_types(&frames), vec![b'I', b'Z']);
+
+        let frames = aw!(cp.process(simple_query(b" ; \0")))?;
+        assert_eq!(message_types(&frames), vec![b'I', b'Z']);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_statements() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(
+            b"create table foo (bar text); insert into foo values('baz'); select bar from foo;\0"
+        )))?;
+        assert_eq!(
+            message_types(&frames),
+            vec![b'C', b'C', b'T', b'D', b'C', b'Z']
+        );
+        assert_eq!(frames[0].payload, Bytes::from_static(b"CREATE TABLE\0"));
+        assert_eq!(frames[1].payload, Bytes::from_static(b"INSERT 0 1\0"));
+        assert_eq!(frames[4].payload, Bytes::from_static(b"SELECT 1\0"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_at_first_error() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(
+            b"create table foo (bar text); select baz from foo; insert into foo values('baz');\0"
+        )))?;
+        assert_eq!(message_types(&frames), vec![b'C', b'N', b'Z']);
+
+        //The implicit transaction was aborted so the table should not exist
+        let frames = aw!(cp.process(simple_query(b"select bar from foo\0")))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_codes() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(
+            b"create table foo (bar varchar(3)); insert into foo values('abcd');\0"
+        )))?;
+        assert_eq!(message_types(&frames), vec![b'C', b'N', b'Z']);
+        assert!(frames[1].payload.ends_with(b"C22001\0\0"));
+
+        let frames = aw!(cp.process(simple_query(b"select baz\0")))?;
+        assert!(frames[0].payload.ends_with(b"C58000\0\0"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_blocks() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(
+            b"create table foo (bar text); begin; insert into foo values('kept');\0"
+        )))?;
+        assert_eq!(message_types(&frames), vec![b'C', b'C', b'C', b'Z']);
+        assert_eq!(frames[3].payload, Bytes::from_static(b"T"));
+        let frames = aw!(cp.process(simple_query(b"commit\0")))?;
+        assert_eq!(frames[1].payload, Bytes::from_static(b"I"));
+
+        //A failed block refuses everything until it is ended, which rolls it back
+        aw!(cp.process(simple_query(b"begin; insert into foo values('lost')\0")))?;
+        let frames = aw!(cp.process(simple_query(b"select baz from foo\0")))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        assert_eq!(frames[1].payload, Bytes::from_static(b"E"));
+        let frames = aw!(cp.process(simple_query(b"select bar from foo\0")))?;
+        assert!(frames[0].payload.ends_with(b"C25P02\0\0"));
+        let frames = aw!(cp.process(simple_query(b"commit\0")))?;
+        assert_eq!(frames[0].payload, Bytes::from_static(b"ROLLBACK\0"));
+
+        //A read only block can't write
+        let frames = aw!(cp.process(simple_query(
+            b"begin read only; select bar from foo; insert into foo values('no')\0"
+        )))?;
+        assert_eq!(
+            message_types(&frames),
+            vec![b'C', b'T', b'D', b'C', b'N', b'Z']
+        );
+        assert!(frames[4].payload.ends_with(b"C25006\0\0"));
+        aw!(cp.process(simple_query(b"rollback\0")))?;
+
+        let frames = aw!(cp.process(simple_query(b"select bar from foo\0")))?;
+        assert_eq!(message_types(&frames), vec![b'T', b'D', b'C', b'Z']);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_in() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(
+            b"create table foo (bar text, baz integer); copy foo from stdin; select bar from foo\0"
+        )))?;
+        assert_eq!(message_types(&frames), vec![b'C', b'G']);
+        assert_eq!(frames[1].payload, Bytes::from_static(b"\0\0\x02\0\0\0\0"));
+        assert!(
+            aw!(cp.process(NetworkFrame::new(b'd', Bytes::from_static(b"a\t1\nb\t"))))?.is_empty()
+        );
+        aw!(cp.process(NetworkFrame::new(b'd', Bytes::from_static(b"\\N\n"))))?;
+        let frames = aw!(cp.process(NetworkFrame::new(b'c', Bytes::new())))?;
+        assert_eq!(
+            message_types(&frames),
+            vec![b'C', b'T', b'D', b'D', b'C', b'Z']
+        );
+        assert_eq!(frames[0].payload, Bytes::from_static(b"COPY 2\0"));
+
+        //Giving up loses the rows
+        aw!(cp.process(simple_query(b"copy foo (baz) from stdin\0")))?;
+        aw!(cp.process(NetworkFrame::new(b'd', Bytes::from_static(b"3\n"))))?;
+        let frames = aw!(cp.process(NetworkFrame::new(b'f', Bytes::from_static(b"oops\0"))))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        assert!(frames[0].payload.ends_with(b"C57014\0\0"));
+
+        let frames = aw!(cp.process(simple_query(b"copy foo (bar, baz) from stdin\0")))?;
+        assert_eq!(message_types(&frames), vec![b'G']);
+        aw!(cp.process(NetworkFrame::new(b'd', Bytes::from_static(b"c\n"))))?;
+        let frames = aw!(cp.process(NetworkFrame::new(b'c', Bytes::new())))?;
+        assert!(frames[0].payload.ends_with(b"C22P04\0\0"));
+
+        let frames = aw!(cp.process(simple_query(b"select bar from foo\0")))?;
+        assert_eq!(frames[3].payload, Bytes::from_static(b"SELECT 2\0"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_csv_and_out() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(simple_query(
+            b"create table foo (bar text, baz integer); copy foo from stdin (format csv, header)\0"
+        )))?;
+        assert_eq!(message_types(&frames), vec![b'C', b'G']);
+        aw!(cp.process(NetworkFrame::new(
+            b'd',
+            Bytes::from_static(b"bar,baz\n\"a,b\",1\n\"\",\n")
+        )))?;
+        let frames = aw!(cp.process(NetworkFrame::new(b'c', Bytes::new())))?;
+        assert_eq!(message_types(&frames), vec![b'C', b'Z']);
+        assert_eq!(frames[0].payload, Bytes::from_static(b"COPY 2\0"));
+
+        let frames = aw!(cp.process(simple_query(
+            b"copy foo (baz, bar) to stdout (format csv, null 'NULL')\0"
+        )))?;
+        assert_eq!(
+            message_types(&frames),
+            vec![b'H', b'd', b'd', b'c', b'C', b'Z']
+        );
+        assert_eq!(frames[0].payload, Bytes::from_static(b"\0\0\x02\0\0\0\0"));
+        assert_eq!(frames[1].payload, Bytes::from_static(b"1,\"a,b\"\n"));
+        assert_eq!(frames[2].payload, Bytes::from_static(b"NULL,\n"));
+        assert_eq!(frames[4].payload, Bytes::from_static(b"COPY 2\0"));
+
+        //Bad options are caught before any rows are asked for
+        let frames = aw!(cp.process(simple_query(b"copy foo from stdin (format xml)\0")))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        assert!(frames[0].payload.ends_with(b"C22023\0\0"));
+        let frames = aw!(cp.process(simple_query(b"copy pg_class from stdin\0")))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        assert!(frames[0].payload.ends_with(b"C42501\0\0"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replication_commands() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+        let start = b"START_REPLICATION PHYSICAL 0/0\0";
+
+        //Without asking for replication it is just bad SQL
+        let frames = aw!(cp.process(simple_query(start)))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+
+        aw!(cp.process(NetworkFrame::new(
+            0,
+            Bytes::from_static(b"\0\x03\0\0user\0postgres\0replication\0true\0\0")
+        )))?;
+        let frames = aw!(cp.process(simple_query(start)))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        assert!(frames[0].payload.ends_with(b"C0A000\0\0"));
+        assert_eq!(cp.take_replication_start(), None);
+
+        //Base backups are only sent over a replication connection, never written by the server
+        let frames = aw!(cp.process(simple_query(b"BASE_BACKUP\0")))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+        let frames = aw!(cp.process(simple_query(b"BASE_BACKUP '/tmp/copy'\0")))?;
+        assert_eq!(message_types(&frames), vec![b'N', b'Z']);
+
+        //Queries still work
+        let frames = aw!(cp.process(simple_query(b"show timezone\0")))?;
+        assert_eq!(message_types(&frames), vec![b'T', b'D', b'C', b'Z']);
+        Ok(())
+    }
+
+    #[test]
+    fn test_startup_parameters() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cp = get_processor();
+
+        let frames = aw!(cp.process(NetworkFrame::new(
+            0,
+            Bytes::from_static(b"\0\x03\0\0user\0postgres\0TimeZone\0Europe/Berlin\0\0")
+        )))?;
+        assert_eq!(
+            message_types(&frames),
+            vec![b'R', b'S', b'S', b'S', b'S', b'Z']
+        );
+        assert_eq!(
+            frames[3].payload,
+            Bytes::from_static(b"TimeZone\0Europe/Berlin\0")
+        );
+
+        let frames = aw!(cp.process(simple_query(
+            b"set time zone '-07'; show timezone; select '2021-01-01 12:00+00'::timestamptz\0"
+        )))?;
+        assert_eq!(
+            message_types(&frames),
+            vec![b'C', b'S', b'T', b'D', b'C', b'T', b'D', b'C', b'Z']
+        );
+        assert_eq!(frames[0].payload, Bytes::from_static(b"SET\0"));
+        assert_eq!(frames[1].payload, Bytes::from_static(b"TimeZone\0-07\0"));
+        assert_eq!(frames[4].payload, Bytes::from_static(b"SHOW\0"));
+        assert_eq!(
+            frames[6].payload,
+            Bytes::from_static(b"\0\x01\0\0\0\x162021-01-01 05:00:00-07")
+        );
+        Ok(())
+    }
+}
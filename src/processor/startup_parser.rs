@@ -68,10 +68,7 @@ mod tests {
     fn test_invalid_utf8_till_null() {
         let test_string = b"\xc3\x28\0";
 
-        match till_null(test_string) {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+        assert!(till_null(test_string).is_err());
     }
 
     #[test]
@@ -0,0 +1,41 @@
+//! Streaming physical replication, a standby follows a primary by replaying its write ahead log
+//! as it is written. See: https://www.postgresql.org/docs/current/warm-standby.html
+//!
+//! The standby connects like any client with replication=true in its startup message and sends
+//! START_REPLICATION, from then on the connection is a copy stream in both directions. The
+//! primary sends each record as it is flushed and the standby says how far it has got.
+//! See: https://www.postgresql.org/docs/current/protocol-replication.html
+//!
+//! Logical replication streams a slot's decoded transactions over the same protocol instead of
+//! the log itself, for consumers that want row changes rather than pages.
+//!
+//! A new standby starts from a base backup, which BASE_BACKUP sends over a replication
+//! connection too.
+
+mod base_backup_receiver;
+pub use base_backup_receiver::BaseBackupReceiver;
+pub use base_backup_receiver::BaseBackupReceiverError;
+
+mod logical_sender;
+pub use logical_sender::LogicalSender;
+pub use logical_sender::LogicalSenderError;
+
+mod pgoutput;
+pub use pgoutput::PgOutput;
+
+mod replication_command;
+pub use replication_command::format_lsn;
+pub use replication_command::parse_lsn;
+pub use replication_command::ReplicationCommand;
+
+mod replication_message;
+pub use replication_message::ReplicationMessage;
+
+mod wal_receiver;
+pub use wal_receiver::WalReceiver;
+pub use wal_receiver::WalReceiverError;
+
+mod wal_sender;
+pub use wal_sender::WalSender;
+pub use wal_sender::WalSenderError;
+pub use wal_sender::KEEPALIVE_INTERVAL;
@@ -0,0 +1,119 @@
+//! Takes a base backup of a running primary over a replication connection, like pg_basebackup.
+//!
+//! The primary sends each file of the backup as a BackupFile in a copy stream and then where the
+//! backup's log starts and stops. The files are written in the order they are sent, which puts
+//! the control file last, so a backup cut short is never mistaken for a finished one.
+use super::{parse_lsn, ReplicationMessage, WalReceiver};
+use crate::codec::{NetworkFrame, PgCodec};
+use crate::engine::io::{BaseBackup, DataDirectory, DataDirectoryError};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use std::convert::TryFrom;
+use std::path::Path;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+pub struct BaseBackupReceiver {
+    primary: String,
+}
+
+impl BaseBackupReceiver {
+    /// The primary is a host:port to connect to
+    pub fn new(primary: String) -> BaseBackupReceiver {
+        BaseBackupReceiver { primary }
+    }
+
+    /// Writes the backup to the target, which must be missing or empty
+    pub async fn run(&self, target: &Path) -> Result<BaseBackup, BaseBackupReceiverError> {
+        DataDirectory::create_backup_target(target).await?;
+        let stream = TcpStream::connect(&self.primary).await?;
+        let (mut sink, mut input) = Framed::new(stream, PgCodec {}).split();
+
+        sink.send(WalReceiver::startup_message()).await?;
+        loop {
+            match BaseBackupReceiver::next_frame(&mut input).await? {
+                NetworkFrame {
+                    message_type: b'Z', ..
+                } => break,
+                NetworkFrame {
+                    message_type: b'R' | b'S',
+                    ..
+                } => {}
+                f => return Err(BaseBackupReceiverError::Refused(f.payload)),
+            }
+        }
+
+        let mut query = BytesMut::new();
+        query.put(&b"BASE_BACKUP"[..]);
+        query.put_u8(b'\0');
+        sink.send(NetworkFrame::new(b'Q', query.freeze())).await?;
+        info!("Taking a base backup of {}", self.primary);
+
+        let mut backup = None;
+        loop {
+            let frame = BaseBackupReceiver::next_frame(&mut input).await?;
+            match frame.message_type {
+                b'd' => match ReplicationMessage::parse(frame.payload) {
+                    Some(ReplicationMessage::BackupFile { name, contents }) => {
+                        DataDirectory::write_backup_file(target, &name, &contents).await?;
+                    }
+                    _ => return Err(BaseBackupReceiverError::UnexpectedMessage(b'd')),
+                },
+                b'D' => backup = BaseBackupReceiver::parse_result(frame.payload),
+                b'E' | b'N' => return Err(BaseBackupReceiverError::Refused(frame.payload)),
+                b'H' | b'c' | b'T' | b'C' => {}
+                b'Z' => break,
+                t => return Err(BaseBackupReceiverError::UnexpectedMessage(t)),
+            }
+        }
+        backup.ok_or(BaseBackupReceiverError::UnexpectedMessage(b'Z'))
+    }
+
+    //The start and stop LSNs and the count of relation files, all as text
+    fn parse_result(mut payload: Bytes) -> Option<BaseBackup> {
+        if payload.remaining() < 2 || payload.get_u16() != 3 {
+            return None;
+        }
+        let mut values = vec![];
+        for _ in 0..3 {
+            if payload.remaining() < 4 {
+                return None;
+            }
+            let len = usize::try_from(payload.get_i32()).ok()?;
+            if payload.remaining() < len {
+                return None;
+            }
+            values.push(String::from_utf8(payload.split_to(len).to_vec()).ok()?);
+        }
+        Some(BaseBackup {
+            start_lsn: parse_lsn(&values[0])?,
+            stop_lsn: parse_lsn(&values[1])?,
+            files: values[2].parse().ok()?,
+        })
+    }
+
+    async fn next_frame<I>(input: &mut I) -> Result<NetworkFrame, BaseBackupReceiverError>
+    where
+        I: futures::Stream<Item = Result<NetworkFrame, std::io::Error>> + Unpin,
+    {
+        match input.next().await {
+            Some(f) => Ok(f?),
+            None => Err(BaseBackupReceiverError::Disconnected()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BaseBackupReceiverError {
+    #[error(transparent)]
+    DataDirectoryError(#[from] DataDirectoryError),
+    #[error("The primary closed the connection")]
+    Disconnected(),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("The primary refused the base backup: {0:?}")]
+    Refused(Bytes),
+    #[error("Got an unexpected message {0} from the primary")]
+    UnexpectedMessage(u8),
+}
@@ -0,0 +1,175 @@
+//! Streams a logical replication slot's decoded transactions to a consumer, like postgres'
+//! walsender does for START_REPLICATION SLOT ... LOGICAL.
+//!
+//! Transactions are sent as they commit in the format of the slot's plugin. The consumer's
+//! status replies say how far it has flushed, the slot is moved up to the last transaction it
+//! has so a reconnecting consumer carries on from there.
+use super::{PgOutput, ReplicationMessage, KEEPALIVE_INTERVAL};
+use crate::codec::NetworkFrame;
+use crate::engine::io::{
+    DataDirectory, DataDirectoryError, DecodedTransaction, LogicalDecoder, ReplicationSlot,
+    WriteAheadLogError,
+};
+use crate::engine::transactions::TransactionManager;
+use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::time::SystemTime;
+use thiserror::Error;
+
+pub struct LogicalSender {
+    directory: DataDirectory,
+    tran_manager: TransactionManager,
+}
+
+impl LogicalSender {
+    pub fn new(directory: DataDirectory, tran_manager: TransactionManager) -> LogicalSender {
+        LogicalSender {
+            directory,
+            tran_manager,
+        }
+    }
+
+    /// Streams the transactions that end after the LSN from a slot the caller has acquired until
+    /// the consumer ends the copy or disconnects. The slot is released either way.
+    pub async fn run<S, I>(
+        &mut self,
+        slot: ReplicationSlot,
+        start: u64,
+        sink: &mut S,
+        input: &mut I,
+    ) -> Result<(), LogicalSenderError>
+    where
+        S: Sink<NetworkFrame, Error = std::io::Error> + Unpin,
+        I: Stream<Item = Result<NetworkFrame, std::io::Error>> + Unpin,
+    {
+        let result = self.stream(&slot, start, sink, input).await;
+        self.directory.release_slot(&slot.name).await;
+        result
+    }
+
+    async fn stream<S, I>(
+        &mut self,
+        slot: &ReplicationSlot,
+        start: u64,
+        sink: &mut S,
+        input: &mut I,
+    ) -> Result<(), LogicalSenderError>
+    where
+        S: Sink<NetworkFrame, Error = std::io::Error> + Unpin,
+        I: Stream<Item = Result<NetworkFrame, std::io::Error>> + Unpin,
+    {
+        let wal = self.directory.wal().clone();
+        let mut flushed = wal.subscribe();
+        let mut decoder = LogicalDecoder::new(slot);
+        let mut output = PgOutput::new();
+        let skip_to = start.max(slot.confirmed_flush);
+        //The end and restart point of each transaction sent but not yet confirmed
+        let mut unconfirmed = VecDeque::new();
+        sink.send(NetworkFrame::copy_both_response()).await?;
+
+        loop {
+            let end = *flushed.borrow_and_update();
+            let decoded = decoder.decode(&wal, &mut self.tran_manager).await?;
+            for tran in decoded.iter().filter(|t| t.end_lsn > skip_to) {
+                unconfirmed.push_back((tran.end_lsn, tran.restart_lsn));
+                //Every transaction is logged, reads included, so only ones that changed rows
+                //are sent
+                if tran.changes.is_empty() {
+                    continue;
+                }
+                for (lsn, data) in LogicalSender::messages(&slot.plugin, &mut output, tran) {
+                    let message = ReplicationMessage::XLogData {
+                        start: lsn,
+                        end,
+                        sent: SystemTime::now(),
+                        data,
+                    };
+                    sink.feed(message.to_frame()).await?;
+                }
+            }
+            sink.flush().await?;
+
+            tokio::select! {
+                changed = flushed.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                }
+                frame = input.next() => {
+                    let frame = match frame {
+                        Some(f) => f?,
+                        None => return Ok(()),
+                    };
+                    match frame.message_type {
+                        b'd' => match ReplicationMessage::parse(frame.payload) {
+                            Some(ReplicationMessage::StandbyStatus { flush, .. }) => {
+                                self.confirm(&slot.name, flush, &mut unconfirmed).await?;
+                            }
+                            _ => return Err(LogicalSenderError::UnexpectedMessage(b'd')),
+                        },
+                        b'c' | b'X' => return Ok(()),
+                        t => return Err(LogicalSenderError::UnexpectedMessage(t)),
+                    }
+                }
+                _ = tokio::time::sleep(KEEPALIVE_INTERVAL) => {
+                    let keepalive = ReplicationMessage::Keepalive {
+                        end,
+                        sent: SystemTime::now(),
+                        reply_requested: false,
+                    };
+                    sink.send(keepalive.to_frame()).await?;
+                }
+            }
+        }
+    }
+
+    //Moves the slot past every transaction that ends by the flushed LSN
+    async fn confirm(
+        &self,
+        slot: &str,
+        flush: u64,
+        unconfirmed: &mut VecDeque<(u64, u64)>,
+    ) -> Result<(), LogicalSenderError> {
+        let mut confirmed = None;
+        while let Some((end, restart)) = unconfirmed.front() {
+            if *end > flush {
+                break;
+            }
+            confirmed = Some((*end, *restart));
+            unconfirmed.pop_front();
+        }
+        if let Some((end, restart)) = confirmed {
+            self.directory.advance_slot(slot, end, restart).await?;
+        }
+        Ok(())
+    }
+
+    //test_decoding sends each line as its own message
+    fn messages(
+        plugin: &str,
+        output: &mut PgOutput,
+        tran: &DecodedTransaction,
+    ) -> Vec<(u64, Bytes)> {
+        match plugin {
+            "test_decoding" => tran
+                .test_decoding()
+                .into_iter()
+                .map(|(lsn, line)| (lsn, Bytes::from(line)))
+                .collect(),
+            _ => output.encode(tran),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LogicalSenderError {
+    #[error(transparent)]
+    DataDirectoryError(#[from] DataDirectoryError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("Got an unexpected message {0} from the consumer")]
+    UnexpectedMessage(u8),
+    #[error(transparent)]
+    WriteAheadLogError(#[from] WriteAheadLogError),
+}
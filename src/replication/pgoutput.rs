@@ -0,0 +1,198 @@
+//! Writes decoded transactions as pgoutput, the logical replication protocol postgres subscribers
+//! and most change data capture tools read. Only version 1 of the protocol is spoken.
+//! Format here: https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+//!
+//! Every value is sent as text. Rows carry every column before and after they change, like a
+//! table with REPLICA IDENTITY FULL.
+use crate::engine::io::{ChangeColumn, DecodedTransaction, RowChange, RowChangeKind};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+///Postgres times are microseconds since 2000-01-01
+const PG_EPOCH_SECS: u64 = 946_684_800;
+
+/// Remembers the relations a consumer has been told about, each connection needs its own
+#[derive(Default)]
+pub struct PgOutput {
+    relations: HashMap<u32, Vec<ChangeColumn>>,
+}
+
+impl PgOutput {
+    pub fn new() -> PgOutput {
+        PgOutput::default()
+    }
+
+    /// The messages for the transaction with the LSN each is sent at. A relation is described
+    /// before its first change and again if its columns have changed since.
+    pub fn encode(&mut self, tran: &DecodedTransaction) -> Vec<(u64, Bytes)> {
+        let mut messages = vec![];
+
+        let mut begin = BytesMut::new();
+        begin.put_u8(b'B');
+        begin.put_u64(tran.commit_lsn);
+        begin.put_i64(PgOutput::to_pg_time(tran.committed));
+        begin.put_u32(tran.tran_id.get_u64() as u32);
+        messages.push((tran.begin_lsn, begin.freeze()));
+
+        for (lsn, change) in tran.changes.iter() {
+            let relid = PgOutput::relation_id(change);
+            if self.relations.get(&relid) != Some(&change.columns) {
+                messages.push((*lsn, PgOutput::relation(relid, change)));
+                self.relations.insert(relid, change.columns.clone());
+            }
+
+            let mut buffer = BytesMut::new();
+            match change.kind {
+                RowChangeKind::Insert => {
+                    buffer.put_u8(b'I');
+                    buffer.put_u32(relid);
+                    PgOutput::put_tuple(&mut buffer, b'N', &change.new);
+                }
+                RowChangeKind::Update => {
+                    buffer.put_u8(b'U');
+                    buffer.put_u32(relid);
+                    PgOutput::put_tuple(&mut buffer, b'O', &change.old);
+                    PgOutput::put_tuple(&mut buffer, b'N', &change.new);
+                }
+                RowChangeKind::Delete => {
+                    buffer.put_u8(b'D');
+                    buffer.put_u32(relid);
+                    PgOutput::put_tuple(&mut buffer, b'O', &change.old);
+                }
+            }
+            messages.push((*lsn, buffer.freeze()));
+        }
+
+        let mut commit = BytesMut::new();
+        commit.put_u8(b'C');
+        commit.put_u8(0);
+        commit.put_u64(tran.commit_lsn);
+        commit.put_u64(tran.end_lsn);
+        commit.put_i64(PgOutput::to_pg_time(tran.committed));
+        messages.push((tran.commit_lsn, commit.freeze()));
+
+        messages
+    }
+
+    /// Relations are uuids here, the protocol only has room for the first four bytes
+    pub fn relation_id(change: &RowChange) -> u32 {
+        let b = change.relation.as_bytes();
+        u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+    }
+
+    //Every column is part of the replica identity
+    fn relation(relid: u32, change: &RowChange) -> Bytes {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(b'R');
+        buffer.put_u32(relid);
+        PgOutput::put_string(&mut buffer, "public");
+        PgOutput::put_string(&mut buffer, &change.relation_name);
+        buffer.put_u8(b'f');
+        buffer.put_u16(change.columns.len() as u16);
+        for c in change.columns.iter() {
+            buffer.put_u8(1);
+            PgOutput::put_string(&mut buffer, &c.name);
+            buffer.put_u32(c.type_oid);
+            buffer.put_i32(-1);
+        }
+        buffer.freeze()
+    }
+
+    fn put_tuple(buffer: &mut BytesMut, kind: u8, row: &Option<Vec<Option<String>>>) {
+        let values = match row {
+            Some(v) => v,
+            None => return,
+        };
+        buffer.put_u8(kind);
+        buffer.put_u16(values.len() as u16);
+        for v in values {
+            match v {
+                Some(v) => {
+                    buffer.put_u8(b't');
+                    buffer.put_u32(v.len() as u32);
+                    buffer.put_slice(v.as_bytes());
+                }
+                None => buffer.put_u8(b'n'),
+            }
+        }
+    }
+
+    fn put_string(buffer: &mut BytesMut, value: &str) {
+        buffer.put_slice(value.as_bytes());
+        buffer.put_u8(0);
+    }
+
+    fn to_pg_time(time: SystemTime) -> i64 {
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(PG_EPOCH_SECS);
+        match time.duration_since(epoch) {
+            Ok(d) => d.as_micros() as i64,
+            Err(e) => -(e.duration().as_micros() as i64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::transactions::TransactionId;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_pgoutput_messages() {
+        let change = RowChange {
+            kind: RowChangeKind::Insert,
+            tran_id: TransactionId::new(5),
+            relation: Uuid::from_bytes([0, 0, 0, 9, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]),
+            relation_name: "people".to_string(),
+            columns: vec![ChangeColumn {
+                name: "name".to_string(),
+                type_name: "text".to_string(),
+                type_oid: 25,
+            }],
+            old: None,
+            new: Some(vec![Some("Bob".to_string())]),
+        };
+        let mut delete = change.clone();
+        delete.kind = RowChangeKind::Delete;
+        delete.old = Some(vec![None]);
+        delete.new = None;
+
+        let tran = DecodedTransaction {
+            tran_id: TransactionId::new(5),
+            begin_lsn: 0x10,
+            commit_lsn: 0x40,
+            end_lsn: 0x50,
+            committed: SystemTime::UNIX_EPOCH + Duration::from_secs(PG_EPOCH_SECS),
+            changes: vec![(0x20, change), (0x30, delete)],
+            restart_lsn: 0x50,
+        };
+
+        let mut output = PgOutput::new();
+        let messages = output.encode(&tran);
+        let lsns: Vec<u64> = messages.iter().map(|(l, _)| *l).collect();
+        assert_eq!(lsns, vec![0x10, 0x20, 0x20, 0x30, 0x40]);
+        assert_eq!(
+            messages[0].1,
+            Bytes::from_static(b"B\0\0\0\0\0\0\0\x40\0\0\0\0\0\0\0\0\0\0\0\x05")
+        );
+        assert_eq!(
+            messages[1].1,
+            Bytes::from_static(
+                b"R\0\0\0\x09public\0people\0f\0\x01\x01name\0\0\0\0\x19\xff\xff\xff\xff"
+            )
+        );
+        assert_eq!(
+            messages[2].1,
+            Bytes::from_static(b"I\0\0\0\x09N\0\x01t\0\0\0\x03Bob")
+        );
+        assert_eq!(messages[3].1, Bytes::from_static(b"D\0\0\0\x09O\0\x01n"));
+        assert_eq!(
+            messages[4].1,
+            Bytes::from_static(b"C\0\0\0\0\0\0\0\0\x40\0\0\0\0\0\0\0\x50\0\0\0\0\0\0\0\0")
+        );
+
+        //The relation was already sent
+        assert_eq!(output.encode(&tran).len(), 4);
+    }
+}
@@ -0,0 +1,268 @@
+//! The commands a replication connection sends instead of SQL, starting a stream, taking a base
+//! backup and managing logical replication slots. Format here: https://www.postgresql.org/docs/current/protocol-replication.html
+//!
+//! LSNs are written like postgres does, the high and low 32 bits in hex: 0/16B3748
+
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, tag_no_case, take_while1};
+use nom::character::complete::{digit1, hex_digit1, multispace0, multispace1};
+use nom::combinator::{all_consuming, map, map_res, opt};
+use nom::error::VerboseError;
+use nom::sequence::{delimited, preceded, terminated, tuple};
+use nom::IResult;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplicationCommand {
+    ///Where the log ends, what clients ask before streaming
+    IdentifySystem,
+    ///Send a copy of the data directory and the log it needs, options are accepted but ignored
+    BaseBackup,
+    ///Stream the log from the LSN on, the timeline is accepted but there is only ever one
+    StartReplication(u64),
+    ///Make a logical slot, snapshot options are accepted but there are no exported snapshots
+    CreateReplicationSlot { slot: String, plugin: String },
+    ///Remove a slot, WAIT is accepted but a slot in use is always an error
+    DropReplicationSlot(String),
+    ///Stream the slot's decoded transactions that end after the LSN, plugin options are
+    ///accepted but ignored
+    StartLogicalReplication { slot: String, start: u64 },
+}
+
+impl ReplicationCommand {
+    /// None if the query isn't a replication command, so it should be run as SQL
+    pub fn parse(query: &str) -> Option<ReplicationCommand> {
+        let (_, command) = all_consuming(terminated(
+            preceded(
+                multispace0,
+                alt((
+                    map(tag_no_case("identify_system"), |_| {
+                        ReplicationCommand::IdentifySystem
+                    }),
+                    map(
+                        tuple((
+                            tag_no_case("base_backup"),
+                            opt(preceded(multispace0, options)),
+                        )),
+                        |_| ReplicationCommand::BaseBackup,
+                    ),
+                    parse_start_logical_replication,
+                    map(
+                        parse_start_replication,
+                        ReplicationCommand::StartReplication,
+                    ),
+                    parse_create_replication_slot,
+                    parse_drop_replication_slot,
+                )),
+            ),
+            tuple((multispace0, opt(tag(";")), multispace0)),
+        ))(query)
+        .ok()?;
+        Some(command)
+    }
+}
+
+/// Writes the LSN the way postgres does
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+pub fn parse_lsn(input: &str) -> Option<u64> {
+    all_consuming(lsn)(input).ok().map(|(_, l)| l)
+}
+
+fn parse_start_replication(input: &str) -> IResult<&str, u64, VerboseError<&str>> {
+    let (input, (_, _, _, start, _)) = tuple((
+        tag_no_case("start_replication"),
+        multispace1,
+        opt(terminated(tag_no_case("physical"), multispace1)),
+        lsn,
+        opt(preceded(
+            tuple((multispace1, tag_no_case("timeline"), multispace1)),
+            digit1,
+        )),
+    ))(input)?;
+    Ok((input, start))
+}
+
+fn parse_start_logical_replication(
+    input: &str,
+) -> IResult<&str, ReplicationCommand, VerboseError<&str>> {
+    let (input, (_, _, _, _, slot, _, _, _, start, _)) = tuple((
+        tag_no_case("start_replication"),
+        multispace1,
+        tag_no_case("slot"),
+        multispace1,
+        name,
+        multispace1,
+        tag_no_case("logical"),
+        multispace1,
+        lsn,
+        opt(preceded(multispace0, options)),
+    ))(input)?;
+    Ok((
+        input,
+        ReplicationCommand::StartLogicalReplication { slot, start },
+    ))
+}
+
+fn parse_create_replication_slot(
+    input: &str,
+) -> IResult<&str, ReplicationCommand, VerboseError<&str>> {
+    let (input, (_, _, slot, _, _, _, plugin, _)) = tuple((
+        tag_no_case("create_replication_slot"),
+        multispace1,
+        name,
+        multispace1,
+        tag_no_case("logical"),
+        multispace1,
+        name,
+        opt(preceded(
+            multispace1,
+            alt((
+                options,
+                tag_no_case("export_snapshot"),
+                tag_no_case("noexport_snapshot"),
+                tag_no_case("use_snapshot"),
+            )),
+        )),
+    ))(input)?;
+    Ok((
+        input,
+        ReplicationCommand::CreateReplicationSlot { slot, plugin },
+    ))
+}
+
+fn parse_drop_replication_slot(
+    input: &str,
+) -> IResult<&str, ReplicationCommand, VerboseError<&str>> {
+    let (input, (_, _, slot, _)) = tuple((
+        tag_no_case("drop_replication_slot"),
+        multispace1,
+        name,
+        opt(preceded(multispace1, tag_no_case("wait"))),
+    ))(input)?;
+    Ok((input, ReplicationCommand::DropReplicationSlot(slot)))
+}
+
+//Unquoted names are folded to lower case like any identifier
+fn name(input: &str) -> IResult<&str, String, VerboseError<&str>> {
+    alt((
+        map(delimited(tag("\""), is_not("\""), tag("\"")), |n: &str| {
+            n.to_string()
+        }),
+        map(
+            take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+            |n: &str| n.to_lowercase(),
+        ),
+    ))(input)
+}
+
+fn options(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    delimited(tag("("), is_not(")"), tag(")"))(input)
+}
+
+fn lsn(input: &str) -> IResult<&str, u64, VerboseError<&str>> {
+    let (input, (high, _, low)) = tuple((half_lsn, tag("/"), half_lsn))(input)?;
+    Ok((input, (high << 32) | low))
+}
+
+fn half_lsn(input: &str) -> IResult<&str, u64, VerboseError<&str>> {
+    map_res(hex_digit1, |h| u32::from_str_radix(h, 16).map(u64::from))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lsn_roundtrip() {
+        for lsn in [0, 0x16B3748, 0x1_0000_0000, u64::MAX] {
+            assert_eq!(parse_lsn(&format_lsn(lsn)), Some(lsn));
+        }
+        assert_eq!(format_lsn(0x3_0000_1000), "3/1000");
+        assert_eq!(parse_lsn("3/1000"), Some(0x3_0000_1000));
+        assert_eq!(parse_lsn("1000"), None);
+        assert_eq!(parse_lsn("1/100000000"), None);
+    }
+
+    #[test]
+    fn test_start_replication_parser() {
+        assert_eq!(
+            ReplicationCommand::parse("START_REPLICATION PHYSICAL 0/3000000"),
+            Some(ReplicationCommand::StartReplication(0x300_0000))
+        );
+        assert_eq!(
+            ReplicationCommand::parse("start_replication 1/A TIMELINE 1;"),
+            Some(ReplicationCommand::StartReplication(0x1_0000_000A))
+        );
+        assert_eq!(ReplicationCommand::parse("START_REPLICATION"), None);
+        assert_eq!(ReplicationCommand::parse("select 1"), None);
+    }
+
+    #[test]
+    fn test_logical_replication_parser() {
+        assert_eq!(
+            ReplicationCommand::parse(
+                "CREATE_REPLICATION_SLOT Indexer LOGICAL pgoutput NOEXPORT_SNAPSHOT"
+            ),
+            Some(ReplicationCommand::CreateReplicationSlot {
+                slot: "indexer".to_string(),
+                plugin: "pgoutput".to_string()
+            })
+        );
+        assert_eq!(
+            ReplicationCommand::parse(
+                "create_replication_slot indexer logical test_decoding (SNAPSHOT 'nothing');"
+            ),
+            Some(ReplicationCommand::CreateReplicationSlot {
+                slot: "indexer".to_string(),
+                plugin: "test_decoding".to_string()
+            })
+        );
+        assert_eq!(
+            ReplicationCommand::parse("DROP_REPLICATION_SLOT indexer WAIT"),
+            Some(ReplicationCommand::DropReplicationSlot(
+                "indexer".to_string()
+            ))
+        );
+        assert_eq!(
+            ReplicationCommand::parse(
+                "START_REPLICATION SLOT indexer LOGICAL 0/16B3748 (proto_version '1', publication_names 'everything')"
+            ),
+            Some(ReplicationCommand::StartLogicalReplication {
+                slot: "indexer".to_string(),
+                start: 0x16B3748
+            })
+        );
+        assert_eq!(
+            ReplicationCommand::parse(
+                "START_REPLICATION SLOT \"indexer\" LOGICAL 0/0 (\"proto_version\" '1')"
+            ),
+            Some(ReplicationCommand::StartLogicalReplication {
+                slot: "indexer".to_string(),
+                start: 0
+            })
+        );
+        assert_eq!(
+            ReplicationCommand::parse("identify_system;"),
+            Some(ReplicationCommand::IdentifySystem)
+        );
+        assert_eq!(
+            ReplicationCommand::parse("START_REPLICATION SLOT indexer 0/0"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_base_backup_parser() {
+        assert_eq!(
+            ReplicationCommand::parse("BASE_BACKUP"),
+            Some(ReplicationCommand::BaseBackup)
+        );
+        assert_eq!(
+            ReplicationCommand::parse("base_backup (LABEL 'monday', CHECKPOINT 'fast');"),
+            Some(ReplicationCommand::BaseBackup)
+        );
+        assert_eq!(ReplicationCommand::parse("BASE_BACKUP '/tmp/copy'"), None);
+    }
+}
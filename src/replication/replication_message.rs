@@ -0,0 +1,236 @@
+//! The messages inside the copy stream of a replication connection, each one is the payload of a
+//! CopyData ('d') frame. Format here: https://www.postgresql.org/docs/current/protocol-replication.html
+//!
+//! Where postgres sends raw log bytes physical replication's XLogData carries a single WalEntry as
+//! it is stored, since records never cross segments the standby can write it at its LSN as is.
+//! Logical replication sends a pgoutput message instead. A base backup is sent as a BackupFile
+//! for each file rather than postgres' tar archive.
+use crate::codec::NetworkFrame;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::time::{Duration, SystemTime};
+
+///Postgres times are microseconds since 2000-01-01
+const PG_EPOCH_SECS: u64 = 946_684_800;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplicationMessage {
+    ///Sent by the primary, data from the LSN on and how far its log has been flushed
+    XLogData {
+        start: u64,
+        end: u64,
+        sent: SystemTime,
+        data: Bytes,
+    },
+    ///Sent by the primary when it has nothing else to send
+    Keepalive {
+        end: u64,
+        sent: SystemTime,
+        reply_requested: bool,
+    },
+    ///Sent by the standby, the LSNs are just past what it has written, flushed and replayed
+    StandbyStatus {
+        write: u64,
+        flush: u64,
+        apply: u64,
+        sent: SystemTime,
+        reply_requested: bool,
+    },
+    ///Sent by BASE_BACKUP, the name is relative to the data directory with / between its parts
+    BackupFile { name: String, contents: Bytes },
+}
+
+impl ReplicationMessage {
+    pub fn serialize(&self) -> Bytes {
+        let mut buffer = BytesMut::new();
+        match self {
+            ReplicationMessage::XLogData {
+                start,
+                end,
+                sent,
+                data,
+            } => {
+                buffer.put_u8(b'w');
+                buffer.put_u64(*start);
+                buffer.put_u64(*end);
+                buffer.put_i64(ReplicationMessage::to_pg_time(*sent));
+                buffer.put_slice(data);
+            }
+            ReplicationMessage::Keepalive {
+                end,
+                sent,
+                reply_requested,
+            } => {
+                buffer.put_u8(b'k');
+                buffer.put_u64(*end);
+                buffer.put_i64(ReplicationMessage::to_pg_time(*sent));
+                buffer.put_u8(*reply_requested as u8);
+            }
+            ReplicationMessage::StandbyStatus {
+                write,
+                flush,
+                apply,
+                sent,
+                reply_requested,
+            } => {
+                buffer.put_u8(b'r');
+                buffer.put_u64(*write);
+                buffer.put_u64(*flush);
+                buffer.put_u64(*apply);
+                buffer.put_i64(ReplicationMessage::to_pg_time(*sent));
+                buffer.put_u8(*reply_requested as u8);
+            }
+            ReplicationMessage::BackupFile { name, contents } => {
+                buffer.put_u8(b'f');
+                buffer.put_slice(name.as_bytes());
+                buffer.put_u8(b'\0');
+                buffer.put_slice(contents);
+            }
+        }
+        buffer.freeze()
+    }
+
+    /// None if the message is malformed or one this server doesn't use
+    pub fn parse(mut buffer: Bytes) -> Option<ReplicationMessage> {
+        if !buffer.has_remaining() {
+            return None;
+        }
+        match buffer.get_u8() {
+            b'w' => {
+                if buffer.remaining() < 24 {
+                    return None;
+                }
+                Some(ReplicationMessage::XLogData {
+                    start: buffer.get_u64(),
+                    end: buffer.get_u64(),
+                    sent: ReplicationMessage::from_pg_time(buffer.get_i64()),
+                    data: buffer,
+                })
+            }
+            b'k' => {
+                if buffer.remaining() != 17 {
+                    return None;
+                }
+                Some(ReplicationMessage::Keepalive {
+                    end: buffer.get_u64(),
+                    sent: ReplicationMessage::from_pg_time(buffer.get_i64()),
+                    reply_requested: buffer.get_u8() != 0,
+                })
+            }
+            b'r' => {
+                if buffer.remaining() != 33 {
+                    return None;
+                }
+                Some(ReplicationMessage::StandbyStatus {
+                    write: buffer.get_u64(),
+                    flush: buffer.get_u64(),
+                    apply: buffer.get_u64(),
+                    sent: ReplicationMessage::from_pg_time(buffer.get_i64()),
+                    reply_requested: buffer.get_u8() != 0,
+                })
+            }
+            b'f' => {
+                let end = buffer.iter().position(|b| *b == b'\0')?;
+                let name = String::from_utf8(buffer.split_to(end).to_vec()).ok()?;
+                buffer.advance(1);
+                Some(ReplicationMessage::BackupFile {
+                    name,
+                    contents: buffer,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Wrapped in a CopyData frame
+    pub fn to_frame(&self) -> NetworkFrame {
+        NetworkFrame::new(b'd', self.serialize())
+    }
+
+    fn to_pg_time(time: SystemTime) -> i64 {
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(PG_EPOCH_SECS);
+        match time.duration_since(epoch) {
+            Ok(d) => d.as_micros() as i64,
+            Err(e) => -(e.duration().as_micros() as i64),
+        }
+    }
+
+    fn from_pg_time(micros: i64) -> SystemTime {
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(PG_EPOCH_SECS);
+        if micros >= 0 {
+            epoch + Duration::from_micros(micros as u64)
+        } else {
+            epoch - Duration::from_micros(micros.unsigned_abs())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::io::{WalRecord, WriteAheadLog};
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_replication_message_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = tempfile::tempdir()?;
+        let wal = aw!(WriteAheadLog::create(tmp.path()))?;
+        aw!(wal.append(&WalRecord::BackupStart))?;
+        aw!(wal.append(&WalRecord::BackupEnd { start: 0 }))?;
+        aw!(wal.flush())?;
+        let entry = aw!(wal.read_since(0))?.remove(1);
+
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_micros(1_634_567_890_123_456);
+        let messages = vec![
+            ReplicationMessage::XLogData {
+                start: entry.lsn,
+                end: entry.end(),
+                sent,
+                data: entry.serialize(),
+            },
+            ReplicationMessage::Keepalive {
+                end: 42,
+                sent,
+                reply_requested: true,
+            },
+            ReplicationMessage::StandbyStatus {
+                write: 1,
+                flush: 2,
+                apply: 3,
+                sent,
+                reply_requested: false,
+            },
+            ReplicationMessage::BackupFile {
+                name: "base/1234".to_string(),
+                contents: Bytes::from_static(b"\0page\0"),
+            },
+        ];
+        for m in messages {
+            assert_eq!(ReplicationMessage::parse(m.serialize()), Some(m));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_keepalive_format() {
+        let keepalive = ReplicationMessage::Keepalive {
+            end: 0x1000,
+            sent: SystemTime::UNIX_EPOCH + Duration::from_secs(PG_EPOCH_SECS + 1),
+            reply_requested: false,
+        };
+        assert_eq!(
+            keepalive.serialize(),
+            Bytes::from_static(b"k\0\0\0\0\0\0\x10\0\0\0\0\0\0\x0f\x42\x40\0")
+        );
+        assert_eq!(ReplicationMessage::parse(Bytes::from_static(b"k\0")), None);
+        assert_eq!(
+            ReplicationMessage::parse(Bytes::from_static(b"fno end")),
+            None
+        );
+        assert_eq!(ReplicationMessage::parse(Bytes::new()), None);
+    }
+}
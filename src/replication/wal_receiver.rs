@@ -0,0 +1,203 @@
+//! Follows a primary from a hot standby, like postgres' walreceiver.
+//!
+//! Each record is written to the standby's own log at the same LSN and replayed, so the standby
+//! can be restarted, promoted or followed by another standby. Transaction statuses are also
+//! given to the transaction manager so new snapshots see them. A lost connection is retried
+//! until the standby is promoted.
+use super::{format_lsn, ReplicationMessage};
+use crate::codec::{NetworkFrame, PgCodec};
+use crate::engine::io::{IOManager, IOManagerError, WalEntry, WalRecord};
+use crate::engine::transactions::{TransactionManager, TransactionManagerError};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio_util::codec::Framed;
+
+///How long to wait before connecting to the primary again
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct WalReceiver {
+    primary: String,
+    io_manager: IOManager,
+    tran_manager: TransactionManager,
+}
+
+impl WalReceiver {
+    /// The primary is a host:port to connect to
+    pub fn new(
+        primary: String,
+        io_manager: IOManager,
+        tran_manager: TransactionManager,
+    ) -> WalReceiver {
+        WalReceiver {
+            primary,
+            io_manager,
+            tran_manager,
+        }
+    }
+
+    /// Streams until the standby is promoted
+    pub async fn run(self) {
+        while self.io_manager.in_recovery() {
+            match self.stream().await {
+                Ok(()) => {}
+                Err(e) => {
+                    if !self.io_manager.in_recovery() {
+                        break;
+                    }
+                    warn!("Lost replication from {}: {}", self.primary, e);
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        }
+        info!("Stopped replicating from {}", self.primary);
+    }
+
+    /// Connects and replays from the end of the standby's log, returns once promoted
+    pub async fn stream(&self) -> Result<(), WalReceiverError> {
+        let wal = self
+            .io_manager
+            .directory()
+            .ok_or(WalReceiverError::IOManagerError(
+                IOManagerError::NoDataDirectory(),
+            ))?
+            .wal();
+        let stream = TcpStream::connect(&self.primary).await?;
+        let (mut sink, mut input) = Framed::new(stream, PgCodec {}).split();
+
+        sink.send(WalReceiver::startup_message()).await?;
+        loop {
+            match WalReceiver::next_frame(&mut input).await? {
+                NetworkFrame {
+                    message_type: b'Z', ..
+                } => break,
+                NetworkFrame {
+                    message_type: b'R' | b'S',
+                    ..
+                } => {}
+                f => return Err(WalReceiverError::Refused(f.payload)),
+            }
+        }
+
+        let start = wal.end_lsn().await;
+        let mut query = BytesMut::new();
+        query.put(format!("START_REPLICATION PHYSICAL {}", format_lsn(start)).as_bytes());
+        query.put_u8(b'\0');
+        sink.send(NetworkFrame::new(b'Q', query.freeze())).await?;
+        let response = WalReceiver::next_frame(&mut input).await?;
+        if response.message_type != b'W' {
+            return Err(WalReceiverError::Refused(response.payload));
+        }
+        info!(
+            "Replicating from {} starting at {}",
+            self.primary,
+            format_lsn(start)
+        );
+
+        let mut recovery = self.io_manager.watch_recovery();
+        loop {
+            let frame = tokio::select! {
+                _ = WalReceiver::promoted(&mut recovery) => {
+                    sink.send(NetworkFrame::new(b'c', Bytes::new())).await?;
+                    return Ok(());
+                }
+                frame = WalReceiver::next_frame(&mut input) => frame?,
+            };
+            if frame.message_type != b'd' {
+                return Err(WalReceiverError::UnexpectedMessage(frame.message_type));
+            }
+
+            let reply_requested = match ReplicationMessage::parse(frame.payload) {
+                Some(ReplicationMessage::XLogData {
+                    start, mut data, ..
+                }) => {
+                    let entry = match WalEntry::parse(&mut data) {
+                        Some(e) if e.lsn == start && !data.has_remaining() => e,
+                        _ => return Err(WalReceiverError::UnexpectedMessage(b'w')),
+                    };
+                    self.io_manager.replay(&entry).await?;
+                    if let WalRecord::TransactionStatus { tran_id, status } = entry.record {
+                        self.tran_manager.replay_status(tran_id, status).await?;
+                    }
+                    true
+                }
+                Some(ReplicationMessage::Keepalive {
+                    reply_requested, ..
+                }) => reply_requested,
+                _ => return Err(WalReceiverError::UnexpectedMessage(b'd')),
+            };
+
+            if reply_requested {
+                let end = wal.end_lsn().await;
+                let status = ReplicationMessage::StandbyStatus {
+                    write: end,
+                    flush: end,
+                    apply: end,
+                    sent: SystemTime::now(),
+                    reply_requested: false,
+                };
+                sink.send(status.to_frame()).await?;
+            }
+        }
+    }
+
+    async fn promoted(recovery: &mut watch::Receiver<bool>) {
+        while WalReceiver::in_recovery(recovery) {
+            if recovery.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    //The borrow can't be held across an await
+    fn in_recovery(recovery: &watch::Receiver<bool>) -> bool {
+        *recovery.borrow()
+    }
+
+    async fn next_frame<I>(input: &mut I) -> Result<NetworkFrame, WalReceiverError>
+    where
+        I: futures::Stream<Item = Result<NetworkFrame, std::io::Error>> + Unpin,
+    {
+        match input.next().await {
+            Some(f) => Ok(f?),
+            None => Err(WalReceiverError::Disconnected()),
+        }
+    }
+
+    //Protocol 3.0 asking for a replication connection, written raw so it has its own length
+    pub(super) fn startup_message() -> NetworkFrame {
+        let mut params = BytesMut::new();
+        params.put_u32(196608);
+        for (key, value) in [("user", "feophant"), ("replication", "true")] {
+            params.put(key.as_bytes());
+            params.put_u8(b'\0');
+            params.put(value.as_bytes());
+            params.put_u8(b'\0');
+        }
+        params.put_u8(b'\0');
+
+        let mut buffer = BytesMut::new();
+        buffer.put_u32(params.len() as u32 + 4);
+        buffer.put(params);
+        NetworkFrame::new(0, buffer.freeze())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WalReceiverError {
+    #[error("The primary closed the connection")]
+    Disconnected(),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    IOManagerError(#[from] IOManagerError),
+    #[error("The primary refused to replicate: {0:?}")]
+    Refused(Bytes),
+    #[error(transparent)]
+    TransactionManagerError(#[from] TransactionManagerError),
+    #[error("Got an unexpected message {0} from the primary")]
+    UnexpectedMessage(u8),
+}
@@ -0,0 +1,100 @@
+//! Streams a primary's write ahead log to a standby, like postgres' walsender.
+//!
+//! Records are only sent once they are flushed so a standby never gets ahead of what the primary
+//! would have after a crash. Between records the sender waits for the next flush, the standby's
+//! status replies or for it to be quiet long enough to need a keepalive.
+use super::ReplicationMessage;
+use crate::codec::NetworkFrame;
+use crate::engine::io::{WriteAheadLog, WriteAheadLogError};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+///How long the sender is quiet before telling the standby it is still there
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct WalSender {
+    wal: WriteAheadLog,
+}
+
+impl WalSender {
+    pub fn new(wal: WriteAheadLog) -> WalSender {
+        WalSender { wal }
+    }
+
+    /// Answers START_REPLICATION and streams from the LSN on until the standby ends the copy or
+    /// disconnects
+    pub async fn run<S, I>(
+        &self,
+        start: u64,
+        sink: &mut S,
+        input: &mut I,
+    ) -> Result<(), WalSenderError>
+    where
+        S: Sink<NetworkFrame, Error = std::io::Error> + Unpin,
+        I: Stream<Item = Result<NetworkFrame, std::io::Error>> + Unpin,
+    {
+        let mut flushed = self.wal.subscribe();
+        let mut sent = start;
+        sink.send(NetworkFrame::copy_both_response()).await?;
+
+        loop {
+            let end = *flushed.borrow_and_update();
+            if sent < end {
+                for entry in self.wal.read_since(sent).await? {
+                    sent = entry.end();
+                    let message = ReplicationMessage::XLogData {
+                        start: entry.lsn,
+                        end,
+                        sent: SystemTime::now(),
+                        data: entry.serialize(),
+                    };
+                    sink.feed(message.to_frame()).await?;
+                }
+                sink.flush().await?;
+            }
+
+            tokio::select! {
+                changed = flushed.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                }
+                frame = input.next() => {
+                    let frame = match frame {
+                        Some(f) => f?,
+                        None => return Ok(()),
+                    };
+                    match frame.message_type {
+                        b'd' => match ReplicationMessage::parse(frame.payload) {
+                            Some(ReplicationMessage::StandbyStatus { apply, .. }) => {
+                                debug!("Standby has replayed up to {}", apply);
+                            }
+                            _ => return Err(WalSenderError::UnexpectedMessage(b'd')),
+                        },
+                        b'c' | b'X' => return Ok(()),
+                        t => return Err(WalSenderError::UnexpectedMessage(t)),
+                    }
+                }
+                _ = tokio::time::sleep(KEEPALIVE_INTERVAL) => {
+                    let keepalive = ReplicationMessage::Keepalive {
+                        end,
+                        sent: SystemTime::now(),
+                        reply_requested: false,
+                    };
+                    sink.send(keepalive.to_frame()).await?;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WalSenderError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("Got an unexpected message {0} from the standby")]
+    UnexpectedMessage(u8),
+    #[error(transparent)]
+    WriteAheadLogError(#[from] WriteAheadLogError),
+}
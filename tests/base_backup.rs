@@ -0,0 +1,161 @@
+mod common;
+
+use common::{run, serve_engine, start};
+use feophantlib::{
+    engine::{
+        io::{
+            page_formats::PageSize, verify_checksums, ControlFile, DataDirectory,
+            DataDirectoryError, IOManager, IOManagerError, WriteAheadLog,
+        },
+        transactions::TransactionManager,
+        Engine,
+    },
+    replication::{BaseBackupReceiver, BaseBackupReceiverError},
+};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio::runtime::Runtime;
+
+fn count(engine: &mut Engine, tm: &mut TransactionManager) -> usize {
+    run(engine, tm, "select value from busy").unwrap()[0]
+        .rows
+        .len()
+}
+
+#[test]
+fn backup_while_writing() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let data = tmp.path().join("data");
+    let backup = tmp.path().join("backup");
+    let rt = Runtime::new()?;
+
+    let io_manager = aw!(IOManager::initdb_directory(
+        &data,
+        ControlFile::new(PageSize::Kb4).with_checksums()
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    let address = serve_engine(&rt, engine.clone(), tm.clone())?;
+    run(&mut engine, &mut tm, "create table busy (value text)")?;
+    for _ in 0..20 {
+        run(&mut engine, &mut tm, "insert into busy values('before')")?;
+    }
+
+    //Keeps writing for the whole backup
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let stop = stop.clone();
+        let mut tm = tm.clone();
+        let mut engine = Engine::new(io_manager.clone(), tm.clone());
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                run(&mut engine, &mut tm, "insert into busy values('during')").unwrap();
+            }
+        })
+    };
+    let backed_up = rt.block_on(BaseBackupReceiver::new(address).run(&backup))?;
+    stop.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+
+    assert!(backed_up.start_lsn < backed_up.stop_lsn);
+    assert!(backed_up.files > 0);
+    assert!(backup.join("backup_label").exists());
+    let total = count(&mut engine, &mut tm);
+    aw!(io_manager.shutdown())?;
+
+    //The copy redoes the log up to the end of the backup and is then consistent
+    let restored = aw!(IOManager::open(&backup))?;
+    assert!(!backup.join("backup_label").exists());
+    assert_eq!(aw!(verify_checksums(&restored))?, vec![]);
+    let (mut tm, mut engine) = start(&restored);
+    let copied = count(&mut engine, &mut tm);
+    assert!(copied >= 20);
+    assert!(copied <= total);
+
+    run(&mut engine, &mut tm, "insert into busy values('after')")?;
+    assert_eq!(count(&mut engine, &mut tm), copied + 1);
+    Ok(())
+}
+
+#[test]
+fn backup_needs_its_log() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let data = tmp.path().join("data");
+    let backup = tmp.path().join("backup");
+    let rt = Runtime::new()?;
+
+    let io_manager = aw!(IOManager::initdb_directory(
+        &data,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    run(&mut engine, &mut tm, "create table busy (value text)")?;
+    let address = serve_engine(&rt, engine.clone(), tm.clone())?;
+    let receiver = BaseBackupReceiver::new(address);
+    let backed_up = rt.block_on(receiver.run(&backup))?;
+    assert!(matches!(
+        rt.block_on(receiver.run(&backup)),
+        Err(BaseBackupReceiverError::DataDirectoryError(
+            DataDirectoryError::NotEmpty(_)
+        ))
+    ));
+
+    //Without the segment holding the backup's end record the copy can't be trusted
+    let wal = backup.join("pg_wal");
+    for segment in aw!(WriteAheadLog::segments(&wal))? {
+        fs::remove_file(WriteAheadLog::segment_path(&wal, segment))?;
+    }
+    assert!(matches!(
+        aw!(DataDirectory::open(&backup, None)),
+        Err(DataDirectoryError::BackupIncomplete(s)) if s == backed_up.start_lsn
+    ));
+
+    assert!(matches!(
+        aw!(IOManager::new().base_backup()),
+        Err(IOManagerError::NoDataDirectory())
+    ));
+    Ok(())
+}
+
+#[test]
+fn backup_only_over_replication() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("backup");
+    let io_manager = aw!(IOManager::initdb_directory(
+        &tmp.path().join("data"),
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+
+    //No client can have the server write a copy somewhere
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        &format!("base_backup '{}'", target.display())
+    )
+    .is_err());
+    assert!(!target.exists());
+
+    //Nor can a primary have the receiver write outside the target
+    for name in [
+        "../escaped",
+        "/etc/escaped",
+        "base/../../escaped",
+        "base//x",
+        ".",
+    ] {
+        assert!(matches!(
+            aw!(DataDirectory::write_backup_file(&target, name, b"nope")),
+            Err(DataDirectoryError::BadBackupFile(_))
+        ));
+    }
+    assert!(!tmp.path().join("escaped").exists());
+    aw!(DataDirectory::write_backup_file(
+        &target,
+        "base/kept",
+        b"yes"
+    ))?;
+    assert_eq!(fs::read(target.join("base").join("kept"))?, b"yes");
+    Ok(())
+}
@@ -1,4 +1,16 @@
-use feophantlib::engine::{io::IOManager, transactions::TransactionManager, Engine};
+//Each test binary only uses some of these helpers
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use feophantlib::engine::{
+    io::IOManager,
+    objects::{QueryResult, ServerSettings},
+    transactions::TransactionManager,
+    Engine, EngineError,
+};
+use feophantlib::processor::handle_connection;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
 
 #[macro_export]
 macro_rules! aw {
@@ -12,3 +24,79 @@ pub fn _create_engine() -> (TransactionManager, Engine) {
     let engine = Engine::new(IOManager::new(), transaction_manager.clone());
     (transaction_manager, engine)
 }
+
+//An engine over a data directory the io manager already opened
+pub fn start(io_manager: &IOManager) -> (TransactionManager, Engine) {
+    let tm = aw!(TransactionManager::open(io_manager.directory().unwrap())).unwrap();
+    let engine = Engine::new(io_manager.clone(), tm.clone());
+    (tm, engine)
+}
+
+//Runs the query in its own transaction, committing it if it worked
+pub fn run(
+    engine: &mut Engine,
+    tm: &mut TransactionManager,
+    query: &str,
+) -> Result<Vec<QueryResult>, EngineError> {
+    let tran = aw!(tm.start_trans()).unwrap();
+    let result = aw!(engine.process_query(tran, query.to_string()));
+    match result {
+        Ok(_) => aw!(tm.commit_trans(tran)).unwrap(),
+        Err(_) => aw!(tm.abort_trans(tran)).unwrap(),
+    }
+    result
+}
+
+//The SQLSTATE a failed query would send to the client
+pub fn error_code(result: Result<Vec<QueryResult>, EngineError>) -> Bytes {
+    match result {
+        Err(e) => e.pg_error_code().value(),
+        Ok(r) => panic!("Expected an error, got {:?}", r),
+    }
+}
+
+pub fn as_strings(result: &QueryResult) -> Vec<Vec<Option<String>>> {
+    result
+        .rows
+        .iter()
+        .map(|r| {
+            r.0.iter()
+                .map(|c| c.as_ref().map(|v| v.to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+pub fn row(values: &[Option<&str>]) -> Vec<Option<String>> {
+    values.iter().map(|v| v.map(|s| s.to_string())).collect()
+}
+
+//An empty in memory server, returning its address
+pub fn serve(rt: &Runtime) -> Result<String, Box<dyn std::error::Error>> {
+    serve_with(rt, ServerSettings::default())
+}
+
+pub fn serve_with(
+    rt: &Runtime,
+    settings: ServerSettings,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let tm = TransactionManager::new();
+    let engine = Engine::new(IOManager::new(), tm.clone()).with_server_settings(settings);
+    serve_engine(rt, engine, tm)
+}
+
+//Listens on localhost like any server would, returning its address
+pub fn serve_engine(
+    rt: &Runtime,
+    engine: Engine,
+    tm: TransactionManager,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let listener = rt.block_on(TcpListener::bind("127.0.0.1:0"))?;
+    let address = listener.local_addr()?.to_string();
+    rt.spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle_connection(stream, engine.clone(), tm.clone()));
+        }
+    });
+    Ok(address)
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use common::{as_strings, error_code, row, run, start};
+use feophantlib::{
+    constants::{BuiltinSqlTypes, PgErrorCodes},
+    engine::{
+        io::{
+            page_formats::PageSize, CheckpointKind, ClusterState, ControlFile, DataDirectory,
+            DataDirectoryError, IOManager, IOManagerError,
+        },
+        objects::ServerSettings,
+    },
+};
+use std::fs;
+
+#[test]
+fn data_survives_restart() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+
+    let io_manager = aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    run(&mut engine, &mut tm, "create table kept (value text)")?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into kept values('first'); insert into kept values('second')",
+    )?;
+
+    //An aborted insert must stay invisible after the restart
+    let tran = aw!(tm.start_trans())?;
+    aw!(engine.process_query(tran, "insert into kept values('lost')".to_string()))?;
+    aw!(tm.abort_trans(tran))?;
+    aw!(io_manager.shutdown())?;
+    drop(engine);
+    drop(io_manager);
+
+    let io_manager = aw!(IOManager::open(&path))?;
+    assert_eq!(io_manager.control_file().state, ClusterState::InProduction);
+    let (mut tm, mut engine) = start(&io_manager);
+
+    let result = run(&mut engine, &mut tm, "select value from kept")?;
+    assert_eq!(
+        result[0]
+            .rows
+            .iter()
+            .map(|r| r.0[0].clone())
+            .collect::<Vec<_>>(),
+        vec![
+            Some(BuiltinSqlTypes::Text("first".to_string())),
+            Some(BuiltinSqlTypes::Text("second".to_string()))
+        ]
+    );
+
+    //New transactions must not reuse ids from before the restart
+    run(&mut engine, &mut tm, "insert into kept values('third')")?;
+    let result = run(&mut engine, &mut tm, "select value from kept")?;
+    assert_eq!(result[0].rows.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn bootstrap_catalogs_selectable() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let io_manager = aw!(IOManager::initdb_directory(
+        &tmp.path().join("data"),
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+
+    let result = run(&mut engine, &mut tm, "select nspname from pg_namespace")?;
+    let names: Vec<_> = result[0].rows.iter().map(|r| r.0[0].clone()).collect();
+    assert!(names.contains(&Some(BuiltinSqlTypes::Text("pg_catalog".to_string()))));
+    assert!(names.contains(&Some(BuiltinSqlTypes::Text("public".to_string()))));
+
+    let result = run(&mut engine, &mut tm, "select name from pg_class")?;
+    let names: Vec<_> = result[0].rows.iter().map(|r| r.0[0].clone()).collect();
+    assert!(names.contains(&Some(BuiltinSqlTypes::Text("pg_attribute".to_string()))));
+    assert!(names.contains(&Some(BuiltinSqlTypes::Text("pg_type".to_string()))));
+    Ok(())
+}
+
+#[test]
+fn checkpoint_flushes_writes() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+    let io_manager = aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+
+    run(&mut engine, &mut tm, "create table flushed (value text)")?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into flushed values('on disk')",
+    )?;
+    let result = run(&mut engine, &mut tm, "checkpoint")?;
+    assert!(result[0].columns.is_empty());
+    assert!(result[0].rows.is_empty());
+    let stats = aw!(io_manager.checkpoint_stats());
+    assert_eq!(stats.checkpoints_requested, 1);
+    assert!(stats.files_synced > 0);
+
+    //Nothing was written since, so there is nothing more to flush
+    let synced = stats.files_synced;
+    let stats = aw!(io_manager.checkpoint(CheckpointKind::Timed))?;
+    assert_eq!(stats.checkpoints_timed, 1);
+    assert_eq!(stats.files_synced, synced);
+
+    let (directory, _) = aw!(DataDirectory::open(&path, None))?;
+    assert_eq!(
+        aw!(directory.control_file()).last_checkpoint,
+        stats.last_checkpoint
+    );
+    Ok(())
+}
+
+#[test]
+fn background_writer_flushes_ahead() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+    let io_manager = aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    aw!(io_manager.checkpoint(CheckpointKind::Requested))?;
+    let mut settings = ServerSettings::default();
+    settings.set("checkpoint_timeout", "10min")?;
+    settings.set("bgwriter_lru_maxpages", "1")?;
+    let (mut tm, engine) = start(&io_manager);
+    let mut engine = engine.with_server_settings(settings);
+
+    //The catalogs and the new table each get a file
+    run(&mut engine, &mut tm, "create table trickled (value text)")?;
+    run(&mut engine, &mut tm, "insert into trickled values('a')")?;
+
+    let stats = aw!(io_manager.background_write(1))?;
+    assert_eq!(stats.files_cleaned, 1);
+    assert_eq!(stats.maxwritten_clean, 1);
+    let stats = aw!(io_manager.background_write(100))?;
+    assert!(stats.files_cleaned > 1);
+    assert_eq!(stats.maxwritten_clean, 1);
+
+    //What the background writer flushed the checkpoint doesn't have to
+    let before = stats.files_synced;
+    let stats = aw!(io_manager.checkpoint(CheckpointKind::Requested))?;
+    assert_eq!(stats.files_synced, before);
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select checkpoints_timed, checkpoints_req, buffers_checkpoint, buffers_clean, maxwritten_clean from pg_stat_bgwriter",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some("0"),
+            Some("2"),
+            Some(&stats.files_synced.to_string()),
+            Some(&stats.files_cleaned.to_string()),
+            Some("1"),
+        ])]
+    );
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select last_checkpoint from pg_stat_bgwriter",
+    )?;
+    assert!(result[0].rows[0].0[0].is_some());
+    assert_eq!(
+        error_code(run(
+            &mut engine,
+            &mut tm,
+            "insert into pg_stat_bgwriter (checkpoints_timed) values(1)"
+        )),
+        PgErrorCodes::ObjectNotInPrerequisiteState.value()
+    );
+    assert!(run(&mut engine, &mut tm, "vacuum pg_stat_bgwriter").is_err());
+    assert!(run(&mut engine, &mut tm, "drop table pg_stat_bgwriter").is_err());
+
+    //Read by SHOW, fixed until the server restarts
+    let result = run(&mut engine, &mut tm, "show checkpoint_timeout")?;
+    assert_eq!(as_strings(&result[0]), vec![row(&[Some("10min")])]);
+    let result = run(&mut engine, &mut tm, "show bgwriter_delay")?;
+    assert_eq!(as_strings(&result[0]), vec![row(&[Some("200ms")])]);
+    assert_eq!(
+        error_code(run(&mut engine, &mut tm, "set checkpoint_timeout = '1min'")),
+        PgErrorCodes::CantChangeRuntimeParam.value()
+    );
+    Ok(())
+}
+
+#[test]
+fn hint_bits_not_logged() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+    let io_manager = aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    run(&mut engine, &mut tm, "create table hinted (value text)")?;
+    for _ in 0..10 {
+        run(&mut engine, &mut tm, "insert into hinted values('a')")?;
+    }
+
+    //The scan hints every row and marks the page all visible without a log record
+    let wal = io_manager.directory().unwrap().wal();
+    let tran = aw!(tm.start_trans())?;
+    let before = aw!(wal.end_lsn());
+    let lookups = tm.lookups();
+    let result = aw!(engine.process_query(tran, "select value from hinted".to_string()))?;
+    assert_eq!(result[0].rows.len(), 10);
+    assert!(tm.lookups() - lookups >= 10);
+    assert_eq!(aw!(wal.end_lsn()), before);
+    aw!(tm.commit_trans(tran))?;
+    let lookups = tm.lookups();
+    run(&mut engine, &mut tm, "select value from hinted")?;
+    let hinted = tm.lookups() - lookups;
+    aw!(io_manager.shutdown())?;
+    drop(engine);
+    drop(io_manager);
+
+    //Still written to the file, so the rows need no more lookups than before the restart
+    let io_manager = aw!(IOManager::open(&path))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    let lookups = tm.lookups();
+    let result = run(&mut engine, &mut tm, "select value from hinted")?;
+    assert_eq!(result[0].rows.len(), 10);
+    assert_eq!(tm.lookups() - lookups, hinted);
+    Ok(())
+}
+
+#[test]
+fn refuses_bad_directories() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+    aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+
+    assert!(matches!(
+        aw!(IOManager::initdb_directory(
+            &path,
+            ControlFile::new(PageSize::Kb4)
+        )),
+        Err(IOManagerError::DataDirectoryError(
+            DataDirectoryError::NotEmpty(_)
+        ))
+    ));
+
+    fs::remove_file(path.join("pg_xact"))?;
+    assert!(matches!(
+        aw!(IOManager::open(&path)),
+        Err(IOManagerError::DataDirectoryError(
+            DataDirectoryError::HalfInitialized(_)
+        ))
+    ));
+
+    fs::remove_file(path.join("global").join("feophant_control"))?;
+    assert!(matches!(
+        aw!(IOManager::open(&path)),
+        Err(IOManagerError::DataDirectoryError(
+            DataDirectoryError::HalfInitialized(_)
+        ))
+    ));
+    Ok(())
+}
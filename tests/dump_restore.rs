@@ -0,0 +1,120 @@
+mod common;
+
+use common::serve;
+use feophantlib::dump::{Client, ClientError, Dumper, Restorer};
+use tokio::runtime::Runtime;
+
+fn dump(rt: &Runtime, address: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut script = vec![];
+    let client = rt.block_on(Client::connect(address))?;
+    rt.block_on(Dumper::new(client).dump(&mut script))?;
+    Ok(String::from_utf8(script)?)
+}
+
+const QUERIES: [&str; 4] = [
+    "select id, status from checkins",
+    "select x from empty",
+    "select nums, moods, at from lists",
+    "select a, b, c, d, e from words",
+];
+
+#[test]
+fn dump_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let rt = Runtime::new()?;
+    let source = serve(&rt)?;
+    let mut client = rt.block_on(Client::connect(&source))?;
+    rt.block_on(client.query(
+        "create type mood as enum ('sad', 'it''s ok', 'happy'); create type status as (label varchar(5), feeling mood, score int)",
+    ))?;
+    rt.block_on(client.query(
+        "create table words (a varchar(10) not null, b text, c bytea, d numeric(6,2), e bool)",
+    ))?;
+    rt.block_on(client.query("create table lists (nums int[], moods mood[], at timestamptz)"))?;
+    rt.block_on(client.query("create table checkins (id bigint not null, status status)"))?;
+    rt.block_on(client.query("create table empty (x double precision)"))?;
+    rt.block_on(client.query(
+        "insert into words values('plain', 'tab\there', '\\xdead', 12.5, true); insert into words values('lines', 'one\ntwo\\three', null, null, false); insert into words values('nulls', null, null, -0.01, null)",
+    ))?;
+    rt.block_on(client.query(
+        r#"insert into lists values('{1,NULL,3}', '{sad,"it''s ok"}', '2021-02-28 13:45:30-05'); insert into lists values(null, null, null)"#,
+    ))?;
+    rt.block_on(client.query(
+        r#"insert into checkins values(1, ROW('fine', 'happy', 3)); insert into checkins values(2, '("a b",sad,)'); insert into checkins values(3, null)"#,
+    ))?;
+
+    let script = dump(&rt, &source)?;
+    assert!(script.contains("CREATE TYPE mood AS ENUM ('sad', 'it''s ok', 'happy');\n"));
+    assert!(script.contains(
+        "CREATE TYPE status AS (label character varying(5), feeling mood, score integer);\n"
+    ));
+    assert!(script.contains("CREATE TABLE words (a character varying(10) NOT NULL, b text, c bytea, d numeric(6,2), e boolean);\n"));
+    assert!(script.contains("lines\tone\\ntwo\\\\three\t\\N\t\\N\tf\n"));
+    assert!(script.find("CREATE TYPE status").unwrap() > script.find("CREATE TYPE mood").unwrap());
+
+    //Loading into an empty server makes the same database
+    let target = serve(&rt)?;
+    let restored = rt.block_on(
+        Restorer::new(rt.block_on(Client::connect(&target))?).restore(script.as_bytes()),
+    )?;
+    assert_eq!(restored, 12);
+    assert_eq!(dump(&rt, &target)?, script);
+
+    let mut restored = rt.block_on(Client::connect(&target))?;
+    for query in QUERIES {
+        assert_eq!(
+            rt.block_on(restored.query(query))?,
+            rt.block_on(client.query(query))?
+        );
+    }
+    assert_eq!(rt.block_on(restored.query(QUERIES[0]))?.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn dump_is_one_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+    let rt = Runtime::new()?;
+    let address = serve(&rt)?;
+    let mut writer = rt.block_on(Client::connect(&address))?;
+    let mut reader = rt.block_on(Client::connect(&address))?;
+    rt.block_on(writer.query("create table people (name text)"))?;
+    rt.block_on(writer.query("insert into people values('Bob')"))?;
+
+    rt.block_on(reader.query("BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY"))?;
+    assert_eq!(
+        rt.block_on(reader.query("select name from people"))?.len(),
+        1
+    );
+    rt.block_on(writer.query("insert into people values('Alice')"))?;
+    rt.block_on(writer.query("create table later (x int)"))?;
+    assert_eq!(
+        rt.block_on(reader.query("select name from people"))?.len(),
+        1
+    );
+    assert!(matches!(
+        rt.block_on(reader.query("insert into people values('Nope')")),
+        Err(ClientError::Server(code, _)) if code == "25006"
+    ));
+    rt.block_on(reader.query("ROLLBACK"))?;
+    assert_eq!(
+        rt.block_on(reader.query("select name from people"))?.len(),
+        2
+    );
+    Ok(())
+}
+
+#[test]
+fn restore_stops_at_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let rt = Runtime::new()?;
+    let address = serve(&rt)?;
+    let script =
+        "BEGIN;\nCREATE TABLE t (a int);\nCOPY t (a) FROM stdin;\n1\nnot a number\n\\.\nCOMMIT;\n";
+    let client = rt.block_on(Client::connect(&address))?;
+    assert!(rt
+        .block_on(Restorer::new(client).restore(script.as_bytes()))
+        .is_err());
+
+    //The failed load left nothing behind
+    let mut client = rt.block_on(Client::connect(&address))?;
+    assert!(rt.block_on(client.query("select a from t")).is_err());
+    Ok(())
+}
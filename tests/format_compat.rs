@@ -0,0 +1,141 @@
+//! The on-disk format must read back the same on every build, these pin it to golden files.
+//!
+//! After an intentional format change bump PAGE_LAYOUT_VERSION, then regenerate the files by
+//! running this test with FEOPHANT_BLESS_GOLDEN=1.
+mod common;
+
+use bytes::Bytes;
+use feophantlib::{
+    constants::{BuiltinSqlTypes, DeserializeTypes, Nullable},
+    engine::{
+        io::{
+            page_formats::{PageData, PageHeader, PageSize, UInt15, PAGE_LAYOUT_VERSION},
+            row_formats::{InfoMask, ItemPointer, RowData},
+        },
+        objects::{Attribute, SqlTuple, Table},
+        transactions::TransactionId,
+    },
+};
+use std::{env, fs, path::PathBuf, sync::Arc};
+use uuid::Uuid;
+
+fn golden(name: &str, current: &Bytes) -> Bytes {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect();
+    if env::var_os("FEOPHANT_BLESS_GOLDEN").is_some() {
+        fs::write(&path, current).unwrap();
+    }
+    Bytes::from(fs::read(&path).unwrap())
+}
+
+fn get_table() -> Arc<Table> {
+    Arc::new(Table::new(
+        "golden".to_string(),
+        vec![
+            Attribute::new(
+                Uuid::nil(),
+                "name".to_string(),
+                DeserializeTypes::Text,
+                Nullable::NotNull,
+            ),
+            Attribute::new(
+                Uuid::nil(),
+                "id".to_string(),
+                DeserializeTypes::Uuid,
+                Nullable::Null,
+            ),
+            Attribute::new(
+                Uuid::nil(),
+                "count".to_string(),
+                DeserializeTypes::Integer,
+                Nullable::Null,
+            ),
+        ],
+    ))
+}
+
+fn get_rows(table: Arc<Table>) -> Result<Vec<RowData>, Box<dyn std::error::Error>> {
+    let mut deleted = RowData::new(
+        table.clone(),
+        TransactionId::new(2),
+        Some(TransactionId::new(0x0102_0304_0506_0708)),
+        ItemPointer::new(0, UInt15::new(0).unwrap()),
+        Arc::new(SqlTuple(vec![
+            Some(BuiltinSqlTypes::Text("deleted".to_string())),
+            Some(BuiltinSqlTypes::Uuid(Uuid::from_u128(
+                0x0011_2233_4455_6677_8899_aabb_ccdd_eeff,
+            ))),
+            Some(BuiltinSqlTypes::Integer(-2)),
+        ])),
+    )?;
+    deleted.hints = InfoMask::XMIN_COMMITTED;
+
+    let live = RowData::new(
+        table,
+        TransactionId::new(3),
+        None,
+        ItemPointer::new(0, UInt15::new(1).unwrap()),
+        Arc::new(SqlTuple(vec![
+            Some(BuiltinSqlTypes::Text("live".to_string())),
+            None,
+            Some(BuiltinSqlTypes::Integer(i32::MAX)),
+        ])),
+    )?;
+    Ok(vec![deleted, live])
+}
+
+#[test]
+fn golden_item_pointer() -> Result<(), Box<dyn std::error::Error>> {
+    let pointer = ItemPointer::new(0x7654_3210, UInt15::new(0x1234).unwrap());
+    let current = pointer.serialize();
+    let expected = golden("item_pointer.bin", &current);
+    assert_eq!(current, expected);
+    assert_eq!(ItemPointer::parse(&mut expected.clone())?, pointer);
+    Ok(())
+}
+
+#[test]
+fn golden_row() -> Result<(), Box<dyn std::error::Error>> {
+    let table = get_table();
+    let rows = get_rows(table.clone())?;
+
+    let current = rows[0].serialize();
+    let expected = golden("row.bin", &current);
+    assert_eq!(current, expected);
+
+    let parsed = RowData::parse(table, expected)?;
+    assert_eq!(parsed, rows[0]);
+    assert_eq!(parsed.max, Some(TransactionId::new(0x0102_0304_0506_0708)));
+    assert_eq!(parsed.hints, InfoMask::XMIN_COMMITTED);
+    Ok(())
+}
+
+#[test]
+fn golden_page() -> Result<(), Box<dyn std::error::Error>> {
+    let table = get_table();
+    let rows = get_rows(table.clone())?;
+
+    let mut page = PageData::new(0, PageSize::Kb4);
+    page.set_checksum(true);
+    for r in rows.iter() {
+        page.insert(r.clone())?;
+    }
+
+    let current = page.serialize();
+    let expected = golden("page.bin", &current);
+    assert_eq!(current, expected);
+    assert_eq!(
+        u16::from_le_bytes([expected[8], expected[9]]),
+        PAGE_LAYOUT_VERSION
+    );
+    assert_eq!(
+        PageHeader::stored_checksum(&expected),
+        PageHeader::compute_checksum(&expected, 0)
+    );
+
+    let parsed = PageData::parse(table, 0, expected)?;
+    let parsed_rows: Vec<RowData> = aw!(futures::StreamExt::collect(parsed.get_stream()));
+    assert_eq!(parsed_rows, rows);
+    Ok(())
+}
@@ -0,0 +1,308 @@
+mod common;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use common::{as_strings, run, start};
+use feophantlib::{
+    codec::{NetworkFrame, PgCodec},
+    engine::{
+        io::{page_formats::PageSize, ControlFile, IOManager},
+        transactions::TransactionManager,
+        Engine, EngineError,
+    },
+    processor::handle_connection,
+    replication::ReplicationMessage,
+};
+use futures::{SinkExt, StreamExt};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+use tokio_util::codec::Framed;
+
+//The rows of the last statement as text
+fn text_rows(
+    engine: &mut Engine,
+    tm: &mut TransactionManager,
+    query: &str,
+) -> Result<Vec<Vec<Option<String>>>, EngineError> {
+    Ok(run(engine, tm, query)?
+        .last()
+        .map(as_strings)
+        .unwrap_or_default())
+}
+
+//Only the decoded text of each row
+fn data(rows: Vec<Vec<Option<String>>>) -> Vec<String> {
+    rows.into_iter()
+        .map(|mut r| r.pop().flatten().unwrap())
+        .collect()
+}
+
+#[test]
+fn sql_slot_functions() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+    let io_manager = aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    text_rows(
+        &mut engine,
+        &mut tm,
+        "create table people (name text, age integer)",
+    )?;
+    text_rows(
+        &mut engine,
+        &mut tm,
+        "insert into people values('Before', 1)",
+    )?;
+
+    let created = text_rows(
+        &mut engine,
+        &mut tm,
+        "select * from pg_create_logical_replication_slot('indexer', 'test_decoding')",
+    )?;
+    assert_eq!(created[0][0], Some("indexer".to_string()));
+    assert!(matches!(
+        text_rows(
+            &mut engine,
+            &mut tm,
+            "select pg_create_logical_replication_slot('indexer', 'test_decoding')"
+        ),
+        Err(EngineError::DataDirectoryError(_))
+    ));
+
+    text_rows(
+        &mut engine,
+        &mut tm,
+        "insert into people values('Bob', 42); insert into people values('O''Brien', null)",
+    )?;
+    let aborted = aw!(tm.start_trans())?;
+    aw!(engine.process_query(aborted, "insert into people values('Never', 0)".to_string()))?;
+    aw!(tm.abort_trans(aborted))?;
+
+    let expected = [
+        "table public.people: INSERT: name[text]:'Bob' age[integer]:42".to_string(),
+        "table public.people: INSERT: name[text]:'O''Brien' age[integer]:null".to_string(),
+    ];
+    let peek = "select * from pg_logical_slot_peek_changes('indexer', NULL, NULL)";
+    let get = "select * from pg_logical_slot_get_changes('indexer', NULL, NULL)";
+    for query in [peek, peek, get] {
+        let rows = text_rows(&mut engine, &mut tm, query)?;
+        let xid = rows[0][1].clone().unwrap();
+        let lines = data(rows);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], format!("BEGIN {}", xid));
+        assert_eq!(lines[1..3], expected[..]);
+        assert_eq!(lines[3], format!("COMMIT {}", xid));
+    }
+    assert_eq!(
+        text_rows(&mut engine, &mut tm, get)?,
+        vec![] as Vec<Vec<Option<String>>>
+    );
+
+    //Where the slot has got to survives a restart
+    text_rows(
+        &mut engine,
+        &mut tm,
+        "insert into people values('After', 2)",
+    )?;
+    aw!(io_manager.shutdown())?;
+    let io_manager = aw!(IOManager::open(&path))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    let lines = data(text_rows(&mut engine, &mut tm, get)?);
+    assert_eq!(
+        lines[1..],
+        [
+            "table public.people: INSERT: name[text]:'After' age[integer]:2".to_string(),
+            lines[2].clone()
+        ]
+    );
+
+    //Binary plugins can't be read as text
+    text_rows(
+        &mut engine,
+        &mut tm,
+        "select * from pg_create_logical_replication_slot('cdc', 'pgoutput')",
+    )?;
+    assert!(matches!(
+        text_rows(
+            &mut engine,
+            &mut tm,
+            "select * from pg_logical_slot_get_changes('cdc', NULL, NULL)"
+        ),
+        Err(EngineError::BinaryOutputPlugin(_))
+    ));
+
+    text_rows(
+        &mut engine,
+        &mut tm,
+        "select pg_drop_replication_slot('indexer')",
+    )?;
+    assert!(text_rows(&mut engine, &mut tm, get).is_err());
+    Ok(())
+}
+
+type Client = Framed<TcpStream, PgCodec>;
+
+async fn next_frame(client: &mut Client) -> NetworkFrame {
+    tokio::time::timeout(Duration::from_secs(10), client.next())
+        .await
+        .expect("Timed out waiting for the server")
+        .unwrap()
+        .unwrap()
+}
+
+//Every frame up to the ready for query
+async fn query(client: &mut Client, query: &str) -> Vec<NetworkFrame> {
+    let mut buffer = BytesMut::new();
+    buffer.put(query.as_bytes());
+    buffer.put_u8(b'\0');
+    client
+        .send(NetworkFrame::new(b'Q', buffer.freeze()))
+        .await
+        .unwrap();
+    let mut frames = vec![];
+    loop {
+        let frame = next_frame(client).await;
+        if frame.message_type == b'Z' {
+            return frames;
+        }
+        frames.push(frame);
+    }
+}
+
+async fn connect(address: &str) -> Client {
+    let mut params = BytesMut::new();
+    params.put_u32(196608);
+    for p in ["user", "feophant", "replication", "true", ""] {
+        params.put(p.as_bytes());
+        params.put_u8(b'\0');
+    }
+    let mut startup = BytesMut::new();
+    startup.put_u32(params.len() as u32 + 4);
+    startup.put(params);
+
+    let mut client = Framed::new(TcpStream::connect(address).await.unwrap(), PgCodec {});
+    client
+        .send(NetworkFrame::new(0, startup.freeze()))
+        .await
+        .unwrap();
+    while next_frame(&mut client).await.message_type != b'Z' {}
+    client
+}
+
+//The pgoutput messages until a commit
+async fn next_transaction(client: &mut Client) -> Vec<Bytes> {
+    let mut messages = vec![];
+    loop {
+        let frame = next_frame(client).await;
+        assert_eq!(frame.message_type, b'd');
+        match ReplicationMessage::parse(frame.payload) {
+            Some(ReplicationMessage::XLogData { data, .. }) => {
+                let commit = data[0] == b'C';
+                messages.push(data);
+                if commit {
+                    return messages;
+                }
+            }
+            Some(ReplicationMessage::Keepalive { .. }) => {}
+            m => panic!("Unexpected message {:?}", m),
+        }
+    }
+}
+
+#[test]
+fn pgoutput_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let rt = Runtime::new()?;
+    let io_manager = aw!(IOManager::initdb_directory(
+        &tmp.path().join("data"),
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    text_rows(&mut engine, &mut tm, "create table people (name text)")?;
+
+    let listener = rt.block_on(TcpListener::bind("127.0.0.1:0"))?;
+    let address = listener.local_addr()?.to_string();
+    {
+        let tm = tm.clone();
+        let engine = engine.clone();
+        rt.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, engine.clone(), tm.clone()));
+            }
+        });
+    }
+
+    let mut client = rt.block_on(connect(&address));
+    let created = rt.block_on(query(
+        &mut client,
+        "CREATE_REPLICATION_SLOT cdc LOGICAL pgoutput NOEXPORT_SNAPSHOT",
+    ));
+    let types: Vec<u8> = created.iter().map(|f| f.message_type).collect();
+    assert_eq!(types, vec![b'T', b'D', b'C']);
+
+    text_rows(&mut engine, &mut tm, "insert into people values('Bob')")?;
+
+    let mut start = BytesMut::new();
+    start.put(&b"START_REPLICATION SLOT cdc LOGICAL 0/0 (proto_version '1', publication_names 'all')\0"[..]);
+    rt.block_on(client.send(NetworkFrame::new(b'Q', start.freeze())))?;
+    assert_eq!(rt.block_on(next_frame(&mut client)).message_type, b'W');
+
+    let messages = rt.block_on(next_transaction(&mut client));
+    let types: Vec<u8> = messages.iter().map(|m| m[0]).collect();
+    assert_eq!(types, vec![b'B', b'R', b'I', b'C']);
+    assert!(messages[1].ends_with(b"public\0people\0f\0\x01\x01name\0\0\0\0\x19\xff\xff\xff\xff"));
+    assert!(messages[2].ends_with(b"N\0\x01t\0\0\0\x03Bob"));
+
+    //A slot in use can't be read by anyone else
+    let mut other = rt.block_on(connect(&address));
+    let refused = rt.block_on(query(&mut other, "START_REPLICATION SLOT cdc LOGICAL 0/0"));
+    assert!(refused[0].payload.ends_with(b"C55006\0\0"));
+
+    //Confirming the commit moves the slot
+    let mut commit = messages[3].clone();
+    commit.advance(10);
+    let end = commit.get_u64();
+    let status = ReplicationMessage::StandbyStatus {
+        write: end,
+        flush: end,
+        apply: end,
+        sent: SystemTime::now(),
+        reply_requested: false,
+    };
+    rt.block_on(client.send(status.to_frame()))?;
+    let directory = io_manager.directory().unwrap();
+    for _ in 0..200 {
+        if aw!(directory.slots())[0].confirmed_flush == end {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(aw!(directory.slots())[0].confirmed_flush, end);
+
+    //Later changes keep streaming
+    text_rows(&mut engine, &mut tm, "insert into people values('Alice')")?;
+    let messages = rt.block_on(next_transaction(&mut client));
+    let types: Vec<u8> = messages.iter().map(|m| m[0]).collect();
+    assert_eq!(types, vec![b'B', b'I', b'C']);
+
+    //Once the consumer is gone the slot can be dropped
+    rt.block_on(client.send(NetworkFrame::new(b'c', Bytes::new())))?;
+    drop(client);
+    for _ in 0..200 {
+        if !aw!(directory.slots())[0].active {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let dropped = rt.block_on(query(&mut other, "DROP_REPLICATION_SLOT cdc"));
+    assert_eq!(
+        dropped[0].payload,
+        Bytes::from_static(b"DROP_REPLICATION_SLOT\0")
+    );
+    assert_eq!(aw!(directory.slots()), vec![]);
+    Ok(())
+}
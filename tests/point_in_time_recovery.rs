@@ -0,0 +1,124 @@
+mod common;
+
+use common::{run, start};
+use feophantlib::{
+    constants::BuiltinSqlTypes,
+    engine::io::{
+        page_formats::PageSize, ControlFile, DataDirectory, DataDirectoryError, IOManager,
+        RecoveryTarget,
+    },
+};
+use std::fs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+//A base backup taken while the cluster is shut down is only a copy
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+        } else {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn recover_to_before_drop() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let data = tmp.path().join("data");
+    let archive = tmp.path().join("archive");
+    let backup = tmp.path().join("backup");
+
+    aw!(IOManager::initdb_directory(
+        &data,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let io_manager = aw!(IOManager::open_with_archive(&data, Some(archive.clone())))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    run(&mut engine, &mut tm, "create table precious (value text)")?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into precious values('backed up')",
+    )?;
+    aw!(io_manager.shutdown())?;
+    copy_dir(&data, &backup)?;
+
+    let io_manager = aw!(IOManager::open_with_archive(&data, Some(archive.clone())))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    let last_good = aw!(tm.start_trans())?;
+    aw!(engine.process_query(
+        last_good,
+        "insert into precious values('archived')".to_string()
+    ))?;
+    aw!(tm.commit_trans(last_good))?;
+
+    let before_lsn = aw!(io_manager.directory().unwrap().wal().end_lsn());
+    sleep(Duration::from_millis(5));
+    let before_time = SystemTime::now();
+    sleep(Duration::from_millis(5));
+
+    run(&mut engine, &mut tm, "drop table precious")?;
+    assert!(run(&mut engine, &mut tm, "select value from precious").is_err());
+    aw!(io_manager.shutdown())?;
+
+    for (i, target) in [
+        RecoveryTarget::Lsn(before_lsn),
+        RecoveryTarget::TransactionId(last_good),
+        RecoveryTarget::Time(before_time),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let restored = tmp.path().join(format!("restored{}", i));
+        copy_dir(&backup, &restored)?;
+        aw!(DataDirectory::recover(&restored, &archive, *target))?;
+
+        let io_manager = aw!(IOManager::open(&restored))?;
+        let (mut tm, mut engine) = start(&io_manager);
+        let result = run(&mut engine, &mut tm, "select value from precious")?;
+        assert_eq!(
+            result[0]
+                .rows
+                .iter()
+                .map(|r| r.0[0].clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Some(BuiltinSqlTypes::Text("backed up".to_string())),
+                Some(BuiltinSqlTypes::Text("archived".to_string()))
+            ],
+            "recovering to {:?}",
+            target
+        );
+
+        //The recovered cluster carries on from there
+        run(&mut engine, &mut tm, "insert into precious values('after')")?;
+        let result = run(&mut engine, &mut tm, "select value from precious")?;
+        assert_eq!(result[0].rows.len(), 3);
+    }
+
+    let restored = tmp.path().join("too_far");
+    copy_dir(&backup, &restored)?;
+    assert!(matches!(
+        aw!(DataDirectory::recover(
+            &restored,
+            &archive,
+            RecoveryTarget::Lsn(u64::MAX)
+        )),
+        Err(DataDirectoryError::TargetNotReached(_))
+    ));
+    assert!(matches!(
+        aw!(DataDirectory::recover(
+            &restored,
+            &archive,
+            RecoveryTarget::Time(SystemTime::UNIX_EPOCH)
+        )),
+        Err(DataDirectoryError::TargetBeforeBackup(_))
+    ));
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use feophantlib::engine::io::{page_formats::PageSize, DataDirectory, IOManager};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn server(args: &[&str]) -> Result<Output, Box<dyn std::error::Error>> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_feophant-server"))
+        .args(args)
+        .output()?)
+}
+
+fn path(p: &Path) -> &str {
+    p.to_str().unwrap()
+}
+
+#[test]
+fn init_page_size() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+
+    for (kb, page_size) in [
+        ("4", PageSize::Kb4),
+        ("16", PageSize::Kb16),
+        ("32", PageSize::Kb32),
+    ] {
+        let dir = tmp.path().join(kb);
+        assert!(server(&["--init", path(&dir), "--page-size", kb])?
+            .status
+            .success());
+        let (control_file, _) = tokio_test::block_on(DataDirectory::read_offline(&dir))?;
+        assert_eq!(control_file.page_size, page_size);
+    }
+
+    let dir = tmp.path().join("default");
+    assert!(server(&["--init", path(&dir)])?.status.success());
+    let (control_file, _) = tokio_test::block_on(DataDirectory::read_offline(&dir))?;
+    assert_eq!(control_file.page_size, PageSize::Kb8);
+
+    let dir = tmp.path().join("bad");
+    for args in [
+        vec!["--init", path(&dir), "--page-size", "5"],
+        vec!["--init", path(&dir), "--page-size", "8192"],
+        vec!["-D", path(&dir), "--page-size", "8"],
+    ] {
+        assert_eq!(server(&args)?.status.code(), Some(2));
+    }
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn verify_checksums_offline() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("data");
+    assert!(server(&["--init", path(&dir), "--data-checksums"])?
+        .status
+        .success());
+    assert!(server(&["--verify-checksums", path(&dir)])?
+        .status
+        .success());
+
+    //Flip a bit in the first page of a catalog
+    let mut relation = None;
+    for entry in fs::read_dir(dir.join("base"))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.contains('_') && entry.metadata()?.len() > 0 {
+            relation = Some((name, entry.path()));
+            break;
+        }
+    }
+    let (id, file) = relation.unwrap();
+    let mut contents = fs::read(&file)?;
+    contents[2000] ^= 0x01;
+    fs::write(&file, contents)?;
+
+    let output = server(&["--verify-checksums", path(&dir)])?;
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8(output.stderr)? + &String::from_utf8(output.stdout)?;
+    assert!(report.contains(&format!("Invalid page 0 of relation {}", id)));
+    assert!(report.contains("1 bad pages"));
+
+    //Without checksums or while in use there is nothing it can check
+    let plain = tmp.path().join("plain");
+    assert!(server(&["--init", path(&plain)])?.status.success());
+    assert_eq!(
+        server(&["--verify-checksums", path(&plain)])?.status.code(),
+        Some(1)
+    );
+    let running = tmp.path().join("running");
+    assert!(server(&["--init", path(&running), "--data-checksums"])?
+        .status
+        .success());
+    tokio_test::block_on(IOManager::open(&running))?;
+    assert_eq!(
+        server(&["--verify-checksums", path(&running)])?
+            .status
+            .code(),
+        Some(1)
+    );
+    Ok(())
+}
+
+#[test]
+fn bad_server_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("data");
+    for args in [
+        vec!["-c", "checkpoint_timeout=1"],
+        vec!["-c", "no_such_setting=1"],
+        vec!["-c", "bgwriter_delay"],
+        vec!["--init", path(&dir), "-c", "bgwriter_lru_maxpages=10"],
+    ] {
+        assert_eq!(server(&args)?.status.code(), Some(2));
+    }
+    assert!(!dir.exists());
+    Ok(())
+}
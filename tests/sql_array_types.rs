@@ -0,0 +1,222 @@
+mod common;
+
+use common::{as_strings, row, run};
+use feophantlib::constants::{DeserializeTypes, PgErrorCodes};
+
+#[test]
+fn array_columns() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table lists (id integer, nums int[], tags text array, codes varchar(3)[][])",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        r#"insert into lists values(1, '{1,2,3}', '{"a b",NULL,c}', '{{ab,cd},{ef,NULL}}')"#,
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into lists values(2, ARRAY[4, NULL], ARRAY['x'], ARRAY[]::text[])",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into lists values(3, '[0:1]={5,6}', null, ARRAY[['a'],['b']])",
+    )?;
+
+    let result = run(&mut engine, &mut tm, "select nums, tags, codes from lists")?;
+    assert_eq!(
+        result[0]
+            .columns
+            .iter()
+            .map(|(_, t)| t.oid())
+            .collect::<Vec<u32>>(),
+        vec![1007, 1009, 1015]
+    );
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[
+                Some("{1,2,3}"),
+                Some(r#"{"a b",NULL,c}"#),
+                Some("{{ab,cd},{ef,NULL}}")
+            ]),
+            row(&[Some("{4,NULL}"), Some("{x}"), Some("{}")]),
+            row(&[Some("[0:1]={5,6}"), None, Some("{{a},{b}}")]),
+        ]
+    );
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select nums[1], nums[2:], codes[2][1], codes[1:1], tags[5] from lists",
+    )?;
+    assert_eq!(result[0].columns[0].1, DeserializeTypes::Integer);
+    assert_eq!(result[0].columns[0].0, "nums");
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[
+                Some("1"),
+                Some("{2,3}"),
+                Some("ef"),
+                Some("{{ab,cd}}"),
+                None
+            ]),
+            row(&[Some("4"), Some("{NULL}"), None, Some("{}"), None]),
+            row(&[Some("6"), Some("{}"), Some("b"), Some("{{a}}"), None]),
+        ]
+    );
+
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "insert into lists values(4, null, null, '{abcd}')"
+    )
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn array_expressions() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select ARRAY[1, 2.5], ARRAY[[1, 2], [3, 4]], ARRAY['a', null], '{1,2}'::int[] = ARRAY[1, 2], ARRAY[1, 2] < ARRAY[1, 2, 3], '{abc, de}'::varchar(2)[], ARRAY[1, 2]::text",
+    )?;
+    assert_eq!(result[0].columns[0].0, "array");
+    assert_eq!(
+        result[0].columns[1].1,
+        DeserializeTypes::Array(Box::new(DeserializeTypes::Integer))
+    );
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some("{1,2.5}"),
+            Some("{{1,2},{3,4}}"),
+            Some("{a,NULL}"),
+            Some("t"),
+            Some("t"),
+            Some("{ab,de}"),
+            Some("{1,2}"),
+        ])]
+    );
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select 2 = ANY('{1,2}'), 3 = any(ARRAY[1, null]), 1 < ALL(ARRAY[2, 3]), 1 = SOME('{}'::int[]), 1 = ALL('{}'::int[]), 'b' <> all('{a,b}')",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some("t"),
+            None,
+            Some("t"),
+            Some("f"),
+            Some("t"),
+            Some("f"),
+        ])]
+    );
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select to_jsonb(ARRAY[[1, 2], [3, null]]), json_build_array('{a}'::text[])",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[Some("[[1, 2], [3, null]]"), Some(r#"[["a"]]"#)])]
+    );
+    Ok(())
+}
+
+#[test]
+fn array_unnest() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select unnest(ARRAY[1, 2, 3]) * 10, unnest('{a,b}'::text[])",
+    )?;
+    assert_eq!(result[0].columns[0].1, DeserializeTypes::Integer);
+    assert_eq!(result[0].columns[1].0, "unnest");
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[Some("10"), Some("a")]),
+            row(&[Some("20"), Some("b")]),
+            row(&[Some("30"), None]),
+        ]
+    );
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table lists (id integer, nums int[])",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into lists values(1, '{{1,2},{3,4}}'); insert into lists values(2, null); insert into lists values(3, '{5}')",
+    )?;
+    let result = run(&mut engine, &mut tm, "select id, unnest(nums) from lists")?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[Some("1"), Some("1")]),
+            row(&[Some("1"), Some("2")]),
+            row(&[Some("1"), Some("3")]),
+            row(&[Some("1"), Some("4")]),
+            row(&[Some("3"), Some("5")]),
+        ]
+    );
+
+    assert!(run(&mut engine, &mut tm, "select unnest(1)").is_err());
+    assert!(run(&mut engine, &mut tm, "select unnest(unnest(ARRAY[1]))").is_err());
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "select jsonb_agg(unnest(nums)) from lists"
+    )
+    .is_err());
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "insert into lists values(unnest(ARRAY[1]), null)"
+    )
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn array_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(&mut engine, &mut tm, "create table lists (nums int[])")?;
+
+    let tran = aw!(tm.start_trans())?;
+    let err = aw!(engine.process_query(tran, "insert into lists values('{1,{2}}')".to_string()))
+        .unwrap_err();
+    aw!(tm.abort_trans(tran))?;
+    assert_eq!(
+        err.pg_error_code().value(),
+        PgErrorCodes::InvalidTextRepresentation.value()
+    );
+
+    assert!(run(&mut engine, &mut tm, "select ARRAY[1, 'a'::text]").is_err());
+    assert!(run(&mut engine, &mut tm, "select ARRAY[]").is_err());
+    assert!(run(&mut engine, &mut tm, "select ARRAY[[1], 2]").is_err());
+    assert!(run(&mut engine, &mut tm, "select ARRAY[[1], [2, 3]]").is_err());
+    assert!(run(&mut engine, &mut tm, "select (1)[1]").is_err());
+    assert!(run(&mut engine, &mut tm, "select 1 = ANY(1)").is_err());
+    assert!(run(&mut engine, &mut tm, "select ARRAY[1]['a']").is_err());
+    Ok(())
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use bytes::BytesMut;
+use common::run;
+use feophantlib::{
+    constants::{BuiltinSqlTypes, PgErrorCodes},
+    engine::{
+        io::{page_formats::PageSize, verify_checksums, ControlFile, ForkNumber, IOManager},
+        objects::Table,
+        transactions::TransactionManager,
+        Engine,
+    },
+};
+use std::sync::Arc;
+
+#[test]
+fn corrupt_page_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let io_manager = IOManager::initdb_with(ControlFile::new(PageSize::Kb4).with_checksums());
+    let mut tm = TransactionManager::new();
+    let mut engine = Engine::new(io_manager.clone(), tm.clone());
+
+    run(&mut engine, &mut tm, "create table guarded (value text)")?;
+    let before: Vec<_> = aw!(io_manager.fork_snapshot(ForkNumber::Main))
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into guarded values('safe'); insert into guarded values('sound')",
+    )?;
+    let result = run(&mut engine, &mut tm, "select value from guarded")?;
+    assert_eq!(
+        result[0].rows[1].0[0],
+        Some(BuiltinSqlTypes::Text("sound".to_string()))
+    );
+    assert_eq!(aw!(verify_checksums(&io_manager))?, vec![]);
+
+    //The table gets its first page from the insert, the catalogs already had theirs
+    let (table_id, pages) = aw!(io_manager.fork_snapshot(ForkNumber::Main))
+        .into_iter()
+        .find(|(id, _)| !before.contains(id))
+        .unwrap();
+    let mut table = Table::new("guarded".to_string(), vec![]);
+    table.id = table_id;
+    let mut corrupt = BytesMut::from(&pages[0][..]);
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 0x20;
+    aw!(io_manager.update_page(Arc::new(table), corrupt.freeze(), 0))?;
+
+    let err = run(&mut engine, &mut tm, "select value from guarded").unwrap_err();
+    assert_eq!(
+        err.pg_error_code().value(),
+        PgErrorCodes::DataCorrupted.value()
+    );
+    assert!(err.to_string().contains("page 0 of relation guarded"));
+
+    let failures = aw!(verify_checksums(&io_manager))?;
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].relation, table_id);
+    assert_eq!(failures[0].page, 0);
+    Ok(())
+}
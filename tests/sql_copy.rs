@@ -0,0 +1,226 @@
+mod common;
+
+use bytes::Bytes;
+use common::{serve, serve_with};
+use feophantlib::dump::{Client, ClientError};
+use feophantlib::engine::objects::ServerSettings;
+use std::io::Write;
+use tokio::runtime::Runtime;
+
+fn server_code(result: Result<impl std::fmt::Debug, ClientError>) -> String {
+    match result {
+        Err(ClientError::Server(code, _)) => code,
+        r => panic!("Expected a server error, got {:?}", r),
+    }
+}
+
+#[test]
+fn copy_csv_options() -> Result<(), Box<dyn std::error::Error>> {
+    let rt = Runtime::new()?;
+    let mut client = rt.block_on(Client::connect(&serve(&rt)?))?;
+    rt.block_on(client.query(
+        "create table prices (item text not null, amount numeric(6,2), sold date, note text)",
+    ))?;
+
+    rt.block_on(client.copy_in(
+        "copy prices (item, amount, sold, note) from stdin (format csv, header, delimiter ';', quote '''', null 'NULL')",
+        Bytes::from_static(
+            b"item;amount;sold;note\nwidget;1.005;2021-03-04;'semi; colon'\n'NULL';NULL;NULL;''\r\nmulti;-3;2020-02-29;'two\nlines'\n",
+        ),
+    ))?;
+    assert_eq!(
+        rt.block_on(client.query("select item, amount, sold, note from prices"))?,
+        vec![
+            vec![
+                Some("widget".to_string()),
+                Some("1.01".to_string()),
+                Some("2021-03-04".to_string()),
+                Some("semi; colon".to_string())
+            ],
+            vec![Some("NULL".to_string()), None, None, Some("".to_string())],
+            vec![
+                Some("multi".to_string()),
+                Some("-3.00".to_string()),
+                Some("2020-02-29".to_string()),
+                Some("two\nlines".to_string())
+            ],
+        ]
+    );
+
+    let data =
+        rt.block_on(client.copy_out("copy prices (note, item) to stdout (format csv, header)"))?;
+    assert_eq!(
+        &data[..],
+        b"note,item\nsemi; colon,widget\n\"\",NULL\n\"two\nlines\",multi\n"
+    );
+
+    //The missing columns must be nullable and the values must fit
+    assert_eq!(
+        server_code(rt.block_on(client.copy_in(
+            "copy prices (amount) from stdin (format csv)",
+            Bytes::from_static(b"1\n")
+        ))),
+        "58000"
+    );
+    assert_eq!(
+        server_code(rt.block_on(client.copy_in(
+            "copy prices from stdin (format csv)",
+            Bytes::from_static(b"big,lots,,\n")
+        ))),
+        "22P02"
+    );
+    assert_eq!(
+        server_code(rt.block_on(client.copy_in(
+            "copy prices from stdin (format csv)",
+            Bytes::from_static(b"open,\"1\n")
+        ))),
+        "22P04"
+    );
+    assert_eq!(
+        server_code(
+            rt.block_on(client.copy_out("copy prices to stdout (format csv, delimiter ',,')"))
+        ),
+        "22023"
+    );
+    assert_eq!(
+        rt.block_on(client.query("select item from prices"))?.len(),
+        3
+    );
+    Ok(())
+}
+
+#[test]
+fn copy_binary_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let rt = Runtime::new()?;
+    let mut client = rt.block_on(Client::connect(&serve(&rt)?))?;
+    rt.block_on(client.query(
+        "create type mood as enum ('sad', 'happy'); create type status as (label varchar(5), feeling mood, scores int[])",
+    ))?;
+    for table in ["source", "target"] {
+        rt.block_on(client.query(&format!(
+            "create table {} (id bigint not null, price numeric(10,3), at timestamptz, took interval, raw bytea, doc jsonb, tags text[], status status, ok bool, ratio double precision)",
+            table
+        )))?;
+    }
+    rt.block_on(client.query(
+        r#"insert into source values(1, -12345.678, '2021-02-28 13:45:30.5-05', '1 year 2 days 00:00:01.25', '\xdeadbeef', '{"b": [1, null], "a": "x"}', '{{a,NULL},{"b c",d}}', ROW('fine', 'happy', '{1,NULL,3}'), true, 0.1)"#,
+    ))?;
+    rt.block_on(client.query(
+        "insert into source values(2, 0, null, null, '', 'null', '{}', ROW(null, null, null), false, -1e300); insert into source (id) values(3)",
+    ))?;
+
+    let data = rt.block_on(client.copy_out("copy source to stdout (format binary)"))?;
+    assert!(data.starts_with(b"PGCOPY\n\xff\r\n\0"));
+    assert!(data.ends_with(b"\xff\xff"));
+    rt.block_on(client.copy_in("copy target from stdin (format binary)", data))?;
+
+    let source = rt.block_on(client.copy_out("copy source to stdout"))?;
+    let target = rt.block_on(client.copy_out("copy target to stdout"))?;
+    assert_eq!(source, target);
+    assert!(std::str::from_utf8(&source)?.starts_with(
+        "1\t-12345.678\t2021-02-28 18:45:30.5+00\t1 year 2 days 00:00:01.25\t\\\\xdeadbeef\t"
+    ));
+
+    //Binary values are checked against the columns like text ones
+    let data = rt.block_on(client.copy_out("copy source (ok) to stdout (format binary)"))?;
+    assert_eq!(
+        server_code(
+            rt.block_on(client.copy_in("copy target (ratio) from stdin (format binary)", data))
+        ),
+        "58000"
+    );
+    assert_eq!(
+        server_code(rt.block_on(client.copy_in(
+            "copy target from stdin (format binary)",
+            Bytes::from_static(b"PGCOPY\n\xff")
+        ))),
+        "22P04"
+    );
+    Ok(())
+}
+
+#[test]
+fn copy_from_file() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(b"name,age\nann,31\n\"bo, jr\",\n")?;
+    let path = file.path().to_str().unwrap().replace('\'', "''");
+
+    //Clients can't read the server's files unless it was started allowing it
+    let rt = Runtime::new()?;
+    let mut client = rt.block_on(Client::connect(&serve(&rt)?))?;
+    rt.block_on(client.query("create table people (name text, age int)"))?;
+    assert_eq!(
+        server_code(rt.block_on(client.query(&format!("copy people from '{}'", path)))),
+        "42501"
+    );
+    assert!(rt
+        .block_on(client.query("select name from people"))?
+        .is_empty());
+
+    let mut settings = ServerSettings::default();
+    settings.set("allow_server_files", "on")?;
+    let mut client = rt.block_on(Client::connect(&serve_with(&rt, settings)?))?;
+    rt.block_on(client.query("create table people (name text, age int)"))?;
+
+    rt.block_on(client.query(&format!(
+        "copy people from '{}' (format csv, header true)",
+        path
+    )))?;
+    assert_eq!(
+        rt.block_on(client.query("select name, age from people"))?,
+        vec![
+            vec![Some("ann".to_string()), Some("31".to_string())],
+            vec![Some("bo, jr".to_string()), None],
+        ]
+    );
+
+    assert_eq!(
+        server_code(rt.block_on(client.query(&format!("copy people from '{}.missing'", path)))),
+        "58P01"
+    );
+    Ok(())
+}
+
+#[test]
+fn copy_large_load() -> Result<(), Box<dyn std::error::Error>> {
+    let rt = Runtime::new()?;
+    let mut client = rt.block_on(Client::connect(&serve(&rt)?))?;
+    rt.block_on(client.query("create table big (id int not null, label text)"))?;
+
+    //Several batches worth, the last one partly full
+    let rows = 2500;
+    let data: String = (0..rows)
+        .map(|i| format!("{}\tlabel number {}\n", i, i))
+        .collect();
+    rt.block_on(client.copy_in("copy big from stdin", Bytes::from(data.clone())))?;
+    let loaded = rt.block_on(client.query("select id, label from big"))?;
+    assert_eq!(loaded.len(), rows);
+    assert_eq!(
+        loaded[rows - 1],
+        vec![
+            Some("2499".to_string()),
+            Some("label number 2499".to_string())
+        ]
+    );
+
+    //A bad row near the end undoes the batches already inserted
+    let bad = format!("{}oops\tlast\n", data);
+    assert_eq!(
+        server_code(rt.block_on(client.copy_in("copy big from stdin", Bytes::from(bad)))),
+        "22P02"
+    );
+    assert_eq!(rt.block_on(client.query("select id from big"))?.len(), rows);
+
+    //Reading is fine in a read only transaction, loading is not
+    rt.block_on(client.query("begin read only"))?;
+    let out = rt.block_on(client.copy_out("copy big (id) to stdout"))?;
+    assert_eq!(out.iter().filter(|b| **b == b'\n').count(), rows);
+    assert_eq!(
+        server_code(
+            rt.block_on(client.copy_in("copy big from stdin", Bytes::from_static(b"1\tx\n")))
+        ),
+        "25006"
+    );
+    rt.block_on(client.query("rollback"))?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use common::{as_strings, run};
+use feophantlib::{constants::DeserializeTypes, engine::objects::QueryResult};
+
+fn single(result: &QueryResult) -> String {
+    as_strings(result)[0][0].clone().unwrap()
+}
+
+#[test]
+fn date_time_columns() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table events (a date, b time, c timestamp, d timestamptz, e interval)",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into events values('2021-02-28', '13:45:30.5', '2021-02-28 13:45:30', '2021-02-28 13:45:30-05', '1 year 2 mons 3 days 04:05:06')",
+    )?;
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select a, b, c, d, e, a + 1, c + '1 mon', d - '2021-02-28 00:00:00+00', e * 2 from events",
+    )?;
+    assert_eq!(
+        result[0].columns[..5]
+            .iter()
+            .map(|(_, t)| t.oid())
+            .collect::<Vec<u32>>(),
+        vec![1082, 1083, 1114, 1184, 1186]
+    );
+    assert_eq!(result[0].columns[5].1, DeserializeTypes::Date);
+    assert_eq!(result[0].columns[7].1, DeserializeTypes::Interval);
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![vec![
+            Some("2021-02-28".to_string()),
+            Some("13:45:30.5".to_string()),
+            Some("2021-02-28 13:45:30".to_string()),
+            Some("2021-02-28 18:45:30+00".to_string()),
+            Some("1 year 2 mons 3 days 04:05:06".to_string()),
+            Some("2021-03-01".to_string()),
+            Some("2021-03-28 13:45:30".to_string()),
+            Some("18:45:30".to_string()),
+            Some("2 years 4 mons 6 days 08:10:12".to_string()),
+        ]]
+    );
+    Ok(())
+}
+
+#[test]
+fn date_time_comparisons() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select '2021-01-01'::date < '2021-01-01 00:00:01'::timestamp, '1 day'::interval = '24 hours', now() = current_timestamp, localtimestamp <= clock_timestamp()::timestamp",
+    )?;
+    assert_eq!(result[0].columns[0].1, DeserializeTypes::Bool);
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![vec![
+            Some("t".to_string()),
+            Some("t".to_string()),
+            Some("t".to_string()),
+            Some("t".to_string()),
+        ]]
+    );
+    Ok(())
+}
+
+#[test]
+fn now_is_the_transaction_start() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let tran = aw!(tm.start_trans())?;
+    let first = aw!(engine.process_query(tran, "select now()".to_string()))?;
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let second = aw!(engine.process_query(tran, "select now()".to_string()))?;
+    aw!(tm.commit_trans(tran))?;
+    assert_eq!(first[0].rows, second[0].rows);
+
+    let later = run(&mut engine, &mut tm, "select now()")?;
+    assert_ne!(first[0].rows, later[0].rows);
+    Ok(())
+}
+
+#[test]
+fn session_time_zone() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(&mut engine, &mut tm, "show timezone")?;
+    assert_eq!(result[0].columns[0].0, "TimeZone");
+    assert_eq!(single(&result[0]), "UTC");
+
+    run(&mut engine, &mut tm, "set time zone 'America/New_York'")?;
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "show time zone; select '2021-07-01 12:00:00+00'::timestamptz::text, '2021-07-01 12:00'::timestamptz::date, '2021-01-01'::date::timestamptz - '2021-01-01 00:00:00+00'",
+    )?;
+    assert_eq!(single(&result[0]), "America/New_York");
+    assert_eq!(
+        as_strings(&result[1]),
+        vec![vec![
+            Some("2021-07-01 08:00:00-04".to_string()),
+            Some("2021-07-01".to_string()),
+            Some("05:00:00".to_string()),
+        ]]
+    );
+
+    //A day later is the same wall clock time even across the daylight savings change
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select ('2021-03-13 12:00'::timestamptz + '1 day')::text",
+    )?;
+    assert_eq!(single(&result[0]), "2021-03-14 12:00:00-04");
+
+    run(&mut engine, &mut tm, "set timezone to default")?;
+    let result = run(&mut engine, &mut tm, "show timezone")?;
+    assert_eq!(single(&result[0]), "UTC");
+
+    assert!(run(&mut engine, &mut tm, "set timezone = 'Mars/Olympus_Mons'").is_err());
+    assert!(run(&mut engine, &mut tm, "show not_a_setting").is_err());
+    Ok(())
+}
+
+#[test]
+fn date_time_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    assert!(run(&mut engine, &mut tm, "select '2021-02-30'::date").is_err());
+    assert!(run(&mut engine, &mut tm, "select '25:00'::time").is_err());
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "select '2021-01-01'::date + '2021-01-01'::date"
+    )
+    .is_err());
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "select 'infinity'::date - '2021-01-01'::date"
+    )
+    .is_err());
+    assert!(run(&mut engine, &mut tm, "select '1 day'::interval / 0").is_err());
+    assert!(run(&mut engine, &mut tm, "select now(1)").is_err());
+    Ok(())
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use feophantlib::constants::{BuiltinSqlTypes, PgErrorCodes};
+
+#[test]
+fn drop_table_and_recreate() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let tran = aw!(tm.start_trans())?;
+    aw!(engine.process_query(
+        tran,
+        "create table doomed (value text); insert into doomed values('gone')".to_string()
+    ))?;
+    aw!(tm.commit_trans(tran))?;
+
+    //Still there for a transaction that started before the drop committed
+    let before = aw!(tm.start_trans())?;
+    let drop = aw!(tm.start_trans())?;
+    aw!(engine.process_query(drop, "drop table doomed".to_string()))?;
+    assert!(aw!(engine.process_query(drop, "select value from doomed".to_string())).is_err());
+    aw!(tm.commit_trans(drop))?;
+    let result = aw!(engine.process_query(before, "select value from doomed".to_string()))?;
+    assert_eq!(result[0].rows.len(), 1);
+    aw!(tm.commit_trans(before))?;
+
+    let tran = aw!(tm.start_trans())?;
+    aw!(engine.process_query(
+        tran,
+        "create table doomed (value text); insert into doomed values('back')".to_string()
+    ))?;
+    let result = aw!(engine.process_query(tran, "select value from doomed".to_string()))?;
+    assert_eq!(
+        result[0].rows[0].0[0],
+        Some(BuiltinSqlTypes::Text("back".to_string()))
+    );
+    aw!(tm.commit_trans(tran))?;
+    Ok(())
+}
+
+#[test]
+fn drop_system_table_refused() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let tran = aw!(tm.start_trans())?;
+    let err = aw!(engine.process_query(tran, "drop table pg_class".to_string())).unwrap_err();
+    assert_eq!(
+        err.pg_error_code().value(),
+        PgErrorCodes::InsufficientPrivilege.value()
+    );
+    aw!(tm.abort_trans(tran))?;
+    Ok(())
+}
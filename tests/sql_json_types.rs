@@ -0,0 +1,175 @@
+mod common;
+
+use common::{as_strings, row, run};
+use feophantlib::constants::{DeserializeTypes, PgErrorCodes};
+
+#[test]
+fn json_columns() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table docs (id integer, data jsonb, raw json)",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        r#"insert into docs values(1, '{"b": [1, "two", null], "a": {"x": 1}}', '{"b":  [1, "two"], "a": 1}')"#,
+    )?;
+
+    let result = run(&mut engine, &mut tm, "select data, raw from docs")?;
+    assert_eq!(
+        result[0]
+            .columns
+            .iter()
+            .map(|(_, t)| t.oid())
+            .collect::<Vec<u32>>(),
+        vec![3802, 114]
+    );
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some(r#"{"a": {"x": 1}, "b": [1, "two", null]}"#),
+            Some(r#"{"b":  [1, "two"], "a": 1}"#),
+        ])]
+    );
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select data -> 'a', data -> 'b' ->> 1, data #> '{b,0}', data #>> '{a, x}', data -> 'b' -> -1, data ->> 'missing', raw -> 'b', raw ->> 'a' from docs",
+    )?;
+    assert_eq!(result[0].columns[0].1, DeserializeTypes::Jsonb);
+    assert_eq!(result[0].columns[1].1, DeserializeTypes::Text);
+    assert_eq!(result[0].columns[6].1, DeserializeTypes::Json);
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some(r#"{"x": 1}"#),
+            Some("two"),
+            Some("1"),
+            Some("1"),
+            Some("null"),
+            None,
+            Some(r#"[1, "two"]"#),
+            Some("1"),
+        ])]
+    );
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        r#"select data @> '{"a": {"x": 1}}', data @> '{"b": [2]}', '{"a": {}}' <@ data, data ? 'a', data -> 'b' ? 'two', data = '{"b": [1, "two", null], "a": {"x": 1.0}}' from docs"#,
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some("t"),
+            Some("f"),
+            Some("t"),
+            Some("t"),
+            Some("t"),
+            Some("t"),
+        ])]
+    );
+    Ok(())
+}
+
+#[test]
+fn json_functions() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select jsonb_build_object('b', 1, 'a', 'x', 'c', null), json_build_object('b', 1.50, 'a', true), jsonb_build_array(1, 'two', '2021-01-01 12:00'::timestamp), json_build_array(), to_jsonb('a\"b'::text), jsonb_typeof('[1]'), json_typeof('{}')",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some(r#"{"a": "x", "b": 1, "c": null}"#),
+            Some(r#"{"b" : 1.50, "a" : true}"#),
+            Some(r#"[1, "two", "2021-01-01T12:00:00"]"#),
+            Some("[]"),
+            Some(r#""a\"b""#),
+            Some("array"),
+            Some("object"),
+        ])]
+    );
+
+    assert!(run(&mut engine, &mut tm, "select jsonb_build_object('a')").is_err());
+    assert!(run(&mut engine, &mut tm, "select jsonb_build_object(null, 1)").is_err());
+    assert!(run(&mut engine, &mut tm, "select jsonb_typeof(1)").is_err());
+    Ok(())
+}
+
+#[test]
+fn json_aggregates() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table tags (name text, weight integer)",
+    )?;
+
+    let result = run(&mut engine, &mut tm, "select jsonb_agg(name) from tags")?;
+    assert_eq!(as_strings(&result[0]), vec![row(&[None])]);
+
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into tags values('red', 1); insert into tags values('blue', null)",
+    )?;
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select jsonb_agg(name), json_agg(weight), jsonb_agg(jsonb_build_object('n', name)) -> 1 ->> 'n' from tags",
+    )?;
+    assert_eq!(result[0].columns[0].0, "jsonb_agg");
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[
+            Some(r#"["red", "blue"]"#),
+            Some("[1, null]"),
+            Some("blue"),
+        ])]
+    );
+
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "select name, jsonb_agg(weight) from tags"
+    )
+    .is_err());
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "select jsonb_agg(jsonb_agg(name)) from tags"
+    )
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn json_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(&mut engine, &mut tm, "create table docs (data jsonb)")?;
+
+    let tran = aw!(tm.start_trans())?;
+    let err = aw!(engine.process_query(tran, "insert into docs values('{\"a\": }')".to_string()))
+        .unwrap_err();
+    aw!(tm.abort_trans(tran))?;
+    assert_eq!(
+        err.pg_error_code().value(),
+        PgErrorCodes::InvalidTextRepresentation.value()
+    );
+
+    assert!(run(&mut engine, &mut tm, "select '[1, 2'::json").is_err());
+    assert!(run(&mut engine, &mut tm, "select '{}'::json = '{}'::json").is_err());
+    assert!(run(&mut engine, &mut tm, "select '{}'::json @> '{}'::json").is_err());
+    assert!(run(&mut engine, &mut tm, "select '{}'::jsonb #> 'a'").is_err());
+    Ok(())
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use common::{as_strings, error_code, run};
+use feophantlib::constants::DeserializeTypes;
+
+#[test]
+fn numeric_columns() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table nums (a smallint, b integer, c bigint, d real, e double precision, f numeric(5,2))",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into nums values(-1, -2147483648, 9223372036854775807, 1.5, -0.25, 123.456)",
+    )?;
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select a, b, c, d, e, f, a * 2, f / 3, d + e, c::numeric + 1 from nums",
+    )?;
+    assert_eq!(
+        result[0].columns[..6]
+            .iter()
+            .map(|(_, t)| t.oid())
+            .collect::<Vec<u32>>(),
+        vec![21, 23, 20, 700, 701, 1700]
+    );
+    assert_eq!(result[0].columns[6].1, DeserializeTypes::Integer);
+    assert_eq!(result[0].columns[6].0, "?column?");
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![vec![
+            Some("-1".to_string()),
+            Some("-2147483648".to_string()),
+            Some("9223372036854775807".to_string()),
+            Some("1.5".to_string()),
+            Some("-0.25".to_string()),
+            Some("123.46".to_string()),
+            Some("-2".to_string()),
+            Some("41.1533333333333333".to_string()),
+            Some("1.25".to_string()),
+            Some("9223372036854775808".to_string()),
+        ]]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn numeric_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table nums (a smallint, f numeric(3,1))",
+    )?;
+
+    assert_eq!(
+        error_code(run(
+            &mut engine,
+            &mut tm,
+            "insert into nums (a) values(32768)"
+        )),
+        "22003"
+    );
+    assert_eq!(
+        error_code(run(
+            &mut engine,
+            &mut tm,
+            "insert into nums (f) values(100)"
+        )),
+        "22003"
+    );
+    assert_eq!(
+        error_code(run(
+            &mut engine,
+            &mut tm,
+            "insert into nums (a) values('x')"
+        )),
+        "22P02"
+    );
+    assert_eq!(
+        error_code(run(&mut engine, &mut tm, "select 1 / 0")),
+        "22012"
+    );
+    assert_eq!(
+        error_code(run(&mut engine, &mut tm, "select 2147483647 + 1")),
+        "22003"
+    );
+    assert!(run(&mut engine, &mut tm, "select 'a' + 1").is_err());
+    assert!(run(&mut engine, &mut tm, "create table bad (a integer(3))").is_err());
+
+    let result = run(&mut engine, &mut tm, "select count from nums");
+    assert!(result.is_err());
+
+    let result = run(&mut engine, &mut tm, "select a from nums")?;
+    assert!(result[0].rows.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn select_without_from() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select 1 + 2 * 3, -7 % 3, 2147483648, 1.5::real * 2, '10'::smallint - 1, true, null",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![vec![
+            Some("7".to_string()),
+            Some("-1".to_string()),
+            Some("2147483648".to_string()),
+            Some("3".to_string()),
+            Some("9".to_string()),
+            Some("t".to_string()),
+            None,
+        ]]
+    );
+    assert_eq!(
+        result[0]
+            .columns
+            .iter()
+            .map(|(_, t)| t.clone())
+            .collect::<Vec<DeserializeTypes>>(),
+        vec![
+            DeserializeTypes::Integer,
+            DeserializeTypes::Integer,
+            DeserializeTypes::BigInt,
+            DeserializeTypes::DoublePrecision,
+            DeserializeTypes::Integer,
+            DeserializeTypes::Bool,
+            DeserializeTypes::Text,
+        ]
+    );
+
+    Ok(())
+}
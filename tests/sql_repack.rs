@@ -0,0 +1,124 @@
+mod common;
+
+use common::{run, start};
+use feophantlib::{
+    constants::BuiltinSqlTypes,
+    engine::io::{page_formats::PageSize, ControlFile, IOManager},
+};
+
+fn stat(result: &feophantlib::engine::objects::QueryResult, column: usize) -> i64 {
+    match result.rows[0].0[column] {
+        Some(BuiltinSqlTypes::BigInt(b)) => b,
+        _ => panic!("Wrong type"),
+    }
+}
+
+#[test]
+fn repack_table() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table foo (bar text, baz text)",
+    )?;
+    let filler = "x".repeat(500);
+    let big: String = (0..3000).map(|i| format!("{} ", i * 7919)).collect();
+    run(
+        &mut engine,
+        &mut tm,
+        &format!(
+            "insert into foo values('kept', '{}'); insert into foo values('big', '{}')",
+            filler, big
+        ),
+    )?;
+
+    //Rows from aborted inserts are dead, enough of them to fill several pages
+    let tran = aw!(tm.start_trans())?;
+    for _ in 0..50 {
+        aw!(engine.process_query(
+            tran,
+            format!("insert into foo values('gone', '{}')", filler)
+        ))?;
+    }
+    aw!(engine.process_query(
+        tran,
+        format!("insert into foo values('gone big', '{}')", big)
+    ))?;
+    aw!(tm.abort_trans(tran))?;
+
+    let result = run(&mut engine, &mut tm, "repack foo")?;
+    assert_eq!(
+        result[0].rows[0].0[0],
+        Some(BuiltinSqlTypes::Text("foo".to_string()))
+    );
+    assert!(stat(&result[0], 1) > 2); //Pages before
+    assert!(stat(&result[0], 2) > 51); //The toast chunks of the big value go too
+    assert!(stat(&result[0], 4) > 0);
+
+    let result = run(&mut engine, &mut tm, "select bar, baz from foo")?;
+    assert_eq!(result[0].rows.len(), 2);
+    assert_eq!(
+        result[0].rows[1].0,
+        vec![
+            Some(BuiltinSqlTypes::Text("big".to_string())),
+            Some(BuiltinSqlTypes::Text(big))
+        ]
+    );
+
+    //Nothing left to remove, the other spelling works the same
+    let result = run(&mut engine, &mut tm, "VACUUM FULL CONCURRENTLY foo")?;
+    assert_eq!(stat(&result[0], 2), 0);
+    assert_eq!(stat(&result[0], 4), 0);
+
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into foo values('after', null)",
+    )?;
+    let result = run(&mut engine, &mut tm, "select bar from foo")?;
+    assert_eq!(result[0].rows.len(), 3);
+
+    assert!(run(&mut engine, &mut tm, "repack nope").is_err());
+    Ok(())
+}
+
+#[test]
+fn repack_survives_crash() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("data");
+    let io_manager = aw!(IOManager::initdb_directory(
+        &path,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    run(&mut engine, &mut tm, "create table foo (bar text)")?;
+    run(&mut engine, &mut tm, "insert into foo values('kept')")?;
+    let tran = aw!(tm.start_trans())?;
+    for _ in 0..20 {
+        aw!(engine.process_query(tran, "insert into foo values('gone')".to_string()))?;
+    }
+    aw!(tm.abort_trans(tran))?;
+
+    //No checkpoint, the swap is redone from the log
+    run(&mut engine, &mut tm, "repack foo")?;
+    run(&mut engine, &mut tm, "insert into foo values('after')")?;
+    drop(engine);
+    drop(io_manager);
+
+    let io_manager = aw!(IOManager::open(&path))?;
+    let (mut tm, mut engine) = start(&io_manager);
+    let result = run(&mut engine, &mut tm, "select bar from foo")?;
+    assert_eq!(
+        result[0]
+            .rows
+            .iter()
+            .map(|r| r.0[0].clone())
+            .collect::<Vec<_>>(),
+        vec![
+            Some(BuiltinSqlTypes::Text("kept".to_string())),
+            Some(BuiltinSqlTypes::Text("after".to_string()))
+        ]
+    );
+    Ok(())
+}
@@ -1,7 +1,7 @@
 mod common;
 
 use feophantlib::{
-    constants::BuiltinSqlTypes,
+    constants::{BuiltinSqlTypes, DeserializeTypes},
     engine::objects::{QueryResult, SqlTuple},
 };
 
@@ -33,7 +33,11 @@ fn simple_select() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    let select_columns = vec!["baz".to_string(), "bar".to_string(), "another".to_string()];
+    let select_columns = vec![
+        ("baz".to_string(), DeserializeTypes::Text),
+        ("bar".to_string(), DeserializeTypes::Text),
+        ("another".to_string(), DeserializeTypes::Text),
+    ];
 
     let select_row = vec![SqlTuple(vec![
         Some(BuiltinSqlTypes::Text("two".to_string())),
@@ -43,10 +47,10 @@ fn simple_select() -> Result<(), Box<dyn std::error::Error>> {
 
     assert_eq!(
         result,
-        QueryResult {
+        vec![QueryResult {
             columns: select_columns,
             rows: select_row
-        }
+        }]
     );
 
     aw!(tm.commit_trans(tran))?;
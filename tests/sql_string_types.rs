@@ -0,0 +1,127 @@
+mod common;
+
+use common::{as_strings, run};
+use feophantlib::constants::DeserializeTypes;
+
+#[test]
+fn string_columns() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table words (a varchar(3), b char(3), c character varying, d bytea)",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into words values('ab', 'ab', 'anything at all', '\\xdeadbeef')",
+    )?;
+    //Trailing spaces past the limit are silently dropped
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into words values('abc  ', 'xyz   ', 'x', 'a\\\\b\\001')",
+    )?;
+
+    let result = run(&mut engine, &mut tm, "select a, b, c, d from words")?;
+    assert_eq!(
+        result[0]
+            .columns
+            .iter()
+            .map(|(_, t)| (t.oid(), t.type_modifier()))
+            .collect::<Vec<(u32, i32)>>(),
+        vec![(1043, 7), (1042, 7), (1043, -1), (17, -1)]
+    );
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            vec![
+                Some("ab".to_string()),
+                Some("ab ".to_string()),
+                Some("anything at all".to_string()),
+                Some("\\xdeadbeef".to_string()),
+            ],
+            vec![
+                Some("abc".to_string()),
+                Some("xyz".to_string()),
+                Some("x".to_string()),
+                Some("\\x615c6201".to_string()),
+            ]
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn string_length_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table words (a varchar(3), b char(2))",
+    )?;
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "insert into words values('abcd', 'a')"
+    )
+    .is_err());
+    assert!(run(&mut engine, &mut tm, "insert into words values('a', 'abc')").is_err());
+    assert!(run(&mut engine, &mut tm, "insert into words values('a', 'a b')").is_err());
+
+    let result = run(&mut engine, &mut tm, "select a from words")?;
+    assert!(result[0].rows.is_empty());
+
+    assert!(run(&mut engine, &mut tm, "create table bad (a varchar(0))").is_err());
+    assert!(run(&mut engine, &mut tm, "select '\\xabc'::bytea").is_err());
+    Ok(())
+}
+
+#[test]
+fn string_casts_and_comparisons() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select 'abcdef'::varchar(3), 'abcdef'::char(2), 'a'::char(3) = 'a  '::char(3), 'a'::char(3)::text, 'a '::varchar = 'a', 12345::varchar(2)",
+    )?;
+    assert_eq!(result[0].columns[0].1, DeserializeTypes::VarChar(Some(3)));
+    assert_eq!(result[0].columns[2].1, DeserializeTypes::Bool);
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![vec![
+            Some("abc".to_string()),
+            Some("ab".to_string()),
+            Some("t".to_string()),
+            Some("a".to_string()),
+            Some("f".to_string()),
+            Some("12".to_string()),
+        ]]
+    );
+    Ok(())
+}
+
+#[test]
+fn bytea_output() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    let result = run(&mut engine, &mut tm, "show bytea_output")?;
+    assert_eq!(as_strings(&result[0]), vec![vec![Some("hex".to_string())]]);
+
+    run(&mut engine, &mut tm, "set bytea_output = 'escape'")?;
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select '\\x41005c'::bytea::text, 'a\\\\b'::bytea = '\\x615c62'::bytea",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![vec![Some("A\\000\\\\".to_string()), Some("t".to_string())]]
+    );
+
+    assert!(run(&mut engine, &mut tm, "set bytea_output = 'base64'").is_err());
+    Ok(())
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::run;
+use feophantlib::constants::BuiltinSqlTypes;
+
+#[test]
+fn large_values() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create table documents (name text, body text, raw bytea)",
+    )?;
+
+    //Repetitive enough to compress
+    let repeated = "feophant ".repeat(2000);
+    //Distinct words, too large to keep in the row once compressed
+    let counted: String = (0..5000).map(|i| format!("{} ", i * 7919)).collect();
+    let raw: String = (0..3000)
+        .map(|i| format!("{:02x}", (i * 37) % 251))
+        .collect();
+
+    run(
+        &mut engine,
+        &mut tm,
+        &format!(
+            "insert into documents values('repeated', '{}', null); insert into documents values('counted', '{}', '\\x{}')",
+            repeated, counted, raw
+        ),
+    )?;
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select name, body, raw from documents",
+    )?;
+    assert_eq!(result[0].rows.len(), 2);
+    assert_eq!(
+        result[0].rows[0].0[1],
+        Some(BuiltinSqlTypes::Text(repeated))
+    );
+    assert_eq!(result[0].rows[0].0[2], None);
+    assert_eq!(result[0].rows[1].0[1], Some(BuiltinSqlTypes::Text(counted)));
+    assert_eq!(
+        result[0].rows[1].0[2],
+        Some(BuiltinSqlTypes::Bytea(
+            (0..3000).map(|i| ((i * 37) % 251) as u8).collect()
+        ))
+    );
+    Ok(())
+}
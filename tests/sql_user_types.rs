@@ -0,0 +1,215 @@
+mod common;
+
+use common::{as_strings, error_code, row, run};
+use feophantlib::constants::{DeserializeTypes, PgErrorCodes, FIRST_USER_OID};
+
+#[test]
+fn enum_types() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create type mood as enum ('sad', 'ok', 'happy')",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "create table people (name text, current mood, history mood[])",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into people values('a', 'happy', '{sad,ok}'); insert into people values('b', null, ARRAY['ok'::mood])",
+    )?;
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select current, history, current > 'ok', current::text, history[1] < current from people",
+    )?;
+    let columns = &result[0].columns;
+    assert_eq!(columns[0].1.oid(), FIRST_USER_OID);
+    assert_eq!(columns[1].1.oid(), FIRST_USER_OID + 1);
+    assert_eq!(columns[0].1.to_string(), "mood");
+    assert_eq!(columns[3].1, DeserializeTypes::Text);
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[
+                Some("happy"),
+                Some("{sad,ok}"),
+                Some("t"),
+                Some("happy"),
+                Some("t")
+            ]),
+            row(&[None, Some("{ok}"), None, None, None]),
+        ]
+    );
+
+    assert_eq!(
+        error_code(run(
+            &mut engine,
+            &mut tm,
+            "insert into people values('c', 'meh', null)"
+        )),
+        PgErrorCodes::InvalidTextRepresentation.value()
+    );
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "insert into people values('c', 'happy'::text, null)"
+    )
+    .is_err());
+    assert!(run(&mut engine, &mut tm, "select current = 1 from people").is_err());
+    Ok(())
+}
+
+#[test]
+fn composite_types() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create type mood as enum ('sad', 'happy'); create type status as (label varchar(5), feeling mood, score int)",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        "create table checkins (id int, status status)",
+    )?;
+    run(
+        &mut engine,
+        &mut tm,
+        r#"insert into checkins values(1, ROW('fine', 'happy', 3)); insert into checkins values(2, '("a b",sad,)'); insert into checkins values(3, null)"#,
+    )?;
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select status, (status).feeling, status.score + 1, status = ('fine', 'happy', 3) from checkins",
+    )?;
+    let columns = &result[0].columns;
+    assert_eq!(columns[0].1.oid(), FIRST_USER_OID + 2);
+    assert_eq!(columns[1].0, "feeling");
+    assert_eq!(columns[1].1.oid(), FIRST_USER_OID);
+    assert_eq!(columns[2].1, DeserializeTypes::Integer);
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[Some("(fine,happy,3)"), Some("happy"), Some("4"), Some("t")]),
+            row(&[Some(r#"("a b",sad,)"#), Some("sad"), None, Some("f")]),
+            row(&[None, None, None, None]),
+        ]
+    );
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select ROW('x', 'sad', 1)::status, '(y,happy,2)'::status::text",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![row(&[Some("(x,sad,1)"), Some("(y,happy,2)")])]
+    );
+
+    assert!(run(
+        &mut engine,
+        &mut tm,
+        "insert into checkins values(4, ROW('x', 'sad'))"
+    )
+    .is_err());
+    assert_eq!(
+        error_code(run(
+            &mut engine,
+            &mut tm,
+            "insert into checkins values(4, ROW('too long', 'sad', 1))"
+        )),
+        PgErrorCodes::StringDataRightTruncation.value()
+    );
+    assert!(run(&mut engine, &mut tm, "select ROW(1, 2)").is_err());
+    assert!(run(&mut engine, &mut tm, "select (status).nope from checkins").is_err());
+    assert!(run(&mut engine, &mut tm, "select (id).label from checkins").is_err());
+    Ok(())
+}
+
+#[test]
+fn pg_type_catalog() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(
+        &mut engine,
+        &mut tm,
+        "create type mood as enum ('sad', 'happy'); create type pair as (a int, b mood)",
+    )?;
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select oid, typname, typtype, typelem, typarray from pg_type",
+    )?;
+    let rows = as_strings(&result[0]);
+    assert!(rows.contains(&row(&[
+        Some("23"),
+        Some("int4"),
+        Some("b"),
+        Some("0"),
+        Some("1007")
+    ])));
+    assert!(rows.contains(&row(&[
+        Some("1007"),
+        Some("_int4"),
+        Some("b"),
+        Some("23"),
+        Some("0")
+    ])));
+    assert!(rows.contains(&row(&[
+        Some("16384"),
+        Some("mood"),
+        Some("e"),
+        Some("0"),
+        Some("16385")
+    ])));
+    assert!(rows.contains(&row(&[
+        Some("16385"),
+        Some("_mood"),
+        Some("b"),
+        Some("16384"),
+        Some("0")
+    ])));
+    assert!(rows.contains(&row(&[
+        Some("16386"),
+        Some("pair"),
+        Some("c"),
+        Some("0"),
+        Some("16387")
+    ])));
+
+    let result = run(
+        &mut engine,
+        &mut tm,
+        "select enumtypid, enumsortorder, enumlabel from pg_enum",
+    )?;
+    assert_eq!(
+        as_strings(&result[0]),
+        vec![
+            row(&[Some("16384"), Some("0"), Some("sad")]),
+            row(&[Some("16384"), Some("1"), Some("happy")]),
+        ]
+    );
+
+    assert_eq!(
+        error_code(run(&mut engine, &mut tm, "create type mood as enum ('x')")),
+        PgErrorCodes::DuplicateObject.value()
+    );
+    assert_eq!(
+        error_code(run(&mut engine, &mut tm, "create type text as (a int)")),
+        PgErrorCodes::DuplicateObject.value()
+    );
+    assert!(run(&mut engine, &mut tm, "create type dup as enum ('a', 'a')").is_err());
+    assert!(run(&mut engine, &mut tm, "create type dup as (a int, a text)").is_err());
+    assert!(run(&mut engine, &mut tm, "create type dup as (a nope)").is_err());
+    assert!(run(&mut engine, &mut tm, "create table t (a nope)").is_err());
+    Ok(())
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::run;
+use feophantlib::constants::{BuiltinSqlTypes, TableDefinitions};
+use feophantlib::engine::autovacuum::AUTOVACUUM_NAPTIME;
+
+fn run_aborted(
+    engine: &mut feophantlib::engine::Engine,
+    tm: &mut feophantlib::engine::transactions::TransactionManager,
+    query: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tran = aw!(tm.start_trans())?;
+    aw!(engine.process_query(tran, query.to_string()))?;
+    aw!(tm.abort_trans(tran))?;
+    Ok(())
+}
+
+#[test]
+fn vacuum_table() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(&mut engine, &mut tm, "create table foo (bar text)")?;
+    run(&mut engine, &mut tm, "insert into foo values('kept')")?;
+    run_aborted(
+        &mut engine,
+        &mut tm,
+        "insert into foo values('gone'); insert into foo values('also gone')",
+    )?;
+
+    let result = run(&mut engine, &mut tm, "vacuum foo")?;
+    assert_eq!(result[0].columns.len(), 5);
+    assert_eq!(
+        result[0].rows[0].0,
+        vec![
+            Some(BuiltinSqlTypes::Text("foo".to_string())),
+            Some(BuiltinSqlTypes::BigInt(1)),
+            Some(BuiltinSqlTypes::BigInt(2)),
+            Some(BuiltinSqlTypes::BigInt(1)),
+            result[0].rows[0].0[4].clone(),
+        ]
+    );
+    assert!(matches!(
+        result[0].rows[0].0[4],
+        Some(BuiltinSqlTypes::BigInt(b)) if b > 0
+    ));
+
+    let result = run(&mut engine, &mut tm, "select bar from foo")?;
+    assert_eq!(
+        result[0].rows[0].0,
+        vec![Some(BuiltinSqlTypes::Text("kept".to_string()))]
+    );
+    assert_eq!(result[0].rows.len(), 1);
+
+    assert!(run(&mut engine, &mut tm, "vacuum nope").is_err());
+    Ok(())
+}
+
+#[test]
+fn vacuum_everything() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tm, mut engine) = common::_create_engine();
+
+    run(&mut engine, &mut tm, "create table foo (bar text)")?;
+    run_aborted(&mut engine, &mut tm, "create table baz (bar text)")?;
+
+    //The aborted table's catalog rows are cleaned up along with everything else
+    let result = run(&mut engine, &mut tm, "VACUUM")?;
+    let tables: Vec<_> = result[0].rows.iter().map(|r| r.0[0].clone()).collect();
+    assert!(tables.contains(&Some(BuiltinSqlTypes::Text("pg_class".to_string()))));
+    assert!(tables.contains(&Some(BuiltinSqlTypes::Text("foo".to_string()))));
+    assert!(!tables.contains(&Some(BuiltinSqlTypes::Text("baz".to_string()))));
+    let removed: i64 = result[0]
+        .rows
+        .iter()
+        .map(|r| match r.0[2] {
+            Some(BuiltinSqlTypes::BigInt(b)) => b,
+            _ => 0,
+        })
+        .sum();
+    assert_eq!(removed, 2); //baz's pg_class and pg_attribute rows
+
+    run_aborted(&mut engine, &mut tm, "insert into foo values('gone')")?;
+    let stats = aw!(engine.autovacuum(AUTOVACUUM_NAPTIME).vacuum_all())?;
+    assert_eq!(stats.rows_removed, 1);
+    let bootstrap: usize = TableDefinitions::VALUES
+        .iter()
+        .map(|t| t.bootstrap_rows().len())
+        .sum();
+    assert_eq!(stats.rows_remaining, bootstrap + 2); //Plus foo's pg_class and pg_attribute rows
+    Ok(())
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{run, serve_engine, start};
+use feophantlib::{
+    engine::{
+        io::{page_formats::PageSize, ControlFile, IOManager, IOManagerError},
+        transactions::TransactionManager,
+        Engine, EngineError,
+    },
+    replication::{BaseBackupReceiver, WalReceiver},
+};
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+//Replay happens in the background so the standby catches up eventually
+fn wait_for_rows(engine: &mut Engine, tm: &mut TransactionManager, rows: usize) {
+    for _ in 0..200 {
+        if run(engine, tm, "select value from logged").unwrap()[0]
+            .rows
+            .len()
+            == rows
+        {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("The standby never saw {} rows", rows);
+}
+
+#[test]
+fn standby_follows_primary() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let data = tmp.path().join("primary");
+    let standby_data = tmp.path().join("standby");
+    let rt = Runtime::new()?;
+
+    let primary = aw!(IOManager::initdb_directory(
+        &data,
+        ControlFile::new(PageSize::Kb4)
+    ))?;
+    let (mut tm, mut engine) = start(&primary);
+    run(&mut engine, &mut tm, "create table logged (value text)")?;
+    run(&mut engine, &mut tm, "insert into logged values('before')")?;
+
+    let address = serve_engine(&rt, engine.clone(), tm.clone())?;
+
+    //The standby starts from a base backup and streams the rest
+    rt.block_on(BaseBackupReceiver::new(address.clone()).run(&standby_data))?;
+    let standby = aw!(IOManager::open(&standby_data))?;
+    let (mut standby_tm, mut standby_engine) = start(&standby);
+    aw!(standby.start_recovery())?;
+    standby_tm.start_recovery();
+    rt.spawn(WalReceiver::new(address, standby.clone(), standby_tm.clone()).run());
+
+    for _ in 0..3 {
+        run(
+            &mut engine,
+            &mut tm,
+            "insert into logged values('streamed')",
+        )?;
+    }
+    wait_for_rows(&mut standby_engine, &mut standby_tm, 4);
+
+    //A read only transaction keeps its snapshot while replay continues
+    let reader = aw!(standby_tm.start_trans())?;
+    run(&mut engine, &mut tm, "insert into logged values('later')")?;
+    wait_for_rows(&mut standby_engine, &mut standby_tm, 5);
+    let held = aw!(standby_engine.process_query(reader, "select value from logged".to_string()))?;
+    assert_eq!(held[0].rows.len(), 4);
+    aw!(standby_tm.commit_trans(reader))?;
+
+    //Nothing can be changed on a standby
+    assert!(matches!(
+        run(
+            &mut standby_engine,
+            &mut standby_tm,
+            "insert into logged values('standby')"
+        ),
+        Err(EngineError::ReadOnlyTransaction())
+    ));
+    assert!(standby.in_recovery());
+
+    //Promoted it stops following and takes changes
+    run(&mut standby_engine, &mut standby_tm, "promote")?;
+    assert!(!standby.in_recovery());
+    run(
+        &mut engine,
+        &mut tm,
+        "insert into logged values('not followed')",
+    )?;
+    run(
+        &mut standby_engine,
+        &mut standby_tm,
+        "insert into logged values('promoted')",
+    )?;
+    assert_eq!(
+        run(
+            &mut standby_engine,
+            &mut standby_tm,
+            "select value from logged"
+        )?[0]
+            .rows
+            .len(),
+        6
+    );
+    assert!(matches!(
+        aw!(standby.promote()),
+        Err(IOManagerError::NotInRecovery())
+    ));
+    Ok(())
+}
@@ -1,7 +1,11 @@
 use feophantlib::{
     constants::{BuiltinSqlTypes, DeserializeTypes, Nullable},
     engine::{
-        io::{row_formats::RowData, IOManager, RowManager, VisibleRowManager},
+        io::{
+            page_formats::ItemIdFlags,
+            row_formats::{InfoMask, RowData},
+            IOManager, RowManager, VacuumStats, VisibilityBits, VisibleRowManager,
+        },
         objects::{Attribute, SqlTuple, Table},
         transactions::TransactionManager,
     },
@@ -105,3 +109,327 @@ fn test_row_manager_visibility() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_vacuum() -> Result<(), Box<dyn std::error::Error>> {
+    let table = get_table();
+    let mut tm = TransactionManager::new();
+    let rm = RowManager::new(IOManager::new());
+    let vm = VisibleRowManager::new(rm.clone(), tm.clone());
+
+    let tran_id = aw!(tm.start_trans())?;
+    let mut pointers = vec![];
+    for name in ["a", "b", "c"] {
+        pointers.push(aw!(rm.clone().insert_row(
+            tran_id,
+            table.clone(),
+            get_row(name.to_string())
+        ))?);
+    }
+    //Distinct enough that it has to be moved out to the toast relation
+    let big: String = (0..3000).map(|i| format!("{} ", i * 7919)).collect();
+    let big_pointer = aw!(rm.clone().insert_row(tran_id, table.clone(), get_row(big)))?;
+    aw!(tm.commit_trans(tran_id))?;
+
+    info!("Aborted inserts are dead right away");
+    let aborted = aw!(tm.start_trans())?;
+    aw!(rm
+        .clone()
+        .insert_row(aborted, table.clone(), get_row("d".to_string())))?;
+    aw!(tm.abort_trans(aborted))?;
+
+    info!("Deletes are kept while an older transaction could still see them");
+    let reader = aw!(tm.start_trans())?;
+    let deleter = aw!(tm.start_trans())?;
+    aw!(rm.clone().delete_row(deleter, table.clone(), pointers[0]))?;
+    aw!(rm.clone().delete_row(deleter, table.clone(), big_pointer))?;
+    aw!(tm.commit_trans(deleter))?;
+
+    let stats = aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(stats.rows_removed, 1);
+    assert!(stats.rows_remaining > 4); //The toast chunks are still there too
+    assert!(stats.bytes_freed > 0);
+
+    let res: Vec<RowData> = aw!(vm
+        .clone()
+        .get_stream(reader, table.clone())
+        .map(Result::unwrap)
+        .collect());
+    assert_eq!(res.len(), 4);
+
+    info!("Once nothing can see them they go too, chunks included");
+    aw!(tm.commit_trans(reader))?;
+    let stats = aw!(vm.vacuum(table.clone()))?;
+    assert!(stats.rows_removed > 2);
+    assert_eq!(stats.rows_remaining, 2);
+
+    //Nothing left to do
+    let stats = aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(
+        stats,
+        VacuumStats {
+            pages_scanned: stats.pages_scanned,
+            rows_remaining: 2,
+            ..Default::default()
+        }
+    );
+
+    info!("The space is used again");
+    let tran_id = aw!(tm.start_trans())?;
+    let reused = aw!(rm
+        .clone()
+        .insert_row(tran_id, table.clone(), get_row("e".to_string())))?;
+    assert_eq!(reused, pointers[0]);
+    aw!(tm.commit_trans(tran_id))?;
+
+    let res: Vec<RowData> = aw!(vm
+        .clone()
+        .get_stream(tran_id, table.clone())
+        .map(Result::unwrap)
+        .collect());
+    assert_eq!(res.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_hot_update() -> Result<(), Box<dyn std::error::Error>> {
+    let table = get_table();
+    let mut tm = TransactionManager::new();
+    let mut rm = RowManager::new(IOManager::new());
+    let vm = VisibleRowManager::new(rm.clone(), tm.clone());
+
+    //Indexes only hold the pointer a row was inserted at, so lookups go through that
+    let tran_id = aw!(tm.start_trans())?;
+    let root = aw!(rm
+        .clone()
+        .insert_row(tran_id, table.clone(), get_row("v0".to_string())))?;
+    aw!(rm
+        .clone()
+        .insert_row(tran_id, table.clone(), get_row("other".to_string())))?;
+    aw!(tm.commit_trans(tran_id))?;
+    let reader = aw!(tm.start_trans())?;
+
+    info!("Updates stay on the page chained from the root");
+    let mut latest = root;
+    for name in ["v1", "v2"] {
+        let updater = aw!(tm.start_trans())?;
+        aw!(rm.update_row(updater, table.clone(), latest, get_row(name.to_string())))?;
+        aw!(tm.commit_trans(updater))?;
+        latest = aw!(vm.get(updater, table.clone(), root))?.1.item_pointer;
+        assert_eq!(latest.page, root.page);
+    }
+    let chain = aw!(rm.get_chain(table.clone(), root))?;
+    assert_eq!(chain.len(), 3);
+    assert!(chain[0].1.hot_updated && !chain[0].1.heap_only);
+    assert!(chain[1].1.hot_updated && chain[1].1.heap_only);
+    assert!(!chain[2].1.hot_updated && chain[2].1.heap_only);
+
+    let current = aw!(tm.start_trans())?;
+    assert_eq!(
+        aw!(vm.get(reader, table.clone(), root))?.1.user_data,
+        get_row("v0".to_string())
+    );
+    assert_eq!(
+        aw!(vm.get(current, table.clone(), root))?.1.user_data,
+        get_row("v2".to_string())
+    );
+
+    info!("Pruning turns the root into a redirect to the live version");
+    aw!(tm.commit_trans(reader))?;
+    aw!(tm.commit_trans(current))?;
+    let stats = aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(stats.rows_removed, 2);
+    assert_eq!(stats.rows_remaining, 2);
+
+    let page = aw!(rm.get_page(table.clone(), root.page))?.unwrap();
+    assert_eq!(
+        page.get_item(root.count).and_then(|i| i.redirect_target()),
+        Some(latest.count)
+    );
+    let current = aw!(tm.start_trans())?;
+    assert_eq!(
+        aw!(vm.get(current, table.clone(), root))?.1.user_data,
+        get_row("v2".to_string())
+    );
+    let res: Vec<RowData> = aw!(vm
+        .clone()
+        .get_stream(current, table.clone())
+        .map(Result::unwrap)
+        .collect());
+    assert_eq!(res.len(), 2);
+
+    info!("The heap only version's line pointer is free, the root's is not");
+    let reused = aw!(rm
+        .clone()
+        .insert_row(current, table.clone(), get_row("new".to_string())))?;
+    assert_eq!(reused.page, root.page);
+    assert_ne!(reused.count, root.count);
+    assert_ne!(reused.count, latest.count);
+
+    info!("The chain carries on from the redirect");
+    aw!(rm.update_row(current, table.clone(), latest, get_row("v3".to_string())))?;
+    aw!(tm.commit_trans(current))?;
+    let (_, row) = aw!(vm.get(current, table.clone(), root))?;
+    assert_eq!(row.user_data, get_row("v3".to_string()));
+
+    info!("Once the whole chain is dead so is the root");
+    let deleter = aw!(tm.start_trans())?;
+    aw!(rm
+        .clone()
+        .delete_row(deleter, table.clone(), row.item_pointer))?;
+    aw!(tm.commit_trans(deleter))?;
+    aw!(vm.vacuum(table.clone()))?;
+
+    let page = aw!(rm.get_page(table.clone(), root.page))?.unwrap();
+    assert_eq!(
+        page.get_item(root.count).map(|i| i.flags()),
+        Some(ItemIdFlags::Unused)
+    );
+    let after = aw!(tm.start_trans())?;
+    assert!(aw!(vm.get(after, table.clone(), root)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_hint_bits() -> Result<(), Box<dyn std::error::Error>> {
+    let table = get_table();
+    let mut tm = TransactionManager::new();
+    let rm = RowManager::new(IOManager::new());
+    let vm = VisibleRowManager::new(rm.clone(), tm.clone());
+
+    let tran_id = aw!(tm.start_trans())?;
+    let mut pointers = vec![];
+    for i in 0..50 {
+        pointers.push(aw!(rm.clone().insert_row(
+            tran_id,
+            table.clone(),
+            get_row(i.to_string())
+        ))?);
+    }
+    aw!(tm.commit_trans(tran_id))?;
+
+    let deleter = aw!(tm.start_trans())?;
+    for p in pointers.iter().take(10) {
+        aw!(rm.clone().delete_row(deleter, table.clone(), *p))?;
+    }
+    aw!(tm.commit_trans(deleter))?;
+
+    let scan = |tran_id| -> Result<(usize, usize), Box<dyn std::error::Error>> {
+        let before = tm.lookups();
+        let res: Vec<RowData> = aw!(vm
+            .clone()
+            .get_stream(tran_id, table.clone())
+            .map(Result::unwrap)
+            .collect());
+        Ok((res.len(), tm.lookups() - before))
+    };
+
+    info!("The first scan looks every row up, after that the hints answer");
+    let reader = aw!(tm.clone().start_trans())?;
+    let (found, lookups) = scan(reader)?;
+    assert_eq!(found, 40);
+    assert_eq!(lookups, 60);
+    assert_eq!(scan(reader)?, (40, 0));
+
+    let page = aw!(rm.get_page(table.clone(), 0))?.unwrap();
+    let (_, deleted) = page.get_rows().next().unwrap();
+    assert_eq!(
+        deleted.hints,
+        InfoMask::XMIN_COMMITTED | InfoMask::XMAX_COMMITTED
+    );
+
+    info!("Transactions still running can't be hinted");
+    let running = aw!(tm.clone().start_trans())?;
+    aw!(rm.clone().delete_row(running, table.clone(), pointers[10]))?;
+    let reader = aw!(tm.clone().start_trans())?;
+    assert_eq!(scan(reader)?, (40, 1));
+    assert_eq!(scan(reader)?, (40, 1));
+    aw!(tm.clone().abort_trans(running))?;
+    assert_eq!(scan(reader)?, (40, 1));
+    assert_eq!(scan(reader)?, (40, 0));
+
+    info!("Lookups through an item pointer set them too");
+    let (_, row) = aw!(vm.get(reader, table.clone(), pointers[20]))?;
+    assert!(row.hints.contains(InfoMask::XMIN_COMMITTED));
+    Ok(())
+}
+
+#[test]
+fn test_visibility_map() -> Result<(), Box<dyn std::error::Error>> {
+    let table = get_table();
+    let mut tm = TransactionManager::new();
+    let rm = RowManager::new(IOManager::new());
+    let vm = VisibleRowManager::new(rm.clone(), tm.clone());
+
+    let tran_id = aw!(tm.start_trans())?;
+    let mut pointers = vec![];
+    for i in 0..5 {
+        pointers.push(aw!(rm.clone().insert_row(
+            tran_id,
+            table.clone(),
+            get_row(i.to_string())
+        ))?);
+    }
+    aw!(tm.commit_trans(tran_id))?;
+
+    let visibility = || -> Result<(bool, VisibilityBits), Box<dyn std::error::Error>> {
+        let page = aw!(rm.get_page(table.clone(), 0))?.unwrap();
+        Ok((page.all_visible(), aw!(rm.get_visibility(table.clone(), 0))))
+    };
+    let scan = |tran_id| -> usize {
+        aw!(vm
+            .clone()
+            .get_stream(tran_id, table.clone())
+            .map(Result::unwrap)
+            .count())
+    };
+
+    info!("Rows still being inserted keep the page from being all visible");
+    let inserter = aw!(tm.start_trans())?;
+    let pending =
+        aw!(rm
+            .clone()
+            .insert_row(inserter, table.clone(), get_row("pending".to_string())))?;
+    aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(visibility()?, (false, VisibilityBits::empty()));
+    aw!(tm.abort_trans(inserter))?;
+
+    info!("Vacuum marks and freezes the page once everyone can see it");
+    aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(visibility()?, (true, VisibilityBits::all()));
+    let page = aw!(rm.get_page(table.clone(), 0))?.unwrap();
+    assert!(page
+        .get_rows()
+        .all(|(_, r)| r.hints.contains(InfoMask::XMIN_FROZEN)));
+    assert!(page.get_row(pending.count).is_none());
+
+    let reader = aw!(tm.start_trans())?;
+    let before = tm.lookups();
+    assert_eq!(scan(reader), 5);
+    assert_eq!(tm.lookups(), before);
+
+    info!("Any change clears it");
+    let deleter = aw!(tm.start_trans())?;
+    aw!(rm.clone().delete_row(deleter, table.clone(), pointers[0]))?;
+    assert_eq!(visibility()?, (false, VisibilityBits::empty()));
+    aw!(tm.commit_trans(deleter))?;
+    assert_eq!(scan(reader), 5);
+    let after = aw!(tm.start_trans())?;
+    assert_eq!(scan(after), 4);
+
+    info!("Not while the deleted row can still be seen");
+    aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(visibility()?, (false, VisibilityBits::empty()));
+
+    aw!(tm.commit_trans(reader))?;
+    aw!(tm.commit_trans(after))?;
+    let stats = aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(stats.rows_removed, 1);
+    assert_eq!(visibility()?, (true, VisibilityBits::all()));
+
+    info!("All frozen pages are skipped");
+    let stats = aw!(vm.vacuum(table.clone()))?;
+    assert_eq!(stats.pages_scanned, 0);
+    assert_eq!(stats.rows_remaining, 4);
+    Ok(())
+}